@@ -68,11 +68,33 @@
 
 mod parse;
 
-use self::parse::{FieldKind, ItemData, PathMap, VariantData};
+use self::parse::{FieldKind, ItemData, PathMap, PathSegment, VariantData};
 use proc_macro2::{Ident, Span, TokenStream};
-use quote::{quote, ToTokens};
+use quote::{quote, quote_spanned, ToTokens};
 use std::iter::{self, FromIterator};
-use synstructure::{AddBounds, Structure, VariantInfo};
+use synstructure::{AddBounds, BindingInfo, Structure, VariantInfo};
+
+/// The `FromRequest` method that code is generated for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    /// `from_request_and_body`, which returns a `DefaultFuture<Self, BoxedError>`.
+    Async,
+    /// `try_from_request_parts`, which returns an `Option<Result<Self, BoxedError>>`.
+    Sync,
+    /// `match_route`, which returns a `RouteMatch` without decoding anything.
+    DryRun,
+}
+
+impl Mode {
+    /// Generates a statement returning `error` (a `hyperdrive::Error`) from the method.
+    fn fail(self, error: TokenStream) -> TokenStream {
+        match self {
+            Mode::Async => quote!(return #error.into_future();),
+            Mode::Sync => quote!(return Some(Err(#error.into()));),
+            Mode::DryRun => unreachable!("internal error: dry runs don't create errors"),
+        }
+    }
+}
 
 pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
     let is_struct;
@@ -201,8 +223,27 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
         })
         .unzip();
     let variants = &variants;
+    let variant_matches_path = &variant_matches_path;
+
+    // Matching a failed #[forward]ed impl's path against our routes also requires stripping the
+    // matrix parameters. The path was already matched, so it can't consist only of them.
+    let strip_path = if item_data.strip_matrix_params() {
+        quote! {
+            let stripped = hyperdrive::matrix::strip(path);
+            let path: &str = match &stripped {
+                Some(stripped) => stripped,
+                None => path,
+            };
+        }
+    } else {
+        quote!()
+    };
+    let strip_path = &strip_path;
 
-    let mut regex_match_arms = pathmap
+    // The arms matching the regex index and method to a `Variant`. Arms that reject the request
+    // (or that construct a fallback variant) return from the generated method.
+    let regex_match_arms = |mode: Mode| -> Vec<TokenStream> {
+        let mut regex_match_arms = pathmap
         .paths()
         .enumerate()
         .flat_map(|(i, pathinfo)| {
@@ -242,7 +283,6 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
                                 .unzip();
 
                             quote! {{
-                                let path = request.uri().path();
                                 let regex = REGEXES[#i].as_ref().unwrap();
                                 let mut methods = Vec::new();
 
@@ -256,7 +296,16 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
                         }
                     };
 
-                    if let Some(fallback) = pathmap.fallback() {
+                    if let (Some(fallback), Mode::DryRun) = (pathmap.fallback(), mode) {
+                        // Ask the forwarded type, merging the accepted methods like below
+                        let forward = forward_match(fallback, quote!(methods));
+                        quote! {
+                            (Some(#i), _) => {
+                                let methods = #find_accepted_methods;
+                                return #forward;
+                            }
+                        }
+                    } else if let Some(fallback) = pathmap.fallback() {
                         // If there's a fallback variant, it might save us and accept the request.
                         // If not, we match the request path against all variants and collect the
                         // accepted methods.
@@ -268,17 +317,28 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
                             .iter()
                             .find(|v| v.ast().ident == fallback.variant_name())
                             .expect("couldn't find fallback variant");
-                        let construct = construct_variant(info, fallback);
+                        let construct = construct_variant(&item_data, info, fallback, mode);
+                        let ret = match mode {
+                            Mode::Async => quote! {
+                                let future = #construct;
+                                return Box::new(future.map_err(merge_methods));
+                            },
+                            Mode::Sync => quote! {
+                                let result: Option<Result<Self, BoxedError>> = #construct;
+                                return result.map(|result| result.map_err(merge_methods));
+                            },
+                            Mode::DryRun => unreachable!("internal error: dry run handled above"),
+                        };
 
                         quote! {
                             (Some(#i), _) => {
-                                // FIXME `find_accepted_methods` needs access to `request.uri()`
-                                // in the `map_err`. Clean things up so we don't need this.
+                                // FIXME `find_accepted_methods` and the error need the request method
+                                // and URI in the `map_err`. Clean things up so we don't need this.
                                 let mut tmp_request = http::Request::new(());
+                                *tmp_request.method_mut() = request.method().clone();
                                 *tmp_request.uri_mut() = request.uri().clone();
 
-                                let future = #construct;
-                                let future = future.map_err(move |mut e| {
+                                let merge_methods = move |mut e: BoxedError| -> BoxedError {
                                     use hyperdrive::{Error, http::StatusCode};
 
                                     // If the #[forward]ed impl also failed with "wrong_method", add
@@ -286,32 +346,52 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
                                     if let Some(err) = e.downcast_mut::<Error>() {
                                         if err.http_status() == StatusCode::METHOD_NOT_ALLOWED {
                                             let request = tmp_request;
-                                            let mut our_methods = Vec::from(#find_accepted_methods);
+                                            let path = request.uri().path();
+                                            #strip_path
+                                            let our_methods = #find_accepted_methods;
                                             let inner_methods = err.allowed_methods()
                                                 .expect("`WrongMethod` but no `allowed_methods()`?");
 
-                                            our_methods.extend(inner_methods);
-
-                                            Box::new(Error::wrong_method(Vec::from(our_methods)))
+                                            // `wrong_method` removes methods accepted by both
+                                            let methods = our_methods
+                                                .iter()
+                                                .chain(inner_methods)
+                                                .cloned()
+                                                .collect::<Vec<_>>();
+
+                                            Box::new(
+                                                Error::wrong_method(methods)
+                                                    .with_request(
+                                                        request.method().clone(),
+                                                        request.uri().path(),
+                                                    )
+                                            )
                                         } else {
                                             e
                                         }
                                     } else {
                                         e
                                     }
-                                });
+                                };
 
-                                return Box::new(future);
+                                #ret
                             }
                         }
                     } else {
                         // No fallback variant. Match the request path against all variants
                         // sharing the same path pattern, checking if the FromStr succeeds,
                         // and collecting all accepted methods.
+                        let fail = match mode {
+                            Mode::DryRun => quote!(return RouteMatch::wrong_method(methods);),
+                            _ => mode.fail(quote! {
+                                Error::wrong_method(methods)
+                                    .with_request(request.method().clone(), request.uri().path())
+                            }),
+                        };
                         quote! {
                             (Some(#i), _) => {
                                 let methods = #find_accepted_methods;
-                                return Error::wrong_method(methods).into_future();
+                                #fail
                             }
                         }
                     }
@@ -319,38 +399,54 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
         })
         .collect::<Vec<_>>();
 
-    if let Some(fallback) = pathmap.fallback() {
-        // If we have a fallback route, return it when no other regex matches.
-        // Note that this is not sufficient to correctly handle #[forward].
-        let variant = fallback.variant_name();
-        regex_match_arms.push(quote! {
-            _ => {
-                Variant::#variant
-            }
-        });
-    } else {
-        // No fallback route, add an error arm
-        regex_match_arms.push(quote! {
-            _ => {
-                return Error::from_status(StatusCode::NOT_FOUND).into_future();
-            }
-        });
-    }
+        if let Some(fallback) = pathmap.fallback() {
+            // If we have a fallback route, return it when no other regex matches.
+            // Note that this is not sufficient to correctly handle #[forward].
+            let variant = fallback.variant_name();
+            regex_match_arms.push(quote! {
+                _ => {
+                    Variant::#variant
+                }
+            });
+        } else {
+            // No fallback route, add an error arm
+            let fail = match mode {
+                Mode::DryRun => quote!(return RouteMatch::NoMatchingRoute;),
+                _ => mode.fail(quote! {
+                    Error::no_matching_route()
+                        .with_request(request.method().clone(), request.uri().path())
+                }),
+            };
+            regex_match_arms.push(quote! {
+                _ => {
+                    #fail
+                }
+            });
+        }
+        regex_match_arms
+    };
 
-    let variant_arms = s
-        .variants()
-        .iter()
-        .zip(&variant_data)
-        .filter_map(|(variant, data)| {
-            if data.constructible() {
-                Some(construct_variant(variant, data))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    let variant_arms = |mode: Mode| -> Vec<TokenStream> {
+        s.variants()
+            .iter()
+            .zip(&variant_data)
+            .filter_map(|(variant, data)| {
+                if !data.constructible() {
+                    None
+                } else if mode == Mode::DryRun {
+                    Some(match_variant(variant, data))
+                } else {
+                    Some(construct_variant(&item_data, variant, data, mode))
+                }
+            })
+            .collect()
+    };
 
-    // The `lazy_static!` declarations containing the route regexes
+    // The `lazy_static!` declarations containing the route regexes.
+    //
+    // These only depend on the route attributes, never on type parameters, so they're emitted next
+    // to the impl instead of inside of it. This way, all instantiations of a generic type (and
+    // both `from_request_and_body` and `try_from_request_parts`) share the same compiled regexes.
     let statics = if all_regexes.is_empty() {
         // No routes
         quote! {}
@@ -383,9 +479,173 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
         }}
     };
 
+    // The body of the generated method, which finds the matching route and constructs it
+    let decode = |mode: Mode| {
+        let regex_match_arms = regex_match_arms(mode);
+        let variant_arms = variant_arms(mode);
+        // `match_route` gets the method and path as arguments
+        let request_parts = if mode == Mode::DryRun {
+            quote!()
+        } else {
+            quote! {
+                let method = request.method();
+                let path = request.uri().path();
+            }
+        };
+        // With `#[strip_matrix_params]`, all matching is done on the stripped path
+        let strip_matrix_params = if item_data.strip_matrix_params() {
+            let fail = match mode {
+                Mode::DryRun => quote!(return RouteMatch::NoMatchingRoute;),
+                _ => mode.fail(quote! {
+                    Error::no_matching_route()
+                        .with_request(request.method().clone(), request.uri().path())
+                }),
+            };
+            quote! {
+                let path = match hyperdrive::matrix::strip(path) {
+                    Some(path) => path,
+                    None => {
+                        #fail
+                    }
+                };
+                let path: &str = &path;
+            }
+        } else {
+            quote!()
+        };
+        quote! {
+            // Step 0: `Variant` has all variants of the input enum that have a route attribute
+            // but without any data.
+            enum Variant {
+                #(#variants,)*
+            }
+
+            // Returns whether `self`, with `regex`, matches `path`.
+            //
+            // This checks all path placeholder's `FromStr` implementations against the
+            // path segments and returns `true` if they all succeed.
+            //
+            // This is a closure instead of a function to allow use of the `impl`-level generics
+            // (if any).
+            let variant_matches_path = |var: Variant, regex: &Regex, path: &str| -> bool {
+                match var {
+                    #( Variant::#variants => { #variant_matches_path } )*
+                }
+            };
+
+            // Step 1: Match against the generated regex set and inspect the HTTP
+            // method in order to find the route that matches.
+            #request_parts
+            #strip_matrix_params
+            let index: Option<usize> = #matching_regex;
+
+            let variant = match (index, method) {
+                #(#regex_match_arms)*
+            };
+
+            match variant {
+                #( Variant::#variants => #variant_arms, )*
+            }
+        }
+    };
+    let decode_async = decode(Mode::Async);
+    let decode_sync = decode(Mode::Sync);
+    let decode_dry_run = decode(Mode::DryRun);
+
     // Don't automatically add bounds, we'll do that ourselves
     s.add_bounds(AddBounds::None);
 
+    // Routes are named after their variant (or the struct itself)
+    let route_name_arms = s.each_variant(|variant| {
+        let name = variant.ast().ident.to_string();
+        quote!(Some(#name))
+    });
+
+    // The path templates of all routes, for `FromRequest::route_paths`
+    let mut route_paths = Vec::new();
+    for route in variant_data.iter().flat_map(|data| data.routes()) {
+        if route.path() != "*" && !route_paths.contains(&route.path()) {
+            route_paths.push(route.path());
+        }
+    }
+    // Routes of a fallback variant are defined by the `#[forward]`ed type
+    let forwarded = pathmap.fallback().and_then(|fallback| {
+        fallback
+            .field_uses()
+            .find(|(_, kind)| *kind == FieldKind::Forward)
+            .map(|(field, _)| field.ty.clone())
+    });
+    let route_paths = match &forwarded {
+        Some(ty) => quote! {{
+            let mut paths = vec![#(#route_paths),*];
+            paths.extend(<#ty as FromRequest>::route_paths());
+            paths
+        }},
+        None => quote!(vec![#(#route_paths),*]),
+    };
+
+    // The metadata of all routes, for `FromRequest::routes`
+    let route_infos = pathmap
+        .paths()
+        .flat_map(|path| path.routes())
+        .map(|(variant, route)| {
+            let method = route.method();
+            let path = route.path();
+            let name = variant.variant_name().to_string();
+            let mut info = quote!(RouteInfo::new(http::Method::#method, #path, #name));
+            if route.is_implied() {
+                info = quote!(#info.implied());
+            }
+            if let Some(doc) = variant.doc() {
+                info = quote!(#info.documented(#doc));
+            }
+            for (param, doc) in variant.param_docs() {
+                info = quote!(#info.param_doc(#param, #doc));
+            }
+            info
+        })
+        .collect::<Vec<_>>();
+    let routes = match (pathmap.fallback(), &forwarded) {
+        (Some(fallback), Some(ty)) => {
+            let name = fallback.variant_name().to_string();
+            quote! {{
+                let mut routes = vec![#(#route_infos),*];
+                routes.extend(
+                    <#ty as FromRequest>::routes()
+                        .into_iter()
+                        .map(|route| route.forwarded_by(#name)),
+                );
+                routes
+            }}
+        }
+        _ => quote!(vec![#(#route_infos),*]),
+    };
+
+    // Proc macros can't emit warnings directly, so use of a deprecated item is used to warn about
+    // `#[body]` fields on `DELETE` routes.
+    let warnings = variant_data
+        .iter()
+        .filter_map(|data| data.body_on_delete().map(|field| (data, field)))
+        .map(|(data, field)| {
+            let name = Ident::new(&format!("BodyOnDelete{}", data.variant_name()), field.span());
+            let warn = Ident::new(&format!("warn_{}", name), field.span());
+            let note = format!(
+                "the #[body] field `{}` of `{}` is decoded from DELETE requests, whose bodies are dropped by many clients and proxies (add `#[allow_body_on_get]` to silence this warning)",
+                field,
+                data.variant_name()
+            );
+            quote_spanned! {field.span()=>
+                #[deprecated(note = #note)]
+                struct #name;
+
+                #[allow(dead_code, non_snake_case)]
+                fn #warn() {
+                    let _ = #name;
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
     // Whether the impl is generic over types (ie. has type parameters)
     let is_type_generic = s.ast().generics.type_params().next().is_some();
 
@@ -408,19 +668,31 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
         Vec::new()
     };
 
-    s.gen_impl(quote!(
+    let to_request = if item_data.client() {
+        generate_to_request(&s, &variant_data, is_type_generic)
+    } else {
+        TokenStream::new()
+    };
+
+    let from_request = s.gen_impl(quote!(
         extern crate hyperdrive;
         use hyperdrive::{
-            FromBody, FromRequest, Guard, DefaultFuture, NoContext, BoxedError, Error,
-            http::{self, StatusCode}, hyper, lazy_static, regex::{RegexSet, Regex},
+            FromBody, FromContext, FromRequest, Guard, DefaultFuture, NoContext, BoxedError, Error, ErrorKind,
+            RouteMatch, routes::RouteInfo, http::{self, StatusCode}, hyper, lazy_static, regex::{RegexSet, Regex},
             futures::{IntoFuture, Future},
         };
+        use hyperdrive::select_context::{Select, SelectDirect, SelectLeft, SelectRight};
+        use hyperdrive::try_sync::{TrySync, TrySyncResult, TrySyncFallback};
         // Make sure `.as_ref()` always refers to the `AsRef` trait in libstd.
         // Otherwise the calling crate could override this.
         use core::convert::AsRef;
         use core::str::FromStr;
         use std::sync::Arc;
 
+        #statics
+
+        #(#warnings)*
+
         gen impl<#(#impl_generics),*> FromRequest for @Self #where_clause {
             type Future = DefaultFuture<Self, BoxedError>;
             type Context = #context;
@@ -428,45 +700,189 @@ pub fn derive_from_request(mut s: Structure<'_>) -> TokenStream {
             fn from_request_and_body(
                 request: &Arc<http::Request<()>>,
                 body: hyper::Body,
-                context: Self::Context,
+                context: Arc<Self::Context>,
             ) -> Self::Future {
-                // Step 0: `Variant` has all variants of the input enum that have a route attribute
-                // but without any data.
-                enum Variant {
-                    #(#variants,)*
+                #decode_async
+            }
+
+            fn try_from_request_parts(
+                request: &Arc<http::Request<()>>,
+                context: &Self::Context,
+            ) -> Option<Result<Self, BoxedError>> {
+                #decode_sync
+            }
+
+            fn route_name(&self) -> Option<&'static str> {
+                match *self {
+                    #route_name_arms
                 }
+            }
 
-                // Returns whether `self`, with `regex`, matches `path`.
-                //
-                // This checks all path placeholder's `FromStr` implementations against the
-                // path segments and returns `true` if they all succeed.
-                //
-                // This is a closure instead of a function to allow use of the `impl`-level generics
-                // (if any).
-                let variant_matches_path = |var: Variant, regex: &Regex, path: &str| -> bool {
-                    match var {
-                        #( Variant::#variants => { #variant_matches_path } )*
-                    }
-                };
+            fn route_paths() -> Vec<&'static str> {
+                #route_paths
+            }
+
+            fn routes() -> Vec<RouteInfo> {
+                #routes
+            }
 
-                // Step 1: Match against the generated regex set and inspect the HTTP
-                // method in order to find the route that matches.
-                #statics
+            fn match_route(method: &http::Method, path: &str) -> RouteMatch {
+                #decode_dry_run
+            }
+        }
+    ));
 
-                let method = request.method();
-                let path = request.uri().path();
-                let index: Option<usize> = #matching_regex;
+    quote!(#from_request #to_request)
+}
 
-                let variant = match (index, method) {
-                    #(#regex_match_arms)*
-                };
+/// Generates the `ToRequest` impl requested with `#[hyperdrive(client)]`.
+///
+/// Each variant with a route attribute creates a request using the method and path of its first
+/// route, filling in the placeholders using `Display`. The `#[query_params]` field is serialized
+/// into the query string and the `#[body]` field via `ToBody`. Guards are skipped. Variants with
+/// a `#[forward]` field delegate to the forwarded type.
+fn generate_to_request(
+    s: &Structure<'_>,
+    variant_data: &[VariantData],
+    is_type_generic: bool,
+) -> TokenStream {
+    let mut bounds = Vec::new();
+    let data = |variant: &VariantInfo<'_>| {
+        variant_data
+            .iter()
+            .find(|data| data.variant_name() == variant.ast().ident)
+            .expect("internal error: couldn't find variant data")
+    };
+    fn binding<'a, 'b>(variant: &'b VariantInfo<'a>, name: &Ident) -> &'b BindingInfo<'a> {
+        variant
+            .bindings()
+            .iter()
+            .find(|binding| binding.ast().ident.as_ref() == Some(name))
+            .expect("internal error: couldn't find field by name")
+    }
+    let no_route = |variant: &VariantInfo<'_>| {
+        let message = format!(
+            "`{}` has no route attribute, so it can't be turned into a request",
+            variant.ast().ident
+        );
+        quote!(panic!(#message))
+    };
+
+    // Builds the path and query string
+    let path_arms = s.each_variant(|variant| {
+        let data = data(variant);
+        if let Some(forward) = data.forward_field() {
+            let binding = binding(variant, forward);
+            let ty = &binding.ast().ty;
+            bounds.push(quote!(#ty: ::hyperdrive::ToRequest));
+            return quote!(::hyperdrive::ToRequest::path_and_query(#binding));
+        }
+
+        let route = match data.routes().first() {
+            Some(route) => route,
+            None => return no_route(variant),
+        };
+
+        let mut path = Vec::new();
+        if route.segments().is_empty() {
+            path.push(quote!(path.push('*');));
+        }
+        for segment in route.segments() {
+            let (name, rest) = match segment {
+                PathSegment::Literal(literal) => {
+                    path.push(quote! {
+                        path.push('/');
+                        path.push_str(#literal);
+                    });
+                    continue;
+                }
+                PathSegment::Placeholder(name) => (name, false),
+                PathSegment::Rest(name) => (name, true),
+            };
+            let binding = binding(variant, name);
+            let ty = &binding.ast().ty;
+            bounds.push(quote!(#ty: ::std::fmt::Display));
+            path.push(quote! {
+                path.push('/');
+                ::hyperdrive::reverse_route::push_placeholder(
+                    &mut path,
+                    &::std::string::ToString::to_string(#binding),
+                    #rest,
+                );
+            });
+        }
+
+        if let Some(query_params) = data.query_params_field() {
+            let binding = binding(variant, query_params);
+            let ty = &binding.ast().ty;
+            bounds.push(quote!(#ty: ::hyperdrive::serde::Serialize));
+            path.push(quote! {
+                ::hyperdrive::reverse_route::push_query(&mut path, #binding);
+            });
+        }
+
+        quote! {
+            let mut path = ::std::string::String::new();
+            #(#path)*
+            path
+        }
+    });
+
+    // Creates the request with the method and body
+    let request_arms = s.each_variant(|variant| {
+        let data = data(variant);
+        if let Some(forward) = data.forward_field() {
+            let binding = binding(variant, forward);
+            return quote!(::hyperdrive::ToRequest::to_request(#binding));
+        }
+
+        let route = match data.routes().first() {
+            Some(route) => route,
+            None => return no_route(variant),
+        };
+
+        let body = data.body_field().map(|body| {
+            let binding = binding(variant, body);
+            let ty = &binding.ast().ty;
+            bounds.push(quote!(#ty: ::hyperdrive::ToBody));
+            quote!(::hyperdrive::ToBody::to_body(#binding, &mut request);)
+        });
+
+        let method = route.method();
+        quote! {
+            let path = ::hyperdrive::ToRequest::path_and_query(self);
+
+            let mut request = ::hyperdrive::http::Request::new(::hyperdrive::hyper::Body::empty());
+            *request.method_mut() = ::hyperdrive::http::Method::#method;
+            *request.uri_mut() = path.parse().expect("ToRequest created an invalid URI");
+            #body
+            request
+        }
+    });
+
+    let where_clause = if is_type_generic {
+        quote!(where #(#bounds),*)
+    } else {
+        TokenStream::new()
+    };
+
+    s.gen_impl(quote! {
+        extern crate hyperdrive;
+
+        gen impl hyperdrive::ToRequest for @Self #where_clause {
+            fn to_request(&self) -> hyperdrive::http::Request<hyperdrive::hyper::Body> {
+                match *self {
+                    #request_arms
+                }
+            }
 
-                match variant {
-                    #( Variant::#variants => #variant_arms, )*
+            fn path_and_query(&self) -> ::std::string::String {
+                match *self {
+                    #path_arms
                 }
             }
         }
-    ))
+    })
 }
 
 /// Information about trait bounds that need to hold for a `FromRequest` impl to be applicable.
@@ -520,8 +936,8 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
 
     let mut bounds: Bounds = variants
         .iter()
-        .flat_map(|v| v.field_uses())
-        .map(|(field, field_kind)| {
+        .flat_map(|v| v.field_uses().map(move |(field, kind)| (v, field, kind)))
+        .map(|(variant, field, field_kind)| {
             let ty = field.ty.clone();
             match field_kind {
                 FieldKind::PathSegment => Bounds {
@@ -558,10 +974,8 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
                                 ::std::marker::Send +
                                 'static
                             ),
-                            quote!( #context: AsRef<#frombody_context> ),
-                            // better implied bounds plz
                             quote!( #frombody_context:
-                                ::hyperdrive::RequestContext
+                                ::hyperdrive::FromContext<#context>
                             ),
                             quote!( #frombody_result:
                                 ::hyperdrive::futures::IntoFuture<
@@ -598,10 +1012,8 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
                                 ::std::marker::Send +
                                 'static
                             ),
-                            quote!( #context: AsRef<#guard_context> ),
-                            // better implied bounds plz
                             quote!( #guard_context:
-                                ::hyperdrive::RequestContext
+                                ::hyperdrive::FromContext<#context>
                             ),
                             quote!( #guard_result:
                                 ::hyperdrive::futures::IntoFuture<
@@ -623,16 +1035,25 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
                         ],
                     }
                 },
-                FieldKind::Forward => Bounds {
-                    addl_ty_params: Vec::new(),
-                    impl_bounds: vec![
-                        // FIXME: support `AsRef` conversion here too
-                        quote!( #ty:
-                            ::hyperdrive::FromRequest<Context=#context> +
-                            ::std::marker::Send +
-                            'static
-                        ),
-                    ],
+                FieldKind::Forward => {
+                    // With `#[forward(with = function)]`, the context is whatever the function
+                    // returns
+                    let ty_context = if variant.forward_with().is_some() {
+                        quote!()
+                    } else {
+                        quote!(<Context=#context>)
+                    };
+                    Bounds {
+                        addl_ty_params: Vec::new(),
+                        impl_bounds: vec![
+                            // FIXME: support `AsRef` conversion here too
+                            quote!( #ty:
+                                ::hyperdrive::FromRequest #ty_context +
+                                ::std::marker::Send +
+                                'static
+                            ),
+                        ],
+                    }
                 },
             }
         })
@@ -645,7 +1066,8 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
 /// Generates all the code needed to build an enum variant from a matching
 /// request.
 ///
-/// Returns an expression of type `DefaultFuture<Self, BoxedError>`.
+/// Returns an expression of type `DefaultFuture<Self, BoxedError>` in `Mode::Async`, and of type
+/// `Option<Result<Self, BoxedError>>` in `Mode::Sync`.
 ///
 /// The generated code will do the following:
 /// * If the path has any segment placeholders:
@@ -660,7 +1082,12 @@ fn generate_trait_bounds(item: &ItemData, variants: &[VariantData]) -> Bounds {
 ///
 /// The code will also assume:
 /// * That `request` is the incoming request, and can be consumed.
-fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStream {
+fn construct_variant(
+    item: &ItemData,
+    variant: &VariantInfo<'_>,
+    data: &VariantData,
+    mode: Mode,
+) -> TokenStream {
     let field_by_name = |name: &Ident| -> &syn::Field {
         variant
             .ast()
@@ -681,9 +1108,14 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
                     .iter()
                     .enumerate()
                     .map(|(i, field_name)| {
-                        let variable = Ident::new(&format!("fld_{}", field_name), Span::call_site());
+                        let variable =
+                            Ident::new(&format!("fld_{}", field_name), Span::call_site());
                         let capture = i + 1;
                         let ty = &field_by_name(field_name).ty;
+                        let field = field_name.to_string();
+                        let fail = mode.fail(quote! {
+                            Error::with_source_and_field(ErrorKind::PathSegment, #field, e)
+                        });
                         quote! {
                             let #variable = captures
                                 .get(#capture)
@@ -692,8 +1124,7 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
                             let #variable = match <#ty as FromStr>::from_str(#variable) {
                                 Ok(v) => v,
                                 Err(e) => {
-                                    return Error::with_source(StatusCode::NOT_FOUND, e)
-                                        .into_future();
+                                    #fail
                                 }
                             };
                         }
@@ -705,7 +1136,7 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
                     let captures = REGEXES[index.expect("no regex matched, but there's placeholders?")]
                         .as_ref()
                         .expect("internal error: no regex for route with placeholders")
-                        .captures(request.uri().path())
+                        .captures(path)
                         .expect("internal error: regex first matched but now didn't?");
 
                     #(#parse)*
@@ -722,12 +1153,18 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
     let query = if let Some(query_params_field) = data.query_params_field() {
         let ty = &field_by_name(&query_params_field).ty;
         let variable = Ident::new(&format!("fld_{}", query_params_field), Span::call_site());
+        let fail = mode.fail(quote!(Error::query_param::<#ty, _>(raw_query, e)));
+        let from_str = if data.query_params_strict() {
+            quote!(from_str_strict)
+        } else {
+            quote!(from_str)
+        };
         quote! {
             // Parse query params
             let raw_query = request.uri().query().unwrap_or("");
-            let #variable = match serde_urlencoded::from_str::<#ty>(raw_query) {
+            let #variable = match hyperdrive::query::#from_str::<#ty>(raw_query) {
                 Ok(val) => val,
-                Err(e) => return Error::with_source(StatusCode::BAD_REQUEST, e).into_future(),
+                Err(e) => { #fail }
             };
         }
     } else {
@@ -746,6 +1183,11 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
         };
         Ident::new(&format!("fld_{}", name), Span::call_site())
     });
+
+    if mode == Mode::Sync {
+        return construct_variant_sync(item, data, field_by_name, placeholders, query, construct);
+    }
+
     let mut future = quote! {
         Ok(#construct).into_future()
     };
@@ -754,8 +1196,9 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
     if let Some(body) = data.body_field() {
         let ty = &field_by_name(body).ty;
         let var = Ident::new(&format!("fld_{}", body), Span::call_site());
+        let context = select_context(item, quote!(<#ty as FromBody>::Context));
         future = quote! {
-            <#ty as FromBody>::from_body(&request, body, context.as_ref())
+            <#ty as FromBody>::from_body(&request, body, #context)
                 .into_future()
                 .and_then(move |#var| #future)
         };
@@ -765,8 +1208,21 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
     if let Some(forward) = data.forward_field() {
         let ty = &field_by_name(forward).ty;
         let var = Ident::new(&format!("fld_{}", forward), Span::call_site());
+        let context = match data.forward_with() {
+            Some(function) => {
+                // All guards have been evaluated at this point
+                let guards = data.guard_fields().iter().map(|fld| {
+                    Ident::new(
+                        &format!("fld_{}", fld.ident.as_ref().unwrap()),
+                        Span::call_site(),
+                    )
+                });
+                quote!(Arc::new(#function(&*context, #(&#guards),*)))
+            }
+            None => quote!(context),
+        };
         future = quote! {{
-            <#ty as FromRequest>::from_request_and_body(&request, body, context)
+            <#ty as FromRequest>::from_request_and_body(&request, body, #context)
                 .into_future()
                 .and_then(move |#var| #future)
         }};
@@ -782,8 +1238,9 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
     {
         let ty = &field_by_name(&guard).ty;
         let var = Ident::new(&format!("fld_{}", guard), Span::call_site());
+        let context = select_context(item, quote!(<#ty as Guard>::Context));
         future = quote! {
-            <#ty as Guard>::from_request(&request, context.as_ref())
+            <#ty as Guard>::from_request(&request, #context)
                 .into_future()
                 .and_then(move |#var| #future)
         };
@@ -803,6 +1260,190 @@ fn construct_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStre
     }}
 }
 
+/// Generates the `RouteMatch` returned by `match_route` when the regex and method of `variant`
+/// matched (or when it's the fallback variant).
+///
+/// Like `construct_variant`, this checks the `FromStr` implementations of the placeholders, but
+/// returns the captured segments instead of the parsed values. Guards and the body are ignored.
+fn match_variant(variant: &VariantInfo<'_>, data: &VariantData) -> TokenStream {
+    let name = variant.ast().ident.to_string();
+
+    let placeholders = match data.routes().first() {
+        Some(route) if !route.placeholders().is_empty() => {
+            let probes = route
+                .placeholders()
+                .iter()
+                .enumerate()
+                .map(|(i, field_name)| {
+                    let capture = i + 1;
+                    let ty = &variant
+                        .ast()
+                        .fields
+                        .iter()
+                        .find(|field| field.ident.as_ref() == Some(field_name))
+                        .expect("internal error: couldn't find field by name")
+                        .ty;
+                    let field = field_name.to_string();
+                    quote! {{
+                        let segment = captures
+                            .get(#capture)
+                            .expect("internal error: capture group did not match anything")
+                            .as_str();
+                        if <#ty as FromStr>::from_str(segment).is_err() {
+                            return RouteMatch::PathSegment { name: #name, field: #field };
+                        }
+                        (#field, segment.to_string())
+                    }}
+                })
+                .collect::<Vec<_>>();
+
+            quote! {{
+                let captures = REGEXES[index.expect("no regex matched, but there's placeholders?")]
+                    .as_ref()
+                    .expect("internal error: no regex for route with placeholders")
+                    .captures(path)
+                    .expect("internal error: regex first matched but now didn't?");
+
+                vec![#(#probes),*]
+            }}
+        }
+        _ => quote!(Vec::new()),
+    };
+
+    if data.forward_field().is_some() {
+        let forward = forward_match(data, quote!(Vec::new()));
+        // The placeholders still have to be valid
+        quote! {{
+            let _: Vec<(&str, String)> = #placeholders;
+            #forward
+        }}
+    } else {
+        quote! {
+            RouteMatch::Route {
+                name: #name,
+                placeholders: #placeholders,
+            }
+        }
+    }
+}
+
+/// Generates a call to `RouteMatch::forward`, asking the `#[forward]`ed type of `data` to match
+/// the request.
+///
+/// `methods` evaluates to the methods accepted by the routes matching the path, which are merged
+/// into a `WrongMethod` match of the forwarded type.
+fn forward_match(data: &VariantData, methods: TokenStream) -> TokenStream {
+    let name = data.variant_name().to_string();
+    let ty = data
+        .field_uses()
+        .find(|(_, kind)| *kind == FieldKind::Forward)
+        .map(|(field, _)| &field.ty)
+        .expect("internal error: no #[forward] field");
+    quote! {
+        RouteMatch::forward(
+            #name,
+            <#ty as FromRequest>::match_route(method, path),
+            #methods,
+        )
+    }
+}
+
+/// Generates the expression passing the context of type `ty` to a field.
+///
+/// For a `ContextPair`, the halves of the pair are searched as well (see the `select_context`
+/// module of hyperdrive). Every other context has to provide `ty` itself.
+fn select_context(item: &ItemData, ty: TokenStream) -> TokenStream {
+    if item.context_is_pair() {
+        quote!((&&&Select::<Self::Context, #ty>::default()).select_context(&*context))
+    } else {
+        quote!(<#ty as FromContext<Self::Context>>::from_context(&*context))
+    }
+}
+
+/// Generates the body of a variant in `Mode::Sync` from the parts created by `construct_variant`.
+///
+/// Returns `None` for variants that need to run asynchronous operations, before running any
+/// guards (otherwise, `from_request_sync` would run them twice).
+fn construct_variant_sync<'a>(
+    item: &ItemData,
+    data: &VariantData,
+    field_by_name: impl Fn(&Ident) -> &'a syn::Field,
+    placeholders: TokenStream,
+    query: TokenStream,
+    construct: TokenStream,
+) -> TokenStream {
+    let guards = data
+        .guard_fields()
+        .iter()
+        .map(|fld| fld.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+
+    // Reading the body is always asynchronous. A forwarded type can't be guaranteed to be
+    // synchronous before our guards are run.
+    if data.body_field().is_some() || (data.forward_field().is_some() && !guards.is_empty()) {
+        return quote!(None);
+    }
+
+    let mut checks = Vec::new();
+    let mut runs = Vec::new();
+    for guard in &guards {
+        let ty = &field_by_name(guard).ty;
+        let var = Ident::new(&format!("fld_{}", guard), Span::call_site());
+        let sync = Ident::new(&format!("sync_{}", guard), Span::call_site());
+        let context = select_context(item, quote!(<#ty as Guard>::Context));
+        checks.push(quote! {
+            let #sync = match (&&TrySync::<<#ty as Guard>::Result>::default()).check() {
+                Some(sync) => sync,
+                None => return None,
+            };
+        });
+        runs.push(quote! {
+            let #var = #sync.result(<#ty as Guard>::from_request(request, #context))?;
+        });
+    }
+
+    // Errors of guards and the forwarded type are the value of the expression (instead of being
+    // returned), so that a fallback variant can merge the allowed methods into them.
+    let result = if let Some(forward) = data.forward_field() {
+        let ty = &field_by_name(forward).ty;
+        let var = Ident::new(&format!("fld_{}", forward), Span::call_site());
+        let context = match data.forward_with() {
+            Some(function) => quote!(&#function(&*context)),
+            None => quote!(context),
+        };
+        quote! {
+            match <#ty as FromRequest>::try_from_request_parts(request, #context) {
+                Some(Ok(#var)) => Some(Ok(#construct)),
+                Some(Err(e)) => Some(Err(e)),
+                None => None,
+            }
+        }
+    } else if guards.is_empty() {
+        quote!(Some(Ok(#construct)))
+    } else {
+        quote! {
+            Some((|| -> Result<Self, BoxedError> {
+                #(#runs)*
+
+                Ok(#construct)
+            })())
+        }
+    };
+
+    quote! {{
+        use std::str::FromStr;
+
+        #placeholders
+
+        #query
+
+        // Check that all guards are synchronous before running any of them
+        #(#checks)*
+
+        #result
+    }}
+}
+
 #[cfg(test)]
 mod tests {
     use super::derive_from_request;
@@ -842,6 +1483,103 @@ mod tests {
         }
     }
 
+    #[test]
+    #[should_panic(expected = "`Search` only has GET/HEAD routes")]
+    fn body_on_get() {
+        expand! {
+            enum Routes {
+                #[get("/search")]
+                #[head("/search")]
+                Search {
+                    #[body]
+                    query: String,
+                },
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`MyStruct` only has GET/HEAD routes")]
+    fn body_on_get_struct() {
+        expand! {
+            #[get("/search")]
+            struct MyStruct {
+                #[body]
+                query: String,
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[allow_body_on_get]` is not valid on enums")]
+    fn allow_body_on_get_on_enum() {
+        expand! {
+            #[allow_body_on_get]
+            enum Routes {}
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`#[strip_matrix_params]` is not valid on enum variants")]
+    fn strip_matrix_params_on_variant() {
+        expand! {
+            enum Routes {
+                #[strip_matrix_params]
+                #[get("/")]
+                Index,
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown #[query_params] option (expected `strict`)")]
+    fn query_params_unknown_option() {
+        expand! {
+            #[get("/")]
+            struct MyStruct {
+                #[query_params(lenient)]
+                query: Query,
+            }
+        }
+    }
+
+    /// Expands `item`, returning the generated code as a string.
+    fn expand_to_string(item: &str) -> String {
+        let ast = syn::parse_str::<syn::DeriveInput>(item).unwrap();
+        derive_from_request(synstructure::Structure::new(&ast)).to_string()
+    }
+
+    #[test]
+    fn body_on_delete_warns() {
+        let expanded = expand_to_string(
+            r#"
+            enum Routes {
+                #[delete("/items")]
+                DeleteMany {
+                    #[body]
+                    ids: String,
+                },
+            }
+            "#,
+        );
+        assert!(expanded.contains("deprecated"));
+        assert!(expanded.contains("BodyOnDeleteDeleteMany"));
+
+        let expanded = expand_to_string(
+            r#"
+            enum Routes {
+                #[delete("/items")]
+                #[allow_body_on_get]
+                DeleteMany {
+                    #[body]
+                    ids: String,
+                },
+            }
+            "#,
+        );
+        assert!(!expanded.contains("deprecated"));
+    }
+
     #[test]
     #[should_panic(expected = "at least one variant of `Routes` must be constructible")]
     fn no_route_enum() {
@@ -1038,5 +1776,32 @@ mod tests {
         }
     }
 
+    #[test]
+    #[should_panic(expected = "invalid #[forward] attribute")]
+    fn invalid_forward_with() {
+        expand! {
+            #[derive(FromRequest)]
+            enum Enum {
+                Variant {
+                    #[forward(using = inner_context)]
+                    inner: (),
+                },
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown #[hyperdrive] option")]
+    fn unknown_hyperdrive_option() {
+        expand! {
+            #[derive(FromRequest)]
+            #[hyperdrive(server)]
+            enum Enum {
+                #[get("/")]
+                Variant,
+            }
+        }
+    }
+
     // TODO write lots more tests
 }
@@ -3,7 +3,11 @@ use indexmap::{map::Entry, IndexMap};
 use proc_macro2::{Ident, Span};
 use regex::Regex;
 use std::{fmt, slice};
-use syn::{Attribute, Field, Lit, Meta, NestedMeta};
+use syn::{
+    parenthesized,
+    parse::{ParseStream, Parser},
+    Attribute, Field, Lit, Meta, NestedMeta, Path, Token,
+};
 use synstructure::VariantAst;
 
 // Attributes need to be kept in sync with lib.rs
@@ -16,7 +20,15 @@ const METHOD_ATTRS: &[&str] = &[
 fn our_attrs() -> impl Iterator<Item = &'static str> {
     METHOD_ATTRS
         .iter()
-        .chain(&["context", "body", "forward", "query_params"])
+        .chain(&[
+            "context",
+            "body",
+            "forward",
+            "query_params",
+            "hyperdrive",
+            "allow_body_on_get",
+            "strip_matrix_params",
+        ])
         .cloned()
 }
 
@@ -25,6 +37,68 @@ fn known_attr(name: &Ident) -> bool {
     our_attrs().find(|s| name == s).is_some()
 }
 
+/// Parses the function path out of a `#[forward(with = function)]` attribute.
+fn parse_forward_with(attr: &Attribute) -> Path {
+    let parser = |input: ParseStream<'_>| -> syn::Result<Path> {
+        let content;
+        parenthesized!(content in input);
+        let key = content.parse::<Ident>()?;
+        if key != "with" {
+            return Err(content.error("expected `with`"));
+        }
+        content.parse::<Token![=]>()?;
+        content.parse()
+    };
+    parser.parse2(attr.tts.clone()).expect(
+        "invalid #[forward] attribute (expected `#[forward]` or `#[forward(with = function)]`)",
+    )
+}
+
+/// Parses a `#[query_params]` or `#[query_params(strict)]` attribute, returning whether `strict`
+/// was specified.
+fn parse_query_params_strict(meta: &Meta) -> bool {
+    match meta {
+        Meta::Word(_) => false,
+        Meta::List(list) => match list.nested.iter().collect::<Vec<_>>().as_slice() {
+            [NestedMeta::Meta(Meta::Word(option))] if option == "strict" => true,
+            _ => panic!("unknown #[query_params] option (expected `strict`)"),
+        },
+        Meta::NameValue(_) => panic!(
+            "invalid #[query_params] attribute (expected `#[query_params]` or `#[query_params(strict)]`)"
+        ),
+    }
+}
+
+/// Collects the text of the doc comments in `attrs`, with the space after each `///` removed.
+///
+/// Returns `None` if there are no (non-empty) doc comments.
+fn doc_string(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(ref nv)) if nv.ident == "doc" => match &nv.lit {
+                Lit::Str(s) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| {
+            if line.starts_with(' ') {
+                line.replacen(' ', "", 1)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+    let doc = lines.join("\n");
+    let doc = doc.trim();
+    if doc.is_empty() {
+        None
+    } else {
+        Some(doc.to_string())
+    }
+}
+
 /// Returns whether `name` names an HTTP method attribute (lowercase only).
 fn is_method(name: &Ident) -> bool {
     let name = name.to_string().to_lowercase();
@@ -35,17 +109,51 @@ fn is_method(name: &Ident) -> bool {
 pub struct ItemData {
     name: Ident,
     context: Option<syn::Type>,
+    /// Whether `#[hyperdrive(client)]` was specified, which generates a `ToRequest` impl.
+    client: bool,
+    /// Whether `#[strip_matrix_params]` was specified, which removes matrix parameters from the
+    /// path before matching it.
+    strip_matrix_params: bool,
 }
 
 impl ItemData {
     pub fn parse(name: Ident, attrs: &[Attribute], is_struct: bool) -> Self {
         let mut context = None;
+        let mut client = false;
+        let mut strip_matrix_params = false;
 
         for attr in attrs {
-            let name = attr.parse_meta().unwrap().name();
+            // Not using `parse_meta`, since the context type can contain generic
+            // arguments, which aren't valid in a meta item.
+            let name = attr
+                .path
+                .segments
+                .last()
+                .expect("attribute path can't be empty")
+                .into_value()
+                .ident
+                .clone();
             if name == "context" {
                 let ty = syn::parse2(attr.tts.clone()).expect("#[context] must be given a type");
                 insert("#[context]", &mut context, ty);
+            } else if name == "hyperdrive" {
+                let options = match attr.parse_meta() {
+                    Ok(Meta::List(list)) => list.nested,
+                    _ => panic!("#[hyperdrive] must be of the form `#[hyperdrive(option)]`"),
+                };
+                for option in options {
+                    match option {
+                        NestedMeta::Meta(Meta::Word(ref ident)) if ident == "client" => {
+                            client = true;
+                        }
+                        _ => panic!("unknown #[hyperdrive] option (expected `client`)"),
+                    }
+                }
+            } else if name == "strip_matrix_params" {
+                if !attr.tts.is_empty() {
+                    panic!("#[strip_matrix_params] does not take any arguments");
+                }
+                strip_matrix_params = true;
             } else if known_attr(&name) && !is_struct {
                 panic!(
                     "`#[{}]` is not valid on enums (did you mean to place it on a variant instead?)",
@@ -54,13 +162,50 @@ impl ItemData {
             }
         }
 
-        Self { name, context }
+        Self {
+            name,
+            context,
+            client,
+            strip_matrix_params,
+        }
     }
 
     /// Returns the custom context type (`None` if none was specified).
     pub fn context(&self) -> Option<&syn::Type> {
         self.context.as_ref()
     }
+
+    /// Returns whether the context type is a `ContextPair`, whose halves provide the contexts of
+    /// the fields as well.
+    ///
+    /// This can only be determined by the name of the type, so a type alias isn't recognized.
+    pub fn context_is_pair(&self) -> bool {
+        let mut ty = match &self.context {
+            Some(ty) => ty,
+            None => return false,
+        };
+        // `#[context(T)]` is parsed as the parenthesized type `(T)`
+        while let syn::Type::Paren(paren) = ty {
+            ty = &paren.elem;
+        }
+        match ty {
+            syn::Type::Path(ty) => match ty.path.segments.last() {
+                Some(segment) => segment.into_value().ident == "ContextPair",
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns whether a `ToRequest` impl should be generated.
+    pub fn client(&self) -> bool {
+        self.client
+    }
+
+    /// Returns whether matrix parameters are removed from the path before matching it.
+    pub fn strip_matrix_params(&self) -> bool {
+        self.strip_matrix_params
+    }
 }
 
 /// Attribute data attached to an enum variant or struct.
@@ -68,6 +213,8 @@ impl ItemData {
 pub struct VariantData {
     /// Name of the variant.
     name: Ident,
+    /// The doc comment of the variant, if any.
+    doc: Option<String>,
     /// The parsed HTTP routes. There's one for each `#[method]`-style attribute
     /// on the variant.
     ///
@@ -78,9 +225,17 @@ pub struct VariantData {
     /// If this is empty and there's no `forward_field`, then this variant will not be created by
     /// the derived `FromRequest` implementation.
     routes: Vec<Route>,
+    /// Whether `#[allow_body_on_get]` was specified, which allows a `#[body]` field on a variant
+    /// that only has `GET` and `HEAD` routes.
+    allow_body_on_get: bool,
     body_field: Option<Field>,
     forward_field: Option<Field>,
+    /// The function given as `#[forward(with = function)]`, which creates the
+    /// context of the forwarded type.
+    forward_with: Option<Path>,
     query_params_field: Option<Field>,
+    /// Whether `#[query_params(strict)]` was used, which rejects unknown query parameters.
+    query_params_strict: bool,
     guard_fields: Vec<Field>,
     path_segment_fields: Vec<Field>,
 }
@@ -104,6 +259,7 @@ impl VariantData {
     pub fn parse(ast: &VariantAst<'_>, is_struct: bool) -> Self {
         // Collect all the route attributes on the variant
         let mut routes = Vec::new();
+        let mut allow_body_on_get = false;
         for attr in ast.attrs {
             let meta = attr.parse_meta().unwrap();
             match &meta {
@@ -113,6 +269,9 @@ impl VariantData {
                         &list.nested.iter().collect::<Vec<_>>(),
                     ));
                 }
+                Meta::Word(ident) if ident == "allow_body_on_get" => {
+                    allow_body_on_get = true;
+                }
                 _ if known_attr(&meta.name()) && !is_struct => {
                     panic!("`#[{}]` is not valid on enum variants", meta.name())
                 }
@@ -170,7 +329,9 @@ impl VariantData {
         // Now check all attributes on the variant's fields
         let mut body_field = None;
         let mut forward_field = None;
+        let mut forward_with = None;
         let mut query_params_field = None;
+        let mut query_params_strict = false;
         let mut guard_fields = Vec::new();
         let mut path_segment_fields = Vec::new();
         for field in ast.fields.iter() {
@@ -184,6 +345,25 @@ impl VariantData {
             };
 
             for attr in &field.attrs {
+                let is_forward =
+                    attr.path.segments.len() == 1 && attr.path.segments[0].ident == "forward";
+                if is_forward && !attr.tts.is_empty() {
+                    // `#[forward(with = function)]` isn't a valid meta item
+                    if let Some(ident) = &field.ident {
+                        insert("#[forward]", &mut forward_field, ident.clone());
+                    } else {
+                        panic!("#[forward] is not supported on unnamed fields");
+                    }
+                    forward_with = Some(parse_forward_with(attr));
+
+                    insert(
+                        "#[body]/#[query_params]/#[forward]",
+                        &mut field_kind,
+                        FieldKind::Forward,
+                    );
+                    continue;
+                }
+
                 let meta = attr.parse_meta().unwrap();
                 match &meta {
                     Meta::Word(ident) if ident == "body" => {
@@ -199,7 +379,8 @@ impl VariantData {
                             FieldKind::Body,
                         );
                     }
-                    Meta::Word(ident) if ident == "query_params" => {
+                    _ if meta.name() == "query_params" => {
+                        query_params_strict = parse_query_params_strict(&meta);
                         if let Some(ident) = &field.ident {
                             insert("#[query_params]", &mut query_params_field, ident.clone());
                         } else {
@@ -261,6 +442,18 @@ impl VariantData {
             }
         }
 
+        // Many clients and proxies drop the body of `GET` and `HEAD` requests, so a `#[body]` on
+        // such a variant almost never works as intended
+        let only_get_or_head = routes
+            .iter()
+            .all(|route| route.method == "GET" || route.method == "HEAD");
+        if body_field.is_some() && !routes.is_empty() && only_get_or_head && !allow_body_on_get {
+            panic!(
+                "`{}` only has GET/HEAD routes, whose request bodies are dropped by many clients and proxies (add `#[allow_body_on_get]` to use #[body] anyway)",
+                ast.ident
+            );
+        }
+
         // Given a field name, returns the whole `Field`
         let fld = |ident: Ident| -> Field {
             ast.fields
@@ -272,10 +465,14 @@ impl VariantData {
 
         Self {
             name: ast.ident.clone(),
+            doc: doc_string(ast.attrs),
             routes,
+            allow_body_on_get,
             body_field: body_field.map(fld),
             forward_field: forward_field.map(fld),
+            forward_with,
             query_params_field: query_params_field.map(fld),
+            query_params_strict,
             guard_fields: guard_fields.into_iter().map(fld).collect(),
             path_segment_fields: path_segment_fields.into_iter().map(fld).collect(),
         }
@@ -290,6 +487,24 @@ impl VariantData {
         &self.name
     }
 
+    /// Returns the doc comment of the variant (or struct).
+    pub fn doc(&self) -> Option<&String> {
+        self.doc.as_ref()
+    }
+
+    /// Returns the names and doc comments of the documented placeholder and `#[query_params]`
+    /// fields, in that order.
+    pub fn param_docs(&self) -> Vec<(String, String)> {
+        self.path_segment_fields
+            .iter()
+            .chain(&self.query_params_field)
+            .filter_map(|fld| {
+                let doc = doc_string(&fld.attrs)?;
+                Some((fld.ident.as_ref().unwrap().to_string(), doc))
+            })
+            .collect()
+    }
+
     /// Returns the parsed route attributes attached to this variant.
     ///
     /// This might be empty, in which case the custom derive should just ignore
@@ -298,6 +513,18 @@ impl VariantData {
         &self.routes
     }
 
+    /// Returns the `#[body]` field if it is decoded from `DELETE` requests, which many clients and
+    /// proxies send without their body.
+    ///
+    /// The custom derive emits a warning for such a field, unless `#[allow_body_on_get]` was
+    /// specified.
+    pub fn body_on_delete(&self) -> Option<&Ident> {
+        if self.allow_body_on_get || !self.routes.iter().any(|route| route.method == "DELETE") {
+            return None;
+        }
+        self.body_field()
+    }
+
     /// Returns the name of the field marked with `#[body]`.
     ///
     /// If this is `None`, the body is ignored.
@@ -316,6 +543,13 @@ impl VariantData {
             .map(|fld| fld.ident.as_ref().unwrap())
     }
 
+    /// Returns the function that creates the context of the forwarded type.
+    ///
+    /// If this is `None`, the context is passed to the forwarded type unchanged.
+    pub fn forward_with(&self) -> Option<&Path> {
+        self.forward_with.as_ref()
+    }
+
     /// Returns the name of the field marked with `#[query_params]`.
     ///
     /// If this is `None`, the query parameters are ignored.
@@ -325,6 +559,11 @@ impl VariantData {
             .map(|fld| fld.ident.as_ref().unwrap())
     }
 
+    /// Returns whether the `#[query_params]` field rejects unknown query parameters.
+    pub fn query_params_strict(&self) -> bool {
+        self.query_params_strict
+    }
+
     /// Returns the list of fields that store guard objects.
     pub fn guard_fields(&self) -> &[Field] {
         &self.guard_fields
@@ -360,6 +599,8 @@ pub struct Route {
     /// Name of the associated constant on `http::Method`.
     method: Ident,
     path: RoutePath,
+    /// Whether this is a `HEAD` route implied by a `GET` route.
+    implied: bool,
 }
 
 impl Route {
@@ -371,6 +612,7 @@ impl Route {
                 Self {
                     method: Ident::new(&method.to_string().to_uppercase(), Span::call_site()),
                     path: RoutePath::parse(path),
+                    implied: false,
                 }
             }
             _ => {
@@ -382,6 +624,28 @@ impl Route {
     pub fn placeholders(&self) -> &[Ident] {
         &self.path.placeholders
     }
+
+    /// Returns the name of the associated constant on `http::Method`.
+    pub fn method(&self) -> &Ident {
+        &self.method
+    }
+
+    /// Returns the segments making up the path.
+    ///
+    /// If empty, this is the asterisk path `*`.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.path.segments
+    }
+
+    /// Returns the path as written in the attribute.
+    pub fn path(&self) -> &str {
+        &self.path.raw
+    }
+
+    /// Returns whether this is a `HEAD` route implied by a `GET` route.
+    pub fn is_implied(&self) -> bool {
+        self.implied
+    }
 }
 
 impl fmt::Display for Route {
@@ -498,9 +762,9 @@ impl RoutePath {
     pub fn find_overlap(&self, other: &Self) -> Option<String> {
         use self::PathSegment::*;
 
-        if self.segments.is_empty() {
-            // self is "*"
-            if other.segments.is_empty() {
+        if self.segments.is_empty() || other.segments.is_empty() {
+            // "*" only overlaps with itself
+            if self.segments.is_empty() && other.segments.is_empty() {
                 return Some("*".into());
             } else {
                 return None;
@@ -706,6 +970,7 @@ impl PathMap {
                     let head = Route {
                         method: Ident::new("HEAD", Span::call_site()),
                         path: route.path.clone(),
+                        implied: true,
                     };
                     if !any_head_overlaps_with(&head) {
                         implied_head_routes.push((variant.clone(), head));
@@ -718,6 +983,22 @@ impl PathMap {
             this.add_route(variant, route);
         }
 
+        // Sort the paths by template and the routes by method, so that the generated route table
+        // and regex indices don't depend on the order the routes were declared in. Routes don't
+        // overlap, so this doesn't affect which route a request matches.
+        for route_map in this.regex_map.values_mut() {
+            route_map
+                .sort_by(|method1, _, method2, _| method1.to_string().cmp(&method2.to_string()));
+        }
+        let template = |route_map: &IndexMap<Ident, (VariantData, Route)>| {
+            route_map
+                .values()
+                .map(|(_, route)| route.path.raw.clone())
+                .min()
+        };
+        this.regex_map
+            .sort_by(|_, routes1, _, routes2| template(routes1).cmp(&template(routes2)));
+
         this
     }
 
@@ -770,6 +1051,14 @@ impl<'a> PathInfo<'a> {
     pub fn method_map(&self) -> impl Iterator<Item = (&'a Ident, &'a VariantData)> {
         self.method_map.iter().map(|(k, v)| (k, &v.0))
     }
+
+    /// Returns an iterator over the routes (including implied `HEAD` routes) using this path, and
+    /// the variants they construct.
+    pub fn routes(&self) -> impl Iterator<Item = (&'a VariantData, &'a Route)> {
+        self.method_map
+            .values()
+            .map(|(variant, route)| (variant, route))
+    }
 }
 
 fn insert<T>(name: &str, slot: &mut Option<T>, value: T) {
@@ -842,6 +1131,7 @@ mod tests {
         assert_eq!(intersect!("/lit/bla", "/blit/{b...}"), None);
         assert_eq!(intersect!("*", "/{b...}"), None);
         assert_eq!(intersect!("*", "/"), None);
+        assert_eq!(intersect!("/{b...}", "*"), None);
         assert_eq!(intersect!("*", "*"), Some("*"));
     }
 }
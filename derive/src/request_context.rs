@@ -1,55 +1,47 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{Attribute, Data, Index, Meta};
+use std::collections::HashMap;
+use syn::{
+    Attribute, Data, GenericArgument, Ident, Index, Meta, NestedMeta, PathArguments, Type, TypePath,
+};
 use synstructure::Structure;
 
 pub fn derive_request_context(s: Structure<'_>) -> TokenStream {
     deny_attr("as_ref", &s.ast().attrs);
+    let mut forwards = false;
     let additional_impls = match &s.ast().data {
         Data::Struct(st) => {
             let mut impls = Vec::new();
+            let mut forwarded = None;
+            // Maps the (stringified) types we generate `AsRef` impls for to the
+            // field providing them.
+            let mut provided = HashMap::new();
             for (index, field) in st.fields.iter().enumerate() {
-                let as_ref_count = field
+                let display_name = || {
+                    if let Some(name) = &field.ident {
+                        format!("field `{}`", name)
+                    } else {
+                        format!("field of type `{}`", field.ty.clone().into_token_stream())
+                    }
+                };
+                let attrs = field
                     .attrs
                     .iter()
-                    .filter(|attr| match attr.parse_meta() {
-                        Ok(ref meta) if meta.name() == "as_ref" => {
-                            if let Meta::Word(_) = meta {
-                                true
-                            } else {
-                                if let Some(field) = &field.ident {
-                                    panic!(
-                                        "invalid syntax for #[as_ref] attribute on field `{}`",
-                                        field
-                                    );
-                                } else {
-                                    panic!(
-                                        "invalid syntax for #[as_ref] attribute on field of type `{}`",
-                                        field.ty.clone().into_token_stream()
-                                    );
-                                }
-                            }
-                        }
-                        _ => false,
+                    .filter_map(|attr| match attr.parse_meta() {
+                        Ok(ref meta) if meta.name() == "as_ref" => match parse_as_ref(meta) {
+                            Some(attr) => Some(attr),
+                            None => panic!(
+                                "invalid syntax for #[as_ref] attribute on {}",
+                                display_name()
+                            ),
+                        },
+                        _ => None,
                     })
-                    .count();
-
-                match as_ref_count {
-                    0 => {} // no AsRef impl generated
-                    1 => {
-                        let ty = &field.ty;
-                        let field_name = if let Some(name) = &field.ident {
-                            quote!(#name)
-                        } else {
-                            let index = Index::from(index);
-                            quote!(#index)
-                        };
-                        impls.push(s.gen_impl(quote! {
-                            gen impl AsRef<#ty> for @Self {
-                                fn as_ref(&self) -> &#ty { &self.#field_name }
-                            }
-                        }));
-                    }
+                    .collect::<Vec<_>>();
+
+                let attr = match attrs.len() {
+                    0 => continue, // no AsRef impl generated
+                    1 => attrs[0],
                     _ => {
                         let name = if let Some(name) = &field.ident {
                             name.into_token_stream()
@@ -61,8 +53,59 @@ pub fn derive_request_context(s: Structure<'_>) -> TokenStream {
                             name
                         )
                     }
+                };
+
+                let ty = &field.ty;
+                let field_name = if let Some(name) = &field.ident {
+                    quote!(#name)
+                } else {
+                    let index = Index::from(index);
+                    quote!(#index)
+                };
+                match attr {
+                    AsRefAttr::Field { deref } => {
+                        provide(&mut provided, ty, display_name());
+                        impls.push(s.gen_impl(quote! {
+                            gen impl AsRef<#ty> for @Self {
+                                fn as_ref(&self) -> &#ty { &self.#field_name }
+                            }
+                        }));
+
+                        if let Some((wrapper, inner)) = smart_pointer(ty).filter(|_| deref) {
+                            provide(
+                                &mut provided,
+                                inner,
+                                format!("{} (through `{}`)", display_name(), wrapper),
+                            );
+                            impls.push(s.gen_impl(quote! {
+                                gen impl AsRef<#inner> for @Self {
+                                    fn as_ref(&self) -> &#inner { &*self.#field_name }
+                                }
+                            }));
+                        }
+                    }
+                    AsRefAttr::Forward => {
+                        if let Some(previous) = forwarded.replace(display_name()) {
+                            panic!(
+                                "#[as_ref(forward)] is only permitted on one field (found on {} and {})",
+                                previous,
+                                display_name()
+                            );
+                        }
+                        impls.push(s.gen_impl(quote! {
+                            gen impl<__HyperdriveT: ?Sized> AsRef<__HyperdriveT> for @Self
+                            where
+                                #ty: AsRef<__HyperdriveT>,
+                            {
+                                fn as_ref(&self) -> &__HyperdriveT {
+                                    AsRef::<__HyperdriveT>::as_ref(&self.#field_name)
+                                }
+                            }
+                        }));
+                    }
                 }
             }
+            forwards = forwarded.is_some();
             impls
         }
         Data::Enum(e) => {
@@ -83,14 +126,20 @@ pub fn derive_request_context(s: Structure<'_>) -> TokenStream {
         }
     };
 
-    let asref_nocontext = s.gen_impl(quote!(
-        extern crate hyperdrive;
-        use hyperdrive::NoContext;
+    // A forwarded context already provides `AsRef<NoContext>` (and a second
+    // impl would overlap with the blanket one).
+    let asref_nocontext = if forwards {
+        quote!()
+    } else {
+        s.gen_impl(quote!(
+            extern crate hyperdrive;
+            use hyperdrive::NoContext;
 
-        gen impl AsRef<NoContext> for @Self {
-            fn as_ref(&self) -> &NoContext { &NoContext }
-        }
-    ));
+            gen impl AsRef<NoContext> for @Self {
+                fn as_ref(&self) -> &NoContext { &NoContext }
+            }
+        ))
+    };
     let asref_self = s.gen_impl(quote!(
         gen impl AsRef<Self> for @Self {
             fn as_ref(&self) -> &Self { self }
@@ -114,6 +163,72 @@ pub fn derive_request_context(s: Structure<'_>) -> TokenStream {
     )
 }
 
+/// The forms of the `#[as_ref]` field attribute.
+#[derive(Copy, Clone)]
+enum AsRefAttr {
+    /// `#[as_ref]`: implement `AsRef` for the field's type.
+    ///
+    /// If `deref` is `true` and the type is an `Arc`, `Rc` or `Box`, `AsRef`
+    /// is also implemented for the pointee. `#[as_ref(no_deref)]` disables
+    /// this.
+    Field { deref: bool },
+    /// `#[as_ref(forward)]`: implement every `AsRef` the field's type
+    /// implements.
+    Forward,
+}
+
+/// Parses an `#[as_ref]` attribute, returning `None` if its syntax is invalid.
+fn parse_as_ref(meta: &Meta) -> Option<AsRefAttr> {
+    match meta {
+        Meta::Word(_) => Some(AsRefAttr::Field { deref: true }),
+        Meta::List(list) if list.nested.len() == 1 => match &list.nested[0] {
+            NestedMeta::Meta(Meta::Word(word)) if word == "forward" => Some(AsRefAttr::Forward),
+            NestedMeta::Meta(Meta::Word(word)) if word == "no_deref" => {
+                Some(AsRefAttr::Field { deref: false })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// If `ty` is an `Arc<T>`, `Rc<T>` or `Box<T>`, returns the name of the
+/// pointer type and `T`.
+fn smart_pointer(ty: &Type) -> Option<(&Ident, &Type)> {
+    let path = match ty {
+        Type::Path(TypePath { qself: None, path }) => path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?.into_value();
+    if segment.ident != "Arc" && segment.ident != "Rc" && segment.ident != "Box" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some((&segment.ident, inner)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Records that an `AsRef<ty>` impl is generated for the field described by
+/// `source`, panicking if another field already provides it.
+///
+/// The types are only compared syntactically, so this can't catch every
+/// conflict, but it produces a clearer error than the compiler for the common
+/// cases.
+fn provide(provided: &mut HashMap<String, String>, ty: &Type, source: String) {
+    let key = ty.into_token_stream().to_string();
+    if let Some(previous) = provided.get(&key) {
+        panic!(
+            "conflicting #[as_ref] attributes: `AsRef<{}>` is provided by {} and {}",
+            key, previous, source
+        );
+    }
+    provided.insert(key, source);
+}
+
 fn deny_attr<'a, I>(name: &str, attrs: I)
 where
     I: IntoIterator<Item = &'a Attribute>,
@@ -223,4 +338,45 @@ mod tests {
             struct MyStruct(#[as_ref] #[as_ref] u8);
         }
     }
+
+    #[test]
+    #[should_panic(expected = "invalid syntax for #[as_ref] attribute on field `field`")]
+    fn invalid5_unknown_option() {
+        expand! {
+            struct MyStruct {
+                #[as_ref(backward)]
+                field: u8,
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "#[as_ref(forward)] is only permitted on one field (found on field `a` and field `b`)"
+    )]
+    fn invalid6_multiple_forwards() {
+        expand! {
+            struct MyStruct {
+                #[as_ref(forward)]
+                a: Context,
+                #[as_ref(forward)]
+                b: Context,
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "conflicting #[as_ref] attributes: `AsRef<Pool>` is provided by field `shared` (through `Arc`) and field `pool`"
+    )]
+    fn invalid7_deref_conflict() {
+        expand! {
+            struct MyStruct {
+                #[as_ref]
+                shared: Arc<Pool>,
+                #[as_ref]
+                pool: Pool,
+            }
+        }
+    }
 }
@@ -1,22 +1,39 @@
 //! Provides wrappers that deserialize a request body.
 //!
-//! All wrappers provided here implement [`FromBody`].
+//! All wrappers provided here implement [`FromBody`]. They also implement
+//! [`ToBody`], which serializes them into the body of an outgoing request.
+//!
+//! If the body can't be deserialized, the wrappers fail with an [`Error`] of
+//! kind [`ErrorKind::MalformedBody`], which results in a `400 Bad Request`
+//! response.
+//!
+//! Before reading the body, the wrappers check that the request headers
+//! describe it unambiguously, like [`guard::RequestSanity`] does. Requests
+//! failing these checks are rejected with an error of kind
+//! `ErrorKind::MalformedRequest` or `ErrorKind::UnsupportedTransferEncoding`.
 //!
 //! Note that the wrapper types will not inspect the `Content-Type` header and
 //! instead assume that the body has the right format. You can add a [`Guard`]
 //! if you want to reject requests that don't specify the right type.
 //!
 //! The wrappers will also ignore the `Content-Length` header. If you want to
-//! limit the maximum request size, you can do that in a [`Guard`] as well.
+//! limit the maximum request size, you can use [`ServiceExt::limit_body`], or
+//! do that in a [`Guard`] as well.
 //!
 //! [`FromBody`]: ../trait.FromBody.html
+//! [`ToBody`]: ../trait.ToBody.html
+//! [`Error`]: ../struct.Error.html
+//! [`ErrorKind::MalformedBody`]: ../enum.ErrorKind.html#variant.MalformedBody
 //! [`Guard`]: ../trait.Guard.html
+//! [`guard::RequestSanity`]: ../guard/struct.RequestSanity.html
+//! [`ServiceExt::limit_body`]: ../service/trait.ServiceExt.html#tymethod.limit_body
 
 // TODO: Add many more types here and make them optional
 
-use crate::{BoxedError, DefaultFuture, FromBody, NoContext};
-use futures::{Future, Stream};
-use serde::de::DeserializeOwned;
+use crate::{guard::check_request, BoxedError, DefaultFuture, Error, FromBody, NoContext, ToBody};
+use futures::{Future, IntoFuture, Stream};
+use http::header::{HeaderValue, CONTENT_TYPE};
+use serde::{de::DeserializeOwned, Serialize};
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
@@ -100,19 +117,30 @@ impl<T: DeserializeOwned + Send + 'static> FromBody for HtmlForm<T> {
     type Result = DefaultFuture<Self, BoxedError>;
 
     fn from_body(
-        _request: &Arc<http::Request<()>>,
+        request: &Arc<http::Request<()>>,
         body: hyper::Body,
         _context: &Self::Context,
     ) -> Self::Result {
-        Box::new(body.concat2().map_err(Into::into).and_then(|body| {
+        Box::new(read_body(request, body).and_then(|body| {
             match serde_urlencoded::from_bytes(&body) {
                 Ok(t) => Ok(HtmlForm(t)),
-                Err(e) => Err(e.into()),
+                Err(e) => Err(Error::malformed_body(e).into()),
             }
         }))
     }
 }
 
+impl<T: DeserializeOwned + Serialize + Send + 'static> ToBody for HtmlForm<T> {
+    fn to_body(&self, request: &mut http::Request<hyper::Body>) {
+        let body = serde_urlencoded::to_string(&self.0).expect("couldn't serialize form body");
+        request.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        *request.body_mut() = body.into();
+    }
+}
+
 deref!(HtmlForm<T>);
 
 /// Decodes a JSON-encoded request body.
@@ -174,17 +202,38 @@ impl<T: DeserializeOwned + Send + 'static> FromBody for Json<T> {
     type Result = DefaultFuture<Self, BoxedError>;
 
     fn from_body(
-        _request: &Arc<http::Request<()>>,
+        request: &Arc<http::Request<()>>,
         body: hyper::Body,
         _context: &Self::Context,
     ) -> Self::Result {
-        Box::new(body.concat2().map_err(Into::into).and_then(|body| {
-            match serde_json::from_slice(&body) {
+        Box::new(
+            read_body(request, body).and_then(|body| match serde_json::from_slice(&body) {
                 Ok(t) => Ok(Json(t)),
-                Err(e) => Err(e.into()),
-            }
-        }))
+                Err(e) => Err(Error::malformed_body(e).into()),
+            }),
+        )
+    }
+}
+
+impl<T: DeserializeOwned + Serialize + Send + 'static> ToBody for Json<T> {
+    fn to_body(&self, request: &mut http::Request<hyper::Body>) {
+        let body = serde_json::to_vec(&self.0).expect("couldn't serialize JSON body");
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        *request.body_mut() = body.into();
     }
 }
 
 deref!(Json<T>);
+
+/// Reads the whole request `body`, after checking that the headers of
+/// `request` describe it unambiguously.
+fn read_body(
+    request: &http::Request<()>,
+    body: hyper::Body,
+) -> impl Future<Item = hyper::Chunk, Error = BoxedError> {
+    check_request(request.headers())
+        .into_future()
+        .and_then(move |()| body.concat2().map_err(Into::into))
+}
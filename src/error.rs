@@ -1,7 +1,21 @@
-use crate::{BoxedError, DefaultFuture};
+use crate::{
+    lazy_static,
+    response::{IntoResponse, Json},
+    BoxedError, DefaultFuture,
+};
 use futures::IntoFuture;
-use http::StatusCode;
-use std::{borrow::Cow, error, fmt};
+use http::{header::HeaderName, HeaderMap, HeaderValue, HttpTryFrom, Response, StatusCode};
+use hyper::Body;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    collections::BTreeMap,
+    error, fmt, iter,
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
 
 /// The error type used by the Hyperdrive library.
 ///
@@ -16,6 +30,110 @@ pub struct Error {
     /// methods.
     allowed_methods: Cow<'static, [&'static http::Method]>,
     source: Option<BoxedError>,
+    /// Additional headers to include in the response.
+    headers: HeaderMap,
+    kind: ErrorKind,
+    /// The path placeholder or query parameter that failed to decode.
+    field: Option<Cow<'static, str>>,
+    /// A description of the error meant for the client.
+    message: Option<String>,
+    /// The method and path of the request that caused the error.
+    request: Option<(http::Method, String)>,
+    /// Why the query parameters couldn't be deserialized.
+    query_param: Option<QueryParamError>,
+    /// Route paths similar to the requested one, for `NoMatchingRoute` errors.
+    suggestions: Vec<&'static str>,
+    /// How long the request took, for `Timeout` errors.
+    timeout: Option<TimeoutInfo>,
+}
+
+/// Describes what caused an [`Error`].
+///
+/// This allows treating errors produced by the routing logic differently from
+/// errors produced by guards or body decoders, which may use the same status
+/// code.
+///
+/// [`Error`]: struct.Error.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The request path did not match any route (`404 Not Found`).
+    NoMatchingRoute,
+    /// The request path matched a route, but not with the request method (`405
+    /// Method Not Allowed`).
+    WrongMethod,
+    /// The request body exceeded the maximum allowed size (`413 Payload Too
+    /// Large`).
+    BodyTooLarge,
+    /// A path segment matched by a placeholder could not be parsed (`404 Not
+    /// Found`).
+    PathSegment,
+    /// The query parameters could not be deserialized (`400 Bad Request`).
+    QueryParam,
+    /// The request body could not be decoded, eg. because it contains invalid
+    /// JSON (`400 Bad Request`).
+    MalformedBody,
+    /// The request body is in a format or encoding that is not supported
+    /// (`415 Unsupported Media Type`).
+    UnsupportedMediaType,
+    /// The client took too long to send the request body (`408 Request
+    /// Timeout`).
+    BodyReadTimeout,
+    /// The request headers are malformed or ambiguous in a way that prevents
+    /// reading the request reliably, eg. because it has both a
+    /// `Content-Length` and a `Transfer-Encoding` header (`400 Bad Request`).
+    ///
+    /// See [`guard::RequestSanity`] for the checked conditions.
+    ///
+    /// [`guard::RequestSanity`]: guard/struct.RequestSanity.html
+    MalformedRequest,
+    /// The request body uses a transfer coding that the server doesn't
+    /// implement, eg. `Transfer-Encoding: gzip, chunked` (`501 Not
+    /// Implemented`).
+    UnsupportedTransferEncoding,
+    /// The server gave up processing the request because it took too long,
+    /// eg. when the deadline set with [`ServiceExt::timeout`] expired (`503
+    /// Service Unavailable` or `504 Gateway Timeout`).
+    ///
+    /// [`ServiceExt::timeout`]: service/trait.ServiceExt.html#tymethod.timeout
+    Timeout,
+    /// An error created using [`Error::with_status`] or [`ErrorBuilder`], eg.
+    /// by a guard.
+    ///
+    /// [`Error::with_status`]: struct.Error.html#method.with_status
+    /// [`ErrorBuilder`]: struct.ErrorBuilder.html
+    Custom,
+    /// Any other error, eg. created by a guard.
+    Other,
+}
+
+impl ErrorKind {
+    /// Returns the name of this kind in `snake_case` (eg. `no_matching_route`).
+    ///
+    /// The names are stable and suitable for use in logs or metrics. This is
+    /// also how `ErrorKind` implements `Display`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::NoMatchingRoute => "no_matching_route",
+            ErrorKind::WrongMethod => "wrong_method",
+            ErrorKind::BodyTooLarge => "body_too_large",
+            ErrorKind::PathSegment => "path_segment",
+            ErrorKind::QueryParam => "query_param",
+            ErrorKind::MalformedBody => "malformed_body",
+            ErrorKind::UnsupportedMediaType => "unsupported_media_type",
+            ErrorKind::BodyReadTimeout => "body_read_timeout",
+            ErrorKind::MalformedRequest => "malformed_request",
+            ErrorKind::UnsupportedTransferEncoding => "unsupported_transfer_encoding",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Custom => "custom",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl Error {
@@ -23,6 +141,7 @@ impl Error {
         status: StatusCode,
         allowed_methods: Cow<'static, [&'static http::Method]>,
         source: Option<BoxedError>,
+        kind: ErrorKind,
     ) -> Self {
         assert!(
             status.is_client_error() || status.is_server_error(),
@@ -34,6 +153,14 @@ impl Error {
             status,
             allowed_methods,
             source,
+            headers: HeaderMap::new(),
+            kind,
+            field: None,
+            message: None,
+            request: None,
+            query_param: None,
+            suggestions: Vec::new(),
+            timeout: None,
         }
     }
 
@@ -44,7 +171,7 @@ impl Error {
     /// This will panic when called with a `status` that does not indicate a
     /// client or server error.
     pub fn from_status(status: StatusCode) -> Self {
-        Self::new(status, (&[][..]).into(), None)
+        Self::new(status, (&[][..]).into(), None, ErrorKind::Other)
     }
 
     /// Creates an error from an HTTP error code and an underlying error that
@@ -93,7 +220,90 @@ impl Error {
     where
         S: Into<BoxedError>,
     {
-        Self::new(status, (&[][..]).into(), Some(source.into()))
+        Self::new(
+            status,
+            (&[][..]).into(),
+            Some(source.into()),
+            ErrorKind::Other,
+        )
+    }
+
+    /// Creates an error with an arbitrary status code and a message describing
+    /// the error to the client.
+    ///
+    /// This is useful for guards that need to fail with a status that isn't
+    /// covered by the other constructors (eg. `409 Conflict` or `423 Locked`).
+    /// The [`kind`] of the returned error is `ErrorKind::Custom`, and the
+    /// message can be retrieved using [`message`]. Use [`Error::builder`] to
+    /// also add headers or a source error.
+    ///
+    /// # Panics
+    ///
+    /// This will panic when called with a `status` that does not indicate a
+    /// client or server error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{Error, ErrorKind};
+    /// use http::StatusCode;
+    ///
+    /// let error = Error::with_status(StatusCode::PAYMENT_REQUIRED, "your trial has ended");
+    ///
+    /// assert_eq!(error.http_status(), StatusCode::PAYMENT_REQUIRED);
+    /// assert_eq!(error.kind(), ErrorKind::Custom);
+    /// assert_eq!(error.message(), Some("your trial has ended"));
+    /// assert_eq!(error.to_string(), "402 Payment Required: your trial has ended");
+    /// ```
+    ///
+    /// [`kind`]: #method.kind
+    /// [`message`]: #method.message
+    /// [`Error::builder`]: #method.builder
+    pub fn with_status<M>(status: StatusCode, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self {
+            message: Some(message.into()),
+            ..Self::new(status, (&[][..]).into(), None, ErrorKind::Custom)
+        }
+    }
+
+    /// Creates an [`ErrorBuilder`] for an error with the given status code.
+    ///
+    /// The [`kind`] of the built error is `ErrorKind::Custom`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic when called with a `status` that does not indicate a
+    /// client or server error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::{header, StatusCode};
+    ///
+    /// # fn main() -> Result<(), http::Error> {
+    /// let error = Error::builder(StatusCode::CONFLICT)
+    ///     .message("a user with this name already exists")
+    ///     .header(header::LOCATION, "/users/42")
+    ///     .build()?;
+    ///
+    /// let response = error.response();
+    /// assert_eq!(response.status(), StatusCode::CONFLICT);
+    /// assert_eq!(response.headers()["Location"], "/users/42");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ErrorBuilder`]: struct.ErrorBuilder.html
+    /// [`kind`]: #method.kind
+    pub fn builder(status: StatusCode) -> ErrorBuilder {
+        ErrorBuilder {
+            error: Self::new(status, (&[][..]).into(), None, ErrorKind::Custom),
+            header_error: None,
+        }
     }
 
     /// Creates an error with status code `405 Method Not Allowed` and includes
@@ -111,121 +321,1553 @@ impl Error {
     ///
     /// * **`allowed_methods`**: The list of allowed HTTP methods for the path
     ///   in the request. This can be empty, but usually should contain at least
-    ///   one method.
+    ///   one method. Duplicates are removed, and the methods are sorted in a
+    ///   canonical order: `GET`, `HEAD`, `POST`, `PUT`, `PATCH`, `DELETE`,
+    ///   `OPTIONS`, followed by all other methods in alphabetical order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::Method;
+    ///
+    /// let error = Error::wrong_method(vec![&Method::POST, &Method::HEAD, &Method::GET, &Method::POST]);
+    ///
+    /// assert_eq!(
+    ///     error.allowed_methods(),
+    ///     Some(&[&Method::GET, &Method::HEAD, &Method::POST][..])
+    /// );
+    /// assert_eq!(error.response().headers()["Allow"], "GET, HEAD, POST");
+    /// ```
     ///
     /// [required]: https://tools.ietf.org/html/rfc7231#section-6.5.5
     pub fn wrong_method<M>(allowed_methods: M) -> Self
     where
         M: Into<Cow<'static, [&'static http::Method]>>,
     {
-        Self::new(StatusCode::METHOD_NOT_ALLOWED, allowed_methods.into(), None)
+        let mut allowed_methods = allowed_methods.into();
+        let is_canonical = allowed_methods
+            .windows(2)
+            .all(|pair| method_order(pair[0]) < method_order(pair[1]));
+        if !is_canonical {
+            let mut methods = allowed_methods.into_owned();
+            methods.sort_by_key(|method| method_order(method));
+            methods.dedup();
+            allowed_methods = methods.into();
+        }
+
+        Self::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            allowed_methods,
+            None,
+            ErrorKind::WrongMethod,
+        )
     }
 
-    /// Returns the HTTP status code that describes this error.
-    pub fn http_status(&self) -> StatusCode {
-        self.status
+    /// Creates a `404 Not Found` error indicating that the request path did
+    /// not match any route.
+    ///
+    /// This is called by the code generated by `#[derive(FromRequest)]`. The
+    /// [`kind`] of the returned error is `ErrorKind::NoMatchingRoute`.
+    ///
+    /// [`kind`]: #method.kind
+    pub fn no_matching_route() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            (&[][..]).into(),
+            None,
+            ErrorKind::NoMatchingRoute,
+        )
     }
 
-    /// Returns the original cause of this error.
+    /// Creates a `413 Payload Too Large` error indicating that the request body
+    /// exceeded the maximum allowed size.
     ///
-    /// If no source was provided when `self` was created, returns `None`.
+    /// The [`kind`] of the returned error is `ErrorKind::BodyTooLarge`.
     ///
-    /// This is equivalent to the method `std::error::Error::source`, but does
-    /// not require the caller to import the `std::error::Error` trait.
-    pub fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match &self.source {
-            Some(source) => Some(&**source),
-            None => None,
-        }
+    /// [`kind`]: #method.kind
+    pub fn body_too_large<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::BodyTooLarge, source.into())
     }
 
-    /// Creates an HTTP response for indicating this error to the client.
+    /// Creates a `400 Bad Request` error indicating that the request body could
+    /// not be decoded.
     ///
-    /// No body will be provided (hence the `()` body type), but the caller can
-    /// `map` the result to supply one.
+    /// This is used by the wrappers in the [`body`] module when deserializing
+    /// the body fails, with the deserialization error as the `source`. The
+    /// [`kind`] of the returned error is `ErrorKind::MalformedBody`.
     ///
-    /// # Example
+    /// # Examples
     ///
-    /// Call `map` on the response to supply your own HTTP payload:
+    /// ```
+    /// use hyperdrive::{FromRequest, Error, ErrorKind, NoContext, body::Json};
+    /// use http::StatusCode;
     ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[post("/")]
+    ///     Index {
+    ///         #[body]
+    ///         data: Json<Vec<u32>>,
+    ///     },
+    /// }
+    ///
+    /// let request = http::Request::post("/").body("[1, 2".into()).unwrap();
+    /// let error = Route::from_request_sync(request, NoContext).err().unwrap();
+    /// let error = error.downcast::<Error>().unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::MalformedBody);
+    /// assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+    /// assert!(error.source().unwrap().is::<serde_json::Error>());
     /// ```
-    /// # use hyperdrive::Error;
+    ///
+    /// [`body`]: body/index.html
+    /// [`kind`]: #method.kind
+    pub fn malformed_body<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::MalformedBody, source.into())
+    }
+
+    /// Creates a `415 Unsupported Media Type` error indicating that the request
+    /// body is in an unsupported format or encoding.
+    ///
+    /// The [`kind`] of the returned error is `ErrorKind::UnsupportedMediaType`.
+    ///
+    /// [`kind`]: #method.kind
+    pub fn unsupported_media_type<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::UnsupportedMediaType, source.into())
+    }
+
+    /// Creates a `408 Request Timeout` error indicating that the client took
+    /// too long to send the request body.
+    ///
+    /// The [`kind`] of the returned error is `ErrorKind::BodyReadTimeout`.
+    ///
+    /// [`kind`]: #method.kind
+    pub fn body_read_timeout<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::BodyReadTimeout, source.into())
+    }
+
+    /// Creates a `400 Bad Request` error indicating that the request headers
+    /// are malformed or ambiguous.
+    ///
+    /// The [`kind`] of the returned error is `ErrorKind::MalformedRequest`.
+    ///
+    /// [`kind`]: #method.kind
+    pub fn malformed_request<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::MalformedRequest, source.into())
+    }
+
+    /// Creates a `501 Not Implemented` error indicating that the request body
+    /// uses an unsupported transfer coding.
+    ///
+    /// The [`kind`] of the returned error is
+    /// `ErrorKind::UnsupportedTransferEncoding`.
+    ///
+    /// [`kind`]: #method.kind
+    pub fn unsupported_transfer_encoding<S>(source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        Self::with_kind(ErrorKind::UnsupportedTransferEncoding, source.into())
+    }
+
+    /// Creates a `503 Service Unavailable` error indicating that the server
+    /// gave up processing the request after `elapsed`, since it exceeded the
+    /// time `budget` it was given.
+    ///
+    /// The [`kind`] of the returned error is `ErrorKind::Timeout`, and the
+    /// durations are available through [`timeout_info`]. This is the error
+    /// [`ServiceExt::timeout`] responds with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{Error, ErrorKind};
     /// use http::StatusCode;
-    /// use hyper::Body;
+    /// use std::time::Duration;
     ///
-    /// let error = Error::from_status(StatusCode::NOT_FOUND);
-    /// let response = error.response()
-    ///     .map(|()| Body::from("oh no!"));
+    /// let error = Error::timeout(Duration::from_millis(1003), Duration::from_secs(1));
+    /// assert_eq!(error.kind(), ErrorKind::Timeout);
+    /// assert_eq!(error.http_status(), StatusCode::SERVICE_UNAVAILABLE);
+    /// assert_eq!(error.timeout_info().unwrap().budget(), Duration::from_secs(1));
     /// ```
     ///
-    /// Call `map` on the response to provide information about the underlying
-    /// error:
+    /// [`kind`]: #method.kind
+    /// [`timeout_info`]: #method.timeout_info
+    /// [`ServiceExt::timeout`]: service/trait.ServiceExt.html#tymethod.timeout
+    pub fn timeout(elapsed: Duration, budget: Duration) -> Self {
+        Self::timeout_with_status(StatusCode::SERVICE_UNAVAILABLE, elapsed, budget)
+    }
+
+    /// Creates a `504 Gateway Timeout` error indicating that an upstream
+    /// server didn't respond within the time `budget`.
+    ///
+    /// This works like [`Error::timeout`], but is meant for gateways and
+    /// proxies.
+    ///
+    /// [`Error::timeout`]: #method.timeout
+    pub fn upstream_timeout(elapsed: Duration, budget: Duration) -> Self {
+        Self::timeout_with_status(StatusCode::GATEWAY_TIMEOUT, elapsed, budget)
+    }
+
+    pub(crate) fn timeout_with_status(
+        status: StatusCode,
+        elapsed: Duration,
+        budget: Duration,
+    ) -> Self {
+        let mut error = Self::new(status, (&[][..]).into(), None, ErrorKind::Timeout);
+        error.timeout = Some(TimeoutInfo { elapsed, budget });
+        error
+    }
+
+    /// Creates an error of the given `kind`, caused by the path placeholder or
+    /// query parameter `field`.
+    ///
+    /// This is called by the code generated by `#[derive(FromRequest)]` when a
+    /// path segment can't be parsed (with kind `ErrorKind::PathSegment`) or the
+    /// query parameters can't be deserialized (with kind
+    /// `ErrorKind::QueryParam`). The name of the field can be retrieved using
+    /// [`field`] and is included when the error is displayed.
+    ///
+    /// The status code is determined by `kind`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic when called with `ErrorKind::NoMatchingRoute`,
+    /// `ErrorKind::WrongMethod`, `ErrorKind::Custom` or `ErrorKind::Other`,
+    /// since those don't determine a status code.
+    ///
+    /// # Examples
     ///
     /// ```
-    /// use hyperdrive::Error;
+    /// use hyperdrive::{FromRequest, Error, ErrorKind, NoContext};
     /// use http::StatusCode;
     /// use hyper::Body;
     ///
-    /// let err = Error::with_source(StatusCode::NOT_FOUND, "file not found");
-    /// let response = err.response()
-    ///     .map(|()| Body::from(err.to_string()));
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/users/{id}")]
+    ///     User { id: u32 },
+    /// }
     ///
-    /// // response now has a body containing "file not found"
+    /// let request = http::Request::get("/users/me").body(Body::empty()).unwrap();
+    /// let error = Route::from_request_sync(request, NoContext).err().unwrap();
+    /// let error = error.downcast::<Error>().unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::PathSegment);
+    /// assert_eq!(error.http_status(), StatusCode::NOT_FOUND);
+    /// assert_eq!(error.field(), Some("id"));
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "404 Not Found: invalid `id`: invalid digit found in string",
+    /// );
     /// ```
-    pub fn response(&self) -> http::Response<()> {
-        let mut builder = http::Response::builder();
-        builder.status(self.http_status());
+    ///
+    /// [`field`]: #method.field
+    pub fn with_source_and_field<F, S>(kind: ErrorKind, field: F, source: S) -> Self
+    where
+        F: Into<Cow<'static, str>>,
+        S: Into<BoxedError>,
+    {
+        let mut error = Self::with_kind(kind, source.into());
+        error.field = Some(field.into());
+        error
+    }
 
-        if self.status == StatusCode::METHOD_NOT_ALLOWED {
-            // The spec mandates that "405 Method Not Allowed" always sends an
-            // `Allow` header (it may be empty, though).
-            let allowed = self
-                .allowed_methods
-                .iter()
-                .map(|method| method.as_str())
-                .collect::<Vec<_>>()
-                .join(", ");
-            builder.header(http::header::ALLOW, allowed);
-        }
+    /// Creates an `ErrorKind::QueryParam` error from the error returned by
+    /// `query::from_str` when deserializing `query` as a `T`.
+    ///
+    /// This is used by the code generated by `#[derive(FromRequest)]`. The
+    /// offending parameter is determined from the message of `source`, or by
+    /// leaving out one parameter after another until deserialization succeeds.
+    #[doc(hidden)] // not part of public API
+    pub fn query_param<T, S>(query: &str, source: S) -> Self
+    where
+        T: DeserializeOwned,
+        S: std::error::Error + Send + Sync + 'static,
+    {
+        let message = source.to_string();
+        let query_param = diagnose_query::<T>(query, &message);
+        let field = match &query_param {
+            Some(QueryParamError::Missing(name)) => Some(name.clone()),
+            Some(QueryParamError::Invalid { name, .. }) => Some(name.clone()),
+            Some(QueryParamError::Unknown(names)) => Some(names[0].clone()),
+            None => mentioned_field(&message),
+        };
 
-        builder
-            .body(())
-            .expect("could not build HTTP response for error")
+        let mut error = match field {
+            Some(field) => Self::with_source_and_field(ErrorKind::QueryParam, field, source),
+            None => Self::with_kind(ErrorKind::QueryParam, source.into()),
+        };
+        error.query_param = query_param;
+        error
     }
 
-    /// Turns this error into a generic boxed future compatible with the output
-    /// of `#[derive(FromRequest)]`.
+    fn with_kind(kind: ErrorKind, source: BoxedError) -> Self {
+        let status = match kind {
+            ErrorKind::PathSegment => StatusCode::NOT_FOUND,
+            ErrorKind::QueryParam | ErrorKind::MalformedBody => StatusCode::BAD_REQUEST,
+            ErrorKind::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorKind::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorKind::BodyReadTimeout => StatusCode::REQUEST_TIMEOUT,
+            ErrorKind::MalformedRequest => StatusCode::BAD_REQUEST,
+            ErrorKind::UnsupportedTransferEncoding => StatusCode::NOT_IMPLEMENTED,
+            ErrorKind::NoMatchingRoute
+            | ErrorKind::WrongMethod
+            | ErrorKind::Timeout
+            | ErrorKind::Custom
+            | ErrorKind::Other => {
+                panic!("`ErrorKind::{:?}` does not determine a status code", kind)
+            }
+        };
+        Self::new(status, (&[][..]).into(), Some(source), kind)
+    }
+
+    /// Records the method and path of the request that caused this error.
     ///
-    /// This is used by the code generated by `#[derive(FromRequest)]`.
-    #[doc(hidden)] // not part of public API
-    pub fn into_future<T: Send + 'static>(self) -> DefaultFuture<T, BoxedError> {
-        Box::new(Err(BoxedError::from(self)).into_future())
+    /// The code generated by `#[derive(FromRequest)]` attaches the request to
+    /// the routing errors it creates (those of kind
+    /// `ErrorKind::NoMatchingRoute` and `ErrorKind::WrongMethod`). The
+    /// recorded request is included in the output of the alternate `Display`
+    /// format (`{:#}`), which makes logged errors easier to trace back. The
+    /// alternate format also starts with the [`kind`] of the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, Error, NoContext};
+    /// use http::Method;
+    /// use hyper::Body;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let request = http::Request::get("/nope?page=2").body(Body::empty()).unwrap();
+    /// let error = Route::from_request_sync(request, NoContext).err().unwrap();
+    /// let error = error.downcast::<Error>().unwrap();
+    ///
+    /// assert_eq!(error.request_method(), Some(&Method::GET));
+    /// assert_eq!(error.request_path(), Some("/nope"));
+    /// assert_eq!(error.to_string(), "404 Not Found");
+    /// assert_eq!(format!("{:#}", error), "no_matching_route: 404 Not Found (GET /nope)");
+    /// ```
+    ///
+    /// [`kind`]: #method.kind
+    pub fn with_request<P>(mut self, method: http::Method, path: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.request = Some((method, path.into()));
+        self
     }
 
-    /// If `self` is a `405 Method Not Allowed` error, returns the list of
-    /// allowed methods.
+    /// Returns the method of the request that caused this error, if it was
+    /// recorded using [`with_request`].
     ///
-    /// Returns `None` if `self` is a different kind of error.
-    pub fn allowed_methods(&self) -> Option<&[&'static http::Method]> {
-        if self.status == StatusCode::METHOD_NOT_ALLOWED {
-            Some(&self.allowed_methods)
-        } else {
-            None
+    /// [`with_request`]: #method.with_request
+    pub fn request_method(&self) -> Option<&http::Method> {
+        match &self.request {
+            Some((method, _)) => Some(method),
+            None => None,
         }
     }
-}
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.source {
-            None => write!(f, "{}", self.status),
-            Some(source) => write!(f, "{}: {}", self.status, source),
+    /// Returns the path of the request that caused this error, if it was
+    /// recorded using [`with_request`].
+    ///
+    /// [`with_request`]: #method.with_request
+    pub fn request_path(&self) -> Option<&str> {
+        match &self.request {
+            Some((_, path)) => Some(path),
+            None => None,
         }
     }
-}
 
-impl error::Error for Error {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        self.source()
+    /// Returns how long the request took and how long it was allowed to take,
+    /// if this is a `Timeout` error.
+    pub fn timeout_info(&self) -> Option<TimeoutInfo> {
+        self.timeout
+    }
+
+    /// Returns the paths of routes similar to the requested one.
+    ///
+    /// These are only found for `NoMatchingRoute` errors produced by services
+    /// that were created with `suggest_routes(true)`, and are ordered from
+    /// most to least similar. At most three paths are suggested.
+    pub fn route_suggestions(&self) -> &[&'static str] {
+        &self.suggestions
+    }
+
+    /// Suggests the route paths among `routes` that are most similar to the
+    /// recorded request path.
+    pub(crate) fn suggest_routes(&mut self, routes: &[&'static str]) {
+        let path = match &self.request {
+            Some((_, path)) => path,
+            None => return,
+        };
+        let mut scored = routes
+            .iter()
+            .map(|route| (path_distance(path, route), *route))
+            .filter(|(distance, _)| *distance <= 1.0 / 3.0)
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        self.suggestions = scored.into_iter().take(3).map(|(_, route)| route).collect();
+    }
+
+    /// Returns the HTTP status code that describes this error.
+    pub fn http_status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns what caused this error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, Error, ErrorKind, NoContext};
+    /// use hyper::Body;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let request = http::Request::get("/nope").body(Body::empty()).unwrap();
+    /// let error = Route::from_request_sync(request, NoContext).err().unwrap();
+    /// let error = error.downcast::<Error>().unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::NoMatchingRoute);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the name of the path placeholder or query parameter that
+    /// caused this error.
+    ///
+    /// Returns `None` if the error isn't related to a specific field, or the
+    /// field is unknown (eg. because the query string is malformed as a
+    /// whole).
+    pub fn field(&self) -> Option<&str> {
+        match &self.field {
+            Some(field) => Some(field),
+            None => None,
+        }
+    }
+
+    /// Returns whether a query parameter was missing or invalid, if this error
+    /// has kind `ErrorKind::QueryParam` and the parameter could be determined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, Error, NoContext, QueryParamError};
+    /// use hyper::Body;
+    /// # use serde::Deserialize;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Routes {
+    ///     #[get("/users")]
+    ///     UserList {
+    ///         #[query_params]
+    ///         pagination: Pagination,
+    ///     },
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Pagination {
+    ///     start_id: u32,
+    ///     count: u32,
+    /// }
+    ///
+    /// let request = http::Request::get("/users?start_id=42&count=ten")
+    ///     .body(Body::empty())
+    ///     .unwrap();
+    /// let error = Routes::from_request_sync(request, NoContext).err().unwrap();
+    /// let error = error.downcast::<Error>().unwrap();
+    ///
+    /// assert_eq!(
+    ///     error.query_param_error(),
+    ///     Some(&QueryParamError::Invalid {
+    ///         name: "count".to_string(),
+    ///         value: "ten".to_string(),
+    ///     }),
+    /// );
+    /// ```
+    pub fn query_param_error(&self) -> Option<&QueryParamError> {
+        self.query_param.as_ref()
+    }
+
+    /// Returns the message describing this error to the client.
+    ///
+    /// Only errors created using [`Error::with_status`] or an [`ErrorBuilder`]
+    /// can have a message.
+    ///
+    /// [`Error::with_status`]: #method.with_status
+    /// [`ErrorBuilder`]: struct.ErrorBuilder.html
+    pub fn message(&self) -> Option<&str> {
+        match &self.message {
+            Some(message) => Some(message),
+            None => None,
+        }
+    }
+
+    /// Returns the original cause of this error.
+    ///
+    /// If no source was provided when `self` was created, returns `None`.
+    ///
+    /// This is equivalent to the method `std::error::Error::source`, but does
+    /// not require the caller to import the `std::error::Error` trait.
+    pub fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.source {
+            Some(source) => Some(&**source),
+            None => None,
+        }
+    }
+
+    /// Returns an iterator over `self` and the chain of its sources.
+    ///
+    /// The first item is `self`, followed by its [`source`], the source of
+    /// that error, and so on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{Error, ErrorKind};
+    ///
+    /// let source = "abc".parse::<u32>().unwrap_err();
+    /// let error = Error::with_source_and_field(ErrorKind::PathSegment, "id", source);
+    ///
+    /// let chain = error.iter_chain().map(|e| e.to_string()).collect::<Vec<_>>();
+    /// assert_eq!(chain, vec![
+    ///     "404 Not Found: invalid `id`: invalid digit found in string",
+    ///     "invalid digit found in string",
+    /// ]);
+    /// ```
+    ///
+    /// [`source`]: #method.source
+    pub fn iter_chain(&self) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+        iter::successors(Some(self as &(dyn std::error::Error + 'static)), |error| {
+            error.source()
+        })
+    }
+
+    /// Returns the additional headers that will be included in the response
+    /// created by [`Error::response`].
+    ///
+    /// [`Error::response`]: #method.response
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns a mutable reference to the additional headers that will be
+    /// included in the response created by [`Error::response`].
+    ///
+    /// This can be used to send headers that are required for some errors,
+    /// such as `Retry-After` for `503 Service Unavailable` or
+    /// `WWW-Authenticate` for `401 Unauthorized`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::{header, HeaderValue, StatusCode};
+    ///
+    /// let mut error = Error::from_status(StatusCode::SERVICE_UNAVAILABLE);
+    /// error.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(30));
+    ///
+    /// let response = error.response();
+    /// assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    /// ```
+    ///
+    /// [`Error::response`]: #method.response
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Sets a header to include in the response created for this error,
+    /// replacing any previous values of the header.
+    ///
+    /// This is a shorthand for inserting into [`headers_mut`] that accepts
+    /// anything convertible to a header name and value, like
+    /// `http::response::Builder::header` does. It returns `self` to allow
+    /// setting several headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` or `value` can't be converted to a valid
+    /// header name or value. The headers of `self` are left unchanged in that
+    /// case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::StatusCode;
+    ///
+    /// # fn main() -> Result<(), http::Error> {
+    /// let mut error = Error::from_status(StatusCode::TOO_MANY_REQUESTS);
+    /// error
+    ///     .insert_header("Retry-After", "30")?
+    ///     .insert_header("X-RateLimit-Remaining", "0")?;
+    ///
+    /// let response = error.response();
+    /// assert_eq!(response.headers()["Retry-After"], "30");
+    /// assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+    ///
+    /// assert!(error.insert_header("Retry-After", "\n").is_err());
+    /// assert!(error.insert_header("Retry After", "30").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`headers_mut`]: #method.headers_mut
+    pub fn insert_header<K, V>(&mut self, name: K, value: V) -> Result<&mut Self, http::Error>
+    where
+        HeaderName: HttpTryFrom<K>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        let name = HeaderName::try_from(name).map_err(Into::into)?;
+        let value = HeaderValue::try_from(value).map_err(Into::into)?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Creates an HTTP response for indicating this error to the client.
+    ///
+    /// No body will be provided (hence the `()` body type), but the caller can
+    /// `map` the result to supply one. Headers added via [`headers_mut`] are
+    /// included in the response. The [`ErrorKind`] of the error (and its
+    /// [`TimeoutInfo`], if any) is stored in the response extensions, which
+    /// allows adapters like [`ServiceExt::metrics`] to tell what caused an
+    /// error response.
+    ///
+    /// # Example
+    ///
+    /// Call `map` on the response to supply your own HTTP payload:
+    ///
+    /// ```
+    /// # use hyperdrive::Error;
+    /// use http::StatusCode;
+    /// use hyper::Body;
+    ///
+    /// let error = Error::from_status(StatusCode::NOT_FOUND);
+    /// let response = error.response()
+    ///     .map(|()| Body::from("oh no!"));
+    /// ```
+    ///
+    /// Call `map` on the response to provide information about the underlying
+    /// error:
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::StatusCode;
+    /// use hyper::Body;
+    ///
+    /// let err = Error::with_source(StatusCode::NOT_FOUND, "file not found");
+    /// let response = err.response()
+    ///     .map(|()| Body::from(err.to_string()));
+    ///
+    /// // response now has a body containing "file not found"
+    /// ```
+    ///
+    /// [`headers_mut`]: #method.headers_mut
+    /// [`ErrorKind`]: enum.ErrorKind.html
+    /// [`TimeoutInfo`]: struct.TimeoutInfo.html
+    /// [`ServiceExt::metrics`]: service/trait.ServiceExt.html#tymethod.metrics
+    pub fn response(&self) -> http::Response<()> {
+        let mut builder = http::Response::builder();
+        builder.status(self.http_status());
+
+        if self.status == StatusCode::METHOD_NOT_ALLOWED {
+            // The spec mandates that "405 Method Not Allowed" always sends an
+            // `Allow` header (it may be empty, though).
+            builder.header(http::header::ALLOW, self.allow_header_value());
+        }
+
+        let mut response = builder
+            .body(())
+            .expect("could not build HTTP response for error");
+        response.headers_mut().extend(self.headers.clone());
+        self.insert_extensions(response.extensions_mut());
+        response
+    }
+
+    /// Stores the kind and timing information of `self` in `extensions`.
+    fn insert_extensions(&self, extensions: &mut http::Extensions) {
+        extensions.insert(self.kind);
+        if let Some(timeout) = self.timeout {
+            extensions.insert(timeout);
+        }
+    }
+
+    /// Creates an HTTP response for this error, with a body rendered by `f`.
+    ///
+    /// `f` is called with `self` and can return anything implementing
+    /// [`IntoResponse`], eg. a [`Json`] value describing the error. The status
+    /// code and headers of the resulting response are the ones created by
+    /// [`response`], so only the body and any additional headers (like
+    /// `Content-Type`) are taken from the value returned by `f`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyperdrive::{Error, ErrorKind, response::Json};
+    /// use http::StatusCode;
+    /// use serde_json::json;
+    ///
+    /// let error = Error::from_status(StatusCode::BAD_REQUEST);
+    /// let response = error.response_with(|error| {
+    ///     let kind = match error.kind() {
+    ///         ErrorKind::NoMatchingRoute => "not_found",
+    ///         ErrorKind::WrongMethod => "method_not_allowed",
+    ///         _ => "bad_request",
+    ///     };
+    ///     Json(json!({ "error": kind }))
+    /// });
+    ///
+    /// assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// assert_eq!(response.headers()["Content-Type"], "application/json");
+    /// ```
+    ///
+    /// [`IntoResponse`]: response/trait.IntoResponse.html
+    /// [`Json`]: response/struct.Json.html
+    /// [`response`]: #method.response
+    pub fn response_with<F, T, B>(&self, f: F) -> http::Response<B>
+    where
+        F: FnOnce(&Self) -> T,
+        T: IntoResponse<B>,
+    {
+        let (parts, ()) = self.response().into_parts();
+        let mut response = f(self).into_response();
+        *response.status_mut() = parts.status;
+        let headers = response.headers_mut();
+        for name in parts.headers.keys() {
+            headers.remove(name);
+        }
+        for (name, value) in &parts.headers {
+            headers.append(name, value.clone());
+        }
+        self.insert_extensions(response.extensions_mut());
+        response
+    }
+
+    /// Turns this error into a generic boxed future compatible with the output
+    /// of `#[derive(FromRequest)]`.
+    ///
+    /// This is used by the code generated by `#[derive(FromRequest)]`.
+    #[doc(hidden)] // not part of public API
+    pub fn into_future<T: Send + 'static>(self) -> DefaultFuture<T, BoxedError> {
+        Box::new(Err(BoxedError::from(self)).into_future())
+    }
+
+    /// If `self` is a `405 Method Not Allowed` error, returns the list of
+    /// allowed methods.
+    ///
+    /// Returns `None` if `self` is a different kind of error.
+    pub fn allowed_methods(&self) -> Option<&[&'static http::Method]> {
+        if self.status == StatusCode::METHOD_NOT_ALLOWED {
+            Some(&self.allowed_methods)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of the `Allow` header listing the allowed methods.
+    ///
+    /// The methods are separated by `, ` and appear in the order described
+    /// in [`wrong_method`]. The value is empty if `self` isn't a
+    /// `405 Method Not Allowed` error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::Error;
+    /// use http::Method;
+    ///
+    /// let error = Error::wrong_method(vec![&Method::DELETE, &Method::GET]);
+    /// assert_eq!(error.allow_header_value(), "GET, DELETE");
+    /// ```
+    ///
+    /// [`wrong_method`]: #method.wrong_method
+    pub fn allow_header_value(&self) -> HeaderValue {
+        let allowed = self
+            .allowed_methods()
+            .unwrap_or(&[])
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&allowed).expect("method names are valid header values")
+    }
+}
+
+/// Returns the sort key placing `method` in the canonical order of allowed
+/// methods.
+pub(crate) fn method_order(method: &http::Method) -> (u8, &str) {
+    let rank = match *method {
+        http::Method::GET => 0,
+        http::Method::HEAD => 1,
+        http::Method::POST => 2,
+        http::Method::PUT => 3,
+        http::Method::PATCH => 4,
+        http::Method::DELETE => 5,
+        http::Method::OPTIONS => 6,
+        _ => 7,
+    };
+    (rank, method.as_str())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}: ", self.kind)?;
+        }
+        write!(f, "{}", self.status)?;
+        if f.alternate() {
+            if let Some((method, path)) = &self.request {
+                write!(f, " ({} {})", method, path)?;
+            }
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let Some(field) = &self.field {
+            match &self.query_param {
+                Some(QueryParamError::Missing(_)) => write!(f, ": missing `{}`", field)?,
+                Some(QueryParamError::Unknown(_)) => write!(f, ": unknown `{}`", field)?,
+                _ => write!(f, ": invalid `{}`", field)?,
+            }
+        }
+        if !self.suggestions.is_empty() {
+            let suggestions = self
+                .suggestions
+                .iter()
+                .map(|route| format!("`{}`", route))
+                .collect::<Vec<_>>();
+            write!(f, ": did you mean {}?", suggestions.join(", "))?;
+        }
+        if let Some(timeout) = &self.timeout {
+            write!(
+                f,
+                ": timed out after {:?} (limit {:?})",
+                timeout.elapsed, timeout.budget
+            )?;
+        }
+        match &self.source {
+            None => Ok(()),
+            Some(source) => write!(f, ": {}", source),
+        }
+    }
+}
+
+/// Computes how different a request `path` is from the path template of a
+/// route, from 0 (matching) to 1 (completely different).
+///
+/// This is the edit distance between the path segments, where placeholders
+/// match any segment and replacing a segment costs the normalized edit
+/// distance between the two segments.
+fn path_distance(path: &str, route: &str) -> f64 {
+    let path = path.split('/').skip(1).collect::<Vec<_>>();
+    let route = route.split('/').skip(1).collect::<Vec<_>>();
+    let distance = edit_distance(&path, &route, |segment, template| {
+        if template.starts_with('{') && template.ends_with('}') {
+            0.0
+        } else {
+            let segment = segment.chars().collect::<Vec<_>>();
+            let template = template.chars().collect::<Vec<_>>();
+            let len = segment.len().max(template.len()).max(1);
+            edit_distance(&segment, &template, |a, b| if a == b { 0.0 } else { 1.0 }) / len as f64
+        }
+    });
+    distance / path.len().max(route.len()).max(1) as f64
+}
+
+/// The Levenshtein distance between `a` and `b`, using `substitute` to compute
+/// the cost of replacing an element.
+fn edit_distance<A, B, F>(a: &[A], b: &[B], substitute: F) -> f64
+where
+    F: Fn(&A, &B) -> f64,
+{
+    let mut previous = (0..=b.len()).map(|i| i as f64).collect::<Vec<_>>();
+    for (i, a) in a.iter().enumerate() {
+        let mut current = vec![i as f64 + 1.0];
+        for (j, b) in b.iter().enumerate() {
+            let cost = (previous[j] + substitute(a, b))
+                .min(previous[j + 1] + 1.0)
+                .min(current[j] + 1.0);
+            current.push(cost);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// How long a request that timed out took, and how long it was allowed to take.
+///
+/// Returned by [`Error::timeout_info`], and stored in the extensions of the
+/// response created for a `Timeout` error.
+///
+/// [`Error::timeout_info`]: struct.Error.html#method.timeout_info
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeoutInfo {
+    elapsed: Duration,
+    budget: Duration,
+}
+
+impl TimeoutInfo {
+    /// Returns the time that passed before the request was given up on.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the time the request was allowed to take.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+/// Describes why the query parameters of a request couldn't be deserialized.
+///
+/// Returned by [`Error::query_param_error`].
+///
+/// [`Error::query_param_error`]: struct.Error.html#method.query_param_error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryParamError {
+    /// The required parameter with the given name is missing.
+    Missing(String),
+    /// The parameter `name` has a `value` that couldn't be deserialized.
+    Invalid {
+        /// The name of the parameter.
+        name: String,
+        /// The (decoded) value of the parameter.
+        value: String,
+    },
+    /// The parameters with the given names aren't accepted.
+    ///
+    /// This is only returned for `#[query_params(strict)]` fields, and for
+    /// types using `#[serde(deny_unknown_fields)]`.
+    Unknown(Vec<String>),
+}
+
+impl QueryParamError {
+    /// Returns the name of the missing or invalid parameter.
+    ///
+    /// For `Unknown` parameters, this is the name of the first one.
+    pub fn name(&self) -> &str {
+        match self {
+            QueryParamError::Missing(name) => name,
+            QueryParamError::Invalid { name, .. } => name,
+            QueryParamError::Unknown(names) => &names[0],
+        }
+    }
+}
+
+/// Determines which parameter in `query` caused deserializing it as a `T` to
+/// fail with `message`.
+fn diagnose_query<T: DeserializeOwned>(query: &str, message: &str) -> Option<QueryParamError> {
+    if message.starts_with("missing field `") {
+        return mentioned_field(message).map(QueryParamError::Missing);
+    }
+    if message.starts_with("unknown field") {
+        // serde lists the expected fields after the unknown one
+        let unknown = message.split(", expected").next().unwrap_or(message);
+        let names = unknown
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        return if names.is_empty() {
+            None
+        } else {
+            Some(QueryParamError::Unknown(names))
+        };
+    }
+
+    // Leave out one parameter after another. If that fixes the error (or turns
+    // it into a missing field error for that parameter), the parameter is
+    // invalid.
+    let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query).ok()?;
+    for (i, (name, value)) in pairs.iter().enumerate() {
+        let rest = pairs
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, pair)| pair)
+            .collect::<Vec<_>>();
+        let rest = serde_urlencoded::to_string(&rest).ok()?;
+        let culprit = match crate::query::from_str::<T>(&rest) {
+            Ok(_) => true,
+            Err(e) => e.to_string() == format!("missing field `{}`", name),
+        };
+        if culprit {
+            return Some(QueryParamError::Invalid {
+                name: name.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    None
+}
+
+/// Extracts the field name from a serde error message that mentions one (eg.
+/// ``missing field `name` ``).
+fn mentioned_field(message: &str) -> Option<String> {
+    let start = message.find("field `")? + "field `".len();
+    let len = message[start..].find('`')?;
+    Some(message[start..start + len].to_string())
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source()
+    }
+}
+
+/// Creates the response for an error with an empty body.
+///
+/// This is equivalent to calling [`Error::response`] and using an empty body.
+///
+/// [`Error::response`]: struct.Error.html#method.response
+impl From<Error> for Response<Body> {
+    fn from(error: Error) -> Self {
+        error.response().map(|()| Body::empty())
+    }
+}
+
+/// Turns `error` into a response if it is a [`hyperdrive::Error`], or
+/// propagates it otherwise.
+///
+/// This is the logic used by [`AsyncService`] and [`SyncService`] to respond to
+/// errors (unless they are configured otherwise), and is useful when
+/// implementing a `Service` manually. Errors caused by a `hyperdrive::Error`
+/// (eg. a `hyper::Error` produced when reading a body that was limited using
+/// [`ServiceExt::limit_body`]) are turned into a response as well. The response
+/// is created as described in [`Error::response`] and has an empty body.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{map_boxed_error, BoxedError, Error};
+/// use http::StatusCode;
+///
+/// let error: BoxedError = Error::from_status(StatusCode::FORBIDDEN).into();
+/// let response = map_boxed_error(error).unwrap();
+/// assert_eq!(response.status(), StatusCode::FORBIDDEN);
+///
+/// let error: BoxedError = "database is on fire".into();
+/// assert!(map_boxed_error(error).is_err());
+/// ```
+///
+/// [`hyperdrive::Error`]: struct.Error.html
+/// [`AsyncService`]: service/struct.AsyncService.html
+/// [`SyncService`]: service/struct.SyncService.html
+/// [`ServiceExt::limit_body`]: service/trait.ServiceExt.html#tymethod.limit_body
+/// [`Error::response`]: struct.Error.html#method.response
+pub fn map_boxed_error(error: BoxedError) -> Result<Response<Body>, BoxedError> {
+    match as_hyperdrive_error(&error) {
+        Some(our_error) => Ok(our_error.response().map(|()| Body::empty())),
+        None => Err(error),
+    }
+}
+
+/// Returns the [`hyperdrive::Error`] contained in `err`, if any.
+///
+/// The chain of sources of `err` is searched as well. This finds errors that
+/// caused another one, such as a `hyperdrive::Error` returned from a body
+/// stream, which is wrapped in a `hyper::Error` when the body is read.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{as_hyperdrive_error, BoxedError, Error, ErrorKind};
+///
+/// let error: BoxedError = Error::no_matching_route().into();
+/// assert_eq!(as_hyperdrive_error(&error).unwrap().kind(), ErrorKind::NoMatchingRoute);
+///
+/// let error: BoxedError = "connection reset".into();
+/// assert!(as_hyperdrive_error(&error).is_none());
+/// ```
+///
+/// [`hyperdrive::Error`]: struct.Error.html
+pub fn as_hyperdrive_error(err: &BoxedError) -> Option<&Error> {
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(&**err);
+    while let Some(err) = current {
+        if let Some(our_error) = err.downcast_ref::<Error>() {
+            return Some(our_error);
+        }
+        current = err.source();
+    }
+    None
+}
+
+/// Returns the HTTP status code `err` would be answered with, if it is known.
+///
+/// Like the services, this searches the chain of sources of `err` for an error
+/// of a [`ResponseError`] type registered with the `response_error` method of
+/// any service, and returns its [`ResponseError::status`]. Otherwise, this is
+/// the status of the [`hyperdrive::Error`] found by [`as_hyperdrive_error`].
+/// All other errors return `None`; by default, services drop the connection
+/// when they encounter such an error.
+///
+/// [`ResponseError`]: trait.ResponseError.html
+/// [`ResponseError::status`]: trait.ResponseError.html#method.status
+/// [`hyperdrive::Error`]: struct.Error.html
+/// [`as_hyperdrive_error`]: fn.as_hyperdrive_error.html
+pub fn status_of(err: &BoxedError) -> Option<StatusCode> {
+    let response_errors = RESPONSE_ERRORS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    response_errors
+        .iter()
+        .find_map(|&(_, status)| status(err))
+        .or_else(|| as_hyperdrive_error(err).map(Error::http_status))
+}
+
+/// Returns whether `err` is known to be a client error (`4xx`).
+///
+/// See [`status_of`] for how the status of an error is determined.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{is_client_error, is_server_error, BoxedError, Error};
+/// use http::StatusCode;
+///
+/// let error: BoxedError = Error::from_status(StatusCode::FORBIDDEN).into();
+/// assert!(is_client_error(&error));
+/// assert!(!is_server_error(&error));
+///
+/// // Unknown errors are neither
+/// let error: BoxedError = "database is on fire".into();
+/// assert!(!is_client_error(&error));
+/// assert!(!is_server_error(&error));
+/// ```
+///
+/// [`status_of`]: fn.status_of.html
+pub fn is_client_error(err: &BoxedError) -> bool {
+    match status_of(err) {
+        Some(status) => status.is_client_error(),
+        None => false,
+    }
+}
+
+/// Returns whether `err` is known to be a server error (`5xx`).
+///
+/// See [`status_of`] for how the status of an error is determined.
+///
+/// [`status_of`]: fn.status_of.html
+pub fn is_server_error(err: &BoxedError) -> bool {
+    match status_of(err) {
+        Some(status) => status.is_server_error(),
+        None => false,
+    }
+}
+
+/// An application-defined error type that knows how to render itself as an
+/// HTTP response.
+///
+/// Guards and [`FromBody`] implementations can fail with any error type. By
+/// default, [`AsyncService`] and [`SyncService`] only know how to respond to
+/// [`hyperdrive::Error`]s and drop the connection on any other error. Errors
+/// implementing `ResponseError` can be registered with the `response_error`
+/// method of the services, which makes them respond with
+/// [`ResponseError::response`] instead.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{ResponseError, http::StatusCode};
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct QuotaExceeded;
+///
+/// impl fmt::Display for QuotaExceeded {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         f.write_str("request quota exceeded")
+///     }
+/// }
+///
+/// impl std::error::Error for QuotaExceeded {}
+///
+/// impl ResponseError for QuotaExceeded {
+///     fn status(&self) -> StatusCode {
+///         StatusCode::PAYMENT_REQUIRED
+///     }
+/// }
+///
+/// assert_eq!(QuotaExceeded.response().status(), StatusCode::PAYMENT_REQUIRED);
+/// ```
+///
+/// [`FromBody`]: trait.FromBody.html
+/// [`AsyncService`]: service/struct.AsyncService.html
+/// [`SyncService`]: service/struct.SyncService.html
+/// [`hyperdrive::Error`]: struct.Error.html
+/// [`ResponseError::response`]: #method.response
+pub trait ResponseError: error::Error + Send + Sync + 'static {
+    /// Returns the HTTP status code to respond with.
+    ///
+    /// Defaults to `500 Internal Server Error`.
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    /// Creates the response to send to the client.
+    ///
+    /// By default, the response has the status code returned by
+    /// [`status`](#method.status) and an empty body.
+    fn response(&self) -> Response<Body> {
+        self.status().into_response()
+    }
+}
+
+/// Returns the status of the `ResponseError` in the chain of sources of a
+/// `BoxedError`, if there is one of the right type.
+type ResponseErrorStatus = fn(&BoxedError) -> Option<StatusCode>;
+
+lazy_static! {
+    /// The `ResponseError` types registered with any service, which are
+    /// consulted by `status_of`.
+    static ref RESPONSE_ERRORS: RwLock<Vec<(TypeId, ResponseErrorStatus)>> =
+        RwLock::new(Vec::new());
+}
+
+/// Makes `status_of` consult `E`, once it is registered with a service.
+pub(crate) fn register_response_error<E: ResponseError>() {
+    let mut response_errors = RESPONSE_ERRORS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
+    let type_id = TypeId::of::<E>();
+    if response_errors.iter().all(|&(id, _)| id != type_id) {
+        response_errors.push((type_id, |err| find_response_error::<E>(err).map(E::status)));
+    }
+}
+
+/// Searches the chain of sources of `err` for an `E`.
+fn find_response_error<E: ResponseError>(err: &BoxedError) -> Option<&E> {
+    let mut current: Option<&(dyn error::Error + 'static)> = Some(&**err);
+    while let Some(err) = current {
+        if let Some(error) = err.downcast_ref::<E>() {
+            return Some(error);
+        }
+        current = err.source();
+    }
+    None
+}
+
+/// Searches the chain of sources of `err` for an `E` and renders it.
+pub(crate) fn render_response_error<E: ResponseError>(err: &BoxedError) -> Option<Response<Body>> {
+    find_response_error::<E>(err).map(E::response)
+}
+
+/// Builds an [`Error`] with a custom status code, message, headers and source.
+///
+/// Created by [`Error::builder`].
+///
+/// [`Error`]: struct.Error.html
+/// [`Error::builder`]: struct.Error.html#method.builder
+#[derive(Debug)]
+pub struct ErrorBuilder {
+    error: Error,
+    /// The first error that occurred while converting a header.
+    header_error: Option<http::Error>,
+}
+
+impl ErrorBuilder {
+    /// Sets the message describing the error to the client.
+    pub fn message<M>(mut self, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        self.error.message = Some(message.into());
+        self
+    }
+
+    /// Adds a header to the response created for the error.
+    ///
+    /// If `name` or `value` can't be converted to a valid header name or
+    /// value, the error is stored and returned by [`build`], like
+    /// `http::response::Builder` does.
+    ///
+    /// [`build`]: #method.build
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        if self.header_error.is_some() {
+            return self;
+        }
+
+        match (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            (Ok(name), Ok(value)) => {
+                self.error.headers.append(name, value);
+            }
+            (Err(e), _) => self.header_error = Some(e.into()),
+            (_, Err(e)) => self.header_error = Some(e.into()),
+        }
+        self
+    }
+
+    /// Sets the underlying error that caused this one.
+    pub fn source<S>(mut self, source: S) -> Self
+    where
+        S: Into<BoxedError>,
+    {
+        self.error.source = Some(source.into());
+        self
+    }
+
+    /// Creates the `Error`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error that occurred while converting a header passed
+    /// to [`header`].
+    ///
+    /// [`header`]: #method.header
+    pub fn build(self) -> Result<Error, http::Error> {
+        match self.header_error {
+            Some(e) => Err(e),
+            None => Ok(self.error),
+        }
+    }
+}
+
+/// An RFC 7807 "Problem Details" document describing an error.
+///
+/// A `ProblemJson` is sent as a JSON object with a `Content-Type` of
+/// `application/problem+json`. It can be created from an [`Error`] using
+/// [`from_error`], which makes it a ready-made renderer for the `error_body`
+/// hook of the service adapters (eg. [`SyncService::error_body`]):
+///
+/// * `type` is `"about:blank"`, and `title` is the reason phrase of the status
+///   code, as recommended by the RFC.
+/// * `status` is the numeric HTTP status code.
+/// * `detail` is the [`message`] of the error. Errors without a message use
+///   the message of their source instead, but only for client errors (`4xx`),
+///   since the source of a server error might reveal internals.
+/// * For `405 Method Not Allowed` errors, the `allowed_methods` extension
+///   member lists the methods supported by the resource.
+/// * If the error was caused by a path placeholder or query parameter, the
+///   `invalid_field` extension member contains its name (see [`Error::field`]).
+/// * If a query parameter was missing, invalid or unknown, the `query_param`
+///   extension member is an object with the `reason` (`"missing"`,
+///   `"invalid"` or `"unknown"`), the `name` and, for invalid parameters, the
+///   `value` of the parameter. For unknown parameters, `names` lists all of
+///   them (see [`Error::query_param_error`]).
+///
+/// Custom errors (eg. produced by a guard) are rendered like this as well, as
+/// long as they are converted to an [`Error`] (using [`Error::with_source`] to
+/// provide the `detail`). Handlers can also build a `ProblemJson` directly
+/// using [`new`] and return it, since it implements [`IntoResponse`].
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, ProblemJson, service::SyncService};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let service = SyncService::responding(|route: Route, _| match route {
+///     Route::Index => "Hello World!",
+/// }).error_body(ProblemJson::from_error);
+/// ```
+///
+/// Creating a document with a custom problem type:
+///
+/// ```
+/// use hyperdrive::{ProblemJson, response::IntoResponse};
+/// use futures::{Future, Stream};
+/// use http::StatusCode;
+///
+/// let problem = ProblemJson::new(StatusCode::FORBIDDEN)
+///     .type_uri("https://example.com/probs/out-of-credit")
+///     .title("You do not have enough credit.")
+///     .extension("balance", 30);
+///
+/// let response = problem.into_response();
+/// assert_eq!(response.status(), StatusCode::FORBIDDEN);
+/// assert_eq!(response.headers()["Content-Type"], "application/problem+json");
+///
+/// let body = response.into_body().concat2().wait().unwrap();
+/// assert_eq!(
+///     &*body,
+///     &br#"{"type":"https://example.com/probs/out-of-credit","title":"You do not have enough credit.","status":403,"balance":30}"#[..],
+/// );
+/// ```
+///
+/// [`Error`]: struct.Error.html
+/// [`Error::with_source`]: struct.Error.html#method.with_source
+/// [`Error::field`]: struct.Error.html#method.field
+/// [`Error::query_param_error`]: struct.Error.html#method.query_param_error
+/// [`message`]: struct.Error.html#method.message
+/// [`from_error`]: #method.from_error
+/// [`new`]: #method.new
+/// [`IntoResponse`]: response/trait.IntoResponse.html
+/// [`SyncService::error_body`]: service/struct.SyncService.html#method.error_body
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProblemJson {
+    #[serde(rename = "type")]
+    type_uri: String,
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(flatten)]
+    extensions: BTreeMap<String, Value>,
+}
+
+impl ProblemJson {
+    /// Creates a document of type `about:blank` for the given status code.
+    ///
+    /// The `title` is set to the reason phrase of `status`.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            type_uri: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            extensions: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a document describing `error`.
+    ///
+    /// The members that are populated are described in the documentation of
+    /// [`ProblemJson`].
+    ///
+    /// [`ProblemJson`]: struct.ProblemJson.html
+    pub fn from_error(error: &Error) -> Self {
+        let status = error.http_status();
+        let mut problem = Self::new(status);
+
+        if let Some(message) = error.message() {
+            problem.detail = Some(message.to_string());
+        } else if status.is_client_error() {
+            if let Some(source) = error.source() {
+                problem.detail = Some(source.to_string());
+            }
+        }
+
+        if let Some(field) = error.field() {
+            problem = problem.extension("invalid_field", field);
+        }
+
+        match error.query_param_error() {
+            Some(QueryParamError::Missing(name)) => {
+                problem =
+                    problem.extension("query_param", json!({ "reason": "missing", "name": name }));
+            }
+            Some(QueryParamError::Invalid { name, value }) => {
+                problem = problem.extension(
+                    "query_param",
+                    json!({ "reason": "invalid", "name": name, "value": value }),
+                );
+            }
+            Some(QueryParamError::Unknown(names)) => {
+                problem = problem.extension(
+                    "query_param",
+                    json!({ "reason": "unknown", "name": names[0], "names": names }),
+                );
+            }
+            None => {}
+        }
+
+        if !error.route_suggestions().is_empty() {
+            problem = problem.extension("suggestions", error.route_suggestions());
+        }
+
+        if let Some(allowed) = error.allowed_methods() {
+            let allowed = allowed
+                .iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<_>>();
+            problem = problem.extension("allowed_methods", allowed);
+        }
+
+        problem
+    }
+
+    /// Sets the URI identifying the problem type.
+    pub fn type_uri<S: Into<String>>(mut self, type_uri: S) -> Self {
+        self.type_uri = type_uri.into();
+        self
+    }
+
+    /// Sets the short, human-readable summary of the problem type.
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the human-readable explanation of this occurrence of the problem.
+    pub fn detail<S: Into<String>>(mut self, detail: S) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Adds an extension member to the document.
+    ///
+    /// Extension members are serialized next to the standard members. An
+    /// existing extension with the same name is replaced.
+    pub fn extension<K, V>(mut self, name: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.extensions.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the HTTP status code of the problem.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).expect("status was created from a valid StatusCode")
+    }
+
+    /// Returns the extension members of the document.
+    pub fn extensions(&self) -> &BTreeMap<String, Value> {
+        &self.extensions
+    }
+}
+
+impl IntoResponse for ProblemJson {
+    fn into_response(self) -> Response<Body> {
+        let status = self.status();
+        let mut response = Json(self).into_response();
+        if response.status() == StatusCode::OK {
+            *response.status_mut() = status;
+            response.headers_mut().insert(
+                http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/problem+json"),
+            );
+        }
+        response
     }
 }
@@ -0,0 +1,649 @@
+//! Serves static files from a directory.
+//!
+//! [`StaticFiles`] is a [`FromRequest`] implementor that maps the request path
+//! to a file below the root directory configured in its [`StaticFilesConfig`]
+//! context. It can be used as the `#[forward]` target of an application's
+//! routes (passing the configuration via `#[forward(with = function)]`), or be
+//! returned from the handler of a service mounted in a [`Router`], since it
+//! also implements [`IntoResponse`].
+//!
+//! # Examples
+//!
+//! ```
+//! use hyperdrive::{
+//!     FromRequest, RequestContext,
+//!     files::{StaticFiles, StaticFilesConfig},
+//!     response::IntoResponse,
+//!     service::{Router, SyncService},
+//! };
+//!
+//! #[derive(RequestContext)]
+//! struct Context {
+//!     assets: StaticFilesConfig,
+//! }
+//!
+//! fn assets(context: &Context) -> StaticFilesConfig {
+//!     context.assets.clone()
+//! }
+//!
+//! #[derive(FromRequest)]
+//! #[context(Context)]
+//! enum Route {
+//!     #[get("/api/status")]
+//!     Status,
+//!
+//!     // Everything else is looked up in the `public` directory
+//!     Assets {
+//!         #[forward(with = assets)]
+//!         files: StaticFiles,
+//!     },
+//! }
+//!
+//! let context = Context {
+//!     assets: StaticFilesConfig::new("public"),
+//! };
+//! let service = SyncService::with_context(|route: Route, _| match route {
+//!     Route::Status => "ok".into_response(),
+//!     Route::Assets { files } => files.into_response(),
+//! }, context);
+//!
+//! // Alternatively, serve `/docs/*` from the `docs` directory
+//! let docs = StaticFilesConfig::new("docs").index_file("index.html");
+//! let router = Router::new().mount(
+//!     "/docs",
+//!     SyncService::responding_with_context(|files: StaticFiles, _| files, docs),
+//! );
+//! ```
+//!
+//! [`StaticFiles`]: struct.StaticFiles.html
+//! [`StaticFilesConfig`]: struct.StaticFilesConfig.html
+//! [`FromRequest`]: ../trait.FromRequest.html
+//! [`Router`]: ../service/struct.Router.html
+//! [`IntoResponse`]: ../response/trait.IntoResponse.html
+
+use crate::{
+    response::{stream_blocking, IntoResponse},
+    routes::RouteInfo,
+    service::{matches_entity_tag, not_modified},
+    BoxedError, DefaultFuture, Error, FromRequest, RouteMatch,
+};
+use http::{header, HeaderValue, Method, Response, StatusCode};
+use hyper::Body;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The methods a file can be requested with.
+static ALLOWED_METHODS: &[&Method] = &[&Method::GET, &Method::HEAD];
+
+/// A file or directory listing requested by a client.
+///
+/// The request path is percent-decoded and resolved relative to the root
+/// directory of the [`StaticFilesConfig`]. Decoding fails with:
+///
+/// * `405 Method Not Allowed` for requests other than `GET` and `HEAD`.
+/// * `403 Forbidden` if the path tries to leave the root directory (via `..`
+///   segments, encoded slashes or symbolic links), if the file can't be read
+///   due to missing permissions, or if it is a directory that is neither
+///   served via an index file nor listed.
+/// * `404 Not Found` if the file doesn't exist.
+///
+/// Opening the file happens on the blocking thread pool, so `StaticFiles` must
+/// be decoded on a tokio runtime using a thread pool (as `tokio::run` does).
+/// [`FromRequest::from_request_sync`] opens it on the calling thread instead.
+///
+/// The response created by the [`IntoResponse`] implementation streams the
+/// file from the blocking thread pool (see [`stream_blocking`]). It has a
+/// `Content-Type` guessed from the file extension, and the `Last-Modified` and
+/// `ETag` headers derived from the file metadata. Conditional requests using
+/// `If-None-Match` or `If-Modified-Since` are answered with `304 Not
+/// Modified`, and a single byte range requested via `Range` is sent as a `206
+/// Partial Content` response.
+///
+/// [`StaticFilesConfig`]: struct.StaticFilesConfig.html
+/// [`FromRequest::from_request_sync`]: ../trait.FromRequest.html#method.from_request_sync
+/// [`IntoResponse`]: ../response/trait.IntoResponse.html
+/// [`stream_blocking`]: ../response/fn.stream_blocking.html
+#[derive(Debug)]
+pub struct StaticFiles {
+    path: PathBuf,
+    kind: Kind,
+    /// The request headers that affect the response.
+    headers: http::HeaderMap,
+}
+
+/// What to send to the client.
+#[derive(Debug)]
+enum Kind {
+    File {
+        file: File,
+        len: u64,
+        modified: Option<SystemTime>,
+    },
+    Listing {
+        base: String,
+        entries: Vec<String>,
+    },
+}
+
+impl Kind {
+    /// Creates a `Kind::File`, keeping the parts of `metadata` that are needed.
+    fn file(file: File, metadata: &Metadata) -> Self {
+        Kind::File {
+            file,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+impl StaticFiles {
+    /// Returns the path of the file or directory that will be sent.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether `self` lists the contents of a directory.
+    pub fn is_listing(&self) -> bool {
+        match self.kind {
+            Kind::Listing { .. } => true,
+            Kind::File { .. } => false,
+        }
+    }
+
+    /// Resolves the path of `request` and opens the file, blocking the thread.
+    fn open(request: &http::Request<()>, config: &StaticFilesConfig) -> Result<Self, BoxedError> {
+        if !ALLOWED_METHODS.contains(&request.method()) {
+            return Err(Error::wrong_method(ALLOWED_METHODS)
+                .with_request(request.method().clone(), request.uri().path())
+                .into());
+        }
+
+        let root = fs::canonicalize(&config.root).map_err(io_error)?;
+        let mut path = root.clone();
+        let uri_path = request.uri().path();
+        for segment in uri_path.split('/').filter(|segment| !segment.is_empty()) {
+            path.push(decode_segment(segment)?);
+        }
+
+        // Symbolic links must not lead out of the root directory
+        let path = fs::canonicalize(&path).map_err(io_error)?;
+        if !path.starts_with(&root) {
+            return Err(Error::with_source(StatusCode::FORBIDDEN, "path leaves the root").into());
+        }
+
+        let metadata = fs::metadata(&path).map_err(io_error)?;
+        let index = config
+            .index_file
+            .as_ref()
+            .map(|index| path.join(index))
+            .filter(|index| metadata.is_dir() && index.is_file());
+        let (path, kind) = if !metadata.is_dir() {
+            let file = File::open(&path).map_err(io_error)?;
+            (path, Kind::file(file, &metadata))
+        } else if let Some(index) = index {
+            let file = File::open(&index).map_err(io_error)?;
+            let metadata = file.metadata().map_err(io_error)?;
+            (index, Kind::file(file, &metadata))
+        } else if config.list_directories {
+            let entries = list_directory(&path).map_err(io_error)?;
+            let base = listing_base(uri_path);
+            (path, Kind::Listing { base, entries })
+        } else {
+            return Err(Error::with_source(
+                StatusCode::FORBIDDEN,
+                "directory listings are disabled",
+            )
+            .into());
+        };
+
+        Ok(Self {
+            path,
+            kind,
+            headers: request.headers().clone(),
+        })
+    }
+}
+
+impl FromRequest for StaticFiles {
+    type Context = StaticFilesConfig;
+    type Future = DefaultFuture<Self, BoxedError>;
+
+    fn from_request_and_body(
+        request: &Arc<http::Request<()>>,
+        _body: Body,
+        context: Arc<Self::Context>,
+    ) -> Self::Future {
+        let request = request.clone();
+        Box::new(crate::blocking(move || Self::open(&request, &context)))
+    }
+
+    fn try_from_request_parts(
+        request: &Arc<http::Request<()>>,
+        context: &Self::Context,
+    ) -> Option<Result<Self, BoxedError>> {
+        // Only used by `from_request_sync`, which is allowed to block
+        Some(Self::open(request, context))
+    }
+
+    fn route_name(&self) -> Option<&'static str> {
+        Some("StaticFiles")
+    }
+
+    fn routes() -> Vec<RouteInfo> {
+        vec![
+            RouteInfo::new(Method::GET, "/{path...}", "StaticFiles"),
+            RouteInfo::new(Method::HEAD, "/{path...}", "StaticFiles").implied(),
+        ]
+    }
+
+    /// Only checks the method, since whether the path exists is only known
+    /// once it is opened.
+    fn match_route(method: &Method, _path: &str) -> RouteMatch {
+        if ALLOWED_METHODS.contains(&method) {
+            RouteMatch::Route {
+                name: "StaticFiles",
+                placeholders: Vec::new(),
+            }
+        } else {
+            RouteMatch::wrong_method(ALLOWED_METHODS.iter().cloned())
+        }
+    }
+}
+
+impl IntoResponse for StaticFiles {
+    fn into_response(self) -> Response<Body> {
+        let StaticFiles {
+            path,
+            kind,
+            headers: request_headers,
+        } = self;
+        let (file, len, modified) = match kind {
+            Kind::File {
+                file,
+                len,
+                modified,
+            } => (file, len, modified),
+            Kind::Listing { base, entries } => {
+                let mut response = Response::new(Body::from(render_listing(&base, &entries)));
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("text/html; charset=utf-8"),
+                );
+                return response;
+            }
+        };
+
+        let mut response = Response::new(Body::empty());
+        let content_type = mime_guess::from_path(&path)
+            .first_raw()
+            .unwrap_or("application/octet-stream");
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+        let last_modified = modified.map(|modified| {
+            HeaderValue::from_str(&http_date(modified)).expect("dates are valid header values")
+        });
+        let etag = modified.map(|modified| entity_tag(len, modified));
+        if let Some(last_modified) = &last_modified {
+            headers.insert(header::LAST_MODIFIED, last_modified.clone());
+        }
+        if let Some(etag) = &etag {
+            headers.insert(header::ETAG, etag.clone());
+        }
+
+        // `If-Modified-Since` is ignored when `If-None-Match` is present
+        let is_fresh = if request_headers.contains_key(header::IF_NONE_MATCH) {
+            match &etag {
+                Some(etag) => request_headers
+                    .get_all(header::IF_NONE_MATCH)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .any(|value| matches_entity_tag(value, etag)),
+                None => false,
+            }
+        } else {
+            // Dates are compared exactly, which covers clients that echo the
+            // `Last-Modified` header
+            last_modified.is_some()
+                && request_headers.get(header::IF_MODIFIED_SINCE) == last_modified.as_ref()
+        };
+        if is_fresh {
+            let mut response = not_modified(response);
+            if let Some(etag) = etag {
+                response.headers_mut().insert(header::ETAG, etag);
+            }
+            return response;
+        }
+
+        let (start, count) = match request_headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_range)
+        {
+            None => (0, len),
+            Some(range) => match range.resolve(len) {
+                Some((start, count)) => {
+                    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    let content_range = format!("bytes {}-{}/{}", start, start + count - 1, len);
+                    response.headers_mut().insert(
+                        header::CONTENT_RANGE,
+                        HeaderValue::from_str(&content_range).expect("valid header value"),
+                    );
+                    (start, count)
+                }
+                None => {
+                    *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    response.headers_mut().insert(
+                        header::CONTENT_RANGE,
+                        HeaderValue::from_str(&format!("bytes */{}", len))
+                            .expect("valid header value"),
+                    );
+                    return response;
+                }
+            },
+        };
+
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(count));
+        *response.body_mut() = stream_blocking(move |writer| {
+            let mut file = file;
+            file.seek(SeekFrom::Start(start))?;
+            io::copy(&mut file.take(count), writer)?;
+            Ok(())
+        });
+        response
+    }
+}
+
+/// Configures the [`StaticFiles`] route.
+///
+/// By default, only files are served. Directories can be served using an
+/// index file contained in them (eg. `index.html`) and/or a listing of their
+/// contents, which have to be enabled explicitly.
+///
+/// [`StaticFiles`]: struct.StaticFiles.html
+#[derive(Debug, Clone)]
+pub struct StaticFilesConfig {
+    root: PathBuf,
+    index_file: Option<String>,
+    list_directories: bool,
+}
+
+impl StaticFilesConfig {
+    /// Creates a configuration serving the files below `root`.
+    ///
+    /// `root` doesn't have to exist yet: requests fail with `404 Not Found`
+    /// until it does.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            index_file: None,
+            list_directories: false,
+        }
+    }
+
+    /// Serves the file called `name` when a directory containing it is
+    /// requested.
+    pub fn index_file(mut self, name: &str) -> Self {
+        self.index_file = Some(name.to_string());
+        self
+    }
+
+    /// Sets whether to list the contents of directories that don't contain an
+    /// index file.
+    ///
+    /// The listing is a minimal HTML page linking to every entry of the
+    /// directory, including hidden files.
+    pub fn list_directories(mut self, list: bool) -> Self {
+        self.list_directories = list;
+        self
+    }
+}
+
+impl_request_context!(StaticFilesConfig);
+
+/// Turns an I/O error into an `Error` with a matching status code.
+fn io_error(error: io::Error) -> BoxedError {
+    let status = match error.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    Error::with_source(status, error).into()
+}
+
+/// Percent-decodes a segment of the request path.
+///
+/// Fails with `403 Forbidden` if the decoded segment isn't a plain file name,
+/// eg. when it is `..` or contains an encoded `/`.
+fn decode_segment(segment: &str) -> Result<String, BoxedError> {
+    let forbidden = || Error::with_source(StatusCode::FORBIDDEN, "invalid path segment").into();
+
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2]).map_err(|_| forbidden())?;
+            let decoded = u8::from_str_radix(hex, 16).map_err(|_| forbidden())?;
+            bytes.push(decoded);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    let decoded = String::from_utf8(bytes).map_err(|_| forbidden())?;
+    let mut components = Path::new(&decoded).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None)
+            if name == decoded.as_str() && !decoded.contains(['/', '\\', '\0'].as_ref()) =>
+        {
+            Ok(decoded)
+        }
+        _ => Err(forbidden()),
+    }
+}
+
+/// Returns the sorted names of the entries in `dir`, with a trailing `/` for
+/// directories.
+fn list_directory(dir: &Path) -> io::Result<Vec<String>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() {
+            name.push('/');
+        }
+        entries.push(name);
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Returns what the links in the listing of the directory at `uri_path` have
+/// to start with.
+///
+/// Links are relative, so that they work when the prefix of a mounted service
+/// was stripped from the path. Without a trailing slash, the last segment of
+/// the path is what relative links are resolved against.
+fn listing_base(uri_path: &str) -> String {
+    if uri_path.ends_with('/') {
+        "./".to_string()
+    } else {
+        let last = uri_path.rsplit('/').next().unwrap_or("");
+        format!("./{}/", last)
+    }
+}
+
+/// Renders an HTML page listing the directory `entries`.
+fn render_listing(base: &str, entries: &[String]) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<body>\n<ul>\n");
+    for entry in entries {
+        html.push_str(&format!(
+            "<li><a href=\"{}{}\">{}</a></li>\n",
+            escape_html(base),
+            escape_html(&encode_path_segment(entry)),
+            escape_html(entry)
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+/// Percent-encodes the characters of a file name that can't appear in a URL
+/// path, keeping a trailing `/`.
+fn encode_path_segment(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    let (name, slash) = match name.len().checked_sub(1) {
+        Some(last) if name.ends_with('/') => (&name[..last], "/"),
+        _ => (name, ""),
+    };
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded.push_str(slash);
+    encoded
+}
+
+/// Escapes the characters with a special meaning in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Computes the `ETag` of a file from its size and modification time.
+///
+/// This avoids reading the file, at the cost of not detecting modifications
+/// that preserve both.
+fn entity_tag(len: u64, modified: SystemTime) -> HeaderValue {
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    HeaderValue::from_str(&format!(
+        "\"{:x}-{:x}.{:x}\"",
+        len,
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    ))
+    .expect("hex digits are a valid header value")
+}
+
+/// Formats `time` as an HTTP date (eg. `Sun, 06 Nov 1994 08:49:37 GMT`).
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+
+    // Converts days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// A single byte range requested via the `Range` header.
+enum ByteRange {
+    /// `bytes=start-` or `bytes=start-end` (inclusive).
+    FromStart(u64, Option<u64>),
+    /// `bytes=-count`, the last `count` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Returns the offset and length of the range in a file of `len` bytes,
+    /// or `None` if the range isn't satisfiable.
+    fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::FromStart(start, _) if start >= len => None,
+            ByteRange::FromStart(start, end) => {
+                let end = end.map_or(len - 1, |end| end.min(len - 1));
+                Some((start, end - start + 1))
+            }
+            ByteRange::Suffix(0) => None,
+            ByteRange::Suffix(count) => {
+                let count = count.min(len);
+                if count == 0 {
+                    None
+                } else {
+                    Some((len - count, count))
+                }
+            }
+        }
+    }
+}
+
+/// Parses a `Range` header requesting a single byte range.
+///
+/// Returns `None` for invalid headers and ones requesting several ranges,
+/// which are then ignored (as permitted by RFC 7233), so the whole file is
+/// sent.
+fn parse_range(value: &str) -> Option<ByteRange> {
+    let value = value.trim();
+    if !value.starts_with("bytes=") || value.contains(',') {
+        return None;
+    }
+
+    let spec = &value["bytes=".len()..];
+
+    let dash = spec.find('-')?;
+    let (start, end) = (spec[..dash].trim(), spec[dash + 1..].trim());
+    if start.is_empty() {
+        return end.parse().ok().map(ByteRange::Suffix);
+    }
+
+    let start = start.parse().ok()?;
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(end.parse().ok()?)
+    };
+    match end {
+        Some(end) if end < start => None,
+        _ => Some(ByteRange::FromStart(start, end)),
+    }
+}
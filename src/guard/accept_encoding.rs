@@ -0,0 +1,183 @@
+use crate::{BoxedError, Guard, NoContext};
+use http::{header, HeaderValue};
+use std::sync::Arc;
+
+/// The content codings the client accepts for the response, parsed from the
+/// `Accept-Encoding` header ([RFC 7231]).
+///
+/// This is meant for handlers that pick an encoding themselves, eg. to serve
+/// pre-compressed files, and set the `Content-Encoding` header accordingly.
+/// Responses can instead be compressed automatically using
+/// [`ServiceExt::compress`].
+///
+/// The quality values of the codings are interpreted as described in the RFC:
+///
+/// * A coding that is listed explicitly has the listed quality (`1` if none
+///   is given). Coding names are case-insensitive.
+/// * Other codings have the quality of the `*` wildcard, or are not acceptable
+///   if there's no wildcard.
+/// * `identity` (no encoding) is always acceptable, unless it is excluded with
+///   `identity;q=0`, or with `*;q=0` without listing `identity`. The client
+///   then requires the response to be encoded.
+///
+/// A request without an `Accept-Encoding` header (or with an empty one) only
+/// accepts `identity`. While the RFC allows sending any coding in this case,
+/// clients that don't send the header usually can't decode compressed
+/// responses. Malformed entries are skipped instead of failing the request.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::AcceptEncoding};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/app.js")]
+///     Script {
+///         encoding: AcceptEncoding,
+///     },
+/// }
+///
+/// let request = http::Request::get("/app.js")
+///     .header("Accept-Encoding", "gzip, br;q=0.9, *;q=0.1")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Script { encoding } = Route::from_request_sync(request, NoContext).unwrap();
+///
+/// // Serve `app.js.gz`, `app.js.br` or `app.js`
+/// assert_eq!(encoding.negotiate(&["br", "gzip"]), Some("gzip"));
+/// assert_eq!(encoding.quality("deflate"), 0.1);
+/// assert!(!encoding.requires_encoding());
+/// ```
+///
+/// [RFC 7231]: https://tools.ietf.org/html/rfc7231#section-5.3.4
+/// [`ServiceExt::compress`]: ../service/trait.ServiceExt.html#tymethod.compress
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AcceptEncoding {
+    /// `(lowercase coding, quality)`, sorted by descending quality.
+    codings: Vec<(String, f32)>,
+}
+
+impl AcceptEncoding {
+    /// Parses a list of `Accept-Encoding` header values.
+    pub fn parse<'a, I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = &'a HeaderValue>,
+    {
+        let mut codings = Vec::<(String, f32)>::new();
+        let items = values
+            .into_iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for item in items {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            if coding.is_empty() {
+                continue;
+            }
+
+            let mut quality = Some(1.0);
+            for param in params {
+                let param = param.trim();
+                if param.starts_with("q=") || param.starts_with("Q=") {
+                    quality = param[2..]
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| *q >= 0.0 && *q <= 1.0);
+                }
+            }
+
+            if let Some(quality) = quality {
+                if codings.iter().all(|(c, _)| *c != coding) {
+                    codings.push((coding, quality));
+                }
+            }
+        }
+
+        // The sort is stable, so codings with the same quality stay in the
+        // order the client listed them
+        codings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        Self { codings }
+    }
+
+    /// Returns the listed codings and their quality values, most preferred
+    /// first.
+    ///
+    /// Coding names are lowercase, and codings with the same quality are in
+    /// the order the client listed them. This includes the `*` wildcard and
+    /// codings with quality `0`, which the client explicitly refuses.
+    pub fn preferences(&self) -> &[(String, f32)] {
+        &self.codings
+    }
+
+    /// Returns the quality value the client assigns to `coding`, from `0.0`
+    /// (not acceptable) to `1.0`.
+    pub fn quality(&self, coding: &str) -> f32 {
+        if let Some(quality) = self.listed(coding) {
+            return quality;
+        }
+
+        let wildcard = self.listed("*");
+        if !coding.eq_ignore_ascii_case("identity") {
+            wildcard.unwrap_or(0.0)
+        } else if wildcard == Some(0.0) {
+            // `*;q=0` also excludes `identity`, but a positive wildcard
+            // quality doesn't lower its default quality
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns whether the client accepts responses encoded with `coding`.
+    pub fn is_acceptable(&self, coding: &str) -> bool {
+        self.quality(coding) > 0.0
+    }
+
+    /// Returns whether the client refuses unencoded responses (eg. by sending
+    /// `identity;q=0`).
+    pub fn requires_encoding(&self) -> bool {
+        !self.is_acceptable("identity")
+    }
+
+    /// Picks the coding from `available` that the client prefers.
+    ///
+    /// `available` lists the codings the server can provide, most preferred
+    /// first, which decides between codings of equal quality. It may include
+    /// `identity`. Returns `None` if the client accepts none of them; the
+    /// response should then be sent unencoded if [`requires_encoding`] is
+    /// `false`, or be answered with `406 Not Acceptable` otherwise.
+    ///
+    /// [`requires_encoding`]: #method.requires_encoding
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&'a str, f32)> = None;
+        for &coding in available {
+            let quality = self.quality(coding);
+            if quality > 0.0 && best.map(|(_, q)| quality > q).unwrap_or(true) {
+                best = Some((coding, quality));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+
+    /// Returns the quality of `coding` if it is listed explicitly.
+    fn listed(&self, coding: &str) -> Option<f32> {
+        self.codings
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(coding))
+            .map(|(_, q)| *q)
+    }
+}
+
+impl Guard for AcceptEncoding {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        Ok(Self::parse(
+            request.headers().get_all(header::ACCEPT_ENCODING),
+        ))
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{BoxedError, DefaultFuture, Error, Guard};
+use futures::{Future, IntoFuture};
+use http::{header::HeaderName, StatusCode};
+use std::fmt;
+use std::sync::Arc;
+
+/// An API key extracted from a request.
+///
+/// The key can be sent by the client in any of the places configured in the
+/// [`ApiKeyConfig`] context. The sources are tried in order, and the first one
+/// that is present in the request is used.
+///
+/// If the [`ApiKeyConfig`] has a [`KeyValidator`], the key is checked before
+/// the guard succeeds. Requests without a key, or with a key rejected by the
+/// validator, fail with a `401 Unauthorized` error.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, guard::{ApiKey, ApiKeyConfig}};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// #[context(ApiKeyConfig)]
+/// enum Route {
+///     #[get("/reports")]
+///     Reports {
+///         key: ApiKey,
+///     },
+/// }
+///
+/// let config = ApiKeyConfig::new().validator(|key: &str| key == "s3cr3t");
+///
+/// let request = http::Request::get("/reports?api_key=s3cr3t")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Reports { key } = Route::from_request_sync(request, config).unwrap();
+///
+/// assert_eq!(key.key(), "s3cr3t");
+/// // Handlers might want to warn clients that still use the query parameter
+/// assert!(key.is_from_query());
+/// ```
+///
+/// [`ApiKeyConfig`]: struct.ApiKeyConfig.html
+/// [`KeyValidator`]: trait.KeyValidator.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    key: String,
+    source: ApiKeySource,
+}
+
+impl ApiKey {
+    /// Returns the API key sent by the client.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the place in the request the key was taken from.
+    pub fn source(&self) -> &ApiKeySource {
+        &self.source
+    }
+
+    /// Returns whether the key was passed as a query parameter.
+    ///
+    /// Query parameters tend to end up in server and proxy logs, so APIs might
+    /// want to deprecate this way of passing the key.
+    pub fn is_from_query(&self) -> bool {
+        match self.source {
+            ApiKeySource::QueryParam(_) => true,
+            ApiKeySource::Header(_) | ApiKeySource::Authorization => false,
+        }
+    }
+}
+
+impl Guard for ApiKey {
+    type Context = ApiKeyConfig;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let api_key = match context
+            .sources
+            .iter()
+            .filter_map(|source| {
+                source.extract(request).map(|key| ApiKey {
+                    key,
+                    source: source.clone(),
+                })
+            })
+            .next()
+        {
+            Some(api_key) => api_key,
+            None => {
+                return Error::with_source(StatusCode::UNAUTHORIZED, "missing API key")
+                    .into_future();
+            }
+        };
+
+        match &context.validator {
+            None => Box::new(Ok(api_key).into_future()),
+            Some(validator) => Box::new(validator.validate(&api_key.key).and_then(|valid| {
+                if valid {
+                    Ok(api_key)
+                } else {
+                    Err(Error::with_source(StatusCode::UNAUTHORIZED, "invalid API key").into())
+                }
+            })),
+        }
+    }
+}
+
+/// A place in the request that can carry an API key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeySource {
+    /// A dedicated header containing only the key (eg. `X-Api-Key: <key>`).
+    Header(HeaderName),
+    /// The `Authorization` header, using the `ApiKey` scheme
+    /// (`Authorization: ApiKey <key>`).
+    Authorization,
+    /// A query parameter with the given name (eg. `?api_key=<key>`).
+    QueryParam(String),
+}
+
+impl ApiKeySource {
+    /// Extracts the key from `request`, if it is present in this source.
+    ///
+    /// Empty keys are treated as missing.
+    fn extract(&self, request: &http::Request<()>) -> Option<String> {
+        let key = match self {
+            ApiKeySource::Header(name) => request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string()),
+            ApiKeySource::Authorization => request
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| {
+                    let value = value.trim();
+                    let space = value.find(' ')?;
+                    if value[..space].eq_ignore_ascii_case("apikey") {
+                        Some(value[space..].trim().to_string())
+                    } else {
+                        None
+                    }
+                }),
+            ApiKeySource::QueryParam(param) => {
+                let query = request.uri().query().unwrap_or("");
+                serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+                    .ok()?
+                    .into_iter()
+                    .find(|(name, _)| name == param)
+                    .map(|(_, value)| value)
+            }
+        };
+
+        key.filter(|key| !key.is_empty())
+    }
+}
+
+/// Checks whether an API key is valid.
+///
+/// This is implemented for all closures taking a `&str` and returning a
+/// `bool`, which covers synchronous checks against a fixed set of keys.
+/// Validators that need to perform asynchronous work (eg. a database lookup)
+/// can implement this trait directly and return a future.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{guard::KeyValidator, BoxedError, DefaultFuture};
+/// use futures::IntoFuture;
+///
+/// struct KeyDatabase;
+///
+/// impl KeyValidator for KeyDatabase {
+///     fn validate(&self, key: &str) -> DefaultFuture<bool, BoxedError> {
+///         // A real implementation would query the database here
+///         Box::new(Ok(key.starts_with("db-")).into_future())
+///     }
+/// }
+/// ```
+pub trait KeyValidator: Send + Sync + 'static {
+    /// Returns a future resolving to whether `key` is valid.
+    ///
+    /// Resolving to `false` makes the [`ApiKey`] guard fail with a `401
+    /// Unauthorized` error. Resolving to an error fails the request with that
+    /// error instead.
+    ///
+    /// [`ApiKey`]: struct.ApiKey.html
+    fn validate(&self, key: &str) -> DefaultFuture<bool, BoxedError>;
+}
+
+impl<F> KeyValidator for F
+where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+    fn validate(&self, key: &str) -> DefaultFuture<bool, BoxedError> {
+        Box::new(Ok(self(key)).into_future())
+    }
+}
+
+/// Configures the [`ApiKey`] guard.
+///
+/// By default, the following sources are checked, in this order:
+///
+/// 1. The `X-Api-Key` header.
+/// 2. The `Authorization` header, using the `ApiKey` scheme.
+/// 3. The `api_key` query parameter.
+///
+/// No validator is installed by default, so any key will be accepted.
+///
+/// [`ApiKey`]: struct.ApiKey.html
+#[derive(Clone)]
+pub struct ApiKeyConfig {
+    sources: Vec<ApiKeySource>,
+    validator: Option<Arc<dyn KeyValidator>>,
+}
+
+impl ApiKeyConfig {
+    /// Creates a configuration using the default sources and no validator.
+    pub fn new() -> Self {
+        Self {
+            sources: vec![
+                ApiKeySource::Header(HeaderName::from_static("x-api-key")),
+                ApiKeySource::Authorization,
+                ApiKeySource::QueryParam("api_key".to_string()),
+            ],
+            validator: None,
+        }
+    }
+
+    /// Replaces the list of sources to take the key from.
+    ///
+    /// The sources are tried in the given order, and the first one present in
+    /// a request is used.
+    pub fn sources<I>(mut self, sources: I) -> Self
+    where
+        I: IntoIterator<Item = ApiKeySource>,
+    {
+        self.sources = sources.into_iter().collect();
+        self
+    }
+
+    /// Sets the [`KeyValidator`] used to check incoming keys.
+    ///
+    /// [`KeyValidator`]: trait.KeyValidator.html
+    pub fn validator<V: KeyValidator>(mut self, validator: V) -> Self {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ApiKeyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyConfig")
+            .field("sources", &self.sources)
+            .field("validator", &self.validator.as_ref().map(|_| "<validator>"))
+            .finish()
+    }
+}
+
+impl_request_context!(ApiKeyConfig);
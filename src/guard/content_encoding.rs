@@ -0,0 +1,165 @@
+use crate::{BoxedError, Error, Guard};
+use http::{header, HeaderValue, StatusCode};
+use std::sync::Arc;
+
+/// Ensures that the request body uses only content codings the server can
+/// decode.
+///
+/// The `Content-Encoding` header lists the codings applied to the request body,
+/// in the order they were applied. If any of them isn't listed in the
+/// [`ContentEncodingConfig`] context, the guard fails with a `415 Unsupported
+/// Media Type` error naming the unsupported coding. As recommended by [RFC
+/// 7694], the error response includes an `Accept-Encoding` header listing the
+/// supported codings.
+///
+/// Without this guard, a body using an unsupported coding would be passed to
+/// the body decoder (eg. [`Json`]) as-is, resulting in a confusing parse error.
+///
+/// Requests without a `Content-Encoding` header use the `identity` coding,
+/// which is always supported.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, body::Json, guard::{AcceptedContentEncodings, ContentEncodingConfig}};
+/// use hyper::Body;
+/// use http::StatusCode;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Upload {
+///     name: String,
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(ContentEncodingConfig)]
+/// enum Route {
+///     #[post("/upload")]
+///     Upload {
+///         _encoding: AcceptedContentEncodings,
+///
+///         #[body]
+///         upload: Json<Upload>,
+///     },
+/// }
+///
+/// let request = http::Request::post("/upload")
+///     .header("Content-Encoding", "br")
+///     .body(Body::from("<compressed data>"))
+///     .unwrap();
+/// let error = Route::from_request_sync(request, ContentEncodingConfig::new()).err().unwrap();
+/// let error = error.downcast::<hyperdrive::Error>().unwrap();
+///
+/// assert_eq!(error.http_status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+/// assert_eq!(error.response().headers()["accept-encoding"], "identity");
+/// ```
+///
+/// [`ContentEncodingConfig`]: struct.ContentEncodingConfig.html
+/// [RFC 7694]: https://tools.ietf.org/html/rfc7694
+/// [`Json`]: ../body/struct.Json.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptedContentEncodings {
+    codings: Vec<String>,
+}
+
+impl AcceptedContentEncodings {
+    /// Returns the content codings applied to the body, in the order they were
+    /// applied.
+    ///
+    /// The codings are lowercase, and `identity` is omitted. If the body isn't
+    /// encoded, this is empty.
+    pub fn codings(&self) -> &[String] {
+        &self.codings
+    }
+
+    /// Returns whether the body is sent without any content coding.
+    pub fn is_identity(&self) -> bool {
+        self.codings.is_empty()
+    }
+}
+
+impl Guard for AcceptedContentEncodings {
+    type Context = ContentEncodingConfig;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let mut codings = Vec::new();
+
+        for value in request.headers().get_all(header::CONTENT_ENCODING) {
+            let value = value.to_str().map_err(|_| {
+                Error::with_source(StatusCode::BAD_REQUEST, "invalid Content-Encoding header")
+            })?;
+
+            for coding in value.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                let coding = coding.to_ascii_lowercase();
+                if coding == "identity" {
+                    continue;
+                }
+
+                if !context.supported.contains(&coding) {
+                    let mut error = Error::unsupported_media_type(format!(
+                        "unsupported content coding `{}`",
+                        coding
+                    ));
+                    error
+                        .headers_mut()
+                        .insert(header::ACCEPT_ENCODING, context.accept_encoding());
+                    return Err(error.into());
+                }
+
+                codings.push(coding);
+            }
+        }
+
+        Ok(Self { codings })
+    }
+}
+
+/// Configures the content codings accepted by the [`AcceptedContentEncodings`]
+/// guard.
+///
+/// The `identity` coding (no encoding) is always accepted.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::guard::ContentEncodingConfig;
+///
+/// // When the body is decompressed before it is decoded:
+/// let config = ContentEncodingConfig::new().accept("gzip").accept("deflate");
+/// ```
+///
+/// [`AcceptedContentEncodings`]: struct.AcceptedContentEncodings.html
+#[derive(Debug, Clone, Default)]
+pub struct ContentEncodingConfig {
+    supported: Vec<String>,
+}
+
+impl ContentEncodingConfig {
+    /// Creates a configuration that only accepts unencoded bodies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `coding` to the list of accepted content codings.
+    ///
+    /// Content codings are case-insensitive.
+    pub fn accept(mut self, coding: &str) -> Self {
+        let coding = coding.to_ascii_lowercase();
+        if coding != "identity" && !self.supported.contains(&coding) {
+            self.supported.push(coding);
+        }
+        self
+    }
+
+    /// Builds the value of the `Accept-Encoding` header sent along with `415`
+    /// errors.
+    fn accept_encoding(&self) -> HeaderValue {
+        let mut codings = self.supported.clone();
+        codings.push("identity".to_string());
+        HeaderValue::from_str(&codings.join(", "))
+            .unwrap_or_else(|_| HeaderValue::from_static("identity"))
+    }
+}
+
+impl_request_context!(ContentEncodingConfig);
@@ -0,0 +1,201 @@
+use crate::{BoxedError, DefaultFuture, Error, Guard, NoContext, RequestContext};
+use futures::Future;
+use http::{header, HeaderValue, StatusCode};
+use r2d2::{ManageConnection, Pool, PooledConnection};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A database connection checked out from an `r2d2` connection pool.
+///
+/// The pool is taken from the [`DbPool`] context. Since checking out a
+/// connection can block until one becomes available, this is done on the
+/// blocking thread pool (see [`blocking`]), so the guard must be used on a
+/// server running on tokio's threadpool.
+///
+/// If no connection becomes available within the pool's connection timeout,
+/// the request fails with a `503 Service Unavailable` error carrying a
+/// `Retry-After` header.
+///
+/// The connection is returned to the pool when the `DbConnection` is dropped.
+/// It dereferences to `M::Connection`.
+///
+/// This type is only available when the `r2d2` feature is enabled.
+///
+/// # Examples
+///
+/// Applications usually put the pool into their own context, next to other
+/// configuration:
+///
+/// ```
+/// use hyperdrive::{FromRequest, RequestContext, guard::{DbConnection, DbPool}};
+/// use r2d2::ManageConnection;
+///
+/// # struct PgManager;
+/// # struct PgConnection;
+/// # impl ManageConnection for PgManager {
+/// #     type Connection = PgConnection;
+/// #     type Error = std::io::Error;
+/// #     fn connect(&self) -> Result<PgConnection, Self::Error> { Ok(PgConnection) }
+/// #     fn is_valid(&self, _: &mut PgConnection) -> Result<(), Self::Error> { Ok(()) }
+/// #     fn has_broken(&self, _: &mut PgConnection) -> bool { false }
+/// # }
+/// #[derive(RequestContext, Clone)]
+/// struct MyContext {
+///     #[as_ref]
+///     db: DbPool<PgManager>,
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(MyContext)]
+/// enum Route {
+///     #[get("/users/{id}")]
+///     User {
+///         id: u32,
+///         db: DbConnection<PgManager>,
+///     },
+/// }
+///
+/// let pool = r2d2::Pool::builder().build(PgManager).unwrap();
+/// let context = MyContext {
+///     db: DbPool::new(pool),
+/// };
+/// ```
+///
+/// [`DbPool`]: struct.DbPool.html
+/// [`blocking`]: ../fn.blocking.html
+pub struct DbConnection<M: ManageConnection>(PooledConnection<M>);
+
+impl<M: ManageConnection> DbConnection<M> {
+    /// Returns the underlying `PooledConnection`.
+    pub fn into_inner(self) -> PooledConnection<M> {
+        self.0
+    }
+}
+
+impl<M: ManageConnection> Guard for DbConnection<M> {
+    type Context = DbPool<M>;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_request(_request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let pool = context.pool.clone();
+        let retry_after = context.retry_after;
+
+        Box::new(
+            crate::blocking(move || pool.get()).then(move |result| match result {
+                Ok(connection) => Ok(DbConnection(connection)),
+                Err(e) => {
+                    let mut error = Error::with_source(StatusCode::SERVICE_UNAVAILABLE, e);
+                    error.headers_mut().insert(
+                        header::RETRY_AFTER,
+                        HeaderValue::from(retry_after.as_secs()),
+                    );
+                    Err(error.into())
+                }
+            }),
+        )
+    }
+}
+
+impl<M: ManageConnection> Deref for DbConnection<M> {
+    type Target = M::Connection;
+
+    fn deref(&self) -> &M::Connection {
+        &self.0
+    }
+}
+
+impl<M: ManageConnection> DerefMut for DbConnection<M> {
+    fn deref_mut(&mut self) -> &mut M::Connection {
+        &mut self.0
+    }
+}
+
+impl<M> fmt::Debug for DbConnection<M>
+where
+    M: ManageConnection,
+    M::Connection: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DbConnection").field(&*self.0).finish()
+    }
+}
+
+/// Context for the [`DbConnection`] guard, wrapping an `r2d2::Pool`.
+///
+/// This type is only available when the `r2d2` feature is enabled.
+///
+/// [`DbConnection`]: struct.DbConnection.html
+pub struct DbPool<M: ManageConnection> {
+    pool: Pool<M>,
+    retry_after: Duration,
+}
+
+impl<M: ManageConnection> DbPool<M> {
+    /// Creates a `DbPool` handing out connections from `pool`.
+    ///
+    /// When the pool is exhausted, clients are told to retry after 1 second.
+    pub fn new(pool: Pool<M>) -> Self {
+        Self {
+            pool,
+            retry_after: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the delay to put in the `Retry-After` header when no connection is
+    /// available.
+    ///
+    /// The header only has a resolution of seconds, so any fractional part of
+    /// `delay` is ignored.
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after = delay;
+        self
+    }
+
+    /// Returns the wrapped `r2d2::Pool`.
+    pub fn pool(&self) -> &Pool<M> {
+        &self.pool
+    }
+}
+
+impl<M: ManageConnection> Clone for DbPool<M> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            retry_after: self.retry_after,
+        }
+    }
+}
+
+impl<M: ManageConnection> From<Pool<M>> for DbPool<M> {
+    fn from(pool: Pool<M>) -> Self {
+        Self::new(pool)
+    }
+}
+
+impl<M> fmt::Debug for DbPool<M>
+where
+    M: ManageConnection + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbPool")
+            .field("pool", &self.pool)
+            .field("retry_after", &self.retry_after)
+            .finish()
+    }
+}
+
+impl<M: ManageConnection> RequestContext for DbPool<M> {}
+
+impl<M: ManageConnection> AsRef<Self> for DbPool<M> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl<M: ManageConnection> AsRef<NoContext> for DbPool<M> {
+    fn as_ref(&self) -> &NoContext {
+        &NoContext
+    }
+}
@@ -0,0 +1,110 @@
+use super::request_extension;
+use crate::{BoxedError, Error, Guard, NoContext};
+use http::StatusCode;
+use std::any::type_name;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// A value of type `T` taken from the request extensions.
+///
+/// Services and middleware that run before the request is decoded can attach
+/// arbitrary values to it using `Request::extensions_mut`. This guard clones
+/// such a value out of the request, giving routes easy access to it.
+///
+/// If the request doesn't contain a value of type `T`, the guard fails with a
+/// `500 Internal Server Error`, since this indicates that the service isn't set
+/// up correctly. Use `Option<Extension<T>>` if the value might be absent.
+///
+/// # Examples
+///
+/// [`ServiceExt::map_request`] makes it easy to insert values into the
+/// extensions of every request:
+///
+/// ```
+/// use hyperdrive::{FromRequest, guard::Extension, service::{AsyncService, ServiceExt}};
+/// use hyper::{Body, Response};
+/// use futures::IntoFuture;
+///
+/// #[derive(Clone)]
+/// struct Config {
+///     greeting: String,
+/// }
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index {
+///         config: Extension<Config>,
+///     },
+/// }
+///
+/// let config = Config {
+///     greeting: "Hello".to_string(),
+/// };
+///
+/// let service = AsyncService::new(|route: Route, _| match route {
+///     Route::Index { config } => {
+///         Ok(Response::new(Body::from(config.greeting.clone()))).into_future()
+///     }
+/// })
+/// .map_request(move |request| {
+///     request.extensions_mut().insert(config.clone());
+/// })
+/// .make_service_by_cloning();
+/// ```
+///
+/// [`ServiceExt::map_request`]: ../service/trait.ServiceExt.html#tymethod.map_request
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Extension<T>(pub T);
+
+impl<T> Extension<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Guard for Extension<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        match request_extension::<T, _>(request) {
+            Some(value) => Ok(Extension(value.clone())),
+            None => Err(Error::with_source(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("missing request extension of type `{}`", type_name::<T>()),
+            )
+            .into()),
+        }
+    }
+}
+
+impl<T> Guard for Option<Extension<T>>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        Ok(request_extension::<T, _>(request).cloned().map(Extension))
+    }
+}
+
+impl<T> Deref for Extension<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Extension<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{BoxedError, Error, Guard};
+use http::{header, Method, StatusCode};
+use std::sync::Arc;
+
+/// Prevents other sites from embedding resources by checking the `Referer`
+/// header.
+///
+/// For requests using a safe method (`GET`, `HEAD`, `OPTIONS` or `TRACE`), the
+/// origin (scheme, host and port) of the `Referer` is compared against the
+/// origins allowed by the [`HotlinkProtectionConfig`] context. Requests
+/// referred by any other origin fail with `403 Forbidden`. Requests using other
+/// methods are always let through, since they can't be used for embedding.
+///
+/// Browsers and privacy extensions frequently strip the `Referer` header, so
+/// requests without one are allowed by default. This can be changed with
+/// [`HotlinkProtectionConfig::allow_missing_referer`].
+///
+/// Note that the `Referer` is controlled by the client, so this only protects
+/// against embedding by other websites, not against direct downloads.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, guard::{HotlinkProtection, HotlinkProtectionConfig}};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// #[context(HotlinkProtectionConfig)]
+/// enum Route {
+///     #[get("/images/{name}")]
+///     Image {
+///         name: String,
+///         _hotlink: HotlinkProtection,
+///     },
+/// }
+///
+/// let config = HotlinkProtectionConfig::new().allow_origin("https://example.com");
+///
+/// let request = http::Request::get("/images/cat.jpg")
+///     .header("Referer", "https://example.com/gallery?page=2")
+///     .body(Body::empty())
+///     .unwrap();
+/// assert!(Route::from_request_sync(request, config.clone()).is_ok());
+///
+/// let request = http::Request::get("/images/cat.jpg")
+///     .header("Referer", "https://elsewhere.example.org/")
+///     .body(Body::empty())
+///     .unwrap();
+/// assert!(Route::from_request_sync(request, config).is_err());
+/// ```
+///
+/// [`HotlinkProtectionConfig`]: struct.HotlinkProtectionConfig.html
+/// [`HotlinkProtectionConfig::allow_missing_referer`]: struct.HotlinkProtectionConfig.html#method.allow_missing_referer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HotlinkProtection {
+    _priv: (),
+}
+
+impl Guard for HotlinkProtection {
+    type Context = HotlinkProtectionConfig;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let ok = Ok(HotlinkProtection { _priv: () });
+
+        match *request.method() {
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE => {}
+            _ => return ok,
+        }
+
+        let referer = match request.headers().get(header::REFERER) {
+            Some(referer) => referer,
+            None if context.allow_missing => return ok,
+            None => {
+                return Err(
+                    Error::with_source(StatusCode::FORBIDDEN, "missing Referer header").into(),
+                );
+            }
+        };
+
+        let origin = referer.to_str().ok().and_then(origin);
+        match origin {
+            Some(origin) if context.allowed.contains(&origin) => ok,
+            Some(origin) => Err(Error::with_source(
+                StatusCode::FORBIDDEN,
+                format!("embedding by `{}` is not allowed", origin),
+            )
+            .into()),
+            None => Err(Error::with_source(StatusCode::FORBIDDEN, "invalid Referer header").into()),
+        }
+    }
+}
+
+/// Configures the [`HotlinkProtection`] guard.
+///
+/// [`HotlinkProtection`]: struct.HotlinkProtection.html
+#[derive(Debug, Clone)]
+pub struct HotlinkProtectionConfig {
+    allowed: Vec<String>,
+    allow_missing: bool,
+}
+
+impl HotlinkProtectionConfig {
+    /// Creates a configuration that doesn't allow any referring origin, but
+    /// allows requests without a `Referer`.
+    pub fn new() -> Self {
+        Self {
+            allowed: Vec::new(),
+            allow_missing: true,
+        }
+    }
+
+    /// Allows requests referred by `origin`.
+    ///
+    /// The origin consists of the scheme, host and optional port (eg.
+    /// `https://example.com` or `http://localhost:8080`). Pages of the site
+    /// itself also send a `Referer`, so its own origin should usually be
+    /// allowed.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `origin` isn't a valid origin.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        let normalized = match self::origin(origin) {
+            Some(normalized) => normalized,
+            None => panic!("invalid origin `{}`", origin),
+        };
+        self.allowed.push(normalized);
+        self
+    }
+
+    /// Sets whether requests without a `Referer` header are allowed.
+    ///
+    /// This is `true` by default, since browsers omit the header depending on
+    /// the `Referrer-Policy` and user privacy settings.
+    pub fn allow_missing_referer(mut self, allow: bool) -> Self {
+        self.allow_missing = allow;
+        self
+    }
+}
+
+impl Default for HotlinkProtectionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl_request_context!(HotlinkProtectionConfig);
+
+/// Extracts the normalized origin (`scheme://host[:port]`) from a URL.
+///
+/// The scheme and host are converted to lowercase and default ports are
+/// removed. Returns `None` if `url` doesn't start with a scheme and authority.
+fn origin(url: &str) -> Option<String> {
+    let sep = url.find("://")?;
+    let scheme = url[..sep].to_ascii_lowercase();
+    if scheme.is_empty() {
+        return None;
+    }
+
+    let rest = &url[sep + 3..];
+    let authority = &rest[..rest.find(&['/', '?', '#'][..]).unwrap_or(rest.len())];
+    // Strip credentials (`user:password@`)
+    let host = match authority.rfind('@') {
+        Some(at) => &authority[at + 1..],
+        None => authority,
+    };
+    if host.is_empty() {
+        return None;
+    }
+
+    let host = host.to_ascii_lowercase();
+    let default_port = match &*scheme {
+        "http" => Some(":80"),
+        "https" => Some(":443"),
+        _ => None,
+    };
+    let host = match default_port {
+        Some(port) if host.ends_with(port) => &host[..host.len() - port.len()],
+        _ => &host,
+    };
+
+    Some(format!("{}://{}", scheme, host))
+}
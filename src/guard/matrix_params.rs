@@ -0,0 +1,110 @@
+use crate::{matrix, BoxedError, Guard, NoContext};
+use std::sync::Arc;
+
+/// The matrix parameters of the request path, such as `color=red` in
+/// `/cars;color=red`.
+///
+/// Routes only match paths with matrix parameters if the type is marked with
+/// `#[strip_matrix_params]`, which removes the parameters before the path is
+/// matched against the routes. This guard can then be used to read them.
+///
+/// Parameters are associated with the index of the path segment they're
+/// attached to, starting at 0 for the first segment after the leading `/`.
+/// Names and values are not percent-decoded, and a parameter without a value
+/// (`;featured`) has an empty value.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::MatrixParams};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// #[strip_matrix_params]
+/// enum Route {
+///     #[get("/cars/{id}")]
+///     Car {
+///         id: u32,
+///         params: MatrixParams,
+///     },
+/// }
+///
+/// let request = http::Request::get("/cars;color=red/17;year=2019")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Car { id, params } = Route::from_request_sync(request, NoContext).unwrap();
+///
+/// assert_eq!(id, 17);
+/// assert_eq!(params.get("color"), Some("red"));
+/// assert_eq!(params.segment(1).collect::<Vec<_>>(), vec![("year", "2019")]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MatrixParams {
+    /// `(segment index, name, value)`, in the order they appear in the path.
+    params: Vec<(usize, String, String)>,
+}
+
+impl MatrixParams {
+    /// Collects the matrix parameters of all segments in `path`.
+    pub fn parse(path: &str) -> Self {
+        let segments = path.trim_start_matches('/').split('/').enumerate();
+        let params = segments
+            .filter_map(|(index, segment)| {
+                matrix::split_segment(segment)
+                    .1
+                    .map(|params| (index, params))
+            })
+            .flat_map(|(index, params)| {
+                matrix::parse_params(params)
+                    .map(move |(name, value)| (index, name.to_string(), value.to_string()))
+            })
+            .collect();
+        Self { params }
+    }
+
+    /// Returns the value of the first parameter called `name`, in any segment.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(_, n, _)| n == name)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    /// Returns the values of all parameters called `name`, in any segment.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.params
+            .iter()
+            .filter(move |(_, n, _)| n == name)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    /// Returns the `(name, value)` pairs of the parameters attached to the
+    /// segment at `index`.
+    pub fn segment(&self, index: usize) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .filter(move |(i, _, _)| *i == index)
+            .map(|(_, name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns all parameters as `(segment index, name, value)`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str, &str)> {
+        self.params
+            .iter()
+            .map(|(index, name, value)| (*index, name.as_str(), value.as_str()))
+    }
+
+    /// Returns whether the path has no matrix parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+impl Guard for MatrixParams {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        Ok(Self::parse(request.uri().path()))
+    }
+}
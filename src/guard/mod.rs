@@ -0,0 +1,105 @@
+//! Provides reusable [`Guard`] implementations for common tasks.
+//!
+//! Guards that need configuration obtain it from their [`Guard::Context`]. The
+//! configuration types provided here implement [`RequestContext`], so they can
+//! either be used directly as the context of a `FromRequest` implementor, or be
+//! embedded in an application-specific context using `#[as_ref]`:
+//!
+//! ```
+//! use hyperdrive::{FromRequest, RequestContext, guard::{ApiKey, ApiKeyConfig}};
+//!
+//! #[derive(RequestContext)]
+//! struct MyContext {
+//!     #[as_ref]
+//!     api_keys: ApiKeyConfig,
+//! }
+//!
+//! #[derive(FromRequest)]
+//! #[context(MyContext)]
+//! enum Route {
+//!     #[get("/reports")]
+//!     Reports {
+//!         key: ApiKey,
+//!     },
+//! }
+//! ```
+//!
+//! [`Guard`]: ../trait.Guard.html
+//! [`Guard::Context`]: ../trait.Guard.html#associatedtype.Context
+//! [`RequestContext`]: ../trait.RequestContext.html
+
+mod accept_encoding;
+mod api_key;
+mod content_encoding;
+#[cfg(feature = "r2d2")]
+mod db;
+mod extension;
+mod hotlink;
+mod matrix_params;
+mod prefer;
+mod remote_addr;
+mod request_sanity;
+mod session;
+mod subdomain;
+mod trace_context;
+
+pub use self::accept_encoding::*;
+pub use self::api_key::*;
+pub use self::content_encoding::*;
+#[cfg(feature = "r2d2")]
+pub use self::db::*;
+pub use self::extension::*;
+pub use self::hotlink::*;
+pub use self::matrix_params::*;
+pub use self::prefer::*;
+pub use self::remote_addr::*;
+pub use self::request_sanity::*;
+pub use self::session::*;
+pub use self::subdomain::*;
+pub use self::trace_context::*;
+
+use std::sync::Arc;
+
+/// Links a request rebuilt by the provided `FromRequest::from_request_and_body`
+/// to the request it was created from.
+///
+/// Request extensions can't be cloned, so the rebuilt request can't contain
+/// the extensions inserted by the services (or by the user). Instead, the
+/// extension lookups in this crate use [`request_extension`], which falls back
+/// to the extensions of the original request.
+pub(crate) struct OriginalRequest(pub(crate) Arc<http::Request<()>>);
+
+/// Returns the extension of type `T` of `request`, or of the request it was
+/// rebuilt from.
+pub(crate) fn request_extension<T, B>(request: &http::Request<B>) -> Option<&T>
+where
+    T: Send + Sync + 'static,
+{
+    match request.extensions().get::<T>() {
+        Some(value) => Some(value),
+        None => request
+            .extensions()
+            .get::<OriginalRequest>()
+            .and_then(|original| request_extension(&original.0)),
+    }
+}
+
+/// Formats `bytes` as lowercase hexadecimal digits.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes hexadecimal digits created by `encode_hex`.
+///
+/// Returns `None` if `s` contains invalid digits or an odd number of them.
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
@@ -0,0 +1,410 @@
+use crate::{BoxedError, Guard, NoContext};
+use http::{header::HeaderName, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The preferences sent by the client in `Prefer` headers ([RFC 7240]).
+///
+/// The registered preferences (`return`, `wait`, `respond-async` and
+/// `handling`) are available through typed accessors. All other preferences
+/// are retained and can be inspected with [`get`] and [`others`].
+///
+/// Requests without a `Prefer` header result in an empty set of preferences.
+/// Since preferences are only hints, malformed entries are skipped instead of
+/// failing the request. If a preference is specified more than once, only the
+/// first occurrence is considered, as mandated by the RFC.
+///
+/// Use [`PreferenceApplied`] to tell the client which preferences were honored.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::{Prefer, Return}};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[post("/items")]
+///     Create {
+///         prefer: Prefer,
+///     },
+/// }
+///
+/// let request = http::Request::post("/items")
+///     .header("Prefer", "return=minimal, wait=10")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Create { prefer } = Route::from_request_sync(request, NoContext).unwrap();
+///
+/// assert_eq!(prefer.return_(), Some(Return::Minimal));
+/// assert_eq!(prefer.wait(), Some(std::time::Duration::from_secs(10)));
+/// assert!(!prefer.respond_async());
+/// ```
+///
+/// [RFC 7240]: https://tools.ietf.org/html/rfc7240
+/// [`get`]: #method.get
+/// [`others`]: #method.others
+/// [`PreferenceApplied`]: struct.PreferenceApplied.html
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Prefer {
+    preferences: Vec<Preference>,
+}
+
+/// The names of the preferences registered by RFC 7240.
+const REGISTERED: &[&str] = &["return", "wait", "respond-async", "handling"];
+
+impl Prefer {
+    /// Parses the preferences contained in a list of `Prefer` header values.
+    pub fn parse<'a, I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = &'a HeaderValue>,
+    {
+        let mut preferences = Vec::<Preference>::new();
+        let elements = values
+            .into_iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| split_quoted(value, ','));
+
+        for element in elements {
+            if let Some(preference) = Preference::parse(element) {
+                if preferences.iter().all(|p| p.name != preference.name) {
+                    preferences.push(preference);
+                }
+            }
+        }
+
+        Self { preferences }
+    }
+
+    /// Returns whether the client didn't express any preferences.
+    pub fn is_empty(&self) -> bool {
+        self.preferences.is_empty()
+    }
+
+    /// Returns an iterator over all preferences, in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = &Preference> {
+        self.preferences.iter()
+    }
+
+    /// Returns the preference with the given name.
+    ///
+    /// Preference names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&Preference> {
+        self.preferences
+            .iter()
+            .find(|preference| preference.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns an iterator over all preferences that aren't registered in RFC
+    /// 7240.
+    pub fn others(&self) -> impl Iterator<Item = &Preference> {
+        self.preferences
+            .iter()
+            .filter(|preference| !REGISTERED.contains(&&*preference.name))
+    }
+
+    /// Returns the `return` preference.
+    ///
+    /// Returns `None` if the preference is missing or has an unknown value.
+    pub fn return_(&self) -> Option<Return> {
+        let value = self.get("return")?.value()?;
+        if value.eq_ignore_ascii_case("minimal") {
+            Some(Return::Minimal)
+        } else if value.eq_ignore_ascii_case("representation") {
+            Some(Return::Representation)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the time the client is willing to wait for a synchronous
+    /// response, as specified by the `wait` preference.
+    pub fn wait(&self) -> Option<Duration> {
+        let value = self.get("wait")?.value()?;
+        value.parse().ok().map(Duration::from_secs)
+    }
+
+    /// Returns whether the client prefers an asynchronous response
+    /// (`respond-async`).
+    pub fn respond_async(&self) -> bool {
+        self.get("respond-async").is_some()
+    }
+
+    /// Returns the `handling` preference.
+    ///
+    /// Returns `None` if the preference is missing or has an unknown value.
+    pub fn handling(&self) -> Option<Handling> {
+        let value = self.get("handling")?.value()?;
+        if value.eq_ignore_ascii_case("strict") {
+            Some(Handling::Strict)
+        } else if value.eq_ignore_ascii_case("lenient") {
+            Some(Handling::Lenient)
+        } else {
+            None
+        }
+    }
+}
+
+impl Guard for Prefer {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        Ok(Self::parse(request.headers().get_all("prefer")))
+    }
+}
+
+/// A single preference sent in a `Prefer` header.
+///
+/// This includes the preference's name, its optional value and any
+/// parameters. Names are converted to lowercase, while values are kept as-is
+/// (with quotes removed).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preference {
+    name: String,
+    value: Option<String>,
+    params: Vec<(String, Option<String>)>,
+}
+
+impl Preference {
+    /// Parses a single list element of a `Prefer` header.
+    ///
+    /// Returns `None` if the preference's name isn't a valid token.
+    fn parse(element: &str) -> Option<Self> {
+        let mut parts = split_quoted(element, ';').into_iter();
+        let (name, value) = parse_pair(parts.next()?)?;
+        let params = parts.filter_map(parse_pair).collect();
+
+        Some(Self {
+            name,
+            value,
+            params,
+        })
+    }
+
+    /// Returns the name of the preference, in lowercase.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value of the preference.
+    ///
+    /// An empty value (`foo=""`) is equivalent to no value (`foo`), so this
+    /// returns `None` in both cases.
+    pub fn value(&self) -> Option<&str> {
+        match &self.value {
+            Some(value) if !value.is_empty() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the parameters of the preference, as name/value pairs.
+    ///
+    /// Parameter names are converted to lowercase.
+    pub fn params(&self) -> &[(String, Option<String>)] {
+        &self.params
+    }
+}
+
+/// The value of the `return` preference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Return {
+    /// `return=minimal`: The client only needs a minimal response.
+    Minimal,
+    /// `return=representation`: The client wants the current representation
+    /// of the affected resource in the response.
+    Representation,
+}
+
+/// The value of the `handling` preference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Handling {
+    /// `handling=strict`: The server should reject requests with any invalid
+    /// or unknown parts.
+    Strict,
+    /// `handling=lenient`: The server should try to process the request even
+    /// if parts of it are invalid.
+    Lenient,
+}
+
+/// Builds a `Preference-Applied` header value.
+///
+/// Servers use this header to tell clients which of their preferences were
+/// honored.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::guard::{PreferenceApplied, Return};
+/// use http::Response;
+/// use hyper::Body;
+///
+/// let mut response = Response::new(Body::empty());
+/// if let Some((name, value)) = PreferenceApplied::new().return_(Return::Minimal).header() {
+///     response.headers_mut().insert(name, value);
+/// }
+///
+/// assert_eq!(response.headers()["preference-applied"], "return=minimal");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PreferenceApplied {
+    applied: Vec<String>,
+}
+
+impl PreferenceApplied {
+    /// Creates an empty `Preference-Applied` builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the given `return` preference as applied.
+    pub fn return_(self, value: Return) -> Self {
+        let value = match value {
+            Return::Minimal => "return=minimal",
+            Return::Representation => "return=representation",
+        };
+        self.raw(value.to_string())
+    }
+
+    /// Marks the `respond-async` preference as applied.
+    pub fn respond_async(self) -> Self {
+        self.raw("respond-async".to_string())
+    }
+
+    /// Marks the given `handling` preference as applied.
+    pub fn handling(self, value: Handling) -> Self {
+        let value = match value {
+            Handling::Strict => "handling=strict",
+            Handling::Lenient => "handling=lenient",
+        };
+        self.raw(value.to_string())
+    }
+
+    /// Marks the `wait` preference as applied.
+    pub fn wait(self, wait: Duration) -> Self {
+        self.raw(format!("wait={}", wait.as_secs()))
+    }
+
+    /// Marks an arbitrary preference received from the client as applied.
+    ///
+    /// The preference's name and value are echoed back. Parameters are not
+    /// included, since the `Preference-Applied` header doesn't allow them.
+    pub fn preference(self, preference: &Preference) -> Self {
+        let entry = match preference.value() {
+            Some(value) => format!("{}={}", preference.name, quote(value)),
+            None => preference.name.clone(),
+        };
+        self.raw(entry)
+    }
+
+    fn raw(mut self, entry: String) -> Self {
+        self.applied.push(entry);
+        self
+    }
+
+    /// Returns the `Preference-Applied` header name and value.
+    ///
+    /// Returns `None` if no preference was marked as applied, in which case
+    /// the header shouldn't be sent.
+    pub fn header(&self) -> Option<(HeaderName, HeaderValue)> {
+        if self.applied.is_empty() {
+            return None;
+        }
+
+        // Values are either tokens or quoted strings containing only visible
+        // ASCII, so this can't fail.
+        let value = HeaderValue::from_str(&self.applied.join(", "))
+            .expect("invalid Preference-Applied value");
+        Some((HeaderName::from_static("preference-applied"), value))
+    }
+}
+
+/// Splits `s` at every occurrence of `sep` that isn't inside a quoted string.
+///
+/// Empty elements are removed, and all elements are trimmed.
+fn split_quoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == sep && !in_quotes {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Parses a `token [ BWS "=" BWS word ]` pair.
+///
+/// The name is converted to lowercase and the value is unquoted. Returns
+/// `None` if the name isn't a valid token.
+fn parse_pair(s: &str) -> Option<(String, Option<String>)> {
+    let (name, value) = match s.find('=') {
+        Some(eq) => (s[..eq].trim(), Some(unquote(s[eq + 1..].trim()))),
+        None => (s, None),
+    };
+
+    if name.is_empty() || !name.chars().all(is_token_char) {
+        return None;
+    }
+
+    Some((name.to_ascii_lowercase(), value))
+}
+
+/// Removes the quotes and escapes from a quoted string.
+///
+/// Tokens are returned as-is.
+fn unquote(s: &str) -> String {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return s.to_string();
+    }
+
+    let mut unquoted = String::new();
+    let mut escaped = false;
+    for c in s[1..s.len() - 1].chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            unquoted.push(c);
+            escaped = false;
+        }
+    }
+    unquoted
+}
+
+/// Turns `s` into a quoted string, unless it is a valid token.
+fn quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(is_token_char) {
+        return s.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns whether `c` may appear in a `token` (RFC 7230, Section 3.2.6).
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
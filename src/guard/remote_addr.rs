@@ -0,0 +1,91 @@
+use super::{request_extension, Extension};
+use crate::{service::ConnectInfo, BoxedError, Error, Guard, NoContext};
+use http::StatusCode;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// The address of the remote peer that sent the request.
+///
+/// This is taken from the [`ConnectInfo`] request extension, which is inserted
+/// by services served via [`ServiceExt::with_connect_info`]. If the request
+/// doesn't contain it (eg. because the service was served without that adapter
+/// or called directly), the guard fails with a `500 Internal Server Error`
+/// explaining that the address wasn't recorded. Use `Option<RemoteAddr>` if it
+/// might be absent.
+///
+/// Note that this is the address of the TCP connection. When the server runs
+/// behind a reverse proxy or load balancer, this is the address of the proxy,
+/// not that of the client. The client address is then usually passed in the
+/// `Forwarded` or `X-Forwarded-For` header instead, which should only be
+/// trusted when the remote address belongs to a known proxy.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::RemoteAddr, service::ConnectInfo};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index {
+///         addr: RemoteAddr,
+///     },
+/// }
+///
+/// let addr = "127.0.0.1:1234".parse().unwrap();
+/// let mut request = http::Request::get("/").body(Body::empty()).unwrap();
+/// request.extensions_mut().insert(ConnectInfo(addr));
+///
+/// let Route::Index { addr: remote } = Route::from_request_sync(request, NoContext).unwrap();
+/// assert_eq!(remote.0, addr);
+/// assert!(remote.ip().is_loopback());
+/// ```
+///
+/// [`ConnectInfo`]: ../service/struct.ConnectInfo.html
+/// [`ServiceExt::with_connect_info`]: ../service/trait.ServiceExt.html#tymethod.with_connect_info
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteAddr(pub SocketAddr);
+
+impl RemoteAddr {
+    /// Returns the remote address.
+    pub fn into_inner(self) -> SocketAddr {
+        self.0
+    }
+}
+
+impl Guard for RemoteAddr {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        match request_extension::<ConnectInfo, _>(request) {
+            Some(ConnectInfo(addr)) => Ok(RemoteAddr(*addr)),
+            None => Err(Error::with_source(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "the remote address is unknown (serve the service via \
+                 `ServiceExt::with_connect_info` to record it)",
+            )
+            .into()),
+        }
+    }
+}
+
+impl Guard for Option<RemoteAddr> {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let info = Option::<Extension<ConnectInfo>>::from_request(request, context)?;
+        Ok(info.map(|Extension(ConnectInfo(addr))| RemoteAddr(addr)))
+    }
+}
+
+impl Deref for RemoteAddr {
+    type Target = SocketAddr;
+
+    fn deref(&self) -> &SocketAddr {
+        &self.0
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{BoxedError, Error, Guard, NoContext};
+use http::{header, HeaderMap};
+use std::sync::Arc;
+
+/// Rejects requests whose headers are malformed or ambiguous in a way that
+/// would make reading the request unreliable.
+///
+/// Such requests are usually produced by broken clients or by attempts at
+/// request smuggling. Without this guard, they would fail later while reading
+/// the body, with an opaque error that drops the connection. The guard
+/// checks that:
+///
+/// * `chunked` is the final transfer coding in `Transfer-Encoding` and is
+///   applied only once, as required by [RFC 7230].
+/// * `Transfer-Encoding` lists no transfer codings other than `chunked`,
+///   since no others are decoded.
+/// * A request with `Transfer-Encoding` has no `Content-Length` header.
+/// * All `Content-Length` headers contain the same, valid number.
+///
+/// Headers using obsolete line folding (a value continued on the next line)
+/// don't need to be checked: hyper's parser already rejects them with a `400
+/// Bad Request` response, and `http::HeaderValue` can't contain line breaks.
+///
+/// Requests with an unsupported transfer coding are rejected with a `501 Not
+/// Implemented` error of kind `ErrorKind::UnsupportedTransferEncoding`. All
+/// other violations result in a `400 Bad Request` error of kind
+/// `ErrorKind::MalformedRequest`.
+///
+/// The body wrappers in the [`body`] module perform the same checks before
+/// reading the body, so this guard is only needed on routes that don't have a
+/// `#[body]` field, or whose body type doesn't perform them.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, ErrorKind, guard::RequestSanity};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[post("/events")]
+///     Events {
+///         _sanity: RequestSanity,
+///     },
+/// }
+///
+/// let request = http::Request::post("/events")
+///     .header("Transfer-Encoding", "chunked, gzip")
+///     .body(Body::empty())
+///     .unwrap();
+/// let error = Route::from_request_sync(request, NoContext).err().unwrap();
+/// let error = error.downcast::<hyperdrive::Error>().unwrap();
+///
+/// assert_eq!(error.kind(), ErrorKind::MalformedRequest);
+/// ```
+///
+/// [RFC 7230]: https://tools.ietf.org/html/rfc7230#section-3.3.3
+/// [`body`]: ../body/index.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RequestSanity;
+
+impl Guard for RequestSanity {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        check_request(request.headers())?;
+        Ok(RequestSanity)
+    }
+}
+
+/// Performs the checks of `RequestSanity` on the request `headers`.
+pub(crate) fn check_request(headers: &HeaderMap) -> Result<(), BoxedError> {
+    if headers.contains_key(header::TRANSFER_ENCODING) {
+        check_transfer_encoding(headers)?;
+        if headers.contains_key(header::CONTENT_LENGTH) {
+            return Err(Error::malformed_request(
+                "request has both a Transfer-Encoding and a Content-Length header",
+            )
+            .into());
+        }
+    }
+
+    let mut lengths = headers.get_all(header::CONTENT_LENGTH).iter().map(|value| {
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    });
+    if let Some(first) = lengths.next() {
+        if first.is_none() || lengths.any(|length| length != first) {
+            return Err(Error::malformed_request("invalid Content-Length header").into());
+        }
+    }
+
+    Ok(())
+}
+
+fn check_transfer_encoding(headers: &HeaderMap) -> Result<(), BoxedError> {
+    let mut codings = Vec::new();
+    for value in headers.get_all(header::TRANSFER_ENCODING) {
+        let value = value
+            .to_str()
+            .map_err(|_| Error::malformed_request("invalid Transfer-Encoding header"))?;
+        codings.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|coding| !coding.is_empty())
+                .map(str::to_ascii_lowercase),
+        );
+    }
+
+    match codings.split_last() {
+        Some((last, rest)) if last == "chunked" => {
+            if rest.iter().any(|coding| coding == "chunked") {
+                return Err(Error::malformed_request(
+                    "transfer coding `chunked` is applied more than once",
+                )
+                .into());
+            }
+            match rest.first() {
+                Some(coding) => Err(Error::unsupported_transfer_encoding(format!(
+                    "unsupported transfer coding `{}`",
+                    coding
+                ))
+                .into()),
+                None => Ok(()),
+            }
+        }
+        _ => Err(Error::malformed_request("`chunked` must be the final transfer coding").into()),
+    }
+}
@@ -0,0 +1,554 @@
+use super::{decode_hex, encode_hex, request_extension};
+use crate::{BoxedError, DefaultFuture, Error, Guard};
+use futures::{future, Future, IntoFuture};
+use hmac::{Hmac, Mac};
+use http::{header, HeaderValue, Response, StatusCode};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Server-side session data, identified by a signed cookie.
+///
+/// The session ID is stored in an `HttpOnly` cookie whose name is configured in
+/// the [`SessionConfig`] context. The cookie is signed with HMAC-SHA256, so
+/// clients can't forge or guess session IDs. The data itself is serialized to
+/// JSON and kept in the [`SessionStore`] of the context.
+///
+/// If the request doesn't carry a valid session cookie, or the session is
+/// unknown to the store, the guard creates a new, empty session. New sessions
+/// are only persisted (and a cookie is only sent) once data is stored in them
+/// using [`set`].
+///
+/// When the privileges of a session change, most importantly when a user logs
+/// in, call [`renew`] to move the session to a fresh ID. Otherwise, an attacker
+/// who planted a session cookie in the user's browser beforehand could use it
+/// to access the logged-in session (session fixation).
+///
+/// Changes to the session are written back by the service adapter returned by
+/// [`ServiceExt::sessions`], after the handler has produced a response. Using
+/// this guard without that adapter fails the request with a `500 Internal
+/// Server Error`.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, guard::{Session, SessionConfig, MemoryStore}};
+/// use hyperdrive::service::{AsyncService, ServiceExt};
+/// use hyper::{Body, Response};
+/// use futures::IntoFuture;
+///
+/// #[derive(FromRequest)]
+/// #[context(SessionConfig)]
+/// enum Route {
+///     #[get("/")]
+///     Index {
+///         session: Session<u32>,
+///     },
+/// }
+///
+/// let config = SessionConfig::new(MemoryStore::new(), "a long, randomly generated key");
+///
+/// let service = AsyncService::with_context(|route: Route, _| {
+///     match route {
+///         Route::Index { mut session } => {
+///             let visits = session.get().cloned().unwrap_or(0) + 1;
+///             let response = session.set(visits).map(|()| {
+///                 Response::new(Body::from(format!("Visit #{}", visits)))
+///             });
+///             response.into_future()
+///         }
+///     }
+/// }, config).sessions();
+/// ```
+///
+/// [`SessionConfig`]: struct.SessionConfig.html
+/// [`SessionStore`]: trait.SessionStore.html
+/// [`set`]: #method.set
+/// [`renew`]: #method.renew
+/// [`ServiceExt::sessions`]: ../service/trait.ServiceExt.html#tymethod.sessions
+pub struct Session<T> {
+    data: Option<T>,
+    state: Arc<Mutex<Option<SessionState>>>,
+}
+
+impl<T> Session<T>
+where
+    T: Serialize,
+{
+    /// Returns the data stored in the session.
+    ///
+    /// Returns `None` if the session is new or has been destroyed.
+    pub fn get(&self) -> Option<&T> {
+        self.data.as_ref()
+    }
+
+    /// Stores `data` in the session, replacing any previous data.
+    ///
+    /// The data is written to the [`SessionStore`] after the response has been
+    /// produced. If the session is new, the session cookie is sent to the
+    /// client along with the response.
+    ///
+    /// Returns an error if `data` can not be serialized.
+    ///
+    /// [`SessionStore`]: trait.SessionStore.html
+    pub fn set(&mut self, data: T) -> Result<(), BoxedError> {
+        let json = serde_json::to_string(&data)?;
+        self.with_state(|state| state.change = Some(Change::Save(json)));
+        self.data = Some(data);
+        Ok(())
+    }
+
+    /// Moves the session to a new, randomly generated ID.
+    ///
+    /// The data is kept, the session stored under the old ID is removed from
+    /// the [`SessionStore`], and a cookie with the new ID is sent to the client
+    /// along with the response. This should be called whenever the user logs
+    /// in (or otherwise gains privileges), so that a session ID known to
+    /// someone else before that point becomes useless.
+    ///
+    /// Returns an error if the data of the session can not be serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use hyperdrive::{BoxedError, guard::Session};
+    /// fn login(session: &mut Session<String>, user: String) -> Result<(), BoxedError> {
+    ///     session.renew()?;
+    ///     session.set(user)
+    /// }
+    /// ```
+    ///
+    /// [`SessionStore`]: trait.SessionStore.html
+    pub fn renew(&mut self) -> Result<(), BoxedError> {
+        let json = match &self.data {
+            Some(data) => Some(serde_json::to_string(data)?),
+            None => None,
+        };
+        self.with_state(|state| state.renew(json));
+        Ok(())
+    }
+
+    /// Destroys the session.
+    ///
+    /// The session is removed from the [`SessionStore`] and the client is
+    /// instructed to delete its session cookie. This is typically done when
+    /// the user logs out.
+    ///
+    /// [`SessionStore`]: trait.SessionStore.html
+    pub fn destroy(&mut self) {
+        self.with_state(|state| state.change = Some(Change::Destroy));
+        self.data = None;
+    }
+
+    fn with_state(&self, f: impl FnOnce(&mut SessionState)) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(state) = &mut *state {
+            f(state);
+        }
+    }
+}
+
+impl<T> Guard for Session<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    type Context = SessionConfig;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let slot = match request_extension::<SessionSlot, _>(request) {
+            Some(slot) => slot.0.clone(),
+            None => {
+                return Error::with_source(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "`Session` guard used without the `ServiceExt::sessions` adapter",
+                )
+                .into_future();
+            }
+        };
+
+        // Reuse the session of any other `Session` guard in the same route, so
+        // that all of them refer to the same ID.
+        let existing = slot.lock().unwrap().as_ref().map(|state| state.id.clone());
+        let id = existing.or_else(|| context.session_id(request));
+
+        let id = match id {
+            Some(id) => id,
+            None => {
+                *slot.lock().unwrap() = Some(SessionState::new(context.clone()));
+                return Box::new(
+                    Ok(Session {
+                        data: None,
+                        state: slot,
+                    })
+                    .into_future(),
+                );
+            }
+        };
+
+        let config = context.clone();
+        Box::new(context.store.load(&id).map(move |data| {
+            let mut state = slot.lock().unwrap();
+            let data = match data {
+                Some(json) => {
+                    if state.is_none() {
+                        *state = Some(SessionState {
+                            config,
+                            id,
+                            has_cookie: true,
+                            previous_id: None,
+                            change: None,
+                        });
+                    }
+
+                    // Data that doesn't match `T` (eg. after changing the
+                    // session type) is treated like an empty session.
+                    serde_json::from_str(&json).ok()
+                }
+                None => {
+                    // The session has expired or was destroyed. Start a new
+                    // one with a fresh ID instead of reusing the client's.
+                    if state.is_none() {
+                        *state = Some(SessionState::new(config));
+                    }
+                    None
+                }
+            };
+            drop(state);
+
+            Session { data, state: slot }
+        }))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Session<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't print the session ID, since it grants access to the session
+        f.debug_struct("Session").field("data", &self.data).finish()
+    }
+}
+
+/// Storage backend for [`Session`] data.
+///
+/// Session data is passed to the store serialized as a JSON string. Since every
+/// operation returns a future, implementations are free to perform I/O, for
+/// example to share sessions between several servers via a database.
+///
+/// [`MemoryStore`] provides a simple implementation that keeps all sessions in
+/// memory.
+///
+/// [`Session`]: struct.Session.html
+/// [`MemoryStore`]: struct.MemoryStore.html
+pub trait SessionStore: Send + Sync + 'static {
+    /// Loads the data of the session with the given ID.
+    ///
+    /// Resolves to `None` if there is no such session.
+    fn load(&self, id: &str) -> DefaultFuture<Option<String>, BoxedError>;
+
+    /// Stores `data` as the data of the session with the given ID, creating the
+    /// session if it doesn't exist yet.
+    fn save(&self, id: &str, data: String) -> DefaultFuture<(), BoxedError>;
+
+    /// Deletes the session with the given ID.
+    fn destroy(&self, id: &str) -> DefaultFuture<(), BoxedError>;
+}
+
+impl<S: SessionStore> SessionStore for Arc<S> {
+    fn load(&self, id: &str) -> DefaultFuture<Option<String>, BoxedError> {
+        (**self).load(id)
+    }
+
+    fn save(&self, id: &str, data: String) -> DefaultFuture<(), BoxedError> {
+        (**self).save(id, data)
+    }
+
+    fn destroy(&self, id: &str) -> DefaultFuture<(), BoxedError> {
+        (**self).destroy(id)
+    }
+}
+
+/// A [`SessionStore`] that keeps all sessions in memory.
+///
+/// All sessions are lost when the process exits, and sessions are never
+/// expired, so this is mostly useful for testing and small applications.
+///
+/// [`SessionStore`]: trait.SessionStore.html
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty `MemoryStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> DefaultFuture<Option<String>, BoxedError> {
+        let data = self.sessions.lock().unwrap().get(id).cloned();
+        Box::new(Ok(data).into_future())
+    }
+
+    fn save(&self, id: &str, data: String) -> DefaultFuture<(), BoxedError> {
+        self.sessions.lock().unwrap().insert(id.to_string(), data);
+        Box::new(Ok(()).into_future())
+    }
+
+    fn destroy(&self, id: &str) -> DefaultFuture<(), BoxedError> {
+        self.sessions.lock().unwrap().remove(id);
+        Box::new(Ok(()).into_future())
+    }
+}
+
+/// Configures the [`Session`] guard.
+///
+/// The configuration holds the [`SessionStore`] to use, the name of the session
+/// cookie (`session` by default), and the keys used to sign it.
+///
+/// Cookies are always signed with the key passed to [`new`]. Additional keys
+/// can be added with [`old_key`]; they are only used to verify cookies, which
+/// allows rotating keys without logging out every user.
+///
+/// [`Session`]: struct.Session.html
+/// [`SessionStore`]: trait.SessionStore.html
+/// [`new`]: #method.new
+/// [`old_key`]: #method.old_key
+#[derive(Clone)]
+pub struct SessionConfig {
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    keys: Vec<Vec<u8>>,
+    secure: bool,
+}
+
+impl SessionConfig {
+    /// Creates a configuration storing sessions in `store` and signing cookies
+    /// with `key`.
+    ///
+    /// The key must be kept secret. It should be randomly generated and at
+    /// least 32 bytes long.
+    pub fn new<S: SessionStore>(store: S, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "session".to_string(),
+            keys: vec![key.into()],
+            secure: false,
+        }
+    }
+
+    /// Sets the name of the session cookie.
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// Adds a key that is accepted when verifying session cookies, but not
+    /// used to sign new ones.
+    pub fn old_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Sets whether the session cookie should have the `Secure` attribute.
+    ///
+    /// Browsers only send `Secure` cookies over HTTPS connections. This should
+    /// be enabled for any site served over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Returns the session ID stored in the request's session cookie, if the
+    /// cookie is present and carries a valid signature.
+    fn session_id(&self, request: &http::Request<()>) -> Option<String> {
+        let value = request
+            .headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| {
+                let cookie = cookie.trim();
+                let eq = cookie.find('=')?;
+                if cookie[..eq] == *self.cookie_name {
+                    Some(&cookie[eq + 1..])
+                } else {
+                    None
+                }
+            })
+            .next()?;
+
+        let dot = value.find('.')?;
+        let (id, signature) = (&value[..dot], decode_hex(&value[dot + 1..])?);
+        let valid = self.keys.iter().any(|key| {
+            let mut mac = self.mac(key);
+            mac.input(id.as_bytes());
+            mac.verify(&signature).is_ok()
+        });
+
+        if valid {
+            Some(id.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Builds the signed `Set-Cookie` value storing the session ID `id`.
+    fn cookie(&self, id: &str) -> String {
+        let mut mac = self.mac(&self.keys[0]);
+        mac.input(id.as_bytes());
+        let signature = encode_hex(&mac.result().code());
+
+        self.cookie_with_attrs(&format!("{}.{}", id, signature), "")
+    }
+
+    /// Builds a `Set-Cookie` value instructing the client to delete the session
+    /// cookie.
+    fn removal_cookie(&self) -> String {
+        self.cookie_with_attrs("", "; Max-Age=0")
+    }
+
+    fn cookie_with_attrs(&self, value: &str, extra: &str) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax{}{}",
+            self.cookie_name,
+            value,
+            if self.secure { "; Secure" } else { "" },
+            extra,
+        )
+    }
+
+    /// Creates a MAC that also covers the cookie name, so a signed value can't
+    /// be reused in a different cookie.
+    fn mac(&self, key: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any length");
+        mac.input(self.cookie_name.as_bytes());
+        mac.input(b"=");
+        mac
+    }
+}
+
+impl fmt::Debug for SessionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionConfig")
+            .field("store", &"<store>")
+            .field("cookie_name", &self.cookie_name)
+            .field("keys", &"<redacted>")
+            .field("secure", &self.secure)
+            .finish()
+    }
+}
+
+impl_request_context!(SessionConfig);
+
+/// Shared between the `Session` guard and the `ServiceExt::sessions` adapter.
+///
+/// The adapter inserts this into the request extensions, and the guard fills in
+/// the state of the session it loaded.
+#[derive(Clone, Default)]
+pub(crate) struct SessionSlot(Arc<Mutex<Option<SessionState>>>);
+
+impl SessionSlot {
+    /// Persists the changes made to the session and adds the corresponding
+    /// `Set-Cookie` header to `response`.
+    pub(crate) fn finish<B: Send + 'static>(
+        self,
+        mut response: Response<B>,
+    ) -> DefaultFuture<Response<B>, BoxedError> {
+        let state = match self.0.lock().unwrap().take() {
+            Some(state) => state,
+            None => return Box::new(Ok(response).into_future()),
+        };
+
+        let store = &state.config.store;
+        let (store_op, cookie) = match state.change {
+            None => (None, None),
+            Some(Change::Save(data)) => {
+                let cookie = if state.has_cookie {
+                    None
+                } else {
+                    Some(state.config.cookie(&state.id))
+                };
+                (Some(store.save(&state.id, data)), cookie)
+            }
+            // If the session was never stored, there's nothing to destroy
+            Some(Change::Destroy) if !state.has_cookie => (None, None),
+            Some(Change::Destroy) => (
+                Some(store.destroy(&state.id)),
+                Some(state.config.removal_cookie()),
+            ),
+        };
+
+        // The client's cookie still refers to the ID the session was renewed
+        // from, so delete it unless the cookie is replaced anyway.
+        let previous = state.previous_id.as_ref().map(|id| store.destroy(id));
+        let cookie = match (&previous, cookie) {
+            (Some(_), None) => Some(state.config.removal_cookie()),
+            (_, cookie) => cookie,
+        };
+
+        let ops = previous.into_iter().chain(store_op).collect::<Vec<_>>();
+        if ops.is_empty() {
+            return Box::new(Ok(response).into_future());
+        }
+
+        Box::new(future::join_all(ops).and_then(move |_| {
+            if let Some(cookie) = cookie {
+                let cookie = HeaderValue::from_str(&cookie)?;
+                response.headers_mut().append(header::SET_COOKIE, cookie);
+            }
+            Ok(response)
+        }))
+    }
+}
+
+struct SessionState {
+    config: SessionConfig,
+    id: String,
+    /// Whether the client already has a session cookie containing `id`.
+    has_cookie: bool,
+    /// The ID stored in the client's cookie, if the session has been renewed.
+    previous_id: Option<String>,
+    change: Option<Change>,
+}
+
+impl SessionState {
+    /// Creates the state of a new session with a random ID.
+    fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            id: random_id(),
+            has_cookie: false,
+            previous_id: None,
+            change: None,
+        }
+    }
+
+    /// Moves the session to a new ID, saving `data` under it unless the
+    /// session is changed in another way.
+    fn renew(&mut self, data: Option<String>) {
+        let old = std::mem::replace(&mut self.id, random_id());
+        if self.has_cookie {
+            self.previous_id = Some(old);
+            self.has_cookie = false;
+        }
+        if self.change.is_none() {
+            self.change = data.map(Change::Save);
+        }
+    }
+}
+
+fn random_id() -> String {
+    let id: [u8; 16] = rand::thread_rng().gen();
+    encode_hex(&id)
+}
+
+enum Change {
+    Save(String),
+    Destroy,
+}
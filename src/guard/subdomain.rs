@@ -0,0 +1,191 @@
+use crate::{BoxedError, Error, Guard};
+use http::{header, StatusCode};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The subdomain of the configured base domain that the request was sent to.
+///
+/// This is useful for multi-tenant applications that serve each tenant on its
+/// own subdomain (eg. `acme.example.com`). The base domain (`example.com`) is
+/// configured in the [`SubdomainConfig`] context.
+///
+/// The host is taken from the `Host` header, or from the request URI's
+/// authority if there's no `Host` header (as is the case with HTTP/2). Ports
+/// are ignored and the comparison is case-insensitive; the extracted subdomain
+/// is converted to lowercase.
+///
+/// The subdomain is parsed into `T` via `FromStr`, so it can be turned into a
+/// typed tenant ID directly. By default, `T` is `String`.
+///
+/// # Errors
+///
+/// * Requests without a host fail with `400 Bad Request`.
+/// * Requests to a host that isn't a subdomain of the base domain fail with
+///   `404 Not Found`. This includes requests to the base domain itself, and
+///   nested subdomains (`eu.acme.example.com`) unless they are enabled with
+///   [`SubdomainConfig::allow_nested`].
+/// * If `T::from_str` fails, the request fails with `404 Not Found`, just like
+///   with path segments.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, guard::{Subdomain, SubdomainConfig}};
+/// use hyper::Body;
+/// use std::str::FromStr;
+///
+/// struct TenantId(String);
+///
+/// impl FromStr for TenantId {
+///     type Err = &'static str;
+///
+///     fn from_str(s: &str) -> Result<Self, Self::Err> {
+///         if s == "www" {
+///             Err("not a tenant")
+///         } else {
+///             Ok(TenantId(s.to_string()))
+///         }
+///     }
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(SubdomainConfig)]
+/// enum Route {
+///     #[get("/")]
+///     Dashboard {
+///         tenant: Subdomain<TenantId>,
+///     },
+/// }
+///
+/// let request = http::Request::get("/")
+///     .header("Host", "ACME.example.com:8080")
+///     .body(Body::empty())
+///     .unwrap();
+/// let config = SubdomainConfig::new("example.com");
+/// let Route::Dashboard { tenant } = Route::from_request_sync(request, config).unwrap();
+///
+/// assert_eq!(tenant.as_str(), "acme");
+/// assert_eq!(tenant.value().0, "acme");
+/// ```
+///
+/// [`SubdomainConfig`]: struct.SubdomainConfig.html
+/// [`SubdomainConfig::allow_nested`]: struct.SubdomainConfig.html#method.allow_nested
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subdomain<T = String> {
+    raw: String,
+    value: T,
+}
+
+impl<T> Subdomain<T> {
+    /// Returns the subdomain as a string, in lowercase.
+    ///
+    /// For nested subdomains, this contains all labels, separated by dots (eg.
+    /// `eu.acme`).
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns the parsed subdomain.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the parsed subdomain, consuming `self`.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Guard for Subdomain<T>
+where
+    T: FromStr,
+    T::Err: Into<BoxedError>,
+{
+    type Context = SubdomainConfig;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, context: &Self::Context) -> Self::Result {
+        let host = request
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| request.uri().authority_part().map(|auth| auth.as_str()))
+            .ok_or_else(|| Error::with_source(StatusCode::BAD_REQUEST, "missing host"))?;
+
+        let raw = context.subdomain(host).ok_or_else(|| {
+            Error::with_source(
+                StatusCode::NOT_FOUND,
+                format!("host `{}` is not a subdomain of `{}`", host, context.base),
+            )
+        })?;
+
+        match raw.parse() {
+            Ok(value) => Ok(Subdomain { raw, value }),
+            Err(e) => Err(Error::with_source(StatusCode::NOT_FOUND, e).into()),
+        }
+    }
+}
+
+/// Configures the [`Subdomain`] guard.
+///
+/// [`Subdomain`]: struct.Subdomain.html
+#[derive(Debug, Clone)]
+pub struct SubdomainConfig {
+    base: String,
+    allow_nested: bool,
+}
+
+impl SubdomainConfig {
+    /// Creates a configuration extracting subdomains of `base_domain` (eg.
+    /// `example.com`).
+    pub fn new(base_domain: impl Into<String>) -> Self {
+        let base = base_domain.into();
+        Self {
+            base: base.trim_end_matches('.').to_ascii_lowercase(),
+            allow_nested: false,
+        }
+    }
+
+    /// Sets whether subdomains consisting of more than one label (eg.
+    /// `eu.acme.example.com`) are accepted.
+    ///
+    /// This is disabled by default.
+    pub fn allow_nested(mut self, allow: bool) -> Self {
+        self.allow_nested = allow;
+        self
+    }
+
+    /// Extracts the subdomain from `host`, which may contain a port.
+    fn subdomain(&self, host: &str) -> Option<String> {
+        // Strip the port. IPv6 literals contain colons too, but they are never
+        // a subdomain of anything, so we reject them right away.
+        if host.starts_with('[') {
+            return None;
+        }
+        let host = match host.rfind(':') {
+            Some(colon) => &host[..colon],
+            None => host,
+        };
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+        if host.len() <= self.base.len() + 1 || !host.ends_with(&self.base) {
+            return None;
+        }
+        let subdomain = &host[..host.len() - self.base.len()];
+        if !subdomain.ends_with('.') {
+            // `notexample.com`
+            return None;
+        }
+        let subdomain = &subdomain[..subdomain.len() - 1];
+
+        if subdomain.split('.').any(str::is_empty)
+            || (!self.allow_nested && subdomain.contains('.'))
+        {
+            return None;
+        }
+
+        Some(subdomain.to_string())
+    }
+}
+
+impl_request_context!(SubdomainConfig);
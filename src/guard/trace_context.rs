@@ -0,0 +1,268 @@
+use super::{encode_hex, request_extension};
+use crate::{BoxedError, Guard, NoContext};
+use http::HeaderMap;
+use rand::Rng;
+use std::fmt;
+use std::sync::Arc;
+
+/// The [W3C Trace Context] of a request, taken from the `traceparent` and
+/// `tracestate` headers.
+///
+/// The `traceparent` header is validated strictly according to the
+/// specification. If it is missing or invalid, a new trace is started with a
+/// random trace ID, so this guard never fails. In that case, the `tracestate`
+/// header is ignored as well, as mandated by the specification. Otherwise, the
+/// `tracestate` is carried along without being interpreted.
+///
+/// When calling other services, use [`child`] to derive the context to
+/// propagate. Its `Display` implementation produces the `traceparent` header
+/// value.
+///
+/// If the request was dispatched by a service wrapped with
+/// [`ServiceExt::trace_requests`] (requires the `tracing` feature), the guard
+/// returns the same context that was recorded in the request's span.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::TraceContext};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index {
+///         trace: TraceContext,
+///     },
+/// }
+///
+/// let request = http::Request::get("/")
+///     .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+///     .header("tracestate", "congo=t61rcWkgMzE")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Index { trace } = Route::from_request_sync(request, NoContext).unwrap();
+///
+/// assert_eq!(trace.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert!(trace.is_sampled());
+///
+/// // Propagate the trace to an outgoing request
+/// let child = trace.child();
+/// let outgoing = http::Request::get("http://backend/")
+///     .header("traceparent", child.to_string())
+///     .header("tracestate", child.tracestate().unwrap())
+///     .body(())
+///     .unwrap();
+/// ```
+///
+/// [W3C Trace Context]: https://www.w3.org/TR/trace-context/
+/// [`child`]: #method.child
+/// [`ServiceExt::trace_requests`]: ../service/trait.ServiceExt.html#tymethod.trace_requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: String,
+    parent_id: String,
+    flags: u8,
+    tracestate: Option<String>,
+    generated: bool,
+}
+
+impl TraceContext {
+    /// Creates a context that starts a new, unsampled trace.
+    pub fn new() -> Self {
+        Self {
+            trace_id: random_id(16),
+            parent_id: random_id(8),
+            flags: 0,
+            tracestate: None,
+            generated: true,
+        }
+    }
+
+    /// Extracts the trace context from the `traceparent` and `tracestate`
+    /// headers in `headers`.
+    ///
+    /// If `traceparent` is missing or invalid, this starts a new trace (see
+    /// [`new`]).
+    ///
+    /// [`new`]: #method.new
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut traceparent = headers.get_all("traceparent").iter();
+        let value = match (traceparent.next(), traceparent.next()) {
+            (Some(value), None) => value,
+            // Missing, or sent more than once
+            _ => return Self::new(),
+        };
+
+        let mut context = match value.to_str().ok().and_then(parse_traceparent) {
+            Some(context) => context,
+            None => return Self::new(),
+        };
+
+        // `tracestate` may be split across several headers, which are combined
+        // like any other list-valued header.
+        let tracestate = headers
+            .get_all("tracestate")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        if !tracestate.is_empty() {
+            context.tracestate = Some(tracestate.join(","));
+        }
+
+        context
+    }
+
+    /// Returns the trace ID, as 32 lowercase hex digits.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns the ID of the caller's span (the `parent-id` field), as 16
+    /// lowercase hex digits.
+    ///
+    /// For generated contexts, this is a random ID.
+    pub fn parent_id(&self) -> &str {
+        &self.parent_id
+    }
+
+    /// Returns the trace flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns whether the caller may have recorded the trace (the `sampled`
+    /// flag).
+    pub fn is_sampled(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Returns the opaque `tracestate` sent along with the `traceparent`.
+    pub fn tracestate(&self) -> Option<&str> {
+        match &self.tracestate {
+            Some(tracestate) => Some(tracestate),
+            None => None,
+        }
+    }
+
+    /// Returns whether this context was generated because the request didn't
+    /// have a valid `traceparent` header.
+    pub fn is_generated(&self) -> bool {
+        self.generated
+    }
+
+    /// Derives the context to propagate to outgoing requests.
+    ///
+    /// The child belongs to the same trace, but has a new random parent ID
+    /// identifying the current operation. The `tracestate` is kept, and flags
+    /// not defined by the specification are cleared.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            parent_id: random_id(8),
+            flags: self.flags & 0x01,
+            tracestate: self.tracestate.clone(),
+            generated: false,
+        }
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the context as a version `00` `traceparent` header value.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+impl Guard for TraceContext {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        // Reuse the context recorded by `ServiceExt::trace_requests`, if any,
+        // so that generated contexts match the request's span.
+        Ok(match request_extension::<TraceContext, _>(request) {
+            Some(context) => context.clone(),
+            None => Self::from_headers(request.headers()),
+        })
+    }
+}
+
+/// Parses a `traceparent` header value.
+///
+/// Returns `None` if the value is invalid.
+fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let value = value.trim();
+    // version "-" trace-id "-" parent-id "-" trace-flags
+    const LEN: usize = 2 + 1 + 32 + 1 + 16 + 1 + 2;
+
+    if value.len() < LEN || !value.is_ascii() {
+        return None;
+    }
+
+    let version = &value[..2];
+    if !is_lower_hex(version) || version == "ff" {
+        return None;
+    }
+
+    // Future versions may append fields, but version 00 must not
+    if version == "00" && value.len() != LEN {
+        return None;
+    }
+    if value.len() > LEN && value.as_bytes()[LEN] != b'-' {
+        return None;
+    }
+
+    let bytes = value.as_bytes();
+    if bytes[2] != b'-' || bytes[35] != b'-' || bytes[52] != b'-' {
+        return None;
+    }
+
+    let (trace_id, parent_id, flags) = (&value[3..35], &value[36..52], &value[53..55]);
+    if !is_lower_hex(trace_id) || !is_lower_hex(parent_id) || !is_lower_hex(flags) {
+        return None;
+    }
+    if is_zero(trace_id) || is_zero(parent_id) {
+        return None;
+    }
+
+    Some(TraceContext {
+        trace_id: trace_id.to_string(),
+        parent_id: parent_id.to_string(),
+        flags: u8::from_str_radix(flags, 16).ok()?,
+        tracestate: None,
+        generated: false,
+    })
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn is_zero(s: &str) -> bool {
+    s.bytes().all(|b| b == b'0')
+}
+
+/// Generates a random, non-zero ID of `len` bytes, formatted as hex.
+fn random_id(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    loop {
+        let bytes = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+        if bytes.iter().any(|&b| b != 0) {
+            return encode_hex(&bytes);
+        }
+    }
+}
@@ -42,13 +42,62 @@
 //!                 Ok(Response::new(Body::from("Hello World!"))).into_future()
 //!             }
 //!             Route::UserInfo { id } => {
-//!                 // You could do an async database query to fetch the user data here
 //!                 Ok(Response::new(Body::from(format!("User #{}", id)))).into_future()
 //!             }
 //!         }
 //!     }));
 //! ```
 //!
+//! Shared application state, like a database connection pool, is stored in a
+//! custom context. It is used when decoding requests, and
+//! [`AsyncService::with_context_handler`] passes it to your handler as well:
+//!
+//! ```
+//! use hyper::{Server, Response, Body};
+//! use hyperdrive::{service::AsyncService, BoxedError, FromRequest, RequestContext};
+//! use futures::{future::Either, Future, IntoFuture};
+//! use std::sync::Arc;
+//!
+//! /// Stand-in for an async database client.
+//! struct Database;
+//!
+//! impl Database {
+//!     fn user_name(&self, id: u32) -> impl Future<Item = String, Error = BoxedError> {
+//!         Ok(format!("User #{}", id)).into_future()
+//!     }
+//! }
+//!
+//! #[derive(RequestContext)]
+//! struct Context {
+//!     db: Database,
+//! }
+//!
+//! #[derive(FromRequest)]
+//! #[context(Context)]
+//! enum Route {
+//!     #[get("/")]
+//!     Index,
+//!
+//!     #[get("/users/{id}")]
+//!     UserInfo { id: u32 },
+//! }
+//!
+//! let context = Context { db: Database };
+//!
+//! let srv = Server::bind(&"127.0.0.1:0".parse().unwrap())
+//!     .serve(AsyncService::with_context_handler(|route: Route, _, context: Arc<Context>| {
+//!         match route {
+//!             Route::Index => {
+//!                 Either::A(Ok(Response::new(Body::from("Hello World!"))).into_future())
+//!             }
+//!             Route::UserInfo { id } => {
+//!                 // Fetch the user data with the database client in the context
+//!                 Either::B(context.db.user_name(id).map(|name| Response::new(Body::from(name))))
+//!             }
+//!         }
+//!     }, context));
+//! ```
+//!
 //! If your app doesn't need to be asynchronous and you'd prefer to write sync
 //! code, you can do that by using [`SyncService`]:
 //!
@@ -67,7 +116,8 @@
 //!
 //! let srv = Server::bind(&"127.0.0.1:0".parse().unwrap())
 //!     .serve(SyncService::new(|route: Route, _| {
-//!         // This closure can block freely, and has to return a `Response<Body>`
+//!         // This closure can block freely, and has to return a response (any
+//!         // type implementing `IntoResponse`)
 //!         match route {
 //!             Route::Index => {
 //!                 Response::new(Body::from("Hello World!"))
@@ -86,7 +136,7 @@
 //! ```
 //! use hyper::{Request, Response, Body, Method, service::Service};
 //! use futures::Future;
-//! use hyperdrive::{FromRequest, DefaultFuture, BoxedError, NoContext};
+//! use hyperdrive::{FromRequest, DefaultFuture, BoxedError, NoContext, map_boxed_error};
 //!
 //! #[derive(FromRequest)]
 //! enum Route {
@@ -115,7 +165,11 @@
 //!             Route::UserInfo { id } => {
 //!                 Response::new(Body::from(format!("User #{} is secret!", id)))
 //!             }
-//!         })).map(move |resp| {
+//!         }))
+//!         // Respond to `hyperdrive::Error`s (eg. `404 Not Found` when no route
+//!         // matches) instead of dropping the connection
+//!         .or_else(map_boxed_error)
+//!         .map(move |resp| {
 //!             if is_head {
 //!                 // Response to HEAD requests must have an empty body
 //!                 resp.map(|_| Body::empty())
@@ -133,6 +187,7 @@
 //! [`FromRequest`].
 //!
 //! [`AsyncService`]: service/struct.AsyncService.html
+//! [`AsyncService::with_context_handler`]: service/struct.AsyncService.html#method.with_context_handler
 //! [`SyncService`]: service/struct.SyncService.html
 //! [`FromRequest`]: trait.FromRequest.html
 
@@ -151,23 +206,70 @@ TODO:
 #![warn(missing_docs)]
 #![warn(rust_2018_idioms)]
 
+/// Implements [`RequestContext`] (and the `AsRef` impls it requires) for a
+/// context type defined in this crate, where the custom derive can't be used.
+///
+/// [`RequestContext`]: trait.RequestContext.html
+macro_rules! impl_request_context {
+    ($t:ty) => {
+        impl crate::RequestContext for $t {}
+
+        impl AsRef<$t> for $t {
+            fn as_ref(&self) -> &Self {
+                self
+            }
+        }
+
+        impl AsRef<crate::NoContext> for $t {
+            fn as_ref(&self) -> &crate::NoContext {
+                &crate::NoContext
+            }
+        }
+    };
+}
+
 pub mod body;
 mod error;
+pub mod files;
+pub mod guard;
 mod readme;
+pub mod response;
+mod route_match;
+pub mod routes;
+mod server;
 pub mod service;
+mod shared;
+pub mod test;
+#[cfg(feature = "websocket")]
+pub mod ws;
 
 pub use error::*;
 pub use hyperderive::*;
+pub use route_match::*;
+pub use server::*;
+pub use shared::*;
 
 // Reexport public deps for use by the custom derive
-pub use {futures, http, hyper, serde};
+pub use {bytes, futures, http, hyper, serde};
 
 // These are hidden because the user never actually interacts with them. They're
 // only used by the generated code internally.
 #[doc(hidden)]
 pub use {lazy_static::lazy_static, regex};
+#[doc(hidden)]
+pub mod matrix;
+#[doc(hidden)]
+pub mod query;
+#[doc(hidden)]
+pub mod reverse_route;
+#[doc(hidden)]
+pub mod select_context;
+#[doc(hidden)]
+pub mod try_sync;
 
+use bytes::Bytes;
 use futures::{Future, IntoFuture};
+use service::RequestScope;
 use std::sync::Arc;
 use tokio::runtime::current_thread::Runtime;
 
@@ -182,6 +284,14 @@ use tokio::runtime::current_thread::Runtime;
 /// [`Guard`]: trait.Guard.html
 pub type DefaultFuture<T, E> = Box<dyn Future<Item = T, Error = E> + Send>;
 
+/// A boxed future that is not required to be `Send`.
+///
+/// This is returned by [`LocalAsyncService`], whose handlers may return
+/// futures that can only run on a single-threaded executor.
+///
+/// [`LocalAsyncService`]: service/struct.LocalAsyncService.html
+pub type LocalDefaultFuture<T, E> = Box<dyn Future<Item = T, Error = E>>;
+
 /// A boxed `std::error::Error` that can be used when the actual error type is
 /// unknown.
 pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
@@ -343,6 +453,21 @@ pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 /// contains predefined adapters implementing that trait, which work with any
 /// type implementing `Deserialize`.
 ///
+/// Many clients and proxies drop the body of `GET` and `HEAD` requests, so
+/// using `#[body]` on a variant that only has `GET` and `HEAD` routes is a
+/// compile error, and using it on a variant with a `DELETE` route emits a
+/// warning. Put `#[allow_body_on_get]` on the variant if the body is
+/// intentional (eg. for a search API that expects clients to send it):
+///
+/// ```notrust
+/// #[get("/search")]
+/// #[allow_body_on_get]
+/// Search {
+///     #[body]
+///     query: Json<Query>,
+/// },
+/// ```
+///
 /// ### Extracting query parameters (`#[query_params]` attribute)
 ///
 /// The route attribute cannot match or extract query parameters (`?name=val`).
@@ -374,8 +499,26 @@ pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 /// the `pagination` field.
 ///
 /// The type of the `#[query_params]` field must implement serde's `Deserialize`
-/// trait and the conversion will be performed using the `serde_urlencoded`
-/// crate.
+/// trait. The conversion works like the one of the `serde_urlencoded` crate,
+/// except that repeated parameters can be deserialized into sequences: A
+/// request like `GET /search?tag=a&tag=b` fills a `tag: Vec<String>` field
+/// with both values. A parameter that occurs once is deserialized into either
+/// a scalar or a one-element sequence, and empty values (`?tag=`) are left out
+/// of sequences. As usual with serde, a missing parameter is an error for a
+/// `Vec` field unless it is marked `#[serde(default)]`, while `Option<Vec<T>>`
+/// fields become `None`.
+///
+/// If the conversion fails, the request is rejected with a `400 Bad Request`
+/// [`Error`](struct.Error.html) of kind `ErrorKind::QueryParam`, whose
+/// [`query_param_error`](struct.Error.html#method.query_param_error) tells
+/// whether a parameter was missing or had an invalid value.
+///
+/// Parameters that the type doesn't know are ignored. Using
+/// `#[query_params(strict)]` instead rejects them, so that clients notice
+/// typos like `?pgae=2` (the `query_param_error` of the error is then
+/// `QueryParamError::Unknown`, listing them). Parameters are known by the
+/// names serde deserializes them from, so `#[serde(rename)]` is taken into
+/// account.
 ///
 /// ## Guards
 ///
@@ -430,9 +573,61 @@ pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 /// This feature can not be combined with `#[body]` inside the same variant,
 /// since both consume the request body.
 ///
-/// Currently, this is limited to `FromRequest` implementations that use the
-/// same [`RequestContext`] as the outer type (ie. no automatic `AsRef`
-/// conversion will take place).
+/// By default, the nested type must use the same [`RequestContext`] as the
+/// outer type (ie. no automatic `AsRef` conversion will take place). Using
+/// `#[forward(with = function)]` instead, the context of the nested type is
+/// created by calling `function`. It is passed a reference to the outer
+/// context, followed by references to the values of all guards of the variant
+/// in declaration order, which are always evaluated before the nested type is
+/// decoded. This allows passing data obtained by the outer type, like the
+/// logged-in user, to the guards of the nested type:
+///
+/// ```
+/// use hyperdrive::{FromRequest, Guard, NoContext, RequestContext, BoxedError};
+/// use std::sync::Arc;
+///
+/// struct User {
+///     id: u32,
+/// }
+///
+/// impl Guard for User {
+///     // (omitted for brevity)
+/// #     type Context = NoContext;
+/// #     type Result = Result<Self, BoxedError>;
+/// #     fn from_request(_: &Arc<http::Request<()>>, _: &NoContext) -> Result<Self, BoxedError> {
+/// #         Ok(User { id: 0 })
+/// #     }
+/// }
+///
+/// #[derive(RequestContext)]
+/// struct UserContext {
+///     user_id: u32,
+/// }
+///
+/// fn user_context(_: &NoContext, user: &User) -> UserContext {
+///     UserContext { user_id: user.id }
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(UserContext)]
+/// enum UserRoute {
+///     #[get("/profile")]
+///     Profile,
+/// }
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+///
+///     User {
+///         user: User,
+///
+///         #[forward(with = user_context)]
+///         inner: UserRoute,
+///     },
+/// }
+/// ```
 ///
 /// A variant or struct defining a `#[forward]` field does not have to define
 /// a route. If no other route matches, this variant will automatically be
@@ -491,6 +686,182 @@ pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 ///
 /// For more info on this, refer to the [`RequestContext`] trait.
 ///
+/// ## Creating requests (`#[hyperdrive(client)]`)
+///
+/// Putting `#[hyperdrive(client)]` on the type makes the custom derive also
+/// implement [`ToRequest`], which turns a value back into an HTTP request.
+/// This lets Rust clients of an API reuse the route definitions of the server.
+///
+/// The request uses the method and path of the first route attribute of the
+/// variant. Placeholders are filled in using their `Display` implementation,
+/// the `#[query_params]` field must implement `Serialize`, and the `#[body]`
+/// field must implement [`ToBody`]. Guards are skipped, since they are
+/// obtained from the request (or from the context) by the server. Variants with
+/// a `#[forward]` field delegate to the [`ToRequest`] impl of the forwarded
+/// type. [`ToRequest::path_and_query`] returns only the path and query
+/// string, eg. for creating links.
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, ToRequest, body::Json};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Post {
+///     title: String,
+/// }
+///
+/// #[derive(FromRequest, PartialEq, Debug)]
+/// #[hyperdrive(client)]
+/// enum Route {
+///     #[get("/posts/{id}")]
+///     Post { id: u32 },
+///
+///     #[put("/posts/{id}")]
+///     EditPost {
+///         id: u32,
+///         #[body]
+///         post: Json<Post>,
+///     },
+/// }
+///
+/// let request = Route::Post { id: 42 }.to_request();
+/// assert_eq!(request.method(), "GET");
+/// assert_eq!(request.uri(), "/posts/42");
+/// assert_eq!(Route::Post { id: 42 }.path_and_query(), "/posts/42");
+///
+/// let route = Route::EditPost {
+///     id: 1,
+///     post: Json(Post { title: "Hello".to_string() }),
+/// };
+/// let decoded = Route::from_request_sync(route.to_request(), NoContext).unwrap();
+/// assert_eq!(decoded, route);
+/// ```
+///
+/// ## Matrix parameters (`#[strip_matrix_params]`)
+///
+/// Some clients attach parameters to individual path segments, as in
+/// `/cars;color=red/17`. By default, these are part of the segment, so the
+/// path doesn't match `/cars/{id}`. Putting `#[strip_matrix_params]` on the
+/// type removes all `;name=value` suffixes from the segments before the path
+/// is matched, and before placeholders are parsed. The parameters can then be
+/// read with the [`MatrixParams`] guard. Paths with a segment that consists
+/// only of matrix parameters (eg. `/cars/;color=red`) don't match any route.
+///
+/// ```
+/// use hyperdrive::{FromRequest, NoContext, guard::MatrixParams};
+/// use hyper::Body;
+///
+/// #[derive(FromRequest)]
+/// #[strip_matrix_params]
+/// enum Route {
+///     #[get("/cars/{id}")]
+///     Car { id: u32, params: MatrixParams },
+/// }
+///
+/// let request = http::Request::get("/cars;color=red/17")
+///     .body(Body::empty())
+///     .unwrap();
+/// let Route::Car { id, params } = Route::from_request_sync(request, NoContext).unwrap();
+/// assert_eq!(id, 17);
+/// assert_eq!(params.get("color"), Some("red"));
+/// ```
+///
+/// `#[forward]`ed types receive the original request, so they need the attribute
+/// as well to match such paths.
+///
+/// ## Testing routes
+///
+/// [`match_route`] determines which route a method and path would be routed
+/// to, without running any guards or reading a body. This allows testing the
+/// routing of a type without building requests or starting a runtime:
+///
+/// ```
+/// use hyperdrive::{FromRequest, RouteMatch};
+/// use http::Method;
+///
+/// #[derive(FromRequest)]
+/// enum Routes {
+///     #[get("/users/{id}")]
+///     UserInfo { id: u32 },
+///
+///     #[patch("/users/{id}")]
+///     EditUser { id: u32 },
+/// }
+///
+/// assert_eq!(
+///     Routes::match_route(&Method::GET, "/users/5"),
+///     RouteMatch::Route {
+///         name: "UserInfo",
+///         placeholders: vec![("id", "5".to_string())],
+///     }
+/// );
+/// assert_eq!(
+///     Routes::match_route(&Method::POST, "/users/5"),
+///     RouteMatch::WrongMethod(vec![&Method::GET, &Method::HEAD, &Method::PATCH])
+/// );
+/// assert_eq!(
+///     Routes::match_route(&Method::GET, "/users/me"),
+///     RouteMatch::PathSegment { name: "UserInfo", field: "id" }
+/// );
+/// assert_eq!(
+///     Routes::match_route(&Method::GET, "/posts"),
+///     RouteMatch::NoMatchingRoute
+/// );
+/// ```
+///
+/// # Implementing `FromRequest` manually
+///
+/// The trait has two entry points, each with a default implementation in
+/// terms of the other, so implementors must provide at least one of them:
+///
+/// * [`from_request_and_body`] takes the request metadata (shared via `Arc`)
+///   and the body separately. This is what the custom derive implements, and
+///   what [`AsyncService`], [`SyncService`] and `#[forward]` call, so it
+///   should be preferred.
+/// * [`from_request`] takes the whole `http::Request<hyper::Body>`. It is the
+///   convenient entry point for calling code, and implementing it can be
+///   easier when the request should be inspected or modified as a whole.
+///
+/// The default [`from_request_and_body`] has to rebuild an owned request,
+/// which copies the method, URI, version and headers. Request extensions can't
+/// be cloned, so the rebuilt request keeps a link to the original one instead:
+/// the guards and types of this crate that read extensions (like
+/// [`RemoteAddr`], [`Extension`], [`RequestScope`] and [`ResponseDecorations`])
+/// still find them, but `request.extensions()` of the rebuilt request does
+/// not contain them. Implementing only [`from_request`] is convenient for
+/// adapters, but less efficient than implementing [`from_request_and_body`].
+///
+/// ```
+/// use hyperdrive::{FromRequest, DefaultFuture, BoxedError};
+/// use futures::prelude::*;
+/// use std::sync::Arc;
+///
+/// /// Decodes the request as `R`, but only after stripping the `/v1` prefix.
+/// struct V1<R>(R);
+///
+/// impl<R: FromRequest + Send + 'static> FromRequest for V1<R> {
+///     type Context = R::Context;
+///     type Future = DefaultFuture<Self, BoxedError>;
+///
+///     fn from_request(
+///         mut request: http::Request<hyper::Body>,
+///         context: impl Into<Arc<Self::Context>>,
+///     ) -> Self::Future {
+///         let stripped = match request.uri().path_and_query() {
+///             Some(pq) if pq.path().starts_with("/v1/") => Some(pq.as_str()[3..].to_string()),
+///             _ => None,
+///         };
+///         match stripped {
+///             Some(path) => {
+///                 *request.uri_mut() = path.parse().unwrap();
+///                 Box::new(R::from_request(request, context).map(V1))
+///             }
+///             None => Box::new(Err("expected a `/v1/` path".into()).into_future()),
+///         }
+///     }
+/// }
+/// ```
+///
 /// [`AsyncService`]: service/struct.AsyncService.html
 /// [`SyncService`]: service/struct.SyncService.html
 /// [`FromBody`]: trait.FromBody.html
@@ -499,7 +870,17 @@ pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 /// [`NoContext`]: struct.NoContext.html
 /// [`DefaultFuture`]: type.DefaultFuture.html
 /// [`body`]: body/index.html
-/// [`from_request`]: #tymethod.from_request
+/// [`from_request`]: #method.from_request
+/// [`from_request_and_body`]: #method.from_request_and_body
+/// [`RequestScope`]: service/struct.RequestScope.html
+/// [`ResponseDecorations`]: service/struct.ResponseDecorations.html
+/// [`RemoteAddr`]: guard/struct.RemoteAddr.html
+/// [`Extension`]: guard/struct.Extension.html
+/// [`ToRequest`]: trait.ToRequest.html
+/// [`ToRequest::path_and_query`]: trait.ToRequest.html#method.path_and_query
+/// [`ToBody`]: trait.ToBody.html
+/// [`MatrixParams`]: guard/struct.MatrixParams.html
+/// [`match_route`]: #method.match_route
 pub trait FromRequest: Sized {
     /// A context parameter passed to [`from_request`].
     ///
@@ -510,7 +891,7 @@ pub trait FromRequest: Sized {
     /// context type that can be obtained from any [`RequestContext`] via
     /// `AsRef`.
     ///
-    /// [`from_request`]: #tymethod.from_request
+    /// [`from_request`]: #method.from_request
     /// [`NoContext`]: struct.NoContext.html
     /// [`RequestContext`]: trait.RequestContext.html
     type Context: RequestContext;
@@ -524,7 +905,7 @@ pub trait FromRequest: Sized {
     /// returned future.
     ///
     /// [`DefaultFuture`]: type.DefaultFuture.html
-    /// [`from_request`]: #tymethod.from_request
+    /// [`from_request`]: #method.from_request
     type Future: Future<Item = Self, Error = BoxedError> + Send;
 
     /// Creates a `Self` from an HTTP request, asynchronously.
@@ -532,22 +913,48 @@ pub trait FromRequest: Sized {
     /// This takes the request metadata, body, and a user-defined context. Only
     /// the body is consumed.
     ///
+    /// This is the method implemented by the custom derive, and the one called
+    /// by the services in the [`service`] module and by `#[forward]`.
+    ///
     /// Implementations of this function must not block, since this function is
     /// always run on a futures executor. If you need to perform blocking I/O or
     /// long-running computations, you can call [`hyperdrive::blocking`].
     ///
+    /// The provided implementation rebuilds an owned request and calls
+    /// [`from_request`]. The request extensions stay with the original request,
+    /// where the guards and types of this crate still find them (see the
+    /// [trait documentation](trait.FromRequest.html) for details).
+    ///
     /// # Parameters
     ///
     /// * **`request`**: HTTP request data (headers, path, method, etc.).
     /// * **`body`**: The streamed HTTP body.
-    /// * **`context`**: The user-defined context.
+    /// * **`context`**: The user-defined context. It is shared via `Arc` so
+    ///   that services don't have to clone it for every request.
     ///
+    /// [`service`]: service/index.html
     /// [`hyperdrive::blocking`]: fn.blocking.html
+    /// [`from_request`]: #method.from_request
     fn from_request_and_body(
         request: &Arc<http::Request<()>>,
         body: hyper::Body,
-        context: Self::Context,
-    ) -> Self::Future;
+        context: Arc<Self::Context>,
+    ) -> Self::Future {
+        let mut owned = http::Request::new(body);
+        *owned.method_mut() = request.method().clone();
+        *owned.uri_mut() = request.uri().clone();
+        *owned.version_mut() = request.version();
+        *owned.headers_mut() = request.headers().clone();
+        // Copied so that `from_request` shares it instead of inserting a new one
+        if let Some(scope) = request.extensions().get::<RequestScope>() {
+            owned.extensions_mut().insert(scope.clone());
+        }
+        owned
+            .extensions_mut()
+            .insert(guard::OriginalRequest(request.clone()));
+
+        Self::from_request(owned, context)
+    }
 
     /// Create a `Self` from an HTTP request, asynchronously.
     ///
@@ -560,39 +967,209 @@ pub trait FromRequest: Sized {
     /// A blocking wrapper around this method is provided by
     /// [`from_request_sync`].
     ///
+    /// The provided implementation inserts an empty [`RequestScope`] into the
+    /// request extensions if there isn't one already, and calls
+    /// [`from_request_and_body`] with the request metadata wrapped in an `Arc`.
+    ///
     /// # Parameters
     ///
     /// * **`request`**: An HTTP request from the `http` crate, containing a
     ///   `hyper::Body`.
-    /// * **`context`**: User-defined context.
+    /// * **`context`**: User-defined context. Either the context itself, or
+    ///   an `Arc` sharing it.
     ///
     /// [`from_request_sync`]: #method.from_request_sync
+    /// [`from_request_and_body`]: #method.from_request_and_body
     /// [`hyperdrive::blocking`]: fn.blocking.html
-    fn from_request(request: http::Request<hyper::Body>, context: Self::Context) -> Self::Future {
-        let (parts, body) = request.into_parts();
+    /// [`RequestScope`]: service/struct.RequestScope.html
+    fn from_request(
+        request: http::Request<hyper::Body>,
+        context: impl Into<Arc<Self::Context>>,
+    ) -> Self::Future {
+        let (mut parts, body) = request.into_parts();
+        if parts.extensions.get::<RequestScope>().is_none() {
+            parts.extensions.insert(RequestScope::default());
+        }
         let request = Arc::new(http::Request::from_parts(parts, ()));
 
-        Self::from_request_and_body(&request, body, context)
+        Self::from_request_and_body(&request, body, context.into())
+    }
+
+    /// Creates a `Self` from the request metadata, without running any
+    /// asynchronous operations.
+    ///
+    /// Returns `None` if decoding the request requires asynchronous work, like
+    /// reading the body. In that case, [`from_request_and_body`] has to be used
+    /// instead. Implementations must return `None` before running any guards,
+    /// so that they don't run twice.
+    ///
+    /// The custom derive returns `Some` when the matched route has no
+    /// `#[body]` field and all of its guards return a `Result` (and if the
+    /// route has a `#[forward]` field, when it has no guards and the forwarded
+    /// type returns `Some`). Errors from matching the route, like
+    /// [`ErrorKind::NoMatchingRoute`], are also returned synchronously. The
+    /// provided default implementation always returns `None`.
+    ///
+    /// This is used by [`from_request_sync`] to avoid starting a runtime.
+    ///
+    /// [`from_request_and_body`]: #method.from_request_and_body
+    /// [`from_request_sync`]: #method.from_request_sync
+    /// [`ErrorKind::NoMatchingRoute`]: enum.ErrorKind.html#variant.NoMatchingRoute
+    fn try_from_request_parts(
+        _request: &Arc<http::Request<()>>,
+        _context: &Self::Context,
+    ) -> Option<Result<Self, BoxedError>> {
+        None
+    }
+
+    /// Returns a name identifying the route `self` was decoded from.
+    ///
+    /// The name is meant to be used as a low-cardinality label, eg. in
+    /// metrics. [`AsyncService`] and [`SyncService`] store it in the response
+    /// extensions as a [`RouteName`].
+    ///
+    /// The custom derive returns the name of the enum variant (or of the
+    /// struct). The provided default implementation returns `None`.
+    ///
+    /// [`AsyncService`]: service/struct.AsyncService.html
+    /// [`SyncService`]: service/struct.SyncService.html
+    /// [`RouteName`]: service/struct.RouteName.html
+    fn route_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the path templates of all routes, as written in the route
+    /// attributes (eg. `/users/{id}`).
+    ///
+    /// This is used by services created with `suggest_routes(true)` to suggest
+    /// similar routes when a request doesn't match any route. The custom derive
+    /// includes the routes of a type used as a `#[forward]` fallback, and
+    /// leaves out the asterisk path `*`. The provided default implementation
+    /// returns an empty `Vec`.
+    fn route_paths() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Returns the metadata of all routes.
+    ///
+    /// This is used by [`routes::audit`] to list the routes of an application
+    /// and to find mistakes in them. The custom derive includes the `HEAD`
+    /// routes implied by `GET` routes, and the routes of a type used as a
+    /// `#[forward]` fallback, and records the doc comments of variants and of
+    /// `{placeholder}` and `#[query_params]` fields. The provided default
+    /// implementation returns an empty `Vec`.
+    ///
+    /// The routes generated by the custom derive are sorted by path template,
+    /// then by method name, so the result doesn't change when variants or
+    /// route attributes are reordered, or when routes are added elsewhere.
+    /// The routes of a `#[forward]` fallback follow, in their own order.
+    ///
+    /// [`routes::audit`]: routes/fn.audit.html
+    fn routes() -> Vec<routes::RouteInfo> {
+        Vec::new()
+    }
+
+    /// Determines how a request with the given `method` and `path` would be
+    /// routed, without decoding it.
+    ///
+    /// `path` is the path of the request URI, without the query string. No
+    /// guards are run and the query string and body are ignored, so this is
+    /// suitable for testing the routing of types whose guards have side
+    /// effects. See [Testing routes](#testing-routes) for an example.
+    ///
+    /// The custom derive matches the path against the same regexes and
+    /// placeholder `FromStr` implementations used by [`from_request`], and
+    /// asks the `#[forward]`ed type of a fallback variant. The provided
+    /// default implementation returns `RouteMatch::Unknown`.
+    ///
+    /// [`from_request`]: #method.from_request
+    fn match_route(_method: &http::Method, _path: &str) -> RouteMatch {
+        RouteMatch::Unknown
     }
 
     /// Create a `Self` from an HTTP request, synchronously.
     ///
     /// This is a blocking version of [`from_request`]. The provided default
-    /// implementation will internally create a single-threaded tokio runtime to
-    /// perform the conversion and receive the request body.
+    /// implementation first tries [`try_from_request_parts`]. Only if that
+    /// returns `None`, it will internally create a single-threaded tokio
+    /// runtime to perform the conversion and receive the request body.
     ///
     /// Note that this does not provide a way to *write* a blocking version of
     /// [`from_request`]. Implementors of this trait must always implement
-    /// [`from_request`] in a non-blocking fashion, even if they *also*
-    /// implement this method.
+    /// [`from_request_and_body`] or [`from_request`] in a non-blocking fashion,
+    /// even if they *also* implement this method.
     ///
-    /// [`from_request`]: #tymethod.from_request
+    /// [`from_request`]: #method.from_request
+    /// [`from_request_and_body`]: #method.from_request_and_body
+    /// [`try_from_request_parts`]: #method.try_from_request_parts
     fn from_request_sync(
         request: http::Request<hyper::Body>,
-        context: Self::Context,
+        context: impl Into<Arc<Self::Context>>,
     ) -> Result<Self, BoxedError> {
+        let context = context.into();
+        let (mut parts, body) = request.into_parts();
+        if parts.extensions.get::<RequestScope>().is_none() {
+            parts.extensions.insert(RequestScope::default());
+        }
+        let request = Arc::new(http::Request::from_parts(parts, ()));
+
+        if let Some(result) = Self::try_from_request_parts(&request, &context) {
+            return result;
+        }
+
+        // Hand the request back to `from_request` if possible, since it might
+        // be the only method that is implemented
+        let future = match Arc::try_unwrap(request) {
+            Ok(request) => {
+                let (parts, ()) = request.into_parts();
+                Self::from_request(http::Request::from_parts(parts, body), context)
+            }
+            Err(request) => Self::from_request_and_body(&request, body, context),
+        };
         let mut rt = Runtime::new().expect("couldn't start single-threaded tokio runtime");
-        rt.block_on(Self::from_request(request, context).into_future())
+        rt.block_on(future.into_future())
+    }
+
+    /// Create a `Self` from an HTTP request whose body is already in memory,
+    /// synchronously.
+    ///
+    /// The body can be anything that converts to [`Bytes`], like a `Vec<u8>`,
+    /// `&[u8]` or `&str`, which makes this convenient for tests and for tools
+    /// replaying recorded requests. The body is wrapped in a `hyper::Body` and
+    /// passed to [`from_request_sync`], so routes that don't need to do any
+    /// asynchronous work are decoded without starting a runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, NoContext, body::Json};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[post("/sum")]
+    ///     Sum {
+    ///         #[body]
+    ///         numbers: Json<Vec<u32>>,
+    ///     },
+    /// }
+    ///
+    /// let request = http::Request::post("/sum").body("[1, 2, 3]").unwrap();
+    /// let Route::Sum { numbers } = Route::from_request_bytes(request, NoContext).unwrap();
+    /// assert_eq!(numbers.0, vec![1, 2, 3]);
+    /// ```
+    ///
+    /// [`Bytes`]: https://docs.rs/bytes/0.4/bytes/struct.Bytes.html
+    /// [`from_request_sync`]: #method.from_request_sync
+    fn from_request_bytes<B>(
+        request: http::Request<B>,
+        context: impl Into<Arc<Self::Context>>,
+    ) -> Result<Self, BoxedError>
+    where
+        B: Into<Bytes>,
+    {
+        let (parts, body) = request.into_parts();
+        let body = hyper::Body::from(body.into());
+        Self::from_request_sync(http::Request::from_parts(parts, body), context)
     }
 }
 
@@ -605,6 +1182,12 @@ pub trait FromRequest: Sized {
 /// A `Guard` can not access the request body. If you need to do that, implement
 /// [`FromBody`] instead.
 ///
+/// Guards can fail with any error type. Returning a [`hyperdrive::Error`]
+/// makes the service respond with its status code. For an application-defined
+/// error type, implement [`ResponseError`] and register the type using the
+/// `response_error` method of the service; otherwise the service doesn't know
+/// how to respond to it.
+///
 /// # Examples
 ///
 /// Define a guard that ensures that required request headers are present:
@@ -660,6 +1243,8 @@ pub trait FromRequest: Sized {
 /// ```
 ///
 /// [`FromBody`]: trait.FromBody.html
+/// [`hyperdrive::Error`]: struct.Error.html
+/// [`ResponseError`]: trait.ResponseError.html
 /// [`RequestContext`]: trait.RequestContext.html
 pub trait Guard: Sized {
     /// A context parameter passed to [`Guard::from_request`].
@@ -670,9 +1255,16 @@ pub trait Guard: Sized {
     ///
     /// If no context is needed, this should be set to [`NoContext`].
     ///
+    /// This doesn't have to be the application's whole [`RequestContext`]:
+    /// `#[derive(FromRequest)]` obtains it from the route's context via
+    /// [`FromContext`], so a guard can declare only the part it needs (eg. a
+    /// connection pool) and be used with any context providing it.
+    ///
     /// [`Guard::from_request`]: #tymethod.from_request
     /// [`NoContext`]: struct.NoContext.html
-    type Context: RequestContext;
+    /// [`RequestContext`]: trait.RequestContext.html
+    /// [`FromContext`]: trait.FromContext.html
+    type Context;
 
     /// The result returned by [`Guard::from_request`].
     ///
@@ -715,6 +1307,14 @@ pub trait Guard: Sized {
 /// Types implementing this trait are provided in the [`body`] module. They
 /// allow easy deserialization from a variety of data formats.
 ///
+/// The custom derive only calls `from_body` after all guards of the route have
+/// succeeded, so the body of a rejected request is never read and the error
+/// response is sent right away. This matters for clients that send `Expect:
+/// 100-continue` before uploading a large body: they receive the final status
+/// without having to upload anything. For the same reason, `from_body`
+/// implementations should not read the body before they are sure that they
+/// will need it.
+///
 /// # Examples
 ///
 /// Collect the whole body and then deserialize it using a serde data format
@@ -787,9 +1387,14 @@ pub trait FromBody: Sized {
     ///
     /// If no context is needed, this should be set to [`NoContext`].
     ///
+    /// Like [`Guard::Context`], this is obtained from the route's context via
+    /// [`FromContext`].
+    ///
     /// [`from_body`]: #tymethod.from_body
     /// [`NoContext`]: struct.NoContext.html
-    type Context: RequestContext;
+    /// [`Guard::Context`]: trait.Guard.html#associatedtype.Context
+    /// [`FromContext`]: trait.FromContext.html
+    type Context;
 
     /// The result returned by [`from_body`].
     ///
@@ -835,6 +1440,63 @@ pub trait FromBody: Sized {
     ) -> Self::Result;
 }
 
+/// Trait for types that can be turned into an HTTP request.
+///
+/// This is the counterpart of [`FromRequest`]: Passing the created request to
+/// [`FromRequest::from_request`] should result in a value equal to `self`.
+/// This allows clients written in Rust to reuse the route definitions of a
+/// server.
+///
+/// `#[derive(FromRequest)]` implements this trait when the type is marked with
+/// `#[hyperdrive(client)]`. Refer to the [`FromRequest`] docs for details.
+///
+/// [`FromRequest`]: trait.FromRequest.html
+/// [`FromRequest::from_request`]: trait.FromRequest.html#method.from_request
+pub trait ToRequest {
+    /// Creates an HTTP request that decodes to `self`.
+    fn to_request(&self) -> http::Request<hyper::Body>;
+
+    /// Returns the path and query string of the request created by
+    /// [`to_request`], eg. `/users?start=10&count=5`.
+    ///
+    /// This is meant for creating links to a route. The custom derive builds
+    /// the string without creating a request. The `#[query_params]` field is
+    /// serialized with all values percent-encoded and with `None` fields left
+    /// out, so it is deserialized into the same value by `FromRequest`. The
+    /// query string is omitted if no parameters remain. Since path segments
+    /// aren't percent-decoded, `{placeholder}` values are only encoded where
+    /// needed to keep the path valid.
+    ///
+    /// The provided default implementation takes the URI of the request
+    /// returned by [`to_request`].
+    ///
+    /// [`to_request`]: #tymethod.to_request
+    fn path_and_query(&self) -> String {
+        self.to_request().uri().to_string()
+    }
+}
+
+/// Trait for types that can be serialized into the body of an HTTP request.
+///
+/// This is the counterpart of [`FromBody`] and used by the [`ToRequest`]
+/// implementation generated for `#[body]` fields. The wrappers in the [`body`]
+/// module implement it.
+///
+/// [`FromBody`]: trait.FromBody.html
+/// [`ToRequest`]: trait.ToRequest.html
+/// [`body`]: body/index.html
+pub trait ToBody {
+    /// Writes `self` into the body of `request`.
+    ///
+    /// Implementations should also set the `Content-Type` header.
+    ///
+    /// # Panics
+    ///
+    /// Since a body that can be decoded can usually also be encoded, this
+    /// method is infallible. It may panic if `self` can't be serialized.
+    fn to_body(&self, request: &mut http::Request<hyper::Body>);
+}
+
 /// A default [`RequestContext`] containing no data.
 ///
 /// This context type should be used in [`FromRequest`], [`FromBody`] and
@@ -892,6 +1554,48 @@ pub struct NoContext;
 /// but provides additional data that may be used only by a few [`Guard`],
 /// [`FromRequest`] or [`FromBody`] implementations.
 ///
+/// A plain `#[as_ref]` only exposes `MyContext` itself. If `MyContext` in turn
+/// provides other contexts via `AsRef` (like a connection pool), those can be
+/// made available as well by using `#[as_ref(forward)]` instead. This will
+/// implement `AsRef<T>` for every `T` the field's type implements `AsRef<T>`
+/// for, so contexts can be nested arbitrarily deep:
+/// ```
+/// # use hyperdrive::RequestContext;
+/// # #[derive(RequestContext)]
+/// # struct ConnectionPool {}
+/// # struct Logger {}
+/// #[derive(RequestContext)]
+/// struct MyContext {
+///     #[as_ref]
+///     db: ConnectionPool,
+/// }
+///
+/// #[derive(RequestContext)]
+/// struct BigContext {
+///     #[as_ref(forward)]
+///     inner: MyContext,
+///     #[as_ref]
+///     logger: Logger,
+/// }
+///
+/// fn needs_pool<C: AsRef<ConnectionPool>>() {}
+/// needs_pool::<BigContext>();
+/// ```
+///
+/// The forwarded field must itself be a `RequestContext`, and only one field
+/// can use `#[as_ref(forward)]`. Since this is implemented using a blanket
+/// `impl<T> AsRef<T>`, other `#[as_ref]` fields must not have a type that the
+/// forwarded context already provides, or the compiler will reject the
+/// overlapping implementations. If the forwarded field's type is defined in
+/// another crate, the compiler can't rule out such an overlap for any field
+/// type that isn't defined in the current crate either.
+///
+/// If an `#[as_ref]` field is an `Arc<T>`, `Rc<T>` or `Box<T>`, `AsRef<T>` is
+/// implemented as well, so that a context can store an `Arc<ConnectionPool>`
+/// and still be used where an `AsRef<ConnectionPool>` is required. Use
+/// `#[as_ref(no_deref)]` to only implement `AsRef` for the pointer type, for
+/// example when another field already provides `AsRef<T>`.
+///
 /// [`Guard`]: trait.Guard.html
 /// [`FromRequest`]: trait.FromRequest.html
 /// [`FromBody`]: trait.FromBody.html
@@ -905,6 +1609,196 @@ impl AsRef<NoContext> for NoContext {
     }
 }
 
+/// Borrows a part of a larger context.
+///
+/// `#[derive(FromRequest)]` uses this to pass the [`Guard::Context`] and
+/// [`FromBody::Context`] of its fields to them. This decouples guards from the
+/// application's context type: a guard declares the part of the context it
+/// needs, and works with every context `C` for which it implements
+/// `FromContext<C>`.
+///
+/// This is implemented for every `T` that `C` implements `AsRef<T>` for, which
+/// includes the fields marked with `#[as_ref]` when deriving
+/// [`RequestContext`]. Existing contexts therefore keep working without
+/// changes.
+///
+/// # Examples
+///
+/// A guard that is reused with two different application contexts:
+///
+/// ```
+/// use hyperdrive::{FromRequest, Guard, RequestContext, BoxedError};
+/// use std::sync::Arc;
+///
+/// /// Only the part of the context that the guard needs.
+/// struct Greeting(&'static str);
+///
+/// struct Greeter(&'static str);
+///
+/// impl Guard for Greeter {
+///     type Context = Greeting;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(_: &Arc<http::Request<()>>, greeting: &Greeting) -> Self::Result {
+///         Ok(Greeter(greeting.0))
+///     }
+/// }
+///
+/// #[derive(RequestContext)]
+/// struct Website {
+///     #[as_ref]
+///     greeting: Greeting,
+/// }
+///
+/// #[derive(RequestContext)]
+/// struct Api {
+///     #[as_ref]
+///     greeting: Greeting,
+///     secret: String,
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(Website)]
+/// enum WebsiteRoute {
+///     #[get("/")]
+///     Index { greeter: Greeter },
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(Api)]
+/// enum ApiRoute {
+///     #[get("/hello")]
+///     Hello { greeter: Greeter },
+/// }
+/// ```
+///
+/// [`Guard::Context`]: trait.Guard.html#associatedtype.Context
+/// [`FromBody::Context`]: trait.FromBody.html#associatedtype.Context
+/// [`RequestContext`]: trait.RequestContext.html
+pub trait FromContext<C>: Sized {
+    /// Borrows `Self` from `context`.
+    fn from_context(context: &C) -> &Self;
+}
+
+impl<C: AsRef<T>, T> FromContext<C> for T {
+    fn from_context(context: &C) -> &T {
+        context.as_ref()
+    }
+}
+
+/// Combines two contexts into one, without defining a new struct.
+///
+/// This is useful when a [`FromRequest`] type needs parts of two contexts that
+/// are defined independently, for example the context required by the guards
+/// of a library and the application's own context. `ContextPair` implements
+/// [`RequestContext`], and can be used as the `#[context]` of a type deriving
+/// [`FromRequest`].
+///
+/// Every field of the derived type can then use a [`Guard::Context`] or
+/// [`FromBody::Context`] that is provided by either half of the pair (via
+/// [`FromContext`]), or the pair itself. If both halves provide the same type,
+/// the one from the first half is used.
+///
+/// Note that this lookup is done by the code generated by
+/// `#[derive(FromRequest)]`, and only works if the type deriving `FromRequest`
+/// isn't generic. The derive recognizes the pair by its name, so the
+/// `#[context]` attribute has to name `ContextPair` (not a type alias of it).
+/// Also, only the two halves are searched, so a `ContextPair`
+/// nested inside another one only provides itself, but not its halves. Use a
+/// context struct with an `#[as_ref(forward)]` field in these cases (see
+/// [`RequestContext`]).
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{ContextPair, FromRequest, Guard, RequestContext, BoxedError};
+/// use std::sync::Arc;
+///
+/// // Defined by a library:
+/// struct AuthConfig;
+///
+/// #[derive(RequestContext)]
+/// struct AuthContext {
+///     #[as_ref]
+///     config: AuthConfig,
+/// }
+///
+/// struct User;
+///
+/// impl Guard for User {
+///     type Context = AuthConfig;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(_: &Arc<http::Request<()>>, _: &AuthConfig) -> Self::Result {
+///         Ok(User)
+///     }
+/// }
+///
+/// // Defined by the application:
+/// struct Templates;
+///
+/// #[derive(RequestContext)]
+/// struct AppContext {
+///     #[as_ref]
+///     templates: Templates,
+/// }
+///
+/// struct Renderer;
+///
+/// impl Guard for Renderer {
+///     type Context = Templates;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(_: &Arc<http::Request<()>>, _: &Templates) -> Self::Result {
+///         Ok(Renderer)
+///     }
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[context(ContextPair<AuthContext, AppContext>)]
+/// enum Route {
+///     #[get("/profile")]
+///     Profile {
+///         user: User,
+///         renderer: Renderer,
+///     },
+/// }
+/// ```
+///
+/// [`FromRequest`]: trait.FromRequest.html
+/// [`RequestContext`]: trait.RequestContext.html
+/// [`Guard::Context`]: trait.Guard.html#associatedtype.Context
+/// [`FromBody::Context`]: trait.FromBody.html#associatedtype.Context
+/// [`FromContext`]: trait.FromContext.html
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ContextPair<A, B>(pub A, pub B);
+
+impl<A, B> ContextPair<A, B> {
+    /// Returns the first half of the pair.
+    pub fn left(&self) -> &A {
+        &self.0
+    }
+
+    /// Returns the second half of the pair.
+    pub fn right(&self) -> &B {
+        &self.1
+    }
+}
+
+impl<A, B> RequestContext for ContextPair<A, B> {}
+
+impl<A, B> AsRef<ContextPair<A, B>> for ContextPair<A, B> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl<A, B> AsRef<NoContext> for ContextPair<A, B> {
+    fn as_ref(&self) -> &NoContext {
+        &NoContext
+    }
+}
+
 /// Turns a blocking closure into an asynchronous `Future`.
 ///
 /// This function takes a blocking closure that does synchronous I/O or heavy
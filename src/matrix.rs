@@ -0,0 +1,58 @@
+//! Splits matrix parameters off of path segments.
+//!
+//! This is used by the code generated by `#[derive(FromRequest)]` for items
+//! marked with `#[strip_matrix_params]`, and by the [`MatrixParams`] guard.
+//!
+//! Matrix parameters are `;name=value` suffixes of a path segment, as in
+//! `/cars;color=red/models;year=2019`. The value may be left out
+//! (`;featured`), in which case it is empty. Names and values are not
+//! percent-decoded.
+//!
+//! [`MatrixParams`]: ../guard/struct.MatrixParams.html
+
+use std::borrow::Cow;
+
+/// Removes the matrix parameters from each segment of `path`.
+///
+/// Returns `None` if a segment consists only of matrix parameters (eg.
+/// `/;color=red`), since there's nothing left to match it against.
+pub fn strip(path: &str) -> Option<Cow<'_, str>> {
+    if !path.contains(';') {
+        return Some(Cow::Borrowed(path));
+    }
+
+    let mut stripped = String::with_capacity(path.len());
+    for (i, segment) in path.split('/').enumerate() {
+        if i > 0 {
+            stripped.push('/');
+        }
+        let (name, params) = split_segment(segment);
+        if name.is_empty() && params.is_some() {
+            return None;
+        }
+        stripped.push_str(name);
+    }
+    Some(Cow::Owned(stripped))
+}
+
+/// Splits `segment` into the part before the first `;` and the matrix
+/// parameters after it, if there are any.
+pub(crate) fn split_segment(segment: &str) -> (&str, Option<&str>) {
+    match segment.find(';') {
+        Some(pos) => (&segment[..pos], Some(&segment[pos + 1..])),
+        None => (segment, None),
+    }
+}
+
+/// Returns the `(name, value)` pairs of the matrix parameters in `params`.
+///
+/// Empty parameters (as in `;;`) are skipped.
+pub(crate) fn parse_params(params: &str) -> impl Iterator<Item = (&str, &str)> {
+    params
+        .split(';')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.find('=') {
+            Some(pos) => (&param[..pos], &param[pos + 1..]),
+            None => (param, ""),
+        })
+}
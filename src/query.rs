@@ -0,0 +1,338 @@
+//! Deserializes query strings.
+//!
+//! This is used by the `FromRequest` implementation generated by
+//! `#[derive(FromRequest)]` to decode `#[query_params]` fields.
+//!
+//! Unlike `serde_urlencoded`, this groups repeated parameters, so that
+//! `?tag=a&tag=b` can be deserialized into a `tag: Vec<String>` field. Empty
+//! values are left out of such sequences, so `?tag=` results in an empty
+//! `Vec`. A parameter that occurs once can be deserialized into both a scalar
+//! and a sequence, while deserializing a repeated parameter into a scalar is
+//! an error.
+//!
+//! [`from_str_strict`] additionally rejects parameters that the deserialized
+//! type ignores, for `#[query_params(strict)]` fields.
+//!
+//! [`from_str_strict`]: fn.from_str_strict.html
+
+use serde::de::{
+    self,
+    value::{Error, MapDeserializer, SeqDeserializer},
+    Deserializer, Error as _, IntoDeserializer, Visitor,
+};
+use serde::{de::DeserializeOwned, forward_to_deserialize_any};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Deserializes a `T` from the (still percent-encoded) query string `query`.
+pub fn from_str<T: DeserializeOwned>(query: &str) -> Result<T, Error> {
+    let (value, _) = deserialize(query)?;
+    Ok(value)
+}
+
+/// Deserializes a `T` from `query` like [`from_str`], but fails if `T` ignores
+/// any of the parameters.
+///
+/// The error names the unknown parameters (eg. ``unknown fields `pgae`,
+/// `sort` ``).
+///
+/// [`from_str`]: fn.from_str.html
+pub fn from_str_strict<T: DeserializeOwned>(query: &str) -> Result<T, Error> {
+    let (value, ignored) = deserialize(query)?;
+    match ignored.as_slice() {
+        [] => Ok(value),
+        [name] => Err(Error::custom(format_args!("unknown field `{}`", name))),
+        names => {
+            let names = names
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            Err(Error::custom(format_args!(
+                "unknown fields {}",
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// Deserializes a `T` from `query`, also returning the names of the
+/// parameters that `T` ignored.
+fn deserialize<T: DeserializeOwned>(query: &str) -> Result<(T, Vec<String>), Error> {
+    let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query)?;
+
+    // Group the values by parameter name, keeping the order of first occurrence
+    let mut params: Vec<(String, Vec<String>)> = Vec::new();
+    for (name, value) in pairs {
+        match params.iter_mut().find(|(param, _)| *param == name) {
+            Some((_, values)) => values.push(value),
+            None => params.push((name, vec![value])),
+        }
+    }
+
+    let ignored = Rc::new(RefCell::new(Vec::new()));
+    let value = T::deserialize(Params {
+        params,
+        ignored: ignored.clone(),
+    })?;
+    let ignored = ignored.borrow().clone();
+    Ok((value, ignored))
+}
+
+/// Deserializes the whole query string as a map from parameter names to
+/// [`Values`].
+struct Params {
+    params: Vec<(String, Vec<String>)>,
+    /// Collects the names of the parameters ignored by the deserialized type.
+    ignored: Rc<RefCell<Vec<String>>>,
+}
+
+impl Params {
+    fn into_map(self) -> MapDeserializer<'static, impl Iterator<Item = (String, Values)>, Error> {
+        let ignored = self.ignored;
+        MapDeserializer::new(self.params.into_iter().map(move |(name, values)| {
+            let values = Values {
+                name: name.clone(),
+                values,
+                ignored: ignored.clone(),
+            };
+            (name, values)
+        }))
+    }
+}
+
+impl<'de> Deserializer<'de> for Params {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(self.into_map())
+    }
+
+    /// Deserializes the parameters as a sequence of `(name, value)` pairs.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let pairs = self.params.into_iter().flat_map(|(name, values)| {
+            values
+                .into_iter()
+                .map(move |value| (name.clone(), Value(value)))
+        });
+        visitor.visit_seq(MapDeserializer::new(pairs))
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.into_map().end()?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string option bytes
+        byte_buf unit_struct newtype_struct tuple_struct struct identifier tuple
+        enum ignored_any
+    }
+}
+
+/// All values of a query parameter.
+struct Values {
+    name: String,
+    values: Vec<String>,
+    ignored: Rc<RefCell<Vec<String>>>,
+}
+
+impl Values {
+    /// Returns the only value of the parameter.
+    fn single(mut self) -> Result<Value, Error> {
+        match self.values.pop() {
+            Some(value) if self.values.is_empty() => Ok(Value(value)),
+            _ => Err(Error::custom(format_args!(
+                "duplicate field `{}`",
+                self.name
+            ))),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Values {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Forwards `deserialize_*` methods to the single value of the parameter.
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Values {
+    type Error = Error;
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let values = self.values.into_iter().filter(|value| !value.is_empty());
+        visitor.visit_seq(SeqDeserializer::new(values.map(Value)))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// Deserializes repeated parameters as a sequence.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.values.len() == 1 {
+            self.single()?.deserialize_any(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    /// Unknown parameters are ignored, even if they are repeated.
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.ignored.borrow_mut().push(self.name);
+        visitor.visit_unit()
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_identifier
+        deserialize_map
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+}
+
+/// A single value of a query parameter.
+///
+/// This behaves like the values deserialized by `serde_urlencoded`: Numbers
+/// and `bool`s are parsed from the string, and enums can only have unit
+/// variants.
+struct Value(String);
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Implements `deserialize_*` methods by parsing the value with `FromStr`.
+macro_rules! forward_parsed_value {
+    ($($ty:ident => $method:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse::<$ty>() {
+                    Ok(value) => value.into_deserializer().$method(visitor),
+                    Err(e) => Err(Error::custom(e)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::value::StringDeserializer::<Error>::new(self.0)
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        char str string unit bytes byte_buf unit_struct tuple_struct struct
+        identifier tuple ignored_any seq map
+    }
+
+    forward_parsed_value! {
+        bool => deserialize_bool,
+        u8 => deserialize_u8,
+        u16 => deserialize_u16,
+        u32 => deserialize_u32,
+        u64 => deserialize_u64,
+        i8 => deserialize_i8,
+        i16 => deserialize_i16,
+        i32 => deserialize_i32,
+        i64 => deserialize_i64,
+        f32 => deserialize_f32,
+        f64 => deserialize_f64,
+    }
+}
@@ -3,7 +3,7 @@
 macro_rules! doc {
     ($e:expr) => {
         #[doc = $e]
-        extern {}
+        extern "C" {}
     };
 }
 
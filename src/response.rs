@@ -0,0 +1,768 @@
+//! Provides the [`IntoResponse`] trait for types that can be sent as a
+//! response.
+//!
+//! The handlers passed to the `responding` constructors of [`AsyncService`]
+//! and [`SyncService`] (eg. [`SyncService::responding`]) can return any type
+//! implementing [`IntoResponse`], which avoids having to build a
+//! `Response<Body>` by hand for common cases:
+//!
+//! * `Response<Body>` is sent as-is.
+//! * `&'static str` and `String` are sent with a `Content-Type` of
+//!   `text/plain; charset=utf-8`.
+//! * `Vec<u8>` is sent with a `Content-Type` of `application/octet-stream`.
+//! * [`Json`] serializes its content as JSON.
+//! * `()` sends an empty `200 OK` response.
+//! * `StatusCode` sends an empty response with that status.
+//! * `(StatusCode, T)` converts `T` into a response and overrides its status.
+//! * `Result<T, E>` converts either `T` or `E` into a response.
+//! * [`Redirect`], [`Created`] and [`NoContent`] send an empty response (or
+//!   one with a body, in the case of [`Created`]) with the respective status
+//!   code and `Location` header.
+//!
+//! [`stream_blocking`] creates a response body from a blocking closure that
+//! writes it piece by piece, which is useful for producing large responses
+//! from [`SyncService`] handlers without buffering them in memory.
+//!
+//! # Examples
+//!
+//! ```
+//! use hyperdrive::{FromRequest, service::SyncService, response::Json};
+//! use hyper::StatusCode;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct User {
+//!     id: u32,
+//!     name: &'static str,
+//! }
+//!
+//! #[derive(FromRequest)]
+//! enum Route {
+//!     #[get("/users/{id}")]
+//!     User { id: u32 },
+//! }
+//!
+//! let service = SyncService::responding(|route: Route, _| match route {
+//!     Route::User { id: 1 } => (StatusCode::OK, Json(User { id: 1, name: "Ferris" })),
+//!     Route::User { .. } => (StatusCode::NOT_FOUND, Json(User { id: 0, name: "nobody" })),
+//! });
+//! ```
+//!
+//! # Application Errors
+//!
+//! Implementing [`IntoResponse`] for an application's error type allows
+//! handlers to return `Result<T, AppError>` and use the `?` operator, while
+//! every error is rendered consistently. Errors returned this way are sent as
+//! a normal response, so they are not passed to the error handler set via
+//! [`AsyncService::with_error_handler`], which only sees errors that
+//! prevented the handler from producing a response.
+//!
+//! ```
+//! use hyperdrive::{FromRequest, service::SyncService, response::{IntoResponse, Json}};
+//! use hyper::{Body, Response, StatusCode};
+//! use serde_json::json;
+//!
+//! enum AppError {
+//!     InvalidName(String),
+//! }
+//!
+//! impl IntoResponse for AppError {
+//!     fn into_response(self) -> Response<Body> {
+//!         match self {
+//!             AppError::InvalidName(name) => (
+//!                 StatusCode::UNPROCESSABLE_ENTITY,
+//!                 Json(json!({ "error": "invalid name", "name": name })),
+//!             )
+//!                 .into_response(),
+//!         }
+//!     }
+//! }
+//!
+//! fn validate(name: &str) -> Result<(), AppError> {
+//!     if name.chars().all(char::is_alphanumeric) {
+//!         Ok(())
+//!     } else {
+//!         Err(AppError::InvalidName(name.to_string()))
+//!     }
+//! }
+//!
+//! #[derive(FromRequest)]
+//! enum Route {
+//!     #[get("/hello/{name}")]
+//!     Hello { name: String },
+//! }
+//!
+//! let service = SyncService::responding(|route: Route, _| -> Result<_, AppError> {
+//!     match route {
+//!         Route::Hello { name } => {
+//!             validate(&name)?;
+//!             Ok(format!("Hello, {}!", name))
+//!         }
+//!     }
+//! });
+//! ```
+//!
+//! [`AsyncService`] handlers return a future that has to fail with a
+//! [`BoxedError`], so a future failing with an application error has to be
+//! converted first. `future.then(Ok::<_, BoxedError>)` turns it into a future
+//! resolving to a `Result`, which is then rendered as above.
+//!
+//! [`IntoResponse`]: trait.IntoResponse.html
+//! [`AsyncService`]: ../service/struct.AsyncService.html
+//! [`SyncService`]: ../service/struct.SyncService.html
+//! [`SyncService::responding`]: ../service/struct.SyncService.html#method.responding
+//! [`Json`]: struct.Json.html
+//! [`Redirect`]: struct.Redirect.html
+//! [`Created`]: struct.Created.html
+//! [`NoContent`]: struct.NoContent.html
+//! [`stream_blocking`]: fn.stream_blocking.html
+//! [`AsyncService::with_error_handler`]: ../service/struct.AsyncService.html#method.with_error_handler
+//! [`BoxedError`]: ../type.BoxedError.html
+
+use crate::{BoxedError, Error, ToRequest};
+use bytes::Bytes;
+use futures::{sync::mpsc, Async, Future, Poll, Sink, Stream};
+use http::{header, HeaderValue, Request, StatusCode, Uri};
+use hyper::{body::Payload, Body, Chunk, Response};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{fmt, mem};
+use tokio::executor::{DefaultExecutor, Executor};
+
+/// Trait for types that can be converted into an HTTP response.
+///
+/// Refer to the [module documentation] for the provided implementations.
+///
+/// The type parameter `B` is the body type of the response, which is `Body` for
+/// all provided implementations except the one for `Response<B>`. Services
+/// producing responses with a different body type are described in the
+/// documentation of [`ErrorBody`].
+///
+/// [module documentation]: index.html
+/// [`ErrorBody`]: trait.ErrorBody.html
+pub trait IntoResponse<B = Body> {
+    /// Converts `self` into a response.
+    fn into_response(self) -> Response<B>;
+}
+
+impl<B> IntoResponse<B> for Response<B> {
+    fn into_response(self) -> Response<B> {
+        self
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response<Body> {
+        with_content_type(Body::from(self), "text/plain; charset=utf-8")
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response<Body> {
+        with_content_type(Body::from(self), "text/plain; charset=utf-8")
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response<Body> {
+        with_content_type(Body::from(self), "application/octet-stream")
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response<Body> {
+        Response::new(Body::empty())
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = self;
+        response
+    }
+}
+
+impl<B, T: IntoResponse<B>> IntoResponse<B> for (StatusCode, T) {
+    fn into_response(self) -> Response<B> {
+        let mut response = self.1.into_response();
+        *response.status_mut() = self.0;
+        response
+    }
+}
+
+impl<B, T: IntoResponse<B>, E: IntoResponse<B>> IntoResponse<B> for Result<T, E> {
+    fn into_response(self) -> Response<B> {
+        match self {
+            Ok(ok) => ok.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+}
+
+/// Response body types that hyperdrive can create responses in.
+///
+/// [`AsyncService`], [`SyncService`] and most [`ServiceExt`] adapters are
+/// generic over the body type `B` of the responses they produce, which
+/// defaults to `Body`. Handlers can then return a `Response<B>`, eg. to pass
+/// the body of a response received from an upstream server through without
+/// re-wrapping it.
+///
+/// Responses created by hyperdrive itself (eg. for errors or requests that
+/// don't match any route) are built with a `Body` and converted using
+/// [`from_body`], and responses to `HEAD` requests get an [`empty`] body.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, response::ErrorBody, service::SyncService};
+/// use hyper::{body::Payload, Body, Chunk, Response};
+/// use futures::{Async, Poll};
+///
+/// /// A body that is either sent all at once, or streamed.
+/// enum MyBody {
+///     Full(Option<Chunk>),
+///     Streamed(Body),
+/// }
+///
+/// impl Payload for MyBody {
+///     type Data = Chunk;
+///     type Error = hyper::Error;
+///
+///     fn poll_data(&mut self) -> Poll<Option<Chunk>, hyper::Error> {
+///         match self {
+///             MyBody::Full(chunk) => Ok(Async::Ready(chunk.take())),
+///             MyBody::Streamed(body) => body.poll_data(),
+///         }
+///     }
+/// }
+///
+/// impl ErrorBody for MyBody {
+///     fn from_body(body: Body) -> Self {
+///         MyBody::Streamed(body)
+///     }
+/// }
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let service = SyncService::new(|route: Route, _| match route {
+///     Route::Index => Response::new(MyBody::Full(Some(Chunk::from("Hello World!")))),
+/// });
+/// ```
+///
+/// [`AsyncService`]: ../service/struct.AsyncService.html
+/// [`SyncService`]: ../service/struct.SyncService.html
+/// [`ServiceExt`]: ../service/trait.ServiceExt.html
+/// [`from_body`]: #tymethod.from_body
+/// [`empty`]: #method.empty
+pub trait ErrorBody: Payload + Sized {
+    /// Converts a `Body` into this body type.
+    fn from_body(body: Body) -> Self;
+
+    /// Creates an empty body.
+    ///
+    /// The default implementation converts an empty `Body`.
+    fn empty() -> Self {
+        Self::from_body(Body::empty())
+    }
+}
+
+impl ErrorBody for Body {
+    fn from_body(body: Body) -> Self {
+        body
+    }
+
+    fn empty() -> Self {
+        Body::empty()
+    }
+}
+
+/// Serializes a value as JSON and sends it with a `Content-Type` of
+/// `application/json`.
+///
+/// This uses `serde_json` for serialization. If serialization fails, an empty
+/// `500 Internal Server Error` response is sent instead.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::response::{IntoResponse, Json};
+/// use futures::{Future, Stream};
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::new();
+/// map.insert("answer", 42);
+///
+/// let response = Json(map).into_response();
+/// assert_eq!(response.headers()["content-type"], "application/json");
+///
+/// let body = response.into_body().concat2().wait().unwrap();
+/// assert_eq!(&*body, br#"{"answer":42}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Json<T: Serialize>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response<Body> {
+        match serde_json::to_vec(&self.0) {
+            Ok(json) => with_content_type(Body::from(json), "application/json"),
+            Err(_) => Error::from_status(StatusCode::INTERNAL_SERVER_ERROR)
+                .response()
+                .map(|()| Body::empty()),
+        }
+    }
+}
+
+/// Types that can be used as the target of a [`Redirect`] or the location of a
+/// [`Created`] resource.
+///
+/// This is implemented for strings and `Uri`s, as well as for the routes of a
+/// type implementing [`ToRequest`] (eg. via `#[hyperdrive(client)]`) and the
+/// requests they create, which use the path and query of the request URI.
+///
+/// [`Redirect`]: struct.Redirect.html
+/// [`Created`]: struct.Created.html
+/// [`ToRequest`]: ../trait.ToRequest.html
+pub trait IntoLocation {
+    /// Converts `self` into the value of a `Location` header.
+    ///
+    /// # Panics
+    ///
+    /// This may panic if `self` isn't a valid header value, eg. because it
+    /// contains a line break.
+    fn into_location(self) -> HeaderValue;
+}
+
+impl IntoLocation for &'_ str {
+    fn into_location(self) -> HeaderValue {
+        HeaderValue::from_str(self)
+            .unwrap_or_else(|_| panic!("invalid `Location` header value `{}`", self))
+    }
+}
+
+impl IntoLocation for String {
+    fn into_location(self) -> HeaderValue {
+        self.as_str().into_location()
+    }
+}
+
+impl IntoLocation for Uri {
+    fn into_location(self) -> HeaderValue {
+        HeaderValue::from_shared(Bytes::from(self.to_string()))
+            .expect("URIs are valid header values")
+    }
+}
+
+impl<B> IntoLocation for Request<B> {
+    fn into_location(self) -> HeaderValue {
+        self.uri().clone().into_location()
+    }
+}
+
+impl<R: ToRequest> IntoLocation for &'_ R {
+    fn into_location(self) -> HeaderValue {
+        self.path_and_query().into_location()
+    }
+}
+
+/// Redirects the client to another URI.
+///
+/// The response has an empty body and a `Location` header pointing to the
+/// target, which can be anything implementing [`IntoLocation`].
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, service::SyncService, response::Redirect};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+///
+///     #[get("/home")]
+///     Home,
+/// }
+///
+/// let service = SyncService::responding(|route: Route, _| match route {
+///     Route::Index => Redirect::temporary("/home"),
+///     Route::Home => Redirect::permanent("https://example.com/"),
+/// });
+/// ```
+///
+/// [`IntoLocation`]: trait.IntoLocation.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    status: StatusCode,
+    location: HeaderValue,
+}
+
+impl Redirect {
+    /// Redirects with `308 Permanent Redirect`.
+    ///
+    /// Clients may remember the redirect and go to `location` directly in the
+    /// future. The request method and body are kept when following the
+    /// redirect.
+    pub fn permanent<L: IntoLocation>(location: L) -> Self {
+        Self::with_status(StatusCode::PERMANENT_REDIRECT, location)
+    }
+
+    /// Redirects with `307 Temporary Redirect`.
+    ///
+    /// The request method and body are kept when following the redirect.
+    pub fn temporary<L: IntoLocation>(location: L) -> Self {
+        Self::with_status(StatusCode::TEMPORARY_REDIRECT, location)
+    }
+
+    /// Redirects with `303 See Other`.
+    ///
+    /// Clients follow the redirect with a `GET` request, which is what should
+    /// be sent after a `POST` request (eg. from a form) was handled.
+    pub fn see_other<L: IntoLocation>(location: L) -> Self {
+        Self::with_status(StatusCode::SEE_OTHER, location)
+    }
+
+    fn with_status<L: IntoLocation>(status: StatusCode, location: L) -> Self {
+        Self {
+            status,
+            location: location.into_location(),
+        }
+    }
+
+    /// Returns the status code of the redirect.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the value of the `Location` header.
+    pub fn location(&self) -> &HeaderValue {
+        &self.location
+    }
+}
+
+impl IntoResponse for Redirect {
+    fn into_response(self) -> Response<Body> {
+        let mut response = self.status.into_response();
+        response
+            .headers_mut()
+            .insert(header::LOCATION, self.location);
+        response
+    }
+}
+
+/// Responds with `201 Created`, pointing to the created resource.
+///
+/// The response has a `Location` header, and an empty body unless one is
+/// set with [`body`] or [`json`].
+///
+/// # Examples
+///
+/// A `POST` handler that points to the new resource using the route that
+/// serves it:
+///
+/// ```
+/// use hyperdrive::{
+///     FromRequest, body, service::SyncService, response::{Created, IntoResponse, Json},
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Post {
+///     title: String,
+/// }
+///
+/// #[derive(FromRequest)]
+/// #[hyperdrive(client)]
+/// enum Route {
+///     #[get("/posts/{id}")]
+///     GetPost { id: u32 },
+///
+///     #[post("/posts")]
+///     AddPost {
+///         #[body]
+///         post: body::Json<Post>,
+///     },
+/// }
+///
+/// let service = SyncService::new(|route: Route, _| match route {
+///     Route::AddPost { post } => {
+///         let id = 42; // store the post in the database
+///         Created::new(&Route::GetPost { id })
+///             .json(post.0)
+///             .into_response()
+///     }
+///     Route::GetPost { id } => Json(Post { title: format!("Post #{}", id) }).into_response(),
+/// });
+/// ```
+///
+/// [`body`]: #method.body
+/// [`json`]: #method.json
+#[derive(Debug)]
+pub struct Created {
+    location: HeaderValue,
+    response: Response<Body>,
+}
+
+impl Created {
+    /// Creates a `201 Created` response pointing to `location`.
+    pub fn new<L: IntoLocation>(location: L) -> Self {
+        Self {
+            location: location.into_location(),
+            response: Response::new(Body::empty()),
+        }
+    }
+
+    /// Sends `body` (converted using [`IntoResponse`]) along with the
+    /// response.
+    ///
+    /// The status code of `body` is replaced with `201 Created` unless it
+    /// indicates an error, like the one [`Json`] sends when serialization
+    /// fails.
+    ///
+    /// [`IntoResponse`]: trait.IntoResponse.html
+    /// [`Json`]: struct.Json.html
+    pub fn body<T: IntoResponse>(mut self, body: T) -> Self {
+        self.response = body.into_response();
+        self
+    }
+
+    /// Sends `value` serialized as JSON along with the response.
+    pub fn json<T: Serialize>(self, value: T) -> Self {
+        self.body(Json(value))
+    }
+
+    /// Returns the value of the `Location` header.
+    pub fn location(&self) -> &HeaderValue {
+        &self.location
+    }
+}
+
+impl IntoResponse for Created {
+    fn into_response(self) -> Response<Body> {
+        let mut response = self.response;
+        if response.status().is_client_error() || response.status().is_server_error() {
+            return response;
+        }
+
+        *response.status_mut() = StatusCode::CREATED;
+        response
+            .headers_mut()
+            .insert(header::LOCATION, self.location);
+        response
+    }
+}
+
+/// Responds with an empty `204 No Content` response.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct NoContent;
+
+impl IntoResponse for NoContent {
+    fn into_response(self) -> Response<Body> {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
+
+pub(crate) fn with_content_type(body: Body, content_type: &'static str) -> Response<Body> {
+    let mut response = Response::new(body);
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+/// Size of the chunks sent by a [`BodyWriter`].
+///
+/// [`BodyWriter`]: struct.BodyWriter.html
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Number of chunks buffered between a [`BodyWriter`] and the response body.
+///
+/// [`BodyWriter`]: struct.BodyWriter.html
+const CHANNEL_CAPACITY: usize = 4;
+
+/// Creates a response body that is produced by a blocking closure.
+///
+/// `f` is called with a [`BodyWriter`] once the body is first polled (so not
+/// at all when the response to a `HEAD` request is sent). It runs on tokio's
+/// blocking thread pool, like the handlers of a [`SyncService`]. Everything
+/// it writes is sent to the client in chunks of 8 KiB. At most a few chunks
+/// are buffered: when the client doesn't read the body fast enough, writing
+/// blocks until there is space again. This allows sending responses of any
+/// size with constant memory.
+///
+/// If `f` returns an error or panics, the body fails, which aborts the
+/// response. If the client disconnects, writing fails with an error of kind
+/// `BrokenPipe`.
+///
+/// The body must be polled on a tokio runtime using a thread pool (as
+/// `tokio::run` does), since `f` is run via [`hyperdrive::blocking`].
+///
+/// # Examples
+///
+/// Send a CSV file with a million rows:
+///
+/// ```
+/// use hyperdrive::{FromRequest, response::stream_blocking, service::SyncService};
+/// use hyper::Response;
+/// use std::io::Write;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/export.csv")]
+///     Export,
+/// }
+///
+/// let service = SyncService::new(|Route::Export, _| {
+///     let body = stream_blocking(|writer| {
+///         writeln!(writer, "id,square")?;
+///         for id in 0..1_000_000u64 {
+///             writeln!(writer, "{},{}", id, id * id)?;
+///         }
+///         Ok(())
+///     });
+///
+///     Response::builder()
+///         .header("Content-Type", "text/csv")
+///         .body(body)
+///         .unwrap()
+/// });
+/// ```
+///
+/// [`BodyWriter`]: struct.BodyWriter.html
+/// [`SyncService`]: ../service/struct.SyncService.html
+/// [`hyperdrive::blocking`]: ../fn.blocking.html
+pub fn stream_blocking<F>(f: F) -> Body
+where
+    F: FnOnce(&mut BodyWriter) -> io::Result<()> + Send + 'static,
+{
+    Body::wrap_stream(BlockingBody::Pending(Some(Box::new(f))))
+}
+
+type Writer = Box<dyn FnOnce(&mut BodyWriter) -> io::Result<()> + Send>;
+
+/// The stream backing the body created by `stream_blocking`.
+enum BlockingBody {
+    /// The writer hasn't been started yet.
+    Pending(Option<Writer>),
+    Streaming(mpsc::Receiver<Result<Chunk, BoxedError>>),
+}
+
+impl Stream for BlockingBody {
+    type Item = Chunk;
+    type Error = BoxedError;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, BoxedError> {
+        loop {
+            match self {
+                BlockingBody::Pending(f) => {
+                    let f = f.take().expect("body polled after failing to start");
+                    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+                    let task = crate::blocking(move || {
+                        write_body(f, tx);
+                        Ok(())
+                    });
+                    DefaultExecutor::current()
+                        .spawn(Box::new(task))
+                        .map_err(|e| format!("couldn't start body writer: {}", e))?;
+                    *self = BlockingBody::Streaming(rx);
+                }
+                BlockingBody::Streaming(rx) => {
+                    return match rx.poll() {
+                        Ok(Async::Ready(Some(Ok(chunk)))) => Ok(Async::Ready(Some(chunk))),
+                        Ok(Async::Ready(Some(Err(e)))) => Err(e),
+                        Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+                        Ok(Async::NotReady) => Ok(Async::NotReady),
+                        Err(()) => unreachable!("receiving from a channel can't fail"),
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Runs `f` and sends its output (or failure) to `tx`.
+fn write_body(f: Writer, tx: mpsc::Sender<Result<Chunk, BoxedError>>) {
+    let mut writer = BodyWriter {
+        tx: Some(tx),
+        buf: Vec::with_capacity(CHUNK_SIZE),
+    };
+
+    let result = match catch_unwind(AssertUnwindSafe(|| f(&mut writer))) {
+        Ok(Ok(())) => match writer.flush() {
+            Ok(()) => return,
+            Err(e) => e.into(),
+        },
+        Ok(Err(e)) => e.into(),
+        Err(_) => BoxedError::from("body writer panicked"),
+    };
+
+    // The client might already be gone, in which case nobody cares about the
+    // error.
+    if let Some(tx) = writer.tx.take() {
+        let _ = tx.send(Err(result)).wait();
+    }
+}
+
+/// Writes a response body created by [`stream_blocking`].
+///
+/// Writes are buffered and sent in chunks. Calling `flush` sends the buffered
+/// data right away, which is otherwise done when the closure passed to
+/// [`stream_blocking`] returns successfully.
+///
+/// [`stream_blocking`]: fn.stream_blocking.html
+pub struct BodyWriter {
+    /// The sending half of the channel, `None` once the receiver is gone.
+    tx: Option<mpsc::Sender<Result<Chunk, BoxedError>>>,
+    buf: Vec<u8>,
+}
+
+impl BodyWriter {
+    /// Sends the buffered data, blocking until there is space in the channel.
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let tx = self.tx.take().ok_or_else(disconnected)?;
+        let chunk = Chunk::from(mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE)));
+        self.tx = Some(tx.send(Ok(chunk)).wait().map_err(|_| disconnected())?);
+        Ok(())
+    }
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.tx.is_none() {
+            return Err(disconnected());
+        }
+
+        let len = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        if self.buf.len() == CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+impl fmt::Debug for BodyWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyWriter")
+            .field("buffered", &self.buf.len())
+            .field("connected", &self.tx.is_some())
+            .finish()
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the response body was dropped")
+}
@@ -0,0 +1,38 @@
+//! Builds request paths from route attributes.
+//!
+//! This is used by the `ToRequest` implementation generated by
+//! `#[derive(FromRequest)]` for types marked with `#[hyperdrive(client)]`.
+
+use serde::Serialize;
+
+/// Appends the value of a `{placeholder}` to `path`.
+///
+/// The generated `FromRequest` code doesn't percent-decode path segments, so
+/// only characters that would make the path invalid or change its structure
+/// are percent-encoded. For `{rest...}` placeholders, `/` is kept.
+pub fn push_placeholder(path: &mut String, value: &str, rest: bool) {
+    for byte in value.bytes() {
+        let keep = match byte {
+            b'/' => rest,
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => true,
+            b'-' | b'.' | b'_' | b'~' | b'%' => true,
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => true,
+            b':' | b'@' => true,
+            _ => false,
+        };
+        if keep {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("%{:02X}", byte));
+        }
+    }
+}
+
+/// Appends the serialized `#[query_params]` field to `path`.
+pub fn push_query<T: Serialize>(path: &mut String, query: &T) {
+    let query = serde_urlencoded::to_string(query).expect("couldn't serialize query parameters");
+    if !query.is_empty() {
+        path.push('?');
+        path.push_str(&query);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::error::method_order;
+
+/// Describes how a request would be routed, without decoding it.
+///
+/// This is returned by [`FromRequest::match_route`], which only looks at the
+/// method and path of a request. Guards, query parameters and the body are
+/// not considered, so a request that matches a route might still be rejected
+/// when it is decoded.
+///
+/// [`FromRequest::match_route`]: trait.FromRequest.html#method.match_route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteMatch {
+    /// The request matches the route of the variant (or struct) `name`.
+    Route {
+        /// The name of the matched variant.
+        name: &'static str,
+        /// The raw path segments captured by the `{placeholders}` of the
+        /// route, in the order they appear in the path, as
+        /// `(field name, segment)` pairs.
+        ///
+        /// Their `FromStr` implementations accepted the segments.
+        placeholders: Vec<(&'static str, String)>,
+    },
+
+    /// The request is passed to the `#[forward]` field of the variant `name`,
+    /// which routes it as described by `inner`.
+    Forward {
+        /// The name of the variant containing the `#[forward]` field.
+        name: &'static str,
+        /// How the forwarded type matches the request.
+        inner: Box<RouteMatch>,
+    },
+
+    /// No route matches the path. Decoding fails with
+    /// `ErrorKind::NoMatchingRoute`.
+    NoMatchingRoute,
+
+    /// The path matches a route, but the method doesn't. Decoding fails with
+    /// `ErrorKind::WrongMethod`.
+    ///
+    /// Contains the methods that are allowed for the path, in the same
+    /// canonical order as `Error::allowed_methods`.
+    WrongMethod(Vec<&'static http::Method>),
+
+    /// The path matches the route of the variant `name`, but the `FromStr`
+    /// implementation of the `{placeholder}` `field` rejects its segment.
+    /// Decoding fails with `ErrorKind::PathSegment`.
+    PathSegment {
+        /// The name of the matched variant.
+        name: &'static str,
+        /// The name of the placeholder that couldn't be parsed.
+        field: &'static str,
+    },
+
+    /// The `FromRequest` implementation can't tell how it routes a request
+    /// without decoding it.
+    ///
+    /// This is returned by the default implementation of `match_route`.
+    Unknown,
+}
+
+impl RouteMatch {
+    /// Creates a `WrongMethod` match from a list of allowed methods.
+    ///
+    /// Duplicates are removed and the methods are sorted like the ones passed
+    /// to `Error::wrong_method`.
+    pub fn wrong_method<M>(allowed_methods: M) -> Self
+    where
+        M: IntoIterator<Item = &'static http::Method>,
+    {
+        let mut methods = allowed_methods.into_iter().collect::<Vec<_>>();
+        methods.sort_by_key(|method| method_order(method));
+        methods.dedup();
+        RouteMatch::WrongMethod(methods)
+    }
+
+    /// Creates the match for a request passed to the `#[forward]` field of
+    /// the variant `name`.
+    ///
+    /// `inner` is the match of the forwarded type. If it rejects the request,
+    /// that rejection is returned instead, and if it rejects the method,
+    /// `allowed_methods` (the methods accepted by the other routes of the
+    /// outer type) are added to the ones accepted by the forwarded type.
+    ///
+    /// This is called by the code generated by `#[derive(FromRequest)]`.
+    pub fn forward<M>(name: &'static str, inner: RouteMatch, allowed_methods: M) -> Self
+    where
+        M: IntoIterator<Item = &'static http::Method>,
+    {
+        match inner {
+            RouteMatch::WrongMethod(methods) => {
+                RouteMatch::wrong_method(allowed_methods.into_iter().chain(methods))
+            }
+            RouteMatch::NoMatchingRoute | RouteMatch::PathSegment { .. } => inner,
+            inner => RouteMatch::Forward {
+                name,
+                inner: Box::new(inner),
+            },
+        }
+    }
+
+    /// Returns the name of the matched variant.
+    ///
+    /// This is the name that `FromRequest::route_name` returns for the
+    /// decoded request. For `Forward` matches, it is the name of the variant
+    /// containing the `#[forward]` field. Returns `None` if the request is
+    /// rejected, or if the match is `Unknown`.
+    pub fn route_name(&self) -> Option<&'static str> {
+        match *self {
+            RouteMatch::Route { name, .. } | RouteMatch::Forward { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Route metadata and auditing.
+//!
+//! [`FromRequest::routes`] lists the routes of a type as [`RouteInfo`]s,
+//! including the routes of types it forwards to. [`audit`] builds on that to
+//! list all routes in a table and to point out suspicious setups, which is
+//! useful to check a router in a test before deploying it.
+//!
+//! # Examples
+//!
+//! ```
+//! use hyperdrive::{FromRequest, routes};
+//!
+//! #[derive(FromRequest)]
+//! enum Api {
+//!     #[get("/users/{id}")]
+//!     User { id: u32 },
+//! }
+//!
+//! #[derive(FromRequest)]
+//! enum Routes {
+//!     #[get("/")]
+//!     Index,
+//!
+//!     #[get("/users/me")]
+//!     Me,
+//!
+//!     Api {
+//!         #[forward]
+//!         api: Api,
+//!     },
+//! }
+//!
+//! let audit = routes::audit::<Routes>();
+//! assert!(audit.warnings().is_empty(), "{}", audit);
+//! assert_eq!(
+//!     audit.to_string(),
+//!     "\
+//! METHOD  PATH         ROUTE
+//! GET     /            Index
+//! HEAD    /            Index (implied)
+//! GET     /users/me    Me
+//! HEAD    /users/me    Me (implied)
+//! GET     /users/{id}  Api > User
+//! HEAD    /users/{id}  Api > User (implied)
+//! "
+//! );
+//! ```
+//!
+//! [`FromRequest::routes`]: ../trait.FromRequest.html#method.routes
+//! [`RouteInfo`]: struct.RouteInfo.html
+//! [`audit`]: fn.audit.html
+
+use crate::FromRequest;
+use http::Method;
+use std::fmt;
+
+/// Describes a route of a [`FromRequest`] implementor.
+///
+/// [`FromRequest`]: ../trait.FromRequest.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    method: Method,
+    path: &'static str,
+    name: &'static str,
+    implied: bool,
+    forwarded_by: Vec<&'static str>,
+    doc: Option<&'static str>,
+    param_docs: Vec<(&'static str, &'static str)>,
+}
+
+impl RouteInfo {
+    /// Creates a route decoding `method` requests to `path` into the variant
+    /// (or struct) `name`.
+    ///
+    /// `path` is written like in route attributes (eg. `/users/{id}`).
+    pub fn new(method: Method, path: &'static str, name: &'static str) -> Self {
+        Self {
+            method,
+            path,
+            name,
+            implied: false,
+            forwarded_by: Vec::new(),
+            doc: None,
+            param_docs: Vec::new(),
+        }
+    }
+
+    /// Marks the route as implied by another one, like the `HEAD` route of
+    /// each `GET` route.
+    pub fn implied(mut self) -> Self {
+        self.implied = true;
+        self
+    }
+
+    /// Marks the route as being reached through the `#[forward]` field of the
+    /// variant `name`.
+    pub fn forwarded_by(mut self, name: &'static str) -> Self {
+        self.forwarded_by.insert(0, name);
+        self
+    }
+
+    /// Sets the documentation of the route.
+    ///
+    /// `#[derive(FromRequest)]` uses the doc comment of the variant (or
+    /// struct).
+    pub fn documented(mut self, doc: &'static str) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
+    /// Adds the documentation of the parameter `name`.
+    ///
+    /// `#[derive(FromRequest)]` uses the doc comments of the `{placeholder}`
+    /// and `#[query_params]` fields.
+    pub fn param_doc(mut self, name: &'static str, doc: &'static str) -> Self {
+        self.param_docs.push((name, doc));
+        self
+    }
+
+    /// Returns the HTTP method of the route.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the path of the route, as written in the route attribute.
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    /// Returns the name of the variant (or struct) decoded from the route.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns whether the route was implied by another one.
+    pub fn is_implied(&self) -> bool {
+        self.implied
+    }
+
+    /// Returns the names of the variants whose `#[forward]` fields lead to
+    /// this route, outermost first.
+    ///
+    /// This is empty for routes of the type itself.
+    pub fn forwarded_by_names(&self) -> &[&'static str] {
+        &self.forwarded_by
+    }
+
+    /// Returns the documentation of the route, if any.
+    pub fn doc(&self) -> Option<&'static str> {
+        self.doc
+    }
+
+    /// Returns the documented parameters of the route as `(name, doc)` pairs.
+    ///
+    /// Placeholders come first, in the order they appear in the path,
+    /// followed by the `#[query_params]` field.
+    pub fn param_docs(&self) -> &[(&'static str, &'static str)] {
+        &self.param_docs
+    }
+
+    /// Formats the variant names leading to the route (eg. `Api > User`).
+    fn route(&self) -> String {
+        let mut route = String::new();
+        for name in &self.forwarded_by {
+            route.push_str(name);
+            route.push_str(" > ");
+        }
+        route.push_str(self.name);
+        route
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{} {}` ({})", self.method, self.path, self.route())
+    }
+}
+
+/// A suspicious route setup found by [`audit`].
+///
+/// [`audit`]: fn.audit.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// `HEAD` requests to the path of a `GET` route are decoded into a
+    /// different variant than `GET` requests, since another variant has an
+    /// explicit `HEAD` route for the same path.
+    HeadOverridden {
+        /// The `GET` route.
+        get: RouteInfo,
+        /// The `HEAD` route of the other variant.
+        head: RouteInfo,
+    },
+
+    /// A route of a forwarded type can never be reached, since a route of the
+    /// forwarding type matches all of its requests first.
+    Shadowed {
+        /// The unreachable route.
+        route: RouteInfo,
+        /// The route matching its requests.
+        by: RouteInfo,
+    },
+
+    /// The paths of two routes only differ by a trailing slash, which is
+    /// often unintended.
+    TrailingSlash {
+        /// The route without the trailing slash.
+        without: RouteInfo,
+        /// The route with the trailing slash.
+        with: RouteInfo,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::HeadOverridden { get, head } => write!(
+                f,
+                "HEAD requests for {} are decoded by {} instead",
+                get, head
+            ),
+            Warning::Shadowed { route, by } => write!(
+                f,
+                "{} is unreachable, since {} matches all of its requests",
+                route, by
+            ),
+            Warning::TrailingSlash { without, with } => write!(
+                f,
+                "{} and {} only differ by a trailing slash",
+                without, with
+            ),
+        }
+    }
+}
+
+/// The routes of a [`FromRequest`] implementor, and any [`Warning`]s about
+/// them.
+///
+/// The `Display` implementation prints a table of all routes, followed by the
+/// warnings.
+///
+/// [`FromRequest`]: ../trait.FromRequest.html
+/// [`Warning`]: enum.Warning.html
+#[derive(Debug, Clone)]
+pub struct Audit {
+    routes: Vec<RouteInfo>,
+    warnings: Vec<Warning>,
+}
+
+impl Audit {
+    /// Returns all routes, including forwarded and implied ones.
+    pub fn routes(&self) -> &[RouteInfo] {
+        &self.routes
+    }
+
+    /// Returns the warnings found for the routes.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+impl fmt::Display for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .routes
+            .iter()
+            .map(|route| {
+                let mut name = route.route();
+                if route.implied {
+                    name.push_str(" (implied)");
+                }
+                (route.method.as_str(), route.path, name)
+            })
+            .collect::<Vec<_>>();
+        let method_width = rows.iter().map(|row| row.0.len()).fold(6, usize::max);
+        let path_width = rows.iter().map(|row| row.1.len()).fold(4, usize::max);
+
+        writeln!(
+            f,
+            "{:m$}  {:p$}  ROUTE",
+            "METHOD",
+            "PATH",
+            m = method_width,
+            p = path_width
+        )?;
+        for (method, path, name) in rows {
+            writeln!(
+                f,
+                "{:m$}  {:p$}  {}",
+                method,
+                path,
+                name,
+                m = method_width,
+                p = path_width
+            )?;
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f)?;
+            for warning in &self.warnings {
+                writeln!(f, "warning: {}", warning)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the routes of `R` and checks them for suspicious setups.
+///
+/// The returned [`Audit`] contains all routes returned by
+/// [`FromRequest::routes`] and [`Warning`]s about:
+///
+/// * `GET` routes whose `HEAD` requests are decoded into another variant.
+/// * Routes of `#[forward]`ed types that are unreachable, because a route of
+///   the forwarding type with the same method matches all of their paths.
+///   Note that a placeholder also matches segments its `FromStr`
+///   implementation rejects, so `/users/{id}` shadows `/users/me`.
+/// * Paths that only differ by a trailing slash.
+///
+/// [`Audit`]: struct.Audit.html
+/// [`FromRequest::routes`]: ../trait.FromRequest.html#method.routes
+/// [`Warning`]: enum.Warning.html
+pub fn audit<R: FromRequest>() -> Audit {
+    let routes = R::routes();
+    let mut warnings = Vec::new();
+
+    for get in routes.iter().filter(|r| r.method == Method::GET) {
+        let head = routes.iter().find(|r| {
+            r.method == Method::HEAD
+                && r.forwarded_by == get.forwarded_by
+                && same_paths(r.path, get.path)
+        });
+        if let Some(head) = head {
+            if head.name != get.name {
+                warnings.push(Warning::HeadOverridden {
+                    get: get.clone(),
+                    head: head.clone(),
+                });
+            }
+        }
+    }
+
+    // Only report routes written by the user, implied ones are shadowed along with them
+    for route in routes.iter().filter(|r| !r.implied) {
+        let by = routes.iter().find(|by| {
+            by.forwarded_by.len() < route.forwarded_by.len()
+                && route.forwarded_by.starts_with(&by.forwarded_by)
+                && by.method == route.method
+                && covers(by.path, route.path)
+        });
+        if let Some(by) = by {
+            warnings.push(Warning::Shadowed {
+                route: route.clone(),
+                by: by.clone(),
+            });
+        }
+    }
+
+    let mut reported = Vec::new();
+    for without in &routes {
+        for with in &routes {
+            if with.path.ends_with('/')
+                && with.path != "/"
+                && same_paths(&with.path[..with.path.len() - 1], without.path)
+                && !reported.contains(&(without.path, with.path))
+            {
+                reported.push((without.path, with.path));
+                warnings.push(Warning::TrailingSlash {
+                    without: without.clone(),
+                    with: with.clone(),
+                });
+            }
+        }
+    }
+
+    Audit { routes, warnings }
+}
+
+/// A segment of a route path.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    /// `{name}`, matching a single non-empty segment.
+    Placeholder,
+    /// `{name...}`, matching the rest of the path.
+    Rest,
+}
+
+/// Splits a route path into its segments.
+///
+/// Returns `None` for the asterisk path `*`.
+fn segments(path: &str) -> Option<Vec<Segment<'_>>> {
+    if path == "*" {
+        return None;
+    }
+
+    let segments = path
+        .split('/')
+        .skip(1)
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with("...}") {
+                Segment::Rest
+            } else if segment.starts_with('{') && segment.ends_with('}') {
+                Segment::Placeholder
+            } else {
+                Segment::Literal(segment)
+            }
+        })
+        .collect();
+    Some(segments)
+}
+
+/// Returns whether two route paths match the same requests (ie. only differ in
+/// the names of their placeholders).
+fn same_paths(a: &str, b: &str) -> bool {
+    segments(a) == segments(b)
+}
+
+/// Returns whether the route path `outer` matches every path matched by
+/// `inner`.
+fn covers(outer: &str, inner: &str) -> bool {
+    fn covers_segments(outer: &[Segment<'_>], inner: &[Segment<'_>]) -> bool {
+        match (outer.split_first(), inner.split_first()) {
+            (None, None) => true,
+            // A rest placeholder matches anything after the `/`
+            (Some((Segment::Rest, _)), Some(_)) => true,
+            (Some((outer_first, outer_rest)), Some((inner_first, inner_rest))) => {
+                let first_covered = match (outer_first, inner_first) {
+                    (Segment::Literal(outer), Segment::Literal(inner)) => outer == inner,
+                    (Segment::Placeholder, Segment::Literal(inner)) => !inner.is_empty(),
+                    (Segment::Placeholder, Segment::Placeholder) => true,
+                    _ => false,
+                };
+                first_covered && covers_segments(outer_rest, inner_rest)
+            }
+            _ => false,
+        }
+    }
+
+    match (segments(outer), segments(inner)) {
+        (Some(outer), Some(inner)) => covers_segments(&outer, &inner),
+        (None, None) => true,
+        _ => false,
+    }
+}
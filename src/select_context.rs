@@ -0,0 +1,66 @@
+//! Selects the context passed to guards and `FromBody` implementations from a
+//! `ContextPair`.
+//!
+//! This is used by the code generated by `#[derive(FromRequest)]` for types
+//! whose `#[context]` is a `ContextPair` (other contexts are passed to
+//! `FromContext` directly). Since the compiler doesn't allow an `AsRef` impl
+//! for "either half", this relies on method resolution preferring receivers
+//! with more references: `Select` has an implementation for every place a
+//! context can come from, and the first one that applies is used. The call
+//! must look like this, with all traits in scope:
+//!
+//! ```ignore
+//! (&&&Select::<C, T>::default()).select_context(context)
+//! ```
+//!
+//! Each implementation is bounded by the `FromContext` impl it uses, so a
+//! context that neither the pair nor its halves provide is a compile error
+//! instead of a path taken at runtime. In generic code, the where clauses only
+//! permit the `FromContext` path, which is also what generic `FromRequest`
+//! impls are bounded by.
+
+use crate::{ContextPair, FromContext};
+use std::marker::PhantomData;
+
+/// Selects a `&T` from a context `C`.
+#[derive(Debug)]
+pub struct Select<C, T>(PhantomData<fn(&C) -> T>);
+
+impl<C, T> Default for Select<C, T> {
+    fn default() -> Self {
+        Select(PhantomData)
+    }
+}
+
+/// Uses the `FromContext` impl of `T`, if there is one.
+pub trait SelectDirect<C, T> {
+    fn select_context<'a>(&self, context: &'a C) -> &'a T;
+}
+
+impl<C, T: FromContext<C>> SelectDirect<C, T> for &&Select<C, T> {
+    fn select_context<'a>(&self, context: &'a C) -> &'a T {
+        T::from_context(context)
+    }
+}
+
+/// Takes `T` from the first half of a `ContextPair`.
+pub trait SelectLeft<C, T> {
+    fn select_context<'a>(&self, context: &'a C) -> &'a T;
+}
+
+impl<A, B, T: FromContext<A>> SelectLeft<ContextPair<A, B>, T> for &Select<ContextPair<A, B>, T> {
+    fn select_context<'a>(&self, context: &'a ContextPair<A, B>) -> &'a T {
+        T::from_context(&context.0)
+    }
+}
+
+/// Takes `T` from the second half of a `ContextPair`.
+pub trait SelectRight<C, T> {
+    fn select_context<'a>(&self, context: &'a C) -> &'a T;
+}
+
+impl<A, B, T: FromContext<B>> SelectRight<ContextPair<A, B>, T> for Select<ContextPair<A, B>, T> {
+    fn select_context<'a>(&self, context: &'a ContextPair<A, B>) -> &'a T {
+        T::from_context(&context.1)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{service::GracefulShutdown, BoxedError};
+use futures::{future, Future};
+use hyper::{
+    body::Payload,
+    server::conn::AddrStream,
+    service::{MakeService, Service},
+    Body, Server,
+};
+use std::io;
+use std::net::ToSocketAddrs;
+use tokio::runtime::Runtime;
+
+/// Binds a hyper server to `addr` and serves requests using `service`, blocking
+/// the current thread.
+///
+/// This is a shorthand for the common setup of binding a `hyper::Server`,
+/// creating a multi-threaded tokio runtime and running the server on it. Both
+/// [`AsyncService`] and [`SyncService`] can be passed directly; other services
+/// (for example, ones wrapped in [`ServiceExt`] adapters) can be turned into a
+/// `MakeService` using [`ServiceExt::make_service_by_cloning`].
+///
+/// This function only returns when the server fails. Errors encountered while
+/// binding to `addr` are returned. Any error produced by the running server is
+/// logged (using the `hyperdrive::server` target) and then returned as well.
+/// Use [`serve_with_shutdown`] to be able to stop the server.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyperdrive::{FromRequest, service::SyncService};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// hyperdrive::serve(
+///     "127.0.0.1:8080",
+///     SyncService::responding(|_: Route, _| "Hello World!"),
+/// ).expect("server failed");
+/// ```
+///
+/// [`AsyncService`]: service/struct.AsyncService.html
+/// [`SyncService`]: service/struct.SyncService.html
+/// [`ServiceExt`]: service/trait.ServiceExt.html
+/// [`ServiceExt::make_service_by_cloning`]: service/trait.ServiceExt.html#tymethod.make_service_by_cloning
+/// [`serve_with_shutdown`]: fn.serve_with_shutdown.html
+pub fn serve<A, M, S, F, E, B>(addr: A, service: M) -> Result<(), BoxedError>
+where
+    A: ToSocketAddrs,
+    M: for<'a> MakeService<
+            &'a AddrStream,
+            ReqBody = Body,
+            ResBody = B,
+            Error = BoxedError,
+            Service = S,
+            Future = F,
+            MakeError = E,
+        > + Send
+        + 'static,
+    S: Service<ReqBody = Body, ResBody = B, Error = BoxedError> + Send + 'static,
+    S::Future: Send + 'static,
+    F: Future<Item = S, Error = E> + Send + 'static,
+    E: Into<BoxedError>,
+    B: Payload,
+{
+    run(addr, service, future::empty::<(), ()>())
+}
+
+/// Like [`serve`], but stops the server when the shutdown of `shutdown` is
+/// triggered.
+///
+/// When [`GracefulShutdown::shutdown`] is called, the server stops accepting
+/// new connections and waits for all open connections to finish their
+/// in-flight requests. This function returns `Ok(())` once that is done and all
+/// remaining tasks on the runtime have completed.
+///
+/// Wrapping `service` using [`ServiceExt::graceful_shutdown`] with the same
+/// handle additionally allows using [`GracefulShutdown::wait_idle`] from
+/// another thread to bound the time requests are given to complete.
+///
+/// # Examples
+///
+/// Shut down when Ctrl+C is pressed:
+///
+/// ```no_run
+/// use hyperdrive::{FromRequest, service::{GracefulShutdown, SyncService}};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let shutdown = GracefulShutdown::new();
+/// let handle = shutdown.clone();
+/// ctrlc::set_handler(move || handle.shutdown()).expect("couldn't set Ctrl+C handler");
+///
+/// hyperdrive::serve_with_shutdown(
+///     "127.0.0.1:8080",
+///     SyncService::responding(|_: Route, _| "Hello World!"),
+///     &shutdown,
+/// ).expect("server failed");
+/// ```
+///
+/// [`serve`]: fn.serve.html
+/// [`GracefulShutdown::shutdown`]: service/struct.GracefulShutdown.html#method.shutdown
+/// [`GracefulShutdown::wait_idle`]: service/struct.GracefulShutdown.html#method.wait_idle
+/// [`ServiceExt::graceful_shutdown`]: service/trait.ServiceExt.html#tymethod.graceful_shutdown
+pub fn serve_with_shutdown<A, M, S, F, E, B>(
+    addr: A,
+    service: M,
+    shutdown: &GracefulShutdown,
+) -> Result<(), BoxedError>
+where
+    A: ToSocketAddrs,
+    M: for<'a> MakeService<
+            &'a AddrStream,
+            ReqBody = Body,
+            ResBody = B,
+            Error = BoxedError,
+            Service = S,
+            Future = F,
+            MakeError = E,
+        > + Send
+        + 'static,
+    S: Service<ReqBody = Body, ResBody = B, Error = BoxedError> + Send + 'static,
+    S::Future: Send + 'static,
+    F: Future<Item = S, Error = E> + Send + 'static,
+    E: Into<BoxedError>,
+    B: Payload,
+{
+    run(addr, service, shutdown.signal())
+}
+
+fn run<A, M, S, F, E, B, G>(addr: A, service: M, signal: G) -> Result<(), BoxedError>
+where
+    A: ToSocketAddrs,
+    M: for<'a> MakeService<
+            &'a AddrStream,
+            ReqBody = Body,
+            ResBody = B,
+            Error = BoxedError,
+            Service = S,
+            Future = F,
+            MakeError = E,
+        > + Send
+        + 'static,
+    S: Service<ReqBody = Body, ResBody = B, Error = BoxedError> + Send + 'static,
+    S::Future: Send + 'static,
+    F: Future<Item = S, Error = E> + Send + 'static,
+    E: Into<BoxedError>,
+    B: Payload,
+    G: Future<Item = ()> + Send + 'static,
+{
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to bind to"))?;
+
+    let mut runtime = Runtime::new()?;
+    let server = Server::try_bind(&addr)?
+        .serve(service)
+        .with_graceful_shutdown(signal)
+        .map_err(|error| {
+            log::error!(target: "hyperdrive::server", "server error: {}", error);
+            error
+        });
+
+    let result = runtime.block_on(server);
+    runtime
+        .shutdown_on_idle()
+        .wait()
+        .expect("runtime shutdown can't fail");
+    Ok(result?)
+}
@@ -6,24 +6,97 @@
 //!   server and will decode incoming requests for you and invoke a handler
 //!   closure. They make it very easy to use any type implementing
 //!   [`FromRequest`] as the main entry point of your app.
+//! * [`LocalAsyncService`] works like [`AsyncService`], but supports handlers
+//!   that aren't `Send`, for servers running on a single-threaded executor.
 //! * [`ServiceExt`] provides adapter methods on Hyper `Service`s that simplify
-//!   common patterns like catching panics.
+//!   common patterns like catching panics or persisting sessions.
+//! * [`Middleware`] allows writing custom adapters with a single method, which
+//!   can also be applied to a subset of routes only.
+//! * [`Router`] dispatches requests to services mounted under path prefixes,
+//!   for apps whose set of routes is only known at runtime.
+//! * [`ResponseDecorations`] lets guards add headers to the response sent by
+//!   [`AsyncService`] and [`SyncService`].
+//! * [`RequestScope`] lets guards store state belonging to a single request.
+//! * [`GracefulShutdown`] shuts down a server without interrupting the
+//!   requests it is processing.
+//! * [`ConnectInfo`] carries the address of the client, and is inserted into
+//!   every request by [`ServiceExt::with_connect_info`].
 //!
 //! [`AsyncService`]: struct.AsyncService.html
 //! [`SyncService`]: struct.SyncService.html
+//! [`LocalAsyncService`]: struct.LocalAsyncService.html
 //! [`ServiceExt`]: trait.ServiceExt.html
+//! [`Middleware`]: trait.Middleware.html
+//! [`Router`]: struct.Router.html
+//! [`ResponseDecorations`]: struct.ResponseDecorations.html
+//! [`RequestScope`]: struct.RequestScope.html
+//! [`GracefulShutdown`]: struct.GracefulShutdown.html
+//! [`ConnectInfo`]: struct.ConnectInfo.html
+//! [`ServiceExt::with_connect_info`]: trait.ServiceExt.html#tymethod.with_connect_info
 //! [`FromRequest`]: ../trait.FromRequest.html
+//!
+//! # `Expect: 100-continue`
+//!
+//! Clients can send `Expect: 100-continue` to ask the server whether it will
+//! accept a request before uploading a large body. None of the adapters in
+//! this module read the body on their own: [`AsyncService`] and
+//! [`SyncService`] only hand it to the [`FromBody`] implementation of a route
+//! once its guards have succeeded, and [`ServiceExt::limit_body`] rejects
+//! bodies whose declared `Content-Length` is too large without reading them.
+//! If a request is rejected, the final response is sent immediately and the
+//! body is never read.
+//!
+//! Note that Hyper 0.12 sends the interim `100 Continue` response as soon as it
+//! has parsed the request head, before the service is called. A client
+//! therefore may start uploading the body of a request that is about to be
+//! rejected; it should stop once the final response arrives.
+//!
+//! [`FromBody`]: ../trait.FromBody.html
+//! [`ServiceExt::limit_body`]: trait.ServiceExt.html#tymethod.limit_body
 
-use crate::{BoxedError, DefaultFuture, Error, FromRequest, NoContext};
-use futures::{future::FutureResult, Future, IntoFuture};
+#[cfg(feature = "tracing")]
+use crate::guard::TraceContext;
+use crate::{
+    as_hyperdrive_error,
+    error::{register_response_error, render_response_error},
+    guard::{decode_hex, encode_hex, request_extension, AcceptEncoding, SessionSlot},
+    map_boxed_error,
+    response::{with_content_type, ErrorBody, IntoResponse},
+    BoxedError, DefaultFuture, Error, ErrorKind, FromRequest, LocalDefaultFuture, NoContext,
+    ProblemJson, ResponseError,
+};
+use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
+use futures::{
+    future::{Either, FutureResult},
+    sync::oneshot,
+    try_ready, Async, Future, IntoFuture, Poll, Stream,
+};
+use futures_cpupool::{Builder as CpuPoolBuilder, CpuFuture, CpuPool};
+use http::{header, header::HeaderName, uri::Authority, HeaderMap, HeaderValue, StatusCode};
 use hyper::{
+    body::Payload,
+    server::conn::AddrStream,
     service::{MakeService, Service},
-    Body, Method, Request, Response,
+    upgrade::OnUpgrade,
+    Body, Chunk, Method, Request, Response, Uri,
 };
-use std::any::Any;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::sync::Arc;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 /// Asynchronous hyper service adapter.
 ///
@@ -34,16 +107,24 @@ use std::sync::Arc;
 /// it handles:
 ///
 /// * Suppressing the body of the response when the request used `HEAD`.
-/// * Turning any [`hyperdrive::Error`] into a proper HTTP response.
+/// * Turning any [`hyperdrive::Error`] into a proper HTTP response (this can
+///   be customized with [`with_error_handler`] and [`not_found`]).
+/// * Applying the [`ResponseDecorations`] added by guards to the response.
 ///
 /// This type stores an async request handler `H` and the context needed by the
-/// [`FromRequest`] implementation. The context is cloned for every request.
+/// [`FromRequest`] implementation. The context is shared by all requests via
+/// an `Arc`, unless the service was created with [`with_context_fn`], which
+/// creates a new context for every request instead.
 ///
 /// # Type Parameters
 ///
 /// * **`H`**: The handler closure. Takes a [`FromRequest`] implementor `R`, and
-///   the original request. Returns a future resolving to the response to return
-///   to the client. Shared via `Arc`.
+///   the original request. If the service was created with
+///   [`with_context_handler`], it is also passed the context.
+///   Returns a future resolving to the response to return to the client.
+///   If the service was created with one of the `responding` constructors
+///   (eg. [`responding`]), the response can be any type implementing
+///   [`IntoResponse`]. Shared via `Arc`.
 /// * **`R`**: The request type expected by the handler `H`. Implements
 ///   [`FromRequest`].
 /// * **`F`**: The `Future` returned by the handler closure `H`.
@@ -80,24 +161,443 @@ use std::sync::Arc;
 ///
 /// [`FromRequest`]: ../trait.FromRequest.html
 /// [`hyperdrive::Error`]: ../struct.Error.html
-pub struct AsyncService<H, R, F>
+/// [`ResponseDecorations`]: struct.ResponseDecorations.html
+/// [`with_context_handler`]: #method.with_context_handler
+/// [`responding`]: #method.responding
+/// [`with_context_fn`]: #method.with_context_fn
+/// [`with_error_handler`]: #method.with_error_handler
+/// [`not_found`]: #method.not_found
+/// [`IntoResponse`]: ../response/trait.IntoResponse.html
+pub struct AsyncService<H, R, F, B = Body>
 where
-    H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
     R: FromRequest,
-    R::Context: Clone,
-    R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
 {
     handler: Arc<H>,
-    context: R::Context,
+    context: ContextSource<R::Context>,
+    prepare: Prepare<H, R, F>,
+    errors: ErrorHooks,
+    _body: PhantomData<fn() -> B>,
+}
+
+/// Prepares the invocation of a handler `H` for a single request.
+///
+/// This is what allows a service to call handlers with different signatures:
+/// Each constructor picks a function that clones whatever the handler needs
+/// (eg. the context) and returns a closure calling it with the decoded request.
+type Prepare<H, R, O> = fn(
+    &Arc<H>,
+    &Arc<<R as FromRequest>::Context>,
+) -> Box<dyn FnOnce(R, Arc<Request<()>>) -> O + Send>;
+
+/// A user-provided function that creates the context for a request.
+type ContextFactory<C> = dyn Fn(&Request<()>) -> DefaultFuture<C, BoxedError> + Send + Sync;
+
+/// Provides the context for the requests processed by [`AsyncService`] and
+/// [`SyncService`].
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+enum ContextSource<C> {
+    /// A context that is shared by all requests.
+    Shared(Arc<C>),
+    /// A function creating a new context for every request.
+    Factory(Arc<ContextFactory<C>>),
+}
+
+impl<C> ContextSource<C> {
+    fn shared(context: C) -> Self {
+        ContextSource::Shared(Arc::new(context))
+    }
+
+    fn factory<F, T>(factory: F) -> Self
+    where
+        F: Fn(&Request<()>) -> T + Send + Sync + 'static,
+        T: IntoFuture<Item = C, Error = BoxedError>,
+        T::Future: Send + 'static,
+    {
+        ContextSource::Factory(Arc::new(move |req| {
+            Box::new(factory(req).into_future().map_err(|err| {
+                BoxedError::from(Error::with_source(StatusCode::SERVICE_UNAVAILABLE, err))
+            }))
+        }))
+    }
+
+    /// Obtains the context for `req` and passes it to `f`.
+    fn with<F, T>(&self, req: &Request<()>, f: F) -> impl Future<Item = T::Item, Error = BoxedError>
+    where
+        F: FnOnce(Arc<C>) -> T,
+        T: IntoFuture<Error = BoxedError>,
+    {
+        match self {
+            ContextSource::Shared(context) => Either::A(f(context.clone()).into_future()),
+            ContextSource::Factory(factory) => Either::B(factory(req).map(Arc::new).and_then(f)),
+        }
+    }
+}
+
+impl<C> Clone for ContextSource<C> {
+    fn clone(&self) -> Self {
+        match self {
+            ContextSource::Shared(context) => ContextSource::Shared(context.clone()),
+            ContextSource::Factory(factory) => ContextSource::Factory(factory.clone()),
+        }
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for ContextSource<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextSource::Shared(context) => context.fmt(f),
+            ContextSource::Factory(_) => f.write_str("<closure>"),
+        }
+    }
+}
+
+/// A user-provided function that turns an error into a response.
+type ErrorHandler = dyn Fn(BoxedError) -> Response<Body> + Send + Sync;
+
+/// Renders registered `ResponseError` types found in an error.
+type ResponseErrorRenderer = fn(&BoxedError) -> Option<Response<Body>>;
+
+/// A user-provided function that creates the response for requests that don't
+/// match any route.
+type NotFoundHandler =
+    dyn Fn(Arc<Request<()>>) -> DefaultFuture<Response<Body>, BoxedError> + Send + Sync;
+
+/// Determines how [`AsyncService`] and [`SyncService`] respond to errors.
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+#[derive(Clone, Default)]
+struct ErrorHooks {
+    handler: Option<Arc<ErrorHandler>>,
+    body: Option<Arc<dyn ErrorRenderer>>,
+    not_found: Option<Arc<NotFoundHandler>>,
+    response_errors: Arc<Vec<ResponseErrorRenderer>>,
+    /// Returns the route paths to suggest similar routes from.
+    suggest_routes: Option<fn() -> Vec<&'static str>>,
+    auto_options: bool,
+    recover_panics: bool,
+    /// How the `ResponseDecorations` of a request resolve conflicting headers.
+    header_policy: HeaderPolicy,
+}
+
+impl ErrorHooks {
+    /// Creates the response to send when processing `req` failed with `err`.
+    ///
+    /// The hooks are tried in the order documented on
+    /// `AsyncService::with_error_handler`, so the `handler` only sees errors
+    /// that no more specific hook answers.
+    ///
+    /// Errors with kind `NoMatchingRoute` are passed to the `not_found` handler
+    /// if there is one. If `auto_options` is set, `WrongMethod` errors for
+    /// `OPTIONS` requests are answered with the allowed methods. Errors of a
+    /// registered `ResponseError` type are rendered by that type. If
+    /// `suggest_routes` is set, `NoMatchingRoute` errors are annotated with
+    /// similar routes, which are described in the body unless it is rendered
+    /// differently.
+    ///
+    /// Without a custom handler, only `hyperdrive::Error`s are turned into a
+    /// response (including ones wrapped by another error, such as those
+    /// produced while reading the body), whose body is rendered by the `body`
+    /// function if there is one. All other errors are returned as-is, which
+    /// drops the connection.
+    fn respond(
+        &self,
+        mut err: BoxedError,
+        req: Arc<Request<()>>,
+    ) -> DefaultFuture<Response<Body>, BoxedError> {
+        if let (Some(route_paths), Some(our_error)) =
+            (self.suggest_routes, err.downcast_mut::<Error>())
+        {
+            if our_error.kind() == ErrorKind::NoMatchingRoute && self.not_found.is_none() {
+                our_error.suggest_routes(&route_paths());
+            }
+        }
+
+        if let Some(our_error) = as_hyperdrive_error(&err) {
+            match (our_error.kind(), &self.not_found) {
+                (ErrorKind::NoMatchingRoute, Some(not_found)) => return not_found(req),
+                (ErrorKind::WrongMethod, _)
+                    if self.auto_options && req.method() == Method::OPTIONS =>
+                {
+                    let response = Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .header(header::ALLOW, our_error.allow_header_value())
+                        .body(Body::empty())
+                        .expect("could not build OPTIONS response");
+                    return Box::new(Ok(response).into_future());
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(response) = self.response_errors.iter().find_map(|render| render(&err)) {
+            return Box::new(Ok(response).into_future());
+        }
+
+        if let Some(handler) = &self.handler {
+            return Box::new(Ok(handler(err)).into_future());
+        }
+
+        let response = match &self.body {
+            Some(renderer) => match as_hyperdrive_error(&err) {
+                Some(our_error) => {
+                    Ok(our_error.response_with(|error| renderer.render(error, &req)))
+                }
+                None => Err(err),
+            },
+            None => match as_hyperdrive_error(&err) {
+                // Describe the suggested routes in the body
+                Some(our_error) if !our_error.route_suggestions().is_empty() => {
+                    Ok(our_error.response_with(|error| error.to_string()))
+                }
+                _ => map_boxed_error(err),
+            },
+        };
+        Box::new(response.into_future())
+    }
+}
+
+/// Renders the body of the responses created for [`hyperdrive::Error`]s.
+///
+/// An `ErrorRenderer` can be passed to the `error_body` method of
+/// [`AsyncService`], [`SyncService`] and [`LocalAsyncService`]. It is
+/// implemented for all functions taking a `&hyperdrive::Error` and returning a
+/// type implementing [`IntoResponse`], and by [`Negotiated`], which also
+/// inspects the request.
+///
+/// [`hyperdrive::Error`]: ../struct.Error.html
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`LocalAsyncService`]: struct.LocalAsyncService.html
+/// [`IntoResponse`]: ../response/trait.IntoResponse.html
+/// [`Negotiated`]: struct.Negotiated.html
+pub trait ErrorRenderer: Send + Sync + 'static {
+    /// Renders the response for `error`, which occurred while processing
+    /// `request`.
+    ///
+    /// The status and the headers of `error` are applied to the returned
+    /// response afterwards, as described in [`Error::response_with`].
+    ///
+    /// [`Error::response_with`]: ../struct.Error.html#method.response_with
+    fn render(&self, error: &Error, request: &Request<()>) -> Response<Body>;
+}
+
+impl<F, T> ErrorRenderer for F
+where
+    F: Fn(&Error) -> T + Send + Sync + 'static,
+    T: IntoResponse,
+{
+    fn render(&self, error: &Error, _request: &Request<()>) -> Response<Body> {
+        self(error).into_response()
+    }
+}
+
+/// A renderer for a single representation of an error.
+type RenderFn = dyn Fn(&Error) -> Response<Body> + Send + Sync;
+
+/// An [`ErrorRenderer`] that picks the format of the response based on the
+/// `Accept` header of the request.
+///
+/// The error is rendered as HTML, JSON or plain text, whichever the client
+/// prefers. If the request has no `Accept` header, accepts any of them equally
+/// (eg. `*/*`), or doesn't accept any of them, plain text is sent. Since the
+/// response depends on the `Accept` header, `Vary: Accept` is added to it.
+///
+/// By default, the HTML representation is a minimal page showing the status
+/// code, the JSON representation is a [`ProblemJson`] document, and the plain
+/// text representation is the status code and its reason phrase (eg. `404 Not
+/// Found`). Each of them can be replaced using [`html`], [`json`] and [`text`].
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, service::{Negotiated, SyncService}};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let service = SyncService::responding(|route: Route, _| match route {
+///     Route::Index => "Hello World!",
+/// })
+/// .error_body(Negotiated::new().text(|error| format!("Error: {}", error.http_status())));
+/// ```
+///
+/// [`ErrorRenderer`]: trait.ErrorRenderer.html
+/// [`ProblemJson`]: ../struct.ProblemJson.html
+/// [`html`]: #method.html
+/// [`json`]: #method.json
+/// [`text`]: #method.text
+pub struct Negotiated {
+    html: Box<RenderFn>,
+    json: Box<RenderFn>,
+    text: Box<RenderFn>,
+}
+
+impl Negotiated {
+    /// Creates a `Negotiated` renderer using the default representations.
+    pub fn new() -> Self {
+        Self {
+            html: Box::new(|error| {
+                let page = format!(
+                    "<!DOCTYPE html>\n<html>\n<head><title>{0}</title></head>\n<body><h1>{0}</h1></body>\n</html>\n",
+                    error.http_status(),
+                );
+                with_content_type(Body::from(page), "text/html; charset=utf-8")
+            }),
+            json: Box::new(|error| ProblemJson::from_error(error).into_response()),
+            text: Box::new(|error| error.http_status().to_string().into_response()),
+        }
+    }
+
+    /// Sets the function rendering the HTML representation, sent to clients
+    /// preferring `text/html`.
+    pub fn html<E, T>(mut self, render: E) -> Self
+    where
+        E: Fn(&Error) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.html = Box::new(move |error| render(error).into_response());
+        self
+    }
+
+    /// Sets the function rendering the JSON representation, sent to clients
+    /// preferring `application/json` or `application/problem+json`.
+    pub fn json<E, T>(mut self, render: E) -> Self
+    where
+        E: Fn(&Error) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.json = Box::new(move |error| render(error).into_response());
+        self
+    }
+
+    /// Sets the function rendering the plain text representation, sent to
+    /// clients preferring `text/plain` and those that don't express a
+    /// preference.
+    pub fn text<E, T>(mut self, render: E) -> Self
+    where
+        E: Fn(&Error) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.text = Box::new(move |error| render(error).into_response());
+        self
+    }
+}
+
+impl Default for Negotiated {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Negotiated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Negotiated")
+            .field("html", &"<closure>")
+            .field("json", &"<closure>")
+            .field("text", &"<closure>")
+            .finish()
+    }
+}
+
+impl ErrorRenderer for Negotiated {
+    fn render(&self, error: &Error, request: &Request<()>) -> Response<Body> {
+        let headers = request.headers();
+        // On ties, the earlier representation wins
+        let candidates: [(&RenderFn, f32); 3] = [
+            (&*self.text, media_quality(headers, &["text/plain"])),
+            (
+                &*self.json,
+                media_quality(headers, &["application/json", "application/problem+json"]),
+            ),
+            (&*self.html, media_quality(headers, &["text/html"])),
+        ];
+        let mut best = (&*self.text, 0.0);
+        for &(render, quality) in &candidates {
+            if quality > best.1 {
+                best = (render, quality);
+            }
+        }
+
+        let mut response = (best.0)(error);
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("Accept"));
+        response
+    }
 }
 
-impl<H, R, F> AsyncService<H, R, F>
+/// Returns the quality value the `Accept` header assigns to the media type
+/// known by any of `names`.
+///
+/// The most specific matching media range determines the quality. A missing
+/// `Accept` header accepts every media type.
+fn media_quality(headers: &HeaderMap, names: &[&str]) -> f32 {
+    if !headers.contains_key(header::ACCEPT) {
+        return 1.0;
+    }
+
+    // (specificity, quality) of the best match so far
+    let mut best: Option<(u8, f32)> = None;
+
+    let items = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for item in items {
+        let mut params = item.split(';');
+        let range = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| {
+                let param = param.trim();
+                if param.starts_with("q=") || param.starts_with("Q=") {
+                    param[2..].trim().parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+
+        let specificity = if range == "*/*" {
+            0
+        } else if range.ends_with("/*") {
+            let prefix = &range[..range.len() - 1];
+            if names.iter().any(|name| {
+                name.len() >= prefix.len() && name[..prefix.len()].eq_ignore_ascii_case(prefix)
+            }) {
+                1
+            } else {
+                continue;
+            }
+        } else if names.iter().any(|name| range.eq_ignore_ascii_case(name)) {
+            2
+        } else {
+            continue;
+        };
+
+        match best {
+            Some((best_specificity, _)) if best_specificity > specificity => {}
+            _ => best = Some((specificity, q)),
+        }
+    }
+
+    best.map(|(_, q)| q).unwrap_or(0.0)
+}
+
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
     H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
     R: FromRequest<Context = NoContext>,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + Send + 'static,
+    B: ErrorBody,
 {
     /// Creates an `AsyncService` from a handler closure.
     ///
@@ -110,17 +610,17 @@ where
     /// [`FromRequest`]: ../trait.FromRequest.html
     /// [`with_context`]: #method.with_context
     pub fn new(handler: H) -> Self {
-        Self::with_context(handler, NoContext)
+        Self::responding(handler)
     }
 }
 
-impl<H, R, F> AsyncService<H, R, F>
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
     H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
     R: FromRequest,
-    R::Context: Clone,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + Send + 'static,
+    B: ErrorBody,
 {
     /// Creates an `AsyncService` that will call `handler` to process incoming
     /// requests.
@@ -131,112 +631,823 @@ where
     ///   passed every decoded request `R`. Returns a future `F` resolving to
     ///   the response to return.
     /// * **`context`**: The context to pass to your [`FromRequest`]
-    ///   implementor.
+    ///   implementor. If the handler needs access to the context as well, use
+    ///   [`with_context_handler`] instead.
     ///
     /// [`FromRequest`]: ../trait.FromRequest.html
+    /// [`with_context_handler`]: #method.with_context_handler
+    /// [`responding`]: #method.responding
     pub fn with_context(handler: H, context: R::Context) -> Self {
-        Self {
-            handler: Arc::new(handler),
-            context,
-        }
+        Self::responding_with_context(handler, context)
     }
 }
 
-impl<H, R, F> Clone for AsyncService<H, R, F>
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
     H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
     R: FromRequest,
-    R::Context: Clone,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + Send + 'static,
+    B: ErrorBody,
 {
-    fn clone(&self) -> Self {
-        Self {
-            handler: self.handler.clone(),
-            context: self.context.clone(),
-        }
+    /// Creates an `AsyncService` that calls `factory` to create a new context
+    /// for every request.
+    ///
+    /// This is useful when the context holds a resource that should not be
+    /// shared between requests, like a database connection checked out from a
+    /// pool. The context doesn't have to implement `Clone`.
+    ///
+    /// `factory` is called with the request before it is decoded, and returns
+    /// the context or a future resolving to it. If it fails, the request is
+    /// rejected with a `503 Service Unavailable` error, which is turned into a
+    /// response like any other error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{BoxedError, FromRequest, RequestContext, service::AsyncService};
+    /// use hyper::{Body, Request, Response};
+    /// use futures::IntoFuture;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// #[derive(RequestContext)]
+    /// struct Context {
+    ///     request_id: u64,
+    /// }
+    ///
+    /// #[derive(FromRequest)]
+    /// #[context(Context)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let next_id = Arc::new(AtomicU64::new(0));
+    /// let service = AsyncService::with_context_fn(
+    ///     |route: Route, _| match route {
+    ///         Route::Index => Ok(Response::new(Body::from("Hello World!"))).into_future(),
+    ///     },
+    ///     move |_: &Request<()>| -> Result<_, BoxedError> {
+    ///         Ok(Context {
+    ///             request_id: next_id.fetch_add(1, Ordering::SeqCst),
+    ///         })
+    ///     },
+    /// );
+    /// ```
+    pub fn with_context_fn<C, T>(handler: H, factory: C) -> Self
+    where
+        C: Fn(&Request<()>) -> T + Send + Sync + 'static,
+        T: IntoFuture<Item = R::Context, Error = BoxedError>,
+        T::Future: Send + 'static,
+    {
+        Self::responding_with_context_fn(handler, factory)
     }
 }
 
-impl<C, H, R, F> MakeService<C> for AsyncService<H, R, F>
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
+    H: Fn(R, Arc<Request<()>>, Arc<R::Context>) -> F + Send + Sync + 'static,
     R: FromRequest,
-    R::Context: Clone,
+    R::Context: Send + Sync + 'static,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + Send + 'static,
+    B: ErrorBody,
 {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = BoxedError;
-    type Service = Self;
-    type Future = FutureResult<Self, BoxedError>;
-    type MakeError = BoxedError;
-
-    fn make_service(&mut self, _ctx: C) -> Self::Future {
-        Ok(self.clone()).into_future()
+    /// Creates an `AsyncService` whose handler is also passed the context.
+    ///
+    /// `context` is stored in an `Arc` that is shared by the request decoding
+    /// and the handler, so it is never cloned. This makes the context the
+    /// single place to store shared application state, like a database
+    /// connection pool, that is needed by both guards and handlers.
+    ///
+    /// # Parameters
+    ///
+    /// * **`handler`**: The handler closure. This is stored in an `Arc` and is
+    ///   passed every decoded request `R`, the original request, and the
+    ///   context. Returns a future `F` resolving to the response to return.
+    /// * **`context`**: The context to pass to your [`FromRequest`]
+    ///   implementor and the handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, RequestContext, service::AsyncService};
+    /// use hyper::{Request, Response, Body};
+    /// use futures::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// #[derive(RequestContext)]
+    /// struct Context {
+    ///     greeting: String,
+    /// }
+    ///
+    /// #[derive(FromRequest)]
+    /// #[context(Context)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let context = Context {
+    ///     greeting: "Hello World!".to_string(),
+    /// };
+    /// let service = AsyncService::with_context_handler(
+    ///     |route: Route, _: Arc<Request<()>>, context: Arc<Context>| match route {
+    ///         Route::Index => Ok(Response::new(Body::from(context.greeting.to_string()))).into_future(),
+    ///     },
+    ///     context,
+    /// );
+    /// ```
+    ///
+    /// [`FromRequest`]: ../trait.FromRequest.html
+    pub fn with_context_handler(handler: H, context: R::Context) -> Self {
+        Self::responding_with_context_handler(handler, context)
     }
 }
 
-impl<H, R, F> Service for AsyncService<H, R, F>
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
     H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
-    R: FromRequest,
-    R::Context: Clone,
+    R: FromRequest<Context = NoContext>,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
 {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = BoxedError;
-    type Future = DefaultFuture<Response<Body>, BoxedError>;
-
-    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
-        let is_head = req.method() == Method::HEAD;
-        let handler = self.handler.clone();
-        let (parts, body) = req.into_parts();
-        let req = Arc::new(Request::from_parts(parts, ()));
-        let fut = R::from_request_and_body(&req, body, self.context.clone())
-            .and_then(move |r| handler(r, req))
-            .map(move |response| {
-                if is_head {
-                    // Responses to HEAD requests must have an empty body
-                    response.map(|_| Body::empty())
-                } else {
-                    response
-                }
-            })
-            .or_else(|err| {
-                if let Some(our_error) = err.downcast_ref::<Error>() {
-                    Ok(our_error.response().map(|()| Body::empty()))
-                } else {
-                    Err(err)
-                }
-            });
-
-        Box::new(fut)
+    /// Creates an `AsyncService` whose handler can resolve to any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// This works like [`new`], which requires the handler to resolve to a
+    /// `Response`. That keeps the return type of handlers that never return
+    /// (eg. because they always panic) inferable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::responding(|route: Route, _| match route {
+    ///     Route::Index => Ok("Hello world!").into_future(),
+    /// });
+    /// ```
+    ///
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    /// [`new`]: #method.new
+    pub fn responding(handler: H) -> Self {
+        Self::responding_with_context(handler, NoContext)
     }
 }
 
-impl<H, R, F> fmt::Debug for AsyncService<H, R, F>
+impl<H, R, F, B> AsyncService<H, R, F, B>
 where
     H: Fn(R, Arc<Request<()>>) -> F + Send + Sync + 'static,
     R: FromRequest,
-    R::Context: Clone + fmt::Debug,
     R::Future: 'static,
-    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Closures aren't debug-printable, so we print a few Arc stats instead
+    /// Like [`with_context`], but the handler can resolve to any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// [`with_context`]: #method.with_context
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context(handler: H, context: R::Context) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::shared(context),
+            prepare: |handler, _context| {
+                let handler = handler.clone();
+                Box::new(move |route, request| handler(route, request))
+            },
+            errors: ErrorHooks::default(),
+            _body: PhantomData,
+        }
+    }
 
-        #[derive(Debug)]
-        struct HandlerRef {
-            strong_count: usize,
-            weak_count: usize,
+    /// Like [`with_context_fn`], but the handler can resolve to any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// [`with_context_fn`]: #method.with_context_fn
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context_fn<C, T>(handler: H, factory: C) -> Self
+    where
+        C: Fn(&Request<()>) -> T + Send + Sync + 'static,
+        T: IntoFuture<Item = R::Context, Error = BoxedError>,
+        T::Future: Send + 'static,
+    {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::factory(factory),
+            prepare: |handler, _context| {
+                let handler = handler.clone();
+                Box::new(move |route, request| handler(route, request))
+            },
+            errors: ErrorHooks::default(),
+            _body: PhantomData,
         }
+    }
+}
 
-        f.debug_struct("AsyncService")
-            .field(
+impl<H, R, F, B> AsyncService<H, R, F, B>
+where
+    H: Fn(R, Arc<Request<()>>, Arc<R::Context>) -> F + Send + Sync + 'static,
+    R: FromRequest,
+    R::Context: Send + Sync + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    /// Like [`with_context_handler`], but the handler can resolve to any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// [`with_context_handler`]: #method.with_context_handler
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context_handler(handler: H, context: R::Context) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::shared(context),
+            prepare: |handler, context| {
+                let handler = handler.clone();
+                let context = context.clone();
+                Box::new(move |route, request| handler(route, request, context))
+            },
+            errors: ErrorHooks::default(),
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<H, R, F, B> AsyncService<H, R, F, B>
+where
+    R: FromRequest,
+{
+    /// Sets a function that creates the response for any error that occurs
+    /// while processing a request.
+    ///
+    /// By default, [`hyperdrive::Error`]s (eg. caused by a request that
+    /// doesn't match any route, or a failing guard) are turned into a response
+    /// with an empty body, while all other errors (eg. returned by the handler)
+    /// cause the connection to be dropped without sending a response.
+    ///
+    /// `handler` is called with these errors instead, so it can render custom
+    /// error pages. Use `downcast_ref` to check whether the error is a
+    /// [`hyperdrive::Error`] and obtain its status code.
+    ///
+    /// # Precedence
+    ///
+    /// Some errors are answered by more specific hooks and never reach
+    /// `handler`. An error is answered by the first of these that applies:
+    ///
+    /// 1. The function passed to [`not_found`], for errors of kind
+    ///    [`ErrorKind::NoMatchingRoute`].
+    /// 2. An automatic `204 No Content` response, for `OPTIONS` requests failing
+    ///    with [`ErrorKind::WrongMethod`] if [`auto_options`] is enabled.
+    /// 3. [`ResponseError::response`], for errors of (or caused by) a type
+    ///    registered with [`response_error`].
+    /// 4. `handler`.
+    /// 5. The renderer passed to [`error_body`], for [`hyperdrive::Error`]s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use hyper::{Body, Response, StatusCode};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .with_error_handler(|error| {
+    ///     let status = match error.downcast_ref::<hyperdrive::Error>() {
+    ///         Some(error) => error.http_status(),
+    ///         None => StatusCode::INTERNAL_SERVER_ERROR,
+    ///     };
+    ///
+    ///     Response::builder()
+    ///         .status(status)
+    ///         .header("Content-Type", "application/json")
+    ///         .body(Body::from(format!(r#"{{"status":{}}}"#, status.as_u16())))
+    ///         .unwrap()
+    /// });
+    /// ```
+    ///
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`not_found`]: #method.not_found
+    /// [`ErrorKind::NoMatchingRoute`]: ../enum.ErrorKind.html#variant.NoMatchingRoute
+    /// [`auto_options`]: #method.auto_options
+    /// [`ErrorKind::WrongMethod`]: ../enum.ErrorKind.html#variant.WrongMethod
+    /// [`ResponseError::response`]: ../trait.ResponseError.html#method.response
+    /// [`response_error`]: #method.response_error
+    /// [`error_body`]: #method.error_body
+    pub fn with_error_handler<E>(mut self, handler: E) -> Self
+    where
+        E: Fn(BoxedError) -> Response<Body> + Send + Sync + 'static,
+    {
+        self.errors.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a function that renders the body of the responses created for
+    /// [`hyperdrive::Error`]s.
+    ///
+    /// By default, these responses (eg. `404 Not Found` when no route matches,
+    /// or `405 Method Not Allowed`) have an empty body. `renderer` is usually
+    /// a function that is called with the error and can return any type
+    /// implementing [`IntoResponse`]. The status and headers of the error
+    /// response are kept, as described in [`Error::response_with`], so
+    /// `renderer` only provides the body and headers like `Content-Type`. It
+    /// has access to the [`ErrorKind`], the status code and the [allowed
+    /// methods] of the error.
+    ///
+    /// Any other [`ErrorRenderer`] can be passed as well, such as
+    /// [`Negotiated`], which picks the format of the body based on the `Accept`
+    /// header of the request.
+    ///
+    /// Functions passed to [`with_error_handler`] and [`not_found`] take
+    /// precedence over `renderer`.
+    ///
+    /// # Examples
+    ///
+    /// Describe errors in a JSON body:
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, Error, ErrorKind, response::Json, service::AsyncService};
+    /// use hyper::{Body, Response};
+    /// use futures::IntoFuture;
+    /// use serde_json::json;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .error_body(|error: &Error| {
+    ///     let methods = error
+    ///         .allowed_methods()
+    ///         .unwrap_or(&[])
+    ///         .iter()
+    ///         .map(|method| method.as_str())
+    ///         .collect::<Vec<_>>();
+    ///     match error.kind() {
+    ///         ErrorKind::NoMatchingRoute => Json(json!({ "error": "not_found" })),
+    ///         ErrorKind::WrongMethod => Json(json!({ "error": "method_not_allowed", "allowed": methods })),
+    ///         _ => Json(json!({ "error": error.http_status().as_u16() })),
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    /// [`Error::response_with`]: ../struct.Error.html#method.response_with
+    /// [`ErrorKind`]: ../enum.ErrorKind.html
+    /// [allowed methods]: ../struct.Error.html#method.allowed_methods
+    /// [`ErrorRenderer`]: trait.ErrorRenderer.html
+    /// [`Negotiated`]: struct.Negotiated.html
+    /// [`with_error_handler`]: #method.with_error_handler
+    /// [`not_found`]: #method.not_found
+    pub fn error_body<E>(mut self, renderer: E) -> Self
+    where
+        E: ErrorRenderer,
+    {
+        self.errors.body = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Responds to errors of type `E` using [`ResponseError::response`].
+    ///
+    /// Guards and [`FromBody`] implementations can fail with errors of any
+    /// type, which the service can't respond to unless it knows about them.
+    /// After registering `E`, errors of that type (and errors caused by one)
+    /// are turned into the response returned by `E`. Multiple types can be
+    /// registered by calling this method several times.
+    ///
+    /// Registered errors are never passed to the function set with
+    /// [`with_error_handler`]. Once `E` is registered, [`status_of`] (and the
+    /// functions based on it) return the status of errors of that type as
+    /// well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, Guard, NoContext, BoxedError, ResponseError};
+    /// use hyperdrive::service::AsyncService;
+    /// use hyper::{Body, Response, StatusCode};
+    /// use futures::IntoFuture;
+    /// use std::{fmt, sync::Arc};
+    ///
+    /// #[derive(Debug)]
+    /// struct QuotaExceeded;
+    ///
+    /// impl fmt::Display for QuotaExceeded {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         f.write_str("request quota exceeded")
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for QuotaExceeded {}
+    ///
+    /// impl ResponseError for QuotaExceeded {
+    ///     fn status(&self) -> StatusCode {
+    ///         StatusCode::PAYMENT_REQUIRED
+    ///     }
+    /// }
+    ///
+    /// struct Quota;
+    ///
+    /// impl Guard for Quota {
+    ///     type Context = NoContext;
+    ///     type Result = Result<Self, BoxedError>;
+    ///
+    ///     fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+    ///         if request.headers().contains_key("X-Over-Quota") {
+    ///             Err(QuotaExceeded.into())
+    ///         } else {
+    ///             Ok(Quota)
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index { quota: Quota },
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .response_error::<QuotaExceeded>();
+    /// ```
+    ///
+    /// [`ResponseError::response`]: ../trait.ResponseError.html#method.response
+    /// [`FromBody`]: ../trait.FromBody.html
+    /// [`with_error_handler`]: #method.with_error_handler
+    /// [`status_of`]: ../fn.status_of.html
+    pub fn response_error<E>(mut self) -> Self
+    where
+        E: ResponseError,
+    {
+        register_response_error::<E>();
+        Arc::make_mut(&mut self.errors.response_errors).push(render_response_error::<E>);
+        self
+    }
+
+    /// Sets a function that creates the response for requests that don't
+    /// match any route.
+    ///
+    /// `handler` is called with the original request whenever decoding it
+    /// fails with an [`Error`] of kind [`ErrorKind::NoMatchingRoute`], and
+    /// returns a future resolving to the response. It takes precedence over
+    /// the function passed to [`with_error_handler`]. All other errors,
+    /// including `405 Method Not Allowed` errors, are not passed to `handler`.
+    ///
+    /// Note that the response is sent as-is, so `handler` should usually set
+    /// the status to `404 Not Found`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use hyper::{Body, Response, StatusCode};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .not_found(|request| {
+    ///     let body = format!("there's nothing at {}", request.uri().path());
+    ///     Ok((StatusCode::NOT_FOUND, body)).into_future()
+    /// });
+    /// ```
+    ///
+    /// [`Error`]: ../struct.Error.html
+    /// [`ErrorKind::NoMatchingRoute`]: ../enum.ErrorKind.html#variant.NoMatchingRoute
+    /// [`with_error_handler`]: #method.with_error_handler
+    /// [`not_found`]: #method.not_found
+    pub fn not_found<N, T>(mut self, handler: N) -> Self
+    where
+        N: Fn(Arc<Request<()>>) -> T + Send + Sync + 'static,
+        T: IntoFuture<Error = BoxedError>,
+        T::Future: Send + 'static,
+        T::Item: IntoResponse + 'static,
+    {
+        self.errors.not_found = Some(Arc::new(move |req| {
+            Box::new(handler(req).into_future().map(IntoResponse::into_response))
+        }));
+        self
+    }
+
+    /// Sets whether `OPTIONS` requests are answered automatically.
+    ///
+    /// When enabled, an `OPTIONS` request to a path that has routes, but none
+    /// for `OPTIONS`, is answered with an empty `204 No Content` response
+    /// listing the methods allowed for the path in an `Allow` header.
+    ///
+    /// Routes explicitly declared with `#[options]` are unaffected and still
+    /// handle the request. Adapters wrapping this service, such as one
+    /// answering CORS preflight requests, see the request first and thus take
+    /// precedence. The automatic response takes precedence over the function
+    /// passed to [`with_error_handler`]. This is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use hyper::{Body, Response};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/users/{id}")]
+    ///     User { id: u32 },
+    ///
+    ///     #[patch("/users/{id}")]
+    ///     UpdateUser { id: u32 },
+    /// }
+    ///
+    /// // `OPTIONS /users/5` is answered with `Allow: GET, HEAD, PATCH`
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::empty())).into_future()
+    /// })
+    /// .auto_options(true);
+    /// ```
+    ///
+    /// [`with_error_handler`]: #method.with_error_handler
+    pub fn auto_options(mut self, enabled: bool) -> Self {
+        self.errors.auto_options = enabled;
+        self
+    }
+
+    /// Sets whether similar routes are suggested when no route matches.
+    ///
+    /// When enabled, the `404 Not Found` response to a request that doesn't
+    /// match any route names up to three routes with similar paths, comparing
+    /// the path segments of the request with the path templates returned by
+    /// [`FromRequest::route_paths`]. The suggestions are included in the
+    /// [`Display`] output of the error (and thus in the response body, unless
+    /// it is rendered differently) and can be accessed using
+    /// [`Error::route_suggestions`].
+    ///
+    /// This is meant as a debugging aid during development. Since it reveals
+    /// the routes of the application to clients, it is disabled by default.
+    /// A function set with [`not_found`] takes precedence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use hyper::{Body, Response};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/users/{id}")]
+    ///     User { id: u32 },
+    /// }
+    ///
+    /// // `GET /user/5` is answered with a body suggesting `/users/{id}`
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::empty())).into_future()
+    /// })
+    /// .suggest_routes(cfg!(debug_assertions));
+    /// ```
+    ///
+    /// [`FromRequest::route_paths`]: ../trait.FromRequest.html#method.route_paths
+    /// [`Display`]: ../struct.Error.html#impl-Display
+    /// [`Error::route_suggestions`]: ../struct.Error.html#method.route_suggestions
+    /// [`not_found`]: #method.not_found
+    pub fn suggest_routes(mut self, enabled: bool) -> Self {
+        self.errors.suggest_routes = if enabled { Some(R::route_paths) } else { None };
+        self
+    }
+
+    /// Turns panics into `500 Internal Server Error` responses.
+    ///
+    /// Panics in the handler, as well as in guards and `FromBody`
+    /// implementations, are answered with a `text/plain` body reading
+    /// "internal server error" instead of dropping the connection. If the panic
+    /// payload is a string, it is logged via the `log` crate.
+    ///
+    /// This is a shortcut for the most common use of
+    /// [`ServiceExt::catch_unwind`], which can be used to send a custom
+    /// response instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::AsyncService};
+    /// use hyper::{Body, Response};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .recover_panics();
+    /// ```
+    ///
+    /// [`ServiceExt::catch_unwind`]: trait.ServiceExt.html#tymethod.catch_unwind
+    pub fn recover_panics(mut self) -> Self {
+        self.errors.recover_panics = true;
+        self
+    }
+
+    /// Sets which value is kept when [`ResponseDecorations`] set a header that
+    /// already has a different value.
+    ///
+    /// The default is `HeaderPolicy::LastWins`, which lets guards override
+    /// the headers of the handler. List-valued headers like `Set-Cookie` are
+    /// always appended. See [`HeaderPolicy`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{AsyncService, HeaderPolicy}};
+    /// use hyper::{Body, Response};
+    /// use futures::IntoFuture;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// // Guards can't override the `Content-Type` chosen by the handler
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .header_policy(HeaderPolicy::FirstWins);
+    /// ```
+    ///
+    /// [`ResponseDecorations`]: struct.ResponseDecorations.html
+    /// [`HeaderPolicy`]: enum.HeaderPolicy.html
+    pub fn header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.errors.header_policy = policy;
+        self
+    }
+}
+
+impl<H, R, F, B> Clone for AsyncService<H, R, F, B>
+where
+    R: FromRequest,
+{
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            context: self.context.clone(),
+            prepare: self.prepare,
+            errors: self.errors.clone(),
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<C, H, R, F, B> MakeService<C> for AsyncService<H, R, F, B>
+where
+    H: Send + Sync + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    type ReqBody = Body;
+    type ResBody = B;
+    type Error = BoxedError;
+    type Service = Self;
+    type Future = FutureResult<Self, BoxedError>;
+    type MakeError = BoxedError;
+
+    fn make_service(&mut self, _ctx: C) -> Self::Future {
+        Ok(self.clone()).into_future()
+    }
+}
+
+impl<H, R, F, B> Service for AsyncService<H, R, F, B>
+where
+    H: Send + Sync + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    type ReqBody = Body;
+    type ResBody = B;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<B>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        if self.errors.recover_panics {
+            let future =
+                CatchUnwindFuture::catch(|| self.dispatch(req), panic_handler::RecoverPanic);
+            Box::new(future)
+        } else {
+            self.dispatch(req)
+        }
+    }
+}
+
+impl<H, R, F, B> AsyncService<H, R, F, B>
+where
+    H: Send + Sync + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + Send + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    /// Decodes `req` and passes it to the handler.
+    fn dispatch(&self, mut req: Request<Body>) -> DefaultFuture<Response<B>, BoxedError> {
+        let is_head = req.method() == Method::HEAD;
+        let handler = self.handler.clone();
+        let prepare = self.prepare;
+        let errors = self.errors.clone();
+        let decorations = ResponseDecorations::with_policy(errors.header_policy);
+        req.extensions_mut().insert(decorations.clone());
+        req.extensions_mut().insert(RequestScope::default());
+        PendingUpgrade::extract(&mut req);
+        let (parts, body) = req.into_parts();
+        let req = Arc::new(Request::from_parts(parts, ()));
+        let orig = req.clone();
+        let fut = self
+            .context
+            .with(&orig, move |context| {
+                let handler = prepare(&handler, &context);
+                R::from_request_and_body(&req, body, context).and_then(move |route| {
+                    let name = route.route_name();
+                    handler(route, req)
+                        .map(move |response| with_route_name(response.into_response(), name))
+                })
+            })
+            .or_else(move |err| {
+                errors
+                    .respond(err, orig)
+                    .map(|response| response.map(B::from_body))
+            })
+            .map(move |response| {
+                if is_head {
+                    strip_body(response)
+                } else {
+                    response
+                }
+            })
+            .map(move |mut response| {
+                decorations.apply(&mut response);
+                response
+            });
+
+        Box::new(fut)
+    }
+}
+
+impl<H, R, F, B> fmt::Debug for AsyncService<H, R, F, B>
+where
+    R: FromRequest,
+    R::Context: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable, so we print a few Arc stats instead
+
+        #[derive(Debug)]
+        struct HandlerRef {
+            strong_count: usize,
+            weak_count: usize,
+        }
+
+        f.debug_struct("AsyncService")
+            .field(
                 "handler",
                 &HandlerRef {
                     strong_count: Arc::strong_count(&self.handler),
@@ -254,7 +1465,9 @@ where
 /// from your app. Specifically, it handles:
 ///
 /// * Suppressing the body of the response when the request used `HEAD`.
-/// * Turning any [`hyperdrive::Error`] into a proper HTTP response.
+/// * Turning any [`hyperdrive::Error`] into a proper HTTP response (this can
+///   be customized with [`with_error_handler`] and [`not_found`]).
+/// * Applying the [`ResponseDecorations`] added by guards to the response.
 ///
 /// This is effectively a bridge between async hyper and a synchronous,
 /// blocking app. Writing sync code is much simpler than writing async code
@@ -264,8 +1477,11 @@ where
 /// # Type Parameters
 ///
 /// * **`H`**: The handler closure. It is called with the request type `R` and
-///   the original request. It has to return the `Response<Body>` to send to the
-///   client.
+///   the original request. If the service was created with
+///   [`with_context_handler`], it is also passed the context. It
+///   has to return the `Response` to send to the client. If the service was
+///   created with one of the `responding` constructors (eg. [`responding`]),
+///   it can return any type implementing [`IntoResponse`].
 /// * **`R`**: The request type implementing `FromRequest`.
 ///
 /// # Examples
@@ -294,33 +1510,40 @@ where
 ///
 /// [`AsyncService`]: struct.AsyncService.html
 /// [`hyperdrive::Error`]: ../struct.Error.html
-pub struct SyncService<H, R>
+/// [`ResponseDecorations`]: struct.ResponseDecorations.html
+/// [`with_context_handler`]: #method.with_context_handler
+/// [`with_error_handler`]: #method.with_error_handler
+/// [`not_found`]: #method.not_found
+/// [`IntoResponse`]: ../response/trait.IntoResponse.html
+pub struct SyncService<H, R, B = Body>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
     R: FromRequest + Send + 'static,
-    R::Context: Clone,
 {
     handler: Arc<H>,
-    context: R::Context,
+    context: ContextSource<R::Context>,
+    prepare: Prepare<H, R, Response<B>>,
+    errors: ErrorHooks,
+    pool: Option<Arc<HandlerPool>>,
 }
 
-impl<H, R> SyncService<H, R>
+impl<H, R, B> SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
+    H: Fn(R, Arc<Request<()>>) -> Response<B> + Send + Sync + 'static,
+    B: ErrorBody,
     R: FromRequest<Context = NoContext> + Send + 'static,
 {
     /// Creates a `SyncService` that will call `handler` to process incoming
     /// requests.
     pub fn new(handler: H) -> Self {
-        Self::with_context(handler, NoContext)
+        Self::responding(handler)
     }
 }
 
-impl<H, R> SyncService<H, R>
+impl<H, R, B> SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
+    H: Fn(R, Arc<Request<()>>) -> Response<B> + Send + Sync + 'static,
+    B: ErrorBody,
     R: FromRequest + Send + 'static,
-    R::Context: Clone,
 {
     /// Creates a `SyncService` that will call `handler` to process incoming
     /// requests.
@@ -332,40 +1555,467 @@ where
     ///   to the client.
     /// * **`context`**: The context to pass to your [`FromRequest`]
     ///   implementor. If you don't need a special context, [`new()`] should be
-    ///   called instead.
+    ///   called instead. If the handler needs access to the context as well,
+    ///   use [`with_context_handler`].
     ///
     /// [`new()`]: #method.new
+    /// [`with_context_handler`]: #method.with_context_handler
     /// [`FromRequest`]: ../trait.FromRequest.html
     pub fn with_context(handler: H, context: R::Context) -> Self {
-        Self {
-            handler: Arc::new(handler),
-            context,
-        }
+        Self::responding_with_context(handler, context)
+    }
+
+    /// Creates a `SyncService` that runs `handler` on its own thread pool
+    /// instead of the blocking pool shared with the rest of the process.
+    ///
+    /// This works like [`with_context`], but handlers are run on a dedicated
+    /// pool of `config.threads` threads. At most `config.queue_limit` requests
+    /// wait for a free thread; further requests are rejected with a `503
+    /// Service Unavailable` error, which is turned into a response like any
+    /// other error.
+    ///
+    /// This is useful for CPU-heavy handlers, which would otherwise occupy
+    /// the threads of the shared pool.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `config.threads` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, NoContext, service::{PoolConfig, SyncService}};
+    /// use hyper::{Body, Response};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/report.pdf")]
+    ///     Report,
+    /// }
+    ///
+    /// let service = SyncService::with_thread_pool(
+    ///     |route: Route, _| match route {
+    ///         Route::Report => Response::new(Body::from("rendering PDFs is hard work")),
+    ///     },
+    ///     NoContext,
+    ///     PoolConfig {
+    ///         threads: 4,
+    ///         queue_limit: 16,
+    ///     },
+    /// );
+    /// ```
+    ///
+    /// [`with_context`]: #method.with_context
+    pub fn with_thread_pool(handler: H, context: R::Context, config: PoolConfig) -> Self {
+        Self::responding_with_thread_pool(handler, context, config)
     }
 }
 
-impl<H, R> Clone for SyncService<H, R>
+impl<H, R, B> SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
+    H: Fn(R, Arc<Request<()>>) -> Response<B> + Send + Sync + 'static,
+    B: ErrorBody,
     R: FromRequest + Send + 'static,
-    R::Context: Clone,
 {
-    fn clone(&self) -> Self {
-        Self {
-            handler: self.handler.clone(),
+    /// Creates a `SyncService` that calls `factory` to create a new context
+    /// for every request.
+    ///
+    /// This works like [`AsyncService::with_context_fn`]. Note that `factory`
+    /// is called on the futures executor, so it must not block. To obtain the
+    /// context via blocking code, return a future created by
+    /// [`hyperdrive::blocking`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{BoxedError, FromRequest, RequestContext, service::SyncService};
+    /// use hyper::{Body, Request, Response};
+    ///
+    /// # struct Connection;
+    /// # fn connect() -> Result<Connection, BoxedError> { Ok(Connection) }
+    /// #[derive(RequestContext)]
+    /// struct Context {
+    ///     db: Connection,
+    /// }
+    ///
+    /// #[derive(FromRequest)]
+    /// #[context(Context)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::with_context_fn(
+    ///     |route: Route, _| match route {
+    ///         Route::Index => Response::new(Body::from("Hello World!")),
+    ///     },
+    ///     |_: &Request<()>| hyperdrive::blocking(|| Ok(Context { db: connect()? })),
+    /// );
+    /// ```
+    ///
+    /// [`AsyncService::with_context_fn`]: struct.AsyncService.html#method.with_context_fn
+    /// [`hyperdrive::blocking`]: ../fn.blocking.html
+    pub fn with_context_fn<C, F>(handler: H, factory: C) -> Self
+    where
+        C: Fn(&Request<()>) -> F + Send + Sync + 'static,
+        F: IntoFuture<Item = R::Context, Error = BoxedError>,
+        F::Future: Send + 'static,
+    {
+        Self::responding_with_context_fn(handler, factory)
+    }
+}
+
+impl<H, R, B> SyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>, Arc<R::Context>) -> Response<B> + Send + Sync + 'static,
+    B: ErrorBody,
+    R: FromRequest + Send + 'static,
+    R::Context: Send + Sync + 'static,
+{
+    /// Creates a `SyncService` whose handler is also passed the context.
+    ///
+    /// `context` is stored in an `Arc` that is shared by the request decoding
+    /// and the handler, so it is never cloned.
+    ///
+    /// Since the handler is run on the blocking thread pool, the context has
+    /// to be `Send` and `Sync`.
+    ///
+    /// # Parameters
+    ///
+    /// * **`handler`**: The handler closure. This is stored in an `Arc` and is
+    ///   called with every decoded request `R`, the original request, and the
+    ///   context. Returns the response to return to the client.
+    /// * **`context`**: The context to pass to your [`FromRequest`]
+    ///   implementor and the handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, RequestContext, service::SyncService};
+    /// use hyper::{Request, Response, Body};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(RequestContext)]
+    /// struct Context {
+    ///     visitors: Mutex<u64>,
+    /// }
+    ///
+    /// #[derive(FromRequest)]
+    /// #[context(Context)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let context = Context {
+    ///     visitors: Mutex::new(0),
+    /// };
+    /// let service = SyncService::with_context_handler(
+    ///     |route: Route, _: Arc<Request<()>>, context: Arc<Context>| match route {
+    ///         Route::Index => {
+    ///             let mut visitors = context.visitors.lock().unwrap();
+    ///             *visitors += 1;
+    ///             Response::new(Body::from(format!("You are visitor #{}", visitors)))
+    ///         }
+    ///     },
+    ///     context,
+    /// );
+    /// ```
+    ///
+    /// [`FromRequest`]: ../trait.FromRequest.html
+    pub fn with_context_handler(handler: H, context: R::Context) -> Self {
+        Self::responding_with_context_handler(handler, context)
+    }
+}
+
+impl<H, R, T, B> SyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> T + Send + Sync + 'static,
+    T: IntoResponse<B>,
+    B: ErrorBody,
+    R: FromRequest<Context = NoContext> + Send + 'static,
+{
+    /// Creates a `SyncService` whose handler can return any type implementing
+    /// [`IntoResponse`].
+    ///
+    /// This works like [`new`], which requires the handler to return a
+    /// `Response`. That keeps the return type of handlers that never return
+    /// (eg. because they always panic) inferable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::SyncService};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|route: Route, _| match route {
+    ///     Route::Index => "Hello world!",
+    /// });
+    /// ```
+    ///
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    /// [`new`]: #method.new
+    pub fn responding(handler: H) -> Self {
+        Self::responding_with_context(handler, NoContext)
+    }
+}
+
+impl<H, R, T, B> SyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> T + Send + Sync + 'static,
+    T: IntoResponse<B>,
+    B: ErrorBody,
+    R: FromRequest + Send + 'static,
+{
+    /// Like [`with_context`], but the handler can return any type implementing
+    /// [`IntoResponse`].
+    ///
+    /// [`with_context`]: #method.with_context
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context(handler: H, context: R::Context) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::shared(context),
+            prepare: |handler, _context| {
+                let handler = handler.clone();
+                Box::new(move |route, request| handler(route, request).into_response())
+            },
+            errors: ErrorHooks::default(),
+            pool: None,
+        }
+    }
+
+    /// Like [`with_thread_pool`], but the handler can return any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `config.threads` is 0.
+    ///
+    /// [`with_thread_pool`]: #method.with_thread_pool
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_thread_pool(
+        handler: H,
+        context: R::Context,
+        config: PoolConfig,
+    ) -> Self {
+        Self {
+            pool: Some(Arc::new(HandlerPool::new(config))),
+            ..Self::responding_with_context(handler, context)
+        }
+    }
+
+    /// Like [`with_context_fn`], but the handler can return any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// [`with_context_fn`]: #method.with_context_fn
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context_fn<C, F>(handler: H, factory: C) -> Self
+    where
+        C: Fn(&Request<()>) -> F + Send + Sync + 'static,
+        F: IntoFuture<Item = R::Context, Error = BoxedError>,
+        F::Future: Send + 'static,
+    {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::factory(factory),
+            prepare: |handler, _context| {
+                let handler = handler.clone();
+                Box::new(move |route, request| handler(route, request).into_response())
+            },
+            errors: ErrorHooks::default(),
+            pool: None,
+        }
+    }
+}
+
+impl<H, R, T, B> SyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>, Arc<R::Context>) -> T + Send + Sync + 'static,
+    T: IntoResponse<B>,
+    B: ErrorBody,
+    R: FromRequest + Send + 'static,
+    R::Context: Send + Sync + 'static,
+{
+    /// Like [`with_context_handler`], but the handler can return any type
+    /// implementing [`IntoResponse`].
+    ///
+    /// [`with_context_handler`]: #method.with_context_handler
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context_handler(handler: H, context: R::Context) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            context: ContextSource::shared(context),
+            prepare: |handler, context| {
+                let handler = handler.clone();
+                let context = context.clone();
+                Box::new(move |route, request| handler(route, request, context).into_response())
+            },
+            errors: ErrorHooks::default(),
+            pool: None,
+        }
+    }
+}
+
+impl<H, R, B> SyncService<H, R, B>
+where
+    R: FromRequest + Send + 'static,
+{
+    /// Sets a function that creates the response for any error that occurs
+    /// while processing a request.
+    ///
+    /// This works exactly like [`AsyncService::with_error_handler`]. The
+    /// function is called on the futures executor, so it must not block.
+    ///
+    /// [`AsyncService::with_error_handler`]: struct.AsyncService.html#method.with_error_handler
+    pub fn with_error_handler<E>(mut self, handler: E) -> Self
+    where
+        E: Fn(BoxedError) -> Response<Body> + Send + Sync + 'static,
+    {
+        self.errors.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a function that renders the body of the responses created for
+    /// [`hyperdrive::Error`]s.
+    ///
+    /// This works exactly like [`AsyncService::error_body`].
+    ///
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`AsyncService::error_body`]: struct.AsyncService.html#method.error_body
+    pub fn error_body<E>(mut self, renderer: E) -> Self
+    where
+        E: ErrorRenderer,
+    {
+        self.errors.body = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Responds to errors of type `E` using [`ResponseError::response`].
+    ///
+    /// This works exactly like [`AsyncService::response_error`].
+    ///
+    /// [`ResponseError::response`]: ../trait.ResponseError.html#method.response
+    /// [`AsyncService::response_error`]: struct.AsyncService.html#method.response_error
+    pub fn response_error<E>(mut self) -> Self
+    where
+        E: ResponseError,
+    {
+        register_response_error::<E>();
+        Arc::make_mut(&mut self.errors.response_errors).push(render_response_error::<E>);
+        self
+    }
+
+    /// Sets a function that creates the response for requests that don't
+    /// match any route.
+    ///
+    /// This works like [`AsyncService::not_found`], except that `handler`
+    /// directly returns the response. Like the request handler, it is run on
+    /// the blocking thread pool.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::SyncService};
+    /// use hyper::StatusCode;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .not_found(|request| {
+    ///         let body = format!("there's nothing at {}", request.uri().path());
+    ///         (StatusCode::NOT_FOUND, body)
+    ///     });
+    /// ```
+    ///
+    /// [`AsyncService::not_found`]: struct.AsyncService.html#method.not_found
+    pub fn not_found<N, T>(mut self, handler: N) -> Self
+    where
+        N: Fn(Arc<Request<()>>) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let handler = Arc::new(handler);
+        self.errors.not_found = Some(Arc::new(move |req| {
+            let handler = handler.clone();
+            Box::new(crate::blocking(move || Ok(handler(req).into_response())))
+        }));
+        self
+    }
+
+    /// Sets whether `OPTIONS` requests are answered automatically.
+    ///
+    /// This works exactly like [`AsyncService::auto_options`].
+    ///
+    /// [`AsyncService::auto_options`]: struct.AsyncService.html#method.auto_options
+    pub fn auto_options(mut self, enabled: bool) -> Self {
+        self.errors.auto_options = enabled;
+        self
+    }
+
+    /// Sets whether similar routes are suggested when no route matches.
+    ///
+    /// This works exactly like [`AsyncService::suggest_routes`].
+    ///
+    /// [`AsyncService::suggest_routes`]: struct.AsyncService.html#method.suggest_routes
+    pub fn suggest_routes(mut self, enabled: bool) -> Self {
+        self.errors.suggest_routes = if enabled { Some(R::route_paths) } else { None };
+        self
+    }
+
+    /// Turns panics into `500 Internal Server Error` responses.
+    ///
+    /// This works exactly like [`AsyncService::recover_panics`].
+    ///
+    /// [`AsyncService::recover_panics`]: struct.AsyncService.html#method.recover_panics
+    pub fn recover_panics(mut self) -> Self {
+        self.errors.recover_panics = true;
+        self
+    }
+
+    /// Sets which value is kept when `ResponseDecorations` set a header that
+    /// already has a different value.
+    ///
+    /// This works exactly like [`AsyncService::header_policy`].
+    ///
+    /// [`AsyncService::header_policy`]: struct.AsyncService.html#method.header_policy
+    pub fn header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.errors.header_policy = policy;
+        self
+    }
+}
+
+impl<H, R, B> Clone for SyncService<H, R, B>
+where
+    R: FromRequest + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
             context: self.context.clone(),
+            prepare: self.prepare,
+            errors: self.errors.clone(),
+            pool: self.pool.clone(),
         }
     }
 }
 
-impl<C, H, R> MakeService<C> for SyncService<H, R>
+impl<C, H, R, B> MakeService<C> for SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
+    H: Send + Sync + 'static,
     R: FromRequest + Send + 'static,
-    R::Context: Clone,
+    B: ErrorBody,
 {
     type ReqBody = Body;
-    type ResBody = Body;
+    type ResBody = B;
     type Error = BoxedError;
     type Service = Self;
     type Future = FutureResult<Self, BoxedError>;
@@ -376,54 +2026,92 @@ where
     }
 }
 
-impl<H, R> Service for SyncService<H, R>
+impl<H, R, B> Service for SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
+    H: Send + Sync + 'static,
     R: FromRequest + Send + 'static,
-    R::Context: Clone,
+    B: ErrorBody,
 {
     type ReqBody = Body;
-    type ResBody = Body;
+    type ResBody = B;
     type Error = BoxedError;
-    type Future = DefaultFuture<Response<Body>, BoxedError>;
+    type Future = DefaultFuture<Response<B>, BoxedError>;
 
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        if self.errors.recover_panics {
+            let future =
+                CatchUnwindFuture::catch(|| self.dispatch(req), panic_handler::RecoverPanic);
+            Box::new(future)
+        } else {
+            self.dispatch(req)
+        }
+    }
+}
+
+impl<H, R, B> SyncService<H, R, B>
+where
+    H: Send + Sync + 'static,
+    R: FromRequest + Send + 'static,
+    B: ErrorBody,
+{
+    /// Decodes `req` and passes it to the handler on a thread pool.
+    fn dispatch(&self, mut req: Request<Body>) -> DefaultFuture<Response<B>, BoxedError> {
         let is_head = req.method() == Method::HEAD;
         let handler = self.handler.clone();
+        let prepare = self.prepare;
+        let errors = self.errors.clone();
+        let decorations = ResponseDecorations::with_policy(errors.header_policy);
+        req.extensions_mut().insert(decorations.clone());
+        req.extensions_mut().insert(RequestScope::default());
+        PendingUpgrade::extract(&mut req);
 
         let (parts, body) = req.into_parts();
         let req = Arc::new(Request::from_parts(parts, ()));
 
-        let fut = R::from_request_and_body(&req, body, self.context.clone())
-            .and_then(move |route| {
-                // Run the sync handler on the blocking thread pool.
-                crate::blocking(move || Ok(handler(route, req)))
+        let orig = req.clone();
+        let pool = self.pool.clone();
+        let fut = self
+            .context
+            .with(&orig, move |context| {
+                let handler = prepare(&handler, &context);
+                R::from_request_and_body(&req, body, context).and_then(move |route| {
+                    let name = route.route_name();
+                    match pool {
+                        // Run the sync handler on the dedicated pool, if configured
+                        Some(pool) => {
+                            Either::A(HandlerPool::run(&pool, move || handler(route, req)))
+                        }
+                        // Otherwise run it on the blocking thread pool
+                        None => Either::B(crate::blocking(move || Ok(handler(route, req)))),
+                    }
+                    .map(move |response| with_route_name(response, name))
+                })
+            })
+            .or_else(move |err| {
+                errors
+                    .respond(err, orig)
+                    .map(|response| response.map(B::from_body))
             })
             .map(move |response| {
                 if is_head {
-                    // Responses to HEAD requests must have an empty body
-                    response.map(|_| Body::empty())
+                    strip_body(response)
                 } else {
                     response
                 }
             })
-            .or_else(|err| {
-                if let Some(our_error) = err.downcast_ref::<Error>() {
-                    Ok(our_error.response().map(|()| Body::empty()))
-                } else {
-                    Err(err)
-                }
+            .map(move |mut response| {
+                decorations.apply(&mut response);
+                response
             });
 
         Box::new(fut)
     }
 }
 
-impl<H, R> fmt::Debug for SyncService<H, R>
+impl<H, R, B> fmt::Debug for SyncService<H, R, B>
 where
-    H: Fn(R, Arc<Request<()>>) -> Response<Body> + Send + Sync + 'static,
     R: FromRequest + Send + 'static,
-    R::Context: Clone + fmt::Debug,
+    R::Context: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Closures aren't debug-printable, so we print a few Arc stats instead
@@ -447,195 +2135,6669 @@ where
     }
 }
 
-/// Extension trait for types implementing Hyper's `Service` trait.
+/// Asynchronous hyper service adapter for handlers that aren't `Send`.
 ///
-/// This adds a number of convenience methods that can be used to build robust
-/// applications with Hyper and Hyperdrive.
-pub trait ServiceExt: Service + Sized {
-    /// Catches any panics that occur in the service `self`, and calls an
-    /// asynchronous panic handler with the panic payload.
-    ///
-    /// The `handler` can decide if and how the request should be answered. A
-    /// common option is to return a `500 Internal Server Error` response to the
-    /// client. If the handler returns an error, the connection will be dropped
-    /// and no response will be sent, which mirrors the behavior of Hyper.
+/// This works like [`AsyncService`], but neither the handler closure nor the
+/// future it returns have to be `Send` or `Sync`. The handler may capture an
+/// `Rc` or use thread-local state, like a template engine or database handle
+/// that can't be shared between threads.
+///
+/// In return, the service and the futures it creates can only run on a
+/// single-threaded executor. Pass tokio's `current_thread::TaskExecutor` to
+/// `hyper::server::Builder::executor` to serve it.
+///
+/// # Limitations
+///
+/// Only the handler is freed from the `Send` requirement. Requests are still
+/// decoded by the [`FromRequest`] implementation, whose future must be `Send`,
+/// so the route type, the guards and [`FromBody`] implementations it uses and
+/// the context must be `Send` as before. The functions passed to
+/// [`with_error_handler`] and [`not_found`] must be `Send` and `Sync` as well.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, service::LocalAsyncService};
+/// use hyper::{Body, Response, Server};
+/// use futures::IntoFuture;
+/// use tokio::runtime::current_thread;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let counter = Rc::new(Cell::new(0));
+/// let service = LocalAsyncService::new(move |route: Route, _| match route {
+///     Route::Index => {
+///         counter.set(counter.get() + 1);
+///         Ok(Response::new(Body::from(format!("visitor #{}", counter.get())))).into_future()
+///     }
+/// });
+///
+/// let srv = Server::bind(&"127.0.0.1:0".parse().unwrap())
+///     .executor(current_thread::TaskExecutor::current())
+///     .serve(service);
+///
+/// // To run the server:
+/// // current_thread::block_on_all(srv).unwrap();
+/// ```
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`FromRequest`]: ../trait.FromRequest.html
+/// [`FromBody`]: ../trait.FromBody.html
+/// [`with_error_handler`]: #method.with_error_handler
+/// [`not_found`]: #method.not_found
+pub struct LocalAsyncService<H, R, B = Body>
+where
+    R: FromRequest,
+{
+    handler: Rc<H>,
+    context: Arc<R::Context>,
+    errors: ErrorHooks,
+    _body: PhantomData<fn() -> B>,
+}
+
+impl<H, R, F, B> LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest<Context = NoContext>,
+    R::Future: 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + 'static,
+    B: ErrorBody,
+{
+    /// Creates a `LocalAsyncService` from a handler closure.
     ///
-    /// **Note**: Panics occurring inside of `handler` will not be caught again.
-    /// The behavior in this case depends on the futures executor in use. When
-    /// using tokio, it will catch the panic in the worker thread and recover.
-    /// The connection to the client will be dropped.
+    /// This will pass a [`NoContext`] to the [`FromRequest`] implementation.
+    /// If you need to pass a custom context, refer to [`with_context`].
     ///
-    /// **Note**: Like `std::panic::catch_unwind`, this only works when the
-    /// final binary is compiled with `panic = unwind` (the default). Using
-    /// `panic = abort` will *always* abort the whole process on any panic and
-    /// cannot be caught.
+    /// [`NoContext`]: ../struct.NoContext.html
+    /// [`FromRequest`]: ../trait.FromRequest.html
+    /// [`with_context`]: #method.with_context
+    pub fn new(handler: H) -> Self {
+        Self::responding(handler)
+    }
+}
+
+impl<H, R, F, B> LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest,
+    R::Future: 'static,
+    F: Future<Item = Response<B>, Error = BoxedError> + 'static,
+    B: ErrorBody,
+{
+    /// Creates a `LocalAsyncService` that will call `handler` to process
+    /// incoming requests.
     ///
-    /// **Note**: This mechanism is not very suitable for *logging* panics,
-    /// since no useful backtrace can be constructed and no location information
-    /// is available. The panic hook mechanism in the standard library is better
-    /// suited for that (see `std::panic::set_hook`).
+    /// `context` is shared by all requests and passed to the [`FromRequest`]
+    /// implementation.
     ///
-    /// # Examples
+    /// [`FromRequest`]: ../trait.FromRequest.html
+    pub fn with_context(handler: H, context: R::Context) -> Self {
+        Self::responding_with_context(handler, context)
+    }
+}
+
+impl<H, R, F, B> LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest<Context = NoContext>,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    /// Like [`new`], but the handler can resolve to any type implementing
+    /// [`IntoResponse`].
     ///
-    /// ```rust,no_run
-    /// use hyperdrive::{FromRequest, service::*};
-    /// use hyper::{Body, Server, Response};
-    /// use futures::Future;
-    /// use http::StatusCode;
+    /// [`new`]: #method.new
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding(handler: H) -> Self {
+        Self::responding_with_context(handler, NoContext)
+    }
+}
+
+impl<H, R, F, B> LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    /// Like [`with_context`], but the handler can resolve to any type
+    /// implementing [`IntoResponse`].
     ///
-    /// #[derive(FromRequest)]
-    /// enum Routes {
-    ///     #[get("/")]
-    ///     Panic,
-    /// }
+    /// [`with_context`]: #method.with_context
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    pub fn responding_with_context(handler: H, context: R::Context) -> Self {
+        Self {
+            handler: Rc::new(handler),
+            context: Arc::new(context),
+            errors: ErrorHooks::default(),
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<H, R, B> LocalAsyncService<H, R, B>
+where
+    R: FromRequest,
+{
+    /// Sets a function that creates the response for any error that occurs
+    /// while processing a request.
     ///
-    /// let service = SyncService::new(|route: Routes, orig_request| {
-    ///     match route {
-    ///         Routes::Panic => panic!("Oops, something went wrong!"),
-    ///     }
-    /// }).catch_unwind(|panic_payload| {
-    ///     // We ignore the payload here. We could also downcast it to `String`/`&'static str`
-    ///     // and include it in the response.
-    ///     let _ = panic_payload;
+    /// This works exactly like [`AsyncService::with_error_handler`].
     ///
-    ///     let message = r#"
-    ///         <!DOCTYPE html>
-    ///         <html>
-    ///         <body>
-    ///             <h1>Internal Server Error</h1>
-    ///             <p>
-    ///                 The server has encountered an internal error and can not process
-    ///                 your request at this time. Please try again later or contact us
-    ///                 at <pre>help@example.com</pre>.
-    ///             </p>
-    ///         </body>
-    ///         </html>
-    ///     "#;
+    /// [`AsyncService::with_error_handler`]: struct.AsyncService.html#method.with_error_handler
+    pub fn with_error_handler<E>(mut self, handler: E) -> Self
+    where
+        E: Fn(BoxedError) -> Response<Body> + Send + Sync + 'static,
+    {
+        self.errors.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a function that renders the body of the responses created for
+    /// [`hyperdrive::Error`]s.
     ///
-    ///     Ok(Response::builder()
-    ///         .status(StatusCode::INTERNAL_SERVER_ERROR)
-    ///         .header("Content-Type", "text/html")
-    ///         .body(Body::from(message))
-    ///         .expect("couldn't build response"))
-    /// }).make_service_by_cloning();
+    /// This works exactly like [`AsyncService::error_body`].
     ///
-    /// let server = Server::bind(&"127.0.0.1:0".parse().unwrap())
-    ///     .serve(service);
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`AsyncService::error_body`]: struct.AsyncService.html#method.error_body
+    pub fn error_body<E>(mut self, renderer: E) -> Self
+    where
+        E: ErrorRenderer,
+    {
+        self.errors.body = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Responds to errors of type `E` using [`ResponseError::response`].
     ///
-    /// tokio::run(server.map_err(|e| {
-    ///     panic!("unexpected error: {}", e);
-    /// }));
-    /// ```
-    fn catch_unwind<H, R>(self, handler: H) -> CatchUnwind<Self, R, H>
+    /// This works exactly like [`AsyncService::response_error`].
+    ///
+    /// [`ResponseError::response`]: ../trait.ResponseError.html#method.response
+    /// [`AsyncService::response_error`]: struct.AsyncService.html#method.response_error
+    pub fn response_error<E>(mut self) -> Self
     where
-        Self: Service<ResBody = Body, Error = BoxedError> + Sync,
-        Self::Future: Send,
-        H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
-        R: IntoFuture<Item = Response<Body>, Error = BoxedError>,
-        R::Future: Send + 'static;
+        E: ResponseError,
+    {
+        register_response_error::<E>();
+        Arc::make_mut(&mut self.errors.response_errors).push(render_response_error::<E>);
+        self
+    }
 
-    /// Creates a type implementing `MakeService` by cloning `self` for every
-    /// incoming connection.
+    /// Sets a function that creates the response for requests that don't
+    /// match any route.
+    ///
+    /// This works exactly like [`AsyncService::not_found`].
+    ///
+    /// [`AsyncService::not_found`]: struct.AsyncService.html#method.not_found
+    pub fn not_found<N, T>(mut self, handler: N) -> Self
+    where
+        N: Fn(Arc<Request<()>>) -> T + Send + Sync + 'static,
+        T: IntoFuture<Error = BoxedError>,
+        T::Future: Send + 'static,
+        T::Item: IntoResponse + 'static,
+    {
+        self.errors.not_found = Some(Arc::new(move |req| {
+            Box::new(handler(req).into_future().map(IntoResponse::into_response))
+        }));
+        self
+    }
+
+    /// Sets whether `OPTIONS` requests are answered automatically.
+    ///
+    /// This works exactly like [`AsyncService::auto_options`].
+    ///
+    /// [`AsyncService::auto_options`]: struct.AsyncService.html#method.auto_options
+    pub fn auto_options(mut self, enabled: bool) -> Self {
+        self.errors.auto_options = enabled;
+        self
+    }
+
+    /// Sets whether similar routes are suggested when no route matches.
+    ///
+    /// This works exactly like [`AsyncService::suggest_routes`].
+    ///
+    /// [`AsyncService::suggest_routes`]: struct.AsyncService.html#method.suggest_routes
+    pub fn suggest_routes(mut self, enabled: bool) -> Self {
+        self.errors.suggest_routes = if enabled { Some(R::route_paths) } else { None };
+        self
+    }
+
+    /// Turns panics into `500 Internal Server Error` responses.
+    ///
+    /// This works exactly like [`AsyncService::recover_panics`].
+    ///
+    /// [`AsyncService::recover_panics`]: struct.AsyncService.html#method.recover_panics
+    pub fn recover_panics(mut self) -> Self {
+        self.errors.recover_panics = true;
+        self
+    }
+
+    /// Sets which value is kept when `ResponseDecorations` set a header that
+    /// already has a different value.
+    ///
+    /// This works exactly like [`AsyncService::header_policy`].
+    ///
+    /// [`AsyncService::header_policy`]: struct.AsyncService.html#method.header_policy
+    pub fn header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.errors.header_policy = policy;
+        self
+    }
+}
+
+impl<H, R, B> Clone for LocalAsyncService<H, R, B>
+where
+    R: FromRequest,
+{
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            context: self.context.clone(),
+            errors: self.errors.clone(),
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<C, H, R, F, B> MakeService<C> for LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    type ReqBody = Body;
+    type ResBody = B;
+    type Error = BoxedError;
+    type Service = Self;
+    type Future = FutureResult<Self, BoxedError>;
+    type MakeError = BoxedError;
+
+    fn make_service(&mut self, _ctx: C) -> Self::Future {
+        Ok(self.clone()).into_future()
+    }
+}
+
+impl<H, R, F, B> Service for LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    type ReqBody = Body;
+    type ResBody = B;
+    type Error = BoxedError;
+    type Future = LocalDefaultFuture<Response<B>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        if self.errors.recover_panics {
+            let future =
+                CatchUnwindFuture::catch(|| self.dispatch(req), panic_handler::RecoverPanic);
+            Box::new(future)
+        } else {
+            self.dispatch(req)
+        }
+    }
+}
+
+impl<H, R, F, B> LocalAsyncService<H, R, B>
+where
+    H: Fn(R, Arc<Request<()>>) -> F + 'static,
+    R: FromRequest + 'static,
+    R::Future: 'static,
+    F: Future<Error = BoxedError> + 'static,
+    F::Item: IntoResponse<B>,
+    B: ErrorBody,
+{
+    /// Decodes `req` and passes it to the handler.
+    fn dispatch(&self, mut req: Request<Body>) -> LocalDefaultFuture<Response<B>, BoxedError> {
+        let is_head = req.method() == Method::HEAD;
+        let handler = self.handler.clone();
+        let errors = self.errors.clone();
+        let decorations = ResponseDecorations::with_policy(errors.header_policy);
+        req.extensions_mut().insert(decorations.clone());
+        req.extensions_mut().insert(RequestScope::default());
+        PendingUpgrade::extract(&mut req);
+        let (parts, body) = req.into_parts();
+        let req = Arc::new(Request::from_parts(parts, ()));
+        let orig = req.clone();
+        let fut = R::from_request_and_body(&req, body, self.context.clone())
+            .and_then(move |route| {
+                let name = route.route_name();
+                handler(route, req)
+                    .map(move |response| with_route_name(response.into_response(), name))
+            })
+            .or_else(move |err| {
+                errors
+                    .respond(err, orig)
+                    .map(|response| response.map(B::from_body))
+            })
+            .map(move |response| {
+                if is_head {
+                    strip_body(response)
+                } else {
+                    response
+                }
+            })
+            .map(move |mut response| {
+                decorations.apply(&mut response);
+                response
+            });
+
+        Box::new(fut)
+    }
+}
+
+impl<H, R, B> fmt::Debug for LocalAsyncService<H, R, B>
+where
+    R: FromRequest,
+    R::Context: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalAsyncService")
+            .field("handler", &"<closure>")
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+/// Removes the body of a response to a `HEAD` request.
+///
+/// Responses to `HEAD` requests must have an empty body, but should otherwise
+/// carry the same headers as the `GET` response would. If the size of the body
+/// is known, it is advertised via `Content-Length`, unless the handler already
+/// set that header.
+fn strip_body<B: ErrorBody>(mut response: Response<B>) -> Response<B> {
+    if let Some(length) = response.body().content_length() {
+        response
+            .headers_mut()
+            .entry(header::CONTENT_LENGTH)
+            .expect("valid header name")
+            .or_insert_with(|| HeaderValue::from(length));
+    }
+    response.map(|_| B::empty())
+}
+
+/// The name of the route that produced a response.
+///
+/// [`AsyncService`] and [`SyncService`] insert this into the extensions of
+/// every response produced by their handler, using the name returned by
+/// [`FromRequest::route_name`]. Outer adapters like [`ServiceExt::metrics`]
+/// can use it to group requests by route.
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`FromRequest::route_name`]: ../trait.FromRequest.html#method.route_name
+/// [`ServiceExt::metrics`]: trait.ServiceExt.html#tymethod.metrics
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RouteName(pub &'static str);
+
+/// Stores `name` in the extensions of `response`, if there is one.
+fn with_route_name<B>(mut response: Response<B>, name: Option<&'static str>) -> Response<B> {
+    if let Some(name) = name {
+        response.extensions_mut().insert(RouteName(name));
+    }
+    response
+}
+
+/// Configures the dedicated thread pool of a [`SyncService`].
+///
+/// Passed to [`SyncService::with_thread_pool`].
+///
+/// [`SyncService`]: struct.SyncService.html
+/// [`SyncService::with_thread_pool`]: struct.SyncService.html#method.with_thread_pool
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The number of threads handlers are run on.
+    pub threads: usize,
+    /// The maximum number of requests waiting for a free thread.
+    pub queue_limit: usize,
+}
+
+/// The dedicated thread pool of a [`SyncService`].
+///
+/// [`SyncService`]: struct.SyncService.html
+#[derive(Debug)]
+struct HandlerPool {
+    pool: CpuPool,
+    /// The number of handlers that are running or queued.
+    pending: AtomicUsize,
+    max_pending: usize,
+}
+
+impl HandlerPool {
+    fn new(config: PoolConfig) -> Self {
+        assert!(
+            config.threads > 0,
+            "thread pool must have at least 1 thread"
+        );
+
+        Self {
+            pool: CpuPoolBuilder::new()
+                .pool_size(config.threads)
+                .name_prefix("hyperdrive-handler-")
+                .create(),
+            pending: AtomicUsize::new(0),
+            max_pending: config.threads + config.queue_limit,
+        }
+    }
+
+    /// Runs `f` on the pool, or fails with `503 Service Unavailable` if the
+    /// queue is full.
+    fn run<F, T>(
+        this: &Arc<Self>,
+        f: F,
+    ) -> Either<FutureResult<T, BoxedError>, CpuFuture<T, BoxedError>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if this.pending.fetch_add(1, Ordering::SeqCst) >= this.max_pending {
+            this.pending.fetch_sub(1, Ordering::SeqCst);
+            let error = Error::from_status(StatusCode::SERVICE_UNAVAILABLE);
+            return Either::A(Err(error.into()).into_future());
+        }
+
+        // Dropped when `f` has run, or when the request is cancelled before
+        // that (which drops the closure).
+        let slot = PoolSlot(this.clone());
+        Either::B(this.pool.spawn_fn(move || {
+            let _slot = slot;
+            Ok(f())
+        }))
+    }
+}
+
+/// Frees a slot in a `HandlerPool` when dropped.
+struct PoolSlot(Arc<HandlerPool>);
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Headers and status changes that guards want applied to the response.
+///
+/// [`AsyncService`] and [`SyncService`] insert a `ResponseDecorations` into the
+/// extensions of every incoming request before decoding it. Guards can retrieve
+/// it with [`ResponseDecorations::get`] and record changes to the response,
+/// for example to send rate limiting information or echo a request ID. Once
+/// the response has been produced, the service applies all recorded changes to
+/// it. This also happens when decoding the request fails and an error response
+/// is sent instead, so guards that ran before the failure still get to add
+/// their headers.
+///
+/// `ResponseDecorations` is a cheap handle to shared state: cloning it yields
+/// another handle to the same set of changes.
+///
+/// # Ordering and duplicate headers
+///
+/// Changes are applied after the handler has built the response, in the order
+/// they were recorded (guards run in field declaration order):
+///
+/// * [`insert_header`] sets the header to the given value. If the header
+///   already has a different value, set by the handler or by an earlier
+///   decoration, the [`HeaderPolicy`] decides which one is kept, and the
+///   conflict is logged as a warning via the `log` crate.
+/// * [`append_header`] adds the value to the response and keeps all existing
+///   values of the header.
+///
+/// The list-valued headers `Set-Cookie`, `Vary` and `Warning` are always
+/// appended, even by [`insert_header`], so that a guard setting a cookie
+/// doesn't drop the cookies set by the handler or by other guards.
+///
+/// If [`set_status`] is called more than once, the last status wins. The
+/// status is only applied to successful responses, so error responses keep
+/// the status that describes the error.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{Guard, NoContext, BoxedError, service::ResponseDecorations};
+/// use http::{header::HeaderName, HeaderValue};
+/// use std::sync::Arc;
+///
+/// /// Tells clients which API version served their request.
+/// struct ApiVersion;
+///
+/// impl Guard for ApiVersion {
+///     type Context = NoContext;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+///         // Not present when the request isn't dispatched by one of our
+///         // services, for example in tests calling `from_request_sync`.
+///         if let Some(decorations) = ResponseDecorations::get(request) {
+///             decorations.insert_header(
+///                 HeaderName::from_static("api-version"),
+///                 HeaderValue::from_static("2"),
+///             );
+///         }
+///         Ok(ApiVersion)
+///     }
+/// }
+/// ```
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`ResponseDecorations::get`]: #method.get
+/// [`insert_header`]: #method.insert_header
+/// [`append_header`]: #method.append_header
+/// [`set_status`]: #method.set_status
+/// [`HeaderPolicy`]: enum.HeaderPolicy.html
+#[derive(Debug, Clone, Default)]
+pub struct ResponseDecorations {
+    inner: Arc<Mutex<Decorations>>,
+}
+
+#[derive(Debug, Default)]
+struct Decorations {
+    headers: Vec<(HeaderName, HeaderValue, HeaderOp)>,
+    status: Option<StatusCode>,
+    policy: HeaderPolicy,
+}
+
+#[derive(Debug)]
+enum HeaderOp {
+    Insert,
+    Append,
+}
+
+impl ResponseDecorations {
+    /// Returns the `ResponseDecorations` stored in the extensions of `request`.
+    ///
+    /// Returns `None` if the request isn't being processed by a service that
+    /// applies decorations.
+    pub fn get<B>(request: &http::Request<B>) -> Option<&Self> {
+        request_extension(request)
+    }
+
+    /// Creates an empty set of changes that resolves conflicting headers
+    /// according to `policy`.
+    ///
+    /// `ResponseDecorations::default()` uses `HeaderPolicy::LastWins`.
+    pub fn with_policy(policy: HeaderPolicy) -> Self {
+        let decorations = Self::default();
+        decorations.inner.lock().unwrap().policy = policy;
+        decorations
+    }
+
+    /// Sets the header `name` to `value` in the response, replacing any
+    /// existing values.
+    pub fn insert_header(&self, name: HeaderName, value: HeaderValue) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.headers.push((name, value, HeaderOp::Insert));
+    }
+
+    /// Adds `value` to the header `name` in the response, keeping existing
+    /// values.
+    pub fn append_header(&self, name: HeaderName, value: HeaderValue) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.headers.push((name, value, HeaderOp::Append));
+    }
+
+    /// Overrides the status code of a successful response.
+    pub fn set_status(&self, status: StatusCode) {
+        self.inner.lock().unwrap().status = Some(status);
+    }
+
+    /// Applies all recorded changes to `response`.
+    ///
+    /// This is done automatically by [`AsyncService`] and [`SyncService`], and
+    /// only needs to be called by custom services.
+    ///
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    pub fn apply<B>(&self, response: &mut Response<B>) {
+        let inner = self.inner.lock().unwrap();
+        let headers = response.headers_mut();
+        for (name, value, op) in &inner.headers {
+            match op {
+                HeaderOp::Insert if !is_list_header(name) => {
+                    let conflict = headers.get_all(name).iter().any(|old| old != value);
+                    if conflict {
+                        let old = headers.get_all(name).iter().collect::<Vec<_>>();
+                        log::warn!(
+                            "conflicting values for response header `{}`: {:?} and {:?} ({:?})",
+                            name,
+                            old,
+                            value,
+                            inner.policy
+                        );
+                    }
+                    if inner.policy == HeaderPolicy::LastWins || !headers.contains_key(name) {
+                        headers.insert(name.clone(), value.clone());
+                    }
+                }
+                HeaderOp::Insert | HeaderOp::Append => {
+                    headers.append(name.clone(), value.clone());
+                }
+            }
+        }
+
+        if let Some(status) = inner.status {
+            if !response.status().is_client_error() && !response.status().is_server_error() {
+                *response.status_mut() = status;
+            }
+        }
+    }
+}
+
+/// Determines which value [`ResponseDecorations`] keep when a header is set
+/// more than once.
+///
+/// This applies to headers set with [`insert_header`] that already have a
+/// different value, either from the handler or from an earlier decoration.
+/// The list-valued headers `Set-Cookie`, `Vary` and `Warning` are appended
+/// instead. Services use `LastWins` unless configured otherwise with their
+/// `header_policy` method.
+///
+/// [`ResponseDecorations`]: struct.ResponseDecorations.html
+/// [`insert_header`]: struct.ResponseDecorations.html#method.insert_header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeaderPolicy {
+    /// The value that was set first is kept. Since decorations are applied
+    /// after the handler, this keeps the headers of the handler.
+    FirstWins,
+    /// The value that was set last replaces all others.
+    LastWins,
+}
+
+// `#[default]` on variants requires a newer compiler than we support
+#[allow(clippy::derivable_impls)]
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        HeaderPolicy::LastWins
+    }
+}
+
+/// Returns whether `name` is a header that is commonly sent several times,
+/// whose values thus must not replace each other.
+fn is_list_header(name: &HeaderName) -> bool {
+    name == header::SET_COOKIE || name == header::VARY || name == header::WARNING
+}
+
+/// Storage for state that is accumulated while processing a single request.
+///
+/// The context passed to guards and [`FromBody`] implementations is shared by
+/// all requests and can't be modified. When they need to record something
+/// about the current request instead (like timing marks or audit log entries),
+/// they can store it in the `RequestScope`.
+///
+/// [`AsyncService`], [`SyncService`] and [`LocalAsyncService`] insert a new
+/// `RequestScope` into the extensions of every incoming request before decoding
+/// it, as does [`FromRequest::from_request`] if the request doesn't already
+/// contain one. Guards and `FromBody` implementations retrieve it with
+/// [`RequestScope::of`], and routes can also take it as an
+/// [`Extension<RequestScope>`] field. The handler receives the same request, so
+/// it can read everything that was stored while decoding it.
+///
+/// A `RequestScope` stores at most one value of every type. It is a cheap
+/// handle to shared state: cloning it yields another handle to the same
+/// values.
+///
+/// The values are stored in an `Arc`, and the methods hand out clones of it,
+/// so the scope is never locked after a method returns. This means that the
+/// scope can be used freely while holding on to a value, and that values that
+/// are modified by several guards need interior mutability (eg. a `Mutex`).
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{Guard, NoContext, BoxedError, service::RequestScope};
+/// use std::sync::{Arc, Mutex};
+///
+/// /// The audit log of the current request.
+/// struct AuditLog(Mutex<Vec<String>>);
+///
+/// struct Authenticated;
+///
+/// impl Guard for Authenticated {
+///     type Context = NoContext;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+///         if let Some(scope) = RequestScope::of(request) {
+///             scope
+///                 .get_or_insert_with(|| AuditLog(Mutex::new(Vec::new())))
+///                 .0
+///                 .lock()
+///                 .unwrap()
+///                 .push("authenticated".to_string());
+///         }
+///         Ok(Authenticated)
+///     }
+/// }
+/// ```
+///
+/// [`FromBody`]: ../trait.FromBody.html
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`LocalAsyncService`]: struct.LocalAsyncService.html
+/// [`FromRequest::from_request`]: ../trait.FromRequest.html#method.from_request
+/// [`RequestScope::of`]: #method.of
+/// [`Extension<RequestScope>`]: ../guard/struct.Extension.html
+#[derive(Clone, Default)]
+pub struct RequestScope {
+    values: Arc<Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl RequestScope {
+    /// Returns the `RequestScope` stored in the extensions of `request`.
+    ///
+    /// Returns `None` if the request isn't being processed by a service or
+    /// method that inserts one.
+    pub fn of<B>(request: &http::Request<B>) -> Option<&Self> {
+        request_extension(request)
+    }
+
+    /// Stores `value` in the scope, returning the value of the same type that
+    /// was stored before.
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<Arc<T>> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .map(downcast)
+    }
+
+    /// Returns the value of type `T`, or `None` if no such value is stored.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .map(downcast)
+    }
+
+    /// Returns the value of type `T`, storing the result of `default` first if
+    /// no such value is stored.
+    ///
+    /// The scope isn't locked while `default` runs, so it may use the scope as
+    /// well. If it stores a value of type `T` itself, that value is kept and
+    /// returned instead.
+    pub fn get_or_insert_with<T, F>(&self, default: F) -> Arc<T>
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        let value = Arc::new(default());
+        let mut values = self.values.lock().unwrap();
+        let value = values.entry(TypeId::of::<T>()).or_insert(value).clone();
+        downcast(value)
+    }
+
+    /// Removes the value of type `T` from the scope and returns it.
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values
+            .lock()
+            .unwrap()
+            .remove(&TypeId::of::<T>())
+            .map(downcast)
+    }
+}
+
+impl fmt::Debug for RequestScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestScope")
+            .field("len", &self.values.lock().unwrap().len())
+            .finish()
+    }
+}
+
+fn downcast<T: Send + Sync + 'static>(value: Arc<dyn Any + Send + Sync>) -> Arc<T> {
+    value
+        .downcast()
+        .unwrap_or_else(|_| unreachable!("value stored under the wrong type ID"))
+}
+
+/// The pending protocol upgrade of a request (eg. to a WebSocket connection).
+///
+/// hyper hands out the upgraded connection via the request body, which guards
+/// and handlers don't have access to. [`AsyncService`], [`SyncService`] and
+/// [`LocalAsyncService`] therefore move it into the request extensions before
+/// decoding requests that have an `Upgrade` header and no body. From there, it
+/// can be taken exactly once using [`PendingUpgrade::take`].
+///
+/// The connection is only upgraded if the response has the status code `101
+/// Switching Protocols`.
+///
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`LocalAsyncService`]: struct.LocalAsyncService.html
+/// [`PendingUpgrade::take`]: #method.take
+#[derive(Debug)]
+pub struct PendingUpgrade {
+    upgrade: Mutex<Option<OnUpgrade>>,
+}
+
+impl PendingUpgrade {
+    /// Moves the pending upgrade of `request` into its extensions, if it asks
+    /// for one.
+    fn extract(request: &mut Request<Body>) {
+        if !request.headers().contains_key(header::UPGRADE) || !request.body().is_end_stream() {
+            return;
+        }
+
+        let body = std::mem::replace(request.body_mut(), Body::empty());
+        request.extensions_mut().insert(PendingUpgrade {
+            upgrade: Mutex::new(Some(body.on_upgrade())),
+        });
+    }
+
+    /// Takes the pending upgrade out of the extensions of `request`.
+    ///
+    /// Returns `None` if the request doesn't ask for an upgrade, if it isn't
+    /// being processed by one of the services that provide it, or if it was
+    /// already taken.
+    pub fn take<B>(request: &http::Request<B>) -> Option<OnUpgrade> {
+        request_extension::<Self, _>(request)
+            .and_then(|pending| pending.upgrade.lock().unwrap().take())
+    }
+}
+
+/// Extension trait for types implementing Hyper's `Service` trait.
+///
+/// This adds a number of convenience methods that can be used to build robust
+/// applications with Hyper and Hyperdrive.
+pub trait ServiceExt: Service + Sized {
+    /// Catches any panics that occur in the service `self`, and calls an
+    /// asynchronous panic handler with the panic payload.
+    ///
+    /// The `handler` can decide if and how the request should be answered. A
+    /// common option is to return a `500 Internal Server Error` response to the
+    /// client. If the handler returns an error, the connection will be dropped
+    /// and no response will be sent, which mirrors the behavior of Hyper.
+    ///
+    /// **Note**: Panics occurring inside of `handler` will not be caught again.
+    /// The behavior in this case depends on the futures executor in use. When
+    /// using tokio, it will catch the panic in the worker thread and recover.
+    /// The connection to the client will be dropped.
+    ///
+    /// **Note**: Like `std::panic::catch_unwind`, this only works when the
+    /// final binary is compiled with `panic = unwind` (the default). Using
+    /// `panic = abort` will *always* abort the whole process on any panic and
+    /// cannot be caught.
+    ///
+    /// **Note**: This mechanism is not very suitable for *logging* panics,
+    /// since no useful backtrace can be constructed and no location information
+    /// is available. The panic hook mechanism in the standard library is better
+    /// suited for that (see `std::panic::set_hook`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use hyperdrive::{FromRequest, service::*};
+    /// use hyper::{Body, Server, Response};
+    /// use futures::Future;
+    /// use http::StatusCode;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Routes {
+    ///     #[get("/")]
+    ///     Panic,
+    /// }
+    ///
+    /// let service = SyncService::new(|route: Routes, orig_request| {
+    ///     match route {
+    ///         Routes::Panic => panic!("Oops, something went wrong!"),
+    ///     }
+    /// }).catch_unwind(|panic_payload| {
+    ///     // We ignore the payload here. We could also downcast it to `String`/`&'static str`
+    ///     // and include it in the response.
+    ///     let _ = panic_payload;
+    ///
+    ///     let message = r#"
+    ///         <!DOCTYPE html>
+    ///         <html>
+    ///         <body>
+    ///             <h1>Internal Server Error</h1>
+    ///             <p>
+    ///                 The server has encountered an internal error and can not process
+    ///                 your request at this time. Please try again later or contact us
+    ///                 at <pre>help@example.com</pre>.
+    ///             </p>
+    ///         </body>
+    ///         </html>
+    ///     "#;
+    ///
+    ///     Ok(Response::builder()
+    ///         .status(StatusCode::INTERNAL_SERVER_ERROR)
+    ///         .header("Content-Type", "text/html")
+    ///         .body(Body::from(message))
+    ///         .expect("couldn't build response"))
+    /// }).make_service_by_cloning();
+    ///
+    /// let server = Server::bind(&"127.0.0.1:0".parse().unwrap())
+    ///     .serve(service);
+    ///
+    /// tokio::run(server.map_err(|e| {
+    ///     panic!("unexpected error: {}", e);
+    /// }));
+    /// ```
+    fn catch_unwind<H, R>(self, handler: H) -> CatchUnwind<Self, R, H>
+    where
+        Self: Service<Error = BoxedError> + Sync,
+        Self::Future: Send,
+        H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = Response<Self::ResBody>, Error = BoxedError>,
+        R::Future: Send + 'static;
+
+    /// Like [`catch_unwind`], but also passes information about the request
+    /// that caused the panic to `handler`.
+    ///
+    /// The [`RequestInfo`] is captured before the request is passed to `self`,
+    /// which makes it possible to include the method and path of the failing
+    /// request in the response or in an error report.
+    ///
+    /// The notes on [`catch_unwind`] apply here as well.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use hyperdrive::{FromRequest, service::*};
+    /// use hyper::{Body, Server, Response};
+    /// use futures::Future;
+    /// use http::StatusCode;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Routes {
+    ///     #[get("/")]
+    ///     Panic,
+    /// }
+    ///
+    /// let service = SyncService::new(|route: Routes, orig_request| {
+    ///     match route {
+    ///         Routes::Panic => panic!("Oops, something went wrong!"),
+    ///     }
+    /// }).catch_unwind_with_request(|_panic_payload, request| {
+    ///     let message = format!("{} {} failed", request.method(), request.path());
+    ///
+    ///     Ok(Response::builder()
+    ///         .status(StatusCode::INTERNAL_SERVER_ERROR)
+    ///         .body(Body::from(message))
+    ///         .expect("couldn't build response"))
+    /// }).make_service_by_cloning();
+    ///
+    /// let server = Server::bind(&"127.0.0.1:0".parse().unwrap())
+    ///     .serve(service);
+    ///
+    /// tokio::run(server.map_err(|e| {
+    ///     panic!("unexpected error: {}", e);
+    /// }));
+    /// ```
+    ///
+    /// [`catch_unwind`]: #tymethod.catch_unwind
+    /// [`RequestInfo`]: struct.RequestInfo.html
+    fn catch_unwind_with_request<H, R>(self, handler: H) -> CatchUnwindWithRequest<Self, R, H>
+    where
+        Self: Service<Error = BoxedError> + Sync,
+        Self::Future: Send,
+        H: Fn(Box<dyn Any + Send>, RequestInfo) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = Response<Self::ResBody>, Error = BoxedError>,
+        R::Future: Send + 'static;
+
+    /// Creates a type implementing `MakeService` by cloning `self` for every
+    /// incoming connection.
+    ///
+    /// The result of this can be directly passed to Hyper's `Builder::serve`.
+    fn make_service_by_cloning(self) -> MakeServiceByCloning<Self>
+    where
+        Self: Clone;
+
+    /// Creates a type implementing `MakeService` that clones `self` for every
+    /// incoming connection and inserts the connection's [`ConnectInfo`] into
+    /// every request.
+    ///
+    /// This is needed for the [`RemoteAddr`] guard. The connection type must
+    /// implement [`Connected`], as Hyper's `AddrStream` (used by
+    /// `Server::bind`) does. For other connection types, either implement
+    /// [`Connected`] for them, or insert a [`ConnectInfo`] using
+    /// [`MakeServiceByCloning::with_connection_hook`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, guard::RemoteAddr, service::{ServiceExt, SyncService}};
+    /// use hyper::Server;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index { addr: RemoteAddr },
+    /// }
+    ///
+    /// let service = SyncService::responding(|Route::Index { addr }, _| {
+    ///     format!("Hello, {}!", addr.ip())
+    /// })
+    /// .with_connect_info();
+    ///
+    /// let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    /// ```
+    ///
+    /// [`ConnectInfo`]: struct.ConnectInfo.html
+    /// [`Connected`]: trait.Connected.html
+    /// [`RemoteAddr`]: ../guard/struct.RemoteAddr.html
+    /// [`MakeServiceByCloning::with_connection_hook`]: struct.MakeServiceByCloning.html#method.with_connection_hook
+    fn with_connect_info(self) -> MakeWithConnectInfo<Self>
+    where
+        Self: Clone;
+
+    /// Calls `f` with every incoming request before passing it on to `self`.
+    ///
+    /// `f` can modify the request in any way. A common use is inserting values
+    /// into the request extensions, which can then be retrieved by routes via
+    /// the [`Extension`] guard. The body is passed on as-is and never
+    /// buffered.
+    ///
+    /// Since `f` runs before the request reaches `self`, an [`AsyncService`]
+    /// or [`SyncService`] decodes the modified request: if `f` rewrites the
+    /// URI, the route is matched against the new path. The original request is
+    /// not retained anywhere.
+    ///
+    /// If `f` needs to reject requests, use [`try_map_request`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    /// use hyper::{Body, Response};
+    /// use std::time::Instant;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::new(|_: Route, orig| {
+    ///     let received_at = orig.extensions().get::<Instant>();
+    ///     Response::new(Body::from(format!("Received at {:?}", received_at)))
+    /// })
+    /// .map_request(|request| {
+    ///     request.extensions_mut().insert(Instant::now());
+    /// });
+    /// ```
+    ///
+    /// Strip a legacy `/v1` prefix from all paths, so that `/v1/users` is
+    /// routed like `/users`:
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/users")]
+    ///     Users,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "[]")
+    ///     .map_request(|request| {
+    ///         let path_and_query = match request.uri().path_and_query() {
+    ///             Some(pq) if pq.path().starts_with("/v1/") => pq.as_str()[3..].to_string(),
+    ///             _ => return,
+    ///         };
+    ///         *request.uri_mut() = path_and_query.parse().unwrap();
+    ///     });
+    /// ```
+    ///
+    /// [`Extension`]: ../guard/struct.Extension.html
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    /// [`try_map_request`]: #tymethod.try_map_request
+    fn map_request<F>(self, f: F) -> MapRequest<Self, F>
+    where
+        F: Fn(&mut Request<Self::ReqBody>) + Send + Sync + 'static;
+
+    /// Calls the fallible function `f` with every incoming request before
+    /// passing it on to `self`.
+    ///
+    /// This works like [`map_request`], but `f` can reject a request by
+    /// returning an error, which can be any type implementing
+    /// [`IntoResponse`]. The resulting response is sent to the client right
+    /// away, without calling `self`.
+    ///
+    /// # Examples
+    ///
+    /// Reject requests with a deprecated header:
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    /// use hyper::StatusCode;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .try_map_request(|request| {
+    ///         if request.headers().contains_key("x-legacy-auth") {
+    ///             return Err((StatusCode::BAD_REQUEST, "X-Legacy-Auth is no longer supported"));
+    ///         }
+    ///         Ok(())
+    ///     });
+    /// ```
+    ///
+    /// [`map_request`]: #tymethod.map_request
+    /// [`IntoResponse`]: ../response/trait.IntoResponse.html
+    fn try_map_request<F, R>(self, f: F) -> TryMapRequest<Self, F, R>
+    where
+        F: Fn(&mut Request<Self::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+        R: IntoResponse<Self::ResBody>;
+
+    /// Calls `f` with every response produced by `self`.
+    ///
+    /// This is applied to all responses, including the error responses
+    /// [`AsyncService`] and [`SyncService`] create from a [`hyperdrive::Error`]
+    /// (eg. `404 Not Found` when no route matches). It is not applied when the
+    /// inner service fails with an error that isn't turned into a response.
+    ///
+    /// If `f` can fail, use [`try_map_response`] instead.
+    ///
+    /// # Examples
+    ///
+    /// Add a `Server` header to every response:
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    /// use http::{header, HeaderValue};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .map_response(|mut response| {
+    ///         response
+    ///             .headers_mut()
+    ///             .insert(header::SERVER, HeaderValue::from_static("my-app"));
+    ///         response
+    ///     });
+    /// ```
+    ///
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`try_map_response`]: #tymethod.try_map_response
+    fn map_response<F>(self, f: F) -> MapResponse<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(Response<Self::ResBody>) -> Response<Self::ResBody> + Send + Sync + 'static;
+
+    /// Calls the fallible function `f` with every response produced by
+    /// `self`.
+    ///
+    /// This works like [`map_response`], but `f` returns a `Result`. If it
+    /// returns an error, the request fails with that error.
+    ///
+    /// [`map_response`]: #tymethod.map_response
+    fn try_map_response<F>(self, f: F) -> TryMapResponse<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(Response<Self::ResBody>) -> Result<Response<Self::ResBody>, BoxedError>
+            + Send
+            + Sync
+            + 'static;
+
+    /// Calls `logger` once for every request processed by `self`, after the
+    /// response has been produced.
+    ///
+    /// `logger` is passed information about the request and the response, and
+    /// the time it took to produce the response (measured from the call to the
+    /// inner service until its future resolved). If the inner service fails
+    /// with an error, no response will be sent and the connection is dropped.
+    /// This is still logged, as a `500 Internal Server Error` with
+    /// [`ResponseInfo::is_dropped`] returning `true`.
+    ///
+    /// The [`log_default`] method provides a logger based on the `log` crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .log(|request, response, elapsed| {
+    ///         eprintln!(
+    ///             "{} {} -> {} in {:?}",
+    ///             request.method(),
+    ///             request.path(),
+    ///             response.status(),
+    ///             elapsed,
+    ///         );
+    ///     });
+    /// ```
+    ///
+    /// [`ResponseInfo::is_dropped`]: struct.ResponseInfo.html#method.is_dropped
+    /// [`log_default`]: #tymethod.log_default
+    fn log<L>(self, logger: L) -> AccessLog<Self, L>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static;
+
+    /// Logs every request processed by `self` using the `log` crate.
+    ///
+    /// Requests are logged at the `info` level, with the target
+    /// `hyperdrive::access`. The query string isn't logged, since it might
+    /// contain sensitive data. Refer to [`log`] for details.
+    ///
+    /// [`log`]: #tymethod.log
+    fn log_default(self) -> AccessLog<Self, fn(&RequestInfo, &ResponseInfo, Duration)>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Calls `f` with every error returned by `self`, along with information
+    /// about the request that caused it.
+    ///
+    /// An error returned by a service makes hyper close the connection without
+    /// sending a response, so the client only sees a connection reset. This
+    /// adapter makes these errors visible. The error is still returned after
+    /// `f` has been called.
+    ///
+    /// [`AsyncService`] and [`SyncService`] turn hyperdrive [`Error`]s into
+    /// responses, so usually only other errors (eg. returned by a handler's
+    /// future) reach this adapter. When wrapping other services, [`Error`]s
+    /// are expected and aren't passed to `f` by default, which can be changed
+    /// with [`OnError::include_hyperdrive_errors`].
+    ///
+    /// The [`log_errors`] method logs errors using the `log` crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SyncService, ServiceExt}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .on_error(|error, request| {
+    ///         eprintln!("{} {} failed: {}", request.method(), request.path(), error);
+    ///     });
+    /// ```
+    ///
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    /// [`Error`]: ../struct.Error.html
+    /// [`OnError::include_hyperdrive_errors`]: struct.OnError.html#method.include_hyperdrive_errors
+    /// [`log_errors`]: #tymethod.log_errors
+    fn on_error<F>(self, f: F) -> OnError<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static;
+
+    /// Logs every error returned by `self` using the `log` crate.
+    ///
+    /// Errors are logged at the `error` level, with the target
+    /// `hyperdrive::error`. Like with [`log_default`], the query string isn't
+    /// logged. Refer to [`on_error`] for details.
+    ///
+    /// [`log_default`]: #tymethod.log_default
+    /// [`on_error`]: #tymethod.on_error
+    fn log_errors(self) -> OnError<Self, fn(&BoxedError, &RequestInfo)>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Reports metrics about every request processed by `self` to `recorder`.
+    ///
+    /// `recorder` is notified when a request starts and when it is finished,
+    /// which allows it to track the number of requests in flight. Once the
+    /// inner service has produced the response, it is also passed a
+    /// [`MetricsEvent`] with the request method, the route, the response status
+    /// and the latency (measured from the call to the inner service until the
+    /// response, but not its body, is ready). Like with [`log`], a failing
+    /// inner service is recorded as a `500 Internal Server Error`.
+    ///
+    /// Requests are labeled with their [`RouteName`] if the inner service
+    /// provides one (as [`AsyncService`] and [`SyncService`] do), and with the
+    /// request path otherwise. Note that paths can contain IDs, which results
+    /// in a large number of distinct labels.
+    ///
+    /// Any closure taking a `&MetricsEvent` can be used as the recorder.
+    /// [`AtomicMetrics`] is a recorder that aggregates counters and a latency
+    /// histogram, which can be rendered for Prometheus.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{AtomicMetrics, SyncService, ServiceExt}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    ///
+    ///     #[get("/metrics")]
+    ///     Metrics,
+    /// }
+    ///
+    /// let metrics = AtomicMetrics::new();
+    /// let recorder = metrics.clone();
+    ///
+    /// let service = SyncService::responding(move |route: Route, _| match route {
+    ///     Route::Index => "Hello World!".to_string(),
+    ///     // Render the metrics in the Prometheus text format
+    ///     Route::Metrics => metrics.snapshot().to_string(),
+    /// })
+    /// .metrics(recorder);
+    /// ```
+    ///
+    /// [`MetricsEvent`]: struct.MetricsEvent.html
+    /// [`log`]: #tymethod.log
+    /// [`RouteName`]: struct.RouteName.html
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    /// [`AtomicMetrics`]: struct.AtomicMetrics.html
+    fn metrics<M>(self, recorder: M) -> Metrics<Self, M>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        M: MetricsRecorder;
+
+    /// Records requests processed by `self` for replaying them later, eg. to
+    /// reproduce a bug in a test.
+    ///
+    /// Every request is first passed to [`RequestRecorder::sample`]. Only if
+    /// that returns `true`, the request head is captured and the request body
+    /// is copied into a buffer while the inner service reads it, up to a
+    /// limit of 64 KiB (see [`RecordRequests::max_body`]). Once the response
+    /// is produced, [`RequestRecorder::should_record`] decides whether the
+    /// [`RecordedRequest`] is passed to [`RequestRecorder::record`]. By
+    /// default, requests answered with a `5xx` status are recorded, including
+    /// those whose connection was dropped because the inner service failed.
+    ///
+    /// Note that the recorded headers may contain credentials and cookies,
+    /// and the body personal data.
+    ///
+    /// [`JsonLinesRecorder`] appends recorded requests to a file, and
+    /// [`RecordedRequest::into_request`] turns them back into requests that
+    /// can be decoded with [`FromRequest::from_request_bytes`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hyperdrive::{FromRequest, service::{JsonLinesRecorder, SyncService, ServiceExt}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let recorder = JsonLinesRecorder::open("failed-requests.jsonl").unwrap();
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!").record(recorder);
+    /// ```
+    ///
+    /// [`RequestRecorder::sample`]: trait.RequestRecorder.html#method.sample
+    /// [`RequestRecorder::should_record`]: trait.RequestRecorder.html#method.should_record
+    /// [`RequestRecorder::record`]: trait.RequestRecorder.html#tymethod.record
+    /// [`RecordRequests::max_body`]: struct.RecordRequests.html#method.max_body
+    /// [`RecordedRequest`]: struct.RecordedRequest.html
+    /// [`JsonLinesRecorder`]: struct.JsonLinesRecorder.html
+    /// [`RecordedRequest::into_request`]: struct.RecordedRequest.html#method.into_request
+    /// [`FromRequest::from_request_bytes`]: ../trait.FromRequest.html#method.from_request_bytes
+    fn record<R>(self, recorder: R) -> RecordRequests<Self, R>
+    where
+        Self: Service<ReqBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        R: RequestRecorder;
+
+    /// Wraps `self` in a [`Middleware`].
+    ///
+    /// Every request is passed to `middleware`, which can run code before and
+    /// after forwarding the request to `self`, or answer it on its own.
+    ///
+    /// Refer to the [`Middleware`] documentation for an example.
+    ///
+    /// [`Middleware`]: trait.Middleware.html
+    fn wrap<M>(self, middleware: M) -> Wrapped<Self, M>
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        M: Middleware;
+
+    /// Wraps `self` in a [`Middleware`] that only applies to some requests.
+    ///
+    /// `predicate` is called with every request before it is passed on. If it
+    /// returns `true`, the request is processed by `middleware` like with
+    /// [`wrap`]. Otherwise, it is passed directly to `self`.
+    ///
+    /// [`path_prefix`] and [`path_prefixes`] create predicates matching the
+    /// request path.
+    ///
+    /// Like other adapters, calls to `wrap_routes` nest from the outside in:
+    /// the middleware added last sees a request first.
+    ///
+    /// # Examples
+    ///
+    /// Only require an API version for the API routes:
+    ///
+    /// ```
+    /// # use hyperdrive::{DefaultFuture, BoxedError, service::{Middleware, Next}};
+    /// # use hyper::{Body, Request, Response};
+    /// # struct RequireApiVersion;
+    /// # impl Middleware for RequireApiVersion {
+    /// #     fn handle(&self, req: Request<Body>, next: Next<'_>) -> DefaultFuture<Response<Body>, BoxedError> {
+    /// #         next.run(req)
+    /// #     }
+    /// # }
+    /// use hyperdrive::{FromRequest, service::{path_prefix, ServiceExt, SyncService}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/api/users")]
+    ///     Users,
+    ///
+    ///     #[get("/healthz")]
+    ///     Health,
+    /// }
+    ///
+    /// let service = SyncService::responding(|route: Route, _| match route {
+    ///     Route::Users => "[]",
+    ///     Route::Health => "ok",
+    /// })
+    /// .wrap_routes(path_prefix("/api"), RequireApiVersion)
+    /// .make_service_by_cloning();
+    /// ```
+    ///
+    /// [`Middleware`]: trait.Middleware.html
+    /// [`wrap`]: #tymethod.wrap
+    /// [`path_prefix`]: fn.path_prefix.html
+    /// [`path_prefixes`]: fn.path_prefixes.html
+    fn wrap_routes<P, M>(self, predicate: P, middleware: M) -> WrappedRoutes<Self, P, M>
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+        M: Middleware;
+
+    /// Processes every request in a `tracing` span that records the request
+    /// and its outcome.
+    ///
+    /// The span is named `http_request` and records the following fields:
+    ///
+    /// * `http.method` and `http.target` (the path and query of the request),
+    ///   when the request is received.
+    /// * `trace_id`, which is taken from the request's W3C Trace Context (see
+    ///   [`TraceContext`]), so it can be correlated with the traces of other
+    ///   services. The [`TraceContext`] guard returns the same context that was
+    ///   recorded in the span, even if it had to be generated.
+    /// * `route`, `http.status_code` and `latency_ms`, once the response has
+    ///   been produced. The route is the [`RouteName`] provided by the inner
+    ///   service.
+    ///
+    /// If the inner service fails, an event is logged at error level instead.
+    /// For [`Error`]s, the event includes their [`ErrorKind`] and status.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    ///
+    /// [`TraceContext`]: ../guard/struct.TraceContext.html
+    /// [`RouteName`]: struct.RouteName.html
+    /// [`Error`]: ../struct.Error.html
+    /// [`ErrorKind`]: ../enum.ErrorKind.html
+    #[cfg(feature = "tracing")]
+    fn trace_requests(self) -> TraceRequests<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Enables the [`Session`] guard for requests handled by `self`, and
+    /// persists changes made to sessions.
+    ///
+    /// Once the inner service has produced a response, modified sessions are
+    /// written back to their [`SessionStore`] and destroyed sessions are
+    /// removed from it. The `Set-Cookie` header needed to create or delete the
+    /// session cookie is added to the response.
+    ///
+    /// If storing the session fails, the request fails with that error and no
+    /// response is sent.
+    ///
+    /// [`Session`]: ../guard/struct.Session.html
+    /// [`SessionStore`]: ../guard/trait.SessionStore.html
+    fn sessions(self) -> Sessions<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Limits the time `self` may take to produce a response.
+    ///
+    /// If the future returned by the inner service doesn't resolve within
+    /// `duration`, it is dropped and a `503 Service Unavailable` response is
+    /// sent instead. The status code can be changed with [`Timeout::status`].
+    /// Errors returned by the inner service before the deadline are passed
+    /// through unchanged.
+    ///
+    /// The response is created from an [`Error::timeout`], so its extensions
+    /// contain `ErrorKind::Timeout` and a [`TimeoutInfo`] with the time that
+    /// passed and `duration`.
+    ///
+    /// The deadline is enforced using `tokio-timer`, so the service has to be
+    /// run on a tokio runtime that provides a timer (as `tokio::run` does).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{AsyncService, ServiceExt}};
+    /// use hyper::{Body, Response, StatusCode};
+    /// use futures::IntoFuture;
+    /// use std::time::Duration;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = AsyncService::new(|_: Route, _| {
+    ///     Ok(Response::new(Body::from("Hello World!"))).into_future()
+    /// })
+    /// .timeout(Duration::from_secs(30))
+    /// .status(StatusCode::GATEWAY_TIMEOUT);
+    /// ```
+    ///
+    /// [`Timeout::status`]: struct.Timeout.html#method.status
+    /// [`Error::timeout`]: ../struct.Error.html#method.timeout
+    /// [`TimeoutInfo`]: ../struct.TimeoutInfo.html
+    fn timeout(self, duration: Duration) -> Timeout<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Tracks the requests processed by `self`, so that a server can be shut
+    /// down without interrupting them.
+    ///
+    /// Every request passed to the returned service is registered with
+    /// `shutdown` until its response has been produced. This allows
+    /// [`GracefulShutdown::wait_idle`] to wait until all in-flight requests
+    /// have completed, including those handled by a [`SyncService`] on the
+    /// blocking thread pool.
+    ///
+    /// Refer to [`GracefulShutdown`] for an example.
+    ///
+    /// [`GracefulShutdown`]: struct.GracefulShutdown.html
+    /// [`GracefulShutdown::wait_idle`]: struct.GracefulShutdown.html#method.wait_idle
+    /// [`SyncService`]: struct.SyncService.html
+    fn graceful_shutdown(self, shutdown: &GracefulShutdown) -> Drain<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Compresses the responses produced by `self`.
+    ///
+    /// The content coding is picked based on the `Accept-Encoding` header of
+    /// the request. `gzip` is always supported, and `br` (Brotli) is supported
+    /// when the `brotli` feature is enabled.
+    ///
+    /// The header is interpreted like the [`AcceptEncoding`] guard does.
+    ///
+    /// A response is only compressed if its `Content-Type` is considered
+    /// compressible by `config`, it doesn't already have a `Content-Encoding`,
+    /// and its body isn't known to be smaller than the configured minimum size.
+    /// If the client refuses unencoded responses (eg. with `identity;q=0`),
+    /// the content type and size are ignored. Responses to `HEAD` requests and
+    /// responses with status `204 No Content` or `304 Not Modified` are never
+    /// modified.
+    ///
+    /// The body is compressed while it is streamed to the client, so streaming
+    /// responses don't have to be buffered. Since the compressed size isn't
+    /// known in advance, the `Content-Length` header is removed. A `Vary:
+    /// Accept-Encoding` header is added to all compressible responses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{CompressionConfig, ServiceExt, SyncService}};
+    /// use hyperdrive::response::Json;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/numbers")]
+    ///     Numbers,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| Json((0..1000).collect::<Vec<u32>>()))
+    ///     .compress(CompressionConfig::new().min_size(256));
+    /// ```
+    ///
+    /// [`AcceptEncoding`]: ../guard/struct.AcceptEncoding.html
+    fn compress(self, config: CompressionConfig) -> Compress<Self>
+    where
+        Self: Service<ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Adds an `ETag` to the responses produced by `self`, and answers
+    /// conditional requests with `304 Not Modified`.
+    ///
+    /// The `ETag` is a strong entity tag computed from the SHA-256 hash of the
+    /// body. It is only added to `200 OK` responses to `GET` and `HEAD`
+    /// requests whose body is available in full (ie. isn't streamed) and
+    /// doesn't exceed the size configured via [`AutoEtag::max_size`].
+    /// Responses that already have an `ETag` are left alone.
+    ///
+    /// If one of the entity tags in the request's `If-None-Match` header matches
+    /// the computed `ETag`, the response is replaced with `304 Not Modified`,
+    /// which keeps the caching-related headers of the original response (eg.
+    /// `Cache-Control` and `Vary`), but no body.
+    ///
+    /// To compute the `ETag` of responses to `HEAD` requests, they are passed
+    /// to `self` as `GET` requests, and the body is dropped afterwards.
+    ///
+    /// When combined with [`compress`], call this method first, so that the
+    /// `ETag` is computed from the uncompressed body.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{ServiceExt, SyncService}};
+    /// use hyperdrive::response::Json;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/countries")]
+    ///     Countries,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| Json(vec!["de", "fr", "it"]))
+    ///     .auto_etag();
+    /// ```
+    ///
+    /// [`AutoEtag::max_size`]: struct.AutoEtag.html#method.max_size
+    /// [`compress`]: #tymethod.compress
+    fn auto_etag(self) -> AutoEtag<Self>
+    where
+        Self: Service<ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Adds security-related headers to every response produced by `self`.
+    ///
+    /// Refer to [`SecurityHeaders`] for the headers that are added. Headers
+    /// already present in a response are left alone, unless
+    /// [`SecurityHeaders::force`] is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{SecurityHeaders, ServiceExt, SyncService}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .security_headers(
+    ///         SecurityHeaders::default()
+    ///             .content_security_policy(Some("default-src 'self'"))
+    ///             .trust_forwarded_proto(true),
+    ///     );
+    /// ```
+    ///
+    /// [`SecurityHeaders`]: struct.SecurityHeaders.html
+    /// [`SecurityHeaders::force`]: struct.SecurityHeaders.html#method.force
+    fn security_headers(self, config: SecurityHeaders) -> WithSecurityHeaders<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static;
+
+    /// Limits the size of request bodies passed to `self` to `max_bytes`.
+    ///
+    /// Requests with a `Content-Length` above the limit are rejected with `413
+    /// Payload Too Large` without calling the inner service. Bodies without a
+    /// declared length (ie. using chunked encoding) are counted while they
+    /// are read, and reading fails with a `413 Payload Too Large` error once
+    /// they exceed the limit.
+    ///
+    /// This protects all routes, not just those with a `#[body]` field that
+    /// checks the size. When reading the body fails, [`AsyncService`] and
+    /// [`SyncService`] turn the error into a `413` response as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, body::Json, service::{ServiceExt, SyncService}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[post("/numbers")]
+    ///     Numbers {
+    ///         #[body]
+    ///         numbers: Json<Vec<u32>>,
+    ///     },
+    /// }
+    ///
+    /// let service = SyncService::responding(|Route::Numbers { numbers }, _| {
+    ///     numbers.iter().sum::<u32>().to_string()
+    /// })
+    /// .limit_body(64 * 1024);
+    /// ```
+    ///
+    /// [`AsyncService`]: struct.AsyncService.html
+    /// [`SyncService`]: struct.SyncService.html
+    fn limit_body(self, max_bytes: u64) -> LimitBody<Self>
+    where
+        Self: Service<ReqBody = Body, Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Limits the number of requests `self` processes concurrently.
+    ///
+    /// Once `max_in_flight` requests are being processed, further requests are
+    /// shed: they are immediately answered with `503 Service Unavailable` and
+    /// a `Retry-After` header (see [`ConcurrencyLimit::retry_after`]). Under
+    /// load, this fails some requests quickly instead of letting all of them
+    /// time out.
+    ///
+    /// Alternatively, requests can wait a bounded amount of time for another
+    /// request to complete by setting [`ConcurrencyLimit::max_wait`].
+    ///
+    /// A request counts towards the limit from the moment it is passed to the
+    /// inner service until its future completes or is dropped, so requests
+    /// that fail or panic are accounted for correctly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{ServiceExt, SyncService}};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .concurrency_limit(100)
+    ///     .max_wait(Duration::from_millis(50));
+    /// ```
+    ///
+    /// [`ConcurrencyLimit::retry_after`]: struct.ConcurrencyLimit.html#method.retry_after
+    /// [`ConcurrencyLimit::max_wait`]: struct.ConcurrencyLimit.html#method.max_wait
+    fn concurrency_limit(self, max_in_flight: usize) -> ConcurrencyLimit<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Redirects `GET` and `HEAD` requests to the canonical form of their path,
+    /// according to a trailing slash `policy`.
+    ///
+    /// Requests whose path doesn't follow the policy are answered with `308
+    /// Permanent Redirect` to the corrected path, preserving the query
+    /// string, without calling `self`. Requests using other methods are
+    /// passed through untouched, since redirecting them could break form
+    /// submissions.
+    ///
+    /// The root path `/` is never rewritten. Neither are paths whose canonical
+    /// form would start with `//`, since browsers would interpret the
+    /// `Location` as a URL on a different host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{ServiceExt, SyncService, TrailingSlash}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/blog/")]
+    ///     Blog,
+    /// }
+    ///
+    /// // Redirects `/blog` to `/blog/`
+    /// let service = SyncService::responding(|_: Route, _| "My Blog")
+    ///     .redirect_trailing_slash(TrailingSlash::Add);
+    /// ```
+    fn redirect_trailing_slash(self, policy: TrailingSlash) -> RedirectTrailingSlash<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Redirects requests that weren't made via HTTPS to the same URL with the
+    /// `https` scheme.
+    ///
+    /// How requests are determined to be secure, the host to redirect to and
+    /// the paths that are exempt from the redirect are configured by
+    /// [`HttpsRedirect`]. Requests to exempt paths, such as ACME HTTP-01
+    /// challenges, and secure requests are passed to `self`.
+    ///
+    /// `GET` and `HEAD` requests are answered with `301 Moved Permanently`.
+    /// Other requests are answered with `308 Permanent Redirect`, which tells
+    /// the client to repeat the request with the same method and body.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{HttpsRedirect, ServiceExt, SyncService}};
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// // Behind a reverse proxy that terminates TLS
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .redirect_to_https(HttpsRedirect::new().trust_forwarded_proto(true));
+    /// ```
+    ///
+    /// [`HttpsRedirect`]: struct.HttpsRedirect.html
+    fn redirect_to_https(self, config: HttpsRedirect) -> RedirectToHttps<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+
+    /// Erases the type of `self` by boxing it.
+    ///
+    /// Stacking several adapters results in a deeply nested type that is
+    /// usually impossible to name. [`BoxedService`] is a concrete type that
+    /// can be stored in a struct field or returned from a function instead. It
+    /// can be cloned, so it can also be passed to
+    /// [`make_service_by_cloning`].
+    ///
+    /// Boxing costs an additional allocation per request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, service::{BoxedService, ServiceExt, SyncService}};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// fn app() -> BoxedService {
+    ///     SyncService::responding(|_: Route, _| "Hello World!")
+    ///         .timeout(Duration::from_secs(30))
+    ///         .limit_body(64 * 1024)
+    ///         .boxed()
+    /// }
+    /// ```
+    ///
+    /// [`BoxedService`]: struct.BoxedService.html
+    /// [`make_service_by_cloning`]: #tymethod.make_service_by_cloning
+    fn boxed(self) -> BoxedService
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        Self::Future: Send + 'static;
+
+    /// Answers liveness and readiness checks before passing requests on to
+    /// `self`.
+    ///
+    /// `GET` and `HEAD` requests for the liveness path (`/healthz` by default)
+    /// are always answered with `200 OK`, as long as the server is able to
+    /// respond at all. Requests for the readiness path (`/readyz` by default)
+    /// are answered with `200 OK` or `503 Service Unavailable`, depending on
+    /// the readiness check configured in [`HealthConfig`]. This keeps these
+    /// endpoints out of the application's routes.
+    ///
+    /// The responses are plain text and must not be cached. They are not
+    /// logged by [`ServiceExt::log`] unless [`AccessLog::log_health_checks`]
+    /// is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{
+    ///     FromRequest,
+    ///     service::{GracefulShutdown, HealthConfig, ReadinessFlag, ServiceExt, SyncService},
+    /// };
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index,
+    /// }
+    ///
+    /// let ready = ReadinessFlag::new();
+    /// let shutdown = GracefulShutdown::new();
+    ///
+    /// let service = SyncService::responding(|_: Route, _| "Hello World!")
+    ///     .graceful_shutdown(&shutdown)
+    ///     .log_default()
+    ///     .with_health(
+    ///         HealthConfig::new()
+    ///             .readiness_flag(&ready)
+    ///             .graceful_shutdown(&shutdown),
+    ///     );
+    ///
+    /// // ...start the server, and once the app has warmed up:
+    /// ready.set_ready(true);
+    /// ```
+    ///
+    /// [`HealthConfig`]: struct.HealthConfig.html
+    /// [`ServiceExt::log`]: #tymethod.log
+    /// [`AccessLog::log_health_checks`]: struct.AccessLog.html#method.log_health_checks
+    fn with_health(self, config: HealthConfig) -> Health<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static;
+}
+
+impl<T: Service> ServiceExt for T {
+    fn catch_unwind<H, R>(self, handler: H) -> CatchUnwind<Self, R, H>
+    where
+        Self: Service<Error = BoxedError> + Sync,
+        Self::Future: Send,
+        H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = Response<Self::ResBody>, Error = BoxedError>,
+        R::Future: Send + 'static,
+    {
+        CatchUnwind {
+            inner: self,
+            handler: Arc::new(handler),
+        }
+    }
+
+    fn catch_unwind_with_request<H, R>(self, handler: H) -> CatchUnwindWithRequest<Self, R, H>
+    where
+        Self: Service<Error = BoxedError> + Sync,
+        Self::Future: Send,
+        H: Fn(Box<dyn Any + Send>, RequestInfo) -> R + Send + Sync + 'static,
+        R: IntoFuture<Item = Response<Self::ResBody>, Error = BoxedError>,
+        R::Future: Send + 'static,
+    {
+        CatchUnwindWithRequest {
+            inner: self,
+            handler: Arc::new(handler),
+        }
+    }
+
+    fn make_service_by_cloning(self) -> MakeServiceByCloning<Self>
+    where
+        Self: Clone,
+    {
+        MakeServiceByCloning { service: self }
+    }
+
+    fn with_connect_info(self) -> MakeWithConnectInfo<Self>
+    where
+        Self: Clone,
+    {
+        MakeWithConnectInfo { service: self }
+    }
+
+    fn map_request<F>(self, f: F) -> MapRequest<Self, F>
+    where
+        F: Fn(&mut Request<Self::ReqBody>) + Send + Sync + 'static,
+    {
+        MapRequest {
+            inner: self,
+            f: Arc::new(f),
+        }
+    }
+
+    fn try_map_request<F, R>(self, f: F) -> TryMapRequest<Self, F, R>
+    where
+        F: Fn(&mut Request<Self::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+        R: IntoResponse<Self::ResBody>,
+    {
+        TryMapRequest {
+            inner: self,
+            f: Arc::new(f),
+            _response: PhantomData,
+        }
+    }
+
+    fn map_response<F>(self, f: F) -> MapResponse<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(Response<Self::ResBody>) -> Response<Self::ResBody> + Send + Sync + 'static,
+    {
+        MapResponse {
+            inner: self,
+            f: Arc::new(f),
+        }
+    }
+
+    fn try_map_response<F>(self, f: F) -> TryMapResponse<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(Response<Self::ResBody>) -> Result<Response<Self::ResBody>, BoxedError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        TryMapResponse {
+            inner: self,
+            f: Arc::new(f),
+        }
+    }
+
+    fn log<L>(self, logger: L) -> AccessLog<Self, L>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+    {
+        AccessLog {
+            inner: self,
+            logger: Arc::new(logger),
+            log_health_checks: false,
+        }
+    }
+
+    fn log_default(self) -> AccessLog<Self, fn(&RequestInfo, &ResponseInfo, Duration)>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        self.log(log_access)
+    }
+
+    fn on_error<F>(self, f: F) -> OnError<Self, F>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+    {
+        OnError {
+            inner: self,
+            f: Arc::new(f),
+            include_hyperdrive_errors: false,
+        }
+    }
+
+    fn log_errors(self) -> OnError<Self, fn(&BoxedError, &RequestInfo)>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        self.on_error(log_error)
+    }
+
+    fn metrics<M>(self, recorder: M) -> Metrics<Self, M>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+        M: MetricsRecorder,
+    {
+        Metrics {
+            inner: self,
+            recorder: Arc::new(recorder),
+        }
+    }
+
+    fn record<R>(self, recorder: R) -> RecordRequests<Self, R>
+    where
+        Self: Service<ReqBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        R: RequestRecorder,
+    {
+        RecordRequests {
+            inner: self,
+            recorder: Arc::new(recorder),
+            max_body: 64 * 1024,
+        }
+    }
+
+    fn wrap<M>(self, middleware: M) -> Wrapped<Self, M>
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        M: Middleware,
+    {
+        Wrapped {
+            inner: self,
+            middleware: Arc::new(middleware),
+        }
+    }
+
+    fn wrap_routes<P, M>(self, predicate: P, middleware: M) -> WrappedRoutes<Self, P, M>
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+        P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+        M: Middleware,
+    {
+        WrappedRoutes {
+            inner: self,
+            predicate: Arc::new(predicate),
+            middleware: Arc::new(middleware),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_requests(self) -> TraceRequests<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        TraceRequests { inner: self }
+    }
+
+    fn sessions(self) -> Sessions<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        Sessions { inner: self }
+    }
+
+    fn timeout(self, duration: Duration) -> Timeout<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        Timeout {
+            inner: self,
+            duration,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn graceful_shutdown(self, shutdown: &GracefulShutdown) -> Drain<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        Drain {
+            inner: self,
+            state: shutdown.state.clone(),
+        }
+    }
+
+    fn compress(self, config: CompressionConfig) -> Compress<Self>
+    where
+        Self: Service<ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        Compress {
+            inner: self,
+            config: Arc::new(config),
+        }
+    }
+
+    fn auto_etag(self) -> AutoEtag<Self>
+    where
+        Self: Service<ResBody = Body, Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        AutoEtag {
+            inner: self,
+            max_size: 1024 * 1024,
+        }
+    }
+
+    fn security_headers(self, config: SecurityHeaders) -> WithSecurityHeaders<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::Future: Send + 'static,
+    {
+        WithSecurityHeaders {
+            inner: self,
+            config: Arc::new(config),
+        }
+    }
+
+    fn limit_body(self, max_bytes: u64) -> LimitBody<Self>
+    where
+        Self: Service<ReqBody = Body, Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        LimitBody {
+            inner: self,
+            max_bytes,
+        }
+    }
+
+    fn concurrency_limit(self, max_in_flight: usize) -> ConcurrencyLimit<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        ConcurrencyLimit {
+            inner: self,
+            limiter: Arc::new(Limiter {
+                max_in_flight,
+                state: Mutex::new(LimiterState::default()),
+            }),
+            retry_after: Duration::from_secs(1),
+            max_wait: Duration::from_secs(0),
+        }
+    }
+
+    fn redirect_trailing_slash(self, policy: TrailingSlash) -> RedirectTrailingSlash<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        RedirectTrailingSlash {
+            inner: self,
+            policy,
+        }
+    }
+
+    fn redirect_to_https(self, config: HttpsRedirect) -> RedirectToHttps<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        RedirectToHttps {
+            inner: self,
+            config: Arc::new(config),
+        }
+    }
+
+    fn boxed(self) -> BoxedService
+    where
+        Self: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        Self::Future: Send + 'static,
+    {
+        BoxedService {
+            inner: Box::new(self),
+        }
+    }
+
+    fn with_health(self, config: HealthConfig) -> Health<Self>
+    where
+        Self: Service<Error = BoxedError>,
+        Self::ResBody: ErrorBody,
+        Self::Future: Send + 'static,
+    {
+        Health {
+            inner: self,
+            config: Arc::new(config),
+        }
+    }
+}
+
+/// A `Service` adapter that catches unwinding panics.
+///
+/// Returned by [`ServiceExt::catch_unwind`].
+///
+/// [`ServiceExt::catch_unwind`]: trait.ServiceExt.html#tymethod.catch_unwind
+#[derive(Debug)]
+pub struct CatchUnwind<S, R, H>
+where
+    S: Service<Error = BoxedError> + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
+{
+    inner: S,
+    handler: Arc<H>,
+}
+
+impl<S, R, H> Service for CatchUnwind<S, R, H>
+where
+    S: Service<Error = BoxedError> + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = CatchUnwindFuture<S::Future, R, Arc<H>>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        CatchUnwindFuture::new(&mut self.inner, req, self.handler.clone())
+    }
+}
+
+impl<S, R, H> Clone for CatchUnwind<S, R, H>
+where
+    S: Service<Error = BoxedError> + Clone + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        CatchUnwind {
+            inner: self.inner.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// A `Service` adapter that catches unwinding panics and passes information
+/// about the request to the panic handler.
+///
+/// Returned by [`ServiceExt::catch_unwind_with_request`].
+///
+/// [`ServiceExt::catch_unwind_with_request`]: trait.ServiceExt.html#tymethod.catch_unwind_with_request
+#[derive(Debug)]
+pub struct CatchUnwindWithRequest<S, R, H>
+where
+    S: Service<Error = BoxedError> + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>, RequestInfo) -> R + Send + Sync + 'static,
+{
+    inner: S,
+    handler: Arc<H>,
+}
+
+impl<S, R, H> Service for CatchUnwindWithRequest<S, R, H>
+where
+    S: Service<Error = BoxedError> + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>, RequestInfo) -> R + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = CatchUnwindFuture<S::Future, R, (Arc<H>, RequestInfo)>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let info = RequestInfo::new(&req);
+        CatchUnwindFuture::new(&mut self.inner, req, (self.handler.clone(), info))
+    }
+}
+
+impl<S, R, H> Clone for CatchUnwindWithRequest<S, R, H>
+where
+    S: Service<Error = BoxedError> + Clone + Sync,
+    S::Future: Send + 'static,
+    R: IntoFuture<Item = Response<S::ResBody>, Error = BoxedError>,
+    R::Future: Send + 'static,
+    H: Fn(Box<dyn Any + Send>, RequestInfo) -> R + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        CatchUnwindWithRequest {
+            inner: self.inner.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// The `Future` returned by [`CatchUnwind`] and [`CatchUnwindWithRequest`].
+///
+/// Resolves to the response of the inner service, or to the response created
+/// by the panic handler `H` if the inner service panics.
+///
+/// [`CatchUnwind`]: struct.CatchUnwind.html
+/// [`CatchUnwindWithRequest`]: struct.CatchUnwindWithRequest.html
+#[must_use = "futures do nothing unless polled"]
+pub struct CatchUnwindFuture<F, R, H>
+where
+    R: IntoFuture,
+{
+    future: Either<F, R::Future>,
+    handler: Option<H>,
+}
+
+impl<F, R, H, B> CatchUnwindFuture<F, R, H>
+where
+    F: Future<Item = Response<B>, Error = BoxedError>,
+    R: IntoFuture<Item = Response<B>, Error = BoxedError>,
+    H: panic_handler::PanicHandler<R>,
+{
+    /// Calls `inner` with `req`, invoking `handler` if that panics.
+    fn new<S>(inner: &mut S, req: Request<S::ReqBody>, handler: H) -> Self
+    where
+        S: Service<ResBody = B, Error = BoxedError, Future = F>,
+    {
+        // We need to make sure that we don't just catch panics that happen while *polling* the
+        // inner service's `Future`, but also those that happen when the inner `Future`s are
+        // constructed, which basically means anything happening inside `inner.call(..)`.
+        Self::catch(move || inner.call(req), handler)
+    }
+
+    /// Calls `f` to create the inner future, invoking `handler` if that panics.
+    fn catch<C>(f: C, handler: H) -> Self
+    where
+        C: FnOnce() -> F,
+    {
+        match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(future) => Self {
+                future: Either::A(future),
+                handler: Some(handler),
+            },
+            Err(panic_payload) => Self {
+                future: Either::B(handler.handle(panic_payload).into_future()),
+                handler: None,
+            },
+        }
+    }
+}
+
+impl<F, R, H, B> Future for CatchUnwindFuture<F, R, H>
+where
+    F: Future<Item = Response<B>, Error = BoxedError>,
+    R: IntoFuture<Item = Response<B>, Error = BoxedError>,
+    H: panic_handler::PanicHandler<R>,
+{
+    type Item = Response<B>;
+    type Error = BoxedError;
+
+    fn poll(&mut self) -> Poll<Response<B>, BoxedError> {
+        let panic_payload = match self.future {
+            Either::A(ref mut future) => match catch_unwind(AssertUnwindSafe(|| future.poll())) {
+                Ok(poll) => return poll,
+                Err(panic_payload) => panic_payload,
+            },
+            Either::B(ref mut future) => return future.poll(),
+        };
+
+        let handler = self
+            .handler
+            .take()
+            .expect("panic handler was already called");
+        self.future = Either::B(handler.handle(panic_payload).into_future());
+        self.poll()
+    }
+}
+
+impl<F, R, H> fmt::Debug for CatchUnwindFuture<F, R, H>
+where
+    R: IntoFuture,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.future {
+            Either::A(_) => "Inner",
+            Either::B(_) => "Handler",
+        };
+        f.debug_struct("CatchUnwindFuture")
+            .field("state", &state)
+            .finish()
+    }
+}
+
+mod panic_handler {
+    use super::*;
+
+    /// A panic handler passed to a [`CatchUnwindFuture`], along with the
+    /// arguments it needs besides the panic payload.
+    ///
+    /// This is public so that it can appear in the bounds of
+    /// `CatchUnwindFuture`, but can't be named outside of this crate.
+    ///
+    /// [`CatchUnwindFuture`]: ../struct.CatchUnwindFuture.html
+    pub trait PanicHandler<R> {
+        fn handle(self, payload: Box<dyn Any + Send>) -> R;
+    }
+
+    impl<H, R> PanicHandler<R> for Arc<H>
+    where
+        H: Fn(Box<dyn Any + Send>) -> R,
+    {
+        fn handle(self, payload: Box<dyn Any + Send>) -> R {
+            self(payload)
+        }
+    }
+
+    impl<H, R> PanicHandler<R> for (Arc<H>, RequestInfo)
+    where
+        H: Fn(Box<dyn Any + Send>, RequestInfo) -> R,
+    {
+        fn handle(self, payload: Box<dyn Any + Send>) -> R {
+            let (handler, info) = self;
+            handler(payload, info)
+        }
+    }
+
+    /// The panic handler used by services with `recover_panics` enabled.
+    ///
+    /// Logs the panic message (if there is one) and responds with a plain
+    /// `500 Internal Server Error`.
+    pub struct RecoverPanic;
+
+    impl<B: ErrorBody> PanicHandler<Result<Response<B>, BoxedError>> for RecoverPanic {
+        fn handle(self, payload: Box<dyn Any + Send>) -> Result<Response<B>, BoxedError> {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => Some(*message),
+                None => payload.downcast_ref::<String>().map(String::as_str),
+            };
+            match message {
+                Some(message) => log::error!("panic while processing request: {}", message),
+                None => log::error!("panic while processing request"),
+            }
+
+            let response: Response<Body> =
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal server error").into_response();
+            Ok(response.map(B::from_body))
+        }
+    }
+}
+
+/// A `Service` adapter that modifies incoming requests.
+///
+/// Returned by [`ServiceExt::map_request`].
+///
+/// [`ServiceExt::map_request`]: trait.ServiceExt.html#tymethod.map_request
+pub struct MapRequest<S, F>
+where
+    S: Service,
+    F: Fn(&mut Request<S::ReqBody>) + Send + Sync + 'static,
+{
+    inner: S,
+    f: Arc<F>,
+}
+
+impl<S, F> Service for MapRequest<S, F>
+where
+    S: Service,
+    F: Fn(&mut Request<S::ReqBody>) + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        (self.f)(&mut req);
+        self.inner.call(req)
+    }
+}
+
+impl<S, F> Clone for MapRequest<S, F>
+where
+    S: Service + Clone,
+    F: Fn(&mut Request<S::ReqBody>) + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        MapRequest {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for MapRequest<S, F>
+where
+    S: Service + fmt::Debug,
+    F: Fn(&mut Request<S::ReqBody>) + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("MapRequest")
+            .field("inner", &self.inner)
+            .field("f", &"<closure>")
+            .finish()
+    }
+}
+
+/// A `Service` adapter that modifies or rejects incoming requests.
+///
+/// Returned by [`ServiceExt::try_map_request`].
+///
+/// [`ServiceExt::try_map_request`]: trait.ServiceExt.html#tymethod.try_map_request
+pub struct TryMapRequest<S, F, R>
+where
+    S: Service,
+    F: Fn(&mut Request<S::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+    R: IntoResponse<S::ResBody>,
+{
+    inner: S,
+    f: Arc<F>,
+    _response: PhantomData<fn() -> R>,
+}
+
+impl<S, F, R> Service for TryMapRequest<S, F, R>
+where
+    S: Service,
+    F: Fn(&mut Request<S::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+    R: IntoResponse<S::ResBody>,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Future = Either<FutureResult<Response<S::ResBody>, S::Error>, S::Future>;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        match (self.f)(&mut req) {
+            Ok(()) => Either::B(self.inner.call(req)),
+            Err(response) => Either::A(Ok(response.into_response()).into_future()),
+        }
+    }
+}
+
+impl<S, F, R> Clone for TryMapRequest<S, F, R>
+where
+    S: Service + Clone,
+    F: Fn(&mut Request<S::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+    R: IntoResponse<S::ResBody>,
+{
+    fn clone(&self) -> Self {
+        TryMapRequest {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+            _response: PhantomData,
+        }
+    }
+}
+
+impl<S, F, R> fmt::Debug for TryMapRequest<S, F, R>
+where
+    S: Service + fmt::Debug,
+    F: Fn(&mut Request<S::ReqBody>) -> Result<(), R> + Send + Sync + 'static,
+    R: IntoResponse<S::ResBody>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("TryMapRequest")
+            .field("inner", &self.inner)
+            .field("f", &"<closure>")
+            .finish()
+    }
+}
+
+/// A `Service` adapter that modifies outgoing responses.
+///
+/// Returned by [`ServiceExt::map_response`].
+///
+/// [`ServiceExt::map_response`]: trait.ServiceExt.html#tymethod.map_response
+pub struct MapResponse<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Response<S::ResBody> + Send + Sync + 'static,
+{
+    inner: S,
+    f: Arc<F>,
+}
+
+impl<S, F> Service for MapResponse<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Response<S::ResBody> + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let f = self.f.clone();
+        Box::new(self.inner.call(req).map(move |response| f(response)))
+    }
+}
+
+impl<S, F> Clone for MapResponse<S, F>
+where
+    S: Service<Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Response<S::ResBody> + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        MapResponse {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for MapResponse<S, F>
+where
+    S: Service<Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Response<S::ResBody> + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("MapResponse")
+            .field("inner", &self.inner)
+            .field("f", &"<closure>")
+            .finish()
+    }
+}
+
+/// A `Service` adapter that modifies outgoing responses using a fallible
+/// function.
+///
+/// Returned by [`ServiceExt::try_map_response`].
+///
+/// [`ServiceExt::try_map_response`]: trait.ServiceExt.html#tymethod.try_map_response
+pub struct TryMapResponse<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Result<Response<S::ResBody>, BoxedError> + Send + Sync + 'static,
+{
+    inner: S,
+    f: Arc<F>,
+}
+
+impl<S, F> Service for TryMapResponse<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Result<Response<S::ResBody>, BoxedError> + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let f = self.f.clone();
+        Box::new(self.inner.call(req).and_then(move |response| f(response)))
+    }
+}
+
+impl<S, F> Clone for TryMapResponse<S, F>
+where
+    S: Service<Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Result<Response<S::ResBody>, BoxedError> + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        TryMapResponse {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for TryMapResponse<S, F>
+where
+    S: Service<Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    F: Fn(Response<S::ResBody>) -> Result<Response<S::ResBody>, BoxedError> + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("TryMapResponse")
+            .field("inner", &self.inner)
+            .field("f", &"<closure>")
+            .finish()
+    }
+}
+
+/// Request processing logic that wraps a service.
+///
+/// Middleware is added to a service with [`ServiceExt::wrap`]. For every
+/// request, [`handle`] is called with the request and a [`Next`] value that
+/// allows forwarding the request to the wrapped service. Code placed before
+/// the call to [`Next::run`] can inspect or modify the request, and code
+/// chained onto the returned future can inspect or modify the response. The
+/// middleware can also respond on its own without calling [`Next::run`] at all.
+///
+/// # Examples
+///
+/// A middleware that rejects requests without an `X-Api-Version` header, and
+/// adds the header to all other responses:
+///
+/// ```
+/// use hyperdrive::{
+///     FromRequest, DefaultFuture, BoxedError,
+///     service::{Middleware, Next, ServiceExt, SyncService},
+/// };
+/// use hyper::{Body, Request, Response, StatusCode};
+/// use futures::{Future, IntoFuture};
+///
+/// struct RequireApiVersion;
+///
+/// impl Middleware for RequireApiVersion {
+///     fn handle(&self, req: Request<Body>, next: Next<'_>) -> DefaultFuture<Response<Body>, BoxedError> {
+///         let version = match req.headers().get("x-api-version") {
+///             Some(version) => version.clone(),
+///             None => {
+///                 // Short-circuit without calling the wrapped service
+///                 let response = Response::builder()
+///                     .status(StatusCode::BAD_REQUEST)
+///                     .body(Body::from("missing X-Api-Version header"))
+///                     .unwrap();
+///                 return Box::new(Ok(response).into_future());
+///             }
+///         };
+///
+///         Box::new(next.run(req).map(move |mut response| {
+///             response.headers_mut().insert("x-api-version", version);
+///             response
+///         }))
+///     }
+/// }
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let service = SyncService::responding(|_: Route, _| "Hello World!")
+///     .wrap(RequireApiVersion)
+///     .make_service_by_cloning();
+/// ```
+///
+/// [`ServiceExt::wrap`]: trait.ServiceExt.html#tymethod.wrap
+/// [`handle`]: #tymethod.handle
+/// [`Next`]: struct.Next.html
+/// [`Next::run`]: struct.Next.html#method.run
+pub trait Middleware: Send + Sync + 'static {
+    /// Processes a request, optionally forwarding it to the wrapped service
+    /// via `next`.
+    fn handle(
+        &self,
+        req: Request<Body>,
+        next: Next<'_>,
+    ) -> DefaultFuture<Response<Body>, BoxedError>;
+}
+
+/// The rest of the request processing chain, passed to [`Middleware::handle`].
+///
+/// [`Middleware::handle`]: trait.Middleware.html#tymethod.handle
+pub struct Next<'a> {
+    call: &'a mut dyn FnMut(Request<Body>) -> DefaultFuture<Response<Body>, BoxedError>,
+}
+
+impl<'a> Next<'a> {
+    /// Passes `req` to the wrapped service.
+    ///
+    /// Returns the future produced by the wrapped service.
+    pub fn run(self, req: Request<Body>) -> DefaultFuture<Response<Body>, BoxedError> {
+        (self.call)(req)
+    }
+}
+
+impl<'a> fmt::Debug for Next<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next").finish()
+    }
+}
+
+/// A `Service` adapter that passes requests through a [`Middleware`].
+///
+/// Returned by [`ServiceExt::wrap`].
+///
+/// [`Middleware`]: trait.Middleware.html
+/// [`ServiceExt::wrap`]: trait.ServiceExt.html#tymethod.wrap
+pub struct Wrapped<S, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    M: Middleware,
+{
+    inner: S,
+    middleware: Arc<M>,
+}
+
+impl<S, M> Service for Wrapped<S, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    M: Middleware,
+{
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let inner = &mut self.inner;
+        let mut call =
+            |req| -> DefaultFuture<Response<Body>, BoxedError> { Box::new(inner.call(req)) };
+        self.middleware.handle(req, Next { call: &mut call })
+    }
+}
+
+impl<S, M> Clone for Wrapped<S, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    M: Middleware,
+{
+    fn clone(&self) -> Self {
+        Wrapped {
+            inner: self.inner.clone(),
+            middleware: self.middleware.clone(),
+        }
+    }
+}
+
+impl<S, M> fmt::Debug for Wrapped<S, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    M: Middleware + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wrapped")
+            .field("inner", &self.inner)
+            .field("middleware", &self.middleware)
+            .finish()
+    }
+}
+
+/// A `Service` adapter that passes the requests matching a predicate through a
+/// [`Middleware`].
+///
+/// Returned by [`ServiceExt::wrap_routes`].
+///
+/// [`Middleware`]: trait.Middleware.html
+/// [`ServiceExt::wrap_routes`]: trait.ServiceExt.html#tymethod.wrap_routes
+pub struct WrappedRoutes<S, P, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+    M: Middleware,
+{
+    inner: S,
+    predicate: Arc<P>,
+    middleware: Arc<M>,
+}
+
+impl<S, P, M> Service for WrappedRoutes<S, P, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+    M: Middleware,
+{
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        // The predicate only gets to see the request head
+        let (parts, body) = req.into_parts();
+        let head = Request::from_parts(parts, ());
+        let matches = (self.predicate)(&head);
+        let req = Request::from_parts(head.into_parts().0, body);
+
+        if !matches {
+            return Box::new(self.inner.call(req));
+        }
+
+        let inner = &mut self.inner;
+        let mut call =
+            |req| -> DefaultFuture<Response<Body>, BoxedError> { Box::new(inner.call(req)) };
+        self.middleware.handle(req, Next { call: &mut call })
+    }
+}
+
+impl<S, P, M> Clone for WrappedRoutes<S, P, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+    M: Middleware,
+{
+    fn clone(&self) -> Self {
+        WrappedRoutes {
+            inner: self.inner.clone(),
+            predicate: self.predicate.clone(),
+            middleware: self.middleware.clone(),
+        }
+    }
+}
+
+impl<S, P, M> fmt::Debug for WrappedRoutes<S, P, M>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    P: Fn(&Request<()>) -> bool + Send + Sync + 'static,
+    M: Middleware + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("WrappedRoutes")
+            .field("inner", &self.inner)
+            .field("predicate", &"<closure>")
+            .field("middleware", &self.middleware)
+            .finish()
+    }
+}
+
+/// Returns a predicate for [`ServiceExt::wrap_routes`] that matches requests
+/// whose path starts with `prefix`.
+///
+/// Like the prefixes of a [`Router`], `prefix` only matches whole path
+/// segments: `/api` matches `/api` and `/api/users`, but not `/apiary`.
+///
+/// [`ServiceExt::wrap_routes`]: trait.ServiceExt.html#tymethod.wrap_routes
+/// [`Router`]: struct.Router.html
+pub fn path_prefix(prefix: &str) -> impl Fn(&Request<()>) -> bool + Clone + Send + Sync + 'static {
+    path_prefixes(&[prefix])
+}
+
+/// Returns a predicate for [`ServiceExt::wrap_routes`] that matches requests
+/// whose path starts with any of `prefixes`.
+///
+/// Prefixes are matched like in [`path_prefix`].
+///
+/// [`ServiceExt::wrap_routes`]: trait.ServiceExt.html#tymethod.wrap_routes
+/// [`path_prefix`]: fn.path_prefix.html
+pub fn path_prefixes(
+    prefixes: &[&str],
+) -> impl Fn(&Request<()>) -> bool + Clone + Send + Sync + 'static {
+    // A trailing slash would prevent matching the prefix itself
+    let prefixes = prefixes
+        .iter()
+        .map(|prefix| prefix.trim_end_matches('/').to_string())
+        .collect::<Vec<_>>();
+    move |req: &Request<()>| {
+        let path = req.uri().path();
+        prefixes.iter().any(|prefix| matches_prefix(path, prefix))
+    }
+}
+
+/// Returns whether `path` starts with the path segments in `prefix`.
+///
+/// `prefix` must not end with a `/`.
+fn matches_prefix(path: &str, prefix: &str) -> bool {
+    path.starts_with(prefix)
+        && (path.len() == prefix.len() || path[prefix.len()..].starts_with('/'))
+}
+
+/// A `Service` adapter that logs every request.
+///
+/// Returned by [`ServiceExt::log`] and [`ServiceExt::log_default`].
+///
+/// [`ServiceExt::log`]: trait.ServiceExt.html#tymethod.log
+/// [`ServiceExt::log_default`]: trait.ServiceExt.html#tymethod.log_default
+pub struct AccessLog<S, L>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+{
+    inner: S,
+    logger: Arc<L>,
+    log_health_checks: bool,
+}
+
+impl<S, L> AccessLog<S, L>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+{
+    /// Sets whether requests answered by [`ServiceExt::with_health`] are
+    /// logged.
+    ///
+    /// Health checks are usually sent every few seconds and would drown out
+    /// the other requests, so they are not logged by default.
+    ///
+    /// [`ServiceExt::with_health`]: trait.ServiceExt.html#tymethod.with_health
+    pub fn log_health_checks(mut self, log: bool) -> Self {
+        self.log_health_checks = log;
+        self
+    }
+}
+
+impl<S, L> Service for AccessLog<S, L>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let logger = self.logger.clone();
+        let log_health_checks = self.log_health_checks;
+        let request = RequestInfo::new(&req);
+        let start = Instant::now();
+
+        Box::new(self.inner.call(req).then(move |result| {
+            let response = match &result {
+                Ok(response) => {
+                    if !log_health_checks && response.extensions().get::<HealthCheck>().is_some() {
+                        return result;
+                    }
+                    ResponseInfo::new(response)
+                }
+                Err(_) => ResponseInfo::dropped(),
+            };
+            logger(&request, &response, start.elapsed());
+            result
+        }))
+    }
+}
+
+impl<S, L> Clone for AccessLog<S, L>
+where
+    S: Service<Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        AccessLog {
+            inner: self.inner.clone(),
+            logger: self.logger.clone(),
+            log_health_checks: self.log_health_checks,
+        }
+    }
+}
+
+impl<S, L> fmt::Debug for AccessLog<S, L>
+where
+    S: Service<Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    L: Fn(&RequestInfo, &ResponseInfo, Duration) + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("AccessLog")
+            .field("inner", &self.inner)
+            .field("logger", &"<closure>")
+            .field("log_health_checks", &self.log_health_checks)
+            .finish()
+    }
+}
+
+/// Information about a request, passed to the logger of [`ServiceExt::log`],
+/// the callback of [`ServiceExt::on_error`] and the panic handler of
+/// [`ServiceExt::catch_unwind_with_request`].
+///
+/// This is captured before the request is handed to the inner service, and
+/// only contains a few selected headers to keep it cheap.
+///
+/// [`ServiceExt::log`]: trait.ServiceExt.html#tymethod.log
+/// [`ServiceExt::on_error`]: trait.ServiceExt.html#tymethod.on_error
+/// [`ServiceExt::catch_unwind_with_request`]: trait.ServiceExt.html#tymethod.catch_unwind_with_request
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    method: Method,
+    uri: Uri,
+    host: Option<HeaderValue>,
+    user_agent: Option<HeaderValue>,
+}
+
+impl RequestInfo {
+    fn new<B>(request: &Request<B>) -> Self {
+        let headers = request.headers();
+        Self {
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            host: headers.get(header::HOST).cloned(),
+            user_agent: headers.get(header::USER_AGENT).cloned(),
+        }
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the full request URI, including the query string.
+    ///
+    /// Note that the query string might contain sensitive data, so care
+    /// should be taken before logging it.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Returns the path of the request URI, without the query string.
+    pub fn path(&self) -> &str {
+        self.uri.path()
+    }
+
+    /// Returns whether the request URI contains a query string.
+    pub fn has_query(&self) -> bool {
+        self.uri.query().is_some()
+    }
+
+    /// Returns the value of the `Host` header, if present.
+    pub fn host(&self) -> Option<&HeaderValue> {
+        self.host.as_ref()
+    }
+
+    /// Returns the value of the `User-Agent` header, if present.
+    pub fn user_agent(&self) -> Option<&HeaderValue> {
+        self.user_agent.as_ref()
+    }
+}
+
+/// Information about a response, passed to the logger of [`ServiceExt::log`].
+///
+/// [`ServiceExt::log`]: trait.ServiceExt.html#tymethod.log
+#[derive(Debug, Clone)]
+pub struct ResponseInfo {
+    status: StatusCode,
+    size_hint: Option<u64>,
+    dropped: bool,
+}
+
+impl ResponseInfo {
+    fn new<B: Payload>(response: &Response<B>) -> Self {
+        Self {
+            status: response.status(),
+            size_hint: response.body().content_length(),
+            dropped: false,
+        }
+    }
+
+    fn dropped() -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            size_hint: None,
+            dropped: true,
+        }
+    }
+
+    /// Returns the status code of the response.
+    ///
+    /// If the inner service failed and the connection was dropped, this is
+    /// `500 Internal Server Error`.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the size of the response body in bytes, if it is known up
+    /// front.
+    ///
+    /// Streamed bodies (eg. created with `Body::wrap_stream`) have no known
+    /// size.
+    pub fn size_hint(&self) -> Option<u64> {
+        self.size_hint
+    }
+
+    /// Returns whether the inner service failed, so that no response was sent
+    /// and the connection was dropped.
+    pub fn is_dropped(&self) -> bool {
+        self.dropped
+    }
+}
+
+/// The logger used by [`ServiceExt::log_default`].
+///
+/// [`ServiceExt::log_default`]: trait.ServiceExt.html#tymethod.log_default
+fn log_access(request: &RequestInfo, response: &ResponseInfo, elapsed: Duration) {
+    let query = if request.has_query() { "?…" } else { "" };
+    let status = if response.is_dropped() {
+        "dropped".to_string()
+    } else {
+        response.status().as_u16().to_string()
+    };
+    let size = match response.size_hint() {
+        Some(size) => size.to_string(),
+        None => "-".to_string(),
+    };
+
+    log::info!(
+        target: "hyperdrive::access",
+        "{} {}{} {} {} {:?}",
+        request.method(),
+        request.path(),
+        query,
+        status,
+        size,
+        elapsed,
+    );
+}
+
+/// A `Service` adapter that reports the errors returned by a service.
+///
+/// Returned by [`ServiceExt::on_error`] and [`ServiceExt::log_errors`].
+///
+/// [`ServiceExt::on_error`]: trait.ServiceExt.html#tymethod.on_error
+/// [`ServiceExt::log_errors`]: trait.ServiceExt.html#tymethod.log_errors
+pub struct OnError<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+{
+    inner: S,
+    f: Arc<F>,
+    include_hyperdrive_errors: bool,
+}
+
+impl<S, F> OnError<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+{
+    /// Sets whether hyperdrive [`Error`]s are reported as well.
+    ///
+    /// By default, they are not, since they describe invalid requests rather
+    /// than failures of the service.
+    ///
+    /// [`Error`]: ../struct.Error.html
+    pub fn include_hyperdrive_errors(mut self, include: bool) -> Self {
+        self.include_hyperdrive_errors = include;
+        self
+    }
+}
+
+impl<S, F> Service for OnError<S, F>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let f = self.f.clone();
+        let include_hyperdrive_errors = self.include_hyperdrive_errors;
+        let request = RequestInfo::new(&req);
+
+        Box::new(self.inner.call(req).map_err(move |error| {
+            if include_hyperdrive_errors || !error.is::<Error>() {
+                f(&error, &request);
+            }
+            error
+        }))
+    }
+}
+
+impl<S, F> Clone for OnError<S, F>
+where
+    S: Service<Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        OnError {
+            inner: self.inner.clone(),
+            f: self.f.clone(),
+            include_hyperdrive_errors: self.include_hyperdrive_errors,
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for OnError<S, F>
+where
+    S: Service<Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    F: Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Closures aren't debug-printable
+        f.debug_struct("OnError")
+            .field("inner", &self.inner)
+            .field("f", &"<closure>")
+            .field("include_hyperdrive_errors", &self.include_hyperdrive_errors)
+            .finish()
+    }
+}
+
+fn log_error(error: &BoxedError, request: &RequestInfo) {
+    let query = if request.has_query() { "?…" } else { "" };
+    log::error!(
+        target: "hyperdrive::error",
+        "{} {}{} failed: {}",
+        request.method(),
+        request.path(),
+        query,
+        error,
+    );
+}
+
+/// Receives requests recorded by [`ServiceExt::record`].
+///
+/// [`ServiceExt::record`]: trait.ServiceExt.html#tymethod.record
+pub trait RequestRecorder: Send + Sync + 'static {
+    /// Decides whether the body of `request` is buffered, before the request
+    /// is passed to the inner service.
+    ///
+    /// Requests that aren't sampled are never recorded and cause no overhead.
+    /// This can be used to only record a fraction of the requests. The
+    /// default implementation samples all requests.
+    fn sample(&self, request: &RequestInfo) -> bool {
+        let _ = request;
+        true
+    }
+
+    /// Decides whether a sampled request is passed to [`record`], once its
+    /// response has been produced.
+    ///
+    /// The default implementation records requests answered with a `5xx`
+    /// status.
+    ///
+    /// [`record`]: #tymethod.record
+    fn should_record(&self, request: &RequestInfo, response: &ResponseInfo) -> bool {
+        let _ = request;
+        response.status().is_server_error()
+    }
+
+    /// Stores a recorded request.
+    ///
+    /// This is called on the executor, so it shouldn't block for long.
+    fn record(&self, request: RecordedRequest);
+}
+
+/// A request captured by [`ServiceExt::record`].
+///
+/// [`ServiceExt::record`]: trait.ServiceExt.html#tymethod.record
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+    body_truncated: bool,
+    status: StatusCode,
+}
+
+impl RecordedRequest {
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the full request URI, including the query string.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Returns the request headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the part of the request body that was recorded.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Returns whether `body` is incomplete.
+    ///
+    /// This is the case if the body exceeded the size limit of the recording,
+    /// or if the inner service didn't read all of it.
+    pub fn is_body_truncated(&self) -> bool {
+        self.body_truncated
+    }
+
+    /// Returns the status of the response to the request.
+    ///
+    /// If the inner service failed and the connection was dropped, this is
+    /// `500 Internal Server Error`.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Converts the recording back into a request, for replaying it.
+    ///
+    /// The request can be decoded with [`FromRequest::from_request_bytes`] or
+    /// converted into a `Request<Body>` and passed to a service.
+    ///
+    /// [`FromRequest::from_request_bytes`]: ../trait.FromRequest.html#method.from_request_bytes
+    pub fn into_request(self) -> Request<Bytes> {
+        let mut request = Request::new(self.body);
+        *request.method_mut() = self.method;
+        *request.uri_mut() = self.uri;
+        *request.headers_mut() = self.headers;
+        request
+    }
+
+    /// Serializes the recording as a single line of JSON, without the line
+    /// break.
+    ///
+    /// Header values and bodies that aren't valid UTF-8 are stored as
+    /// hexadecimal digits.
+    pub fn to_json(&self) -> String {
+        let (body, body_hex) = match std::str::from_utf8(&self.body) {
+            Ok(body) => (Some(body.to_string()), None),
+            Err(_) => (None, Some(encode_hex(&self.body))),
+        };
+        let json = RecordingJson {
+            method: self.method.to_string(),
+            uri: self.uri.to_string(),
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| match value.to_str() {
+                    Ok(value) => (name.to_string(), value.to_string(), false),
+                    Err(_) => (name.to_string(), encode_hex(value.as_bytes()), true),
+                })
+                .collect(),
+            body,
+            body_hex,
+            body_truncated: self.body_truncated,
+            status: self.status.as_u16(),
+        };
+        serde_json::to_string(&json).expect("couldn't serialize recorded request")
+    }
+
+    /// Parses a recording serialized with [`to_json`].
+    ///
+    /// [`to_json`]: #method.to_json
+    pub fn from_json(json: &str) -> Result<Self, BoxedError> {
+        let json = serde_json::from_str::<RecordingJson>(json)?;
+        let mut headers = HeaderMap::new();
+        for (name, value, hex) in json.headers {
+            let value = if hex {
+                decode_hex(&value).ok_or("invalid hex-encoded header value")?
+            } else {
+                value.into_bytes()
+            };
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_bytes(&value)?,
+            );
+        }
+        let body = match (json.body, json.body_hex) {
+            (Some(body), None) => Bytes::from(body),
+            (None, Some(hex)) => Bytes::from(decode_hex(&hex).ok_or("invalid hex-encoded body")?),
+            _ => return Err("recording must contain either `body` or `body_hex`".into()),
+        };
+
+        Ok(Self {
+            method: json.method.parse()?,
+            uri: json.uri.parse()?,
+            headers,
+            body,
+            body_truncated: json.body_truncated,
+            status: StatusCode::from_u16(json.status)?,
+        })
+    }
+}
+
+/// The JSON representation of a `RecordedRequest`.
+#[derive(Serialize, Deserialize)]
+struct RecordingJson {
+    method: String,
+    uri: String,
+    /// `(name, value, hex)`, where `hex` is `true` if `value` is hex-encoded.
+    headers: Vec<(String, String, bool)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_hex: Option<String>,
+    body_truncated: bool,
+    status: u16,
+}
+
+/// A `Service` adapter that records requests for replaying them.
+///
+/// Returned by [`ServiceExt::record`].
+///
+/// [`ServiceExt::record`]: trait.ServiceExt.html#tymethod.record
+pub struct RecordRequests<S, R>
+where
+    S: Service<ReqBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    R: RequestRecorder,
+{
+    inner: S,
+    recorder: Arc<R>,
+    max_body: usize,
+}
+
+impl<S, R> RecordRequests<S, R>
+where
+    S: Service<ReqBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    R: RequestRecorder,
+{
+    /// Sets the maximum number of body bytes recorded per request.
+    ///
+    /// The rest of the body is still passed to the inner service, but the
+    /// recording is marked as truncated. The default is 64 KiB.
+    pub fn max_body(mut self, bytes: usize) -> Self {
+        self.max_body = bytes;
+        self
+    }
+}
+
+impl<S, R> Service for RecordRequests<S, R>
+where
+    S: Service<ReqBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+    R: RequestRecorder,
+{
+    type ReqBody = Body;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let info = RequestInfo::new(&req);
+        if !self.recorder.sample(&info) {
+            return Box::new(self.inner.call(req));
+        }
+
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let headers = req.headers().clone();
+        let tee = Arc::new(Mutex::new(BodyTee {
+            buffer: Vec::new(),
+            truncated: false,
+            complete: false,
+        }));
+
+        // Like in `LimitBody`, bodies known to be empty are passed through so
+        // that `Body::on_upgrade` keeps working
+        let req = if req.body().is_end_stream() {
+            tee.lock().unwrap().complete = true;
+            req
+        } else {
+            let max_body = self.max_body;
+            let tee = tee.clone();
+            req.map(|body| {
+                Body::wrap_stream(TeeBody {
+                    body,
+                    tee,
+                    max_body,
+                })
+            })
+        };
+
+        let recorder = self.recorder.clone();
+        Box::new(self.inner.call(req).then(move |result| {
+            let response = match &result {
+                Ok(response) => ResponseInfo::new(response),
+                Err(_) => ResponseInfo::dropped(),
+            };
+            if recorder.should_record(&info, &response) {
+                let tee = tee.lock().unwrap();
+                recorder.record(RecordedRequest {
+                    method,
+                    uri,
+                    headers,
+                    body: Bytes::from(&tee.buffer[..]),
+                    body_truncated: tee.truncated || !tee.complete,
+                    status: response.status(),
+                });
+            }
+            result
+        }))
+    }
+}
+
+impl<S, R> Clone for RecordRequests<S, R>
+where
+    S: Service<ReqBody = Body, Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    R: RequestRecorder,
+{
+    fn clone(&self) -> Self {
+        RecordRequests {
+            inner: self.inner.clone(),
+            recorder: self.recorder.clone(),
+            max_body: self.max_body,
+        }
+    }
+}
+
+impl<S, R> fmt::Debug for RecordRequests<S, R>
+where
+    S: Service<ReqBody = Body, Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    R: RequestRecorder,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordRequests")
+            .field("inner", &self.inner)
+            .field("recorder", &"<recorder>")
+            .field("max_body", &self.max_body)
+            .finish()
+    }
+}
+
+/// The part of a request body copied by a `TeeBody`.
+struct BodyTee {
+    buffer: Vec<u8>,
+    /// Whether the body exceeded the limit.
+    truncated: bool,
+    /// Whether the whole body was read.
+    complete: bool,
+}
+
+/// A request body that copies up to `max_body` bytes into `tee` while it is
+/// read.
+struct TeeBody {
+    body: Body,
+    tee: Arc<Mutex<BodyTee>>,
+    max_body: usize,
+}
+
+impl Stream for TeeBody {
+    type Item = Chunk;
+    type Error = hyper::Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, hyper::Error> {
+        let chunk = try_ready!(self.body.poll());
+        let mut tee = self.tee.lock().unwrap();
+        match &chunk {
+            Some(chunk) => {
+                let remaining = self.max_body - tee.buffer.len();
+                if chunk.len() > remaining {
+                    tee.truncated = true;
+                }
+                let len = chunk.len().min(remaining);
+                tee.buffer.extend_from_slice(&chunk[..len]);
+            }
+            None => tee.complete = true,
+        }
+        Ok(Async::Ready(chunk))
+    }
+}
+
+/// A [`RequestRecorder`] that appends requests answered with a `5xx` status
+/// to a file, as one line of JSON each.
+///
+/// The lines are created with [`RecordedRequest::to_json`]. [`read`] parses
+/// such a file for replaying the requests.
+///
+/// # Examples
+///
+/// Replaying recorded requests in a test:
+///
+/// ```no_run
+/// use hyperdrive::{FromRequest, NoContext, service::JsonLinesRecorder};
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// for recording in JsonLinesRecorder::read("failed-requests.jsonl").unwrap() {
+///     let route = Route::from_request_bytes(recording.into_request(), NoContext).unwrap();
+///     // ...call the handler with `route`
+/// }
+/// ```
+///
+/// [`RequestRecorder`]: trait.RequestRecorder.html
+/// [`RecordedRequest::to_json`]: struct.RecordedRequest.html#method.to_json
+/// [`read`]: #method.read
+#[derive(Debug)]
+pub struct JsonLinesRecorder {
+    file: Mutex<File>,
+}
+
+impl JsonLinesRecorder {
+    /// Opens the file at `path` for appending recorded requests, creating it
+    /// if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Reads all requests recorded in the file at `path`.
+    ///
+    /// Empty lines are skipped. Fails with `io::ErrorKind::InvalidData` if a
+    /// line isn't a valid recording.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<RecordedRequest>> {
+        let file = BufReader::new(File::open(path)?);
+        let mut recordings = Vec::new();
+        for line in file.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording = RecordedRequest::from_json(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            recordings.push(recording);
+        }
+        Ok(recordings)
+    }
+}
+
+impl RequestRecorder for JsonLinesRecorder {
+    fn record(&self, request: RecordedRequest) {
+        let mut line = request.to_json();
+        line.push('\n');
+        // Write the whole line at once, so that concurrent writers to the same
+        // file don't interleave
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            log::error!(
+                target: "hyperdrive::error",
+                "couldn't record {} {}: {}",
+                request.method(),
+                request.uri().path(),
+                e,
+            );
+        }
+    }
+}
+
+/// Receives metrics from [`ServiceExt::metrics`].
+///
+/// This is implemented for all closures taking a [`MetricsEvent`], which only
+/// receive finished requests, and for [`AtomicMetrics`].
+///
+/// [`ServiceExt::metrics`]: trait.ServiceExt.html#tymethod.metrics
+/// [`MetricsEvent`]: struct.MetricsEvent.html
+/// [`AtomicMetrics`]: struct.AtomicMetrics.html
+pub trait MetricsRecorder: Send + Sync + 'static {
+    /// Called when a request is passed to the inner service.
+    ///
+    /// This can be used to increment a gauge of requests in flight.
+    fn request_started(&self) {}
+
+    /// Called once the inner service has finished processing a request that
+    /// was started, or the request was cancelled.
+    ///
+    /// This can be used to decrement a gauge of requests in flight.
+    fn request_finished(&self) {}
+
+    /// Records a request whose response was produced.
+    fn record(&self, event: &MetricsEvent);
+}
+
+impl<F> MetricsRecorder for F
+where
+    F: Fn(&MetricsEvent) + Send + Sync + 'static,
+{
+    fn record(&self, event: &MetricsEvent) {
+        self(event)
+    }
+}
+
+/// Describes a processed request, passed to a [`MetricsRecorder`].
+///
+/// [`MetricsRecorder`]: trait.MetricsRecorder.html
+#[derive(Debug, Clone)]
+pub struct MetricsEvent {
+    method: Method,
+    route: Cow<'static, str>,
+    status: StatusCode,
+    error_kind: Option<ErrorKind>,
+    latency: Duration,
+}
+
+impl MetricsEvent {
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the route label of the request.
+    ///
+    /// This is the [`RouteName`] provided by the inner service, or the path of
+    /// the request if there is none.
+    ///
+    /// [`RouteName`]: struct.RouteName.html
+    pub fn route(&self) -> &str {
+        &self.route
+    }
+
+    /// Returns the status code of the response.
+    ///
+    /// If the inner service failed, this is `500 Internal Server Error`.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the kind of the [`hyperdrive::Error`] that caused the response.
+    ///
+    /// This is known when the inner service failed with a `hyperdrive::Error`
+    /// or the response was created for one (as described in
+    /// [`Error::response`]), and `None` otherwise.
+    ///
+    /// [`hyperdrive::Error`]: ../struct.Error.html
+    /// [`Error::response`]: ../struct.Error.html#method.response
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        self.error_kind
+    }
+
+    /// Returns whether the request timed out.
+    ///
+    /// This is the case for errors of kind `ErrorKind::Timeout`, such as the
+    /// responses sent by [`ServiceExt::timeout`], and
+    /// `ErrorKind::BodyReadTimeout`.
+    ///
+    /// [`ServiceExt::timeout`]: trait.ServiceExt.html#tymethod.timeout
+    pub fn is_timeout(&self) -> bool {
+        self.error_kind == Some(ErrorKind::Timeout)
+            || self.error_kind == Some(ErrorKind::BodyReadTimeout)
+    }
+
+    /// Returns the time it took the inner service to produce the response.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+}
+
+/// A `Service` adapter that reports metrics about every request.
+///
+/// Returned by [`ServiceExt::metrics`].
+///
+/// [`ServiceExt::metrics`]: trait.ServiceExt.html#tymethod.metrics
+pub struct Metrics<S, M>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    M: MetricsRecorder,
+{
+    inner: S,
+    recorder: Arc<M>,
+}
+
+impl<S, M> Service for Metrics<S, M>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+    M: MetricsRecorder,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let recorder = self.recorder.clone();
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let start = Instant::now();
+
+        recorder.request_started();
+        let in_flight = RequestInFlight(recorder.clone());
+
+        Box::new(self.inner.call(req).then(move |result| {
+            let latency = start.elapsed();
+            drop(in_flight);
+
+            let (route, status, error_kind) = match &result {
+                Ok(response) => (
+                    response.extensions().get::<RouteName>().map(|name| name.0),
+                    response.status(),
+                    response.extensions().get::<ErrorKind>().cloned(),
+                ),
+                Err(error) => (
+                    None,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    as_hyperdrive_error(error).map(Error::kind),
+                ),
+            };
+            let route = match route {
+                Some(name) => Cow::Borrowed(name),
+                None => Cow::Owned(uri.path().to_string()),
+            };
+            recorder.record(&MetricsEvent {
+                method,
+                route,
+                status,
+                error_kind,
+                latency,
+            });
+            result
+        }))
+    }
+}
+
+impl<S, M> Clone for Metrics<S, M>
+where
+    S: Service<Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+    M: MetricsRecorder,
+{
+    fn clone(&self) -> Self {
+        Metrics {
+            inner: self.inner.clone(),
+            recorder: self.recorder.clone(),
+        }
+    }
+}
+
+impl<S, M> fmt::Debug for Metrics<S, M>
+where
+    S: Service<Error = BoxedError> + fmt::Debug,
+    S::Future: Send + 'static,
+    M: MetricsRecorder,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Recorders might be closures, which aren't debug-printable
+        f.debug_struct("Metrics")
+            .field("inner", &self.inner)
+            .field("recorder", &"<recorder>")
+            .finish()
+    }
+}
+
+/// Calls `MetricsRecorder::request_finished` when dropped.
+struct RequestInFlight<M: MetricsRecorder>(Arc<M>);
+
+impl<M: MetricsRecorder> Drop for RequestInFlight<M> {
+    fn drop(&mut self) {
+        self.0.request_finished();
+    }
+}
+
+/// Upper bounds of the latency histogram buckets of [`AtomicMetrics`], in
+/// milliseconds.
+///
+/// These match the default buckets of the Prometheus client libraries.
+///
+/// [`AtomicMetrics`]: struct.AtomicMetrics.html
+const LATENCY_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// A [`MetricsRecorder`] that aggregates request counts and latencies.
+///
+/// `AtomicMetrics` is a cheap handle to shared state: clones record into and
+/// report the same metrics. Use [`snapshot`] to read the current values, eg. to
+/// serve them from a `/metrics` endpoint.
+///
+/// Refer to [`ServiceExt::metrics`] for an example.
+///
+/// [`MetricsRecorder`]: trait.MetricsRecorder.html
+/// [`snapshot`]: #method.snapshot
+/// [`ServiceExt::metrics`]: trait.ServiceExt.html#tymethod.metrics
+#[derive(Debug, Clone, Default)]
+pub struct AtomicMetrics {
+    state: Arc<MetricsState>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    in_flight: AtomicUsize,
+    /// Request counts by method, route and status.
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// Latency counts per bucket, with the last bucket counting latencies
+    /// above the largest bound.
+    latency_buckets: [AtomicU64; 12],
+    latency_sum_micros: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+impl AtomicMetrics {
+    /// Creates a new `AtomicMetrics` with all counters set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current values of all metrics.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let state = &self.state;
+        let mut cumulative = 0;
+        let mut latency_buckets = Vec::with_capacity(LATENCY_BUCKETS_MS.len());
+        for (bound, count) in LATENCY_BUCKETS_MS.iter().zip(&state.latency_buckets) {
+            cumulative += count.load(Ordering::SeqCst);
+            latency_buckets.push((Duration::from_millis(*bound), cumulative));
+        }
+        let latency_count =
+            cumulative + state.latency_buckets[LATENCY_BUCKETS_MS.len()].load(Ordering::SeqCst);
+
+        MetricsSnapshot {
+            in_flight: state.in_flight.load(Ordering::SeqCst),
+            requests: state.requests.lock().unwrap().clone(),
+            latency_buckets,
+            latency_count,
+            latency_sum: Duration::from_micros(state.latency_sum_micros.load(Ordering::SeqCst)),
+            timeouts: state.timeouts.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl MetricsRecorder for AtomicMetrics {
+    fn request_started(&self) {
+        self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn request_finished(&self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn record(&self, event: &MetricsEvent) {
+        let key = (
+            event.method().to_string(),
+            event.route().to_string(),
+            event.status().as_u16(),
+        );
+        *self.state.requests.lock().unwrap().entry(key).or_insert(0) += 1;
+
+        let latency_ms = event.latency().as_millis();
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= u128::from(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.state.latency_buckets[bucket].fetch_add(1, Ordering::SeqCst);
+        self.state
+            .latency_sum_micros
+            .fetch_add(event.latency().as_micros() as u64, Ordering::SeqCst);
+        if event.is_timeout() {
+            self.state.timeouts.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// The values of [`AtomicMetrics`] at some point in time.
+///
+/// The `Display` implementation renders the metrics in the Prometheus text
+/// exposition format, using the metric names `http_requests_in_flight`,
+/// `http_requests_total` (labeled with `method`, `route` and `status`),
+/// `http_request_duration_seconds` and `http_request_timeouts_total`.
+///
+/// [`AtomicMetrics`]: struct.AtomicMetrics.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    in_flight: usize,
+    requests: BTreeMap<(String, String, u16), u64>,
+    latency_buckets: Vec<(Duration, u64)>,
+    latency_count: u64,
+    latency_sum: Duration,
+    timeouts: u64,
+}
+
+impl MetricsSnapshot {
+    /// Returns the number of requests currently being processed.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Returns the total number of recorded requests.
+    pub fn requests(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Returns the number of recorded requests with the given route label.
+    pub fn route_requests(&self, route: &str) -> u64 {
+        self.requests
+            .iter()
+            .filter(|((_, r, _), _)| r == route)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the number of recorded responses whose status belongs to the
+    /// given class, eg. `4` for `4xx` responses.
+    pub fn status_class(&self, class: u16) -> u64 {
+        self.requests
+            .iter()
+            .filter(|((_, _, status), _)| status / 100 == class)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the cumulative latency histogram.
+    ///
+    /// Every entry contains the upper bound of a bucket and the number of
+    /// requests that took at most that long. Requests taking longer than the
+    /// largest bound are only included in [`latency_count`].
+    ///
+    /// [`latency_count`]: #method.latency_count
+    pub fn latency_buckets(&self) -> &[(Duration, u64)] {
+        &self.latency_buckets
+    }
+
+    /// Returns the number of requests included in the latency histogram.
+    pub fn latency_count(&self) -> u64 {
+        self.latency_count
+    }
+
+    /// Returns the number of recorded requests that timed out.
+    ///
+    /// These are the requests for which [`MetricsEvent::is_timeout`] returns
+    /// `true`. They are counted in the status classes as well.
+    ///
+    /// [`MetricsEvent::is_timeout`]: struct.MetricsEvent.html#method.is_timeout
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+
+    /// Returns the sum of the latencies of all requests.
+    pub fn latency_sum(&self) -> Duration {
+        self.latency_sum
+    }
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# HELP http_requests_in_flight Number of requests currently being processed."
+        )?;
+        writeln!(f, "# TYPE http_requests_in_flight gauge")?;
+        writeln!(f, "http_requests_in_flight {}", self.in_flight)?;
+
+        writeln!(
+            f,
+            "# HELP http_requests_total Number of processed requests."
+        )?;
+        writeln!(f, "# TYPE http_requests_total counter")?;
+        for ((method, route, status), count) in &self.requests {
+            writeln!(
+                f,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape_label(method),
+                escape_label(route),
+                status,
+                count
+            )?;
+        }
+
+        writeln!(
+            f,
+            "# HELP http_request_duration_seconds Time until the response was produced."
+        )?;
+        writeln!(f, "# TYPE http_request_duration_seconds histogram")?;
+        for (bound, count) in &self.latency_buckets {
+            writeln!(
+                f,
+                "http_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound.as_secs_f64(),
+                count
+            )?;
+        }
+        writeln!(
+            f,
+            "http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.latency_count
+        )?;
+        writeln!(
+            f,
+            "http_request_duration_seconds_sum {}",
+            self.latency_sum.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "http_request_duration_seconds_count {}",
+            self.latency_count
+        )?;
+
+        writeln!(
+            f,
+            "# HELP http_request_timeouts_total Number of requests that timed out."
+        )?;
+        writeln!(f, "# TYPE http_request_timeouts_total counter")?;
+        writeln!(f, "http_request_timeouts_total {}", self.timeouts)
+    }
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A `Service` adapter that records every request and its outcome in a
+/// `tracing` span.
+///
+/// Returned by [`ServiceExt::trace_requests`]. This type is only available when
+/// the `tracing` feature is enabled.
+///
+/// [`ServiceExt::trace_requests`]: trait.ServiceExt.html#tymethod.trace_requests
+#[cfg(feature = "tracing")]
+#[derive(Debug, Copy, Clone)]
+pub struct TraceRequests<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    inner: S,
+}
+
+#[cfg(feature = "tracing")]
+impl<S> Service for TraceRequests<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        use tracing::field::Empty;
+
+        let trace = TraceContext::from_headers(req.headers());
+        let span = tracing::info_span!(
+            "http_request",
+            http.method = %req.method(),
+            http.target = %req.uri().path_and_query().map_or("/", |target| target.as_str()),
+            trace_id = %trace.trace_id(),
+            route = Empty,
+            http.status_code = Empty,
+            latency_ms = Empty,
+        );
+        req.extensions_mut().insert(trace);
+
+        let start = Instant::now();
+        let inner = {
+            let _enter = span.enter();
+            self.inner.call(req)
+        };
+        let record_span = span.clone();
+        let inner = inner.then(move |result| {
+            let elapsed = start.elapsed();
+            record_span.record(
+                "latency_ms",
+                elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis()),
+            );
+            match &result {
+                Ok(response) => {
+                    if let Some(RouteName(route)) = response.extensions().get() {
+                        record_span.record("route", route);
+                    }
+                    record_span.record("http.status_code", response.status().as_u16());
+                }
+                Err(error) => match as_hyperdrive_error(error) {
+                    Some(our_error) => {
+                        let status = our_error.http_status().as_u16();
+                        record_span.record("http.status_code", status);
+                        tracing::error!(
+                            error = %error,
+                            kind = ?our_error.kind(),
+                            status,
+                            "request failed",
+                        );
+                    }
+                    None => tracing::error!(error = %error, "request failed"),
+                },
+            }
+            result
+        });
+        Box::new(Instrumented { inner, span })
+    }
+}
+
+/// Enters `span` whenever `inner` is polled.
+#[cfg(feature = "tracing")]
+struct Instrumented<F> {
+    inner: F,
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+impl<F: Future> Future for Instrumented<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> futures::Poll<F::Item, F::Error> {
+        let _enter = self.span.enter();
+        self.inner.poll()
+    }
+}
+
+/// A `Service` adapter that persists [`Session`]s.
+///
+/// Returned by [`ServiceExt::sessions`].
+///
+/// [`Session`]: ../guard/struct.Session.html
+/// [`ServiceExt::sessions`]: trait.ServiceExt.html#tymethod.sessions
+#[derive(Debug, Copy, Clone)]
+pub struct Sessions<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    inner: S,
+}
+
+impl<S> Service for Sessions<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        // The `Session` guard picks this up from the request extensions and
+        // records the session it loaded.
+        let slot = SessionSlot::default();
+        req.extensions_mut().insert(slot.clone());
+
+        Box::new(
+            self.inner
+                .call(req)
+                .and_then(move |response| slot.finish(response)),
+        )
+    }
+}
+
+/// A `Service` adapter that responds with an error if the inner service takes
+/// too long.
+///
+/// Returned by [`ServiceExt::timeout`].
+///
+/// [`ServiceExt::timeout`]: trait.ServiceExt.html#tymethod.timeout
+#[derive(Debug, Copy, Clone)]
+pub struct Timeout<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    duration: Duration,
+    status: StatusCode,
+}
+
+impl<S> Timeout<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    /// Sets the status code of the response sent when the deadline expires.
+    ///
+    /// By default, this is `503 Service Unavailable`. When `self` is a gateway
+    /// or proxy, `504 Gateway Timeout` may be more appropriate.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl<S> Service for Timeout<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let status = self.status;
+        let duration = self.duration;
+        let start = Instant::now();
+        let inner = self.inner.call(req);
+
+        Box::new(
+            tokio_timer::Timeout::new(inner, duration).or_else(move |error| {
+                if error.is_elapsed() {
+                    // The inner future has been dropped at this point
+                    Ok(
+                        Error::timeout_with_status(status, start.elapsed(), duration)
+                            .response()
+                            .map(|()| ErrorBody::empty()),
+                    )
+                } else if error.is_inner() {
+                    Err(error.into_inner().unwrap())
+                } else {
+                    Err(error.into_timer().unwrap().into())
+                }
+            }),
+        )
+    }
+}
+
+/// A handle for shutting down a server without interrupting the requests it
+/// is processing.
+///
+/// Hyper's `Server::with_graceful_shutdown` only stops accepting new
+/// connections once its signal future resolves, and then waits for the
+/// existing connections to close. Requests that are still being processed
+/// (eg. by a [`SyncService`] handler on the blocking thread pool) aren't
+/// tracked by hyper, so the runtime might be shut down while they are running.
+///
+/// `GracefulShutdown` ties these pieces together:
+///
+/// * [`signal`] returns the future to pass to `Server::with_graceful_shutdown`.
+/// * [`ServiceExt::graceful_shutdown`] wraps a service so that its in-flight
+///   requests are tracked by this handle.
+/// * [`shutdown`] triggers the shutdown, eg. from a signal handler.
+/// * [`wait_idle`] blocks until all in-flight requests have completed, or a
+///   deadline has passed. Requests that are still running at that point are
+///   answered with `503 Service Unavailable`.
+///
+/// All clones of a `GracefulShutdown` refer to the same state.
+///
+/// # Examples
+///
+/// Shut down when Ctrl+C is pressed, giving requests 30 seconds to complete:
+///
+/// ```no_run
+/// use hyperdrive::{FromRequest, service::{GracefulShutdown, ServiceExt, SyncService}};
+/// use hyper::Server;
+/// use futures::Future;
+/// use std::time::Duration;
+///
+/// #[derive(FromRequest)]
+/// enum Route {
+///     #[get("/")]
+///     Index,
+/// }
+///
+/// let shutdown = GracefulShutdown::new();
+///
+/// let service = SyncService::responding(|_: Route, _| "Hello World!")
+///     .graceful_shutdown(&shutdown)
+///     .make_service_by_cloning();
+/// let server = Server::bind(&"127.0.0.1:8080".parse().unwrap())
+///     .serve(service)
+///     .with_graceful_shutdown(shutdown.signal())
+///     .map_err(|e| eprintln!("server error: {}", e));
+///
+/// let handle = shutdown.clone();
+/// ctrlc::set_handler(move || handle.shutdown()).expect("couldn't set Ctrl+C handler");
+///
+/// let mut runtime = tokio::runtime::Runtime::new().unwrap();
+/// runtime.spawn(server);
+///
+/// // Wait for Ctrl+C, then for the in-flight requests
+/// shutdown.signal().wait().unwrap();
+/// if !shutdown.wait_idle(Duration::from_secs(30)) {
+///     eprintln!("some requests didn't complete in time");
+/// }
+/// runtime.shutdown_on_idle().wait().unwrap();
+/// ```
+///
+/// [`SyncService`]: struct.SyncService.html
+/// [`signal`]: #method.signal
+/// [`shutdown`]: #method.shutdown
+/// [`wait_idle`]: #method.wait_idle
+/// [`ServiceExt::graceful_shutdown`]: trait.ServiceExt.html#tymethod.graceful_shutdown
+#[derive(Debug, Clone)]
+pub struct GracefulShutdown {
+    state: Arc<ShutdownState>,
+}
+
+#[derive(Debug)]
+struct ShutdownState {
+    /// Notifies the futures returned by `GracefulShutdown::signal`.
+    ///
+    /// Set to `None` once the shutdown has been triggered.
+    signals: Mutex<Option<Vec<oneshot::Sender<()>>>>,
+    in_flight: Mutex<InFlight>,
+    /// Notified when the last in-flight request completes.
+    idle: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct InFlight {
+    next_id: u64,
+    /// Maps the ID of every in-flight request to a sender that aborts it.
+    requests: HashMap<u64, oneshot::Sender<()>>,
+    /// Set when `wait_idle` gives up. From then on, all requests fail.
+    expired: bool,
+}
+
+impl GracefulShutdown {
+    /// Creates a new handle.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(ShutdownState {
+                signals: Mutex::new(Some(Vec::new())),
+                in_flight: Mutex::new(InFlight::default()),
+                idle: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns a future that resolves once [`shutdown`] is called.
+    ///
+    /// This can be passed to hyper's `Server::with_graceful_shutdown`. If the
+    /// shutdown has already been triggered, the future resolves immediately.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    pub fn signal(&self) -> impl Future<Item = (), Error = ()> + Send {
+        let (tx, rx) = oneshot::channel();
+        match &mut *self.state.signals.lock().unwrap() {
+            Some(signals) => signals.push(tx),
+            None => {
+                let _ = tx.send(());
+            }
+        }
+
+        // The sender is only dropped without sending when the handle is
+        // dropped, in which case the shutdown can no longer be triggered.
+        rx.or_else(|_canceled| futures::future::empty())
+    }
+
+    /// Triggers the shutdown, resolving all futures returned by [`signal`].
+    ///
+    /// Calling this more than once has no effect.
+    ///
+    /// [`signal`]: #method.signal
+    pub fn shutdown(&self) {
+        let signals = self.state.signals.lock().unwrap().take();
+        for signal in signals.into_iter().flatten() {
+            let _ = signal.send(());
+        }
+    }
+
+    /// Returns whether [`shutdown`] has been called.
+    ///
+    /// [`shutdown`]: #method.shutdown
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.signals.lock().unwrap().is_none()
+    }
+
+    /// Returns the number of requests that are currently being processed.
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.lock().unwrap().requests.len()
+    }
+
+    /// Blocks the current thread until no requests are in flight, or until
+    /// `timeout` has passed.
+    ///
+    /// Returns `true` if all requests have completed. Otherwise, the requests
+    /// that are still in flight are answered with `503 Service Unavailable`
+    /// (their futures are dropped), as are all requests arriving afterwards,
+    /// and `false` is returned.
+    ///
+    /// This is meant to be called from the main thread after the shutdown was
+    /// triggered. It must not be called from a task running on the same
+    /// runtime as the server, since it blocks the thread.
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut in_flight = self.state.in_flight.lock().unwrap();
+
+        while !in_flight.requests.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                in_flight.expired = true;
+                for (_, abort) in in_flight.requests.drain() {
+                    let _ = abort.send(());
+                }
+                return false;
+            }
+
+            in_flight = self
+                .state
+                .idle
+                .wait_timeout(in_flight, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        true
+    }
+}
+
+impl Default for GracefulShutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `Service` adapter that tracks in-flight requests for a
+/// [`GracefulShutdown`].
+///
+/// Returned by [`ServiceExt::graceful_shutdown`].
+///
+/// [`GracefulShutdown`]: struct.GracefulShutdown.html
+/// [`ServiceExt::graceful_shutdown`]: trait.ServiceExt.html#tymethod.graceful_shutdown
+#[derive(Debug, Clone)]
+pub struct Drain<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    state: Arc<ShutdownState>,
+}
+
+impl<S> Service for Drain<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let (abort_tx, abort_rx) = oneshot::channel();
+        let id = {
+            let mut in_flight = self.state.in_flight.lock().unwrap();
+            if in_flight.expired {
+                return Box::new(Ok(service_unavailable()).into_future());
+            }
+
+            let id = in_flight.next_id;
+            in_flight.next_id += 1;
+            in_flight.requests.insert(id, abort_tx);
+            id
+        };
+
+        // Unregisters the request when the future completes or is dropped
+        // (eg. when the connection is closed or a panic unwinds).
+        let guard = InFlightGuard {
+            state: self.state.clone(),
+            id,
+        };
+        let abort = abort_rx
+            .map(|()| service_unavailable())
+            .or_else(|_canceled| futures::future::empty());
+
+        Box::new(
+            self.inner
+                .call(req)
+                .select(abort)
+                .map(|(response, _)| response)
+                .map_err(|(error, _)| error)
+                .then(move |result| {
+                    drop(guard);
+                    result
+                }),
+        )
+    }
+}
+
+struct InFlightGuard {
+    state: Arc<ShutdownState>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.state.in_flight.lock().unwrap();
+        in_flight.requests.remove(&self.id);
+        if in_flight.requests.is_empty() {
+            self.state.idle.notify_all();
+        }
+    }
+}
+
+fn service_unavailable<B: ErrorBody>() -> Response<B> {
+    Error::from_status(StatusCode::SERVICE_UNAVAILABLE)
+        .response()
+        .map(|()| B::empty())
+}
+
+/// Configures the response compression performed by
+/// [`ServiceExt::compress`].
+///
+/// By default, responses of at least 1 KiB are compressed if their
+/// `Content-Type` is `text/*`, `application/json`, `application/javascript`,
+/// `application/xml`, `image/svg+xml`, or uses the `+json` or `+xml` suffix.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::service::CompressionConfig;
+///
+/// let config = CompressionConfig::new()
+///     .min_size(256)
+///     .level(9)
+///     .content_type("application/wasm");
+/// ```
+///
+/// [`ServiceExt::compress`]: trait.ServiceExt.html#tymethod.compress
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    min_size: u64,
+    level: u32,
+    content_types: Vec<String>,
+    #[cfg(feature = "brotli")]
+    brotli: bool,
+}
+
+impl CompressionConfig {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self {
+            min_size: 1024,
+            level: 6,
+            content_types: vec![
+                "text/*".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+                "application/xml".to_string(),
+                "image/svg+xml".to_string(),
+            ],
+            #[cfg(feature = "brotli")]
+            brotli: true,
+        }
+    }
+
+    /// Sets the minimum size of a response body to compress, in bytes.
+    ///
+    /// Compressing tiny bodies usually makes them larger. Bodies of unknown
+    /// size (eg. streams) are always compressed. The default is 1024.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Sets the compression level, from 0 (fastest) to 9 (smallest).
+    ///
+    /// The default is 6. When using Brotli, the level is scaled to a Brotli
+    /// quality between 0 and 11.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `level` is greater than 9.
+    pub fn level(mut self, level: u32) -> Self {
+        assert!(level <= 9, "invalid compression level {}", level);
+        self.level = level;
+        self
+    }
+
+    /// Marks responses with the given `Content-Type` as compressible.
+    ///
+    /// `content_type` is a media type without parameters, such as
+    /// `application/wasm`, or a wildcard matching all subtypes, such as
+    /// `font/*`.
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_types.push(content_type.to_ascii_lowercase());
+        self
+    }
+
+    /// Sets whether the `br` (Brotli) content coding may be used.
+    ///
+    /// This is enabled by default. Clients accepting both `br` and `gzip` with
+    /// the same preference are sent Brotli.
+    #[cfg(feature = "brotli")]
+    pub fn brotli(mut self, enabled: bool) -> Self {
+        self.brotli = enabled;
+        self
+    }
+
+    /// Returns whether a response with `content_type` should be compressed.
+    fn is_compressible(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        if essence.ends_with("+json") || essence.ends_with("+xml") {
+            return true;
+        }
+
+        self.content_types.iter().any(|ty| {
+            if ty.ends_with("/*") {
+                essence.starts_with(&ty[..ty.len() - 1])
+            } else {
+                *ty == essence
+            }
+        })
+    }
+
+    /// Picks the content coding to use based on the `Accept-Encoding` header.
+    ///
+    /// Returns `None` if the client doesn't accept any supported coding.
+    fn negotiate(&self, accept: &AcceptEncoding) -> Option<ContentCoding> {
+        // `x-gzip` is an alias of `gzip`, which takes precedence if both are
+        // listed
+        let listed = |coding| accept.preferences().iter().any(|(c, _)| c == coding);
+        let gzip = if !listed("gzip") && listed("x-gzip") {
+            accept.quality("x-gzip")
+        } else {
+            accept.quality("gzip")
+        };
+
+        #[cfg(feature = "brotli")]
+        {
+            let br = accept.quality("br");
+            if self.brotli && br > 0.0 && br >= gzip {
+                return Some(ContentCoding::Brotli);
+            }
+        }
+
+        if gzip > 0.0 {
+            Some(ContentCoding::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `Service` adapter that compresses responses.
+///
+/// Returned by [`ServiceExt::compress`].
+///
+/// [`ServiceExt::compress`]: trait.ServiceExt.html#tymethod.compress
+#[derive(Debug, Clone)]
+pub struct Compress<S>
+where
+    S: Service<ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    config: Arc<CompressionConfig>,
+}
+
+impl<S> Service for Compress<S>
+where
+    S: Service<ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let is_head = req.method() == Method::HEAD;
+        let accept = AcceptEncoding::parse(req.headers().get_all(header::ACCEPT_ENCODING));
+        let coding = self.config.negotiate(&accept);
+        let requires_encoding = accept.requires_encoding();
+        let config = self.config.clone();
+
+        Box::new(self.inner.call(req).map(move |mut response| {
+            let status = response.status();
+            if is_head
+                || status.is_informational()
+                || status == StatusCode::NO_CONTENT
+                || status == StatusCode::NOT_MODIFIED
+                || response.headers().contains_key(header::CONTENT_ENCODING)
+            {
+                return response;
+            }
+
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok());
+            let compressible = match content_type {
+                Some(content_type) => config.is_compressible(content_type),
+                None => false,
+            };
+            let too_small = match response.body().content_length() {
+                Some(len) => len < config.min_size,
+                None => false,
+            };
+            // Clients refusing unencoded responses get every response
+            // compressed, if possible
+            if (!compressible || too_small) && !(requires_encoding && coding.is_some()) {
+                return response;
+            }
+
+            // The response now depends on `Accept-Encoding`, even if the
+            // client didn't accept any coding we support.
+            add_vary_accept_encoding(response.headers_mut());
+
+            let coding = match coding {
+                Some(coding) => coding,
+                None => return response,
+            };
+            response
+                .headers_mut()
+                .insert(header::CONTENT_ENCODING, coding.header_value());
+            response.headers_mut().remove(header::CONTENT_LENGTH);
+
+            let encoder = Encoder::new(coding, config.level);
+            response.map(|body| {
+                Body::wrap_stream(CompressedBody {
+                    body,
+                    encoder: Some(encoder),
+                })
+            })
+        }))
+    }
+}
+
+fn add_vary_accept_encoding(headers: &mut HeaderMap) {
+    let present = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| {
+            let name = name.trim();
+            name == "*" || name.eq_ignore_ascii_case("accept-encoding")
+        });
+    if !present {
+        headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+}
+
+/// A content coding supported by [`Compress`].
+///
+/// [`Compress`]: struct.Compress.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ContentCoding {
+    Gzip,
+    #[cfg(feature = "brotli")]
+    Brotli,
+}
+
+impl ContentCoding {
+    fn header_value(self) -> HeaderValue {
+        match self {
+            ContentCoding::Gzip => HeaderValue::from_static("gzip"),
+            #[cfg(feature = "brotli")]
+            ContentCoding::Brotli => HeaderValue::from_static("br"),
+        }
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    fn new(coding: ContentCoding, level: u32) -> Self {
+        match coding {
+            ContentCoding::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::new(level)))
+            }
+            #[cfg(feature = "brotli")]
+            ContentCoding::Brotli => {
+                // Brotli qualities range from 0 to 11, and 22 is the
+                // recommended window size.
+                let quality = (level * 11 + 4) / 9;
+                Encoder::Brotli(Box::new(brotli::CompressorWriter::new(
+                    Vec::new(),
+                    4096,
+                    quality,
+                    22,
+                )))
+            }
+        }
+    }
+
+    /// Compresses `data` and returns the compressed output.
+    ///
+    /// The encoder is flushed, so all of `data` can be decompressed by the
+    /// client once it has received the output. This is needed for streaming
+    /// responses.
+    fn encode(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(encoder.get_mut().split_off(0))
+            }
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(encoder.get_mut().split_off(0))
+            }
+        }
+    }
+
+    /// Finishes the compressed stream and returns the remaining output.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(encoder) => Ok(encoder.into_inner()),
+        }
+    }
+}
+
+/// A response body that is compressed as it is streamed.
+struct CompressedBody {
+    body: Body,
+    /// `None` once the compressed stream has been finished.
+    encoder: Option<Encoder>,
+}
+
+impl Stream for CompressedBody {
+    type Item = Chunk;
+    type Error = BoxedError;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, BoxedError> {
+        loop {
+            let encoder = match &mut self.encoder {
+                Some(encoder) => encoder,
+                None => return Ok(Async::Ready(None)),
+            };
+
+            match try_ready!(self.body.poll()) {
+                Some(chunk) => {
+                    let output = encoder.encode(&chunk)?;
+                    if !output.is_empty() {
+                        return Ok(Async::Ready(Some(output.into())));
+                    }
+                }
+                None => {
+                    let output = self.encoder.take().unwrap().finish()?;
+                    return Ok(Async::Ready(Some(output.into())));
+                }
+            }
+        }
+    }
+}
+
+/// A `Service` adapter that adds `ETag`s to responses and answers conditional
+/// requests.
+///
+/// Returned by [`ServiceExt::auto_etag`].
+///
+/// [`ServiceExt::auto_etag`]: trait.ServiceExt.html#tymethod.auto_etag
+#[derive(Debug, Copy, Clone)]
+pub struct AutoEtag<S>
+where
+    S: Service<ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    max_size: u64,
+}
+
+impl<S> AutoEtag<S>
+where
+    S: Service<ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    /// Sets the size of the largest body that an `ETag` is computed for, in
+    /// bytes.
+    ///
+    /// Computing the `ETag` requires hashing the whole body, so responses
+    /// larger than this are passed through unchanged. By default, this is 1
+    /// MiB.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+}
+
+impl<S> Service for AutoEtag<S>
+where
+    S: Service<ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        let is_head = req.method() == Method::HEAD;
+        if req.method() != Method::GET && !is_head {
+            return Box::new(self.inner.call(req));
+        }
+
+        let if_none_match = req
+            .headers()
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if is_head {
+            *req.method_mut() = Method::GET;
+        }
+        let max_size = self.max_size;
+
+        Box::new(self.inner.call(req).and_then(move |response| {
+            let complete = match response.body().content_length() {
+                Some(len) => len <= max_size,
+                None => false,
+            };
+            if response.status() != StatusCode::OK
+                || response.headers().contains_key(header::ETAG)
+                || !complete
+            {
+                let response = if is_head {
+                    strip_body(response)
+                } else {
+                    response
+                };
+                return Either::A(Ok(response).into_future());
+            }
+
+            // The body is available in full, so this resolves immediately
+            let (parts, body) = response.into_parts();
+            Either::B(body.concat2().map_err(BoxedError::from).map(move |body| {
+                let etag = entity_tag(&body);
+                let mut response = Response::from_parts(parts, Body::from(body));
+                if if_none_match
+                    .iter()
+                    .any(|value| matches_entity_tag(value, &etag))
+                {
+                    response = not_modified(response);
+                } else if is_head {
+                    response = strip_body(response);
+                }
+                response.headers_mut().insert(header::ETAG, etag);
+                response
+            }))
+        }))
+    }
+}
+
+/// Computes the strong entity tag of `body`.
+fn entity_tag(body: &[u8]) -> HeaderValue {
+    let hash = Sha256::digest(body);
+    // 128 bits are plenty to tell the versions of a resource apart
+    HeaderValue::from_str(&format!("\"{}\"", encode_hex(&hash[..16])))
+        .expect("hex digits are a valid header value")
+}
+
+/// Returns whether the `If-None-Match` header `value` matches `etag`.
+///
+/// `If-None-Match` uses the weak comparison function, so weak entity tags
+/// match as well.
+pub(crate) fn matches_entity_tag(value: &str, etag: &HeaderValue) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/").as_bytes() == etag.as_bytes())
+}
+
+/// Turns `response` into a `304 Not Modified` response, keeping only the
+/// headers that RFC 7232 requires to be sent along with it.
+pub(crate) fn not_modified(response: Response<Body>) -> Response<Body> {
+    const KEPT_HEADERS: &[HeaderName] = &[
+        header::CACHE_CONTROL,
+        header::CONTENT_LOCATION,
+        header::DATE,
+        header::EXPIRES,
+        header::VARY,
+    ];
+
+    let (mut parts, _) = response.into_parts();
+    let headers = std::mem::replace(&mut parts.headers, HeaderMap::new());
+    for name in KEPT_HEADERS {
+        for value in headers.get_all(name) {
+            parts.headers.append(name, value.clone());
+        }
+    }
+    parts.status = StatusCode::NOT_MODIFIED;
+    Response::from_parts(parts, Body::empty())
+}
+
+/// Configures the headers added by [`ServiceExt::security_headers`].
+///
+/// By default, the following headers are added:
+///
+/// * `X-Content-Type-Options: nosniff`
+/// * `X-Frame-Options: SAMEORIGIN`
+/// * `Strict-Transport-Security: max-age=31536000; includeSubDomains`, but only
+///   to responses to requests made via HTTPS (see [`tls`] and
+///   [`trust_forwarded_proto`]), as required by [RFC 6797].
+/// * `Referrer-Policy: strict-origin-when-cross-origin`
+///
+/// A `Content-Security-Policy` depends on the resources used by an app, so it
+/// isn't added unless configured with [`content_security_policy`].
+///
+/// Every header can be changed or disabled by passing `Some(value)` or `None`
+/// to the corresponding method.
+///
+/// [`ServiceExt::security_headers`]: trait.ServiceExt.html#tymethod.security_headers
+/// [`tls`]: #method.tls
+/// [`trust_forwarded_proto`]: #method.trust_forwarded_proto
+/// [`content_security_policy`]: #method.content_security_policy
+/// [RFC 6797]: https://tools.ietf.org/html/rfc6797#section-7.2
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    content_type_options: Option<HeaderValue>,
+    frame_options: Option<HeaderValue>,
+    strict_transport_security: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+    content_security_policy: Option<HeaderValue>,
+    force: bool,
+    tls: bool,
+    trust_forwarded_proto: bool,
+}
+
+impl SecurityHeaders {
+    /// Sets the `X-Content-Type-Options` header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` isn't a valid header value.
+    pub fn content_type_options(mut self, value: Option<&str>) -> Self {
+        self.content_type_options = value.map(header_value);
+        self
+    }
+
+    /// Sets the `X-Frame-Options` header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` isn't a valid header value.
+    pub fn frame_options(mut self, value: Option<&str>) -> Self {
+        self.frame_options = value.map(header_value);
+        self
+    }
+
+    /// Sets the `Strict-Transport-Security` header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` isn't a valid header value.
+    pub fn strict_transport_security(mut self, value: Option<&str>) -> Self {
+        self.strict_transport_security = value.map(header_value);
+        self
+    }
+
+    /// Sets the `Referrer-Policy` header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` isn't a valid header value.
+    pub fn referrer_policy(mut self, value: Option<&str>) -> Self {
+        self.referrer_policy = value.map(header_value);
+        self
+    }
+
+    /// Sets the `Content-Security-Policy` header.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` isn't a valid header value.
+    pub fn content_security_policy(mut self, value: Option<&str>) -> Self {
+        self.content_security_policy = value.map(header_value);
+        self
+    }
+
+    /// Sets whether headers already present in a response are overwritten.
+    ///
+    /// By default, they are kept, so that handlers can override the headers
+    /// for individual responses.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Sets whether the server terminates TLS itself, so that all requests it
+    /// receives were made via HTTPS.
+    ///
+    /// Hyper doesn't record how a request arrived, so this has to be set for
+    /// `Strict-Transport-Security` to be sent when the server listens for
+    /// HTTPS connections directly.
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sets whether to trust the `X-Forwarded-Proto` header to determine if a
+    /// request was made via HTTPS.
+    ///
+    /// This should only be enabled when the server is only reachable through
+    /// a reverse proxy that sets this header, since clients can send it as
+    /// well.
+    pub fn trust_forwarded_proto(mut self, trust: bool) -> Self {
+        self.trust_forwarded_proto = trust;
+        self
+    }
+
+    /// Returns whether `request` was made via HTTPS.
+    fn is_https<B>(&self, request: &Request<B>) -> bool {
+        if self.tls || request.uri().scheme_str() == Some("https") {
+            return true;
+        }
+
+        if self.trust_forwarded_proto {
+            // The proxy closest to us appends the last value
+            let proto = request
+                .headers()
+                .get_all("x-forwarded-proto")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .last();
+            if let Some(proto) = proto {
+                return proto.trim().eq_ignore_ascii_case("https");
+            }
+        }
+
+        false
+    }
+
+    fn apply(&self, headers: &mut HeaderMap, https: bool) {
+        let hsts = if https {
+            &self.strict_transport_security
+        } else {
+            &None
+        };
+        let all = [
+            (header::X_CONTENT_TYPE_OPTIONS, &self.content_type_options),
+            (header::X_FRAME_OPTIONS, &self.frame_options),
+            (header::STRICT_TRANSPORT_SECURITY, hsts),
+            (header::REFERRER_POLICY, &self.referrer_policy),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &self.content_security_policy,
+            ),
+        ];
+
+        for (name, value) in all.iter() {
+            if let Some(value) = value {
+                if self.force || !headers.contains_key(name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_type_options: Some(HeaderValue::from_static("nosniff")),
+            frame_options: Some(HeaderValue::from_static("SAMEORIGIN")),
+            strict_transport_security: Some(HeaderValue::from_static(
+                "max-age=31536000; includeSubDomains",
+            )),
+            referrer_policy: Some(HeaderValue::from_static("strict-origin-when-cross-origin")),
+            content_security_policy: None,
+            force: false,
+            tls: false,
+            trust_forwarded_proto: false,
+        }
+    }
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    match HeaderValue::from_str(value) {
+        Ok(value) => value,
+        Err(_) => panic!("invalid header value `{}`", value),
+    }
+}
+
+/// A `Service` adapter that adds security-related headers to responses.
+///
+/// Returned by [`ServiceExt::security_headers`].
+///
+/// [`ServiceExt::security_headers`]: trait.ServiceExt.html#tymethod.security_headers
+#[derive(Debug, Clone)]
+pub struct WithSecurityHeaders<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    config: Arc<SecurityHeaders>,
+}
+
+impl<S> Service for WithSecurityHeaders<S>
+where
+    S: Service<Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let https = self.config.is_https(&req);
+        let config = self.config.clone();
+
+        Box::new(self.inner.call(req).map(move |mut response| {
+            config.apply(response.headers_mut(), https);
+            response
+        }))
+    }
+}
+
+/// A `Service` adapter that limits the size of request bodies.
+///
+/// Returned by [`ServiceExt::limit_body`].
+///
+/// [`ServiceExt::limit_body`]: trait.ServiceExt.html#tymethod.limit_body
+#[derive(Debug, Copy, Clone)]
+pub struct LimitBody<S>
+where
+    S: Service<ReqBody = Body, Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    max_bytes: u64,
+}
+
+impl<S> Service for LimitBody<S>
+where
+    S: Service<ReqBody = Body, Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = Body;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let max_bytes = self.max_bytes;
+        let declared_len = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if let Some(len) = declared_len {
+            if len > max_bytes {
+                let response = payload_too_large(max_bytes)
+                    .response()
+                    .map(|()| ErrorBody::empty());
+                return Box::new(Ok(response).into_future());
+            }
+        }
+
+        // Bodies that are known to be empty are passed through unchanged, since
+        // wrapping them would break `Body::on_upgrade`.
+        let req = if req.body().is_end_stream() {
+            req
+        } else {
+            req.map(|body| {
+                Body::wrap_stream(LimitedBody {
+                    body,
+                    remaining: max_bytes,
+                    max_bytes,
+                })
+            })
+        };
+
+        Box::new(self.inner.call(req))
+    }
+}
+
+/// A request body that fails once more than `max_bytes` have been read.
+struct LimitedBody {
+    body: Body,
+    remaining: u64,
+    max_bytes: u64,
+}
+
+impl Stream for LimitedBody {
+    type Item = Chunk;
+    type Error = BoxedError;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, BoxedError> {
+        match try_ready!(self.body.poll()) {
+            Some(chunk) => {
+                let len = chunk.len() as u64;
+                if len > self.remaining {
+                    return Err(payload_too_large(self.max_bytes).into());
+                }
+
+                self.remaining -= len;
+                Ok(Async::Ready(Some(chunk)))
+            }
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+fn payload_too_large(max_bytes: u64) -> Error {
+    Error::body_too_large(format!(
+        "request body exceeds the limit of {} bytes",
+        max_bytes
+    ))
+}
+
+/// A `Service` adapter that limits the number of concurrently processed
+/// requests.
+///
+/// Returned by [`ServiceExt::concurrency_limit`]. All clones of a
+/// `ConcurrencyLimit` share the same limit.
+///
+/// [`ServiceExt::concurrency_limit`]: trait.ServiceExt.html#tymethod.concurrency_limit
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    limiter: Arc<Limiter>,
+    retry_after: Duration,
+    max_wait: Duration,
+}
+
+impl<S> ConcurrencyLimit<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    /// Sets the delay sent in the `Retry-After` header of shed requests.
+    ///
+    /// The delay is rounded down to whole seconds. The default is 1 second.
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after = delay;
+        self
+    }
+
+    /// Sets how long a request may wait for another request to complete
+    /// before it is shed.
+    ///
+    /// By default, requests are shed immediately when the limit is reached.
+    /// Waiting requests are served in the order they arrived.
+    ///
+    /// Waiting uses `tokio-timer`, so the service has to be run on a tokio
+    /// runtime that provides a timer (as `tokio::run` does).
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+}
+
+impl<S> Service for ConcurrencyLimit<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let retry_after = self.retry_after;
+        let waiter = {
+            let mut state = self.limiter.state.lock().unwrap();
+            if state.in_flight < self.limiter.max_in_flight {
+                state.in_flight += 1;
+                None
+            } else if self.max_wait > Duration::from_secs(0) {
+                let (tx, rx) = oneshot::channel();
+                state.waiters.push_back(tx);
+                Some(rx)
+            } else {
+                return Box::new(Ok(overloaded(retry_after)).into_future());
+            }
+        };
+
+        match waiter {
+            None => {
+                // Created before calling the inner service, so that the slot is
+                // released if `call` panics.
+                let permit = Permit {
+                    limiter: self.limiter.clone(),
+                };
+                let inner = self.inner.call(req);
+                Box::new(inner.then(move |result| {
+                    drop(permit);
+                    result
+                }))
+            }
+            Some(rx) => {
+                // The inner future isn't polled before a slot is free.
+                let inner = self.inner.call(req);
+                let wait = WaitForPermit {
+                    rx: Some(rx),
+                    deadline: tokio_timer::Delay::new(Instant::now() + self.max_wait),
+                    limiter: self.limiter.clone(),
+                };
+                Box::new(wait.and_then(move |permit| -> DefaultFuture<_, _> {
+                    match permit {
+                        Some(permit) => Box::new(inner.then(move |result| {
+                            drop(permit);
+                            result
+                        })),
+                        None => Box::new(Ok(overloaded(retry_after)).into_future()),
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// The state shared by all clones of a [`ConcurrencyLimit`].
+///
+/// [`ConcurrencyLimit`]: struct.ConcurrencyLimit.html
+#[derive(Debug)]
+struct Limiter {
+    max_in_flight: usize,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    in_flight: usize,
+    /// Requests waiting for a slot. A slot is handed over by sending `()`.
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+impl Limiter {
+    /// Releases a slot, passing it on to the oldest waiting request, if any.
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiter) = state.waiters.pop_front() {
+            // Fails if the waiting request was dropped or shed
+            if waiter.send(()).is_ok() {
+                return;
+            }
+        }
+
+        state.in_flight -= 1;
+    }
+}
+
+/// Occupies a slot of a [`Limiter`] while it is alive.
+///
+/// [`Limiter`]: struct.Limiter.html
+struct Permit {
+    limiter: Arc<Limiter>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// Waits for a [`Limiter`] slot to be handed over, until a deadline.
+///
+/// Resolves to `None` when the deadline passes.
+///
+/// [`Limiter`]: struct.Limiter.html
+struct WaitForPermit {
+    rx: Option<oneshot::Receiver<()>>,
+    deadline: tokio_timer::Delay,
+    limiter: Arc<Limiter>,
+}
+
+impl Future for WaitForPermit {
+    type Item = Option<Permit>;
+    type Error = BoxedError;
+
+    fn poll(&mut self) -> Poll<Option<Permit>, BoxedError> {
+        let rx = self
+            .rx
+            .as_mut()
+            .expect("WaitForPermit polled after completion");
+        match rx.poll() {
+            Ok(Async::Ready(())) => {
+                self.rx = None;
+                return Ok(Async::Ready(Some(Permit {
+                    limiter: self.limiter.clone(),
+                })));
+            }
+            Ok(Async::NotReady) => {}
+            Err(oneshot::Canceled) => {
+                self.rx = None;
+                return Ok(Async::Ready(None));
+            }
+        }
+
+        match self.deadline.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // The deadline has passed (or the timer failed). `Drop` takes care
+            // of a slot that was handed over in the meantime.
+            Ok(Async::Ready(())) | Err(_) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+impl Drop for WaitForPermit {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if let Ok(Some(())) = rx.try_recv() {
+                self.limiter.release();
+            }
+        }
+    }
+}
+
+/// Creates the response sent for requests shed by a [`ConcurrencyLimit`].
+///
+/// [`ConcurrencyLimit`]: struct.ConcurrencyLimit.html
+fn overloaded<B: ErrorBody>(retry_after: Duration) -> Response<B> {
+    let mut error = Error::from_status(StatusCode::SERVICE_UNAVAILABLE);
+    error.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(retry_after.as_secs()),
+    );
+    error.response().map(|()| B::empty())
+}
+
+/// Whether paths should end with a slash.
+///
+/// Passed to [`ServiceExt::redirect_trailing_slash`].
+///
+/// [`ServiceExt::redirect_trailing_slash`]: trait.ServiceExt.html#tymethod.redirect_trailing_slash
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Paths should end with a slash (`/users/`).
+    Add,
+    /// Paths should not end with a slash (`/users`).
+    Remove,
+}
+
+impl TrailingSlash {
+    /// Returns the canonical form of `path`, or `None` if `path` is already
+    /// canonical or must not be rewritten.
+    fn canonicalize(self, path: &str) -> Option<String> {
+        if path == "/" {
+            return None;
+        }
+
+        let canonical = match self {
+            TrailingSlash::Add if !path.ends_with('/') => format!("{}/", path),
+            TrailingSlash::Remove if path.ends_with('/') => path.trim_end_matches('/').to_string(),
+            _ => return None,
+        };
+
+        // An empty path or one starting with `//` would not be a path on this
+        // host anymore.
+        if canonical.is_empty() || canonical.starts_with("//") {
+            None
+        } else {
+            Some(canonical)
+        }
+    }
+}
+
+/// A `Service` adapter that redirects requests to paths with or without a
+/// trailing slash.
+///
+/// Returned by [`ServiceExt::redirect_trailing_slash`].
+///
+/// [`ServiceExt::redirect_trailing_slash`]: trait.ServiceExt.html#tymethod.redirect_trailing_slash
+#[derive(Debug, Copy, Clone)]
+pub struct RedirectTrailingSlash<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    policy: TrailingSlash,
+}
+
+impl<S> Service for RedirectTrailingSlash<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Box::new(self.inner.call(req));
+        }
+
+        let mut location = match self.policy.canonicalize(req.uri().path()) {
+            Some(path) => path,
+            None => return Box::new(self.inner.call(req)),
+        };
+        if let Some(query) = req.uri().query() {
+            location.push('?');
+            location.push_str(query);
+        }
+
+        // The location is made of parts of a valid URI, so it is a valid
+        // header value.
+        let response = Response::builder()
+            .status(StatusCode::PERMANENT_REDIRECT)
+            .header(header::LOCATION, location)
+            .body(ErrorBody::empty())
+            .map_err(BoxedError::from);
+        Box::new(response.into_future())
+    }
+}
+
+/// Configures the redirects performed by [`ServiceExt::redirect_to_https`].
+///
+/// By default, every request is considered insecure, since hyper doesn't
+/// record how a request arrived. This is correct for a plaintext listener
+/// that only exists to redirect clients. If the server is also reachable
+/// through a reverse proxy that terminates TLS, enable
+/// [`trust_forwarded_proto`] so that requests forwarded via HTTPS are passed
+/// through.
+///
+/// Requests for paths starting with `/.well-known/acme-challenge/` are never
+/// redirected, so that certificates can be obtained via the ACME HTTP-01
+/// challenge. More paths can be exempted with [`exempt_path`].
+///
+/// [`ServiceExt::redirect_to_https`]: trait.ServiceExt.html#tymethod.redirect_to_https
+/// [`trust_forwarded_proto`]: #method.trust_forwarded_proto
+/// [`exempt_path`]: #method.exempt_path
+#[derive(Debug, Clone)]
+pub struct HttpsRedirect {
+    tls: bool,
+    trust_forwarded_proto: bool,
+    host: Option<Authority>,
+    exempt_paths: Vec<String>,
+}
+
+impl HttpsRedirect {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the server terminates TLS itself, so that all requests it
+    /// receives were made via HTTPS.
+    ///
+    /// This is useful when the same service is served on a plaintext and a
+    /// TLS listener, and should be disabled for the plaintext one.
+    pub fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sets whether to trust the `Forwarded` and `X-Forwarded-Proto` headers
+    /// to determine if a request was made via HTTPS.
+    ///
+    /// If both headers are present, `Forwarded` takes precedence. This should
+    /// only be enabled when the server is only reachable through a reverse
+    /// proxy that sets these headers, since clients can send them as well.
+    pub fn trust_forwarded_proto(mut self, trust: bool) -> Self {
+        self.trust_forwarded_proto = trust;
+        self
+    }
+
+    /// Sets the host (and optionally port) to redirect to.
+    ///
+    /// By default, the host of the request is used, without its port, so that
+    /// the client connects to the default HTTPS port.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `host` isn't a valid URI authority.
+    pub fn host(mut self, host: Option<&str>) -> Self {
+        self.host = host.map(|host| match host.parse() {
+            Ok(authority) => authority,
+            Err(_) => panic!("invalid host `{}`", host),
+        });
+        self
+    }
+
+    /// Exempts requests for paths starting with `prefix` from the redirect.
+    pub fn exempt_path(mut self, prefix: &str) -> Self {
+        self.exempt_paths.push(prefix.to_string());
+        self
+    }
+
+    /// Returns whether `request` was made via HTTPS.
+    fn is_https<B>(&self, request: &Request<B>) -> bool {
+        if self.tls || request.uri().scheme_str() == Some("https") {
+            return true;
+        }
+
+        if self.trust_forwarded_proto {
+            if let Some(proto) = forwarded_proto(request.headers()) {
+                return proto.eq_ignore_ascii_case("https");
+            }
+        }
+
+        false
+    }
+
+    /// Returns the URL to redirect `request` to, or `None` if the request
+    /// doesn't specify a valid host.
+    fn location<B>(&self, request: &Request<B>) -> Option<String> {
+        let host = match &self.host {
+            Some(host) => host.as_str().to_string(),
+            None => {
+                let authority = match request.headers().get(header::HOST) {
+                    Some(host) => host.to_str().ok()?.parse::<Authority>().ok()?,
+                    None => request.uri().authority_part()?.clone(),
+                };
+                authority.host().to_string()
+            }
+        };
+
+        let path = request
+            .uri()
+            .path_and_query()
+            .map_or("/", |path| path.as_str());
+        Some(format!("https://{}{}", host, path))
+    }
+}
+
+impl Default for HttpsRedirect {
+    fn default() -> Self {
+        Self {
+            tls: false,
+            trust_forwarded_proto: false,
+            host: None,
+            exempt_paths: vec!["/.well-known/acme-challenge/".to_string()],
+        }
+    }
+}
+
+/// Returns the protocol of the last hop recorded in the `Forwarded` header, or
+/// in `X-Forwarded-Proto` if there is none.
+fn forwarded_proto(headers: &HeaderMap) -> Option<String> {
+    // The proxy closest to us appends the last element
+    let forwarded = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last();
+    if let Some(element) = forwarded {
+        return element.split(';').find_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next()?.trim();
+            let value = parts.next()?.trim().trim_matches('"');
+            if name.eq_ignore_ascii_case("proto") {
+                Some(value.to_string())
+            } else {
+                None
+            }
+        });
+    }
+
+    headers
+        .get_all("x-forwarded-proto")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .map(|proto| proto.trim().to_string())
+}
+
+/// A `Service` adapter that redirects insecure requests to HTTPS.
+///
+/// Returned by [`ServiceExt::redirect_to_https`].
+///
+/// [`ServiceExt::redirect_to_https`]: trait.ServiceExt.html#tymethod.redirect_to_https
+#[derive(Debug, Clone)]
+pub struct RedirectToHttps<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    inner: S,
+    config: Arc<HttpsRedirect>,
+}
+
+impl<S> Service for RedirectToHttps<S>
+where
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
+    S::Future: Send + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
+
+    fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
+        let path = req.uri().path();
+        let exempt = self
+            .config
+            .exempt_paths
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()));
+        if exempt || self.config.is_https(&req) {
+            return Box::new(self.inner.call(req));
+        }
+
+        let location = match self.config.location(&req) {
+            Some(location) => location,
+            None => {
+                let response = Error::from_status(StatusCode::BAD_REQUEST)
+                    .response()
+                    .map(|()| ErrorBody::empty());
+                return Box::new(Ok(response).into_future());
+            }
+        };
+        let status = if req.method() == Method::GET || req.method() == Method::HEAD {
+            StatusCode::MOVED_PERMANENTLY
+        } else {
+            StatusCode::PERMANENT_REDIRECT
+        };
+
+        // The location is made of parts of a valid URI, so it is a valid
+        // header value.
+        let response = Response::builder()
+            .status(status)
+            .header(header::LOCATION, location)
+            .body(ErrorBody::empty())
+            .map_err(BoxedError::from);
+        Box::new(response.into_future())
+    }
+}
+
+/// Whether the application is ready to serve requests.
+///
+/// Returned by the readiness check of a [`HealthConfig`].
+///
+/// [`HealthConfig`]: struct.HealthConfig.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadyState {
+    /// The application can serve requests.
+    Ready,
+    /// The application can't serve requests right now, eg. because it is
+    /// still starting up or shutting down.
+    NotReady,
+}
+
+/// A shared flag indicating whether the application is ready to serve
+/// requests.
+///
+/// The application sets the flag once it has started up (and possibly resets
+/// it when shutting down), and [`HealthConfig::readiness_flag`] reports it on
+/// the readiness endpoint. All clones of a `ReadinessFlag` refer to the same
+/// flag.
+///
+/// [`HealthConfig::readiness_flag`]: struct.HealthConfig.html#method.readiness_flag
+#[derive(Debug, Clone, Default)]
+pub struct ReadinessFlag {
+    ready: Arc<AtomicBool>,
+}
+
+impl ReadinessFlag {
+    /// Creates a new flag that is not set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the application is ready.
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::SeqCst);
+    }
+
+    /// Returns whether the application is ready.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Returns the `ReadyState` corresponding to this flag.
+    pub fn state(&self) -> ReadyState {
+        if self.is_ready() {
+            ReadyState::Ready
+        } else {
+            ReadyState::NotReady
+        }
+    }
+}
+
+/// Configuration for [`ServiceExt::with_health`].
+///
+/// By default, the liveness endpoint is `/healthz`, the readiness endpoint is
+/// `/readyz`, and the application is always reported as ready.
+///
+/// [`ServiceExt::with_health`]: trait.ServiceExt.html#tymethod.with_health
+#[derive(Clone)]
+pub struct HealthConfig {
+    liveness_path: String,
+    readiness_path: String,
+    readiness: Option<Arc<dyn Fn() -> ReadyState + Send + Sync>>,
+    shutdown: Option<GracefulShutdown>,
+}
+
+impl HealthConfig {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        Self {
+            liveness_path: "/healthz".to_string(),
+            readiness_path: "/readyz".to_string(),
+            readiness: None,
+            shutdown: None,
+        }
+    }
+
+    /// Sets the path of the liveness endpoint.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `path` doesn't start with a `/`.
+    pub fn liveness_path(mut self, path: &str) -> Self {
+        self.liveness_path = health_path(path);
+        self
+    }
+
+    /// Sets the path of the readiness endpoint.
+    ///
+    /// # Panics
     ///
-    /// The result of this can be directly passed to Hyper's `Builder::serve`.
-    fn make_service_by_cloning(self) -> MakeServiceByCloning<Self>
-    where
-        Self: Clone;
-}
+    /// This will panic if `path` doesn't start with a `/`.
+    pub fn readiness_path(mut self, path: &str) -> Self {
+        self.readiness_path = health_path(path);
+        self
+    }
 
-impl<T: Service> ServiceExt for T {
-    fn catch_unwind<H, R>(self, handler: H) -> CatchUnwind<Self, R, H>
+    /// Sets the function that is called to check whether the application is
+    /// ready, replacing any previously set check.
+    ///
+    /// The check is called for every request to the readiness endpoint, so it
+    /// should be cheap.
+    pub fn readiness<F>(mut self, check: F) -> Self
     where
-        Self: Service<ResBody = Body, Error = BoxedError> + Sync,
-        Self::Future: Send,
-        H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
-        R: IntoFuture<Item = Response<Body>, Error = BoxedError>,
-        R::Future: Send + 'static,
+        F: Fn() -> ReadyState + Send + Sync + 'static,
     {
-        CatchUnwind {
-            inner: self,
-            handler: Arc::new(handler),
+        self.readiness = Some(Arc::new(check));
+        self
+    }
+
+    /// Reports the state of `flag` on the readiness endpoint, replacing any
+    /// previously set check.
+    pub fn readiness_flag(self, flag: &ReadinessFlag) -> Self {
+        let flag = flag.clone();
+        self.readiness(move || flag.state())
+    }
+
+    /// Reports the application as not ready once `shutdown` has been
+    /// triggered, regardless of the readiness check.
+    ///
+    /// This makes load balancers stop sending new requests while the
+    /// in-flight requests are completed.
+    pub fn graceful_shutdown(mut self, shutdown: &GracefulShutdown) -> Self {
+        self.shutdown = Some(shutdown.clone());
+        self
+    }
+
+    fn is_ready(&self) -> bool {
+        if let Some(shutdown) = &self.shutdown {
+            if shutdown.is_shutting_down() {
+                return false;
+            }
+        }
+
+        match &self.readiness {
+            Some(check) => check() == ReadyState::Ready,
+            None => true,
         }
     }
+}
 
-    fn make_service_by_cloning(self) -> MakeServiceByCloning<Self>
-    where
-        Self: Clone,
-    {
-        MakeServiceByCloning { service: self }
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-/// A `Service` adapter that catches unwinding panics.
+impl fmt::Debug for HealthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthConfig")
+            .field("liveness_path", &self.liveness_path)
+            .field("readiness_path", &self.readiness_path)
+            .field("readiness", &self.readiness.as_ref().map(|_| "<closure>"))
+            .field("shutdown", &self.shutdown)
+            .finish()
+    }
+}
+
+fn health_path(path: &str) -> String {
+    assert!(
+        path.starts_with('/'),
+        "health check path `{}` must start with `/`",
+        path
+    );
+    path.to_string()
+}
+
+/// Marks responses to health checks in the response extensions, so that
+/// [`AccessLog`] can skip them.
 ///
-/// Returned by [`ServiceExt::catch_unwind`].
+/// [`AccessLog`]: struct.AccessLog.html
+#[derive(Debug, Copy, Clone)]
+struct HealthCheck;
+
+/// A `Service` adapter that answers liveness and readiness checks.
 ///
-/// [`ServiceExt::catch_unwind`]: trait.ServiceExt.html#tymethod.catch_unwind
-#[derive(Debug)]
-pub struct CatchUnwind<S, R, H>
+/// Returned by [`ServiceExt::with_health`].
+///
+/// [`ServiceExt::with_health`]: trait.ServiceExt.html#tymethod.with_health
+#[derive(Debug, Clone)]
+pub struct Health<S>
 where
-    S: Service<ResBody = Body, Error = BoxedError> + Sync,
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
     S::Future: Send + 'static,
-    R: IntoFuture<Item = Response<Body>, Error = BoxedError>,
-    R::Future: Send + 'static,
-    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
 {
     inner: S,
-    handler: Arc<H>,
+    config: Arc<HealthConfig>,
 }
 
-impl<S, R, H> Service for CatchUnwind<S, R, H>
+impl<S> Service for Health<S>
 where
-    S: Service<ResBody = Body, Error = BoxedError> + Sync,
+    S: Service<Error = BoxedError>,
+    S::ResBody: ErrorBody,
     S::Future: Send + 'static,
-    R: IntoFuture<Item = Response<Body>, Error = BoxedError>,
-    R::Future: Send + 'static,
-    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
 {
     type ReqBody = S::ReqBody;
-    type ResBody = Body;
+    type ResBody = S::ResBody;
     type Error = BoxedError;
-    type Future = DefaultFuture<Response<Body>, BoxedError>;
+    type Future = DefaultFuture<Response<S::ResBody>, BoxedError>;
 
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
-        // We need to make sure that we don't just catch panics that happen while *polling* the
-        // inner service's `Future`, but also those that happen when the inner `Future`s are
-        // constructed, which basically means anything happening inside `self.inner.call(..)`.
+        let is_head = req.method() == Method::HEAD;
+        if req.method() != Method::GET && !is_head {
+            return Box::new(self.inner.call(req));
+        }
 
-        let handler = self.handler.clone();
-        let inner_future = match catch_unwind(AssertUnwindSafe(move || self.inner.call(req))) {
-            Ok(future) => future,
-            Err(panic_payload) => return Box::new(handler(panic_payload).into_future()),
+        let path = req.uri().path();
+        let (status, text) = if path == self.config.liveness_path {
+            (StatusCode::OK, "ok\n")
+        } else if path == self.config.readiness_path {
+            if self.config.is_ready() {
+                (StatusCode::OK, "ready\n")
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, "not ready\n")
+            }
+        } else {
+            return Box::new(self.inner.call(req));
         };
 
-        Box::new(AssertUnwindSafe(inner_future).catch_unwind().then(
-            move |panic_result| -> DefaultFuture<Response<Body>, BoxedError> {
-                match panic_result {
-                    // FIXME avoid boxing so much here
-                    Ok(result) => Box::new(result.into_future()),
-                    Err(panic_payload) => Box::new(handler(panic_payload).into_future()),
-                }
-            },
-        ))
+        let mut response = Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .header(header::CACHE_CONTROL, "no-cache, no-store")
+            .extension(HealthCheck)
+            .body(ErrorBody::from_body(Body::from(text)))
+            .expect("couldn't build health check response");
+        if is_head {
+            response = strip_body(response);
+        }
+        Box::new(Ok(response).into_future())
     }
 }
 
-impl<S, R, H> Clone for CatchUnwind<S, R, H>
+/// A type-erased `Service`.
+///
+/// Returned by [`ServiceExt::boxed`].
+///
+/// [`ServiceExt::boxed`]: trait.ServiceExt.html#tymethod.boxed
+pub struct BoxedService {
+    inner: Box<dyn CloneService>,
+}
+
+impl Service for BoxedService {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        self.inner.call_boxed(req)
+    }
+}
+
+impl Clone for BoxedService {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone_boxed(),
+        }
+    }
+}
+
+impl fmt::Debug for BoxedService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedService").finish()
+    }
+}
+
+/// Object-safe version of `Service + Clone`, used by [`BoxedService`].
+///
+/// [`BoxedService`]: struct.BoxedService.html
+trait CloneService: Send + Sync {
+    fn call_boxed(&mut self, req: Request<Body>) -> DefaultFuture<Response<Body>, BoxedError>;
+
+    fn clone_boxed(&self) -> Box<dyn CloneService>;
+}
+
+impl<S> CloneService for S
 where
-    S: Service<ResBody = Body, Error = BoxedError> + Clone + Sync,
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + Clone + Send + Sync + 'static,
     S::Future: Send + 'static,
-    R: IntoFuture<Item = Response<Body>, Error = BoxedError>,
-    R::Future: Send + 'static,
-    H: Fn(Box<dyn Any + Send>) -> R + Send + Sync + 'static,
 {
-    fn clone(&self) -> Self {
-        CatchUnwind {
-            inner: self.inner.clone(),
-            handler: self.handler.clone(),
+    fn call_boxed(&mut self, req: Request<Body>) -> DefaultFuture<Response<Body>, BoxedError> {
+        Box::new(self.call(req))
+    }
+
+    fn clone_boxed(&self) -> Box<dyn CloneService> {
+        Box::new(self.clone())
+    }
+}
+
+/// Dispatches requests to services mounted under path prefixes.
+///
+/// A single [`FromRequest`] implementor describes a set of routes that is
+/// fixed at compile time. `Router` combines several services (typically
+/// [`AsyncService`]s or [`SyncService`]s, each decoding its own route type)
+/// at runtime, eg. when plugins register their routes during startup.
+///
+/// # Prefix Matching
+///
+/// A prefix only matches whole path segments: a service mounted at `/api`
+/// receives requests for `/api` and `/api/users`, but not for `/apiary`. When
+/// the prefixes of several services match, the longest one wins, so a service
+/// mounted at `/api/v2` takes precedence over one mounted at `/api`.
+///
+/// The matched prefix is removed from the request URI before the request is
+/// passed to the service, so the routes of a service mounted at `/api` are
+/// declared as `/users`. A request for the prefix itself is passed on with a
+/// path of `/`. The query string is preserved.
+///
+/// Requests that don't match any prefix are passed to the [`fallback`]
+/// service, or answered with `404 Not Found` if there is none.
+///
+/// # Examples
+///
+/// ```
+/// use hyperdrive::{FromRequest, service::{Router, ServiceExt, SyncService}};
+/// use hyper::Server;
+///
+/// #[derive(FromRequest)]
+/// enum Users {
+///     #[get("/{id}")]
+///     User { id: u32 },
+///
+///     #[get("/{id}/posts")]
+///     Posts { id: u32 },
+/// }
+///
+/// #[derive(FromRequest)]
+/// enum Admin {
+///     #[get("/stats")]
+///     Stats,
+/// }
+///
+/// let router = Router::new()
+///     // Handles `/users/{id}` and `/users/{id}/posts`
+///     .mount("/users", SyncService::responding(|route: Users, _| match route {
+///         Users::User { id } => format!("user #{}", id),
+///         Users::Posts { id } => format!("posts of user #{}", id),
+///     }))
+///     // Handles `/admin/stats`
+///     .mount("/admin", SyncService::responding(|route: Admin, _| match route {
+///         Admin::Stats => "stats",
+///     }));
+///
+/// let srv = Server::bind(&"127.0.0.1:0".parse().unwrap())
+///     .serve(router.make_service_by_cloning());
+/// ```
+///
+/// [`FromRequest`]: ../trait.FromRequest.html
+/// [`AsyncService`]: struct.AsyncService.html
+/// [`SyncService`]: struct.SyncService.html
+/// [`fallback`]: #method.fallback
+#[derive(Clone, Default)]
+pub struct Router {
+    /// Sorted by descending prefix length, so the first match is the longest.
+    mounts: Vec<(String, BoxedService)>,
+    fallback: Option<BoxedService>,
+}
+
+impl Router {
+    /// Creates a router without any mounted services.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts `service` at `prefix`.
+    ///
+    /// See the [type documentation] for how prefixes are matched.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `prefix` doesn't start with a `/`, ends with a `/`,
+    /// contains empty segments, a query string or a fragment, or if another
+    /// service is already mounted at the same prefix. Use [`fallback`] to
+    /// handle requests to all paths.
+    ///
+    /// [type documentation]: #prefix-matching
+    /// [`fallback`]: #method.fallback
+    pub fn mount<S>(mut self, prefix: &str, service: S) -> Self
+    where
+        S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        assert!(
+            prefix.starts_with('/'),
+            "mount prefix `{}` must start with `/`",
+            prefix
+        );
+        assert!(
+            prefix != "/",
+            "cannot mount a service at `/`, use `Router::fallback` instead"
+        );
+        assert!(
+            !prefix.ends_with('/'),
+            "mount prefix `{}` must not end with `/`",
+            prefix
+        );
+        assert!(
+            !prefix.contains("//") && !prefix.contains('?') && !prefix.contains('#'),
+            "mount prefix `{}` must not contain empty segments, `?` or `#`",
+            prefix
+        );
+        assert!(
+            self.mounts.iter().all(|(mounted, _)| mounted != prefix),
+            "a service is already mounted at `{}`",
+            prefix
+        );
+
+        let index = self
+            .mounts
+            .iter()
+            .position(|(mounted, _)| mounted.len() < prefix.len())
+            .unwrap_or(self.mounts.len());
+        self.mounts
+            .insert(index, (prefix.to_string(), service.boxed()));
+        self
+    }
+
+    /// Sets the service that handles requests not matching any mounted prefix.
+    ///
+    /// The request is passed to `service` unchanged. Without a fallback, these
+    /// requests are answered with `404 Not Found`.
+    pub fn fallback<S>(mut self, service: S) -> Self
+    where
+        S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.fallback = Some(service.boxed());
+        self
+    }
+}
+
+impl Service for Router {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = DefaultFuture<Response<Body>, BoxedError>;
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        let path = req.uri().path();
+        let mount = self
+            .mounts
+            .iter_mut()
+            .find(|(prefix, _)| matches_prefix(path, prefix));
+
+        match (mount, &mut self.fallback) {
+            (Some((prefix, service)), _) => {
+                *req.uri_mut() = strip_prefix(req.uri(), prefix.len());
+                service.call(req)
+            }
+            (None, Some(fallback)) => fallback.call(req),
+            (None, None) => {
+                let response = Error::from_status(StatusCode::NOT_FOUND)
+                    .response()
+                    .map(|()| Body::empty());
+                Box::new(Ok(response).into_future())
+            }
         }
     }
 }
 
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefixes = self
+            .mounts
+            .iter()
+            .map(|(prefix, _)| prefix)
+            .collect::<Vec<_>>();
+        f.debug_struct("Router")
+            .field("prefixes", &prefixes)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+/// Removes the first `len` bytes (a mount prefix) from the path of `uri`.
+fn strip_prefix(uri: &Uri, len: usize) -> Uri {
+    let path = match &uri.path()[len..] {
+        "" => "/",
+        path => path,
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .expect("stripping a prefix produced an invalid path"),
+    );
+    Uri::from_parts(parts).expect("stripping a prefix produced an invalid URI")
+}
+
 /// Implements Hyper's `MakeService` trait by cloning a service `S` for every
 /// incoming connection.
 ///
@@ -665,3 +8827,228 @@ impl<Ctx, S: Service + Clone> MakeService<Ctx> for MakeServiceByCloning<S> {
         Ok(self.service.clone()).into_future()
     }
 }
+
+impl<S: Service + Clone> MakeServiceByCloning<S> {
+    /// Calls `hook` for every accepted connection, and inserts the data it
+    /// returns into the extensions of every request received on that
+    /// connection.
+    ///
+    /// `hook` is called with the connection (eg. Hyper's `AddrStream`) before
+    /// `S` is cloned for it. This can be used to record information about the
+    /// connection, update statistics, or set up per-connection state. Routes
+    /// can access the data via the [`Extension`] guard. Returning a
+    /// [`ConnectInfo`] from the hook makes the [`RemoteAddr`] guard work for
+    /// connection types that don't implement [`Connected`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyperdrive::{FromRequest, guard::Extension, service::{ServiceExt, SyncService}};
+    /// use hyper::{Server, server::conn::AddrStream};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// #[derive(Debug, Copy, Clone)]
+    /// struct ConnectionId(usize);
+    ///
+    /// #[derive(FromRequest)]
+    /// enum Route {
+    ///     #[get("/")]
+    ///     Index { id: Extension<ConnectionId> },
+    /// }
+    ///
+    /// let connections = Arc::new(AtomicUsize::new(0));
+    /// let service = SyncService::responding(|Route::Index { id }, _| {
+    ///     format!("Connection #{}", id.into_inner().0)
+    /// })
+    /// .make_service_by_cloning()
+    /// .with_connection_hook(move |_: &AddrStream| {
+    ///     ConnectionId(connections.fetch_add(1, Ordering::SeqCst))
+    /// });
+    ///
+    /// let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    /// ```
+    ///
+    /// [`Extension`]: ../guard/struct.Extension.html
+    /// [`ConnectInfo`]: struct.ConnectInfo.html
+    /// [`RemoteAddr`]: ../guard/struct.RemoteAddr.html
+    /// [`Connected`]: trait.Connected.html
+    pub fn with_connection_hook<F>(self, hook: F) -> ConnectionHook<S, F> {
+        ConnectionHook {
+            service: self.service,
+            hook,
+        }
+    }
+}
+
+/// Implements Hyper's `MakeService` trait by calling a hook for every incoming
+/// connection and cloning a service `S` for it.
+///
+/// Returned by [`MakeServiceByCloning::with_connection_hook`].
+///
+/// [`MakeServiceByCloning::with_connection_hook`]: struct.MakeServiceByCloning.html#method.with_connection_hook
+#[derive(Copy, Clone)]
+pub struct ConnectionHook<S: Service + Clone, F> {
+    service: S,
+    hook: F,
+}
+
+impl<'a, C, S, F, D> MakeService<&'a C> for ConnectionHook<S, F>
+where
+    C: ?Sized,
+    S: Service + Clone,
+    F: Fn(&C) -> D,
+    D: Clone + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Service = WithConnectionData<S, D>;
+    type Future = FutureResult<Self::Service, Self::MakeError>;
+    type MakeError = BoxedError;
+
+    fn make_service(&mut self, conn: &'a C) -> Self::Future {
+        Ok(WithConnectionData {
+            inner: self.service.clone(),
+            data: (self.hook)(conn),
+        })
+        .into_future()
+    }
+}
+
+impl<S, F> fmt::Debug for ConnectionHook<S, F>
+where
+    S: Service + Clone + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionHook")
+            .field("service", &self.service)
+            .field("hook", &"<closure>")
+            .finish()
+    }
+}
+
+/// A `Service` adapter that inserts the data returned by a connection hook
+/// into every request.
+///
+/// This is the service created by [`ConnectionHook`].
+///
+/// [`ConnectionHook`]: struct.ConnectionHook.html
+#[derive(Debug, Clone)]
+pub struct WithConnectionData<S, D> {
+    inner: S,
+    data: D,
+}
+
+impl<S, D> Service for WithConnectionData<S, D>
+where
+    S: Service,
+    D: Clone + Send + Sync + 'static,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        req.extensions_mut().insert(self.data.clone());
+        self.inner.call(req)
+    }
+}
+
+/// A connection accepted by a Hyper server.
+///
+/// [`ServiceExt::with_connect_info`] uses this to obtain the [`ConnectInfo`]
+/// of every connection. It is implemented for Hyper's `AddrStream`, which is
+/// used when serving via `Server::bind`. When using a custom connection type
+/// (eg. for TLS), implement this trait for it.
+///
+/// [`ServiceExt::with_connect_info`]: trait.ServiceExt.html#tymethod.with_connect_info
+/// [`ConnectInfo`]: struct.ConnectInfo.html
+pub trait Connected {
+    /// Returns the address of the remote peer, if known.
+    fn remote_addr(&self) -> Option<SocketAddr>;
+}
+
+impl Connected for AddrStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(AddrStream::remote_addr(self))
+    }
+}
+
+/// Information about the connection a request was received on.
+///
+/// This is inserted into the extensions of every request by services created
+/// via [`ServiceExt::with_connect_info`]. The [`RemoteAddr`] guard provides
+/// access to it.
+///
+/// [`ServiceExt::with_connect_info`]: trait.ServiceExt.html#tymethod.with_connect_info
+/// [`RemoteAddr`]: ../guard/struct.RemoteAddr.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectInfo(pub SocketAddr);
+
+/// Implements Hyper's `MakeService` trait by cloning a service `S` for every
+/// incoming connection, and inserting the [`ConnectInfo`] of the connection
+/// into every request.
+///
+/// This type is returned by [`ServiceExt::with_connect_info`].
+///
+/// [`ConnectInfo`]: struct.ConnectInfo.html
+/// [`ServiceExt::with_connect_info`]: trait.ServiceExt.html#tymethod.with_connect_info
+#[derive(Debug, Copy, Clone)]
+pub struct MakeWithConnectInfo<S: Service + Clone> {
+    service: S,
+}
+
+impl<'a, C, S> MakeService<&'a C> for MakeWithConnectInfo<S>
+where
+    C: Connected + ?Sized,
+    S: Service + Clone,
+{
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Service = WithConnectInfo<S>;
+    type Future = FutureResult<Self::Service, Self::MakeError>;
+    type MakeError = BoxedError;
+
+    fn make_service(&mut self, conn: &'a C) -> Self::Future {
+        Ok(WithConnectInfo::new(self.service.clone(), conn)).into_future()
+    }
+}
+
+/// A `Service` adapter that inserts the [`ConnectInfo`] of its connection into
+/// every request.
+///
+/// This is the service created by [`MakeWithConnectInfo`].
+///
+/// [`ConnectInfo`]: struct.ConnectInfo.html
+/// [`MakeWithConnectInfo`]: struct.MakeWithConnectInfo.html
+#[derive(Debug, Clone)]
+pub struct WithConnectInfo<S> {
+    inner: S,
+    info: Option<ConnectInfo>,
+}
+
+impl<S> WithConnectInfo<S> {
+    fn new<C: Connected + ?Sized>(inner: S, conn: &C) -> Self {
+        Self {
+            inner,
+            info: conn.remote_addr().map(ConnectInfo),
+        }
+    }
+}
+
+impl<S: Service> Service for WithConnectInfo<S> {
+    type ReqBody = S::ReqBody;
+    type ResBody = S::ResBody;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn call(&mut self, mut req: Request<Self::ReqBody>) -> Self::Future {
+        if let Some(info) = self.info {
+            req.extensions_mut().insert(info);
+        }
+        self.inner.call(req)
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A value that can be replaced while it is being used, eg. for reloading
+/// configuration without restarting the server.
+///
+/// The context of a service is created once and can't be modified afterwards.
+/// Values that need to change at runtime, like feature flags or allow-lists,
+/// can be stored in a `Shared` field of the context instead. [`load`] returns
+/// a snapshot of the current value, and [`store`] replaces it. Requests that
+/// already loaded the old value keep using it, while all later calls to
+/// [`load`] observe the new one.
+///
+/// Cloning a `Shared` yields another handle to the same value, so the value
+/// can also be replaced from outside of the server (eg. by a thread watching a
+/// configuration file) using a clone created before the service was built.
+///
+/// # Examples
+///
+/// `Shared` can be used as an `#[as_ref]` field of a context. Guards then use
+/// it as their context and call [`load`] to access the current value:
+///
+/// ```
+/// use hyperdrive::{Guard, RequestContext, Shared, BoxedError, Error};
+/// use http::StatusCode;
+/// use std::sync::Arc;
+///
+/// struct AllowList(Vec<String>);
+///
+/// #[derive(RequestContext)]
+/// struct Context {
+///     #[as_ref]
+///     allowed: Shared<AllowList>,
+/// }
+///
+/// /// Only admits clients sending an allowed `X-Client` header.
+/// struct AllowedClient;
+///
+/// impl Guard for AllowedClient {
+///     type Context = Shared<AllowList>;
+///     type Result = Result<Self, BoxedError>;
+///
+///     fn from_request(request: &Arc<http::Request<()>>, allowed: &Shared<AllowList>) -> Self::Result {
+///         let client = request.headers().get("x-client").and_then(|v| v.to_str().ok());
+///         match client {
+///             Some(client) if allowed.load().0.iter().any(|c| c == client) => Ok(AllowedClient),
+///             _ => Err(Error::from_status(StatusCode::FORBIDDEN).into()),
+///         }
+///     }
+/// }
+///
+/// let allowed = Shared::new(AllowList(vec!["dashboard".to_string()]));
+/// let context = Context {
+///     allowed: allowed.clone(),
+/// };
+///
+/// // Later, eg. after the configuration file has changed:
+/// allowed.store(AllowList(vec!["dashboard".to_string(), "cli".to_string()]));
+/// assert_eq!(context.allowed.load().0.len(), 2);
+/// ```
+///
+/// [`load`]: #method.load
+/// [`store`]: #method.store
+pub struct Shared<T> {
+    current: Arc<RwLock<Arc<T>>>,
+}
+
+impl<T> Shared<T> {
+    /// Creates a `Shared` holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(value))),
+        }
+    }
+
+    /// Returns the current value.
+    ///
+    /// The returned snapshot is not affected by later calls to [`store`].
+    ///
+    /// [`store`]: #method.store
+    pub fn load(&self) -> Arc<T> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the current value with `value`.
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Replaces the current value with `value`, returning the previous one.
+    pub fn swap(&self, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, value)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<T: Default> Default for Shared<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Shared<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Shared").field(&self.load()).finish()
+    }
+}
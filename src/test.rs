@@ -0,0 +1,304 @@
+//! Utilities for testing routes and services without starting a server.
+//!
+//! * [`decode`] decodes a [`FromRequest`] implementor from a request built with
+//!   [`RequestBuilder`], which is handy for testing route definitions and
+//!   guards.
+//! * [`Client`] sends requests to a hyper `Service` (eg. an [`AsyncService`])
+//!   in-process and collects the response into a [`TestResponse`].
+//!
+//! Both run their own tokio runtime, so they can be used from ordinary
+//! `#[test]` functions. They must not be called from within a running tokio
+//! runtime.
+//!
+//! # Examples
+//!
+//! ```
+//! use hyperdrive::{FromRequest, service::SyncService, test::{self, Client, RequestBuilder}};
+//! use hyper::StatusCode;
+//!
+//! #[derive(FromRequest, Debug, PartialEq)]
+//! enum Route {
+//!     #[get("/users/{id}")]
+//!     User { id: u32 },
+//! }
+//!
+//! let route = test::decode::<Route>(RequestBuilder::get("/users/7")).unwrap();
+//! assert_eq!(route, Route::User { id: 7 });
+//!
+//! let mut client = Client::new(SyncService::responding(|route: Route, _| match route {
+//!     Route::User { id } => format!("User #{}", id),
+//! }));
+//!
+//! let response = client.send(RequestBuilder::get("/users/7")).unwrap();
+//! assert_eq!(response.status(), StatusCode::OK);
+//! assert_eq!(response.text(), "User #7");
+//!
+//! let response = client.send(RequestBuilder::get("/users/me")).unwrap();
+//! assert_eq!(response.status(), StatusCode::NOT_FOUND);
+//! ```
+//!
+//! [`decode`]: fn.decode.html
+//! [`FromRequest`]: ../trait.FromRequest.html
+//! [`RequestBuilder`]: struct.RequestBuilder.html
+//! [`Client`]: struct.Client.html
+//! [`AsyncService`]: ../service/struct.AsyncService.html
+//! [`TestResponse`]: struct.TestResponse.html
+
+use crate::{BoxedError, FromRequest};
+use futures::{Future, Stream};
+use http::{header, request, HeaderMap, HttpTryFrom, Method, StatusCode};
+use hyper::{service::Service, Body, Request};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+use tokio::runtime::Runtime;
+
+/// Builds a request to send in a test.
+///
+/// Invalid methods, URIs or headers cause a panic when the request is built.
+#[derive(Debug)]
+pub struct RequestBuilder {
+    builder: request::Builder,
+    body: Body,
+}
+
+impl RequestBuilder {
+    /// Creates a builder for a request with the given method and URI.
+    ///
+    /// The URI is usually just a path and query string (`/users?page=2`).
+    pub fn new(method: Method, uri: &str) -> Self {
+        let mut builder = Request::builder();
+        builder.method(method).uri(uri);
+        Self {
+            builder,
+            body: Body::empty(),
+        }
+    }
+
+    /// Creates a builder for a `GET` request.
+    pub fn get(uri: &str) -> Self {
+        Self::new(Method::GET, uri)
+    }
+
+    /// Creates a builder for a `HEAD` request.
+    pub fn head(uri: &str) -> Self {
+        Self::new(Method::HEAD, uri)
+    }
+
+    /// Creates a builder for a `POST` request.
+    pub fn post(uri: &str) -> Self {
+        Self::new(Method::POST, uri)
+    }
+
+    /// Creates a builder for a `PUT` request.
+    pub fn put(uri: &str) -> Self {
+        Self::new(Method::PUT, uri)
+    }
+
+    /// Creates a builder for a `PATCH` request.
+    pub fn patch(uri: &str) -> Self {
+        Self::new(Method::PATCH, uri)
+    }
+
+    /// Creates a builder for a `DELETE` request.
+    pub fn delete(uri: &str) -> Self {
+        Self::new(Method::DELETE, uri)
+    }
+
+    /// Creates a builder for an `OPTIONS` request.
+    pub fn options(uri: &str) -> Self {
+        Self::new(Method::OPTIONS, uri)
+    }
+
+    /// Appends a header to the request.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        header::HeaderName: HttpTryFrom<K>,
+        header::HeaderValue: HttpTryFrom<V>,
+    {
+        self.builder.header(name, value);
+        self
+    }
+
+    /// Inserts a value into the request extensions.
+    ///
+    /// This can be used to provide values that are usually inserted by the
+    /// server, like a [`ConnectInfo`].
+    ///
+    /// [`ConnectInfo`]: ../service/struct.ConnectInfo.html
+    pub fn extension<T>(mut self, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.builder.extension(value);
+        self
+    }
+
+    /// Sets the request body.
+    pub fn body<B: Into<Body>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Serializes `value` as JSON, and uses it as the request body.
+    ///
+    /// This also sets the `Content-Type` header to `application/json`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `value` can not be serialized.
+    pub fn json<T: Serialize>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("couldn't serialize JSON body");
+        self.header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+    }
+
+    /// Builds the request.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if an invalid method, URI or header was passed to the
+    /// builder.
+    pub fn build(mut self) -> Request<Body> {
+        self.builder.body(self.body).expect("invalid test request")
+    }
+}
+
+/// Decodes `request` as a `R`, using the default context.
+///
+/// This builds the request and passes it to [`FromRequest::from_request_sync`].
+///
+/// [`FromRequest::from_request_sync`]: ../trait.FromRequest.html#method.from_request_sync
+pub fn decode<R>(request: RequestBuilder) -> Result<R, BoxedError>
+where
+    R: FromRequest,
+    R::Context: Default,
+{
+    decode_with_context(request, R::Context::default())
+}
+
+/// Decodes `request` as a `R`, using the given context.
+///
+/// This builds the request and passes it to [`FromRequest::from_request_sync`].
+///
+/// [`FromRequest::from_request_sync`]: ../trait.FromRequest.html#method.from_request_sync
+pub fn decode_with_context<R>(request: RequestBuilder, context: R::Context) -> Result<R, BoxedError>
+where
+    R: FromRequest,
+{
+    R::from_request_sync(request.build(), context)
+}
+
+/// Sends requests to a `Service` in-process.
+///
+/// The client owns a tokio runtime that drives the service, so both async
+/// services and those using the blocking thread pool (like [`SyncService`])
+/// are supported.
+///
+/// [`SyncService`]: ../service/struct.SyncService.html
+pub struct Client<S> {
+    service: S,
+    runtime: Runtime,
+}
+
+impl<S> Client<S>
+where
+    S: Service<ReqBody = Body, ResBody = Body>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    /// Creates a client sending requests to `service`.
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            runtime: Runtime::new().expect("couldn't start tokio runtime"),
+        }
+    }
+
+    /// Sends `request` to the service and waits for the full response.
+    ///
+    /// Returns an error if the service fails, or if reading the response body
+    /// fails. Hyper would drop the connection in these cases.
+    pub fn send(&mut self, request: RequestBuilder) -> Result<TestResponse, BoxedError>
+    where
+        S::Error: Into<BoxedError>,
+    {
+        let response = self
+            .service
+            .call(request.build())
+            .map_err(Into::into)
+            .and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2()
+                    .map_err(BoxedError::from)
+                    .map(move |body| TestResponse {
+                        status: parts.status,
+                        headers: parts.headers,
+                        body: body.to_vec(),
+                    })
+            });
+
+        self.runtime.block_on(response)
+    }
+
+    /// Returns a mutable reference to the service.
+    pub fn service_mut(&mut self) -> &mut S {
+        &mut self.service
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Client<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+/// A response received by a [`Client`], with the body read into memory.
+///
+/// [`Client`]: struct.Client.html
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the response headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the first value of the header `name` as a string.
+    ///
+    /// Returns `None` if the header is missing, or if its value isn't a valid
+    /// string.
+    pub fn header<K: header::AsHeaderName>(&self, name: K) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// Returns the response body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the response body as a string.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the body is not valid UTF-8.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("response body is not valid UTF-8")
+    }
+
+    /// Deserializes the response body as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
@@ -0,0 +1,85 @@
+//! Detects guards that can be evaluated without a runtime.
+//!
+//! This is used by the code generated by `#[derive(FromRequest)]` to implement
+//! `FromRequest::try_from_request_parts`. A guard is synchronous if its
+//! `Result` type is a plain `Result`, which is detected the same way as in the
+//! [`select_context`] module: `TrySync` has an implementation for `&TrySync`
+//! when the type is a `Result`, and a fallback for everything else. The calls
+//! must look like this, with all traits in scope:
+//!
+//! ```ignore
+//! let sync = match (&&TrySync::<R>::default()).check() {
+//!     Some(sync) => sync,
+//!     None => return None,
+//! };
+//! // ...
+//! sync.result(result)
+//! ```
+//!
+//! `check` returns a proof that the guard is synchronous. The fallback can't
+//! create one, since its proof type [`Asynchronous`] has no values, so the
+//! result of an asynchronous guard can never be passed to `result`.
+//!
+//! In generic code, the guard result is a type parameter, so the fallback is
+//! always used.
+//!
+//! [`select_context`]: ../select_context/index.html
+//! [`Asynchronous`]: enum.Asynchronous.html
+
+use crate::BoxedError;
+use futures::IntoFuture;
+use std::marker::PhantomData;
+
+/// Inspects the `Result` type `R` of a guard.
+#[derive(Debug)]
+pub struct TrySync<R>(PhantomData<fn(R)>);
+
+impl<R> Default for TrySync<R> {
+    fn default() -> Self {
+        TrySync(PhantomData)
+    }
+}
+
+/// Proof that a guard returns a `Result<T, BoxedError>`.
+#[derive(Debug)]
+pub struct Synchronous<T>(PhantomData<fn(T)>);
+
+impl<T> Synchronous<T> {
+    /// Returns the result of the guard.
+    pub fn result(&self, result: Result<T, BoxedError>) -> Result<T, BoxedError> {
+        result
+    }
+}
+
+/// The proof returned for asynchronous guards, which can't be constructed.
+#[derive(Debug)]
+pub enum Asynchronous {}
+
+impl Asynchronous {
+    /// Can't be called, since there are no values of `Asynchronous`.
+    pub fn result<R: IntoFuture>(&self, _: R) -> Result<R::Item, R::Error> {
+        match *self {}
+    }
+}
+
+/// Applies to guards returning a `Result`.
+pub trait TrySyncResult<T> {
+    fn check(&self) -> Option<Synchronous<T>>;
+}
+
+impl<T> TrySyncResult<T> for &TrySync<Result<T, BoxedError>> {
+    fn check(&self) -> Option<Synchronous<T>> {
+        Some(Synchronous(PhantomData))
+    }
+}
+
+/// Applies to all other guards, which have to be run on a runtime.
+pub trait TrySyncFallback {
+    fn check(&self) -> Option<Asynchronous>;
+}
+
+impl<R: IntoFuture> TrySyncFallback for TrySync<R> {
+    fn check(&self) -> Option<Asynchronous> {
+        None
+    }
+}
@@ -0,0 +1,282 @@
+//! Accepts WebSocket connections.
+//!
+//! This module is only available when the `websocket` feature is enabled.
+//!
+//! A WebSocket connection starts out as a `GET` request asking to upgrade the
+//! connection, so it is routed and decoded like any other request. Adding a
+//! [`WsUpgrade`] guard to a route checks that the request is a valid WebSocket
+//! handshake. The handler then calls [`WsUpgrade::handshake_response`] with
+//! the code that talks to the client, and returns the `101 Switching
+//! Protocols` response created by it. Since the other guards of the route run
+//! first, requests can be authenticated before the connection is upgraded.
+//!
+//! Upgrading a connection requires the request to be dispatched by
+//! [`AsyncService`], [`SyncService`] or [`LocalAsyncService`] (see
+//! [`PendingUpgrade`]), which has to be served over HTTP/1.1 by a hyper
+//! `Server` (eg. using [`hyperdrive::serve`]).
+//!
+//! # Examples
+//!
+//! An echo server:
+//!
+//! ```
+//! use hyperdrive::{FromRequest, service::SyncService, ws::WsUpgrade};
+//! use futures::{Future, Stream};
+//!
+//! #[derive(FromRequest)]
+//! enum Route {
+//!     #[get("/echo")]
+//!     Echo { upgrade: WsUpgrade },
+//! }
+//!
+//! let service = SyncService::new(|route: Route, _| match route {
+//!     Route::Echo { upgrade } => upgrade.handshake_response(|socket| {
+//!         let (sink, stream) = socket.split();
+//!         stream
+//!             .take_while(|message| Ok(!message.is_close()))
+//!             .forward(sink)
+//!             .map(|_| ())
+//!     }),
+//! });
+//! ```
+//!
+//! [`WsUpgrade`]: struct.WsUpgrade.html
+//! [`WsUpgrade::handshake_response`]: struct.WsUpgrade.html#method.handshake_response
+//! [`AsyncService`]: ../service/struct.AsyncService.html
+//! [`SyncService`]: ../service/struct.SyncService.html
+//! [`LocalAsyncService`]: ../service/struct.LocalAsyncService.html
+//! [`PendingUpgrade`]: ../service/struct.PendingUpgrade.html
+//! [`hyperdrive::serve`]: ../fn.serve.html
+
+use crate::{response::IntoResponse, service::PendingUpgrade, BoxedError, Error, Guard, NoContext};
+use futures::{Async, AsyncSink, Future, IntoFuture, Poll, Sink, StartSend, Stream};
+use http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode};
+use hyper::{
+    upgrade::{OnUpgrade, Upgraded},
+    Body,
+};
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use tokio::executor::{DefaultExecutor, Executor};
+use tungstenite::protocol::{Role, WebSocket as Protocol};
+
+pub use tungstenite::{self, Error as WsError, Message};
+
+/// Appended to the `Sec-WebSocket-Key` to compute the `Sec-WebSocket-Accept`
+/// header, as specified by RFC 6455.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A guard that accepts WebSocket handshake requests.
+///
+/// The guard checks the `Connection`, `Upgrade`, `Sec-WebSocket-Version` and
+/// `Sec-WebSocket-Key` headers of the request, and takes the
+/// [`PendingUpgrade`] out of its extensions. It fails with:
+///
+/// * `426 Upgrade Required` if the request doesn't ask for a WebSocket
+///   upgrade, or asks for a protocol version other than 13. The response
+///   tells the client what is required.
+/// * `400 Bad Request` if the `Sec-WebSocket-Key` is missing or malformed.
+/// * `500 Internal Server Error` if the connection can't be upgraded, because
+///   the request wasn't dispatched by a service that provides the
+///   [`PendingUpgrade`].
+///
+/// Refer to the [module documentation] for an example.
+///
+/// [`PendingUpgrade`]: ../service/struct.PendingUpgrade.html
+/// [module documentation]: index.html
+pub struct WsUpgrade {
+    accept: HeaderValue,
+    upgrade: OnUpgrade,
+}
+
+impl WsUpgrade {
+    /// Accepts the WebSocket connection, passing it to `handler` once the
+    /// connection has been upgraded.
+    ///
+    /// Returns the `101 Switching Protocols` response that has to be sent to
+    /// the client to complete the handshake. `handler` is spawned on the
+    /// default executor, and runs after the response was sent. Errors returned
+    /// by it are logged.
+    ///
+    /// If there is no default executor (ie. when not running on a tokio
+    /// runtime), a `500 Internal Server Error` response is returned instead.
+    pub fn handshake_response<F, R>(self, handler: F) -> Response<Body>
+    where
+        F: FnOnce(WsStream) -> R + Send + 'static,
+        R: IntoFuture<Item = ()>,
+        R::Future: Send + 'static,
+        R::Error: fmt::Display,
+    {
+        let task = self
+            .upgrade
+            .map_err(|e| log::error!("couldn't upgrade connection to WebSocket: {}", e))
+            .and_then(move |upgraded| {
+                handler(WsStream::new(upgraded))
+                    .into_future()
+                    .map_err(|e| log::error!("WebSocket handler failed: {}", e))
+            });
+        if let Err(e) = DefaultExecutor::current().spawn(Box::new(task)) {
+            log::error!("couldn't spawn WebSocket handler: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(header::SEC_WEBSOCKET_ACCEPT, self.accept);
+        response
+    }
+}
+
+impl Guard for WsUpgrade {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        let headers = request.headers();
+        if !has_token(headers, header::CONNECTION, "upgrade")
+            || !has_token(headers, header::UPGRADE, "websocket")
+            || headers.get(header::SEC_WEBSOCKET_VERSION) != Some(&HeaderValue::from_static("13"))
+        {
+            return Err(Error::builder(StatusCode::UPGRADE_REQUIRED)
+                .message("expected a WebSocket handshake (version 13)")
+                .header(header::CONNECTION, "Upgrade")
+                .header(header::UPGRADE, "websocket")
+                .header(header::SEC_WEBSOCKET_VERSION, "13")
+                .build()
+                .expect("invalid WebSocket handshake headers")
+                .into());
+        }
+
+        // The key is 16 random bytes, encoded in base64
+        let key = headers
+            .get(header::SEC_WEBSOCKET_KEY)
+            .filter(|key| base64::decode(key.as_bytes()).ok().map(|key| key.len()) == Some(16))
+            .ok_or_else(|| {
+                Error::with_status(StatusCode::BAD_REQUEST, "invalid `Sec-WebSocket-Key`")
+            })?;
+
+        let upgrade = PendingUpgrade::take(request).ok_or_else(|| {
+            Error::with_source(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "connection can't be upgraded",
+            )
+        })?;
+
+        Ok(WsUpgrade {
+            accept: accept_key(key.as_bytes()),
+            upgrade,
+        })
+    }
+}
+
+impl fmt::Debug for WsUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WsUpgrade")
+            .field("accept", &self.accept)
+            .finish()
+    }
+}
+
+/// Returns whether the comma-separated header `name` contains `token`,
+/// ignoring case.
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Computes the `Sec-WebSocket-Accept` header for a `Sec-WebSocket-Key`.
+fn accept_key(key: &[u8]) -> HeaderValue {
+    let mut sha1 = Sha1::new();
+    sha1.input(key);
+    sha1.input(ACCEPT_GUID.as_bytes());
+    HeaderValue::from_str(&base64::encode(&sha1.result())).expect("base64 is a valid header value")
+}
+
+/// An accepted WebSocket connection.
+///
+/// This is a `Stream` of the [`Message`]s received from the client, and a
+/// `Sink` of the messages sent to it. Ping messages are answered
+/// automatically. The stream ends once the connection was closed.
+///
+/// [`Message`]: enum.Message.html
+pub struct WsStream {
+    inner: Protocol<Upgraded>,
+}
+
+impl WsStream {
+    fn new(upgraded: Upgraded) -> Self {
+        Self {
+            inner: Protocol::from_raw_socket(upgraded, Role::Server, None),
+        }
+    }
+}
+
+impl Stream for WsStream {
+    type Item = Message;
+    type Error = WsError;
+
+    fn poll(&mut self) -> Poll<Option<Message>, WsError> {
+        match self.inner.read_message() {
+            Ok(message) => Ok(Async::Ready(Some(message))),
+            Err(WsError::ConnectionClosed) => Ok(Async::Ready(None)),
+            Err(e) => would_block(e),
+        }
+    }
+}
+
+impl Sink for WsStream {
+    type SinkItem = Message;
+    type SinkError = WsError;
+
+    fn start_send(&mut self, message: Message) -> StartSend<Message, WsError> {
+        match self.inner.write_message(message) {
+            Ok(()) => Ok(AsyncSink::Ready),
+            Err(WsError::SendQueueFull(message)) => Ok(AsyncSink::NotReady(message)),
+            // The message was queued, and is sent by `poll_complete`
+            Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                Ok(AsyncSink::Ready)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), WsError> {
+        match self.inner.write_pending() {
+            Ok(()) => Ok(Async::Ready(())),
+            Err(e) => would_block(e),
+        }
+    }
+
+    fn close(&mut self) -> Poll<(), WsError> {
+        match self.inner.close(None) {
+            Ok(()) | Err(WsError::ConnectionClosed) => Ok(Async::Ready(())),
+            Err(e) => would_block(e),
+        }
+    }
+}
+
+impl fmt::Debug for WsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WsStream").finish()
+    }
+}
+
+/// Turns an I/O error of kind `WouldBlock` into `NotReady`.
+///
+/// `Upgraded` registers the current task for wakeup before returning such an
+/// error from its blocking `Read` and `Write` implementations.
+fn would_block<T>(error: WsError) -> Poll<T, WsError> {
+    match error {
+        WsError::Io(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+        e => Err(e),
+    }
+}
@@ -0,0 +1,109 @@
+//! Tests parsing `Accept-Encoding` with `guard::AcceptEncoding`.
+
+use hyperdrive::{
+    guard::AcceptEncoding,
+    http::HeaderValue,
+    test::{decode, RequestBuilder},
+    FromRequest,
+};
+
+fn parse(header: &str) -> AcceptEncoding {
+    AcceptEncoding::parse(&[HeaderValue::from_str(header).unwrap()])
+}
+
+#[test]
+fn missing_header() {
+    #[derive(FromRequest)]
+    enum Route {
+        #[get("/")]
+        Index { encoding: AcceptEncoding },
+    }
+
+    let Route::Index { encoding } = decode(RequestBuilder::get("/")).unwrap();
+    assert!(encoding.preferences().is_empty());
+    assert_eq!(encoding.quality("identity"), 1.0);
+    assert_eq!(encoding.quality("gzip"), 0.0);
+    assert_eq!(encoding.negotiate(&["br", "gzip"]), None);
+    assert_eq!(encoding.negotiate(&["br", "identity"]), Some("identity"));
+    assert!(!encoding.requires_encoding());
+
+    // An empty header means the same
+    assert_eq!(parse(""), encoding);
+
+    let request = RequestBuilder::get("/").header("Accept-Encoding", "gzip;q=0.5, BR");
+    let Route::Index { encoding } = decode(request).unwrap();
+    assert_eq!(encoding.negotiate(&["gzip", "br"]), Some("br"));
+}
+
+/// The examples from RFC 7231, section 5.3.4.
+#[test]
+fn rfc_examples() {
+    let encoding = parse("compress, gzip");
+    assert_eq!(encoding.quality("compress"), 1.0);
+    assert_eq!(encoding.quality("GZIP"), 1.0);
+    assert_eq!(encoding.quality("br"), 0.0);
+    assert_eq!(encoding.quality("identity"), 1.0);
+
+    let encoding = parse("*");
+    assert_eq!(encoding.quality("br"), 1.0);
+    assert_eq!(encoding.quality("identity"), 1.0);
+
+    let encoding = parse("compress;q=0.5, gzip;q=1.0");
+    assert_eq!(
+        encoding.preferences(),
+        &[("gzip".to_string(), 1.0), ("compress".to_string(), 0.5)]
+    );
+    assert_eq!(encoding.negotiate(&["compress", "gzip"]), Some("gzip"));
+
+    let encoding = parse("gzip;q=1.0, identity; q=0.5, *;q=0");
+    assert_eq!(encoding.quality("gzip"), 1.0);
+    assert_eq!(encoding.quality("identity"), 0.5);
+    assert_eq!(encoding.quality("br"), 0.0);
+    assert_eq!(encoding.negotiate(&["br", "identity"]), Some("identity"));
+    assert!(!encoding.requires_encoding());
+}
+
+#[test]
+fn zero_quality() {
+    // `identity;q=0` means that the response must be encoded
+    let encoding = parse("identity;q=0, gzip");
+    assert!(encoding.requires_encoding());
+    assert_eq!(encoding.negotiate(&["br", "gzip"]), Some("gzip"));
+    assert_eq!(encoding.negotiate(&["br", "identity"]), None);
+
+    // So does a zero wildcard if `identity` isn't listed
+    let encoding = parse("br, *;q=0");
+    assert!(encoding.requires_encoding());
+    assert!(!encoding.is_acceptable("gzip"));
+    assert_eq!(encoding.negotiate(&["gzip", "br"]), Some("br"));
+
+    // An explicit `q=0` overrides the wildcard
+    let encoding = parse("br;q=0, *");
+    assert!(!encoding.is_acceptable("br"));
+    assert_eq!(encoding.negotiate(&["br", "gzip"]), Some("gzip"));
+    assert!(!encoding.requires_encoding());
+    assert_eq!(
+        encoding.preferences(),
+        &[("*".to_string(), 1.0), ("br".to_string(), 0.0)]
+    );
+
+    // A wildcard with a lower quality doesn't lower that of `identity`
+    assert_eq!(parse("gzip, *;q=0.1").quality("identity"), 1.0);
+}
+
+#[test]
+fn ties_and_malformed_entries() {
+    // The server's order decides between codings of the same quality
+    let encoding = parse("gzip, br");
+    assert_eq!(encoding.negotiate(&["br", "gzip"]), Some("br"));
+    assert_eq!(encoding.negotiate(&["gzip", "br"]), Some("gzip"));
+
+    // Invalid quality values and empty entries are skipped, and only the first
+    // occurrence of a coding counts
+    let encoding = parse("br;q=2, , gzip;q=abc, deflate;q=0.3, deflate;q=0.9");
+    assert_eq!(encoding.preferences(), &[("deflate".to_string(), 0.3)]);
+    assert_eq!(
+        encoding.negotiate(&["br", "gzip", "deflate"]),
+        Some("deflate")
+    );
+}
@@ -0,0 +1,141 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{Method, Request, Response, StatusCode},
+    hyper::{
+        service::{service_fn, Service},
+        Body,
+    },
+    service::{AsyncService, RequestInfo, ResponseInfo, ServiceExt},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use log::{Log, Metadata, Record};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    AsyncService::new(|Routes::User { id }, _| {
+        Ok(Response::new(Body::from(format!("User #{}", id)))).into_future()
+    })
+}
+
+type Entries = Arc<Mutex<Vec<(RequestInfo, ResponseInfo, Duration)>>>;
+
+fn call<S>(mut service: S, path: &str) -> Result<Response<Body>, BoxedError>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    service.call(request).wait()
+}
+
+fn logged<S>(service: S, path: &str) -> (RequestInfo, ResponseInfo, Duration)
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let entries = Entries::default();
+    let log = entries.clone();
+    let _ = call(
+        service.log(move |request, response, elapsed| {
+            log.lock()
+                .unwrap()
+                .push((request.clone(), response.clone(), elapsed));
+        }),
+        path,
+    );
+
+    let mut entries = entries.lock().unwrap();
+    assert_eq!(entries.len(), 1);
+    entries.pop().unwrap()
+}
+
+#[test]
+fn success() {
+    let (request, response, _) = logged(app(), "/users/5");
+    assert_eq!(request.method(), Method::GET);
+    assert_eq!(request.path(), "/users/5");
+    assert!(!request.has_query());
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.size_hint(), Some("User #5".len() as u64));
+    assert!(!response.is_dropped());
+}
+
+#[test]
+fn query() {
+    let (request, _, _) = logged(app(), "/users/5?token=secret");
+    assert_eq!(request.path(), "/users/5");
+    assert!(request.has_query());
+}
+
+#[test]
+fn not_found() {
+    let (_, response, _) = logged(app(), "/");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(!response.is_dropped());
+}
+
+#[test]
+fn inner_error() {
+    let service = service_fn(|_: Request<Body>| -> Result<Response<Body>, BoxedError> {
+        Err("inner failure".into())
+    });
+    let (_, response, _) = logged(service, "/users/5");
+    assert!(response.status().is_server_error());
+    assert!(response.is_dropped());
+}
+
+/// Collects the access log messages emitted through the `log` crate.
+#[derive(Default)]
+struct VecLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for VecLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "hyperdrive::access"
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            assert_eq!(record.level(), log::Level::Info);
+            self.messages
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn log_default() {
+    // This is the only test that installs a global logger
+    let logger: &'static VecLogger = Box::leak(Box::new(VecLogger::default()));
+    log::set_logger(logger).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    call(app().log_default(), "/users/5?token=secret").unwrap();
+    call(app().log_default(), "/").unwrap();
+
+    let messages = logger.messages.lock().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert!(
+        messages[0].starts_with("GET /users/5?… 200 7 "),
+        "{}",
+        messages[0]
+    );
+    assert!(!messages[0].contains("secret"), "{}", messages[0]);
+    assert!(messages[1].starts_with("GET / 404 "), "{}", messages[1]);
+}
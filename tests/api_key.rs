@@ -0,0 +1,148 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    guard::{ApiKey, ApiKeyConfig, ApiKeySource, KeyValidator},
+    http::{header::HeaderName, Request, StatusCode},
+    BoxedError, DefaultFuture, Error, FromRequest,
+};
+
+#[derive(FromRequest, Debug)]
+#[context(ApiKeyConfig)]
+enum Routes {
+    #[get("/")]
+    Index { key: ApiKey },
+}
+
+fn invoke(request: Request<&str>, config: ApiKeyConfig) -> Result<ApiKey, BoxedError> {
+    Routes::from_request_bytes(request, config).map(|Routes::Index { key }| key)
+}
+
+fn assert_unauthorized(result: Result<ApiKey, BoxedError>) {
+    let error: Box<Error> = result.unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn header() {
+    let key = invoke(
+        Request::get("/")
+            .header("X-Api-Key", "abc")
+            .body("")
+            .unwrap(),
+        ApiKeyConfig::new(),
+    )
+    .unwrap();
+    assert_eq!(key.key(), "abc");
+    assert_eq!(
+        key.source(),
+        &ApiKeySource::Header(HeaderName::from_static("x-api-key"))
+    );
+    assert!(!key.is_from_query());
+}
+
+#[test]
+fn authorization() {
+    let key = invoke(
+        Request::get("/")
+            .header("Authorization", "ApiKey abc")
+            .body("")
+            .unwrap(),
+        ApiKeyConfig::new(),
+    )
+    .unwrap();
+    assert_eq!(key.key(), "abc");
+    assert_eq!(key.source(), &ApiKeySource::Authorization);
+
+    // Other schemes are ignored
+    assert_unauthorized(invoke(
+        Request::get("/")
+            .header("Authorization", "Bearer abc")
+            .body("")
+            .unwrap(),
+        ApiKeyConfig::new(),
+    ));
+}
+
+#[test]
+fn query_param() {
+    let key = invoke(
+        Request::get("/?page=1&api_key=a%20b").body("").unwrap(),
+        ApiKeyConfig::new(),
+    )
+    .unwrap();
+    assert_eq!(key.key(), "a b");
+    assert!(key.is_from_query());
+}
+
+#[test]
+fn priority() {
+    let request = || {
+        Request::get("/?api_key=query")
+            .header("Authorization", "ApiKey authorization")
+            .header("X-Api-Key", "header")
+            .body("")
+            .unwrap()
+    };
+
+    let key = invoke(request(), ApiKeyConfig::new()).unwrap();
+    assert_eq!(key.key(), "header");
+
+    let config = ApiKeyConfig::new().sources(vec![
+        ApiKeySource::QueryParam("api_key".to_string()),
+        ApiKeySource::Authorization,
+    ]);
+    let key = invoke(request(), config).unwrap();
+    assert_eq!(key.key(), "query");
+
+    // Sources that aren't configured are never consulted
+    let config = ApiKeyConfig::new().sources(vec![ApiKeySource::Authorization]);
+    assert_unauthorized(invoke(
+        Request::get("/")
+            .header("X-Api-Key", "header")
+            .body("")
+            .unwrap(),
+        config,
+    ));
+}
+
+#[test]
+fn missing() {
+    assert_unauthorized(invoke(
+        Request::get("/").body("").unwrap(),
+        ApiKeyConfig::new(),
+    ));
+    assert_unauthorized(invoke(
+        Request::get("/").header("X-Api-Key", "").body("").unwrap(),
+        ApiKeyConfig::new(),
+    ));
+}
+
+#[test]
+fn validator() {
+    let config = || ApiKeyConfig::new().validator(|key: &str| key == "valid");
+
+    let key = invoke(Request::get("/?api_key=valid").body("").unwrap(), config()).unwrap();
+    assert_eq!(key.key(), "valid");
+
+    assert_unauthorized(invoke(
+        Request::get("/?api_key=invalid").body("").unwrap(),
+        config(),
+    ));
+}
+
+#[test]
+fn async_validator() {
+    struct Failing;
+
+    impl KeyValidator for Failing {
+        fn validate(&self, _key: &str) -> DefaultFuture<bool, BoxedError> {
+            Box::new(Err(BoxedError::from("database unavailable")).into_future())
+        }
+    }
+
+    let err = invoke(
+        Request::get("/?api_key=valid").body("").unwrap(),
+        ApiKeyConfig::new().validator(Failing),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "database unavailable");
+}
@@ -0,0 +1,123 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{Response, StatusCode},
+    hyper::Body,
+    response::{IntoResponse, Json},
+    service::{AsyncService, SyncService},
+    test::{Client, RequestBuilder, TestResponse},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// An application error, rendered as a JSON response.
+#[derive(Debug)]
+enum AppError {
+    OutOfStock { item: u32 },
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response<Body> {
+        match self {
+            AppError::OutOfStock { item } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({ "error": "out of stock", "item": item })),
+            )
+                .into_response(),
+        }
+    }
+}
+
+fn check_stock(item: u32) -> Result<(), AppError> {
+    if item < 100 {
+        Ok(())
+    } else {
+        Err(AppError::OutOfStock { item })
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[post("/orders/{item}")]
+    Order { item: u32 },
+
+    #[get("/fail")]
+    Fail,
+}
+
+/// Creates an error handler that counts its invocations in `calls`.
+fn error_handler(
+    calls: Arc<AtomicUsize>,
+) -> impl Fn(BoxedError) -> Response<Body> + Send + Sync + 'static {
+    move |_| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+}
+
+fn assert_out_of_stock(response: TestResponse) {
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.header("Content-Type"), Some("application/json"));
+    assert_eq!(
+        response.json::<Value>().unwrap(),
+        json!({ "error": "out of stock", "item": 100 })
+    );
+}
+
+#[test]
+fn sync_service() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let service = SyncService::responding(|route: Routes, _| -> Result<_, AppError> {
+        match route {
+            Routes::Order { item } => {
+                check_stock(item)?;
+                Ok(format!("ordered #{}", item))
+            }
+            Routes::Fail => Ok(String::new()),
+        }
+    })
+    .with_error_handler(error_handler(calls.clone()));
+    let mut client = Client::new(service);
+
+    let response = client.send(RequestBuilder::post("/orders/7")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "ordered #7");
+
+    assert_out_of_stock(client.send(RequestBuilder::post("/orders/100")).unwrap());
+
+    // Application errors are never passed to the error handler
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn async_service() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let service = AsyncService::responding(|route: Routes, _| -> DefaultFuture<_, BoxedError> {
+        match route {
+            Routes::Order { item } => Box::new(
+                check_stock(item)
+                    .map(|()| format!("ordered #{}", item))
+                    .into_future()
+                    .then(Ok::<_, BoxedError>),
+            ),
+            Routes::Fail => Box::new(Err("database unavailable".into()).into_future()),
+        }
+    })
+    .with_error_handler(error_handler(calls.clone()));
+    let mut client = Client::new(service);
+
+    let response = client.send(RequestBuilder::post("/orders/7")).unwrap();
+    assert_eq!(response.text(), "ordered #7");
+
+    assert_out_of_stock(client.send(RequestBuilder::post("/orders/100")).unwrap());
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // Errors of the handler's future still reach the error handler
+    let response = client.send(RequestBuilder::get("/fail")).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
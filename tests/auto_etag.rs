@@ -0,0 +1,159 @@
+use futures::stream;
+use hyperdrive::{
+    http::{header, Response, StatusCode},
+    hyper::{service::Service, Body, Chunk},
+    response::{IntoResponse, Json},
+    service::{ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/countries")]
+    Countries,
+
+    #[post("/countries")]
+    AddCountry,
+
+    #[get("/tagged")]
+    Tagged,
+
+    #[get("/stream")]
+    Stream,
+
+    #[get("/missing")]
+    Missing,
+}
+
+fn app(
+    max_size: u64,
+) -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    SyncService::new(|route: Routes, _| match route {
+        Routes::Countries => {
+            let mut response = Json(vec!["de", "fr", "it"]).into_response();
+            let headers = response.headers_mut();
+            headers.insert(header::CACHE_CONTROL, "no-cache".parse().unwrap());
+            headers.insert("X-Generated-By", "hyperdrive".parse().unwrap());
+            response
+        }
+        Routes::AddCountry => Json(vec!["de", "fr", "it", "nl"]).into_response(),
+        Routes::Tagged => Response::builder()
+            .header(header::ETAG, "\"v1\"")
+            .body(Body::from("tagged"))
+            .unwrap(),
+        Routes::Stream => Response::new(Body::wrap_stream(stream::iter_ok::<_, BoxedError>(vec![
+            Chunk::from("a"),
+            Chunk::from("b"),
+        ]))),
+        Routes::Missing => StatusCode::NOT_FOUND.into_response(),
+    })
+    .auto_etag()
+    .max_size(max_size)
+}
+
+#[test]
+fn not_modified() {
+    let mut client = Client::new(app(1024));
+
+    let response = client.send(RequestBuilder::get("/countries")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), r#"["de","fr","it"]"#);
+    let etag = response.header("ETag").unwrap().to_string();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+    // The ETag only depends on the body
+    let response = client.send(RequestBuilder::get("/countries")).unwrap();
+    assert_eq!(response.header("ETag"), Some(&*etag));
+
+    let response = client
+        .send(RequestBuilder::get("/countries").header("If-None-Match", &*etag))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.text(), "");
+    assert_eq!(response.header("ETag"), Some(&*etag));
+    assert_eq!(response.header("Cache-Control"), Some("no-cache"));
+    assert_eq!(response.header("Content-Type"), None);
+    assert_eq!(response.header("X-Generated-By"), None);
+
+    // Weak comparison, and lists of tags
+    let weak = format!("\"other\", W/{}", etag);
+    let response = client
+        .send(RequestBuilder::get("/countries").header("If-None-Match", &*weak))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = client
+        .send(RequestBuilder::get("/countries").header("If-None-Match", "\"other\""))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), r#"["de","fr","it"]"#);
+}
+
+#[test]
+fn head() {
+    let mut client = Client::new(app(1024));
+
+    let etag = client
+        .send(RequestBuilder::get("/countries"))
+        .unwrap()
+        .header("ETag")
+        .unwrap()
+        .to_string();
+
+    let response = client.send(RequestBuilder::head("/countries")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "");
+    assert_eq!(response.header("ETag"), Some(&*etag));
+    assert_eq!(response.header("Content-Length"), Some("16"));
+
+    let response = client
+        .send(RequestBuilder::head("/countries").header("If-None-Match", &*etag))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[test]
+fn post_unaffected() {
+    let mut client = Client::new(app(1024));
+
+    let response = client.send(RequestBuilder::post("/countries")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("ETag"), None);
+
+    let response = client
+        .send(RequestBuilder::post("/countries").header("If-None-Match", "*"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn passed_through() {
+    let mut client = Client::new(app(1024));
+
+    // Existing ETags are kept, even when they match
+    let response = client
+        .send(RequestBuilder::get("/tagged").header("If-None-Match", "\"v1\""))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("ETag"), Some("\"v1\""));
+
+    let response = client.send(RequestBuilder::get("/stream")).unwrap();
+    assert_eq!(response.header("ETag"), None);
+    assert_eq!(response.text(), "ab");
+
+    let response = client
+        .send(RequestBuilder::get("/missing").header("If-None-Match", "*"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("ETag"), None);
+
+    let mut client = Client::new(app(8));
+    let response = client.send(RequestBuilder::get("/countries")).unwrap();
+    assert_eq!(response.header("ETag"), None);
+}
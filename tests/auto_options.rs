@@ -0,0 +1,108 @@
+//! Tests automatic `OPTIONS` responses against a real server.
+
+use futures::{Future, IntoFuture};
+use http::{header, Method, Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::service::{AsyncService, ServiceExt, SyncService};
+use hyperdrive::FromRequest;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    #[patch("/users/{id}")]
+    UpdateUser { id: u32 },
+
+    #[get("/posts")]
+    Posts,
+
+    #[options("/posts")]
+    PostsOptions,
+}
+
+/// Binds a server to a random port and returns the port.
+fn spawn<S>(service: S) -> u16
+where
+    S: ServiceExt<ReqBody = Body, ResBody = Body, Error = hyperdrive::BoxedError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let srv =
+        Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service.make_service_by_cloning());
+    let port = srv.local_addr().port();
+
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    port
+}
+
+fn options(port: u16, path: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .request(
+            Method::OPTIONS,
+            &format!("http://127.0.0.1:{}{}", port, path),
+        )
+        .send()
+        .expect("request failed")
+}
+
+fn handler(route: Route) -> Response<Body> {
+    match route {
+        Route::User { id } | Route::UpdateUser { id } => {
+            Response::new(Body::from(format!("user {}", id)))
+        }
+        Route::Posts => Response::new(Body::from("posts")),
+        Route::PostsOptions => Response::new(Body::from("custom options")),
+    }
+}
+
+#[test]
+fn sync_service() {
+    let port = spawn(SyncService::new(|route: Route, _| handler(route)).auto_options(true));
+
+    let response = options(port, "/users/5");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[header::ALLOW], "GET, HEAD, PATCH");
+
+    // Explicit OPTIONS routes win
+    let mut response = options(port, "/posts");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().unwrap(), "custom options");
+
+    let response = options(port, "/missing");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Other methods still fail with 405
+    let response = reqwest::Client::new()
+        .delete(&format!("http://127.0.0.1:{}/users/5", port))
+        .send()
+        .expect("request failed");
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()[header::ALLOW], "GET, HEAD, PATCH");
+}
+
+#[test]
+fn async_service() {
+    let port = spawn(
+        AsyncService::new(|route: Route, _| Ok(handler(route)).into_future()).auto_options(true),
+    );
+
+    let response = options(port, "/users/5");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()[header::ALLOW], "GET, HEAD, PATCH");
+}
+
+#[test]
+fn disabled_by_default() {
+    let port = spawn(SyncService::new(|route: Route, _| handler(route)));
+
+    let response = options(port, "/users/5");
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
@@ -0,0 +1,163 @@
+use hyperdrive::{
+    body::{HtmlForm, Json},
+    guard::{AcceptedContentEncodings, ContentEncodingConfig},
+    http::{Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{ServiceExt, SyncService},
+    test::{decode_with_context, Client, RequestBuilder},
+    BoxedError, DefaultFuture, Error, ErrorKind, FromBody, FromRequest, NoContext,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// A body that always fails as if the client stopped sending it.
+struct Stalled;
+
+impl FromBody for Stalled {
+    type Context = NoContext;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_body(_: &Arc<http::Request<()>>, _: Body, _: &NoContext) -> Self::Result {
+        Error::body_read_timeout("no data received for 30 seconds").into_future()
+    }
+}
+
+#[derive(Deserialize)]
+struct Login {
+    user: String,
+}
+
+#[derive(FromRequest)]
+#[context(ContentEncodingConfig)]
+enum Routes {
+    #[post("/numbers")]
+    Numbers {
+        #[body]
+        numbers: Json<Vec<u32>>,
+    },
+
+    #[post("/login")]
+    Login {
+        #[body]
+        form: HtmlForm<Login>,
+    },
+
+    #[post("/upload")]
+    Upload { _encoding: AcceptedContentEncodings },
+
+    #[post("/stalled")]
+    Stalled {
+        #[body]
+        _body: Stalled,
+    },
+}
+
+fn client() -> Client<
+    impl Service<
+        ReqBody = Body,
+        ResBody = Body,
+        Error = BoxedError,
+        Future = DefaultFuture<Response<Body>, BoxedError>,
+    >,
+> {
+    let service = SyncService::responding_with_context(
+        |route: Routes, _| match route {
+            Routes::Numbers { numbers } => numbers.iter().sum::<u32>().to_string(),
+            Routes::Login { form } => format!("Welcome, {}!", form.user),
+            Routes::Upload { .. } | Routes::Stalled { .. } => String::new(),
+        },
+        ContentEncodingConfig::new(),
+    );
+    Client::new(service.limit_body(16))
+}
+
+fn kind(request: RequestBuilder) -> ErrorKind {
+    let error = decode_with_context::<Routes>(request, ContentEncodingConfig::new())
+        .err()
+        .unwrap();
+    error.downcast::<Error>().unwrap().kind()
+}
+
+#[test]
+fn malformed_body() {
+    let mut client = client();
+    let response = client
+        .send(RequestBuilder::post("/numbers").body("[1, 2, 3]"))
+        .unwrap();
+    assert_eq!(response.text(), "6");
+
+    let response = client
+        .send(RequestBuilder::post("/numbers").body("[1, 2,"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client
+        .send(RequestBuilder::post("/login").body("name=me"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(
+        kind(RequestBuilder::post("/numbers").body("[1, 2,")),
+        ErrorKind::MalformedBody
+    );
+
+    // The deserialization error is kept as the source
+    let error = decode_with_context::<Routes>(
+        RequestBuilder::post("/login").body("name=me"),
+        ContentEncodingConfig::new(),
+    )
+    .err()
+    .unwrap()
+    .downcast::<Error>()
+    .unwrap();
+    assert!(error.source().unwrap().is::<serde_urlencoded::de::Error>());
+}
+
+#[test]
+fn body_too_large() {
+    let mut client = client();
+
+    // Rejected based on the `Content-Length` header
+    let response = client
+        .send(RequestBuilder::post("/numbers").body("[1, 2, 3, 4, 5, 6, 7, 8]"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Rejected while reading the body
+    let chunks = futures::stream::iter_ok::<_, BoxedError>(vec!["[1, 2, 3, 4,", " 5, 6, 7, 8]"]);
+    let response = client
+        .send(RequestBuilder::post("/numbers").body(Body::wrap_stream(chunks)))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    assert_eq!(
+        Error::body_too_large("too large").kind(),
+        ErrorKind::BodyTooLarge
+    );
+}
+
+#[test]
+fn unsupported_media_type() {
+    let mut client = client();
+    let response = client
+        .send(RequestBuilder::post("/upload").header("Content-Encoding", "br"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(response.header("Accept-Encoding"), Some("identity"));
+
+    assert_eq!(
+        kind(RequestBuilder::post("/upload").header("Content-Encoding", "br")),
+        ErrorKind::UnsupportedMediaType
+    );
+}
+
+#[test]
+fn body_read_timeout() {
+    let mut client = client();
+    let response = client.send(RequestBuilder::post("/stalled")).unwrap();
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+
+    assert_eq!(
+        kind(RequestBuilder::post("/stalled")),
+        ErrorKind::BodyReadTimeout
+    );
+}
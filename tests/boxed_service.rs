@@ -0,0 +1,96 @@
+use futures::Future;
+use http::{header, Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::{
+    service::{AtomicMetrics, BoxedService, SecurityHeaders, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, FromRequest,
+};
+use std::time::Duration;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[get("/panic")]
+    Panic,
+}
+
+/// Stores the app's service, which is why it needs to be nameable.
+struct App {
+    service: BoxedService,
+    metrics: AtomicMetrics,
+}
+
+impl App {
+    fn new() -> Self {
+        let metrics = AtomicMetrics::new();
+        let service = SyncService::responding(|route: Route, _| match route {
+            Route::Index => "index",
+            Route::Panic => panic!("handler panicked"),
+        })
+        .catch_unwind(|_| -> Result<_, BoxedError> {
+            Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("panicked"))
+                .unwrap())
+        })
+        .timeout(Duration::from_secs(10))
+        .metrics(metrics.clone())
+        .security_headers(SecurityHeaders::default())
+        .limit_body(1024)
+        .map_request(|_| {})
+        .boxed();
+
+        Self { service, metrics }
+    }
+}
+
+#[test]
+fn nested_adapters() {
+    let app = App::new();
+    let mut client = Client::new(app.service.clone());
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "index");
+    assert_eq!(
+        response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+        "nosniff"
+    );
+
+    let response = client.send(RequestBuilder::get("/panic")).unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.text(), "panicked");
+
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    assert_eq!(app.metrics.snapshot().requests(), 3);
+}
+
+#[test]
+fn boxed_again() {
+    let service = App::new().service.map_request(|_| {}).boxed();
+    let response = Client::new(service).send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+}
+
+#[test]
+fn server() {
+    let app = App::new();
+    let srv =
+        Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.service.make_service_by_cloning());
+    let port = srv.local_addr().port();
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let url = format!("http://127.0.0.1:{}/", port);
+    let mut response = reqwest::get(&url).expect("request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().unwrap(), "index");
+}
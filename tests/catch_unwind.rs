@@ -16,6 +16,7 @@ enum Route {
 
     /// Accessing this route will panic in a `FromBody` implementation.
     #[get("/panic-body")]
+    #[allow_body_on_get]
     PanicBody {
         #[body]
         _body: PanicBody,
@@ -64,7 +65,7 @@ fn main() {
                 panic!("panic inside the request handler");
             }
         })
-        .catch_unwind(|_panic_payload| {
+        .catch_unwind_with_request(|_panic_payload, request| {
             Ok(Response::builder()
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .header("Content-Type", "text/html")
@@ -78,9 +79,12 @@ fn main() {
                             UwU we made a fucky wucky!! A wittle fucko boingo! The code monkeys at
                             our headquarters are working VEWY HAWD to fix this!
                         </p>
+                        <p>Failed request: {} {}</p>
                     </body>
                     </html>
-                "#
+                "#,
+                    request.method(),
+                    request.path(),
                 )))
                 .expect("couldn't build response"))
         })
@@ -102,11 +106,17 @@ fn main() {
             .expect("request failed");
 
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let text = response.text().unwrap();
         assert!(
-            response.text().unwrap().contains("UwU"),
+            text.contains("UwU"),
             "route /{} did not send expected response",
             route
         );
+        assert!(
+            text.contains(&format!("Failed request: GET /{}", route)),
+            "route /{} did not include request info",
+            route
+        );
     };
 
     assert_500("panic-handler");
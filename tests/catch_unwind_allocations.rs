@@ -0,0 +1,81 @@
+//! Checks that `catch_unwind` doesn't allocate when nothing panics.
+//!
+//! This uses a counting global allocator, so it lives in its own test binary
+//! with only a single test.
+
+use futures::{future::FutureResult, Future, IntoFuture};
+use hyperdrive::{
+    http::{Request, Response},
+    hyper::{service::Service, Body},
+    service::ServiceExt,
+    BoxedError,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// A service that doesn't allocate.
+#[derive(Clone)]
+struct Hello;
+
+impl Service for Hello {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = BoxedError;
+    type Future = FutureResult<Response<Body>, BoxedError>;
+
+    fn call(&mut self, _: Request<Body>) -> Self::Future {
+        Ok(Response::new(Body::empty())).into_future()
+    }
+}
+
+/// Returns the number of allocations made by calling `service` and waiting for
+/// the response.
+///
+/// Returns the minimum of several runs to filter out allocations made by
+/// other threads.
+fn allocations<S>(mut service: S) -> usize
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    (0..10)
+        .map(|_| {
+            let request = Request::new(Body::empty());
+            let before = ALLOCATIONS.load(Ordering::SeqCst);
+            let response = service.call(request).wait().unwrap();
+            let after = ALLOCATIONS.load(Ordering::SeqCst);
+            drop(response);
+            after - before
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn no_allocations() {
+    let handler = |_| -> Result<_, BoxedError> { Ok(Response::new(Body::empty())) };
+
+    let baseline = allocations(Hello);
+    assert_eq!(allocations(Hello.catch_unwind(handler)), baseline);
+    assert_eq!(
+        allocations(Hello.catch_unwind_with_request(move |payload, _| handler(payload))),
+        baseline
+    );
+}
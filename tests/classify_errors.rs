@@ -0,0 +1,125 @@
+use http::{Response, StatusCode};
+use hyper::Body;
+use hyperdrive::{
+    as_hyperdrive_error, is_client_error, is_server_error,
+    service::SyncService,
+    status_of,
+    test::{decode, RequestBuilder},
+    BoxedError, Error, ErrorKind, FromRequest, ResponseError,
+};
+use std::fmt;
+
+/// An application error caused by a `hyperdrive::Error`.
+#[derive(Debug)]
+struct Wrapped(Error);
+
+impl fmt::Display for Wrapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wrapped: {}", self.0)
+    }
+}
+
+impl std::error::Error for Wrapped {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// An application error that renders itself.
+#[derive(Debug)]
+struct QuotaExceeded;
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("request quota exceeded")
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl ResponseError for QuotaExceeded {
+    fn status(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+/// An application error caused by another one.
+#[derive(Debug)]
+struct Caused(BoxedError);
+
+impl fmt::Display for Caused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "caused by: {}", self.0)
+    }
+}
+
+impl std::error::Error for Caused {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+#[test]
+fn routing_error() {
+    let error = decode::<Routes>(RequestBuilder::get("/missing")).unwrap_err();
+    assert_eq!(
+        as_hyperdrive_error(&error).map(Error::kind),
+        Some(ErrorKind::NoMatchingRoute)
+    );
+    assert_eq!(status_of(&error), Some(StatusCode::NOT_FOUND));
+    assert!(is_client_error(&error));
+    assert!(!is_server_error(&error));
+}
+
+#[test]
+fn server_error() {
+    let error: BoxedError =
+        Error::with_source(StatusCode::SERVICE_UNAVAILABLE, "pool exhausted").into();
+    assert_eq!(status_of(&error), Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert!(!is_client_error(&error));
+    assert!(is_server_error(&error));
+}
+
+#[test]
+fn nested_error() {
+    let error: BoxedError = Wrapped(Error::from_status(StatusCode::FORBIDDEN)).into();
+    assert_eq!(
+        as_hyperdrive_error(&error).map(Error::http_status),
+        Some(StatusCode::FORBIDDEN)
+    );
+    assert!(is_client_error(&error));
+}
+
+#[test]
+fn unknown_error() {
+    let error: BoxedError = "connection reset".into();
+    assert!(as_hyperdrive_error(&error).is_none());
+    assert_eq!(status_of(&error), None);
+    assert!(!is_client_error(&error));
+    assert!(!is_server_error(&error));
+}
+
+#[test]
+fn nested_response_error() {
+    let _ = SyncService::new(|route: Routes, _| match route {
+        Routes::User { .. } => Response::new(Body::empty()),
+    })
+    .response_error::<QuotaExceeded>();
+
+    let error: BoxedError = Caused(QuotaExceeded.into()).into();
+    assert!(as_hyperdrive_error(&error).is_none());
+    assert_eq!(status_of(&error), Some(StatusCode::TOO_MANY_REQUESTS));
+    assert!(is_client_error(&error));
+    assert!(!is_server_error(&error));
+
+    // The registered type takes precedence, like it does in the services
+    let error: BoxedError =
+        Caused(Error::with_source(StatusCode::INTERNAL_SERVER_ERROR, QuotaExceeded).into()).into();
+    assert_eq!(status_of(&error), Some(StatusCode::TOO_MANY_REQUESTS));
+}
@@ -0,0 +1,202 @@
+use flate2::read::GzDecoder;
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    http::{header, Request, Response, StatusCode},
+    hyper::{service::Service, Body, Chunk},
+    service::{AsyncService, CompressionConfig, ServiceExt},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use std::io::Read;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/json")]
+    Json,
+
+    #[get("/small")]
+    Small,
+
+    #[get("/image")]
+    Image,
+
+    #[get("/encoded")]
+    Encoded,
+
+    #[get("/empty")]
+    Empty,
+
+    #[get("/stream")]
+    Stream,
+}
+
+fn json() -> String {
+    let numbers = (0..1000).map(|i| i.to_string()).collect::<Vec<_>>();
+    format!("[{}]", numbers.join(","))
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    AsyncService::new(|route: Routes, _| {
+        let mut response = Response::builder();
+        match route {
+            Routes::Json => response
+                .header("Content-Type", "application/json")
+                .body(Body::from(json())),
+            Routes::Small => response
+                .header("Content-Type", "text/plain")
+                .body(Body::from("tiny")),
+            Routes::Image => response
+                .header("Content-Type", "image/png")
+                .body(Body::from(vec![0; 4096])),
+            Routes::Encoded => response
+                .header("Content-Type", "text/plain")
+                .header("Content-Encoding", "gzip")
+                .body(Body::from("not really gzip, but don't touch it")),
+            Routes::Empty => response
+                .status(StatusCode::NO_CONTENT)
+                .header("Content-Type", "text/plain")
+                .body(Body::empty()),
+            Routes::Stream => {
+                let chunks = vec!["first chunk\n", "second chunk\n", "third chunk\n"];
+                let stream = futures::stream::iter_ok::<_, BoxedError>(chunks);
+                response
+                    .header("Content-Type", "text/plain")
+                    .body(Body::wrap_stream(stream))
+            }
+        }
+        .map_err(BoxedError::from)
+        .into_future()
+    })
+    .compress(CompressionConfig::new().min_size(100))
+}
+
+/// Calls `app()` and returns the response with the body split into chunks.
+fn call(method: &str, path: &str, accept_encoding: Option<&str>) -> Response<Vec<Chunk>> {
+    let mut request = Request::builder();
+    request.method(method).uri(path);
+    if let Some(accept_encoding) = accept_encoding {
+        request.header("Accept-Encoding", accept_encoding);
+    }
+    let request = request.body(Body::empty()).unwrap();
+
+    let mut runtime = Runtime::new().unwrap();
+    let response = runtime.block_on(app().call(request)).unwrap();
+    let (parts, body) = response.into_parts();
+    let chunks = runtime.block_on(body.collect()).unwrap();
+    runtime.shutdown_now().wait().unwrap();
+
+    Response::from_parts(parts, chunks)
+}
+
+fn concat(chunks: &[Chunk]) -> Vec<u8> {
+    chunks
+        .iter()
+        .flat_map(|chunk| chunk.iter().cloned())
+        .collect()
+}
+
+fn gunzip(data: &[u8]) -> String {
+    let mut decoded = String::new();
+    GzDecoder::new(data).read_to_string(&mut decoded).unwrap();
+    decoded
+}
+
+#[test]
+fn gzip() {
+    let response = call("GET", "/json", Some("gzip, deflate"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(response.headers()[header::VARY], "accept-encoding");
+    assert!(!response.headers().contains_key(header::CONTENT_LENGTH));
+
+    let compressed = concat(response.body());
+    assert!(compressed.len() < json().len());
+    assert_eq!(gunzip(&compressed), json());
+}
+
+#[test]
+fn not_accepted() {
+    for accept_encoding in &[None, Some("identity"), Some("gzip;q=0"), Some("*;q=0")] {
+        let response = call("GET", "/json", *accept_encoding);
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        assert_eq!(response.headers()[header::VARY], "accept-encoding");
+        assert_eq!(concat(response.body()), json().as_bytes());
+    }
+
+    let response = call("GET", "/json", Some("*"));
+    assert!(response.headers().contains_key(header::CONTENT_ENCODING));
+}
+
+#[test]
+fn quality_values() {
+    let response = call("GET", "/json", Some("x-gzip"));
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+    let response = call("GET", "/json", Some("gzip;q=0, br;q=0, *"));
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+    // Small responses are compressed if the client refuses unencoded ones
+    let response = call("GET", "/small", Some("gzip, identity;q=0"));
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(gunzip(&concat(response.body())), "tiny");
+
+    let response = call("GET", "/small", Some("*;q=0.5, identity;q=0"));
+    assert!(response.headers().contains_key(header::CONTENT_ENCODING));
+}
+
+#[test]
+fn untouched() {
+    for path in &["/small", "/image", "/encoded"] {
+        let original = call("GET", path, None);
+        let response = call("GET", path, Some("gzip"));
+        assert_eq!(response.headers(), original.headers(), "{}", path);
+        assert_eq!(concat(response.body()), concat(original.body()), "{}", path);
+    }
+
+    let response = call("GET", "/empty", Some("gzip"));
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+    let response = call("HEAD", "/json", Some("gzip"));
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    assert!(!response.headers().contains_key(header::VARY));
+}
+
+#[test]
+fn streaming() {
+    let response = call("GET", "/stream", Some("gzip"));
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+    // Every chunk is compressed and flushed on its own
+    let chunks = response.body();
+    assert_eq!(chunks.len(), 4);
+    let first = concat(&chunks[..1]);
+    let mut decoded = [0; 12];
+    GzDecoder::new(&first[..]).read_exact(&mut decoded).unwrap();
+    assert_eq!(&decoded, b"first chunk\n");
+    assert_eq!(
+        gunzip(&concat(chunks)),
+        "first chunk\nsecond chunk\nthird chunk\n"
+    );
+}
+
+#[cfg(feature = "brotli")]
+#[test]
+fn brotli() {
+    let response = call("GET", "/json", Some("gzip, br"));
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&concat(response.body())[..], 4096)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, json());
+
+    let response = call("GET", "/json", Some("gzip, br;q=0.5"));
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+}
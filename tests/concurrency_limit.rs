@@ -0,0 +1,146 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{header, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, ServiceExt},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    /// Never completes.
+    #[get("/slow")]
+    Slow,
+
+    #[get("/fail")]
+    Fail,
+
+    #[get("/panic")]
+    Panic,
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> + Clone {
+    AsyncService::new(|route: Routes, _| -> DefaultFuture<_, BoxedError> {
+        match route {
+            Routes::Index => Box::new(Ok(Response::new(Body::from("index"))).into_future()),
+            Routes::Slow => Box::new(futures::future::empty()),
+            Routes::Fail => Box::new(Err("handler failed".into()).into_future()),
+            Routes::Panic => panic!("handler panicked"),
+        }
+    })
+}
+
+fn get(path: &str) -> Request<Body> {
+    Request::get(path).body(Body::empty()).unwrap()
+}
+
+fn status<F>(runtime: &mut Runtime, future: F) -> StatusCode
+where
+    F: Future<Item = Response<Body>, Error = BoxedError> + Send + 'static,
+{
+    runtime.block_on(future).unwrap().status()
+}
+
+#[test]
+fn sheds_excess_requests() {
+    let mut runtime = Runtime::new().unwrap();
+    let mut service = app()
+        .concurrency_limit(2)
+        .retry_after(Duration::from_secs(5));
+
+    // Saturate the limit with requests that never complete
+    let slow1 = service.call(get("/slow"));
+    let slow2 = service.clone().call(get("/slow"));
+
+    let response = runtime.block_on(service.call(get("/"))).unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+
+    // Dropping a request frees its slot
+    drop(slow1);
+    assert_eq!(status(&mut runtime, service.call(get("/"))), StatusCode::OK);
+    assert_eq!(status(&mut runtime, service.call(get("/"))), StatusCode::OK);
+
+    drop(slow2);
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn errors_release_slot() {
+    let mut runtime = Runtime::new().unwrap();
+    let mut service = app().concurrency_limit(1);
+
+    assert!(runtime.block_on(service.call(get("/fail"))).is_err());
+    assert_eq!(status(&mut runtime, service.call(get("/"))), StatusCode::OK);
+
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn panics_release_slot() {
+    let mut runtime = Runtime::new().unwrap();
+
+    // `catch_unwind` outside of the limit
+    let mut service = app()
+        .concurrency_limit(1)
+        .catch_unwind(|_| -> Result<_, BoxedError> {
+            Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap())
+        });
+    assert_eq!(
+        status(&mut runtime, service.call(get("/panic"))),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(status(&mut runtime, service.call(get("/"))), StatusCode::OK);
+
+    // `catch_unwind` inside of the limit
+    let mut service = app()
+        .catch_unwind(|_| -> Result<_, BoxedError> {
+            Ok(Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap())
+        })
+        .concurrency_limit(1);
+    assert_eq!(
+        status(&mut runtime, service.call(get("/panic"))),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(status(&mut runtime, service.call(get("/"))), StatusCode::OK);
+
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn bounded_wait() {
+    let mut runtime = Runtime::new().unwrap();
+    let mut service = app()
+        .concurrency_limit(1)
+        .max_wait(Duration::from_millis(50));
+
+    // Times out while the slot is occupied
+    let slow = service.call(get("/slow"));
+    assert_eq!(
+        status(&mut runtime, service.call(get("/"))),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+
+    // Gets the slot once it is released
+    let waiting = service.call(get("/"));
+    drop(slow);
+    assert_eq!(status(&mut runtime, waiting), StatusCode::OK);
+
+    runtime.shutdown_now().wait().unwrap();
+}
@@ -0,0 +1,92 @@
+use futures::Future;
+use hyper::{server::conn::AddrStream, Server};
+use hyperdrive::{
+    guard::{Extension, RemoteAddr},
+    service::{ConnectInfo, ServiceExt, SyncService},
+    FromRequest,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+struct Connection {
+    id: usize,
+    peer: String,
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index {
+        conn: Extension<Connection>,
+        addr: Option<RemoteAddr>,
+    },
+}
+
+#[test]
+fn hook() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls2 = calls.clone();
+    let service = SyncService::responding(|Route::Index { conn, addr }, _| {
+        // The remote address is only recorded when asked to
+        assert!(addr.is_none());
+        assert!(conn.peer.starts_with("127.0.0.1:"));
+        format!("connection {}", conn.id)
+    })
+    .make_service_by_cloning()
+    .with_connection_hook(move |stream: &AddrStream| Connection {
+        id: calls2.fetch_add(1, Ordering::SeqCst),
+        peer: stream.remote_addr().to_string(),
+    });
+
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    let url = format!("http://127.0.0.1:{}/", srv.local_addr().port());
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let get = |client: &reqwest::Client| {
+        let mut response = client.get(&url).send().expect("request failed");
+        assert!(response.status().is_success());
+        response.text().unwrap()
+    };
+
+    // Requests on the same connection share the data
+    let client = reqwest::Client::new();
+    assert_eq!(get(&client), "connection 0");
+    assert_eq!(get(&client), "connection 0");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // A new connection runs the hook again
+    let client = reqwest::Client::new();
+    assert_eq!(get(&client), "connection 1");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn connect_info() {
+    #[derive(FromRequest)]
+    enum Route {
+        #[get("/")]
+        Index { addr: RemoteAddr },
+    }
+
+    // A hook can provide the `ConnectInfo` used by `RemoteAddr`
+    let service = SyncService::responding(|Route::Index { addr }, _| addr.ip().to_string())
+        .make_service_by_cloning()
+        .with_connection_hook(|stream: &AddrStream| ConnectInfo(stream.remote_addr()));
+
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    let url = format!("http://127.0.0.1:{}/", srv.local_addr().port());
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let mut response = reqwest::get(&url).expect("request failed");
+    assert!(response.status().is_success());
+    assert_eq!(response.text().unwrap(), "127.0.0.1");
+}
@@ -0,0 +1,76 @@
+use hyperdrive::{
+    guard::{AcceptedContentEncodings, ContentEncodingConfig},
+    http::{Request, StatusCode},
+    hyper::Body,
+    BoxedError, Error, FromRequest,
+};
+
+#[derive(FromRequest, Debug)]
+#[context(ContentEncodingConfig)]
+enum Routes {
+    #[post("/")]
+    Upload { encoding: AcceptedContentEncodings },
+}
+
+fn invoke(
+    content_encoding: &[&str],
+    config: ContentEncodingConfig,
+) -> Result<AcceptedContentEncodings, BoxedError> {
+    let mut request = Request::post("/");
+    for value in content_encoding {
+        request.header("Content-Encoding", *value);
+    }
+    let request = request.body(Body::empty()).unwrap();
+    Routes::from_request_sync(request, config).map(|Routes::Upload { encoding }| encoding)
+}
+
+fn unsupported(result: Result<AcceptedContentEncodings, BoxedError>) -> Box<Error> {
+    let error: Box<Error> = result.unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    error
+}
+
+fn gzip() -> ContentEncodingConfig {
+    ContentEncodingConfig::new().accept("gzip")
+}
+
+#[test]
+fn identity() {
+    let encoding = invoke(&[], ContentEncodingConfig::new()).unwrap();
+    assert!(encoding.is_identity());
+
+    let encoding = invoke(&["identity"], ContentEncodingConfig::new()).unwrap();
+    assert!(encoding.is_identity());
+}
+
+#[test]
+fn br() {
+    let error = unsupported(invoke(&["br"], ContentEncodingConfig::new()));
+    assert!(error.to_string().contains("`br`"), "{}", error);
+    assert_eq!(error.response().headers()["accept-encoding"], "identity");
+
+    let error = unsupported(invoke(&["br"], gzip()));
+    assert_eq!(
+        error.response().headers()["accept-encoding"],
+        "gzip, identity"
+    );
+}
+
+#[test]
+fn stacked() {
+    let error = unsupported(invoke(&["gzip, br"], gzip()));
+    assert!(error.to_string().contains("`br`"), "{}", error);
+
+    // Codings can be split across several headers
+    let error = unsupported(invoke(&["gzip", "br"], gzip()));
+    assert!(error.to_string().contains("`br`"), "{}", error);
+}
+
+#[test]
+fn supported() {
+    let encoding = invoke(&["GZip"], gzip()).unwrap();
+    assert_eq!(encoding.codings(), &["gzip".to_string()]);
+    assert!(!encoding.is_identity());
+
+    unsupported(invoke(&["gzip"], ContentEncodingConfig::new()));
+}
@@ -0,0 +1,102 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    http::{Request, StatusCode},
+    service::{AsyncService, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, FromRequest, Guard, RequestContext,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A context that can't be cloned, like a checked out database connection.
+#[derive(RequestContext)]
+struct Context {
+    id: usize,
+}
+
+/// Extracts the ID of the context the request was decoded with.
+struct ContextId(usize);
+
+impl Guard for ContextId {
+    type Context = Context;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, context: &Context) -> Self::Result {
+        Ok(ContextId(context.id))
+    }
+}
+
+#[derive(FromRequest)]
+#[context(Context)]
+enum Routes {
+    #[get("/")]
+    Index { id: ContextId },
+}
+
+/// Creates a context factory that counts its invocations in `calls`, and
+/// fails for requests with an `X-Fail` header.
+fn factory(
+    calls: Arc<AtomicUsize>,
+) -> impl Fn(&Request<()>) -> Result<Context, BoxedError> + Send + Sync + 'static {
+    move |request: &Request<()>| {
+        let id = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if request.headers().contains_key("X-Fail") {
+            Err("no connection available".into())
+        } else {
+            Ok(Context { id })
+        }
+    }
+}
+
+#[test]
+fn async_service() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let service = AsyncService::responding_with_context_fn(
+        |route: Routes, _| match route {
+            Routes::Index { id } => Ok(format!("context #{}", id.0)).into_future(),
+        },
+        factory(calls.clone()),
+    );
+    let mut client = Client::new(service);
+
+    for i in 1..=3 {
+        let response = client.send(RequestBuilder::get("/")).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text(), format!("context #{}", i));
+        assert_eq!(calls.load(Ordering::SeqCst), i);
+    }
+
+    let response = client
+        .send(RequestBuilder::get("/").header("X-Fail", "1"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn sync_service() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let service = SyncService::responding_with_context_fn(
+        |route: Routes, _| match route {
+            Routes::Index { id } => format!("context #{}", id.0),
+        },
+        factory(calls.clone()),
+    );
+    let mut client = Client::new(service);
+
+    for i in 1..=3 {
+        let response = client.send(RequestBuilder::get("/")).unwrap();
+        assert_eq!(response.text(), format!("context #{}", i));
+        assert_eq!(calls.load(Ordering::SeqCst), i);
+    }
+
+    let response = client
+        .send(RequestBuilder::get("/").header("X-Fail", "1"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // Requests that don't match any route still create a context
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+}
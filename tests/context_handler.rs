@@ -0,0 +1,106 @@
+//! Tests for the service constructors that pass the context to the handler.
+
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    http::{Request, Response},
+    hyper::{service::Service, Body},
+    service::{AsyncService, SyncService},
+    BoxedError, FromRequest, Guard, RequestContext,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio_threadpool::ThreadPool;
+
+/// A context that doesn't implement `Clone`.
+#[derive(RequestContext)]
+struct Context {
+    hits: AtomicUsize,
+    /// Large data that would be expensive to clone for every request.
+    names: Vec<String>,
+}
+
+impl Context {
+    fn new() -> Self {
+        Self {
+            hits: AtomicUsize::new(0),
+            names: (0..10_000).map(|i| format!("name #{}", i)).collect(),
+        }
+    }
+}
+
+/// Returns a response containing the number of hits and the address of the
+/// context, which must be the same for all requests.
+fn respond(context: &Context) -> Response<Body> {
+    let hits = context.hits.load(Ordering::SeqCst);
+    let address = context as *const Context as usize;
+    Response::new(Body::from(format!("{} {:x}", hits, address)))
+}
+
+/// Counts the requests in the context.
+struct Hit;
+
+impl Guard for Hit {
+    type Context = Context;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_request: &Arc<http::Request<()>>, context: &Context) -> Self::Result {
+        context.hits.fetch_add(1, Ordering::SeqCst);
+        Ok(Hit)
+    }
+}
+
+#[derive(FromRequest)]
+#[context(Context)]
+enum Routes {
+    #[get("/")]
+    Index { _hit: Hit },
+}
+
+/// Sends a request to `service` on a tokio threadpool, which `SyncService`
+/// needs to run the handler, and returns the response body.
+fn get<S>(service: &mut S) -> String
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::get("/").body(Body::empty()).unwrap();
+    let pool = ThreadPool::new();
+    let response = pool.spawn_handle(service.call(request)).wait().unwrap();
+    pool.shutdown_now().wait().unwrap();
+    let body = response.into_body().concat2().wait().unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+/// Sends 2 requests to `service` and checks that they were served using the
+/// same context.
+fn check<S>(mut service: S)
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError> + Clone,
+    S::Future: Send + 'static,
+{
+    let first = get(&mut service);
+    let second = get(&mut service.clone());
+
+    let (hits, address) = first.split_at(first.find(' ').unwrap());
+    assert_eq!(hits, "1");
+    assert_eq!(second, format!("2{}", address));
+}
+
+#[test]
+fn async_handler() {
+    check(AsyncService::with_context_handler(
+        |Routes::Index { .. }, _, context: Arc<Context>| {
+            assert_eq!(context.names.len(), 10_000);
+            Ok(respond(&context)).into_future()
+        },
+        Context::new(),
+    ));
+}
+
+#[test]
+fn sync_handler() {
+    check(SyncService::with_context_handler(
+        |Routes::Index { .. }, _, context: Arc<Context>| respond(&context),
+        Context::new(),
+    ));
+}
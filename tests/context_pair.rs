@@ -0,0 +1,151 @@
+use hyperdrive::{
+    body::Json,
+    http::Request,
+    test::{decode_with_context, RequestBuilder},
+    BoxedError, ContextPair, FromRequest, Guard, RequestContext,
+};
+use std::sync::Arc;
+
+/// Parts of the context of an authentication library.
+mod auth {
+    use super::*;
+
+    pub struct Realm(pub &'static str);
+
+    #[derive(RequestContext)]
+    pub struct AuthContext {
+        #[as_ref]
+        pub realm: Realm,
+        #[as_ref]
+        pub name: &'static str,
+    }
+
+    pub struct User(pub &'static str);
+
+    impl Guard for User {
+        type Context = Realm;
+        type Result = Result<Self, BoxedError>;
+
+        fn from_request(_: &Arc<Request<()>>, realm: &Realm) -> Self::Result {
+            Ok(User(realm.0))
+        }
+    }
+
+    /// Needs the whole library context.
+    pub struct Library(pub &'static str);
+
+    impl Guard for Library {
+        type Context = AuthContext;
+        type Result = Result<Self, BoxedError>;
+
+        fn from_request(_: &Arc<Request<()>>, context: &AuthContext) -> Self::Result {
+            Ok(Library(context.name))
+        }
+    }
+}
+
+use auth::{AuthContext, Library, Realm, User};
+
+struct Templates(&'static str);
+
+#[derive(RequestContext)]
+struct AppContext {
+    #[as_ref]
+    templates: Templates,
+    #[as_ref]
+    name: &'static str,
+}
+
+struct Renderer(&'static str);
+
+impl Guard for Renderer {
+    type Context = Templates;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, templates: &Templates) -> Self::Result {
+        Ok(Renderer(templates.0))
+    }
+}
+
+/// Provided by both halves.
+struct Name(&'static str);
+
+impl Guard for Name {
+    type Context = &'static str;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, name: &&'static str) -> Self::Result {
+        Ok(Name(name))
+    }
+}
+
+type Context = ContextPair<AuthContext, AppContext>;
+
+#[derive(FromRequest)]
+#[context(ContextPair<AuthContext, AppContext>)]
+enum Routes {
+    #[get("/profile")]
+    Profile {
+        user: User,
+        renderer: Renderer,
+        name: Name,
+        library: Library,
+    },
+
+    #[post("/profile")]
+    Update {
+        user: User,
+        #[body]
+        body: Json<String>,
+    },
+}
+
+fn context() -> Context {
+    ContextPair(
+        AuthContext {
+            realm: Realm("users"),
+            name: "auth",
+        },
+        AppContext {
+            templates: Templates("profile.html"),
+            name: "app",
+        },
+    )
+}
+
+#[test]
+fn guards_from_both_halves() {
+    match decode_with_context::<Routes>(RequestBuilder::get("/profile"), context()).unwrap() {
+        Routes::Profile {
+            user,
+            renderer,
+            name,
+            library,
+        } => {
+            assert_eq!(user.0, "users");
+            assert_eq!(renderer.0, "profile.html");
+            // The first half is preferred
+            assert_eq!(name.0, "auth");
+            assert_eq!(library.0, "auth");
+        }
+        _ => panic!("wrong route"),
+    }
+
+    // `Json` uses `NoContext`
+    match decode_with_context::<Routes>(RequestBuilder::post("/profile").json(&"bio"), context())
+        .unwrap()
+    {
+        Routes::Update { user, body } => {
+            assert_eq!(user.0, "users");
+            assert_eq!(body.0, "bio");
+        }
+        _ => panic!("wrong route"),
+    }
+}
+
+#[test]
+fn accessors() {
+    let context = context();
+    assert_eq!(context.left().name, "auth");
+    assert_eq!(context.right().name, "app");
+}
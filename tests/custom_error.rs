@@ -0,0 +1,105 @@
+use hyperdrive::{
+    http::{
+        header::{self, InvalidHeaderName, InvalidHeaderValue},
+        StatusCode,
+    },
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    BoxedError, Error, ErrorKind, FromRequest, Guard, NoContext,
+};
+use std::sync::Arc;
+
+/// Fails with `409 Conflict` if the user in the `X-User` header exists.
+struct NewUser(String);
+
+impl Guard for NewUser {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        let name = request.headers()["x-user"].to_str()?;
+        if name == "admin" {
+            return Err(Error::builder(StatusCode::CONFLICT)
+                .message("user already exists")
+                .header(header::LOCATION, "/users/admin")
+                .build()?
+                .into());
+        }
+        if name == "locked" {
+            return Err(Error::with_status(StatusCode::LOCKED, "registration is locked").into());
+        }
+
+        Ok(NewUser(name.to_string()))
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[post("/users")]
+    AddUser { user: NewUser },
+}
+
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> String, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::AddUser { user } => format!("added {}", user.0),
+    })
+}
+
+#[test]
+fn conflict() {
+    let mut client = Client::new(service());
+    let response = client
+        .send(RequestBuilder::post("/users").header("X-User", "admin"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(response.header("Location"), Some("/users/admin"));
+
+    let response = client
+        .send(RequestBuilder::post("/users").header("X-User", "locked"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::LOCKED);
+
+    let response = client
+        .send(RequestBuilder::post("/users").header("X-User", "jonas"))
+        .unwrap();
+    assert_eq!(response.text(), "added jonas");
+}
+
+#[test]
+fn builder() {
+    let error = Error::builder(StatusCode::CONFLICT)
+        .message("user already exists")
+        .header("Location", "/users/admin")
+        .header("Link", "</users>; rel=\"collection\"")
+        .source("duplicate key")
+        .build()
+        .unwrap();
+
+    assert_eq!(error.kind(), ErrorKind::Custom);
+    assert_eq!(error.http_status(), StatusCode::CONFLICT);
+    assert_eq!(error.message(), Some("user already exists"));
+    assert_eq!(error.source().unwrap().to_string(), "duplicate key");
+    assert_eq!(
+        error.to_string(),
+        "409 Conflict: user already exists: duplicate key"
+    );
+
+    let response = error.response();
+    assert_eq!(response.headers()["location"], "/users/admin");
+    assert_eq!(response.headers()["link"], "</users>; rel=\"collection\"");
+}
+
+#[test]
+fn invalid_header() {
+    let result = Error::builder(StatusCode::CONFLICT)
+        .header("Location", "\n")
+        .header("Link", "</users>")
+        .build();
+    assert!(result.unwrap_err().is::<InvalidHeaderValue>());
+
+    let result = Error::builder(StatusCode::CONFLICT)
+        .header("Invalid Name", "value")
+        .header("Location", "\n")
+        .build();
+    assert!(result.unwrap_err().is::<InvalidHeaderName>());
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "r2d2")]
+
+use futures::Future;
+use hyperdrive::{
+    guard::{DbConnection, DbPool},
+    http::{header, Request, StatusCode},
+    hyper::Body,
+    BoxedError, Error, FromRequest, RequestContext,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio_threadpool::ThreadPool;
+
+/// A fake connection manager that hands out numbered connections.
+#[derive(Debug, Default)]
+struct FakeManager {
+    connects: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct FakeConnection {
+    number: usize,
+}
+
+impl r2d2::ManageConnection for FakeManager {
+    type Connection = FakeConnection;
+    type Error = std::io::Error;
+
+    fn connect(&self) -> Result<FakeConnection, Self::Error> {
+        Ok(FakeConnection {
+            number: self.connects.fetch_add(1, Ordering::SeqCst),
+        })
+    }
+
+    fn is_valid(&self, _conn: &mut FakeConnection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn has_broken(&self, _conn: &mut FakeConnection) -> bool {
+        false
+    }
+}
+
+#[derive(RequestContext, Clone)]
+struct Context {
+    #[as_ref]
+    db: DbPool<FakeManager>,
+}
+
+#[derive(FromRequest, Debug)]
+#[context(Context)]
+enum Routes {
+    #[get("/")]
+    Index { db: DbConnection<FakeManager> },
+}
+
+fn context() -> Context {
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .connection_timeout(Duration::from_millis(100))
+        .build(FakeManager::default())
+        .unwrap();
+    Context {
+        db: DbPool::new(pool).retry_after(Duration::from_secs(5)),
+    }
+}
+
+/// Decodes `Routes` on a tokio threadpool, which the guard needs for
+/// `blocking`.
+fn invoke(context: Context) -> Result<DbConnection<FakeManager>, BoxedError> {
+    let request = Request::get("/").body(Body::empty()).unwrap();
+    let pool = ThreadPool::new();
+    let result = pool
+        .spawn_handle(Routes::from_request(request, context).map(|Routes::Index { db }| db))
+        .wait();
+    pool.shutdown_now().wait().unwrap();
+    result
+}
+
+#[test]
+fn checkout() {
+    let context = context();
+
+    let conn = invoke(context.clone()).unwrap();
+    assert_eq!(conn.number, 0);
+    drop(conn);
+
+    // The connection was returned to the pool and is reused
+    let conn = invoke(context.clone()).unwrap();
+    assert_eq!(conn.number, 0);
+    assert_eq!(context.db.pool().state().connections, 1);
+}
+
+#[test]
+fn exhausted() {
+    let context = context();
+
+    let _held = invoke(context.clone()).unwrap();
+    let error: Box<Error> = invoke(context).unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = error.response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+}
@@ -0,0 +1,123 @@
+use hyperdrive::{
+    http::{Method, StatusCode},
+    response::Json,
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    BoxedError, Error, ErrorKind, FromRequest, Guard, NoContext,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Rejects requests without an `X-Token` header.
+struct Token;
+
+impl Guard for Token {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        if request.headers().contains_key("x-token") {
+            Ok(Token)
+        } else {
+            let mut error = Error::from_status(StatusCode::UNAUTHORIZED);
+            error
+                .headers_mut()
+                .insert("WWW-Authenticate", "Token".parse().unwrap());
+            Err(error.into())
+        }
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users")]
+    Users,
+
+    #[post("/users")]
+    AddUser,
+
+    #[get("/secret")]
+    Secret { _token: Token },
+}
+
+fn render(error: &Error) -> Json<serde_json::Value> {
+    match error.kind() {
+        ErrorKind::NoMatchingRoute => Json(json!({ "error": "not_found" })),
+        ErrorKind::WrongMethod => {
+            let allowed = error
+                .allowed_methods()
+                .unwrap()
+                .iter()
+                .map(|method| method.as_str())
+                .collect::<Vec<_>>();
+            Json(json!({ "error": "method_not_allowed", "allowed": allowed }))
+        }
+        _ => Json(json!({ "error": error.http_status().as_u16() })),
+    }
+}
+
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> &'static str, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Users => "[]",
+        Routes::AddUser => "added",
+        Routes::Secret { .. } => "secret",
+    })
+}
+
+#[test]
+fn not_found() {
+    let mut client = Client::new(service().error_body(render));
+
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("Content-Type"), Some("application/json"));
+    assert_eq!(response.text(), r#"{"error":"not_found"}"#);
+
+    // Successful responses are unaffected
+    let response = client.send(RequestBuilder::get("/users")).unwrap();
+    assert_eq!(response.text(), "[]");
+}
+
+#[test]
+fn wrong_method() {
+    let mut client = Client::new(service().error_body(render));
+
+    let response = client
+        .send(RequestBuilder::new(Method::DELETE, "/users"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("Allow"), Some("GET, HEAD, POST"));
+    assert_eq!(
+        response.text(),
+        r#"{"allowed":["GET","HEAD","POST"],"error":"method_not_allowed"}"#
+    );
+}
+
+#[test]
+fn guard_error() {
+    let mut client = Client::new(service().error_body(render));
+
+    // The status and headers of the error are kept
+    let response = client.send(RequestBuilder::get("/secret")).unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header("WWW-Authenticate"), Some("Token"));
+    assert_eq!(response.text(), r#"{"error":401}"#);
+}
+
+#[test]
+fn default_and_precedence() {
+    let mut client = Client::new(service());
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.text(), "");
+
+    let mut client = Client::new(
+        service()
+            .error_body(render)
+            .not_found(|_| (StatusCode::NOT_FOUND, "nothing here")),
+    );
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.text(), "nothing here");
+    let response = client.send(RequestBuilder::get("/secret")).unwrap();
+    assert_eq!(response.text(), r#"{"error":401}"#);
+}
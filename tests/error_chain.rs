@@ -0,0 +1,69 @@
+use hyperdrive::{
+    hyper::Body,
+    test::{decode, RequestBuilder},
+    Error, ErrorKind, FromRequest, NoContext,
+};
+use std::num::ParseIntError;
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+fn decode_error(path: &str) -> Box<Error> {
+    let request = http::Request::get(path).body(Body::empty()).unwrap();
+    Routes::from_request_sync(request, NoContext)
+        .unwrap_err()
+        .downcast()
+        .unwrap()
+}
+
+#[test]
+fn path_segment_chain() {
+    let error = decode_error("/users/abc");
+    assert_eq!(error.kind(), ErrorKind::PathSegment);
+
+    let chain = error.iter_chain().collect::<Vec<_>>();
+    assert_eq!(chain.len(), 2);
+    assert!(chain[0].downcast_ref::<Error>().is_some());
+    assert!(chain[1].downcast_ref::<ParseIntError>().is_some());
+
+    // `std::error::Error::source` exposes the same cause
+    let source = std::error::Error::source(&*error).unwrap();
+    assert!(source.downcast_ref::<ParseIntError>().is_some());
+}
+
+#[test]
+fn alternate_display() {
+    let error = decode_error("/users/abc");
+    assert_eq!(
+        error.to_string(),
+        "404 Not Found: invalid `id`: invalid digit found in string"
+    );
+    assert_eq!(
+        format!("{:#}", error),
+        "path_segment: 404 Not Found: invalid `id`: invalid digit found in string"
+    );
+
+    let error = decode_error("/users");
+    assert_eq!(
+        format!("{:#}", error),
+        "no_matching_route: 404 Not Found (GET /users)"
+    );
+    assert_eq!(error.iter_chain().count(), 1);
+}
+
+#[test]
+fn kind_names() {
+    assert_eq!(ErrorKind::NoMatchingRoute.as_str(), "no_matching_route");
+    assert_eq!(
+        ErrorKind::UnsupportedMediaType.as_str(),
+        "unsupported_media_type"
+    );
+    assert_eq!(ErrorKind::BodyReadTimeout.to_string(), "body_read_timeout");
+
+    let error = decode::<Routes>(RequestBuilder::post("/users/1")).unwrap_err();
+    let error = error.downcast_ref::<Error>().unwrap();
+    assert_eq!(error.kind().to_string(), "wrong_method");
+}
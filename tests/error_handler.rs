@@ -0,0 +1,202 @@
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    http::{Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    response::IntoResponse,
+    service::{AsyncService, SyncService},
+    BoxedError, DefaultFuture, Error, FromRequest, ResponseError,
+};
+use std::fmt;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/fail")]
+    Fail,
+}
+
+/// Renders every error as a JSON body.
+fn json_error(error: BoxedError) -> Response<Body> {
+    let status = match error.downcast_ref::<Error>() {
+        Some(error) => error.http_status(),
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"status":{},"message":"{}"}}"#,
+            status.as_u16(),
+            error
+        )))
+        .unwrap()
+}
+
+fn call<S>(mut service: S, method: &str, path: &str) -> Result<(StatusCode, String), BoxedError>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let result = runtime.block_on(service.call(request));
+    runtime.shutdown_now().wait().unwrap();
+
+    let response = result?;
+    let status = response.status();
+    let body = response.into_body().concat2().wait()?;
+    Ok((status, String::from_utf8(body.to_vec()).unwrap()))
+}
+
+/// Creates an `AsyncService` whose handler fails for `Routes::Fail`.
+fn async_app(
+    error_handler: Option<fn(BoxedError) -> Response<Body>>,
+) -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    let service = AsyncService::new(|route: Routes, _| {
+        match route {
+            Routes::Index => Ok(Response::new(Body::from("index"))),
+            Routes::Fail => Err("database on fire".into()),
+        }
+        .into_future()
+    });
+
+    match error_handler {
+        Some(error_handler) => service.with_error_handler(error_handler),
+        None => service,
+    }
+}
+
+#[test]
+fn default_behavior() {
+    let (status, body) = call(async_app(None), "GET", "/missing").unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "");
+
+    let error = call(async_app(None), "GET", "/fail").unwrap_err();
+    assert_eq!(error.to_string(), "database on fire");
+}
+
+#[test]
+fn no_matching_route() {
+    let service = async_app(Some(json_error));
+    let (status, body) = call(service, "GET", "/missing").unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, r#"{"status":404,"message":"404 Not Found"}"#);
+}
+
+#[test]
+fn opaque_error() {
+    let service = async_app(Some(json_error));
+    let (status, body) = call(service, "GET", "/fail").unwrap();
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body, r#"{"status":500,"message":"database on fire"}"#);
+}
+
+#[test]
+fn success_unaffected() {
+    let service = async_app(Some(json_error));
+    let (status, body) = call(service, "GET", "/").unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "index");
+}
+
+#[test]
+fn head() {
+    let service = async_app(Some(json_error));
+    let (status, body) = call(service, "HEAD", "/missing").unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "");
+}
+
+#[test]
+fn sync_service() {
+    let service = SyncService::new(|route: Routes, _| match route {
+        Routes::Index => Response::new(Body::from("index")),
+        Routes::Fail => unreachable!(),
+    })
+    .with_error_handler(json_error);
+
+    let (status, body) = call(service.clone(), "POST", "/").unwrap();
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body, r#"{"status":405,"message":"405 Method Not Allowed"}"#);
+
+    let (status, body) = call(service, "GET", "/").unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "index");
+}
+
+/// Answered with `429 Too Many Requests` when registered.
+#[derive(Debug)]
+struct RateLimited;
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("rate limited")
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl ResponseError for RateLimited {
+    fn status(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+#[test]
+fn precedence() {
+    #[derive(FromRequest)]
+    enum Routes {
+        #[get("/")]
+        Index,
+
+        #[get("/limited")]
+        Limited,
+    }
+
+    let service = || {
+        AsyncService::responding(|route: Routes, _| match route {
+            Routes::Index => Ok("index").into_future(),
+            Routes::Limited => Err(RateLimited.into()).into_future(),
+        })
+        .with_error_handler(json_error)
+        .response_error::<RateLimited>()
+        .error_body(|_: &Error| "rendered")
+    };
+
+    // Registered `ResponseError`s are rendered by their type
+    let (status, body) = call(service(), "GET", "/limited").unwrap();
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body, "");
+
+    // The `not_found` function answers unmatched requests
+    let not_found = service().not_found(|_| Ok(StatusCode::GONE.into_response()));
+    let (status, body) = call(not_found, "GET", "/missing").unwrap();
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(body, "");
+
+    // `OPTIONS` requests are answered automatically
+    let (status, _) = call(service().auto_options(true), "OPTIONS", "/").unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // All other errors reach the error handler, which takes precedence over
+    // `error_body`
+    let (status, body) = call(service(), "POST", "/").unwrap();
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body, r#"{"status":405,"message":"405 Method Not Allowed"}"#);
+    let (status, body) = call(service().auto_options(true), "OPTIONS", "/missing").unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, r#"{"status":404,"message":"404 Not Found"}"#);
+}
@@ -0,0 +1,103 @@
+//! Tests that headers attached to errors reach a real client.
+
+use futures::Future;
+use http::{header, StatusCode};
+use hyper::Server;
+use hyperdrive::{
+    service::{ServiceExt, SyncService},
+    BoxedError, Error, FromRequest, Guard, NoContext,
+};
+use std::sync::Arc;
+
+/// Requires an `Authorization` header.
+struct User;
+
+impl Guard for User {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        if request.headers().contains_key(header::AUTHORIZATION) {
+            Ok(User)
+        } else {
+            let mut error = Error::from_status(StatusCode::UNAUTHORIZED);
+            error
+                .insert_header(header::WWW_AUTHENTICATE, r#"Bearer realm="api""#)
+                .unwrap();
+            Err(error.into())
+        }
+    }
+}
+
+/// Rejects every request, as if the rate limit was exhausted.
+struct RateLimit;
+
+impl Guard for RateLimit {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        Err(Error::builder(StatusCode::TOO_MANY_REQUESTS)
+            .header(header::RETRY_AFTER, "60")
+            .header("X-RateLimit-Remaining", "0")
+            .build()?
+            .into())
+    }
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/profile")]
+    Profile { _user: User },
+
+    #[get("/search")]
+    Search { _limit: RateLimit },
+}
+
+/// Binds a server to a random port and returns the port.
+fn spawn() -> u16 {
+    let service = SyncService::responding(|_: Route, _| "ok");
+    let srv =
+        Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service.make_service_by_cloning());
+    let port = srv.local_addr().port();
+
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    port
+}
+
+fn get(port: u16, path: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new().get(&format!("http://127.0.0.1:{}{}", port, path))
+}
+
+#[test]
+fn www_authenticate() {
+    let port = spawn();
+
+    let response = get(port, "/profile").send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers()[header::WWW_AUTHENTICATE],
+        r#"Bearer realm="api""#
+    );
+
+    let response = get(port, "/profile")
+        .header(header::AUTHORIZATION, "Bearer token")
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn retry_after() {
+    let port = spawn();
+
+    let response = get(port, "/search").send().unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+}
@@ -0,0 +1,155 @@
+use futures::{Async, Future};
+use http::{Response, StatusCode};
+use hyper::{Body, Chunk, Server};
+use hyperdrive::{
+    body::Json,
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    BoxedError, Error, FromRequest, Guard, NoContext,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Only lets requests with an `Authorization` header through.
+struct Authorized;
+
+impl Guard for Authorized {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        if request.headers().contains_key("Authorization") {
+            Ok(Authorized)
+        } else {
+            Err(Error::from_status(StatusCode::FORBIDDEN).into())
+        }
+    }
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[post("/upload")]
+    Upload {
+        _auth: Authorized,
+
+        #[body]
+        data: Json<Vec<u32>>,
+    },
+}
+
+fn service() -> SyncService<impl Fn(Route, Arc<http::Request<()>>) -> Response<Body>, Route> {
+    SyncService::new(|route: Route, _| match route {
+        Route::Upload { data, .. } => Response::new(Body::from(data.len().to_string())),
+    })
+}
+
+/// Creates a body that records whether it was polled.
+fn tracked_body(polled: Arc<AtomicBool>) -> Body {
+    let mut sent = false;
+    Body::wrap_stream(futures::stream::poll_fn(
+        move || -> Result<_, BoxedError> {
+            polled.store(true, Ordering::SeqCst);
+            if sent {
+                Ok(Async::Ready(None))
+            } else {
+                sent = true;
+                Ok(Async::Ready(Some(Chunk::from("[1, 2, 3]"))))
+            }
+        },
+    ))
+}
+
+#[test]
+fn rejected_body_is_not_polled() {
+    let mut client = Client::new(service());
+
+    let polled = Arc::new(AtomicBool::new(false));
+    let request = RequestBuilder::post("/upload")
+        .header("Expect", "100-continue")
+        .body(tracked_body(polled.clone()));
+    let response = client.send(request).unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!polled.load(Ordering::SeqCst));
+
+    let polled = Arc::new(AtomicBool::new(false));
+    let request = RequestBuilder::post("/upload")
+        .header("Expect", "100-continue")
+        .header("Authorization", "yes")
+        .body(tracked_body(polled.clone()));
+    let response = client.send(request).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "3");
+    assert!(polled.load(Ordering::SeqCst));
+}
+
+/// Reads a response head (up to the empty line) from `stream`.
+fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream
+            .read_exact(&mut byte)
+            .expect("couldn't read response");
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+/// Reads the head of the final response, skipping interim `1xx` responses.
+fn read_final_head(stream: &mut TcpStream) -> String {
+    loop {
+        let head = read_head(stream);
+        if !head.starts_with("HTTP/1.1 1") {
+            return head;
+        }
+    }
+}
+
+#[test]
+fn server() {
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service());
+    let port = srv.local_addr().port();
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let connect = || {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        stream
+    };
+    let body = "[1, 2, 3]";
+
+    // A rejected request is answered without uploading the body
+    let mut stream = connect();
+    write!(
+        stream,
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+        body.len()
+    )
+    .unwrap();
+    let head = read_final_head(&mut stream);
+    assert!(head.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{}", head);
+
+    // An accepted request is told to continue and answered after the upload
+    let mut stream = connect();
+    write!(
+        stream,
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nAuthorization: yes\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+        body.len()
+    )
+    .unwrap();
+    let head = read_head(&mut stream);
+    assert_eq!(head, "HTTP/1.1 100 Continue\r\n\r\n");
+
+    stream.write_all(body.as_bytes()).unwrap();
+    let head = read_final_head(&mut stream);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+}
@@ -0,0 +1,92 @@
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    guard::Extension,
+    http::{Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, ServiceExt},
+    BoxedError, Error, FromRequest, NoContext,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+struct RemoteAddr(String);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Missing;
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/")]
+    Index { addr: Extension<RemoteAddr> },
+
+    #[get("/optional")]
+    Optional {
+        addr: Option<Extension<RemoteAddr>>,
+        missing: Option<Extension<Missing>>,
+    },
+
+    #[get("/missing")]
+    Missing { _missing: Extension<Missing> },
+}
+
+fn call<S>(service: &mut S, path: &str) -> Response<Body>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    service.call(request).wait().unwrap()
+}
+
+fn body(response: Response<Body>) -> String {
+    let body = response.into_body().concat2().wait().unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[test]
+fn inserted_by_adapter() {
+    let mut service = AsyncService::new(|route: Routes, _| {
+        let body = match route {
+            Routes::Index { addr } => addr.into_inner().0,
+            Routes::Optional { addr, missing } => {
+                assert_eq!(missing, None);
+                addr.unwrap().into_inner().0
+            }
+            Routes::Missing { .. } => unreachable!(),
+        };
+        Ok(Response::new(Body::from(body))).into_future()
+    })
+    .map_request(|request| {
+        request
+            .extensions_mut()
+            .insert(RemoteAddr("127.0.0.1".to_string()));
+    });
+
+    assert_eq!(body(call(&mut service, "/")), "127.0.0.1");
+    assert_eq!(body(call(&mut service, "/optional")), "127.0.0.1");
+
+    let response = call(&mut service, "/missing");
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn missing() {
+    let request = Request::get("/missing").body(Body::empty()).unwrap();
+    let error: Box<Error> = Routes::from_request_sync(request, NoContext)
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert_eq!(error.http_status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(
+        error.to_string().contains("extension::Missing"),
+        "{}",
+        error
+    );
+
+    let request = Request::get("/optional").body(Body::empty()).unwrap();
+    match Routes::from_request_sync(request, NoContext).unwrap() {
+        Routes::Optional { addr, missing } => {
+            assert_eq!(addr, None);
+            assert_eq!(missing, None);
+        }
+        _ => panic!("wrong route"),
+    }
+}
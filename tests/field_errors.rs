@@ -0,0 +1,80 @@
+use http::StatusCode;
+use hyperdrive::{test::decode, test::RequestBuilder, Error, ErrorKind, FromRequest};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+struct Page {
+    count: u32,
+    #[serde(default)]
+    offset: u32,
+}
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/users/{id}/posts/{post}")]
+    Post { id: u32, post: u64 },
+
+    #[get("/search")]
+    Search {
+        #[query_params]
+        page: Page,
+    },
+}
+
+fn decode_error(request: RequestBuilder) -> Box<Error> {
+    decode::<Routes>(request)
+        .unwrap_err()
+        .downcast::<Error>()
+        .unwrap()
+}
+
+#[test]
+fn path_segment() {
+    let error = decode_error(RequestBuilder::get("/users/1/posts/latest"));
+    assert_eq!(error.kind(), ErrorKind::PathSegment);
+    assert_eq!(error.http_status(), StatusCode::NOT_FOUND);
+    assert_eq!(error.field(), Some("post"));
+    assert_eq!(
+        error.to_string(),
+        "404 Not Found: invalid `post`: invalid digit found in string"
+    );
+
+    let error = decode_error(RequestBuilder::get("/users/-1/posts/1"));
+    assert_eq!(error.field(), Some("id"));
+}
+
+#[test]
+fn query_param() {
+    let error = decode_error(RequestBuilder::get("/search?offset=10"));
+    assert_eq!(error.kind(), ErrorKind::QueryParam);
+    assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error.field(), Some("count"));
+    assert_eq!(
+        error.to_string(),
+        "400 Bad Request: missing `count`: missing field `count`"
+    );
+
+    match decode::<Routes>(RequestBuilder::get("/search?count=5")).unwrap() {
+        Routes::Search { page } => assert_eq!((page.count, page.offset), (5, 0)),
+        route => panic!("unexpected route {:?}", route),
+    }
+
+    // Errors that don't mention the field are attributed to it as well
+    let error = decode_error(RequestBuilder::get("/search?count=abc"));
+    assert_eq!(error.kind(), ErrorKind::QueryParam);
+    assert_eq!(error.field(), Some("count"));
+}
+
+#[test]
+fn custom() {
+    let error = Error::with_source_and_field(ErrorKind::QueryParam, "sort", "unknown order");
+    assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error.field(), Some("sort"));
+    assert_eq!(error.source().unwrap().to_string(), "unknown order");
+}
+
+#[test]
+#[should_panic(expected = "does not determine a status code")]
+fn kind_without_status() {
+    Error::with_source_and_field(ErrorKind::Other, "sort", "unknown order");
+}
@@ -0,0 +1,147 @@
+//! Tests passing data from the guards of an outer type to a forwarded type.
+
+use hyperdrive::{
+    http::{Request, StatusCode},
+    test::{decode_with_context, RequestBuilder},
+    BoxedError, Error, FromRequest, Guard, RequestContext,
+};
+use std::sync::Arc;
+
+#[derive(RequestContext)]
+struct AppContext {
+    admin_id: u32,
+}
+
+/// Authenticates the user via the `X-User` header.
+struct CurrentUser {
+    id: u32,
+}
+
+impl Guard for CurrentUser {
+    type Context = AppContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &AppContext) -> Self::Result {
+        request
+            .headers()
+            .get("x-user")
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+            .map(|id| CurrentUser { id })
+            .ok_or_else(|| Error::from_status(StatusCode::UNAUTHORIZED).into())
+    }
+}
+
+struct UserId(u32);
+
+#[derive(RequestContext)]
+struct UserContext {
+    #[as_ref]
+    user: UserId,
+    admin: bool,
+}
+
+fn user_context(app: &AppContext, user: &CurrentUser) -> UserContext {
+    UserContext {
+        user: UserId(user.id),
+        admin: user.id == app.admin_id,
+    }
+}
+
+/// Only lets the user with the given id access a resource.
+struct Owner(u32);
+
+impl Guard for Owner {
+    type Context = UserId;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, user: &UserId) -> Self::Result {
+        Ok(Owner(user.0))
+    }
+}
+
+/// Only succeeds for the admin.
+struct Admin;
+
+impl Guard for Admin {
+    type Context = UserContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, context: &UserContext) -> Self::Result {
+        if context.admin {
+            Ok(Admin)
+        } else {
+            Err(Error::from_status(StatusCode::FORBIDDEN).into())
+        }
+    }
+}
+
+#[derive(FromRequest)]
+#[context(UserContext)]
+enum UserRoutes {
+    #[get("/profile")]
+    Profile { owner: Owner },
+
+    #[get("/admin")]
+    Admin { _admin: Admin },
+}
+
+#[derive(FromRequest)]
+#[context(AppContext)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    User {
+        user: CurrentUser,
+
+        #[forward(with = user_context)]
+        inner: UserRoutes,
+    },
+}
+
+fn decode(request: RequestBuilder) -> Result<(u32, UserRoutes), BoxedError> {
+    match decode_with_context::<Routes>(request, AppContext { admin_id: 1 })? {
+        Routes::User { user, inner } => Ok((user.id, inner)),
+        Routes::Index => panic!("wrong route"),
+    }
+}
+
+/// Returns the HTTP status of the error `request` fails with.
+fn error_status(request: RequestBuilder) -> StatusCode {
+    match decode(request) {
+        Ok(_) => panic!("request succeeded"),
+        Err(error) => error.downcast::<Error>().unwrap().http_status(),
+    }
+}
+
+#[test]
+fn guard_data() {
+    match decode(RequestBuilder::get("/profile").header("X-User", "42")).unwrap() {
+        (42, UserRoutes::Profile { owner }) => assert_eq!(owner.0, 42),
+        _ => panic!("wrong route"),
+    }
+
+    // The outer context is available as well
+    match decode(RequestBuilder::get("/admin").header("X-User", "1")).unwrap() {
+        (1, UserRoutes::Admin { .. }) => {}
+        _ => panic!("wrong route"),
+    }
+    assert_eq!(
+        error_status(RequestBuilder::get("/admin").header("X-User", "2")),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[test]
+fn outer_guard_fails() {
+    assert_eq!(
+        error_status(RequestBuilder::get("/profile")),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Routes of the outer type don't run the guard
+    match decode_with_context::<Routes>(RequestBuilder::get("/"), AppContext { admin_id: 1 }) {
+        Ok(Routes::Index) => {}
+        _ => panic!("wrong route"),
+    }
+}
@@ -0,0 +1,173 @@
+//! Tests reusing guards that only depend on a part of the context.
+
+use futures::{Future, Stream};
+use hyperdrive::{
+    body::Json,
+    http::Request,
+    test::{decode_with_context, RequestBuilder},
+    BoxedError, DefaultFuture, FromBody, FromContext, FromRequest, Guard, RequestContext,
+};
+use std::sync::Arc;
+
+/// Stands in for a database connection pool from another crate, which can't
+/// implement `RequestContext`.
+struct DbPool {
+    name: &'static str,
+}
+
+/// The guard that gets reused. It doesn't know about any application context.
+struct Db(&'static str);
+
+impl Guard for Db {
+    type Context = DbPool;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, pool: &DbPool) -> Self::Result {
+        Ok(Db(pool.name))
+    }
+}
+
+/// Limits the number of bytes a `Limited` body may have.
+struct BodyLimit(usize);
+
+struct Limited(usize);
+
+impl FromBody for Limited {
+    type Context = BodyLimit;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_body(_: &Arc<Request<()>>, body: hyper::Body, limit: &BodyLimit) -> Self::Result {
+        let limit = limit.0;
+        Box::new(body.concat2().map_err(BoxedError::from).and_then(
+            move |body| -> Result<_, BoxedError> {
+                if body.len() > limit {
+                    Err("body too large".into())
+                } else {
+                    Ok(Limited(body.len()))
+                }
+            },
+        ))
+    }
+}
+
+#[derive(RequestContext)]
+struct Website {
+    #[as_ref]
+    db: DbPool,
+}
+
+#[derive(RequestContext)]
+struct Api {
+    #[as_ref]
+    db: Arc<DbPool>,
+    #[as_ref]
+    limit: BodyLimit,
+}
+
+#[derive(FromRequest)]
+#[context(Website)]
+enum WebsiteRoute {
+    #[get("/")]
+    Index { db: Db },
+}
+
+#[derive(FromRequest)]
+#[context(Api)]
+enum ApiRoute {
+    #[get("/users")]
+    Users { db: Db },
+
+    #[post("/users")]
+    AddUser {
+        db: Db,
+        #[body]
+        body: Limited,
+    },
+
+    #[post("/json")]
+    Json {
+        #[body]
+        body: Json<u32>,
+    },
+}
+
+#[test]
+fn reused_guard() {
+    let website = Website {
+        db: DbPool { name: "website" },
+    };
+    let WebsiteRoute::Index { db } =
+        decode_with_context::<WebsiteRoute>(RequestBuilder::get("/"), website).unwrap();
+    assert_eq!(db.0, "website");
+
+    let api = || Api {
+        db: Arc::new(DbPool { name: "api" }),
+        limit: BodyLimit(4),
+    };
+    match decode_with_context::<ApiRoute>(RequestBuilder::get("/users"), api()).unwrap() {
+        ApiRoute::Users { db } => assert_eq!(db.0, "api"),
+        _ => panic!("wrong route"),
+    }
+
+    match decode_with_context::<ApiRoute>(RequestBuilder::post("/users").body("1234"), api())
+        .unwrap()
+    {
+        ApiRoute::AddUser { db, body } => {
+            assert_eq!(db.0, "api");
+            assert_eq!(body.0, 4);
+        }
+        _ => panic!("wrong route"),
+    }
+    assert!(
+        decode_with_context::<ApiRoute>(RequestBuilder::post("/users").body("12345"), api())
+            .is_err()
+    );
+
+    // `NoContext` is still obtained via `AsRef`
+    match decode_with_context::<ApiRoute>(RequestBuilder::post("/json").body("7"), api()).unwrap() {
+        ApiRoute::Json { body } => assert_eq!(body.0, 7),
+        _ => panic!("wrong route"),
+    }
+}
+
+/// A context that provides a `DbPool` without an `AsRef` impl.
+struct Manual {
+    pools: Vec<DbPool>,
+}
+
+impl RequestContext for Manual {}
+
+impl AsRef<Manual> for Manual {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl AsRef<hyperdrive::NoContext> for Manual {
+    fn as_ref(&self) -> &hyperdrive::NoContext {
+        &hyperdrive::NoContext
+    }
+}
+
+impl FromContext<Manual> for DbPool {
+    fn from_context(context: &Manual) -> &DbPool {
+        &context.pools[0]
+    }
+}
+
+#[derive(FromRequest)]
+#[context(Manual)]
+enum ManualRoute {
+    #[get("/")]
+    Index { db: Db },
+}
+
+#[test]
+fn manual_impl() {
+    let context = Manual {
+        pools: vec![DbPool { name: "primary" }, DbPool { name: "replica" }],
+    };
+    let ManualRoute::Index { db } =
+        decode_with_context::<ManualRoute>(RequestBuilder::get("/"), context).unwrap();
+    assert_eq!(db.0, "primary");
+}
@@ -2,7 +2,7 @@ use hyperdrive::{
     body::Json,
     http::{Method, Request, StatusCode},
     hyper::Body,
-    BoxedError, Error, FromRequest, Guard, NoContext, RequestContext,
+    BoxedError, Error, ErrorKind, FromRequest, Guard, NoContext, QueryParamError, RequestContext,
 };
 use serde::Deserialize;
 use std::str::FromStr;
@@ -125,7 +125,7 @@ fn user_app() {
     assert_eq!(error.http_status(), StatusCode::METHOD_NOT_ALLOWED);
     assert_eq!(
         error.allowed_methods().expect("allowed_methods()"),
-        &[&Method::GET, &Method::PATCH, &Method::HEAD]
+        &[&Method::GET, &Method::HEAD, &Method::PATCH]
     );
 
     let user = invoke::<Routes>(Request::get("/users/wrong").body(Body::empty()).unwrap());
@@ -383,12 +383,265 @@ fn query_params() {
     );
 }
 
+/// Repeated query parameters can be deserialized into sequences.
+#[test]
+fn query_param_sequences() {
+    #[derive(FromRequest, PartialEq, Eq, Debug)]
+    enum Routes {
+        #[get("/search")]
+        Search {
+            #[query_params]
+            query: Search,
+        },
+    }
+
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Search {
+        q: String,
+        #[serde(default)]
+        tag: Vec<String>,
+        id: Option<Vec<u32>>,
+    }
+
+    fn search(query: &str) -> Result<Search, Box<Error>> {
+        let request = Request::get(format!("/search{}", query))
+            .body(Body::empty())
+            .unwrap();
+        match invoke::<Routes>(request) {
+            Ok(Routes::Search { query }) => Ok(query),
+            Err(e) => Err(e.downcast().unwrap()),
+        }
+    }
+
+    // Zero occurrences
+    assert_eq!(
+        search("?q=rust").unwrap(),
+        Search {
+            q: "rust".to_string(),
+            tag: vec![],
+            id: None,
+        }
+    );
+
+    // One occurrence
+    assert_eq!(
+        search("?tag=a&q=rust&id=1").unwrap(),
+        Search {
+            q: "rust".to_string(),
+            tag: vec!["a".to_string()],
+            id: Some(vec![1]),
+        }
+    );
+
+    // Many occurrences, in between other parameters
+    assert_eq!(
+        search("?tag=a&id=3&q=rust&tag=b%20c&id=1&tag=a&unknown=1&unknown=2").unwrap(),
+        Search {
+            q: "rust".to_string(),
+            tag: vec!["a".to_string(), "b c".to_string(), "a".to_string()],
+            id: Some(vec![3, 1]),
+        }
+    );
+
+    // Empty values are left out of sequences
+    assert_eq!(
+        search("?q=&tag=&id=&tag=a").unwrap(),
+        Search {
+            q: String::new(),
+            tag: vec!["a".to_string()],
+            id: Some(vec![]),
+        }
+    );
+
+    // Scalars can't be repeated
+    let error = search("?q=rust&q=serde").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::QueryParam);
+    assert_eq!(error.field(), Some("q"));
+
+    let error = search("?q=rust&id=1&id=x").unwrap_err();
+    assert_eq!(
+        error.query_param_error(),
+        Some(&QueryParamError::Invalid {
+            name: "id".to_string(),
+            value: "x".to_string(),
+        })
+    );
+}
+
+/// `#[query_params(strict)]` rejects parameters the type doesn't know.
+#[test]
+fn strict_query_params() {
+    #[derive(FromRequest, PartialEq, Eq, Debug)]
+    enum Routes {
+        #[get("/users")]
+        UserList {
+            #[query_params(strict)]
+            pagination: Pagination,
+        },
+    }
+
+    #[derive(Deserialize, PartialEq, Eq, Debug)]
+    struct Pagination {
+        #[serde(default)]
+        page: u32,
+        #[serde(default, rename = "per_page")]
+        count: u32,
+    }
+
+    fn list(query: &str) -> Result<Pagination, Box<Error>> {
+        let request = Request::get(format!("/users{}", query))
+            .body(Body::empty())
+            .unwrap();
+        match invoke::<Routes>(request) {
+            Ok(Routes::UserList { pagination }) => Ok(pagination),
+            Err(e) => Err(e.downcast().unwrap()),
+        }
+    }
+
+    assert_eq!(list("").unwrap(), Pagination { page: 0, count: 0 });
+    assert_eq!(
+        list("?page=2&per_page=50").unwrap(),
+        Pagination { page: 2, count: 50 }
+    );
+
+    let error = list("?pgae=2").unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::QueryParam);
+    assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error.field(), Some("pgae"));
+    assert_eq!(
+        error.query_param_error(),
+        Some(&QueryParamError::Unknown(vec!["pgae".to_string()]))
+    );
+    assert_eq!(
+        error.to_string(),
+        "400 Bad Request: unknown `pgae`: unknown field `pgae`"
+    );
+
+    // Renamed fields are only known by their serde name
+    let error = list("?page=2&count=50&sort=name&count=10").unwrap_err();
+    assert_eq!(
+        error.query_param_error(),
+        Some(&QueryParamError::Unknown(vec![
+            "count".to_string(),
+            "sort".to_string()
+        ]))
+    );
+}
+
+#[test]
+fn query_param_errors() {
+    #[derive(FromRequest, Debug)]
+    enum Routes {
+        #[get("/users")]
+        UserList {
+            #[query_params]
+            pagination: Pagination,
+        },
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Pagination {
+        start_id: u32,
+        count: u32,
+    }
+
+    let query_error = |uri| {
+        let error: Box<Error> = invoke::<Routes>(Request::get(uri).body(Body::empty()).unwrap())
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::QueryParam);
+        assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+        error.query_param_error().cloned()
+    };
+
+    assert_eq!(
+        query_error("/users?start_id=42"),
+        Some(QueryParamError::Missing("count".to_string()))
+    );
+    assert_eq!(
+        query_error("/users?count=10&start_id=forty-two"),
+        Some(QueryParamError::Invalid {
+            name: "start_id".to_string(),
+            value: "forty-two".to_string(),
+        })
+    );
+    assert_eq!(
+        query_error("/users?start_id=42&count=%F0%9F%A6%80"),
+        Some(QueryParamError::Invalid {
+            name: "count".to_string(),
+            value: "\u{1F980}".to_string(),
+        })
+    );
+
+    let route = invoke::<Routes>(
+        Request::get("/users?start_id=42&count=10")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .unwrap();
+    match route {
+        Routes::UserList { pagination } => {
+            assert_eq!((pagination.start_id, pagination.count), (42, 10));
+        }
+    }
+}
+
+/// Tests that different instantiations of a generic type route the same way.
+#[test]
+fn generic_instantiations() {
+    #[derive(FromRequest, Debug, PartialEq, Eq)]
+    enum Routes<T> {
+        #[get("/")]
+        Index,
+
+        #[get("/items/{id}")]
+        #[delete("/items/{id}")]
+        Item { id: T },
+    }
+
+    assert_eq!(
+        invoke::<Routes<u32>>(Request::get("/items/7").body(Body::empty()).unwrap()).unwrap(),
+        Routes::Item { id: 7 }
+    );
+    assert_eq!(
+        invoke::<Routes<String>>(Request::get("/items/seven").body(Body::empty()).unwrap())
+            .unwrap(),
+        Routes::Item {
+            id: "seven".to_string()
+        }
+    );
+    assert_eq!(
+        invoke::<Routes<u32>>(Request::get("/").body(Body::empty()).unwrap()).unwrap(),
+        Routes::Index
+    );
+    assert_eq!(
+        invoke::<Routes<String>>(Request::get("/").body(Body::empty()).unwrap()).unwrap(),
+        Routes::Index
+    );
+
+    // The placeholder type is still checked per instantiation
+    assert!(
+        invoke::<Routes<u32>>(Request::get("/items/seven").body(Body::empty()).unwrap()).is_err()
+    );
+    let err: Box<Error> =
+        invoke::<Routes<String>>(Request::post("/items/seven").body(Body::empty()).unwrap())
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+    assert_eq!(
+        err.allowed_methods().expect("allowed_methods()"),
+        &[&Method::GET, &Method::HEAD, &Method::DELETE]
+    );
+}
+
 /// Tests that the derive works on generic enums and structs.
 #[test]
 fn generic() {
     #[derive(FromRequest, Debug, PartialEq, Eq)]
     enum Routes<U, Q, B, G> {
         #[get("/{path}")]
+        #[allow_body_on_get]
         OmniRoute {
             path: U,
 
@@ -407,6 +660,7 @@ fn generic() {
 
     #[derive(FromRequest, Debug, PartialEq, Eq)]
     #[get("/{path}")]
+    #[allow_body_on_get]
     #[context(SpecialContext)]
     struct Struct<U, Q, B, G> {
         path: U,
@@ -821,3 +1075,134 @@ fn klepto_arc() {
     assert_eq!(route.guard.request.uri(), "/");
     assert_eq!(route.guard.request.method(), "GET");
 }
+
+#[test]
+fn route_name() {
+    #[derive(FromRequest)]
+    enum Enum {
+        #[get("/")]
+        Index,
+
+        #[get("/users/{id}")]
+        User { id: u32 },
+    }
+
+    #[derive(FromRequest)]
+    #[get("/")]
+    struct Struct {}
+
+    let route: Enum = invoke(Request::get("/").body(Body::empty()).unwrap()).unwrap();
+    assert_eq!(route.route_name(), Some("Index"));
+
+    let route: Enum = invoke(Request::get("/users/1").body(Body::empty()).unwrap()).unwrap();
+    assert_eq!(route.route_name(), Some("User"));
+
+    let route: Struct = invoke(Request::get("/").body(Body::empty()).unwrap()).unwrap();
+    assert_eq!(route.route_name(), Some("Struct"));
+}
+
+/// Tests that the `Allow` header of `405 Method Not Allowed` responses lists every method once,
+/// even if the same methods are allowed by a `#[forward]`ed route.
+#[test]
+fn forward_allow_header() {
+    use hyperdrive::{
+        service::SyncService,
+        test::{Client, RequestBuilder},
+    };
+
+    #[derive(FromRequest, Debug)]
+    enum Inner {
+        #[get("/items")]
+        List,
+
+        #[post("/items")]
+        Create,
+    }
+
+    #[derive(FromRequest, Debug)]
+    enum Wrapper {
+        #[get("/items")]
+        Cached,
+
+        #[delete("/items")]
+        Clear,
+
+        Fallback {
+            #[forward]
+            inner: Inner,
+        },
+    }
+
+    let err: Box<Error> = invoke::<Wrapper>(Request::put("/items").body(Body::empty()).unwrap())
+        .unwrap_err()
+        .downcast()
+        .unwrap();
+    assert_eq!(
+        err.allowed_methods().expect("allowed_methods()"),
+        &[&Method::GET, &Method::HEAD, &Method::POST, &Method::DELETE]
+    );
+    assert_eq!(err.request_method(), Some(&Method::PUT));
+    assert_eq!(err.request_path(), Some("/items"));
+
+    let mut client = Client::new(SyncService::responding(|route: Wrapper, _| match route {
+        Wrapper::Cached => "cached",
+        Wrapper::Clear => "cleared",
+        Wrapper::Fallback { inner: Inner::List } => "list",
+        Wrapper::Fallback {
+            inner: Inner::Create,
+        } => "created",
+    }));
+
+    let response = client.send(RequestBuilder::put("/items")).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, DELETE"));
+
+    let response = client.send(RequestBuilder::post("/items")).unwrap();
+    assert_eq!(response.text(), "created");
+}
+
+/// `#[body]` on `GET`/`HEAD`-only variants requires `#[allow_body_on_get]`,
+/// and is only warned about on `DELETE` routes.
+#[test]
+#[allow(deprecated)] // The warning emitted for `DeleteMany`
+fn body_on_get_and_delete() {
+    #[derive(FromRequest, Debug, PartialEq, Eq)]
+    enum Routes {
+        #[get("/search")]
+        #[allow_body_on_get]
+        Search {
+            #[body]
+            query: Json<String>,
+        },
+
+        #[delete("/items")]
+        DeleteMany {
+            #[body]
+            ids: Json<Vec<u32>>,
+        },
+
+        // A variant with other methods doesn't need the attribute
+        #[get("/items")]
+        #[post("/items")]
+        Items {
+            #[body]
+            item: Json<String>,
+        },
+    }
+
+    let route = invoke::<Routes>(Request::get("/search").body(r#""q""#.into()).unwrap()).unwrap();
+    assert_eq!(
+        route,
+        Routes::Search {
+            query: Json("q".to_string())
+        }
+    );
+
+    let route = invoke::<Routes>(Request::delete("/items").body("[1, 2]".into()).unwrap()).unwrap();
+    assert_eq!(
+        route,
+        Routes::DeleteMany {
+            ids: Json(vec![1, 2])
+        }
+    );
+}
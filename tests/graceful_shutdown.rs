@@ -0,0 +1,123 @@
+use futures::{Future, IntoFuture};
+use http::{Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::service::{AsyncService, GracefulShutdown, ServiceExt, SyncService};
+use hyperdrive::{BoxedError, DefaultFuture, FromRequest};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[get("/slow")]
+    Slow,
+}
+
+/// Starts a server that shuts down via `shutdown`, and returns its port and
+/// the thread running it.
+fn spawn<S>(service: S, shutdown: &GracefulShutdown) -> (u16, JoinHandle<()>)
+where
+    S: ServiceExt<ReqBody = Body, ResBody = Body, Error = BoxedError> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(
+        service
+            .graceful_shutdown(shutdown)
+            .make_service_by_cloning(),
+    );
+    let port = srv.local_addr().port();
+    let srv = srv.with_graceful_shutdown(shutdown.signal());
+
+    let thread = thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    (port, thread)
+}
+
+/// Sends a request from a new thread, returning the status and body.
+fn get(port: u16, path: &'static str) -> JoinHandle<(StatusCode, String)> {
+    thread::spawn(move || {
+        let mut response =
+            reqwest::get(&format!("http://127.0.0.1:{}{}", port, path)).expect("request failed");
+        (response.status(), response.text().unwrap())
+    })
+}
+
+/// Waits until `count` requests are in flight.
+fn wait_for_requests(shutdown: &GracefulShutdown, count: usize) {
+    let start = Instant::now();
+    while shutdown.in_flight() != count {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "request didn't arrive"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn drains_in_flight_requests() {
+    let shutdown = GracefulShutdown::new();
+    let service = SyncService::responding(|route: Route, _| match route {
+        Route::Index => "index",
+        Route::Slow => {
+            thread::sleep(Duration::from_millis(500));
+            "slow"
+        }
+    });
+    let (port, server) = spawn(service, &shutdown);
+
+    let request = get(port, "/slow");
+    wait_for_requests(&shutdown, 1);
+
+    assert!(!shutdown.is_shutting_down());
+    shutdown.shutdown();
+    assert!(shutdown.is_shutting_down());
+
+    assert!(shutdown.wait_idle(Duration::from_secs(10)));
+    assert_eq!(shutdown.in_flight(), 0);
+    assert_eq!(
+        request.join().unwrap(),
+        (StatusCode::OK, "slow".to_string())
+    );
+
+    // The server stops once the connection is closed
+    server.join().unwrap();
+}
+
+#[test]
+fn expired_requests_fail() {
+    let shutdown = GracefulShutdown::new();
+    let service = AsyncService::new(|route: Route, _| -> DefaultFuture<_, BoxedError> {
+        match route {
+            Route::Index => Box::new(Ok(Response::new(Body::from("index"))).into_future()),
+            Route::Slow => Box::new(futures::future::empty()),
+        }
+    });
+    let (port, server) = spawn(service, &shutdown);
+
+    let request = get(port, "/slow");
+    wait_for_requests(&shutdown, 1);
+
+    shutdown.shutdown();
+    assert!(!shutdown.wait_idle(Duration::from_millis(100)));
+    assert_eq!(
+        request.join().unwrap(),
+        (StatusCode::SERVICE_UNAVAILABLE, String::new())
+    );
+
+    server.join().unwrap();
+}
+
+#[test]
+fn signal_after_shutdown() {
+    let shutdown = GracefulShutdown::new();
+    shutdown.shutdown();
+    shutdown.signal().wait().unwrap();
+    assert!(shutdown.wait_idle(Duration::from_secs(0)));
+}
@@ -0,0 +1,83 @@
+use futures::Future;
+use http::{header, HeaderMap, Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::service::SyncService;
+use hyperdrive::FromRequest;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/text")]
+    Text,
+
+    #[get("/etag")]
+    Etag,
+
+    #[get("/explicit")]
+    Explicit,
+}
+
+/// Starts a server on a new thread and returns its port.
+fn spawn() -> u16 {
+    let service = SyncService::new(|route: Route, _| match route {
+        Route::Text => Response::new(Body::from("Hello World!")),
+        Route::Etag => Response::builder()
+            .header("Content-Type", "application/json")
+            .header("ETag", "\"abc\"")
+            .body(Body::from("{\"id\":1}"))
+            .unwrap(),
+        // A handler can advertise a length when it knows the body is omitted
+        Route::Explicit => Response::builder()
+            .header("Content-Length", "3")
+            .body(Body::from("abc"))
+            .unwrap(),
+    });
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    let port = srv.local_addr().port();
+
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    port
+}
+
+/// Sends a `method` request to `path`, and returns the response headers and body.
+fn send(port: u16, method: reqwest::Method, path: &str) -> (HeaderMap, String) {
+    let mut response = reqwest::Client::new()
+        .request(method, &format!("http://127.0.0.1:{}{}", port, path))
+        .send()
+        .expect("request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers().clone();
+    (headers, response.text().unwrap())
+}
+
+#[test]
+fn head_matches_get() {
+    let port = spawn();
+
+    for path in &["/text", "/etag", "/explicit"] {
+        let (get_headers, get_body) = send(port, reqwest::Method::GET, path);
+        let (head_headers, head_body) = send(port, reqwest::Method::HEAD, path);
+
+        assert!(!get_body.is_empty(), "{}", path);
+        assert_eq!(head_body, "", "{}", path);
+        assert_eq!(
+            head_headers[header::CONTENT_LENGTH],
+            get_body.len().to_string(),
+            "{}",
+            path
+        );
+        for name in &[header::CONTENT_LENGTH, header::CONTENT_TYPE, header::ETAG] {
+            assert_eq!(
+                head_headers.get(name),
+                get_headers.get(name),
+                "{} {}",
+                path,
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use hyperdrive::{
+    http::{header, StatusCode},
+    service::{GracefulShutdown, HealthConfig, ReadinessFlag, ReadyState, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[post("/healthz")]
+    Post,
+}
+
+fn app() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> &'static str, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Index => "index",
+        Routes::Post => "post",
+    })
+}
+
+#[test]
+fn readiness_flag() {
+    let ready = ReadinessFlag::new();
+    let mut client = Client::new(app().with_health(HealthConfig::new().readiness_flag(&ready)));
+    let mut status = |path| client.send(RequestBuilder::get(path)).unwrap().status();
+
+    assert_eq!(status("/healthz"), StatusCode::OK);
+    assert_eq!(status("/readyz"), StatusCode::SERVICE_UNAVAILABLE);
+
+    ready.set_ready(true);
+    assert_eq!(status("/healthz"), StatusCode::OK);
+    assert_eq!(status("/readyz"), StatusCode::OK);
+
+    ready.set_ready(false);
+    assert_eq!(status("/readyz"), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[test]
+fn responses() {
+    let mut client = Client::new(app().with_health(HealthConfig::default()));
+
+    let response = client.send(RequestBuilder::get("/readyz")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "ready\n");
+    assert_eq!(
+        response.header(header::CONTENT_TYPE),
+        Some("text/plain; charset=utf-8")
+    );
+    assert_eq!(
+        response.header(header::CACHE_CONTROL),
+        Some("no-cache, no-store")
+    );
+
+    let response = client.send(RequestBuilder::head("/healthz")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body(), b"");
+    assert_eq!(response.header(header::CONTENT_LENGTH), Some("3"));
+
+    // Other requests reach the app
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+    let response = client.send(RequestBuilder::post("/healthz")).unwrap();
+    assert_eq!(response.text(), "post");
+}
+
+#[test]
+fn custom() {
+    let checks = Arc::new(AtomicUsize::new(0));
+    let checks2 = checks.clone();
+    let config = HealthConfig::new()
+        .liveness_path("/live")
+        .readiness_path("/ready")
+        .readiness(move || {
+            checks2.fetch_add(1, Ordering::SeqCst);
+            ReadyState::NotReady
+        });
+    let mut client = Client::new(app().with_health(config));
+    let mut status = |path| client.send(RequestBuilder::get(path)).unwrap().status();
+
+    assert_eq!(status("/live"), StatusCode::OK);
+    assert_eq!(status("/ready"), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(status("/readyz"), StatusCode::NOT_FOUND);
+    assert_eq!(checks.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn graceful_shutdown() {
+    let shutdown = GracefulShutdown::new();
+    let config = HealthConfig::new().graceful_shutdown(&shutdown);
+    let mut client = Client::new(app().graceful_shutdown(&shutdown).with_health(config));
+    let mut status = |path| client.send(RequestBuilder::get(path)).unwrap().status();
+
+    assert_eq!(status("/readyz"), StatusCode::OK);
+    shutdown.shutdown();
+    assert_eq!(status("/readyz"), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(status("/healthz"), StatusCode::OK);
+}
+
+#[test]
+fn not_logged() {
+    let logged = Arc::new(AtomicUsize::new(0));
+    let logged2 = logged.clone();
+    let mut client = Client::new(app().with_health(HealthConfig::new()).log(move |_, _, _| {
+        logged2.fetch_add(1, Ordering::SeqCst);
+    }));
+    client.send(RequestBuilder::get("/healthz")).unwrap();
+    client.send(RequestBuilder::get("/readyz")).unwrap();
+    assert_eq!(logged.load(Ordering::SeqCst), 0);
+    client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(logged.load(Ordering::SeqCst), 1);
+
+    let logged = Arc::new(AtomicUsize::new(0));
+    let logged2 = logged.clone();
+    let mut client = Client::new(
+        app()
+            .with_health(HealthConfig::new())
+            .log(move |_, _, _| {
+                logged2.fetch_add(1, Ordering::SeqCst);
+            })
+            .log_health_checks(true),
+    );
+    client.send(RequestBuilder::get("/healthz")).unwrap();
+    assert_eq!(logged.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[should_panic(expected = "must start with `/`")]
+fn invalid_path() {
+    HealthConfig::new().readiness_path("readyz");
+}
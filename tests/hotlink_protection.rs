@@ -0,0 +1,104 @@
+use hyperdrive::{
+    guard::{HotlinkProtection, HotlinkProtectionConfig},
+    http::{Method, Request, StatusCode},
+    hyper::Body,
+    BoxedError, Error, FromRequest,
+};
+
+#[derive(FromRequest, Debug)]
+#[context(HotlinkProtectionConfig)]
+enum Routes {
+    #[get("/image.png")]
+    #[post("/image.png")]
+    Image { _hotlink: HotlinkProtection },
+}
+
+fn invoke(
+    method: Method,
+    referer: Option<&str>,
+    config: HotlinkProtectionConfig,
+) -> Result<Routes, BoxedError> {
+    let mut request = Request::builder();
+    request.method(method).uri("/image.png");
+    if let Some(referer) = referer {
+        request.header("Referer", referer);
+    }
+    let request = request.body(Body::empty()).unwrap();
+    Routes::from_request_sync(request, config)
+}
+
+fn forbidden(result: Result<Routes, BoxedError>) -> Box<Error> {
+    let error: Box<Error> = result.unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), StatusCode::FORBIDDEN);
+    error
+}
+
+fn config() -> HotlinkProtectionConfig {
+    HotlinkProtectionConfig::new()
+        .allow_origin("https://example.com")
+        .allow_origin("http://localhost:8080")
+}
+
+#[test]
+fn allowed_origin() {
+    for referer in &[
+        "https://example.com/",
+        "https://example.com",
+        "https://example.com/gallery/cats?page=2#top",
+        "HTTPS://EXAMPLE.COM/gallery",
+        "https://example.com:443/gallery",
+        "https://user:pw@example.com/",
+        "http://localhost:8080/test",
+    ] {
+        invoke(Method::GET, Some(referer), config())
+            .unwrap_or_else(|e| panic!("{}: {}", referer, e));
+        invoke(Method::HEAD, Some(referer), config())
+            .unwrap_or_else(|e| panic!("{}: {}", referer, e));
+    }
+}
+
+#[test]
+fn disallowed_origin() {
+    for referer in &[
+        "https://evil.example.org/",
+        "http://example.com/",
+        "https://example.com:8443/",
+        "https://sub.example.com/",
+        "https://example.com.evil.org/",
+        "https://example.com@evil.org/",
+        "http://localhost/",
+    ] {
+        let error = forbidden(invoke(Method::GET, Some(referer), config()));
+        assert!(error.to_string().contains("is not allowed"), "{}", error);
+    }
+
+    let error = forbidden(invoke(Method::GET, Some("/relative"), config()));
+    assert!(error.to_string().contains("invalid"), "{}", error);
+}
+
+#[test]
+fn missing_referer() {
+    invoke(Method::GET, None, config()).unwrap();
+
+    let config = config().allow_missing_referer(false);
+    forbidden(invoke(Method::GET, None, config.clone()));
+    invoke(Method::GET, Some("https://example.com/"), config).unwrap();
+}
+
+#[test]
+fn unsafe_methods() {
+    let config = config().allow_missing_referer(false);
+    invoke(
+        Method::POST,
+        Some("https://evil.example.org/"),
+        config.clone(),
+    )
+    .unwrap();
+    invoke(Method::POST, None, config).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "invalid origin")]
+fn invalid_allowed_origin() {
+    HotlinkProtectionConfig::new().allow_origin("example.com");
+}
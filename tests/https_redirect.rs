@@ -0,0 +1,175 @@
+use hyperdrive::{
+    http::{Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{HttpsRedirect, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[post("/orders")]
+    Order,
+
+    #[get("/.well-known/acme-challenge/{token}")]
+    AcmeChallenge { token: String },
+}
+
+fn app(
+    config: HttpsRedirect,
+) -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Index => "index".to_string(),
+        Routes::Order => "ordered".to_string(),
+        Routes::AcmeChallenge { token } => format!("challenge {}", token),
+    })
+    .redirect_to_https(config)
+}
+
+#[test]
+fn redirects() {
+    let mut client = Client::new(app(HttpsRedirect::new()));
+
+    let response = client
+        .send(RequestBuilder::get("/?page=2").header("Host", "example.com:8080"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        response.header("Location"),
+        Some("https://example.com/?page=2")
+    );
+    assert_eq!(response.text(), "");
+
+    let response = client
+        .send(RequestBuilder::head("/").header("Host", "example.com"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+
+    // Requests without a valid host can't be redirected
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client
+        .send(RequestBuilder::get("/").header("Host", "example.com/evil"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn preserves_method() {
+    let mut client = Client::new(app(HttpsRedirect::new()));
+
+    let response = client
+        .send(RequestBuilder::post("/orders").header("Host", "example.com"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.header("Location"),
+        Some("https://example.com/orders")
+    );
+}
+
+#[test]
+fn host_override() {
+    let mut client = Client::new(app(
+        HttpsRedirect::new().host(Some("secure.example.com:8443"))
+    ));
+
+    let response = client
+        .send(RequestBuilder::get("/").header("Host", "example.com"))
+        .unwrap();
+    assert_eq!(
+        response.header("Location"),
+        Some("https://secure.example.com:8443/")
+    );
+}
+
+#[test]
+fn acme_challenge() {
+    // Additional exemptions don't replace the default one
+    let mut client = Client::new(app(HttpsRedirect::new().exempt_path("/health")));
+
+    let response = client
+        .send(
+            RequestBuilder::get("/.well-known/acme-challenge/abc123").header("Host", "example.com"),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "challenge abc123");
+
+    // Exempt requests are passed through, even if there is no such route
+    let response = client
+        .send(RequestBuilder::get("/health").header("Host", "example.com"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn trusted_proxy() {
+    // Forwarding headers are ignored by default, since clients can send them
+    let mut client = Client::new(app(HttpsRedirect::new()));
+    let response = client
+        .send(
+            RequestBuilder::get("/")
+                .header("Host", "example.com")
+                .header("X-Forwarded-Proto", "https"),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+
+    let mut client = Client::new(app(HttpsRedirect::new().trust_forwarded_proto(true)));
+    let get = |client: &mut Client<_>, name: &str, value: &str| {
+        client
+            .send(
+                RequestBuilder::get("/")
+                    .header("Host", "example.com")
+                    .header(name, value),
+            )
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(
+        get(&mut client, "X-Forwarded-Proto", "https"),
+        StatusCode::OK
+    );
+    assert_eq!(
+        get(&mut client, "X-Forwarded-Proto", "http"),
+        StatusCode::MOVED_PERMANENTLY
+    );
+    // The proxy closest to us appends the last value
+    assert_eq!(
+        get(&mut client, "X-Forwarded-Proto", "https, http"),
+        StatusCode::MOVED_PERMANENTLY
+    );
+    assert_eq!(
+        get(
+            &mut client,
+            "Forwarded",
+            "for=192.0.2.60;proto=https;by=203.0.113.43"
+        ),
+        StatusCode::OK
+    );
+    assert_eq!(
+        get(
+            &mut client,
+            "Forwarded",
+            "proto=https, for=192.0.2.43;proto=http"
+        ),
+        StatusCode::MOVED_PERMANENTLY
+    );
+}
+
+#[test]
+fn tls_listener() {
+    let mut client = Client::new(app(HttpsRedirect::new().tls(true)));
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+}
@@ -0,0 +1,243 @@
+//! Tests the `IntoResponse` implementations through `SyncService`.
+
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    http::{Request, Response, StatusCode, Uri},
+    hyper::{service::Service, Body},
+    response::{Created, IntoResponse, Json, NoContent, Redirect},
+    service::{AsyncService, SyncService},
+    BoxedError, FromRequest, ToRequest,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_threadpool::ThreadPool;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+}
+
+/// Sends a `GET /` request to a `SyncService` whose handler returns `value`,
+/// and returns the response with the collected body.
+fn respond<T>(value: T) -> Response<Vec<u8>>
+where
+    T: IntoResponse + Clone + Send + Sync + 'static,
+{
+    let service = SyncService::responding(move |Routes::Index, _: Arc<Request<()>>| value.clone());
+    call(service, "GET")
+}
+
+fn call<S>(mut service: S, method: &str) -> Response<Vec<u8>>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::builder()
+        .method(method)
+        .uri("/")
+        .body(Body::empty())
+        .unwrap();
+    let pool = ThreadPool::new();
+    let response = pool.spawn_handle(service.call(request)).wait().unwrap();
+    pool.shutdown_now().wait().unwrap();
+    let (parts, body) = response.into_parts();
+    let body = body.concat2().wait().unwrap().to_vec();
+    Response::from_parts(parts, body)
+}
+
+fn content_type(response: &Response<Vec<u8>>) -> &str {
+    response.headers()["content-type"].to_str().unwrap()
+}
+
+#[test]
+fn response() {
+    let response = call(
+        SyncService::new(|Routes::Index, _| {
+            Response::builder()
+                .status(StatusCode::CREATED)
+                .header("x-custom", "yes")
+                .body(Body::from("created"))
+                .unwrap()
+        }),
+        "GET",
+    );
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["x-custom"], "yes");
+    assert_eq!(response.body(), b"created");
+}
+
+#[test]
+fn str() {
+    let response = respond("Hello World!");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "text/plain; charset=utf-8");
+    assert_eq!(response.body(), b"Hello World!");
+}
+
+#[test]
+fn string() {
+    let response = respond(format!("User #{}", 5));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "text/plain; charset=utf-8");
+    assert_eq!(response.body(), b"User #5");
+}
+
+#[test]
+fn bytes() {
+    let response = respond(vec![0, 1, 2, 255]);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "application/octet-stream");
+    assert_eq!(response.body(), &[0, 1, 2, 255]);
+}
+
+#[test]
+fn unit() {
+    let response = respond(());
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("content-type").is_none());
+    assert!(response.body().is_empty());
+}
+
+#[test]
+fn status() {
+    let response = respond(StatusCode::NO_CONTENT);
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(response.headers().get("content-type").is_none());
+    assert!(response.body().is_empty());
+}
+
+#[test]
+fn status_and_body() {
+    let response = respond((StatusCode::IM_A_TEAPOT, "short and stout"));
+    assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+    assert_eq!(content_type(&response), "text/plain; charset=utf-8");
+    assert_eq!(response.body(), b"short and stout");
+}
+
+#[test]
+fn json() {
+    #[derive(Serialize, Clone)]
+    struct User {
+        id: u32,
+        name: &'static str,
+    }
+
+    let response = respond(Json(User {
+        id: 1,
+        name: "Ferris",
+    }));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "application/json");
+    assert_eq!(response.body(), br#"{"id":1,"name":"Ferris"}"#);
+
+    let response = respond((StatusCode::CREATED, Json(vec![1, 2, 3])));
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(content_type(&response), "application/json");
+    assert_eq!(response.body(), b"[1,2,3]");
+}
+
+#[test]
+fn head() {
+    let service = SyncService::responding(|Routes::Index, _| "Hello World!");
+    let response = call(service, "HEAD");
+    assert_eq!(content_type(&response), "text/plain; charset=utf-8");
+    assert!(response.body().is_empty());
+}
+
+#[test]
+fn async_service() {
+    let service = AsyncService::responding(|Routes::Index, _| {
+        Ok((StatusCode::ACCEPTED, "queued")).into_future()
+    });
+    let response = call(service, "GET");
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(response.body(), b"queued");
+}
+
+#[test]
+fn redirect() {
+    let response = respond(Redirect::permanent("/new"));
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()["location"], "/new");
+    assert!(response.body().is_empty());
+
+    let response = respond(Redirect::temporary("https://example.com/".to_string()));
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers()["location"], "https://example.com/");
+
+    let response = respond(Redirect::see_other(Uri::from_static("/done?ok=1")));
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["location"], "/done?ok=1");
+}
+
+#[test]
+#[should_panic(expected = "invalid `Location` header value")]
+fn invalid_location() {
+    Redirect::permanent("/new\nSet-Cookie: evil");
+}
+
+#[derive(FromRequest)]
+#[hyperdrive(client)]
+enum Posts {
+    #[get("/posts/{id}")]
+    Get {
+        id: u32,
+        #[query_params]
+        query: Query,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Query {
+    lang: String,
+}
+
+#[test]
+fn reverse_routed_location() {
+    let route = Posts::Get {
+        id: 7,
+        query: Query {
+            lang: "de".to_string(),
+        },
+    };
+    assert_eq!(Redirect::see_other(&route).location(), "/posts/7?lang=de");
+    assert_eq!(
+        Redirect::see_other(route.to_request()).location(),
+        "/posts/7?lang=de"
+    );
+}
+
+#[test]
+fn created() {
+    let service = SyncService::responding(|Routes::Index, _| Created::new("/users/1"));
+    let response = call(service, "GET");
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["location"], "/users/1");
+    assert!(response.headers().get("content-type").is_none());
+    assert!(response.body().is_empty());
+
+    let service =
+        SyncService::responding(|Routes::Index, _| Created::new("/users/1").json(vec![1]));
+    let response = call(service, "GET");
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["location"], "/users/1");
+    assert_eq!(content_type(&response), "application/json");
+    assert_eq!(response.body(), b"[1]");
+
+    // Errors produced by the body are sent instead
+    let service = SyncService::responding(|Routes::Index, _| {
+        Created::new("/users/1").body(StatusCode::INTERNAL_SERVER_ERROR)
+    });
+    let response = call(service, "GET");
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(response.headers().get("location").is_none());
+}
+
+#[test]
+fn no_content() {
+    let response = respond(NoContent);
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(response.headers().get("content-type").is_none());
+    assert!(response.body().is_empty());
+}
@@ -0,0 +1,81 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    body::Json,
+    http::{Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, ServiceExt},
+    BoxedError, FromRequest,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[post("/sum")]
+    Sum {
+        #[body]
+        numbers: Json<Vec<u32>>,
+    },
+
+    #[post("/ignore")]
+    Ignore,
+}
+
+/// Calls an app limiting bodies to 64 bytes and returns the response status,
+/// and whether the handler was called.
+fn call(request: Request<Body>) -> (StatusCode, bool) {
+    let called = Arc::new(AtomicBool::new(false));
+    let called2 = called.clone();
+    let mut service = AsyncService::new(move |route: Routes, _| {
+        called2.store(true, Ordering::SeqCst);
+        let body = match route {
+            Routes::Sum { numbers } => numbers.iter().sum::<u32>().to_string(),
+            Routes::Ignore => String::new(),
+        };
+        Ok(Response::new(Body::from(body))).into_future()
+    })
+    .limit_body(64);
+
+    let mut runtime = Runtime::new().unwrap();
+    let response = runtime.block_on(service.call(request)).unwrap();
+    runtime.shutdown_now().wait().unwrap();
+    (response.status(), called.load(Ordering::SeqCst))
+}
+
+fn chunked(path: &str, chunks: Vec<&'static str>) -> Request<Body> {
+    let stream = futures::stream::iter_ok::<_, BoxedError>(chunks);
+    Request::post(path).body(Body::wrap_stream(stream)).unwrap()
+}
+
+#[test]
+fn small_request() {
+    let request = Request::post("/sum").body(Body::from("[1, 2, 3]")).unwrap();
+    assert_eq!(call(request), (StatusCode::OK, true));
+
+    let request = chunked("/sum", vec!["[1, 2,", " 3]"]);
+    assert_eq!(call(request), (StatusCode::OK, true));
+}
+
+#[test]
+fn declared_length() {
+    let request = Request::post("/sum")
+        .header("Content-Length", "1000000")
+        .body(Body::from("[1, 2, 3]"))
+        .unwrap();
+    assert_eq!(call(request), (StatusCode::PAYLOAD_TOO_LARGE, false));
+
+    // Routes without a body are protected as well
+    let request = Request::post("/ignore")
+        .header("Content-Length", "65")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(request), (StatusCode::PAYLOAD_TOO_LARGE, false));
+}
+
+#[test]
+fn chunked_overflow() {
+    let chunk = "[1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,";
+    let request = chunked("/sum", vec![chunk, chunk, "1]"]);
+    assert_eq!(call(request), (StatusCode::PAYLOAD_TOO_LARGE, false));
+}
@@ -0,0 +1,79 @@
+use futures::{Future, IntoFuture};
+use http::{Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::{service::LocalAsyncService, FromRequest, LocalDefaultFuture};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+use tokio::runtime::current_thread;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/visit")]
+    Visit,
+
+    #[get("/visitors")]
+    Visitors,
+}
+
+/// A "template engine" that can't be shared between threads.
+struct Templates {
+    visitors: RefCell<u32>,
+}
+
+impl Templates {
+    fn render(&self) -> String {
+        format!("{} visitors", self.visitors.borrow())
+    }
+}
+
+#[test]
+fn current_thread_server() {
+    let (port_tx, port_rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let templates = Rc::new(Templates {
+            visitors: RefCell::new(0),
+        });
+        let service = LocalAsyncService::new(
+            move |route: Route, _| -> LocalDefaultFuture<Response<Body>, _> {
+                match route {
+                    Route::Visit => {
+                        *templates.visitors.borrow_mut() += 1;
+                        Box::new(Ok(Response::new(Body::empty())).into_future())
+                    }
+                    Route::Visitors => {
+                        // The returned future holds on to an `Rc`, too
+                        let templates = templates.clone();
+                        Box::new(futures::future::lazy(move || {
+                            Ok(Response::new(Body::from(templates.render())))
+                        }))
+                    }
+                }
+            },
+        );
+
+        let srv = Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .executor(current_thread::TaskExecutor::current())
+            .serve(service);
+        port_tx.send(srv.local_addr().port()).unwrap();
+
+        current_thread::block_on_all(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let port = port_rx.recv().unwrap();
+    let get = |path: &str| {
+        reqwest::Client::new()
+            .get(&format!("http://127.0.0.1:{}{}", port, path))
+            .send()
+            .expect("request failed")
+    };
+
+    for _ in 0..3 {
+        assert_eq!(get("/visit").status(), StatusCode::OK);
+    }
+    assert_eq!(get("/visitors").text().unwrap(), "3 visitors");
+    assert_eq!(get("/missing").status(), StatusCode::NOT_FOUND);
+}
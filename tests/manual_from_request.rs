@@ -0,0 +1,147 @@
+//! Tests `FromRequest` implementations that only implement one entry point.
+
+use futures::{future::FutureResult, Future, IntoFuture, Stream};
+use hyperdrive::{
+    guard::{Extension, RemoteAddr},
+    http::{header, HeaderValue, Request, StatusCode},
+    service::{ConnectInfo, ResponseDecorations, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest, Guard, NoContext,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Only implements `from_request`, like an adapter would.
+struct Echo {
+    line: String,
+}
+
+impl FromRequest for Echo {
+    type Context = NoContext;
+    type Future = DefaultFuture<Self, BoxedError>;
+
+    fn from_request(
+        request: Request<hyper::Body>,
+        _context: impl Into<Arc<NoContext>>,
+    ) -> Self::Future {
+        // Decorations of the service are carried over
+        ResponseDecorations::get(&request)
+            .unwrap()
+            .insert_header(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+        let (parts, body) = request.into_parts();
+        Box::new(
+            body.concat2()
+                .map_err(BoxedError::from)
+                .map(move |body| Echo {
+                    line: format!(
+                        "{} {} {}",
+                        parts.method,
+                        parts.uri,
+                        String::from_utf8_lossy(&body)
+                    ),
+                }),
+        )
+    }
+}
+
+#[derive(Clone)]
+struct User(&'static str);
+
+/// Only implements `from_request`, and runs guards reading extensions.
+struct Peer {
+    addr: SocketAddr,
+    user: Option<Extension<User>>,
+}
+
+impl FromRequest for Peer {
+    type Context = NoContext;
+    type Future = FutureResult<Self, BoxedError>;
+
+    fn from_request(
+        request: Request<hyper::Body>,
+        _context: impl Into<Arc<NoContext>>,
+    ) -> Self::Future {
+        let (parts, _body) = request.into_parts();
+        let request = Arc::new(Request::from_parts(parts, ()));
+        RemoteAddr::from_request(&request, &NoContext)
+            .and_then(|addr| {
+                Ok(Peer {
+                    addr: *addr,
+                    user: <Option<Extension<User>>>::from_request(&request, &NoContext)?,
+                })
+            })
+            .into_future()
+    }
+}
+
+/// Only implements `from_request_and_body`, like the custom derive.
+struct Method(String);
+
+impl FromRequest for Method {
+    type Context = NoContext;
+    type Future = FutureResult<Self, BoxedError>;
+
+    fn from_request_and_body(
+        request: &Arc<Request<()>>,
+        _body: hyper::Body,
+        _context: Arc<NoContext>,
+    ) -> Self::Future {
+        Ok(Method(request.method().to_string())).into_future()
+    }
+}
+
+#[test]
+fn from_request_only() {
+    let mut client = Client::new(SyncService::responding(|echo: Echo, _| echo.line));
+
+    let response = client
+        .send(RequestBuilder::post("/echo?x=1").body("hello"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "POST /echo?x=1 hello");
+    assert_eq!(response.header("Cache-Control"), Some("no-store"));
+}
+
+#[test]
+fn from_request_and_body_only() {
+    let mut client = Client::new(SyncService::responding(|method: Method, _| method.0));
+    let response = client.send(RequestBuilder::post("/")).unwrap();
+    assert_eq!(response.text(), "POST");
+
+    // The provided `from_request` calls `from_request_and_body`
+    let request = Request::put("/").body(hyper::Body::empty()).unwrap();
+    let method = Method::from_request_sync(request, NoContext).unwrap();
+    assert_eq!(method.0, "PUT");
+}
+
+#[test]
+fn from_request_only_extensions() {
+    let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+    let peer = |request: RequestBuilder| {
+        let mut client = Client::new(SyncService::responding(|peer: Peer, _| {
+            format!(
+                "{} {}",
+                peer.addr,
+                peer.user.map_or("anonymous", |user| (user.0).0)
+            )
+        }));
+        client.send(request).unwrap()
+    };
+
+    // Extensions inserted by the server and by the user are still found by
+    // the guards, even though the request was rebuilt
+    let response = peer(
+        RequestBuilder::get("/")
+            .extension(ConnectInfo(addr))
+            .extension(User("alice")),
+    );
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "127.0.0.1:1234 alice");
+
+    let response = peer(RequestBuilder::get("/").extension(ConnectInfo(addr)));
+    assert_eq!(response.text(), "127.0.0.1:1234 anonymous");
+
+    let response = peer(RequestBuilder::get("/"));
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,159 @@
+use hyperdrive::{
+    body::Json,
+    http::{Response, StatusCode},
+    hyper::Body,
+    map_boxed_error,
+    service::{ServiceExt, SyncService},
+    test::{decode, Client, RequestBuilder},
+    BoxedError, DefaultFuture, Error, ErrorKind, FromBody, FromRequest, Guard, NoContext,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Fails with the error selected by the `X-Fail` header.
+struct Fail;
+
+impl Guard for Fail {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        let error = match request.headers().get("x-fail").map(|v| v.to_str().unwrap()) {
+            None => return Ok(Fail),
+            Some("too-large") => Error::body_too_large("too large"),
+            Some("media-type") => Error::unsupported_media_type("unsupported"),
+            Some("custom") => Error::builder(StatusCode::CONFLICT)
+                .header("Location", "/items/1")
+                .build()?,
+            Some(_) => Error::from_status(StatusCode::FORBIDDEN),
+        };
+        Err(error.into())
+    }
+}
+
+/// A body that is never received in time.
+struct Stalled;
+
+impl FromBody for Stalled {
+    type Context = NoContext;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_body(_: &Arc<http::Request<()>>, _: Body, _: &NoContext) -> Self::Result {
+        Error::body_read_timeout("timed out").into_future()
+    }
+}
+
+#[derive(Deserialize)]
+struct Page {
+    count: u32,
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/items/{id}")]
+    Item { id: u32, _fail: Fail },
+
+    #[get("/search")]
+    Search {
+        #[query_params]
+        page: Page,
+    },
+
+    #[post("/items")]
+    AddItems {
+        #[body]
+        items: Json<Vec<u32>>,
+    },
+
+    #[post("/stalled")]
+    Stalled {
+        #[body]
+        _body: Stalled,
+    },
+}
+
+/// Sends `request` through a `SyncService` and checks that the response
+/// matches the one `map_boxed_error` creates for the decoding error.
+fn compare(request: impl Fn() -> RequestBuilder, kind: ErrorKind) {
+    let error = decode::<Routes>(request()).err().unwrap();
+    assert_eq!(error.downcast_ref::<Error>().unwrap().kind(), kind);
+    let expected: Response<Body> = map_boxed_error(error).unwrap();
+
+    let service = SyncService::responding(|route: Routes, _| match route {
+        Routes::Item { id, .. } => format!("item {}", id),
+        Routes::Search { page } => format!("{} results", page.count),
+        Routes::AddItems { items } => format!("added {}", items.len()),
+        Routes::Stalled { .. } => String::new(),
+    });
+    let response = Client::new(service).send(request()).unwrap();
+
+    assert_eq!(response.status(), expected.status(), "{:?}", kind);
+    for (name, value) in expected.headers() {
+        assert_eq!(response.headers().get(name), Some(value), "{:?}", kind);
+    }
+    assert_eq!(response.text(), "", "{:?}", kind);
+}
+
+#[test]
+fn same_as_adapters() {
+    compare(
+        || RequestBuilder::get("/missing"),
+        ErrorKind::NoMatchingRoute,
+    );
+    compare(|| RequestBuilder::delete("/items"), ErrorKind::WrongMethod);
+    compare(|| RequestBuilder::get("/items/abc"), ErrorKind::PathSegment);
+    compare(
+        || RequestBuilder::get("/search?count=x"),
+        ErrorKind::QueryParam,
+    );
+    compare(
+        || RequestBuilder::post("/items").body("[1,"),
+        ErrorKind::MalformedBody,
+    );
+    compare(
+        || RequestBuilder::get("/items/1").header("X-Fail", "too-large"),
+        ErrorKind::BodyTooLarge,
+    );
+    compare(
+        || RequestBuilder::get("/items/1").header("X-Fail", "media-type"),
+        ErrorKind::UnsupportedMediaType,
+    );
+    compare(
+        || RequestBuilder::post("/stalled"),
+        ErrorKind::BodyReadTimeout,
+    );
+    compare(
+        || RequestBuilder::get("/items/1").header("X-Fail", "custom"),
+        ErrorKind::Custom,
+    );
+    compare(
+        || RequestBuilder::get("/items/1").header("X-Fail", "other"),
+        ErrorKind::Other,
+    );
+}
+
+#[test]
+fn from_error() {
+    let response = Response::<Body>::from(Error::wrong_method(vec![&http::Method::GET]));
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()["Allow"], "GET");
+}
+
+#[test]
+fn wrapped_error() {
+    // Errors caused by a `hyperdrive::Error` are found as well
+    let limited = SyncService::responding(|route: Routes, _| match route {
+        Routes::AddItems { items } => format!("added {}", items.len()),
+        _ => String::new(),
+    })
+    .limit_body(4);
+    let mut client = Client::new(limited);
+    let chunks = futures::stream::iter_ok::<_, BoxedError>(vec!["[1, 2,", " 3]"]);
+    let response = client
+        .send(RequestBuilder::post("/items").body(Body::wrap_stream(chunks)))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let error: BoxedError = "not ours".into();
+    assert_eq!(map_boxed_error(error).unwrap_err().to_string(), "not ours");
+}
@@ -0,0 +1,103 @@
+use hyperdrive::{
+    http::{header, HeaderValue, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users")]
+    Users,
+
+    #[get("/v1/{rest}")]
+    Legacy { rest: String },
+
+    #[get("/accept")]
+    Accept,
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> + Clone {
+    SyncService::responding(|route: Routes, orig| match route {
+        Routes::Users => "users".to_string(),
+        Routes::Legacy { rest } => format!("legacy {}", rest),
+        Routes::Accept => orig.headers()[header::ACCEPT].to_str().unwrap().to_string(),
+    })
+}
+
+/// Removes the `/v1` prefix from the request path.
+fn strip_v1(request: &mut Request<Body>) {
+    let path_and_query = match request.uri().path_and_query() {
+        Some(pq) if pq.path().starts_with("/v1/") => pq.as_str()[3..].to_string(),
+        _ => return,
+    };
+    *request.uri_mut() = path_and_query.parse().unwrap();
+}
+
+#[test]
+fn rewrite_path() {
+    let mut client = Client::new(app());
+    let response = client.send(RequestBuilder::get("/v1/users")).unwrap();
+    assert_eq!(response.text(), "legacy users");
+
+    // The rewritten URI is what gets routed
+    let mut client = Client::new(app().map_request(strip_v1));
+    let response = client.send(RequestBuilder::get("/v1/users")).unwrap();
+    assert_eq!(response.text(), "users");
+    let response = client.send(RequestBuilder::get("/users")).unwrap();
+    assert_eq!(response.text(), "users");
+}
+
+#[test]
+fn default_header() {
+    let mut client = Client::new(app().map_request(|request| {
+        request
+            .headers_mut()
+            .entry(header::ACCEPT)
+            .unwrap()
+            .or_insert_with(|| HeaderValue::from_static("application/json"));
+    }));
+
+    let response = client.send(RequestBuilder::get("/accept")).unwrap();
+    assert_eq!(response.text(), "application/json");
+    let response = client
+        .send(RequestBuilder::get("/accept").header("Accept", "text/html"))
+        .unwrap();
+    assert_eq!(response.text(), "text/html");
+}
+
+#[test]
+fn reject() {
+    let mut client = Client::new(app().map_request(strip_v1).try_map_request(|request| {
+        if request.headers().contains_key("x-legacy-auth") {
+            return Err((StatusCode::BAD_REQUEST, "unsupported"));
+        }
+
+        // Normalize the vendor header
+        if let Some(value) = request.headers_mut().remove("x-vendor-accept") {
+            request.headers_mut().insert(header::ACCEPT, value);
+        }
+        Ok(())
+    }));
+
+    let response = client
+        .send(RequestBuilder::get("/v1/users").header("X-Legacy-Auth", "secret"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.text(), "unsupported");
+
+    let response = client
+        .send(RequestBuilder::get("/accept").header("X-Vendor-Accept", "text/csv"))
+        .unwrap();
+    assert_eq!(response.text(), "text/csv");
+
+    // Composes with `map_request`, which is applied afterwards
+    let response = client.send(RequestBuilder::get("/v1/users")).unwrap();
+    assert_eq!(response.text(), "users");
+}
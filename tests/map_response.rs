@@ -0,0 +1,101 @@
+use futures::Future;
+use hyperdrive::{
+    http::{header, HeaderValue, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{ServiceExt, SyncService},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/panic")]
+    Panic,
+}
+
+fn server_header(mut response: Response<Body>) -> Response<Body> {
+    response
+        .headers_mut()
+        .insert(header::SERVER, HeaderValue::from_static("hyperdrive-test"));
+    response
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> + Clone {
+    SyncService::new(|route: Routes, _| match route {
+        Routes::Index => Response::new(Body::from("Hello World!")),
+        Routes::Panic => panic!("handler panicked"),
+    })
+    .catch_unwind(|_payload| {
+        Ok(Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty())
+            .unwrap())
+    })
+    .map_response(server_header)
+}
+
+fn call<S>(mut service: S, path: &str) -> Result<Response<Body>, BoxedError>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let result = runtime.block_on(service.call(request));
+    runtime.shutdown_now().wait().unwrap();
+    result
+}
+
+#[test]
+fn ok() {
+    let response = call(app(), "/").unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::SERVER], "hyperdrive-test");
+}
+
+#[test]
+fn no_matching_route() {
+    let response = call(app(), "/missing").unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()[header::SERVER], "hyperdrive-test");
+}
+
+#[test]
+fn panic_recovered() {
+    let response = call(app(), "/panic").unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.headers()[header::SERVER], "hyperdrive-test");
+}
+
+#[test]
+fn clone() {
+    // `MapResponse` is `Clone`, so it can be served directly
+    let _make_service = app().make_service_by_cloning();
+    let response = call(app().clone(), "/").unwrap();
+    assert_eq!(response.headers()[header::SERVER], "hyperdrive-test");
+}
+
+#[test]
+fn fallible() {
+    let service = app().try_map_response(|response| {
+        if response.status().is_server_error() {
+            Err("refusing to send server error".into())
+        } else {
+            Ok(response)
+        }
+    });
+
+    let response = call(service.clone(), "/").unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let error = call(service, "/panic").unwrap_err();
+    assert_eq!(error.to_string(), "refusing to send server error");
+}
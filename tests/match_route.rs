@@ -0,0 +1,268 @@
+//! Tests dry-run routing with `FromRequest::match_route`.
+
+use hyperdrive::{
+    body::Json,
+    http::{Method, Request},
+    hyper::Body,
+    BoxedError, Error, ErrorKind, FromRequest, Guard, NoContext, RouteMatch,
+};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static GUARD_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// A guard with a side effect.
+#[derive(Debug)]
+struct CountingGuard;
+
+impl Guard for CountingGuard {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        GUARD_RUNS.fetch_add(1, Ordering::SeqCst);
+        Ok(CountingGuard)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct LoginData {
+    email: String,
+    password: String,
+}
+
+#[derive(FromRequest, Debug)]
+enum Inner {
+    #[get("/inner/{id}")]
+    Item { id: u32 },
+
+    #[delete("/users/{id}")]
+    DeleteUser { id: u32 },
+}
+
+/// The routes of the `user_app` test in `from_request.rs`.
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[post("/login")]
+    Login {
+        #[body]
+        data: Json<LoginData>,
+        #[query_params]
+        params: (),
+
+        gourd: CountingGuard,
+    },
+
+    #[get("/users/{id}")]
+    User { id: u32, guard: CountingGuard },
+
+    #[patch("/users/{id}")]
+    PatchUser {
+        id: u32,
+
+        #[body]
+        data: Json<String>,
+    },
+
+    #[get("/static/{path...}")]
+    Static { path: String },
+}
+
+#[derive(FromRequest, Debug)]
+enum WithFallback {
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    Fallback {
+        guard: CountingGuard,
+        #[forward]
+        inner: Inner,
+    },
+}
+
+fn route(name: &'static str, placeholders: &[(&'static str, &str)]) -> RouteMatch {
+    RouteMatch::Route {
+        name,
+        placeholders: placeholders
+            .iter()
+            .map(|&(field, segment)| (field, segment.to_string()))
+            .collect(),
+    }
+}
+
+#[test]
+fn user_app() {
+    let before = GUARD_RUNS.load(Ordering::SeqCst);
+
+    assert_eq!(
+        Routes::match_route(&Method::POST, "/login"),
+        route("Login", &[])
+    );
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/login"),
+        RouteMatch::WrongMethod(vec![&Method::POST])
+    );
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/users/5"),
+        route("User", &[("id", "5")])
+    );
+    assert_eq!(
+        Routes::match_route(&Method::HEAD, "/users/5").route_name(),
+        Some("User")
+    );
+    assert_eq!(
+        Routes::match_route(&Method::POST, "/users/0"),
+        RouteMatch::WrongMethod(vec![&Method::GET, &Method::HEAD, &Method::PATCH])
+    );
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/users/wrong"),
+        RouteMatch::PathSegment {
+            name: "User",
+            field: "id"
+        }
+    );
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/users"),
+        RouteMatch::NoMatchingRoute
+    );
+
+    // Segments are returned without decoding them
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/static/css/a%20b.css"),
+        route("Static", &[("path", "css/a%20b.css")])
+    );
+
+    // No guard was run
+    assert_eq!(GUARD_RUNS.load(Ordering::SeqCst), before);
+}
+
+#[test]
+fn forward() {
+    assert_eq!(
+        WithFallback::match_route(&Method::GET, "/users/5"),
+        route("User", &[("id", "5")])
+    );
+    assert_eq!(
+        WithFallback::match_route(&Method::GET, "/inner/3"),
+        RouteMatch::Forward {
+            name: "Fallback",
+            inner: Box::new(route("Item", &[("id", "3")])),
+        }
+    );
+    assert_eq!(
+        WithFallback::match_route(&Method::DELETE, "/users/5"),
+        RouteMatch::Forward {
+            name: "Fallback",
+            inner: Box::new(route("DeleteUser", &[("id", "5")])),
+        }
+    );
+
+    // The methods accepted by both types are merged
+    assert_eq!(
+        WithFallback::match_route(&Method::PUT, "/users/5"),
+        RouteMatch::WrongMethod(vec![&Method::GET, &Method::HEAD, &Method::DELETE])
+    );
+
+    // Rejections of the forwarded type are returned as-is
+    assert_eq!(
+        WithFallback::match_route(&Method::GET, "/inner/x"),
+        RouteMatch::PathSegment {
+            name: "Item",
+            field: "id"
+        }
+    );
+    assert_eq!(
+        WithFallback::match_route(&Method::GET, "/missing"),
+        RouteMatch::NoMatchingRoute
+    );
+}
+
+#[test]
+fn structs_and_generics() {
+    #[derive(FromRequest, Debug)]
+    #[get("/items/{id}")]
+    struct Item {
+        id: u32,
+    }
+
+    #[derive(FromRequest, Debug)]
+    enum Generic<T> {
+        #[get("/{id}")]
+        Get { id: T },
+    }
+
+    assert_eq!(
+        Item::match_route(&Method::GET, "/items/1"),
+        route("Item", &[("id", "1")])
+    );
+    assert_eq!(
+        Generic::<u8>::match_route(&Method::GET, "/255"),
+        route("Get", &[("id", "255")])
+    );
+    assert_eq!(
+        Generic::<u8>::match_route(&Method::GET, "/256"),
+        RouteMatch::PathSegment {
+            name: "Get",
+            field: "id"
+        }
+    );
+    assert_eq!(
+        Generic::<String>::match_route(&Method::GET, "/256"),
+        route("Get", &[("id", "256")])
+    );
+}
+
+/// Checks that `match_route` agrees with actually decoding the request.
+#[test]
+fn agrees_with_decoding() {
+    let requests = [
+        (Method::GET, "/users/5"),
+        (Method::HEAD, "/users/5"),
+        (Method::POST, "/users/5"),
+        (Method::DELETE, "/users/5"),
+        (Method::GET, "/users/wrong"),
+        (Method::GET, "/inner/3"),
+        (Method::POST, "/inner/3"),
+        (Method::GET, "/inner/x"),
+        (Method::GET, "/"),
+    ];
+
+    for (method, path) in &requests {
+        let request = Request::builder()
+            .method(method.clone())
+            .uri(*path)
+            .body(Body::empty())
+            .unwrap();
+        let decoded = WithFallback::from_request_sync(request, NoContext);
+
+        let matched = WithFallback::match_route(method, path);
+        match (&matched, decoded) {
+            (RouteMatch::Route { .. }, Ok(route)) | (RouteMatch::Forward { .. }, Ok(route)) => {
+                assert_eq!(
+                    matched.route_name(),
+                    route.route_name(),
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+            (_, Ok(route)) => panic!(
+                "{} {}: {:?}, but decoded {:?}",
+                method, path, matched, route
+            ),
+            (_, Err(e)) => {
+                let error = e.downcast::<Error>().unwrap();
+                match (&matched, error.kind()) {
+                    (RouteMatch::NoMatchingRoute, ErrorKind::NoMatchingRoute)
+                    | (RouteMatch::PathSegment { .. }, ErrorKind::PathSegment) => {}
+                    (RouteMatch::WrongMethod(methods), ErrorKind::WrongMethod) => {
+                        assert_eq!(Some(&methods[..]), error.allowed_methods());
+                    }
+                    _ => panic!("{} {}: {:?}, but got {}", method, path, matched, error),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Tests stripping matrix parameters with `#[strip_matrix_params]` and reading
+//! them with `guard::MatrixParams`.
+
+use hyperdrive::{
+    guard::MatrixParams,
+    http::Method,
+    test::{decode, RequestBuilder},
+    Error, ErrorKind, FromRequest, RouteMatch,
+};
+
+#[derive(FromRequest, Debug, PartialEq)]
+#[strip_matrix_params]
+enum Routes {
+    #[get("/")]
+    Index { params: MatrixParams },
+
+    #[get("/cars/{id}/wheels")]
+    Wheels { id: u32, params: MatrixParams },
+}
+
+#[derive(FromRequest, Debug, PartialEq)]
+enum Unstripped {
+    #[get("/cars/{id}")]
+    Car { id: String, params: MatrixParams },
+}
+
+#[test]
+fn literal_and_placeholder_segments() {
+    let route = decode::<Routes>(RequestBuilder::get(
+        "/cars;color=red;featured/17;year=2019/wheels;size=18?page=2",
+    ))
+    .unwrap();
+    let (id, params) = match route {
+        Routes::Wheels { id, params } => (id, params),
+        route => panic!("unexpected route {:?}", route),
+    };
+    assert_eq!(id, 17);
+    assert_eq!(params.get("color"), Some("red"));
+    assert_eq!(params.get("featured"), Some(""));
+    assert_eq!(params.get("page"), None);
+    assert_eq!(
+        params.segment(0).collect::<Vec<_>>(),
+        vec![("color", "red"), ("featured", "")]
+    );
+    assert_eq!(
+        params.segment(1).collect::<Vec<_>>(),
+        vec![("year", "2019")]
+    );
+    assert_eq!(params.segment(2).collect::<Vec<_>>(), vec![("size", "18")]);
+    assert_eq!(
+        params.iter().map(|(index, _, _)| index).collect::<Vec<_>>(),
+        vec![0, 0, 1, 2]
+    );
+
+    // Paths without parameters still work
+    match decode::<Routes>(RequestBuilder::get("/cars/3/wheels")).unwrap() {
+        Routes::Wheels { id, params } => {
+            assert_eq!(id, 3);
+            assert!(params.is_empty());
+        }
+        route => panic!("unexpected route {:?}", route),
+    }
+
+    // Parameters don't fill in placeholders
+    let error = decode::<Routes>(RequestBuilder::get("/cars/x;id=3/wheels")).unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap().kind(),
+        ErrorKind::PathSegment
+    );
+
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/cars;a=b/1/wheels;c"),
+        RouteMatch::Route {
+            name: "Wheels",
+            placeholders: vec![("id", "1".to_string())],
+        }
+    );
+}
+
+#[test]
+fn param_only_segments_are_rejected() {
+    for path in &["/;color=red", "/cars/;id=1/wheels", "/cars/1/;x/wheels"] {
+        let error = decode::<Routes>(RequestBuilder::get(path)).unwrap_err();
+        assert_eq!(
+            error.downcast::<Error>().unwrap().kind(),
+            ErrorKind::NoMatchingRoute,
+            "{}",
+            path
+        );
+        assert_eq!(
+            Routes::match_route(&Method::GET, path),
+            RouteMatch::NoMatchingRoute,
+            "{}",
+            path
+        );
+    }
+
+    // The root path is an empty segment, not a parameter-only one
+    match decode::<Routes>(RequestBuilder::get("/")).unwrap() {
+        Routes::Index { params } => assert!(params.is_empty()),
+        route => panic!("unexpected route {:?}", route),
+    }
+}
+
+#[test]
+fn disabled() {
+    // Without the attribute, the parameters stay part of the segment
+    match decode::<Unstripped>(RequestBuilder::get("/cars/17;year=2019")).unwrap() {
+        Unstripped::Car { id, params } => {
+            assert_eq!(id, "17;year=2019");
+            assert_eq!(
+                params.segment(1).collect::<Vec<_>>(),
+                vec![("year", "2019")]
+            );
+        }
+    }
+
+    let error = decode::<Unstripped>(RequestBuilder::get("/cars;color=red/17")).unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap().kind(),
+        ErrorKind::NoMatchingRoute
+    );
+    assert_eq!(
+        Unstripped::match_route(&Method::GET, "/cars;color=red/17"),
+        RouteMatch::NoMatchingRoute
+    );
+}
@@ -0,0 +1,127 @@
+use hyperdrive::{
+    http::StatusCode,
+    service::{AtomicMetrics, MetricsEvent, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    #[get("/fail")]
+    Fail,
+
+    #[get("/in-flight")]
+    InFlight,
+}
+
+/// Sends a few requests through a service using `metrics`, and returns their
+/// statuses.
+fn simulate(metrics: AtomicMetrics) -> Vec<StatusCode> {
+    let service = SyncService::responding(|route: Route, _| match route {
+        Route::Index => (StatusCode::OK, String::new()),
+        Route::User { id } => (StatusCode::OK, id.to_string()),
+        Route::Fail => (StatusCode::INTERNAL_SERVER_ERROR, String::new()),
+        Route::InFlight => unreachable!(),
+    })
+    .metrics(metrics);
+    let mut client = Client::new(service);
+
+    ["/", "/", "/users/1", "/users/2", "/missing", "/fail"]
+        .iter()
+        .map(|path| client.send(RequestBuilder::get(path)).unwrap().status())
+        .collect()
+}
+
+#[test]
+fn counters() {
+    let metrics = AtomicMetrics::new();
+    let statuses = simulate(metrics.clone());
+    assert_eq!(statuses[4], StatusCode::NOT_FOUND);
+    assert_eq!(statuses[5], StatusCode::INTERNAL_SERVER_ERROR);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.requests(), 6);
+    assert_eq!(snapshot.in_flight(), 0);
+    assert_eq!(snapshot.status_class(2), 4);
+    assert_eq!(snapshot.status_class(4), 1);
+    assert_eq!(snapshot.status_class(5), 1);
+
+    // Routes are labeled with their variant name, unmatched requests with the path
+    assert_eq!(snapshot.route_requests("Index"), 2);
+    assert_eq!(snapshot.route_requests("User"), 2);
+    assert_eq!(snapshot.route_requests("Fail"), 1);
+    assert_eq!(snapshot.route_requests("/missing"), 1);
+
+    assert_eq!(snapshot.latency_count(), 6);
+    let buckets = snapshot.latency_buckets();
+    assert_eq!(buckets.len(), 11);
+    assert!(buckets.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    assert_eq!(buckets.last().unwrap().1, 6);
+}
+
+#[test]
+fn in_flight() {
+    let metrics = AtomicMetrics::new();
+    let snapshots = metrics.clone();
+    let service =
+        SyncService::responding(move |_: Route, _| snapshots.snapshot().in_flight().to_string())
+            .metrics(metrics.clone());
+
+    let response = Client::new(service)
+        .send(RequestBuilder::get("/in-flight"))
+        .unwrap();
+    assert_eq!(response.text(), "1");
+    assert_eq!(metrics.snapshot().in_flight(), 0);
+}
+
+#[test]
+fn prometheus_format() {
+    let metrics = AtomicMetrics::new();
+    simulate(metrics.clone());
+    let text = metrics.snapshot().to_string();
+
+    assert!(text.contains("# TYPE http_requests_total counter\n"));
+    assert!(text.contains("http_requests_total{method=\"GET\",route=\"Index\",status=\"200\"} 2\n"));
+    assert!(
+        text.contains("http_requests_total{method=\"GET\",route=\"/missing\",status=\"404\"} 1\n")
+    );
+    assert!(text.contains("http_requests_in_flight 0\n"));
+    assert!(text.contains("http_request_duration_seconds_bucket{le=\"0.005\"} "));
+    assert!(text.contains("http_request_duration_seconds_bucket{le=\"+Inf\"} 6\n"));
+    assert!(text.contains("http_request_duration_seconds_count 6\n"));
+}
+
+#[test]
+fn closure_recorder() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let service = SyncService::responding(|_: Route, _| "").metrics(move |event: &MetricsEvent| {
+        recorded.lock().unwrap().push((
+            event.method().clone(),
+            event.route().to_string(),
+            event.status(),
+        ));
+    });
+    let mut client = Client::new(service);
+    client.send(RequestBuilder::get("/users/5")).unwrap();
+    client.send(RequestBuilder::post("/users/5")).unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            (http::Method::GET, "User".to_string(), StatusCode::OK),
+            (
+                http::Method::POST,
+                "/users/5".to_string(),
+                StatusCode::METHOD_NOT_ALLOWED
+            ),
+        ]
+    );
+}
@@ -0,0 +1,113 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{HeaderValue, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, Middleware, Next, ServiceExt},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+}
+
+/// Answers requests with an `X-Block` header itself, and tags all other
+/// responses.
+#[derive(Default)]
+struct Gate {
+    forwarded: Arc<AtomicUsize>,
+}
+
+impl Middleware for Gate {
+    fn handle(
+        &self,
+        mut req: Request<Body>,
+        next: Next<'_>,
+    ) -> DefaultFuture<Response<Body>, BoxedError> {
+        if req.headers().contains_key("x-block") {
+            let response = Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())
+                .unwrap();
+            return Box::new(Ok(response).into_future());
+        }
+
+        self.forwarded.fetch_add(1, Ordering::SeqCst);
+        req.headers_mut()
+            .insert("x-gate", HeaderValue::from_static("passed"));
+        Box::new(next.run(req).map(|mut response| {
+            response
+                .headers_mut()
+                .insert("x-gate", HeaderValue::from_static("done"));
+            response
+        }))
+    }
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> + Clone {
+    AsyncService::new(|Routes::Index, orig: Arc<Request<()>>| {
+        // Echo the header added by the middleware
+        let gate = orig.headers().get("x-gate").cloned();
+        let mut response = Response::new(Body::empty());
+        if let Some(gate) = gate {
+            response.headers_mut().insert("x-seen", gate);
+        }
+        Ok(response).into_future()
+    })
+}
+
+fn call<S>(service: &mut S, block: bool) -> Response<Body>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    let mut request = Request::get("/");
+    if block {
+        request.header("x-block", "1");
+    }
+    service
+        .call(request.body(Body::empty()).unwrap())
+        .wait()
+        .unwrap()
+}
+
+#[test]
+fn pass_through() {
+    let gate = Gate::default();
+    let forwarded = gate.forwarded.clone();
+    let mut service = app().wrap(gate);
+
+    let response = call(&mut service, false);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-seen"], "passed");
+    assert_eq!(response.headers()["x-gate"], "done");
+    assert_eq!(forwarded.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn short_circuit() {
+    let gate = Gate::default();
+    let forwarded = gate.forwarded.clone();
+    let mut service = app().wrap(gate);
+
+    let response = call(&mut service, true);
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response.headers().get("x-gate").is_none());
+    assert_eq!(forwarded.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn nested_and_cloned() {
+    let service = app().wrap(Gate::default()).wrap(Gate::default());
+    let response = call(&mut service.clone(), false);
+    assert_eq!(response.headers()["x-gate"], "done");
+
+    let _make_service = service.make_service_by_cloning();
+}
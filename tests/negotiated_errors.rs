@@ -0,0 +1,110 @@
+use hyperdrive::{
+    http::StatusCode,
+    hyper::{service::Service, Body},
+    response::Json,
+    service::{Negotiated, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, FromRequest,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users")]
+    Users,
+}
+
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> &'static str, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Users => "[]",
+    })
+}
+
+/// Requests a missing page with the given `Accept` header and returns the
+/// `Content-Type` and body of the response.
+fn missing<S>(client: &mut Client<S>, accept: Option<&str>) -> (String, String)
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let mut request = RequestBuilder::get("/missing");
+    if let Some(accept) = accept {
+        request = request.header("Accept", accept);
+    }
+    let response = client.send(request).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.header("Vary"), Some("Accept"));
+    (
+        response.header("Content-Type").unwrap().to_string(),
+        response.text().to_string(),
+    )
+}
+
+#[test]
+fn defaults() {
+    let mut client = Client::new(service().error_body(Negotiated::new()));
+
+    // A browser
+    let (content_type, body) = missing(
+        &mut client,
+        Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+    );
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    assert!(body.starts_with("<!DOCTYPE html>"));
+    assert!(body.contains("<h1>404 Not Found</h1>"));
+
+    // An API client
+    let (content_type, body) = missing(&mut client, Some("application/json"));
+    assert_eq!(content_type, "application/problem+json");
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["status"], 404);
+    assert_eq!(body["title"], "Not Found");
+
+    // No preference
+    for accept in &[None, Some("*/*"), Some("image/png")] {
+        let (content_type, body) = missing(&mut client, *accept);
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        assert_eq!(body, "404 Not Found");
+    }
+}
+
+#[test]
+fn quality() {
+    let mut client = Client::new(service().error_body(Negotiated::new()));
+
+    let (content_type, _) = missing(&mut client, Some("text/html;q=0.5, application/*"));
+    assert_eq!(content_type, "application/problem+json");
+
+    let (content_type, _) = missing(&mut client, Some("text/*;q=0.9, text/plain;q=0.1"));
+    assert_eq!(content_type, "text/html; charset=utf-8");
+
+    let (content_type, _) = missing(&mut client, Some("application/json;q=0, */*"));
+    assert_eq!(content_type, "text/plain; charset=utf-8");
+}
+
+#[test]
+fn custom_representations() {
+    let mut client = Client::new(
+        service().error_body(
+            Negotiated::new()
+                .json(|error| Json(json!({ "code": error.http_status().as_u16() })))
+                .text(|_| "nope"),
+        ),
+    );
+
+    let (content_type, body) = missing(&mut client, Some("application/json"));
+    assert_eq!(content_type, "application/json");
+    assert_eq!(body, r#"{"code":404}"#);
+
+    let (_, body) = missing(&mut client, None);
+    assert_eq!(body, "nope");
+
+    // The error's own headers are kept
+    let response = client
+        .send(RequestBuilder::post("/users").header("Accept", "text/html"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("Allow"), Some("GET, HEAD"));
+    assert_eq!(response.header("Vary"), Some("Accept"));
+}
@@ -0,0 +1,184 @@
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    http::{Method, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, SyncService},
+    BoxedError, Error, ErrorKind, FromRequest, NoContext,
+};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+fn call<S>(mut service: S, method: &str, path: &str) -> (StatusCode, String)
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Body::empty())
+        .unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let response = runtime.block_on(service.call(request)).unwrap();
+    runtime.shutdown_now().wait().unwrap();
+
+    let status = response.status();
+    let body = response.into_body().concat2().wait().unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn not_found_page(request: Arc<Request<()>>) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from(format!("no page at {}", request.uri().path())))
+        .unwrap()
+}
+
+#[test]
+fn error_kinds() {
+    let kind = |method, path| {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        let error = Routes::from_request_sync(request, NoContext).err().unwrap();
+        error.downcast::<Error>().unwrap().kind()
+    };
+
+    assert_eq!(kind("GET", "/missing"), ErrorKind::NoMatchingRoute);
+    assert_eq!(kind("POST", "/"), ErrorKind::WrongMethod);
+    assert_eq!(kind("GET", "/users/abc"), ErrorKind::PathSegment);
+}
+
+#[test]
+fn request_info() {
+    let error = |method, path| {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        let error = Routes::from_request_sync(request, NoContext).err().unwrap();
+        error.downcast::<Error>().unwrap()
+    };
+
+    let not_found = error("GET", "/missing/page?q=1");
+    assert_eq!(not_found.request_method(), Some(&Method::GET));
+    assert_eq!(not_found.request_path(), Some("/missing/page"));
+    assert_eq!(not_found.to_string(), "404 Not Found");
+    assert_eq!(
+        format!("{:#}", not_found),
+        "no_matching_route: 404 Not Found (GET /missing/page)"
+    );
+
+    let wrong_method = error("DELETE", "/users/1");
+    assert_eq!(wrong_method.request_method(), Some(&Method::DELETE));
+    assert_eq!(wrong_method.request_path(), Some("/users/1"));
+    assert_eq!(
+        format!("{:#}", wrong_method),
+        "wrong_method: 405 Method Not Allowed (DELETE /users/1)"
+    );
+
+    // Errors not created by the routing logic have no request attached
+    let bad_segment = error("GET", "/users/abc");
+    assert_eq!(bad_segment.request_path(), None);
+
+    // The request is available to error handlers
+    let service = SyncService::responding(|route: Routes, _| match route {
+        Routes::Index => "index".to_string(),
+        Routes::User { id } => format!("user {}", id),
+    })
+    .with_error_handler(|error| {
+        let error = error.downcast::<Error>().unwrap();
+        Response::builder()
+            .status(error.http_status())
+            .body(Body::from(format!("{:#}", error)))
+            .unwrap()
+    });
+    let (status, body) = call(service, "POST", "/");
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body, "wrong_method: 405 Method Not Allowed (POST /)");
+}
+
+#[test]
+fn async_service() {
+    let service = AsyncService::responding(|route: Routes, _| match route {
+        Routes::Index => Ok("index".to_string()).into_future(),
+        Routes::User { id } => Ok(format!("user {}", id)).into_future(),
+    })
+    .not_found(|request| Ok::<_, BoxedError>(not_found_page(request)));
+
+    let (status, body) = call(service.clone(), "GET", "/missing/page");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "no page at /missing/page");
+
+    let (status, body) = call(service, "GET", "/users/5");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "user 5");
+}
+
+#[test]
+fn sync_service() {
+    let service = SyncService::responding(|route: Routes, _| match route {
+        Routes::Index => "index",
+        Routes::User { .. } => "user",
+    })
+    .not_found(not_found_page);
+
+    let (status, body) = call(service.clone(), "GET", "/missing");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "no page at /missing");
+
+    let (status, body) = call(service, "GET", "/");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "index");
+}
+
+#[test]
+fn wrong_method_not_routed() {
+    let service = SyncService::responding(|_: Routes, _| "ok")
+        .not_found(|_| -> Response<Body> { panic!("not_found handler called") });
+
+    let (status, body) = call(service.clone(), "POST", "/");
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body, "");
+
+    // Path segments that fail to parse aren't a missing route
+    let (status, body) = call(service, "GET", "/users/abc");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "");
+}
+
+#[test]
+fn takes_precedence_over_error_handler() {
+    let service = SyncService::responding(|_: Routes, _| "ok")
+        .with_error_handler(|_| Response::new(Body::from("error handler")))
+        .not_found(not_found_page);
+
+    let (status, body) = call(service.clone(), "GET", "/missing");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "no page at /missing");
+
+    let (status, body) = call(service, "PUT", "/");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "error handler");
+}
+
+#[test]
+fn head() {
+    let service = SyncService::responding(|_: Routes, _| "ok").not_found(not_found_page);
+
+    let (status, body) = call(service, "HEAD", "/missing");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body, "");
+}
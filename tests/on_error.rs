@@ -0,0 +1,145 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    http::{Method, Request, Response, StatusCode},
+    hyper::{
+        service::{service_fn, Service},
+        Body,
+    },
+    service::{AsyncService, RequestInfo, ServiceExt},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, Error, FromRequest,
+};
+use log::{Log, Metadata, Record};
+use std::sync::{Arc, Mutex};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/fail")]
+    Fail,
+}
+
+/// Collects the errors reported to `on_error` as `(method, path, message)`.
+type Reported = Arc<Mutex<Vec<(Method, String, String)>>>;
+
+/// Creates a callback for `on_error` that records errors in `reported`.
+fn recorder(reported: &Reported) -> impl Fn(&BoxedError, &RequestInfo) + Send + Sync + 'static {
+    let reported = reported.clone();
+    move |error, request| {
+        reported.lock().unwrap().push((
+            request.method().clone(),
+            request.path().to_string(),
+            error.to_string(),
+        ));
+    }
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    AsyncService::new(
+        |route: Routes, _| -> DefaultFuture<Response<Body>, BoxedError> {
+            match route {
+                Routes::Index => Box::new(Ok(Response::new(Body::from("index"))).into_future()),
+                Routes::Fail => Box::new(Err("database unavailable".into()).into_future()),
+            }
+        },
+    )
+}
+
+#[test]
+fn reports_opaque_errors() {
+    let reported = Reported::default();
+    let mut client = Client::new(app().on_error(recorder(&reported)));
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+
+    // Mapped to a `404 Not Found` response by `AsyncService`
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(reported.lock().unwrap().is_empty());
+
+    // The error is still returned
+    let error = client.send(RequestBuilder::get("/fail?x=1")).unwrap_err();
+    assert_eq!(error.to_string(), "database unavailable");
+    assert_eq!(
+        *reported.lock().unwrap(),
+        vec![(
+            Method::GET,
+            "/fail".to_string(),
+            "database unavailable".to_string()
+        )]
+    );
+}
+
+#[test]
+fn hyperdrive_errors() {
+    let service = || {
+        service_fn(|_: Request<Body>| -> Result<Response<Body>, BoxedError> {
+            Err(Error::from_status(StatusCode::FORBIDDEN).into())
+        })
+    };
+
+    let reported = Reported::default();
+    let mut client = Client::new(service().on_error(recorder(&reported)));
+    assert!(client.send(RequestBuilder::get("/")).is_err());
+    assert!(reported.lock().unwrap().is_empty());
+
+    let mut client = Client::new(
+        service()
+            .on_error(recorder(&reported))
+            .include_hyperdrive_errors(true),
+    );
+    assert!(client.send(RequestBuilder::get("/")).is_err());
+    assert_eq!(reported.lock().unwrap().len(), 1);
+}
+
+/// Collects the error log messages emitted through the `log` crate.
+#[derive(Default)]
+struct VecLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for VecLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "hyperdrive::error"
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            assert_eq!(record.level(), log::Level::Error);
+            self.messages
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn log_errors() {
+    // This is the only test that installs a global logger
+    let logger: &'static VecLogger = Box::leak(Box::new(VecLogger::default()));
+    log::set_logger(logger).unwrap();
+    log::set_max_level(log::LevelFilter::Error);
+
+    let mut client = Client::new(app().log_errors());
+    client.send(RequestBuilder::get("/")).unwrap();
+    client
+        .send(RequestBuilder::get("/fail?token=secret"))
+        .unwrap_err();
+
+    let messages = logger.messages.lock().unwrap();
+    assert_eq!(
+        *messages,
+        vec!["GET /fail?… failed: database unavailable".to_string()]
+    );
+}
@@ -0,0 +1,147 @@
+//! Tests using the examples from RFC 7240.
+
+use hyperdrive::{
+    guard::{Handling, Prefer, PreferenceApplied, Return},
+    http::{HeaderValue, Request},
+    FromRequest, NoContext,
+};
+use std::time::Duration;
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/")]
+    Index { prefer: Prefer },
+}
+
+fn prefer(headers: &[&str]) -> Prefer {
+    let mut request = Request::get("/");
+    for header in headers {
+        request.header("Prefer", *header);
+    }
+    let request = request.body(&[][..]).unwrap();
+    let Routes::Index { prefer } = Routes::from_request_bytes(request, NoContext).unwrap();
+    prefer
+}
+
+#[test]
+fn missing() {
+    let prefer = prefer(&[]);
+    assert!(prefer.is_empty());
+    assert_eq!(prefer.return_(), None);
+    assert_eq!(prefer.wait(), None);
+    assert!(!prefer.respond_async());
+    assert_eq!(prefer.handling(), None);
+}
+
+/// Section 2: `Prefer: respond-async, wait=100`
+#[test]
+fn respond_async_wait() {
+    let prefer = prefer(&["respond-async, wait=100"]);
+    assert!(prefer.respond_async());
+    assert_eq!(prefer.wait(), Some(Duration::from_secs(100)));
+    assert_eq!(prefer.others().count(), 0);
+}
+
+/// Section 2: multiple headers are equivalent to a single one
+#[test]
+fn multiple_headers() {
+    let combined = prefer(&["respond-async, wait=100, handling=lenient"]);
+    let split = prefer(&["respond-async, wait=100", "handling=lenient"]);
+    assert_eq!(combined, split);
+    assert_eq!(split.handling(), Some(Handling::Lenient));
+}
+
+/// Section 2: `foo; bar` is equivalent to `foo; bar=""`, and `foo=""; bar` to
+/// `foo; bar`
+#[test]
+fn empty_values() {
+    for header in &["foo; bar", "foo; bar=\"\"", "foo=\"\"; bar"] {
+        let prefer = prefer(&[header]);
+        let foo = prefer.get("foo").unwrap();
+        assert_eq!(foo.value(), None);
+        assert_eq!(foo.params().len(), 1);
+        assert_eq!(foo.params()[0].0, "bar");
+    }
+}
+
+/// Section 2: only the first instance of a preference is considered
+#[test]
+fn duplicates() {
+    let prefer = prefer(&["wait=10", "wait=20, return=minimal"]);
+    assert_eq!(prefer.wait(), Some(Duration::from_secs(10)));
+    assert_eq!(prefer.iter().count(), 2);
+}
+
+/// Section 4.2: `return=minimal` and `return=representation`
+#[test]
+fn return_() {
+    assert_eq!(prefer(&["return=minimal"]).return_(), Some(Return::Minimal));
+    assert_eq!(
+        prefer(&["return=representation"]).return_(),
+        Some(Return::Representation)
+    );
+    assert_eq!(prefer(&["return=everything"]).return_(), None);
+}
+
+/// Section 4.4: `Prefer: handling=lenient`
+#[test]
+fn handling() {
+    assert_eq!(
+        prefer(&["handling=lenient"]).handling(),
+        Some(Handling::Lenient)
+    );
+    assert_eq!(
+        prefer(&["handling=strict"]).handling(),
+        Some(Handling::Strict)
+    );
+}
+
+/// Unregistered preferences and parameters are retained
+#[test]
+fn others() {
+    let prefer = prefer(&["Priority=5; scope=\"a; b, c\", respond-async, ignored=\"\\\"x\\\"\""]);
+    let others = prefer.others().collect::<Vec<_>>();
+    assert_eq!(others.len(), 2);
+    assert_eq!(others[0].name(), "priority");
+    assert_eq!(others[0].value(), Some("5"));
+    assert_eq!(
+        others[0].params(),
+        &[("scope".to_string(), Some("a; b, c".to_string()))]
+    );
+    assert_eq!(others[1].name(), "ignored");
+    assert_eq!(others[1].value(), Some("\"x\""));
+    assert!(prefer.respond_async());
+}
+
+#[test]
+fn malformed() {
+    let prefer = prefer(&["=5, wa(it=5, return=minimal,,"]);
+    assert_eq!(prefer.iter().count(), 1);
+    assert_eq!(prefer.return_(), Some(Return::Minimal));
+}
+
+/// Section 3: `Preference-Applied: return=representation`
+#[test]
+fn preference_applied() {
+    assert!(PreferenceApplied::new().header().is_none());
+
+    let (name, value) = PreferenceApplied::new()
+        .return_(Return::Representation)
+        .header()
+        .unwrap();
+    assert_eq!(name, "preference-applied");
+    assert_eq!(value, "return=representation");
+
+    let prefer = prefer(&["respond-async, wait=100, foo=\"a b\"; bar"]);
+    let (_, value) = PreferenceApplied::new()
+        .respond_async()
+        .wait(prefer.wait().unwrap())
+        .preference(prefer.get("foo").unwrap())
+        .handling(Handling::Strict)
+        .header()
+        .unwrap();
+    assert_eq!(
+        value,
+        HeaderValue::from_static("respond-async, wait=100, foo=\"a b\", handling=strict")
+    );
+}
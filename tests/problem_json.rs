@@ -0,0 +1,180 @@
+use futures::{Future, Stream};
+use hyperdrive::{
+    http::{Method, StatusCode},
+    response::IntoResponse,
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    Error, FromRequest, ProblemJson,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users")]
+    Users,
+
+    #[post("/users")]
+    AddUser,
+
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    #[get("/search")]
+    Search {
+        #[query_params]
+        page: Page,
+    },
+}
+
+#[derive(Deserialize)]
+struct Page {
+    count: u32,
+}
+
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> String, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Users => "[]".to_string(),
+        Routes::AddUser => "added".to_string(),
+        Routes::User { id } => format!("user #{}", id),
+        Routes::Search { page } => format!("{} results", page.count),
+    })
+}
+
+/// Renders `problem` and returns the status, content type and parsed body.
+fn render(problem: ProblemJson) -> (StatusCode, String, Value) {
+    let response = problem.into_response();
+    let content_type = response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let status = response.status();
+    let body = response.into_body().concat2().wait().unwrap();
+    (status, content_type, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn no_matching_route() {
+    let problem = ProblemJson::from_error(&Error::no_matching_route());
+    assert_eq!(
+        render(problem),
+        (
+            StatusCode::NOT_FOUND,
+            "application/problem+json".to_string(),
+            json!({ "type": "about:blank", "title": "Not Found", "status": 404 }),
+        )
+    );
+}
+
+#[test]
+fn wrong_method() {
+    let error = Error::wrong_method(&[&Method::GET, &Method::POST][..]);
+    let (status, _, body) = render(ProblemJson::from_error(&error));
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(
+        body,
+        json!({
+            "type": "about:blank",
+            "title": "Method Not Allowed",
+            "status": 405,
+            "allowed_methods": ["GET", "POST"],
+        })
+    );
+}
+
+#[test]
+fn other() {
+    let error = Error::with_source(StatusCode::BAD_REQUEST, "abc".parse::<u32>().unwrap_err());
+    let (_, _, body) = render(ProblemJson::from_error(&error));
+    assert_eq!(
+        body,
+        json!({
+            "type": "about:blank",
+            "title": "Bad Request",
+            "status": 400,
+            "detail": "invalid digit found in string",
+        })
+    );
+
+    // The source of server errors isn't exposed
+    let error = Error::with_source(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "-1".parse::<u32>().unwrap_err(),
+    );
+    let (status, _, body) = render(ProblemJson::from_error(&error));
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body,
+        json!({ "type": "about:blank", "title": "Service Unavailable", "status": 503 })
+    );
+}
+
+#[test]
+fn custom() {
+    let problem = ProblemJson::from_error(&Error::from_status(StatusCode::UNPROCESSABLE_ENTITY))
+        .type_uri("https://example.com/probs/invalid-params")
+        .detail("the request parameters didn't validate")
+        .extension("invalid_field", "age");
+    assert_eq!(problem.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem.extensions()["invalid_field"], "age");
+
+    let (status, _, body) = render(problem);
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body,
+        json!({
+            "type": "https://example.com/probs/invalid-params",
+            "title": "Unprocessable Entity",
+            "status": 422,
+            "detail": "the request parameters didn't validate",
+            "invalid_field": "age",
+        })
+    );
+}
+
+#[test]
+fn end_to_end() {
+    let mut client = Client::new(service().error_body(ProblemJson::from_error));
+
+    let response = client
+        .send(RequestBuilder::new(Method::DELETE, "/users"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("Allow"), Some("GET, HEAD, POST"));
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("application/problem+json")
+    );
+    let body: Value = serde_json::from_str(response.text()).unwrap();
+    assert_eq!(body["allowed_methods"], json!(["GET", "HEAD", "POST"]));
+
+    // Path segments that fail to parse produce a `detail` and `invalid_field`
+    let response = client.send(RequestBuilder::get("/users/me")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = serde_json::from_str(response.text()).unwrap();
+    assert_eq!(body["detail"], "invalid digit found in string");
+    assert_eq!(body["invalid_field"], "id");
+
+    // Missing and invalid query parameters are described
+    let response = client.send(RequestBuilder::get("/search")).unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = serde_json::from_str(response.text()).unwrap();
+    assert_eq!(
+        body["query_param"],
+        json!({ "reason": "missing", "name": "count" })
+    );
+    let response = client
+        .send(RequestBuilder::get("/search?count=many"))
+        .unwrap();
+    let body: Value = serde_json::from_str(response.text()).unwrap();
+    assert_eq!(
+        body["query_param"],
+        json!({ "reason": "invalid", "name": "count", "value": "many" })
+    );
+
+    let response = client.send(RequestBuilder::get("/users/1")).unwrap();
+    assert_eq!(response.text(), "user #1");
+    let response = client.send(RequestBuilder::get("/search?count=3")).unwrap();
+    assert_eq!(response.text(), "3 results");
+}
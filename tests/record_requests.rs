@@ -0,0 +1,148 @@
+//! Tests recording requests with `ServiceExt::record` and replaying them.
+
+use hyperdrive::{
+    body::Json,
+    http::{header, Method, StatusCode},
+    service::{
+        JsonLinesRecorder, RecordedRequest, RequestInfo, RequestRecorder, ResponseInfo, ServiceExt,
+        SyncService,
+    },
+    test::{Client, RequestBuilder},
+    FromRequest, NoContext,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Order {
+    item: String,
+    quantity: u32,
+}
+
+#[derive(FromRequest, Debug, PartialEq)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[post("/orders")]
+    Order {
+        #[body]
+        order: Json<Order>,
+    },
+}
+
+/// Fails orders with a quantity of 0, like a buggy app would.
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> StatusCode, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Index => StatusCode::OK,
+        Routes::Order { order } if order.quantity == 0 => StatusCode::INTERNAL_SERVER_ERROR,
+        Routes::Order { .. } => StatusCode::CREATED,
+    })
+}
+
+#[test]
+fn record_and_replay() {
+    let path = std::env::temp_dir().join(format!(
+        "hyperdrive-record-requests-{}.jsonl",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+
+    let mut client = Client::new(service().record(JsonLinesRecorder::open(&path).unwrap()));
+    let order = |quantity| Order {
+        item: "ünïcode".to_string(),
+        quantity,
+    };
+    let response = client
+        .send(RequestBuilder::post("/orders?source=app").json(&order(1)))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = client
+        .send(
+            RequestBuilder::post("/orders?source=app")
+                .header("x-binary", &b"caf\xe9"[..])
+                .json(&order(0)),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    client.send(RequestBuilder::get("/")).unwrap();
+
+    // Only the failed request was recorded
+    let recordings = JsonLinesRecorder::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(recordings.len(), 1, "{:?}", recordings);
+    let recording = recordings.into_iter().next().unwrap();
+    assert_eq!(recording.method(), Method::POST);
+    assert_eq!(recording.uri(), "/orders?source=app");
+    assert_eq!(recording.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        recording.headers()[header::CONTENT_TYPE],
+        "application/json"
+    );
+    assert_eq!(recording.headers()["x-binary"], &b"caf\xe9"[..]);
+    assert!(!recording.is_body_truncated());
+
+    // Replaying the request reproduces the failure
+    let request = recording.into_request();
+    let route = Routes::from_request_bytes(request, NoContext).unwrap();
+    assert_eq!(
+        route,
+        Routes::Order {
+            order: Json(order(0))
+        }
+    );
+}
+
+/// Collects recordings in memory, only sampling requests to `/orders`.
+#[derive(Clone, Default)]
+struct Memory(Arc<Mutex<Vec<RecordedRequest>>>);
+
+impl RequestRecorder for Memory {
+    fn sample(&self, request: &RequestInfo) -> bool {
+        request.path() == "/orders"
+    }
+
+    fn should_record(&self, _: &RequestInfo, _: &ResponseInfo) -> bool {
+        true
+    }
+
+    fn record(&self, request: RecordedRequest) {
+        self.0.lock().unwrap().push(request);
+    }
+}
+
+#[test]
+fn sampling_and_truncation() {
+    let memory = Memory::default();
+    let mut client = Client::new(service().record(memory.clone()).max_body(8));
+
+    client.send(RequestBuilder::get("/")).unwrap();
+    client
+        .send(RequestBuilder::post("/orders").body("{\"item\": \"x\", \"quantity\": 2}"))
+        .unwrap();
+    // The body isn't read if decoding fails before it
+    client
+        .send(
+            RequestBuilder::new(Method::PUT, "/orders")
+                .body("never read")
+                .header(header::CONTENT_TYPE, "text/plain"),
+        )
+        .unwrap();
+
+    let recordings = memory.0.lock().unwrap();
+    assert_eq!(recordings.len(), 2);
+    assert_eq!(recordings[0].status(), StatusCode::CREATED);
+    assert_eq!(recordings[0].body(), &b"{\"item\":"[..]);
+    assert!(recordings[0].is_body_truncated());
+    assert_eq!(recordings[1].status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(recordings[1].body().is_empty());
+    assert!(recordings[1].is_body_truncated());
+
+    // The JSON representation round-trips
+    let json = recordings[0].to_json();
+    assert!(!json.contains('\n'));
+    let parsed = RecordedRequest::from_json(&json).unwrap();
+    assert_eq!(parsed.to_json(), json);
+    assert!(RecordedRequest::from_json("{}").is_err());
+}
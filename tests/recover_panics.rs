@@ -0,0 +1,132 @@
+//! Tests the built-in panic recovery of `SyncService` and `AsyncService`,
+//! which needs no configuration besides calling `recover_panics`.
+
+use futures::{Future, IntoFuture};
+use http::{Response, StatusCode};
+use hyper::{Body, Server};
+use hyperdrive::service::{AsyncService, SyncService};
+use hyperdrive::test::{Client, RequestBuilder};
+use hyperdrive::{BoxedError, FromBody, FromRequest, Guard, NoContext};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    /// Accessing this route will panic in a `Guard` implementation.
+    #[get("/panic-guard")]
+    PanicGuard { _guard: PanicGuard },
+
+    /// Accessing this route will panic in a `FromBody` implementation.
+    #[get("/panic-body")]
+    #[allow_body_on_get]
+    PanicBody {
+        #[body]
+        _body: PanicBody,
+    },
+
+    /// Accessing this route will panic in the *request handler*.
+    #[get("/panic-handler")]
+    PanicHandler,
+}
+
+enum PanicGuard {}
+
+impl Guard for PanicGuard {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_request: &Arc<http::Request<()>>, _context: &Self::Context) -> Self::Result {
+        panic!("panic inside PanicGuard");
+    }
+}
+
+enum PanicBody {}
+
+impl FromBody for PanicBody {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_body(
+        _request: &Arc<http::Request<()>>,
+        _body: hyper::Body,
+        _context: &Self::Context,
+    ) -> Self::Result {
+        panic!("panic inside PanicBody");
+    }
+}
+
+#[test]
+fn sync_service() {
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(
+        SyncService::responding(|route: Route, _| -> &'static str {
+            match route {
+                Route::Index => "index",
+                Route::PanicGuard { .. } => unreachable!(),
+                Route::PanicBody { .. } => unreachable!(),
+                Route::PanicHandler => {
+                    panic!("panic inside the request handler");
+                }
+            }
+        })
+        .recover_panics(),
+    );
+
+    let port = srv.local_addr().port();
+
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let get = |route: &str| {
+        reqwest::Client::new()
+            .get(&format!("http://127.0.0.1:{}/{}", port, route))
+            .send()
+            .expect("request failed")
+    };
+
+    for route in &["panic-handler", "panic-guard", "panic-body"] {
+        let mut response = get(route);
+        assert_eq!(
+            response.status(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "route /{}",
+            route
+        );
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(response.text().unwrap(), "internal server error");
+    }
+
+    // The server keeps working
+    let mut response = get("");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().unwrap(), "index");
+}
+
+#[test]
+fn async_service() {
+    let mut client = Client::new(
+        AsyncService::new(|route: Route, _| match route {
+            Route::Index => Ok(Response::new(Body::from("index"))).into_future(),
+            Route::PanicGuard { .. } => unreachable!(),
+            Route::PanicBody { .. } => unreachable!(),
+            Route::PanicHandler => panic!("panic inside the request handler"),
+        })
+        .recover_panics(),
+    );
+
+    for route in &["/panic-handler", "/panic-guard", "/panic-body"] {
+        let response = client.send(RequestBuilder::get(route)).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.text(), "internal server error");
+    }
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+}
@@ -0,0 +1,140 @@
+use futures::{Future, IntoFuture, Stream};
+use http::{Response, StatusCode};
+use hyper::server::conn::AddrStream;
+use hyper::service::{MakeService, MakeServiceRef, Service};
+use hyper::{Body, Server};
+use hyperdrive::guard::RemoteAddr;
+use hyperdrive::service::{AsyncService, ServiceExt, SyncService};
+use hyperdrive::{BoxedError, FromRequest, NoContext};
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index { addr: RemoteAddr },
+
+    #[get("/optional")]
+    Optional { addr: Option<RemoteAddr> },
+}
+
+fn describe(route: Route) -> String {
+    match route {
+        Route::Index { addr } => addr.ip().to_string(),
+        Route::Optional { addr } => match addr {
+            Some(addr) => addr.ip().to_string(),
+            None => "unknown".to_string(),
+        },
+    }
+}
+
+/// Starts a server on a new thread and returns its port.
+fn spawn<S>(make_service: S) -> u16
+where
+    S: MakeServiceRef<
+            AddrStream,
+            ReqBody = Body,
+            ResBody = Body,
+            Error = BoxedError,
+            MakeError = BoxedError,
+        > + Send
+        + 'static,
+    S::Future: Send + 'static,
+    S::Service: Send + 'static,
+    <S::Service as Service>::Future: Send + 'static,
+{
+    let srv = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let port = srv.local_addr().port();
+
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    port
+}
+
+fn get(port: u16, path: &str) -> (StatusCode, String) {
+    let mut response =
+        reqwest::get(&format!("http://127.0.0.1:{}{}", port, path)).expect("request failed");
+    (response.status(), response.text().unwrap())
+}
+
+#[test]
+fn sync_service() {
+    let port =
+        spawn(SyncService::responding(|route: Route, _| describe(route)).with_connect_info());
+
+    assert_eq!(get(port, "/"), (StatusCode::OK, "127.0.0.1".to_string()));
+    assert_eq!(
+        get(port, "/optional"),
+        (StatusCode::OK, "127.0.0.1".to_string())
+    );
+}
+
+#[test]
+fn adapters() {
+    let service = AsyncService::new(|route: Route, _| {
+        Ok(Response::new(Body::from(describe(route)))).into_future()
+    })
+    .map_request(|_| {})
+    .with_connect_info();
+    let port = spawn(service);
+
+    assert_eq!(get(port, "/"), (StatusCode::OK, "127.0.0.1".to_string()));
+}
+
+#[test]
+fn opt_in() {
+    // Without `with_connect_info`, the address isn't recorded
+    let port = spawn(SyncService::responding(|route: Route, _| describe(route)));
+
+    assert_eq!(get(port, "/").0, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        get(port, "/optional"),
+        (StatusCode::OK, "unknown".to_string())
+    );
+}
+
+/// A connection type that doesn't implement `Connected`.
+struct Custom;
+
+#[test]
+fn custom_connections() {
+    // Services can be created for any connection type
+    let mut service = SyncService::responding(|route: Route, _| describe(route));
+    MakeService::make_service(&mut service, &Custom)
+        .wait()
+        .unwrap();
+    MakeService::make_service(&mut service, ()).wait().unwrap();
+
+    let mut service = service.make_service_by_cloning();
+    MakeService::make_service(&mut service, &Custom)
+        .wait()
+        .unwrap();
+}
+
+#[test]
+fn missing() {
+    let mut service = SyncService::responding(|route: Route, _| describe(route));
+    let mut call = |path| {
+        let request = http::Request::get(path).body(Body::empty()).unwrap();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime.block_on(service.call(request)).unwrap();
+        let status = response.status();
+        let body = runtime.block_on(response.into_body().concat2()).unwrap();
+        runtime.shutdown_now().wait().unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    };
+
+    // Services called directly don't know the remote address
+    assert_eq!(call("/").0, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(call("/optional"), (StatusCode::OK, "unknown".to_string()));
+
+    // The error explains how to record it
+    let request = http::Request::get("/").body(Body::empty()).unwrap();
+    let error = Route::from_request_sync(request, NoContext)
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("with_connect_info"), "{}", error);
+}
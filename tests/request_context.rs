@@ -2,7 +2,13 @@
 //!
 //! Negative tests are in the derive implementation.
 
-use hyperdrive::RequestContext;
+use hyperdrive::{
+    http::Request,
+    test::{decode_with_context, RequestBuilder},
+    BoxedError, FromRequest, Guard, RequestContext,
+};
+use std::rc::Rc;
+use std::sync::Arc;
 
 fn assert_impls<T: RequestContext>() {}
 
@@ -71,3 +77,110 @@ fn on_enum() {
     }
     assert_impls::<Ctx2>();
 }
+
+/// Stands in for a database connection pool.
+#[derive(RequestContext)]
+struct DbPool {
+    name: &'static str,
+}
+
+struct Logger;
+
+/// A guard that needs the innermost context type.
+struct Db(&'static str);
+
+impl Guard for Db {
+    type Context = DbPool;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, pool: &DbPool) -> Self::Result {
+        Ok(Db(pool.name))
+    }
+}
+
+#[derive(RequestContext)]
+struct MyContext {
+    #[as_ref]
+    db: DbPool,
+}
+
+#[derive(RequestContext)]
+struct BigContext {
+    #[as_ref(forward)]
+    inner: MyContext,
+    #[as_ref]
+    _logger: Logger,
+}
+
+#[derive(RequestContext)]
+struct HugeContext(#[as_ref(forward)] BigContext);
+
+#[derive(FromRequest)]
+#[context(HugeContext)]
+enum Routes {
+    #[get("/")]
+    Index { db: Db },
+}
+
+#[test]
+fn as_ref_forward() {
+    assert_impls::<BigContext>();
+    assert_impls::<HugeContext>();
+
+    // Every impl of the forwarded context is available, including ones
+    // forwarded from its own fields:
+    let _ = <BigContext as AsRef<MyContext>>::as_ref;
+    let _ = <HugeContext as AsRef<BigContext>>::as_ref;
+    let _ = <HugeContext as AsRef<MyContext>>::as_ref;
+    let _ = <HugeContext as AsRef<Logger>>::as_ref;
+
+    let context = HugeContext(BigContext {
+        inner: MyContext {
+            db: DbPool { name: "main" },
+        },
+        _logger: Logger,
+    });
+    let Routes::Index { db } =
+        decode_with_context::<Routes>(RequestBuilder::get("/"), context).unwrap();
+    assert_eq!(db.0, "main");
+}
+
+#[test]
+fn as_ref_smart_pointers() {
+    #[derive(RequestContext)]
+    struct Shared {
+        #[as_ref]
+        db: Arc<DbPool>,
+        #[as_ref]
+        _logger: Box<Logger>,
+        #[as_ref]
+        _counter: Rc<u8>,
+        #[as_ref(no_deref)]
+        _name: Arc<str>,
+    }
+
+    // Both the pointer and the pointee are available:
+    let _ = <Shared as AsRef<Arc<DbPool>>>::as_ref;
+    let _ = <Shared as AsRef<Box<Logger>>>::as_ref;
+    let _ = <Shared as AsRef<Logger>>::as_ref;
+    let _ = <Shared as AsRef<Rc<u8>>>::as_ref;
+    let _ = <Shared as AsRef<u8>>::as_ref;
+    let _ = <Shared as AsRef<Arc<str>>>::as_ref;
+
+    #[derive(FromRequest)]
+    #[context(Shared)]
+    enum Routes {
+        #[get("/")]
+        Index { db: Db },
+    }
+
+    let context = Shared {
+        db: Arc::new(DbPool { name: "shared" }),
+        _logger: Box::new(Logger),
+        _counter: Rc::new(0),
+        _name: "name".into(),
+    };
+    let Routes::Index { db } =
+        decode_with_context::<Routes>(RequestBuilder::get("/"), context).unwrap();
+    assert_eq!(db.0, "shared");
+}
@@ -0,0 +1,110 @@
+//! Tests rejecting malformed requests with `guard::RequestSanity` and the body
+//! wrappers.
+
+use hyperdrive::{
+    body::Json,
+    guard::RequestSanity,
+    http::{header, StatusCode},
+    service::SyncService,
+    test::{decode, Client, RequestBuilder},
+    BoxedError, Error, ErrorKind, FromRequest,
+};
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[post("/events")]
+    Events { _sanity: RequestSanity },
+
+    #[post("/numbers")]
+    Numbers {
+        #[body]
+        numbers: Json<Vec<u32>>,
+    },
+}
+
+fn kind(result: Result<Routes, BoxedError>) -> ErrorKind {
+    result.unwrap_err().downcast::<Error>().unwrap().kind()
+}
+
+#[test]
+fn malformed_requests() {
+    let cases = vec![
+        ("chunked, gzip", None, ErrorKind::MalformedRequest),
+        ("gzip", None, ErrorKind::MalformedRequest),
+        ("chunked, chunked", None, ErrorKind::MalformedRequest),
+        ("", None, ErrorKind::MalformedRequest),
+        ("chunked", Some("5"), ErrorKind::MalformedRequest),
+        (
+            "gzip, chunked",
+            None,
+            ErrorKind::UnsupportedTransferEncoding,
+        ),
+        (
+            "Deflate,Chunked",
+            None,
+            ErrorKind::UnsupportedTransferEncoding,
+        ),
+    ];
+
+    for (transfer_encoding, content_length, expected) in cases {
+        for path in &["/events", "/numbers"] {
+            let mut request = RequestBuilder::post(path)
+                .header(header::TRANSFER_ENCODING, transfer_encoding)
+                .body("[1]");
+            if let Some(length) = content_length {
+                request = request.header(header::CONTENT_LENGTH, length);
+            }
+            assert_eq!(
+                kind(decode(request)),
+                expected,
+                "{} with Transfer-Encoding `{}`",
+                path,
+                transfer_encoding
+            );
+        }
+    }
+
+    let conflicting_lengths = || {
+        RequestBuilder::post("/numbers")
+            .header(header::CONTENT_LENGTH, "3")
+            .header(header::CONTENT_LENGTH, "4")
+            .body("[1]")
+    };
+    assert_eq!(
+        kind(decode(conflicting_lengths())),
+        ErrorKind::MalformedRequest
+    );
+    let invalid_length = RequestBuilder::post("/events").header(header::CONTENT_LENGTH, "-1");
+    assert_eq!(kind(decode(invalid_length)), ErrorKind::MalformedRequest);
+
+    // Well-formed requests are accepted
+    decode::<Routes>(RequestBuilder::post("/events").header(header::TRANSFER_ENCODING, "chunked"))
+        .unwrap();
+    let numbers = RequestBuilder::post("/numbers")
+        .header(header::CONTENT_LENGTH, "6")
+        .header(header::CONTENT_LENGTH, " 6")
+        .body("[1, 2]");
+    match decode::<Routes>(numbers).unwrap() {
+        Routes::Numbers { numbers } => assert_eq!(numbers.0, vec![1, 2]),
+        route => panic!("unexpected route {:?}", route),
+    }
+}
+
+#[test]
+fn responses() {
+    let mut client = Client::new(SyncService::responding(|_: Routes, _| "ok"));
+
+    let response = client
+        .send(
+            RequestBuilder::post("/numbers")
+                .header(header::TRANSFER_ENCODING, "chunked, gzip")
+                .body("[1]"),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .send(RequestBuilder::post("/events").header(header::TRANSFER_ENCODING, "br, chunked"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+}
@@ -0,0 +1,125 @@
+use hyperdrive::{
+    http::Request,
+    service::{RequestScope, SyncService},
+    test::{decode, Client, RequestBuilder},
+    BoxedError, FromRequest, Guard, NoContext,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Entries recorded by guards while decoding a request.
+#[derive(Debug, Default)]
+struct AuditLog(Mutex<Vec<&'static str>>);
+
+/// The time the first guard started running.
+struct Started(Instant);
+
+struct Authenticated;
+
+impl Guard for Authenticated {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        let scope = RequestScope::of(request).unwrap();
+        scope.insert(Started(Instant::now()));
+        scope
+            .get_or_insert_with(AuditLog::default)
+            .0
+            .lock()
+            .unwrap()
+            .push("authenticated");
+        Ok(Authenticated)
+    }
+}
+
+struct RateLimited;
+
+impl Guard for RateLimited {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        let scope = RequestScope::of(request).unwrap();
+        assert!(scope.get::<Started>().unwrap().0 <= Instant::now());
+        scope
+            .get_or_insert_with(AuditLog::default)
+            .0
+            .lock()
+            .unwrap()
+            .push("rate limited");
+        Ok(RateLimited)
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index {
+        _auth: Authenticated,
+        _limit: RateLimited,
+    },
+}
+
+#[test]
+fn handler_reads_entries() {
+    let service = SyncService::responding(|_: Routes, request: Arc<Request<()>>| {
+        let scope = RequestScope::of(&request).unwrap();
+        let log = scope.get::<AuditLog>().unwrap();
+        let entries = log.0.lock().unwrap();
+        entries.join(", ")
+    });
+    let mut client = Client::new(service);
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "authenticated, rate limited");
+
+    // Every request gets its own scope
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "authenticated, rate limited");
+}
+
+#[test]
+fn from_request() {
+    // `FromRequest::from_request` provides a scope as well
+    let _: Routes = decode(RequestBuilder::get("/")).unwrap();
+}
+
+#[test]
+fn values() {
+    let scope = RequestScope::default();
+    assert!(scope.get::<u32>().is_none());
+    assert_eq!(scope.insert(1u32), None);
+    assert_eq!(scope.insert(2u32), Some(Arc::new(1)));
+
+    assert_eq!(*scope.get_or_insert_with(|| 0u32), 2);
+    assert_eq!(*scope.get_or_insert_with(|| 10u8), 10);
+    assert_eq!(*scope.get::<u32>().unwrap(), 2);
+    assert_eq!(*scope.get::<u8>().unwrap(), 10);
+
+    // Clones share the values
+    let clone = scope.clone();
+    assert_eq!(clone.remove::<u32>(), Some(Arc::new(2)));
+    assert!(scope.get::<u32>().is_none());
+    assert_eq!(format!("{:?}", scope), "RequestScope { len: 1 }");
+}
+
+#[test]
+fn reentrant() {
+    let scope = RequestScope::default();
+    scope.insert(1u32);
+
+    // Holding on to a value doesn't lock the scope
+    let value = scope.get::<u32>().unwrap();
+    scope.insert(*value + 1);
+    assert_eq!(*scope.get::<u32>().unwrap(), 2);
+    assert_eq!(*value, 1);
+
+    // `default` may use the scope, and the value it stores wins
+    let value = scope.get_or_insert_with(|| {
+        scope.insert(*scope.get::<u32>().unwrap() as u8 + 1);
+        0u8
+    });
+    assert_eq!(*value, 3);
+    assert_eq!(format!("{:?}", scope), "RequestScope { len: 2 }");
+}
@@ -0,0 +1,189 @@
+//! Tests services producing responses with a body type other than `Body`.
+
+use futures::{future, Async, IntoFuture, Poll};
+use http::{header, Method, Response, StatusCode};
+use hyper::{body::Payload, service::Service, Body, Chunk};
+use hyperdrive::{
+    response::ErrorBody,
+    service::{AsyncService, SecurityHeaders, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest,
+};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// A body that records whether it was created by the application or converted
+/// from a `Body` by hyperdrive.
+#[derive(Debug)]
+enum TrackedBody {
+    App(Option<Chunk>),
+    Converted(Body),
+}
+
+impl Payload for TrackedBody {
+    type Data = Chunk;
+    type Error = hyper::Error;
+
+    fn poll_data(&mut self) -> Poll<Option<Chunk>, hyper::Error> {
+        match self {
+            TrackedBody::App(chunk) => Ok(Async::Ready(chunk.take())),
+            TrackedBody::Converted(body) => body.poll_data(),
+        }
+    }
+}
+
+impl ErrorBody for TrackedBody {
+    fn from_body(body: Body) -> Self {
+        TrackedBody::Converted(body)
+    }
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[get("/panic")]
+    Panic,
+
+    #[get("/hang")]
+    Hang,
+}
+
+fn app_response(text: &'static str) -> Response<TrackedBody> {
+    Response::new(TrackedBody::App(Some(Chunk::from(text))))
+}
+
+/// Sends `request` to `service` and returns the response along with its
+/// collected body.
+fn send<S>(service: &mut S, request: RequestBuilder) -> (Response<TrackedBody>, String)
+where
+    S: Service<ReqBody = Body, ResBody = TrackedBody, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let mut runtime = Runtime::new().unwrap();
+    let mut response = runtime.block_on(service.call(request.build())).unwrap();
+
+    // All bodies used here are available immediately
+    let mut data = Vec::new();
+    loop {
+        match response.body_mut().poll_data().unwrap() {
+            Async::Ready(Some(chunk)) => data.extend_from_slice(&chunk),
+            Async::Ready(None) => break,
+            Async::NotReady => panic!("response body not ready"),
+        }
+    }
+    (response, String::from_utf8(data).unwrap())
+}
+
+fn is_converted(response: &Response<TrackedBody>) -> bool {
+    match response.body() {
+        TrackedBody::App(_) => false,
+        TrackedBody::Converted(_) => true,
+    }
+}
+
+#[test]
+fn async_service() {
+    let mut service = AsyncService::new(|route: Route, _| match route {
+        Route::Index => Ok::<_, BoxedError>(app_response("index")).into_future(),
+        Route::Panic | Route::Hang => unreachable!(),
+    });
+
+    let (response, body) = send(&mut service, RequestBuilder::get("/"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!is_converted(&response));
+    assert_eq!(body, "index");
+
+    // Errors are rendered into a converted `Body`
+    let (response, body) = send(&mut service, RequestBuilder::get("/missing"));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(is_converted(&response));
+    assert_eq!(body, "");
+
+    let (response, body) = send(&mut service, RequestBuilder::head("/"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(is_converted(&response));
+    assert_eq!(body, "");
+}
+
+#[test]
+fn sync_service() {
+    let mut service = SyncService::new(|route: Route, _| match route {
+        Route::Index => app_response("index"),
+        Route::Panic => panic!("panic inside the request handler"),
+        Route::Hang => unreachable!(),
+    })
+    .recover_panics();
+
+    let (response, body) = send(&mut service, RequestBuilder::get("/"));
+    assert!(!is_converted(&response));
+    assert_eq!(body, "index");
+
+    let (response, body) = send(&mut service, RequestBuilder::get("/panic"));
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(is_converted(&response));
+    assert_eq!(body, "internal server error");
+
+    let (response, _) = send(&mut service, RequestBuilder::new(Method::PUT, "/"));
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(is_converted(&response));
+}
+
+#[test]
+fn adapters() {
+    let mut service = AsyncService::new(
+        |route: Route, _| -> DefaultFuture<Response<TrackedBody>, BoxedError> {
+            match route {
+                Route::Index => Box::new(Ok(app_response("index")).into_future()),
+                Route::Panic => panic!("panic inside the request handler"),
+                Route::Hang => Box::new(future::empty()),
+            }
+        },
+    )
+    .catch_unwind(|_| Ok(app_response("recovered")))
+    .timeout(Duration::from_millis(10))
+    .security_headers(SecurityHeaders::default())
+    .map_response(|mut response| {
+        response
+            .headers_mut()
+            .insert("X-Mapped", "yes".parse().unwrap());
+        response
+    });
+
+    let (response, body) = send(&mut service, RequestBuilder::get("/"));
+    assert_eq!(response.headers()["X-Mapped"], "yes");
+    assert!(response
+        .headers()
+        .contains_key(header::X_CONTENT_TYPE_OPTIONS));
+    assert!(!is_converted(&response));
+    assert_eq!(body, "index");
+
+    let (response, body) = send(&mut service, RequestBuilder::get("/panic"));
+    assert!(!is_converted(&response));
+    assert_eq!(body, "recovered");
+
+    let (response, _) = send(&mut service, RequestBuilder::get("/hang"));
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["X-Mapped"], "yes");
+    assert!(is_converted(&response));
+}
+
+#[test]
+fn body_responses() {
+    // Services producing a `Response<Body>` work with the same adapters
+    let service = SyncService::new(|route: Route, _| match route {
+        Route::Index => Response::new(Body::from("index")),
+        Route::Panic | Route::Hang => unreachable!(),
+    })
+    .timeout(Duration::from_secs(10))
+    .security_headers(SecurityHeaders::default());
+    let mut client = Client::new(service);
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.text(), "index");
+    assert_eq!(response.header("X-Content-Type-Options"), Some("nosniff"));
+
+    let response = client.send(RequestBuilder::head("/")).unwrap();
+    assert_eq!(response.text(), "");
+}
@@ -0,0 +1,201 @@
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{
+        header::{self, HeaderName},
+        HeaderValue, Request, Response, StatusCode,
+    },
+    hyper::{service::Service, Body, Server},
+    service::{AsyncService, HeaderPolicy, ResponseDecorations, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, Error, FromRequest, Guard, NoContext,
+};
+use std::sync::Arc;
+
+/// Adds an `X-Guard` header to the response.
+struct Decorate;
+
+impl Guard for Decorate {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        let decorations = ResponseDecorations::get(request).expect("no decorations");
+        decorations.append_header(
+            HeaderName::from_static("x-guard"),
+            HeaderValue::from_static("first"),
+        );
+        decorations.append_header(
+            HeaderName::from_static("x-guard"),
+            HeaderValue::from_static("second"),
+        );
+        decorations.insert_header(
+            HeaderName::from_static("x-handler"),
+            HeaderValue::from_static("guard"),
+        );
+        decorations.set_status(StatusCode::ACCEPTED);
+        Ok(Decorate)
+    }
+}
+
+/// Fails with `403 Forbidden` if the request has an `X-Deny` header.
+struct Deny;
+
+impl Guard for Deny {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        if request.headers().contains_key("x-deny") {
+            Err(Error::from_status(StatusCode::FORBIDDEN).into())
+        } else {
+            Ok(Deny)
+        }
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index { _decorate: Decorate, _deny: Deny },
+}
+
+fn call(request: Request<Body>) -> Response<Body> {
+    let mut service = AsyncService::new(|route: Routes, _| match route {
+        Routes::Index { .. } => Ok(Response::builder()
+            .header("X-Handler", "handler")
+            .header("X-Guard", "handler")
+            .body(Body::empty())
+            .unwrap())
+        .into_future(),
+    });
+    service.call(request).wait().unwrap()
+}
+
+fn values<'a>(response: &'a Response<Body>, name: &str) -> Vec<&'a str> {
+    response
+        .headers()
+        .get_all(name)
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn success() {
+    let response = call(Request::get("/").body(Body::empty()).unwrap());
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(
+        values(&response, "x-guard"),
+        &["handler", "first", "second"]
+    );
+    assert_eq!(values(&response, "x-handler"), &["guard"]);
+}
+
+#[test]
+fn error() {
+    let response = call(
+        Request::get("/")
+            .header("X-Deny", "1")
+            .body(Body::empty())
+            .unwrap(),
+    );
+    // The status override doesn't hide the error
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(values(&response, "x-guard"), &["first", "second"]);
+    assert_eq!(values(&response, "x-handler"), &["guard"]);
+}
+
+/// Sets a session cookie and overrides the `Content-Type`.
+struct Session;
+
+impl Guard for Session {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        let decorations = ResponseDecorations::get(request).expect("no decorations");
+        decorations.insert_header(
+            header::SET_COOKIE,
+            HeaderValue::from_static("session=abc; HttpOnly"),
+        );
+        decorations.insert_header(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        Ok(Session)
+    }
+}
+
+/// Sets a CSRF cookie.
+struct Csrf;
+
+impl Guard for Csrf {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _context: &NoContext) -> Self::Result {
+        let decorations = ResponseDecorations::get(request).expect("no decorations");
+        decorations.insert_header(header::SET_COOKIE, HeaderValue::from_static("csrf=xyz"));
+        decorations.insert_header(header::VARY, HeaderValue::from_static("Cookie"));
+        Ok(Csrf)
+    }
+}
+
+#[derive(FromRequest)]
+enum CookieRoutes {
+    #[get("/")]
+    Index { _session: Session, _csrf: Csrf },
+}
+
+fn cookie_service(
+) -> SyncService<impl Fn(CookieRoutes, Arc<Request<()>>) -> Response<Body>, CookieRoutes> {
+    SyncService::new(|_: CookieRoutes, _| {
+        Response::builder()
+            .header(header::SET_COOKIE, "locale=de")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from("ok"))
+            .unwrap()
+    })
+}
+
+#[test]
+fn list_headers_are_appended() {
+    let service = cookie_service();
+    let srv =
+        Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service.make_service_by_cloning());
+    let port = srv.local_addr().port();
+    std::thread::spawn(move || {
+        tokio::run(srv.map_err(|e| {
+            panic!("unexpected error: {}", e);
+        }))
+    });
+
+    let response = reqwest::get(&format!("http://127.0.0.1:{}/", port)).unwrap();
+    let cookies = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(cookies, &["locale=de", "session=abc; HttpOnly", "csrf=xyz"]);
+    assert_eq!(response.headers()[header::VARY], "Cookie");
+
+    // Singletons are replaced by default
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+}
+
+#[test]
+fn first_wins() {
+    let mut client = Client::new(cookie_service().header_policy(HeaderPolicy::FirstWins));
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.header(header::CONTENT_TYPE), Some("text/plain"));
+    assert_eq!(
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .count(),
+        3
+    );
+
+    let mut client = Client::new(cookie_service().header_policy(HeaderPolicy::LastWins));
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.header(header::CONTENT_TYPE), Some("text/html"));
+}
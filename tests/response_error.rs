@@ -0,0 +1,138 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    response::IntoResponse,
+    service::{AsyncService, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, FromRequest, Guard, NoContext, ResponseError,
+};
+use std::fmt;
+use std::sync::Arc;
+
+/// Fails with `402 Payment Required` once the quota is used up.
+#[derive(Debug)]
+struct QuotaExceeded;
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("request quota exceeded")
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl ResponseError for QuotaExceeded {
+    fn status(&self) -> StatusCode {
+        StatusCode::PAYMENT_REQUIRED
+    }
+}
+
+/// Uses a custom response body.
+#[derive(Debug)]
+struct Banned;
+
+impl fmt::Display for Banned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("user is banned")
+    }
+}
+
+impl std::error::Error for Banned {}
+
+impl ResponseError for Banned {
+    fn response(&self) -> Response<Body> {
+        let mut response = (StatusCode::FORBIDDEN, self.to_string()).into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, "3600".parse().unwrap());
+        response
+    }
+}
+
+struct Account;
+
+impl Guard for Account {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<http::Request<()>>, _: &NoContext) -> Self::Result {
+        match request
+            .headers()
+            .get("x-account")
+            .map(|v| v.to_str().unwrap())
+        {
+            Some("over-quota") => Err(QuotaExceeded.into()),
+            Some("banned") => Err(Banned.into()),
+            Some("broken") => Err("database unavailable".into()),
+            _ => Ok(Account),
+        }
+    }
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index { _account: Account },
+}
+
+fn send<S>(client: &mut Client<S>, account: &str) -> hyperdrive::test::TestResponse
+where
+    S: hyper::service::Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    client
+        .send(RequestBuilder::get("/").header("X-Account", account))
+        .unwrap()
+}
+
+#[test]
+fn sync_service() {
+    let service = SyncService::responding(|_: Routes, _| "welcome")
+        .response_error::<QuotaExceeded>()
+        .response_error::<Banned>();
+    let mut client = Client::new(service);
+
+    assert_eq!(send(&mut client, "ok").text(), "welcome");
+
+    let response = send(&mut client, "over-quota");
+    assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+    assert_eq!(response.text(), "");
+
+    let response = send(&mut client, "banned");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.header("Retry-After"), Some("3600"));
+    assert_eq!(response.text(), "user is banned");
+
+    // Unregistered errors still drop the connection
+    assert!(client
+        .send(RequestBuilder::get("/").header("X-Account", "broken"))
+        .is_err());
+}
+
+#[test]
+fn unregistered() {
+    let mut client = Client::new(SyncService::responding(|_: Routes, _| "welcome"));
+    assert!(client
+        .send(RequestBuilder::get("/").header("X-Account", "over-quota"))
+        .is_err());
+}
+
+#[test]
+fn precedence() {
+    // Registered errors never reach the error handler
+    let service =
+        AsyncService::responding(|_: Routes, _| Ok::<_, BoxedError>("welcome").into_future())
+            .response_error::<QuotaExceeded>()
+            .with_error_handler(|_| StatusCode::SERVICE_UNAVAILABLE.into_response());
+    let mut client = Client::new(service);
+
+    assert_eq!(
+        send(&mut client, "over-quota").status(),
+        StatusCode::PAYMENT_REQUIRED
+    );
+    assert_eq!(
+        send(&mut client, "broken").status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
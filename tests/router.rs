@@ -0,0 +1,126 @@
+use hyperdrive::{
+    http::{Request, StatusCode},
+    service::{Router, SyncService},
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Users {
+    #[get("/")]
+    List,
+
+    #[get("/{id}/profile")]
+    User { id: u32 },
+
+    #[get("/{id}/posts/{post}")]
+    Post { id: u32, post: u32 },
+}
+
+#[derive(FromRequest)]
+enum Admin {
+    #[get("/stats")]
+    Stats,
+
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+fn router() -> Router {
+    Router::new()
+        .mount(
+            "/users",
+            SyncService::responding(|route: Users, _| match route {
+                Users::List => "all users".to_string(),
+                Users::User { id } => format!("user #{}", id),
+                Users::Post { id, post } => format!("post #{} of user #{}", post, id),
+            }),
+        )
+        .mount(
+            "/users/admin",
+            SyncService::responding(|route: Admin, req: Arc<Request<()>>| match route {
+                Admin::Stats => format!("stats {}", req.uri()),
+                Admin::User { id } => format!("admin view of user #{}", id),
+            }),
+        )
+}
+
+fn get(client: &mut Client<Router>, path: &str) -> (StatusCode, String) {
+    let response = client.send(RequestBuilder::get(path)).unwrap();
+    (response.status(), response.text().to_string())
+}
+
+#[test]
+fn strips_prefix() {
+    let mut client = Client::new(router());
+
+    assert_eq!(get(&mut client, "/users/7/profile").1, "user #7");
+    assert_eq!(get(&mut client, "/users/7/posts/3").1, "post #3 of user #7");
+    // The prefix itself is passed on as `/`
+    assert_eq!(get(&mut client, "/users").1, "all users");
+    assert_eq!(get(&mut client, "/users/").1, "all users");
+}
+
+#[test]
+fn longest_prefix_wins() {
+    let mut client = Client::new(router());
+
+    assert_eq!(
+        get(&mut client, "/users/admin/users/7").1,
+        "admin view of user #7"
+    );
+    // The query is preserved
+    assert_eq!(
+        get(&mut client, "/users/admin/stats?days=7").1,
+        "stats /stats?days=7"
+    );
+    // `/users/admin` doesn't handle `/`, and the shorter prefix isn't tried
+    assert_eq!(get(&mut client, "/users/admin").0, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn segment_boundaries() {
+    let mut client = Client::new(router());
+
+    assert_eq!(get(&mut client, "/users7").0, StatusCode::NOT_FOUND);
+    assert_eq!(
+        get(&mut client, "/users/administrator").0,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(get(&mut client, "/").0, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn fallback() {
+    let router = router().fallback(SyncService::responding(
+        |route: Admin, req: Arc<Request<()>>| match route {
+            Admin::Stats => format!("fallback {}", req.uri()),
+            Admin::User { id } => format!("fallback user #{}", id),
+        },
+    ));
+    let mut client = Client::new(router);
+
+    // Requests are passed to the fallback unchanged
+    assert_eq!(get(&mut client, "/stats").1, "fallback /stats");
+    assert_eq!(get(&mut client, "/users7").0, StatusCode::NOT_FOUND);
+    assert_eq!(get(&mut client, "/users/7/profile").1, "user #7");
+}
+
+#[test]
+#[should_panic(expected = "a service is already mounted at `/users`")]
+fn duplicate_prefix() {
+    router().mount("/users", SyncService::responding(|_: Admin, _| ""));
+}
+
+#[test]
+#[should_panic(expected = "must not end with `/`")]
+fn trailing_slash() {
+    Router::new().mount("/users/", SyncService::responding(|_: Admin, _| ""));
+}
+
+#[test]
+#[should_panic(expected = "use `Router::fallback` instead")]
+fn root_prefix() {
+    Router::new().mount("/", SyncService::responding(|_: Admin, _| ""));
+}
@@ -0,0 +1,343 @@
+//! Tests route metadata and `routes::audit`.
+
+use hyperdrive::{
+    files::{StaticFiles, StaticFilesConfig},
+    http::Method,
+    routes::{self, RouteInfo, Warning},
+    FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Inner {
+    #[get("/inner/{id}")]
+    Item { id: u32 },
+
+    #[get("/users/me")]
+    Me,
+
+    #[get("/users/{id}/settings")]
+    Settings { id: u32 },
+}
+
+#[derive(FromRequest)]
+enum Outer {
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    #[post("/users/{id}/settings")]
+    PostSettings { id: u32 },
+
+    Fallback {
+        #[forward]
+        inner: Inner,
+    },
+}
+
+#[test]
+fn forwarded_routes() {
+    assert_eq!(
+        Outer::routes(),
+        vec![
+            RouteInfo::new(Method::GET, "/users/{id}", "User"),
+            RouteInfo::new(Method::HEAD, "/users/{id}", "User").implied(),
+            RouteInfo::new(Method::POST, "/users/{id}/settings", "PostSettings"),
+            RouteInfo::new(Method::GET, "/inner/{id}", "Item").forwarded_by("Fallback"),
+            RouteInfo::new(Method::HEAD, "/inner/{id}", "Item")
+                .implied()
+                .forwarded_by("Fallback"),
+            RouteInfo::new(Method::GET, "/users/me", "Me").forwarded_by("Fallback"),
+            RouteInfo::new(Method::HEAD, "/users/me", "Me")
+                .implied()
+                .forwarded_by("Fallback"),
+            RouteInfo::new(Method::GET, "/users/{id}/settings", "Settings")
+                .forwarded_by("Fallback"),
+            RouteInfo::new(Method::HEAD, "/users/{id}/settings", "Settings")
+                .implied()
+                .forwarded_by("Fallback"),
+        ]
+    );
+
+    let route = RouteInfo::new(Method::GET, "/", "Index")
+        .forwarded_by("Inner")
+        .forwarded_by("Outer");
+    assert_eq!(route.forwarded_by_names(), &["Outer", "Inner"]);
+    assert_eq!(route.to_string(), "`GET /` (Outer > Inner > Index)");
+}
+
+#[test]
+fn shadowed() {
+    let audit = routes::audit::<Outer>();
+
+    // `/users/{id}/settings` is only shadowed for `POST`
+    assert_eq!(
+        audit.warnings(),
+        &[Warning::Shadowed {
+            route: RouteInfo::new(Method::GET, "/users/me", "Me").forwarded_by("Fallback"),
+            by: RouteInfo::new(Method::GET, "/users/{id}", "User"),
+        }]
+    );
+    assert_eq!(
+        audit.to_string(),
+        "\
+METHOD  PATH                  ROUTE
+GET     /users/{id}           User
+HEAD    /users/{id}           User (implied)
+POST    /users/{id}/settings  PostSettings
+GET     /inner/{id}           Fallback > Item
+HEAD    /inner/{id}           Fallback > Item (implied)
+GET     /users/me             Fallback > Me
+HEAD    /users/me             Fallback > Me (implied)
+GET     /users/{id}/settings  Fallback > Settings
+HEAD    /users/{id}/settings  Fallback > Settings (implied)
+
+warning: `GET /users/me` (Fallback > Me) is unreachable, since `GET /users/{id}` (User) \
+matches all of its requests
+"
+    );
+}
+
+#[test]
+fn shadowed_by_rest() {
+    #[derive(FromRequest)]
+    enum Routes {
+        #[get("/{path...}")]
+        Files { path: String },
+
+        Fallback {
+            #[forward]
+            inner: Inner,
+        },
+    }
+
+    let shadowed = routes::audit::<Routes>()
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            Warning::Shadowed { route, by } => (route.name(), by.name()),
+            _ => panic!("unexpected warning: {}", warning),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        shadowed,
+        vec![("Item", "Files"), ("Me", "Files"), ("Settings", "Files")]
+    );
+}
+
+#[test]
+fn head_overridden() {
+    #[derive(FromRequest)]
+    enum Routes {
+        #[get("/users/{id}")]
+        User { id: u32 },
+
+        #[head("/users/{user}")]
+        UserExists { user: u32 },
+
+        #[get("/items/{id}")]
+        #[head("/items/{id}")]
+        Item { id: u32 },
+    }
+
+    let audit = routes::audit::<Routes>();
+    assert_eq!(
+        audit.warnings(),
+        &[Warning::HeadOverridden {
+            get: RouteInfo::new(Method::GET, "/users/{id}", "User"),
+            head: RouteInfo::new(Method::HEAD, "/users/{user}", "UserExists"),
+        }]
+    );
+    assert_eq!(
+        audit.warnings()[0].to_string(),
+        "HEAD requests for `GET /users/{id}` (User) are decoded by \
+         `HEAD /users/{user}` (UserExists) instead"
+    );
+}
+
+#[test]
+fn declaration_order() {
+    #[derive(FromRequest)]
+    enum Routes {
+        #[post("/users")]
+        #[get("/users")]
+        Users,
+
+        #[get("/users/{id}")]
+        #[delete("/users/{id}")]
+        User { id: u32 },
+
+        #[get("/about")]
+        About,
+    }
+
+    #[derive(FromRequest)]
+    enum Reordered {
+        #[get("/about")]
+        About,
+
+        #[delete("/users/{id}")]
+        #[get("/users/{id}")]
+        User { id: u32 },
+
+        #[get("/users")]
+        #[post("/users")]
+        Users,
+    }
+
+    assert_eq!(Routes::routes(), Reordered::routes());
+    assert_eq!(
+        Routes::routes(),
+        vec![
+            RouteInfo::new(Method::GET, "/about", "About"),
+            RouteInfo::new(Method::HEAD, "/about", "About").implied(),
+            RouteInfo::new(Method::GET, "/users", "Users"),
+            RouteInfo::new(Method::HEAD, "/users", "Users").implied(),
+            RouteInfo::new(Method::POST, "/users", "Users"),
+            RouteInfo::new(Method::DELETE, "/users/{id}", "User"),
+            RouteInfo::new(Method::GET, "/users/{id}", "User"),
+            RouteInfo::new(Method::HEAD, "/users/{id}", "User").implied(),
+        ]
+    );
+}
+
+#[test]
+fn trailing_slash() {
+    #[derive(FromRequest)]
+    enum Routes {
+        #[get("/users")]
+        Users,
+
+        #[post("/users/")]
+        CreateUser,
+
+        #[get("/items/{id}")]
+        Item { id: u32 },
+
+        #[get("/items/{item}/")]
+        ItemSlash { item: u32 },
+    }
+
+    let pairs = routes::audit::<Routes>()
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            Warning::TrailingSlash { without, with } => (without.path(), with.path()),
+            _ => panic!("unexpected warning: {}", warning),
+        })
+        .collect::<Vec<_>>();
+
+    // Each pair of paths is only reported once, in the order of the routes
+    assert_eq!(
+        pairs,
+        vec![("/items/{id}", "/items/{item}/"), ("/users", "/users/")]
+    );
+}
+
+#[test]
+fn clean() {
+    fn config(config: &StaticFilesConfig) -> StaticFilesConfig {
+        config.clone()
+    }
+
+    #[derive(FromRequest)]
+    #[context(StaticFilesConfig)]
+    enum Routes {
+        #[get("/")]
+        Index,
+
+        #[get("/api/status")]
+        Status,
+
+        #[get("/users/{id}")]
+        #[delete("/users/{id}")]
+        User { id: u32 },
+
+        Files {
+            #[forward(with = config)]
+            files: StaticFiles,
+        },
+    }
+
+    let audit = routes::audit::<Routes>();
+    assert!(audit.warnings().is_empty(), "{}", audit);
+    assert_eq!(audit.routes().len(), 9);
+    assert!(!audit.to_string().contains("warning"));
+
+    // `*` only overlaps with itself, and types implemented manually have no
+    // routes by default
+    #[derive(FromRequest)]
+    enum Options {
+        #[options("*")]
+        Server,
+
+        #[options("/{path...}")]
+        Path { path: String },
+    }
+    assert!(routes::audit::<Options>().warnings().is_empty());
+    assert!(routes::audit::<NoRoutes>().routes().is_empty());
+}
+
+struct NoRoutes;
+
+impl FromRequest for NoRoutes {
+    type Future = hyperdrive::DefaultFuture<Self, hyperdrive::BoxedError>;
+    type Context = hyperdrive::NoContext;
+
+    fn from_request_and_body(
+        _: &std::sync::Arc<hyperdrive::http::Request<()>>,
+        _: hyperdrive::hyper::Body,
+        _: std::sync::Arc<Self::Context>,
+    ) -> Self::Future {
+        Box::new(futures::future::ok(NoRoutes))
+    }
+}
+
+#[test]
+fn docs() {
+    #[derive(serde::Deserialize)]
+    struct Pagination {}
+
+    #[derive(FromRequest)]
+    enum Routes {
+        /// Returns the user profile; requires auth.
+        ///
+        /// Second paragraph.
+        #[get("/users/{id}")]
+        User {
+            /// The ID of the user.
+            id: u32,
+
+            /// How to page through the activity.
+            #[query_params]
+            page: Pagination,
+        },
+
+        #[post("/users/{id}/{name}")]
+        Rename {
+            id: u32,
+            /// The new name.
+            name: String,
+        },
+    }
+
+    let routes = Routes::routes();
+    let doc = "Returns the user profile; requires auth.\n\nSecond paragraph.";
+    let params: &[_] = &[
+        ("id", "The ID of the user."),
+        ("page", "How to page through the activity."),
+    ];
+    assert_eq!(routes[0].doc(), Some(doc));
+    assert_eq!(routes[0].param_docs(), params);
+
+    // Implied routes are documented like the route implying them
+    assert!(routes[1].is_implied());
+    assert_eq!(routes[1].doc(), Some(doc));
+    assert_eq!(routes[1].param_docs(), params);
+
+    assert_eq!(routes[2].doc(), None);
+    assert_eq!(routes[2].param_docs(), &[("name", "The new name.")]);
+    assert_eq!(
+        routes[2],
+        RouteInfo::new(Method::POST, "/users/{id}/{name}", "Rename")
+            .param_doc("name", "The new name.")
+    );
+}
@@ -0,0 +1,125 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    http::{header, HeaderMap, Response},
+    hyper::Body,
+    service::{AsyncService, SecurityHeaders, ServiceExt},
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/embeddable")]
+    Embeddable,
+}
+
+/// Calls an app using `config` and returns the response headers.
+fn headers(config: SecurityHeaders, request: RequestBuilder) -> HeaderMap {
+    let service = AsyncService::new(|route: Routes, _| {
+        let response = match route {
+            Routes::Index => Response::new(Body::empty()),
+            Routes::Embeddable => Response::builder()
+                .header("X-Frame-Options", "ALLOW-FROM https://example.com")
+                .body(Body::empty())
+                .unwrap(),
+        };
+        Ok(response).into_future()
+    })
+    .security_headers(config);
+
+    let response = Client::new(service).send(request).unwrap();
+    response.headers().clone()
+}
+
+fn get(path: &str) -> RequestBuilder {
+    RequestBuilder::get(path)
+}
+
+fn forwarded(proto: &str) -> RequestBuilder {
+    RequestBuilder::get("/").header("X-Forwarded-Proto", proto)
+}
+
+#[test]
+fn defaults() {
+    let headers = headers(SecurityHeaders::default(), get("/"));
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+    assert_eq!(
+        headers[header::REFERRER_POLICY],
+        "strict-origin-when-cross-origin"
+    );
+    assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    // Not sent via plain HTTP
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+}
+
+#[test]
+fn hsts() {
+    let hsts = |config: SecurityHeaders, request| {
+        headers(config, request)
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .cloned()
+    };
+    let expected = "max-age=31536000; includeSubDomains";
+
+    // `X-Forwarded-Proto` is ignored unless trusted
+    assert_eq!(hsts(SecurityHeaders::default(), forwarded("https")), None);
+
+    let trusted = SecurityHeaders::default().trust_forwarded_proto(true);
+    assert_eq!(hsts(trusted.clone(), forwarded("https")).unwrap(), expected);
+    assert_eq!(hsts(trusted.clone(), forwarded("http")), None);
+    assert_eq!(
+        hsts(trusted.clone(), forwarded("http, https")).unwrap(),
+        expected
+    );
+    assert_eq!(hsts(trusted, get("/")), None);
+
+    let tls = SecurityHeaders::default().tls(true);
+    assert_eq!(hsts(tls, get("/")).unwrap(), expected);
+}
+
+#[test]
+fn overrides() {
+    let config = SecurityHeaders::default()
+        .frame_options(Some("DENY"))
+        .referrer_policy(None)
+        .content_type_options(None)
+        .content_security_policy(Some("default-src 'self'"))
+        .strict_transport_security(Some("max-age=60"))
+        .tls(true);
+    let headers = headers(config, get("/"));
+
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        "default-src 'self'"
+    );
+    assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=60");
+    assert!(!headers.contains_key(header::REFERRER_POLICY));
+    assert!(!headers.contains_key(header::X_CONTENT_TYPE_OPTIONS));
+}
+
+#[test]
+fn no_overwrite() {
+    let headers = headers(SecurityHeaders::default(), get("/embeddable"));
+    assert_eq!(
+        headers[header::X_FRAME_OPTIONS],
+        "ALLOW-FROM https://example.com"
+    );
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+}
+
+#[test]
+fn force() {
+    let headers = headers(SecurityHeaders::default().force(true), get("/embeddable"));
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+}
+
+#[test]
+#[should_panic(expected = "invalid header value")]
+fn invalid_value() {
+    SecurityHeaders::default().content_security_policy(Some("line\nbreak"));
+}
@@ -0,0 +1,127 @@
+use http::{Request, Response, StatusCode};
+use hyper::Body;
+use hyperdrive::{
+    body::Json,
+    service::{GracefulShutdown, ServiceExt, SyncService},
+    FromRequest,
+};
+use serde::Deserialize;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[post("/login")]
+    Login {
+        #[body]
+        data: Json<Login>,
+    },
+}
+
+#[derive(Deserialize)]
+struct Login {
+    email: String,
+    password: String,
+}
+
+fn handler(route: Route, _: Arc<Request<()>>) -> Response<Body> {
+    match route {
+        Route::Index => Response::new(Body::from("Hello World!")),
+        Route::Login { data } => {
+            if data.password == "hunter2" {
+                Response::new(Body::from(format!("Welcome, {}!", data.email)))
+            } else {
+                Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(Body::from("Invalid username or password"))
+                    .expect("building response failed")
+            }
+        }
+    }
+}
+
+/// Returns a port that is (most likely) free to bind to.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Sends a login request, retrying until the server accepts connections.
+fn login(port: u16, password: &str) -> (StatusCode, String) {
+    let start = Instant::now();
+    loop {
+        let result = reqwest::Client::new()
+            .post(&format!("http://127.0.0.1:{}/login", port))
+            .body(format!(
+                r#"{{ "email": "oof@example.com", "password": "{}" }}"#,
+                password
+            ))
+            .send();
+        match result {
+            Ok(mut response) => return (response.status(), response.text().unwrap()),
+            Err(e) => {
+                assert!(
+                    start.elapsed() < Duration::from_secs(10),
+                    "server didn't start: {}",
+                    e
+                );
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+#[test]
+fn serve_with_shutdown() {
+    let port = free_port();
+    let shutdown = GracefulShutdown::new();
+    let handle = shutdown.clone();
+    let server = thread::spawn(move || {
+        hyperdrive::serve_with_shutdown(("127.0.0.1", port), SyncService::new(handler), &handle)
+    });
+
+    assert_eq!(
+        login(port, "hunter2"),
+        (StatusCode::OK, "Welcome, oof@example.com!".to_string())
+    );
+    assert_eq!(login(port, "hunter3").0, StatusCode::UNAUTHORIZED);
+
+    shutdown.shutdown();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn wrapped_service() {
+    let port = free_port();
+    let shutdown = GracefulShutdown::new();
+    let handle = shutdown.clone();
+    let server = thread::spawn(move || {
+        let service = SyncService::new(handler)
+            .graceful_shutdown(&handle)
+            .make_service_by_cloning();
+        hyperdrive::serve_with_shutdown(("127.0.0.1", port), service, &handle)
+    });
+
+    assert_eq!(login(port, "hunter2").0, StatusCode::OK);
+
+    shutdown.shutdown();
+    assert!(shutdown.wait_idle(Duration::from_secs(10)));
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn bind_error() {
+    // Keep the port occupied
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    assert!(hyperdrive::serve(addr, SyncService::new(handler)).is_err());
+}
@@ -0,0 +1,187 @@
+use futures::{Future, IntoFuture, Stream};
+use hyperdrive::{
+    guard::{MemoryStore, Session, SessionConfig},
+    http::{header, Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, ServiceExt},
+    BoxedError, FromRequest,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct User {
+    name: String,
+}
+
+#[derive(FromRequest)]
+#[context(SessionConfig)]
+enum Routes {
+    #[post("/login")]
+    Login { session: Session<User> },
+
+    #[get("/whoami")]
+    WhoAmI { session: Session<User> },
+
+    #[post("/logout")]
+    Logout { session: Session<User> },
+}
+
+fn app(config: SessionConfig) -> impl Service<ReqBody = Body, ResBody = Body, Error = BoxedError> {
+    AsyncService::with_context(
+        |route: Routes, _| {
+            let response = match route {
+                Routes::Login { mut session } => session
+                    .renew()
+                    .and_then(|()| {
+                        session.set(User {
+                            name: "alice".to_string(),
+                        })
+                    })
+                    .map(|()| Response::new(Body::empty())),
+                Routes::WhoAmI { session } => Ok(match session.get() {
+                    Some(user) => Response::new(Body::from(user.name.clone())),
+                    None => Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                        .unwrap(),
+                }),
+                Routes::Logout { mut session } => {
+                    session.destroy();
+                    Ok(Response::new(Body::empty()))
+                }
+            };
+            response.into_future()
+        },
+        config,
+    )
+    .sessions()
+}
+
+fn send<S>(service: &mut S, method: &str, path: &str, cookie: Option<&str>) -> Response<Body>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    let mut request = Request::builder();
+    request.method(method).uri(path);
+    if let Some(cookie) = cookie {
+        request.header(header::COOKIE, cookie);
+    }
+    service
+        .call(request.body(Body::empty()).unwrap())
+        .wait()
+        .unwrap()
+}
+
+fn set_cookie(response: &Response<Body>) -> Option<&str> {
+    response
+        .headers()
+        .get(header::SET_COOKIE)
+        .map(|value| value.to_str().unwrap())
+}
+
+fn body(response: Response<Body>) -> String {
+    let body = response.into_body().concat2().wait().unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[test]
+fn login_logout() {
+    let mut service = app(SessionConfig::new(MemoryStore::new(), "secret key"));
+
+    // No session yet, and none is created just by looking
+    let response = send(&mut service, "GET", "/whoami", None);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(set_cookie(&response), None);
+
+    let response = send(&mut service, "POST", "/login", None);
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_cookie = set_cookie(&response).expect("no session cookie set");
+    assert!(new_cookie.starts_with("session="), "{}", new_cookie);
+    assert!(new_cookie.contains("; HttpOnly"), "{}", new_cookie);
+    let cookie = new_cookie.split(';').next().unwrap().to_string();
+
+    let response = send(&mut service, "GET", "/whoami", Some(&cookie));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(set_cookie(&response), None);
+    assert_eq!(body(response), "alice");
+
+    let response = send(&mut service, "POST", "/logout", Some(&cookie));
+    assert_eq!(response.status(), StatusCode::OK);
+    let removal = set_cookie(&response).expect("session cookie not removed");
+    assert!(removal.starts_with("session=;"), "{}", removal);
+    assert!(removal.contains("; Max-Age=0"), "{}", removal);
+
+    // The old cookie no longer grants access
+    let response = send(&mut service, "GET", "/whoami", Some(&cookie));
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn renewed_at_login() {
+    let mut service = app(SessionConfig::new(MemoryStore::new(), "secret key"));
+
+    // An attacker obtains a valid session cookie and plants it in the victim's
+    // browser
+    let response = send(&mut service, "POST", "/login", None);
+    let planted = set_cookie(&response).unwrap().split(';').next().unwrap();
+    let planted = planted.to_string();
+
+    // Logging in with it moves the session to a new ID
+    let response = send(&mut service, "POST", "/login", Some(&planted));
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = set_cookie(&response).expect("session cookie not renewed");
+    let cookie = cookie.split(';').next().unwrap().to_string();
+    assert!(cookie.starts_with("session="), "{}", cookie);
+    assert_ne!(cookie, planted);
+
+    let response = send(&mut service, "GET", "/whoami", Some(&cookie));
+    assert_eq!(body(response), "alice");
+
+    // The planted cookie no longer grants access
+    let response = send(&mut service, "GET", "/whoami", Some(&planted));
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn forged_cookie() {
+    let mut service = app(SessionConfig::new(MemoryStore::new(), "secret key"));
+
+    let response = send(&mut service, "POST", "/login", None);
+    let cookie = set_cookie(&response).unwrap().split(';').next().unwrap();
+    let (id, _signature) = cookie.split_at(cookie.find('.').unwrap());
+
+    let forged = format!("{}.{}", id, "00".repeat(32));
+    let response = send(&mut service, "GET", "/whoami", Some(&forged));
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // A cookie signed with a different key is rejected as well
+    let mut other = app(SessionConfig::new(MemoryStore::new(), "other key"));
+    let response = send(&mut other, "GET", "/whoami", Some(cookie));
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn key_rotation() {
+    let store = Arc::new(MemoryStore::new());
+    let mut old = app(SessionConfig::new(store.clone(), "old key"));
+    let response = send(&mut old, "POST", "/login", None);
+    let cookie = set_cookie(&response).unwrap().split(';').next().unwrap();
+
+    // Cookies signed with an old key are still accepted
+    let mut rotated = app(SessionConfig::new(store, "new key").old_key("old key"));
+    let response = send(&mut rotated, "GET", "/whoami", Some(cookie));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(response), "alice");
+}
+
+#[test]
+fn missing_adapter() {
+    let config = SessionConfig::new(MemoryStore::new(), "secret key");
+    let err =
+        Routes::from_request_sync(Request::get("/whoami").body(Body::empty()).unwrap(), config)
+            .err()
+            .unwrap();
+    let err: Box<hyperdrive::Error> = err.downcast().unwrap();
+    assert_eq!(err.http_status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,129 @@
+use hyperdrive::{
+    http::{Request, StatusCode},
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    BoxedError, Error, FromRequest, Guard, RequestContext, Shared,
+};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug, Default)]
+struct Flags {
+    beta: bool,
+}
+
+#[derive(RequestContext)]
+struct Context {
+    #[as_ref]
+    flags: Shared<Flags>,
+}
+
+/// Only succeeds if the beta flag is set.
+struct Beta;
+
+impl Guard for Beta {
+    type Context = Shared<Flags>;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, flags: &Shared<Flags>) -> Self::Result {
+        if flags.load().beta {
+            Ok(Beta)
+        } else {
+            Err(Error::from_status(StatusCode::NOT_FOUND).into())
+        }
+    }
+}
+
+#[derive(FromRequest)]
+#[context(Context)]
+enum Routes {
+    #[get("/beta")]
+    Beta { _beta: Beta },
+
+    #[post("/admin/beta/{enabled}")]
+    SetBeta { enabled: bool },
+}
+
+#[test]
+fn admin_route_stores() {
+    let service = SyncService::responding_with_context_handler(
+        |route: Routes, _: Arc<Request<()>>, context: Arc<Context>| match route {
+            Routes::Beta { .. } => "welcome to the beta",
+            Routes::SetBeta { enabled } => {
+                context.flags.store(Flags { beta: enabled });
+                "updated"
+            }
+        },
+        Context {
+            flags: Shared::default(),
+        },
+    );
+    let mut client = Client::new(service);
+
+    let response = client.send(RequestBuilder::get("/beta")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .send(RequestBuilder::post("/admin/beta/true"))
+        .unwrap();
+    assert_eq!(response.text(), "updated");
+
+    let response = client.send(RequestBuilder::get("/beta")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "welcome to the beta");
+
+    client
+        .send(RequestBuilder::post("/admin/beta/false"))
+        .unwrap();
+    let response = client.send(RequestBuilder::get("/beta")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn snapshots() {
+    let shared = Shared::new(1);
+    let before = shared.load();
+    assert_eq!(*shared.swap(2), 1);
+    assert_eq!(*before, 1);
+    assert_eq!(*shared.load(), 2);
+
+    // Clones share the value
+    shared.clone().store(3);
+    assert_eq!(*shared.load(), 3);
+    assert_eq!(format!("{:?}", shared), "Shared(3)");
+}
+
+#[test]
+fn concurrent_load_store() {
+    const WRITES: usize = 1000;
+
+    let shared = Shared::new(0);
+    let writer = {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for i in 1..=WRITES {
+                shared.store(i);
+            }
+        })
+    };
+    let readers = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                // Every reader sees the values in the order they were stored
+                let mut last = 0;
+                while last < WRITES {
+                    let value = *shared.load();
+                    assert!(value >= last, "{} loaded after {}", value, last);
+                    last = value;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(*shared.load(), WRITES);
+}
@@ -0,0 +1,307 @@
+//! Tests serving files with `StaticFiles`.
+
+use hyperdrive::{
+    files::{StaticFiles, StaticFilesConfig},
+    http::{Method, StatusCode},
+    response::IntoResponse,
+    service::{BoxedService, Router, ServiceExt, SyncService},
+    test::{Client, RequestBuilder, TestResponse},
+    FromRequest, RequestContext, RouteMatch,
+};
+use std::fs;
+use std::path::PathBuf;
+
+/// Creates a fresh directory tree to serve, unique to each test.
+///
+/// ```text
+/// <dir>/
+///     secret.txt      (outside of the served directory)
+///     public/
+///         hello.txt
+///         style.css
+///         docs/
+///             index.html
+///         empty/
+/// ```
+///
+/// Returns the path of `public`.
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "hyperdrive-static-files-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("public/docs")).unwrap();
+    fs::create_dir_all(dir.join("public/empty")).unwrap();
+    fs::write(dir.join("secret.txt"), "top secret").unwrap();
+    fs::write(dir.join("public/hello.txt"), "Hello, world!").unwrap();
+    fs::write(dir.join("public/style.css"), "body {}").unwrap();
+    fs::write(dir.join("public/docs/index.html"), "<h1>Docs</h1>").unwrap();
+    dir.join("public")
+}
+
+fn client(config: StaticFilesConfig) -> Client<BoxedService> {
+    Client::new(SyncService::responding_with_context(|files: StaticFiles, _| files, config).boxed())
+}
+
+fn get(client: &mut Client<BoxedService>, path: &str) -> TestResponse {
+    client.send(RequestBuilder::get(path)).unwrap()
+}
+
+#[test]
+fn serves_files() {
+    let mut client = client(StaticFilesConfig::new(fixture("serves_files")));
+
+    let response = get(&mut client, "/hello.txt");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "Hello, world!");
+    assert_eq!(response.header("Content-Type"), Some("text/plain"));
+    assert_eq!(response.header("Content-Length"), Some("13"));
+    assert_eq!(response.header("Accept-Ranges"), Some("bytes"));
+    assert!(response.header("ETag").is_some());
+    assert!(response.header("Last-Modified").unwrap().ends_with(" GMT"));
+
+    let response = get(&mut client, "/style.css");
+    assert_eq!(response.header("Content-Type"), Some("text/css"));
+
+    // `HEAD` requests get the same headers
+    let response = client
+        .send(RequestBuilder::new(Method::HEAD, "/hello.txt"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.header("Content-Length"), Some("13"));
+    assert_eq!(response.body(), b"");
+
+    assert_eq!(
+        get(&mut client, "/missing.txt").status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let response = client.send(RequestBuilder::post("/hello.txt")).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header("Allow"), Some("GET, HEAD"));
+}
+
+#[test]
+fn traversal() {
+    let mut client = client(StaticFilesConfig::new(fixture("traversal")));
+
+    for path in &[
+        "/../secret.txt",
+        "/docs/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/..%2fsecret.txt",
+        "/docs/%2E%2E%2F%2E%2E%2Fsecret.txt",
+        "/..%5csecret.txt",
+        "/hello.txt%00",
+    ] {
+        assert_eq!(
+            get(&mut client, path).status(),
+            StatusCode::FORBIDDEN,
+            "{}",
+            path
+        );
+    }
+
+    // Percent-encoded names are decoded
+    assert_eq!(get(&mut client, "/hello%2Etxt").text(), "Hello, world!");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+    let public = fixture("symlinks");
+    std::os::unix::fs::symlink("../secret.txt", public.join("escape.txt")).unwrap();
+    std::os::unix::fs::symlink("hello.txt", public.join("greeting.txt")).unwrap();
+    let mut client = client(StaticFilesConfig::new(public));
+
+    assert_eq!(
+        get(&mut client, "/escape.txt").status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(get(&mut client, "/greeting.txt").text(), "Hello, world!");
+}
+
+#[test]
+fn ranges() {
+    let mut client = client(StaticFilesConfig::new(fixture("ranges")));
+    let range = |client: &mut Client<BoxedService>, range: &str| {
+        client
+            .send(RequestBuilder::get("/hello.txt").header("Range", range))
+            .unwrap()
+    };
+
+    let response = range(&mut client, "bytes=0-4");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.text(), "Hello");
+    assert_eq!(response.header("Content-Range"), Some("bytes 0-4/13"));
+    assert_eq!(response.header("Content-Length"), Some("5"));
+
+    let response = range(&mut client, "bytes=-6");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.text(), "world!");
+    assert_eq!(response.header("Content-Range"), Some("bytes 7-12/13"));
+
+    let response = range(&mut client, "bytes=7-100");
+    assert_eq!(response.text(), "world!");
+
+    let response = range(&mut client, "bytes=13-");
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.header("Content-Range"), Some("bytes */13"));
+
+    // Invalid and multiple ranges are ignored
+    for header in &["bytes=4-2", "items=0-1", "bytes=0-1,3-4"] {
+        let response = range(&mut client, header);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text(), "Hello, world!");
+    }
+}
+
+#[test]
+fn not_modified() {
+    let mut client = client(StaticFilesConfig::new(fixture("not_modified")));
+    let response = get(&mut client, "/hello.txt");
+    let etag = response.header("ETag").unwrap().to_string();
+    let last_modified = response.header("Last-Modified").unwrap().to_string();
+
+    let response = client
+        .send(RequestBuilder::get("/hello.txt").header("If-None-Match", etag.as_str()))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.body(), b"");
+    assert_eq!(response.header("ETag"), Some(etag.as_str()));
+
+    let response = client
+        .send(RequestBuilder::get("/hello.txt").header("If-Modified-Since", last_modified.as_str()))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // `If-None-Match` takes precedence
+    let response = client
+        .send(
+            RequestBuilder::get("/hello.txt")
+                .header("If-None-Match", "\"other\"")
+                .header("If-Modified-Since", last_modified.as_str()),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "Hello, world!");
+}
+
+#[test]
+fn directories() {
+    let public = fixture("directories");
+
+    let mut files_only = client(StaticFilesConfig::new(&public));
+    assert_eq!(
+        get(&mut files_only, "/docs").status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(get(&mut files_only, "/").status(), StatusCode::FORBIDDEN);
+
+    let mut index = client(StaticFilesConfig::new(&public).index_file("index.html"));
+    let response = get(&mut index, "/docs/");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "<h1>Docs</h1>");
+    assert_eq!(response.header("Content-Type"), Some("text/html"));
+    assert_eq!(get(&mut index, "/empty").status(), StatusCode::FORBIDDEN);
+
+    let mut listing = client(StaticFilesConfig::new(&public).list_directories(true));
+    let response = get(&mut listing, "/");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert!(response.text().contains(r#"<a href="./docs/">docs/</a>"#));
+    assert!(response
+        .text()
+        .contains(r#"<a href="./hello.txt">hello.txt</a>"#));
+    assert!(!response.text().contains("secret"));
+
+    // Links stay relative to the directory without a trailing slash
+    let response = get(&mut listing, "/docs");
+    assert!(response
+        .text()
+        .contains(r#"<a href="./docs/index.html">index.html</a>"#));
+}
+
+#[derive(RequestContext)]
+struct AppContext {
+    files: StaticFilesConfig,
+}
+
+fn files_config(context: &AppContext) -> StaticFilesConfig {
+    context.files.clone()
+}
+
+#[derive(FromRequest)]
+#[context(AppContext)]
+enum Routes {
+    #[get("/api/status")]
+    Status,
+
+    Assets {
+        #[forward(with = files_config)]
+        files: StaticFiles,
+    },
+}
+
+#[test]
+fn forward() {
+    let context = AppContext {
+        files: StaticFilesConfig::new(fixture("forward")),
+    };
+    let mut client = Client::new(SyncService::with_context(
+        |route: Routes, _| match route {
+            Routes::Status => "ok".into_response(),
+            Routes::Assets { files } => files.into_response(),
+        },
+        context,
+    ));
+
+    let response = client.send(RequestBuilder::get("/api/status")).unwrap();
+    assert_eq!(response.text(), "ok");
+    let response = client.send(RequestBuilder::get("/hello.txt")).unwrap();
+    assert_eq!(response.text(), "Hello, world!");
+    let response = client.send(RequestBuilder::get("/missing.txt")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The methods of both are allowed
+    let response = client.send(RequestBuilder::post("/api/status")).unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    // Routing can be checked without touching the file system
+    assert_eq!(
+        Routes::match_route(&Method::GET, "/hello.txt"),
+        RouteMatch::Forward {
+            name: "Assets",
+            inner: Box::new(RouteMatch::Route {
+                name: "StaticFiles",
+                placeholders: Vec::new(),
+            }),
+        }
+    );
+    assert_eq!(
+        Routes::match_route(&Method::POST, "/api/status"),
+        RouteMatch::WrongMethod(vec![&Method::GET, &Method::HEAD])
+    );
+}
+
+#[test]
+fn router() {
+    let config = StaticFilesConfig::new(fixture("router"));
+    let router = Router::new().mount(
+        "/static",
+        SyncService::responding_with_context(|files: StaticFiles, _| files, config),
+    );
+    let mut client = Client::new(router);
+
+    let response = client
+        .send(RequestBuilder::get("/static/hello.txt"))
+        .unwrap();
+    assert_eq!(response.text(), "Hello, world!");
+    let response = client.send(RequestBuilder::get("/hello.txt")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,132 @@
+use futures::{Future, Stream};
+use hyperdrive::{
+    http::{Response, StatusCode},
+    hyper::Body,
+    response::stream_blocking,
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/export.csv")]
+    Export,
+}
+
+const ROWS: u64 = 100_000;
+
+fn csv() -> String {
+    let mut csv = String::from("id,square\n");
+    for id in 0..ROWS {
+        csv.push_str(&format!("{},{}\n", id, id * id));
+    }
+    csv
+}
+
+#[test]
+fn all_bytes() {
+    let mut client = Client::new(SyncService::new(|Route::Export, _| {
+        Response::new(stream_blocking(|writer| {
+            writeln!(writer, "id,square")?;
+            for id in 0..ROWS {
+                writeln!(writer, "{},{}", id, id * id)?;
+            }
+            Ok(())
+        }))
+    }));
+
+    let response = client.send(RequestBuilder::get("/export.csv")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), csv());
+
+    // The body of `HEAD` responses isn't produced
+    let response = client.send(RequestBuilder::head("/export.csv")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body(), b"");
+}
+
+#[test]
+fn bounded_memory() {
+    let total = 100 * 100_000;
+    let written = Arc::new(AtomicUsize::new(0));
+    let written2 = written.clone();
+    let body = stream_blocking(move |writer| {
+        let line = [b'x'; 100];
+        for _ in 0..total / line.len() {
+            writer.write_all(&line)?;
+            written2.fetch_add(line.len(), Ordering::SeqCst);
+        }
+        Ok(())
+    });
+
+    let mut runtime = Runtime::new().unwrap();
+    let (first, rest) = runtime
+        .block_on(body.into_future().map_err(|(e, _)| e))
+        .unwrap();
+    assert!(first.is_some());
+
+    // The writer blocks once the channel is full
+    thread::sleep(Duration::from_millis(100));
+    let buffered = written.load(Ordering::SeqCst);
+    assert!(buffered <= 64 * 1024, "{} bytes written", buffered);
+
+    let rest = runtime.block_on(rest.concat2()).unwrap();
+    assert_eq!(first.unwrap().len() + rest.len(), total);
+    runtime.shutdown_now().wait().unwrap();
+}
+
+#[test]
+fn error_aborts_body() {
+    let body = stream_blocking(|writer| {
+        writer.write_all(b"partial")?;
+        writer.flush()?;
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "database timed out",
+        ))
+    });
+
+    // Collect the chunks and the error instead of stopping at the error
+    let results = body
+        .map(|chunk| chunk.to_vec())
+        .map_err(|e| e.to_string())
+        .then(Ok::<_, ()>)
+        .collect();
+    let mut runtime = Runtime::new().unwrap();
+    let results = runtime.block_on(results).unwrap();
+    runtime.shutdown_now().wait().unwrap();
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0], Ok(b"partial".to_vec()));
+    assert!(results[1]
+        .as_ref()
+        .unwrap_err()
+        .contains("database timed out"));
+}
+
+#[test]
+fn disconnect_stops_writer() {
+    let result = Arc::new(std::sync::Mutex::new(None));
+    let result2 = result.clone();
+    let body: Body = stream_blocking(move |writer| {
+        let outcome = (0..).try_for_each(|_| writer.write_all(&[0; 1024]));
+        *result2.lock().unwrap() = Some(outcome.unwrap_err().kind());
+        Ok(())
+    });
+
+    let mut runtime = Runtime::new().unwrap();
+    let (_, rest) = runtime
+        .block_on(body.into_future().map_err(|(e, _)| e))
+        .unwrap();
+    drop(rest);
+    runtime.shutdown_on_idle().wait().unwrap();
+
+    assert_eq!(*result.lock().unwrap(), Some(io::ErrorKind::BrokenPipe));
+}
@@ -0,0 +1,90 @@
+use hyperdrive::{
+    guard::{Subdomain, SubdomainConfig},
+    http::{Request, StatusCode},
+    hyper::Body,
+    BoxedError, Error, FromRequest,
+};
+
+#[derive(FromRequest, Debug)]
+#[context(SubdomainConfig)]
+enum Routes {
+    #[get("/")]
+    Index { subdomain: Subdomain },
+}
+
+fn invoke(host: &str, config: SubdomainConfig) -> Result<Subdomain, BoxedError> {
+    let request = Request::get("/")
+        .header("Host", host)
+        .body(Body::empty())
+        .unwrap();
+    Routes::from_request_sync(request, config).map(|Routes::Index { subdomain }| subdomain)
+}
+
+fn assert_status(result: Result<Subdomain, BoxedError>, status: StatusCode) {
+    let error: Box<Error> = result.unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), status);
+}
+
+#[test]
+fn one_label() {
+    let config = || SubdomainConfig::new("example.com");
+
+    for host in &[
+        "acme.example.com",
+        "ACME.Example.COM",
+        "acme.example.com:8080",
+        "acme.example.com.",
+    ] {
+        let subdomain = invoke(host, config()).unwrap();
+        assert_eq!(subdomain.as_str(), "acme", "{}", host);
+        assert_eq!(subdomain.value(), "acme", "{}", host);
+    }
+}
+
+#[test]
+fn apex() {
+    let config = || SubdomainConfig::new("example.com");
+
+    assert_status(invoke("example.com", config()), StatusCode::NOT_FOUND);
+    assert_status(invoke("example.com:80", config()), StatusCode::NOT_FOUND);
+    assert_status(invoke(".example.com", config()), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn unknown_host() {
+    let config = || SubdomainConfig::new("example.com");
+
+    assert_status(invoke("notexample.com", config()), StatusCode::NOT_FOUND);
+    assert_status(invoke("acme.example.org", config()), StatusCode::NOT_FOUND);
+    assert_status(invoke("127.0.0.1:8080", config()), StatusCode::NOT_FOUND);
+    assert_status(invoke("[::1]:8080", config()), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn nested() {
+    assert_status(
+        invoke("eu.acme.example.com", SubdomainConfig::new("example.com")),
+        StatusCode::NOT_FOUND,
+    );
+
+    let config = || SubdomainConfig::new("example.com").allow_nested(true);
+    let subdomain = invoke("eu.acme.example.com", config()).unwrap();
+    assert_eq!(subdomain.as_str(), "eu.acme");
+    assert_status(invoke("eu..example.com", config()), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn missing_host() {
+    let request = Request::get("/").body(Body::empty()).unwrap();
+    let result = Routes::from_request_sync(request, SubdomainConfig::new("example.com"));
+    let error: Box<Error> = result.unwrap_err().downcast().unwrap();
+    assert_eq!(error.http_status(), StatusCode::BAD_REQUEST);
+
+    // HTTP/2 requests carry the host in the URI instead
+    let request = Request::get("https://acme.example.com/")
+        .body(Body::empty())
+        .unwrap();
+    let Routes::Index { subdomain } =
+        Routes::from_request_sync(request, SubdomainConfig::new("example.com")).unwrap();
+    assert_eq!(subdomain.as_str(), "acme");
+}
@@ -0,0 +1,118 @@
+use hyperdrive::{
+    http::StatusCode,
+    service::SyncService,
+    test::{Client, RequestBuilder},
+    FromRequest, ProblemJson,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(FromRequest)]
+enum Admin {
+    #[get("/admin/settings")]
+    Settings,
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/users/{id}")]
+    User { id: u32 },
+
+    #[get("/users/{id}/posts")]
+    #[post("/users/{id}/posts")]
+    Posts { id: u32 },
+
+    #[get("/status")]
+    Status,
+
+    Admin {
+        #[forward]
+        admin: Admin,
+    },
+}
+
+fn service() -> SyncService<impl Fn(Routes, Arc<http::Request<()>>) -> String, Routes> {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::User { id } | Routes::Posts { id } => format!("user #{}", id),
+        Routes::Status => "ok".to_string(),
+        Routes::Admin {
+            admin: Admin::Settings,
+        } => "settings".to_string(),
+    })
+}
+
+#[test]
+fn route_paths() {
+    assert_eq!(
+        Routes::route_paths(),
+        vec![
+            "/users/{id}",
+            "/users/{id}/posts",
+            "/status",
+            "/admin/settings"
+        ]
+    );
+}
+
+#[test]
+fn suggestions() {
+    let mut client = Client::new(service().suggest_routes(true));
+
+    let response = client.send(RequestBuilder::get("/user/5")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.text(),
+        "404 Not Found: did you mean `/users/{id}`?"
+    );
+
+    let response = client.send(RequestBuilder::get("/user/5/post")).unwrap();
+    assert_eq!(
+        response.text(),
+        "404 Not Found: did you mean `/users/{id}/posts`?"
+    );
+
+    let response = client.send(RequestBuilder::get("/admin/setings")).unwrap();
+    assert_eq!(
+        response.text(),
+        "404 Not Found: did you mean `/admin/settings`?"
+    );
+
+    // Nothing similar enough
+    let response = client
+        .send(RequestBuilder::get("/completely/unrelated"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.text(), "");
+
+    // Other errors are unaffected
+    let response = client
+        .send(RequestBuilder::post("/admin/settings"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.text(), "");
+}
+
+#[test]
+fn disabled_by_default() {
+    let mut client = Client::new(service());
+    let response = client.send(RequestBuilder::get("/user/5")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.text(), "");
+
+    let mut client = Client::new(service().suggest_routes(true).suggest_routes(false));
+    let response = client.send(RequestBuilder::get("/user/5")).unwrap();
+    assert_eq!(response.text(), "");
+}
+
+#[test]
+fn error_body() {
+    let mut client = Client::new(
+        service()
+            .suggest_routes(true)
+            .error_body(ProblemJson::from_error),
+    );
+    let response = client.send(RequestBuilder::get("/stats")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let problem = response.json::<Value>().unwrap();
+    assert_eq!(problem["suggestions"], serde_json::json!(["/status"]));
+}
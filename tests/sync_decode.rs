@@ -0,0 +1,208 @@
+//! Tests decoding requests without starting a runtime.
+
+use futures::future;
+use hyperdrive::{
+    body::Json,
+    bytes::Bytes,
+    http::{Request, StatusCode},
+    BoxedError, DefaultFuture, Error, FromRequest, Guard, NoContext,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::current_thread::Runtime;
+
+/// Requires an `X-Token` header.
+struct Token;
+
+impl Guard for Token {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        if request.headers().contains_key("x-token") {
+            Ok(Token)
+        } else {
+            Err(Error::from_status(StatusCode::UNAUTHORIZED).into())
+        }
+    }
+}
+
+static ASYNC_GUARD_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// A guard returning a future.
+struct AsyncGuard;
+
+impl Guard for AsyncGuard {
+    type Context = NoContext;
+    type Result = DefaultFuture<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        ASYNC_GUARD_RUNS.fetch_add(1, Ordering::SeqCst);
+        Box::new(future::ok(AsyncGuard))
+    }
+}
+
+#[derive(FromRequest, Debug, PartialEq, Eq)]
+enum Inner {
+    #[get("/inner/{id}")]
+    Item { id: u32 },
+}
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/users/{id}")]
+    User { id: u32, _token: Token },
+
+    #[post("/users")]
+    AddUser {
+        #[body]
+        name: Json<String>,
+    },
+
+    #[get("/async")]
+    Async { _guard: AsyncGuard },
+
+    Fallback {
+        #[forward]
+        inner: Inner,
+    },
+}
+
+fn parts(request: Request<()>) -> Option<Result<Routes, BoxedError>> {
+    Routes::try_from_request_parts(&Arc::new(request), &NoContext)
+}
+
+fn status(result: Option<Result<Routes, BoxedError>>) -> StatusCode {
+    match result {
+        Some(Err(e)) => e.downcast::<Error>().unwrap().http_status(),
+        _ => panic!("expected an error"),
+    }
+}
+
+#[test]
+fn sync_routes() {
+    match parts(Request::get("/").body(()).unwrap()) {
+        Some(Ok(Routes::Index)) => {}
+        _ => panic!("expected `Index`"),
+    }
+
+    let request = Request::get("/users/7")
+        .header("X-Token", "secret")
+        .body(())
+        .unwrap();
+    match parts(request) {
+        Some(Ok(Routes::User { id: 7, .. })) => {}
+        _ => panic!("expected `User`"),
+    }
+
+    match parts(Request::get("/inner/3").body(()).unwrap()) {
+        Some(Ok(Routes::Fallback { inner })) => assert_eq!(inner, Inner::Item { id: 3 }),
+        _ => panic!("expected `Fallback`"),
+    }
+}
+
+#[test]
+fn sync_errors() {
+    let result = parts(Request::get("/users/7").body(()).unwrap());
+    assert_eq!(status(result), StatusCode::UNAUTHORIZED);
+
+    let result = parts(Request::get("/users/x").body(()).unwrap());
+    assert_eq!(status(result), StatusCode::NOT_FOUND);
+
+    let result = parts(Request::delete("/inner/3").body(()).unwrap());
+    assert_eq!(status(result), StatusCode::METHOD_NOT_ALLOWED);
+
+    let result = parts(Request::get("/missing").body(()).unwrap());
+    assert_eq!(status(result), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn async_routes() {
+    assert!(parts(Request::post("/users").body(()).unwrap()).is_none());
+
+    // Asynchronous guards aren't run by `try_from_request_parts`
+    let before = ASYNC_GUARD_RUNS.load(Ordering::SeqCst);
+    assert!(parts(Request::get("/async").body(()).unwrap()).is_none());
+    assert_eq!(ASYNC_GUARD_RUNS.load(Ordering::SeqCst), before);
+
+    // ...but by `from_request_sync`, exactly once
+    let request = Request::get("/async").body(hyper::Body::empty()).unwrap();
+    match Routes::from_request_sync(request, NoContext).unwrap() {
+        Routes::Async { .. } => {}
+        _ => panic!("expected `Async`"),
+    }
+    assert_eq!(ASYNC_GUARD_RUNS.load(Ordering::SeqCst), before + 1);
+
+    let request = Request::post("/users").body(r#""alice""#).unwrap();
+    match Routes::from_request_bytes(request, NoContext).unwrap() {
+        Routes::AddUser { name } => assert_eq!(name.0, "alice"),
+        _ => panic!("expected `AddUser`"),
+    }
+}
+
+/// Decodes an `AddUser` request with an in-memory body, returning the name.
+fn add_user<B: Into<Bytes>>(body: B) -> String {
+    let request = Request::post("/users").body(body).unwrap();
+    match Routes::from_request_bytes(request, NoContext) {
+        Ok(Routes::AddUser { name }) => name.0,
+        _ => panic!("expected `AddUser`"),
+    }
+}
+
+#[test]
+fn in_memory_bodies() {
+    assert_eq!(add_user(br#""bob""#.to_vec()), "bob");
+    assert_eq!(add_user(Bytes::from_static(br#""carol""#)), "carol");
+    assert_eq!(add_user(&br#""dave""#[..]), "dave");
+}
+
+#[test]
+fn fast_path_taken() {
+    // Starting a runtime from inside another one panics, so this only works
+    // if `from_request_sync` doesn't need one.
+    let mut rt = Runtime::new().unwrap();
+    let route = rt
+        .block_on(future::lazy(|| {
+            let request = Request::get("/").body(hyper::Body::empty()).unwrap();
+            Routes::from_request_sync(request, NoContext)
+        }))
+        .unwrap();
+    match route {
+        Routes::Index => {}
+        _ => panic!("expected `Index`"),
+    }
+}
+
+/// Compares the fast path with decoding on a runtime.
+///
+/// Run with `cargo test --release --test sync_decode -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_static_route() {
+    const ITERATIONS: u32 = 10_000;
+
+    let request = || Request::get("/").body(hyper::Body::empty()).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        Routes::from_request_sync(request(), NoContext).unwrap();
+    }
+    let sync = start.elapsed() / ITERATIONS;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(Routes::from_request(request(), NoContext))
+            .unwrap();
+    }
+    let runtime = start.elapsed() / ITERATIONS;
+
+    println!(
+        "from_request_sync: {:?}/iter, runtime: {:?}/iter",
+        sync, runtime
+    );
+}
@@ -0,0 +1,106 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    body::Json,
+    guard::RemoteAddr,
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    service::{AsyncService, ConnectInfo, SyncService},
+    test::{self, Client, RequestBuilder},
+    BoxedError, FromRequest,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct User {
+    name: String,
+}
+
+#[derive(FromRequest, Debug, PartialEq)]
+enum Route {
+    #[get("/")]
+    Index,
+
+    #[post("/users")]
+    CreateUser {
+        #[body]
+        user: Json<User>,
+    },
+
+    #[get("/addr")]
+    Addr { addr: RemoteAddr },
+}
+
+fn user(name: &str) -> User {
+    User {
+        name: name.to_string(),
+    }
+}
+
+#[test]
+fn decode() {
+    assert_eq!(
+        test::decode::<Route>(RequestBuilder::get("/")).unwrap(),
+        Route::Index
+    );
+
+    let request = RequestBuilder::post("/users").json(&user("Ferris"));
+    match test::decode::<Route>(request).unwrap() {
+        Route::CreateUser { user: json } => assert_eq!(json.0, user("Ferris")),
+        route => panic!("unexpected route {:?}", route),
+    }
+
+    // Request extensions can be provided
+    let addr = "127.0.0.1:8080".parse().unwrap();
+    let request = RequestBuilder::get("/addr").extension(ConnectInfo(addr));
+    match test::decode::<Route>(request).unwrap() {
+        Route::Addr { addr: remote } => assert_eq!(remote.0, addr),
+        route => panic!("unexpected route {:?}", route),
+    }
+
+    assert!(test::decode::<Route>(RequestBuilder::delete("/")).is_err());
+}
+
+#[test]
+fn sync_service() {
+    let mut client = Client::new(SyncService::responding(|route: Route, _| match route {
+        Route::Index => (StatusCode::OK, "index".to_string()),
+        Route::CreateUser { user } => (StatusCode::CREATED, user.0.name),
+        Route::Addr { .. } => unreachable!(),
+    }));
+
+    let response = client.send(RequestBuilder::get("/")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text(), "index");
+    assert_eq!(
+        response.header(header::CONTENT_TYPE),
+        Some("text/plain; charset=utf-8")
+    );
+
+    let request = RequestBuilder::post("/users").json(&user("Ferris"));
+    let response = client.send(request).unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.text(), "Ferris");
+
+    let response = client.send(RequestBuilder::get("/missing")).unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn async_service() {
+    let service = AsyncService::new(|route: Route, _| match route {
+        Route::CreateUser { user } => {
+            let body = serde_json::to_vec(&user.0).unwrap();
+            Ok(Response::new(Body::from(body))).into_future()
+        }
+        _ => Err(BoxedError::from("unsupported route")).into_future(),
+    });
+    let mut client = Client::new(service);
+
+    let request = RequestBuilder::post("/users").json(&user("Ferris"));
+    let response = client.send(request).unwrap();
+    assert_eq!(response.json::<User>().unwrap(), user("Ferris"));
+
+    // Service errors are returned
+    let error = client.send(RequestBuilder::get("/")).unwrap_err();
+    assert_eq!(error.to_string(), "unsupported route");
+}
@@ -0,0 +1,114 @@
+use futures::{future, Future};
+use hyperdrive::{
+    http::{Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{PoolConfig, SyncService},
+    BoxedError, DefaultFuture, FromRequest, NoContext,
+};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/")]
+    Index,
+}
+
+/// Tracks how many handlers are running at the same time.
+#[derive(Default)]
+struct Concurrency {
+    current: usize,
+    max: usize,
+}
+
+/// Creates a service with a slow handler that records its concurrency in
+/// `stats`.
+fn service(
+    config: PoolConfig,
+    stats: Arc<Mutex<Concurrency>>,
+) -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    SyncService::responding_with_thread_pool(
+        move |Route::Index, _| {
+            {
+                let mut stats = stats.lock().unwrap();
+                stats.current += 1;
+                stats.max = stats.max.max(stats.current);
+            }
+            thread::sleep(Duration::from_millis(200));
+            stats.lock().unwrap().current -= 1;
+            "done"
+        },
+        NoContext,
+        config,
+    )
+}
+
+/// Sends `count` requests at once and returns the response statuses.
+fn call_concurrently<S>(service: &mut S, count: usize) -> Vec<StatusCode>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let requests = (0..count)
+        .map(|_| service.call(Request::get("/").body(Body::empty()).unwrap()))
+        .collect::<Vec<_>>();
+
+    let mut runtime = Runtime::new().unwrap();
+    let responses = runtime.block_on(future::join_all(requests)).unwrap();
+    runtime.shutdown_now().wait().unwrap();
+    responses.iter().map(|response| response.status()).collect()
+}
+
+#[test]
+fn parallelism() {
+    let stats = Arc::new(Mutex::new(Concurrency::default()));
+    let config = PoolConfig {
+        threads: 2,
+        queue_limit: 10,
+    };
+    let mut service = service(config, stats.clone());
+
+    let statuses = call_concurrently(&mut service, 6);
+    assert_eq!(statuses, vec![StatusCode::OK; 6]);
+    assert_eq!(stats.lock().unwrap().max, 2);
+}
+
+#[test]
+fn overflow() {
+    let config = PoolConfig {
+        threads: 1,
+        queue_limit: 1,
+    };
+    let mut service = service(config, Default::default());
+
+    let statuses = call_concurrently(&mut service, 3);
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::SERVICE_UNAVAILABLE
+        ]
+    );
+
+    // Slots are freed once the handlers finish
+    let statuses = call_concurrently(&mut service, 2);
+    assert_eq!(statuses, vec![StatusCode::OK; 2]);
+}
+
+#[test]
+#[should_panic(expected = "at least 1 thread")]
+fn no_threads() {
+    let config = PoolConfig {
+        threads: 0,
+        queue_limit: 1,
+    };
+    service(config, Default::default());
+}
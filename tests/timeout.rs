@@ -0,0 +1,119 @@
+use futures::{future, Future, IntoFuture};
+use hyperdrive::{
+    http::{Request, Response, StatusCode},
+    hyper::{
+        service::{service_fn, Service},
+        Body,
+    },
+    service::{AsyncService, AtomicMetrics, ServiceExt},
+    BoxedError, DefaultFuture, ErrorKind, FromRequest, TimeoutInfo,
+};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/fast")]
+    Fast,
+
+    #[get("/hang")]
+    Hang,
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> {
+    AsyncService::new(|route: Routes, _| match route {
+        Routes::Fast => future::Either::A(Ok(Response::new(Body::from("fast"))).into_future()),
+        // Simulates a handler stuck waiting for a database connection
+        Routes::Hang => future::Either::B(future::empty()),
+    })
+}
+
+fn call<S>(mut service: S, path: &str) -> Result<Response<Body>, BoxedError>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let result = runtime.block_on(service.call(request));
+    runtime.shutdown_now().wait().unwrap();
+    result
+}
+
+#[test]
+fn never_resolving_handler() {
+    let start = Instant::now();
+    let service = app().timeout(Duration::from_millis(50));
+    let response = call(service, "/hang").unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let service = app()
+        .timeout(Duration::from_millis(50))
+        .status(StatusCode::GATEWAY_TIMEOUT);
+    let response = call(service, "/hang").unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[test]
+fn fast_handler() {
+    let service = app().timeout(Duration::from_secs(30));
+    let start = Instant::now();
+    let response = call(service, "/fast").unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(start.elapsed() < Duration::from_secs(30));
+}
+
+#[test]
+fn inner_error() {
+    let service = service_fn(|_: Request<Body>| -> Result<Response<Body>, BoxedError> {
+        Err("inner failure".into())
+    })
+    .timeout(Duration::from_secs(30));
+    let error = call(service, "/").unwrap_err();
+    assert_eq!(error.to_string(), "inner failure");
+}
+
+#[test]
+fn kind_and_durations() {
+    let service = app().timeout(Duration::from_millis(50));
+    let response = call(service, "/hang").unwrap();
+    assert_eq!(
+        response.extensions().get::<ErrorKind>(),
+        Some(&ErrorKind::Timeout)
+    );
+    let info = response.extensions().get::<TimeoutInfo>().unwrap();
+    assert_eq!(info.budget(), Duration::from_millis(50));
+    assert!(info.elapsed() >= info.budget());
+
+    // Successful responses carry neither
+    let service = app().timeout(Duration::from_secs(30));
+    let response = call(service, "/fast").unwrap();
+    assert!(response.extensions().get::<ErrorKind>().is_none());
+    assert!(response.extensions().get::<TimeoutInfo>().is_none());
+}
+
+#[test]
+fn metrics() {
+    let metrics = AtomicMetrics::new();
+    let service = || {
+        app()
+            .timeout(Duration::from_millis(50))
+            .metrics(metrics.clone())
+    };
+    call(service(), "/hang").unwrap();
+    call(service(), "/hang").unwrap();
+    call(service(), "/fast").unwrap();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.timeouts(), 2);
+    assert_eq!(snapshot.status_class(5), 2);
+    assert!(snapshot
+        .to_string()
+        .contains("\nhttp_request_timeouts_total 2\n"));
+}
@@ -0,0 +1,312 @@
+//! Tests turning routes back into requests with `#[hyperdrive(client)]`.
+
+use futures::{Future, Stream};
+use hyperdrive::{
+    body::{HtmlForm, Json},
+    http::{header::CONTENT_TYPE, Method, Request},
+    hyper::Body,
+    BoxedError, FromRequest, Guard, NoContext, ToRequest,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Pagination {
+    start: u32,
+    count: u32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Search {
+    q: Option<String>,
+    page: Option<u32>,
+    sort: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Login {
+    user: String,
+    password: String,
+}
+
+/// A guard that always succeeds.
+#[derive(PartialEq, Eq, Debug)]
+struct Anyone;
+
+impl Guard for Anyone {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(_: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        Ok(Anyone)
+    }
+}
+
+#[derive(FromRequest, PartialEq, Eq, Debug)]
+#[hyperdrive(client)]
+enum Inner {
+    #[get("/inner/{name}")]
+    Named { name: String },
+}
+
+#[derive(FromRequest, PartialEq, Eq, Debug)]
+#[hyperdrive(client)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/users/{id}/posts/{post}")]
+    #[head("/users/{id}/posts/{post}")]
+    Post { id: u32, post: u32, guard: Anyone },
+
+    #[get("/users")]
+    Users {
+        #[query_params]
+        pagination: Pagination,
+    },
+
+    #[get("/search")]
+    Search {
+        #[query_params]
+        search: Search,
+    },
+
+    #[post("/login")]
+    Login {
+        #[body]
+        form: HtmlForm<Login>,
+    },
+
+    #[put("/users/{id}")]
+    EditUser {
+        id: u32,
+        #[body]
+        login: Json<Login>,
+    },
+
+    #[get("/static/{path...}")]
+    Static { path: String },
+
+    #[options("*")]
+    ServerOptions,
+
+    Fallback {
+        #[forward]
+        inner: Inner,
+    },
+}
+
+/// Asserts that `route` survives a round trip through `to_request`.
+fn round_trip<R>(route: R)
+where
+    R: FromRequest<Context = NoContext> + ToRequest + PartialEq + Debug,
+{
+    let decoded = R::from_request_sync(route.to_request(), NoContext).unwrap();
+    assert_eq!(decoded, route);
+}
+
+fn login() -> Login {
+    Login {
+        user: "root".to_string(),
+        password: "hunter2".to_string(),
+    }
+}
+
+#[test]
+fn round_trips() {
+    round_trip(Routes::Index);
+    round_trip(Routes::Post {
+        id: 1,
+        post: 2,
+        guard: Anyone,
+    });
+    round_trip(Routes::Users {
+        pagination: Pagination {
+            start: 10,
+            count: 5,
+        },
+    });
+    round_trip(Routes::Login {
+        form: HtmlForm(login()),
+    });
+    round_trip(Routes::EditUser {
+        id: 3,
+        login: Json(login()),
+    });
+    round_trip(Routes::Static {
+        path: "css/main.css".to_string(),
+    });
+    round_trip(Routes::ServerOptions);
+    round_trip(Routes::Fallback {
+        inner: Inner::Named {
+            name: "bob".to_string(),
+        },
+    });
+}
+
+#[test]
+fn request_parts() {
+    let request = Routes::Post {
+        id: 1,
+        post: 2,
+        guard: Anyone,
+    }
+    .to_request();
+    // The first route attribute is used
+    assert_eq!(request.method(), Method::GET);
+    assert_eq!(request.uri(), "/users/1/posts/2");
+
+    let request = Routes::Users {
+        pagination: Pagination {
+            start: 10,
+            count: 5,
+        },
+    }
+    .to_request();
+    assert_eq!(request.uri(), "/users?start=10&count=5");
+
+    let request = Routes::EditUser {
+        id: 3,
+        login: Json(login()),
+    }
+    .to_request();
+    assert_eq!(request.method(), Method::PUT);
+    assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+    let body = request.into_body().concat2().wait().unwrap();
+    assert_eq!(&body[..], &br#"{"user":"root","password":"hunter2"}"#[..]);
+
+    let request = Routes::Login {
+        form: HtmlForm(login()),
+    }
+    .to_request();
+    assert_eq!(
+        request.headers()[CONTENT_TYPE],
+        "application/x-www-form-urlencoded"
+    );
+
+    assert_eq!(Routes::ServerOptions.to_request().uri(), "*");
+}
+
+#[test]
+fn placeholder_encoding() {
+    // Characters that would change the path are percent-encoded
+    let request = Inner::Named {
+        name: "a b/c?d".to_string(),
+    }
+    .to_request();
+    assert_eq!(request.uri(), "/inner/a%20b%2Fc%3Fd");
+
+    // ...except for `/` in rest placeholders
+    let request = Routes::Static {
+        path: "img/logo.png".to_string(),
+    }
+    .to_request();
+    assert_eq!(request.uri(), "/static/img/logo.png");
+}
+
+#[test]
+fn generic() {
+    #[derive(FromRequest, PartialEq, Eq, Debug)]
+    #[hyperdrive(client)]
+    enum Generic<T, Q> {
+        #[get("/{id}")]
+        Get {
+            id: T,
+            #[query_params]
+            query: Q,
+        },
+    }
+
+    round_trip(Generic::Get {
+        id: 7,
+        query: Pagination { start: 0, count: 1 },
+    });
+    round_trip(Generic::Get {
+        id: "seven".to_string(),
+        query: Pagination { start: 0, count: 1 },
+    });
+}
+
+#[test]
+fn structs() {
+    #[derive(FromRequest, PartialEq, Eq, Debug)]
+    #[hyperdrive(client)]
+    #[post("/items/{id}")]
+    struct AddItem {
+        id: u32,
+        #[body]
+        data: Json<Pagination>,
+    }
+
+    round_trip(AddItem {
+        id: 1,
+        data: Json(Pagination { start: 1, count: 2 }),
+    });
+}
+
+/// Asserts that a `GET` request to `route.path_and_query()` decodes to `route`.
+fn path_round_trip(route: Routes) -> String {
+    let path = route.path_and_query();
+    let request = Request::get(&*path).body(Body::empty()).unwrap();
+    let decoded = Routes::from_request_sync(request, NoContext).unwrap();
+    assert_eq!(decoded, route);
+    path
+}
+
+#[test]
+fn path_and_query() {
+    let path = path_round_trip(Routes::Users {
+        pagination: Pagination {
+            start: 10,
+            count: 5,
+        },
+    });
+    assert_eq!(path, "/users?start=10&count=5");
+
+    // Query values are percent-encoded, and `None` fields are left out
+    let path = path_round_trip(Routes::Search {
+        search: Search {
+            q: Some("rust & hyper=fast/100% ünïcode+#".to_string()),
+            page: None,
+            sort: Some("-date".to_string()),
+        },
+    });
+    assert_eq!(
+        path,
+        "/search?q=rust+%26+hyper%3Dfast%2F100%25+%C3%BCn%C3%AFcode%2B%23&sort=-date"
+    );
+
+    let path = path_round_trip(Routes::Search {
+        search: Search {
+            q: None,
+            page: Some(2),
+            sort: None,
+        },
+    });
+    assert_eq!(path, "/search?page=2");
+
+    // Without any parameters, the query string is left out
+    let path = path_round_trip(Routes::Search {
+        search: Search {
+            q: None,
+            page: None,
+            sort: None,
+        },
+    });
+    assert_eq!(path, "/search");
+
+    let path = path_round_trip(Routes::Fallback {
+        inner: Inner::Named {
+            name: "bob".to_string(),
+        },
+    });
+    assert_eq!(path, "/inner/bob");
+
+    // The path is the same as the one of the full request
+    let route = Routes::EditUser {
+        id: 3,
+        login: Json(login()),
+    };
+    assert_eq!(route.path_and_query(), route.to_request().uri().to_string());
+}
@@ -0,0 +1,251 @@
+//! Tests using the examples from the W3C Trace Context specification and its
+//! test suite.
+
+use hyperdrive::{
+    guard::TraceContext,
+    http::{HeaderMap, HeaderValue, Request},
+    hyper::Body,
+    FromRequest, NoContext,
+};
+
+#[derive(FromRequest, Debug)]
+enum Routes {
+    #[get("/")]
+    Index { trace: TraceContext },
+}
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+fn headers(traceparent: &[&str], tracestate: &[&str]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for value in traceparent {
+        headers.append("traceparent", HeaderValue::from_str(value).unwrap());
+    }
+    for value in tracestate {
+        headers.append("tracestate", HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+fn parse(traceparent: &str) -> TraceContext {
+    TraceContext::from_headers(&headers(&[traceparent], &[]))
+}
+
+#[test]
+fn valid() {
+    let trace = parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+    assert!(!trace.is_generated());
+    assert_eq!(trace.trace_id(), TRACE_ID);
+    assert_eq!(trace.parent_id(), PARENT_ID);
+    assert_eq!(trace.flags(), 0x01);
+    assert!(trace.is_sampled());
+
+    let trace = parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00");
+    assert!(!trace.is_generated());
+    assert!(!trace.is_sampled());
+}
+
+#[test]
+fn future_version() {
+    // Higher versions are parsed like version 00, and may carry more fields
+    for value in &[
+        "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what-the-future-will-be-like",
+    ] {
+        let trace = parse(value);
+        assert!(!trace.is_generated(), "{}", value);
+        assert_eq!(trace.trace_id(), TRACE_ID);
+    }
+
+    // The child is always sent using version 00, with unknown flags cleared
+    let trace = parse("cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-09");
+    let child = trace.child().to_string();
+    assert!(child.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    assert!(child.ends_with("-01"));
+}
+
+#[test]
+fn invalid() {
+    for value in &[
+        "",
+        "00",
+        // version `ff` is forbidden
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        // uppercase hex digits
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00F067AA0BA902B7-01",
+        "0A-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        // all-zero IDs
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        // version 00 must not have additional fields
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what-the-future-will-be-like",
+        // fields of future versions must be separated by `-`
+        "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01.what-the-future-will-be-like",
+        // wrong lengths
+        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+        "000-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        // non-hex characters
+        "00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0x",
+        // wrong separators
+        "00_4bf92f3577b34da6a3ce929d0e0e4736_00f067aa0ba902b7_01",
+    ] {
+        let trace = parse(value);
+        assert!(trace.is_generated(), "{:?} should be invalid", value);
+        assert_ne!(trace.trace_id(), TRACE_ID);
+    }
+}
+
+#[test]
+fn multiple_traceparent_headers() {
+    let valid = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let trace = TraceContext::from_headers(&headers(&[valid, valid], &[]));
+    assert!(trace.is_generated());
+}
+
+#[test]
+fn tracestate() {
+    let valid = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let trace = TraceContext::from_headers(&headers(
+        &[valid],
+        &["rojo=00f067aa0ba902b7", "congo=t61rcWkgMzE"],
+    ));
+    assert_eq!(
+        trace.tracestate(),
+        Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE")
+    );
+    assert_eq!(trace.child().tracestate(), trace.tracestate());
+
+    // `tracestate` is discarded along with an invalid `traceparent`
+    let invalid = "00-00000000000000000000000000000000-00f067aa0ba902b7-01";
+    let trace = TraceContext::from_headers(&headers(&[invalid], &["rojo=00f067aa0ba902b7"]));
+    assert_eq!(trace.tracestate(), None);
+}
+
+#[test]
+fn generated() {
+    let trace = TraceContext::from_headers(&HeaderMap::new());
+    assert!(trace.is_generated());
+    assert!(!trace.is_sampled());
+    assert_eq!(trace.trace_id().len(), 32);
+    assert_eq!(trace.parent_id().len(), 16);
+
+    // Generated contexts still produce valid children
+    let child = parse(&trace.child().to_string());
+    assert!(!child.is_generated());
+    assert_eq!(child.trace_id(), trace.trace_id());
+    assert_ne!(child.parent_id(), trace.parent_id());
+}
+
+#[test]
+fn guard() {
+    let request = Request::get("/")
+        .header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .body(Body::empty())
+        .unwrap();
+    let Routes::Index { trace } = Routes::from_request_sync(request, NoContext).unwrap();
+    assert_eq!(trace.trace_id(), TRACE_ID);
+
+    // Invalid headers don't fail the request
+    let request = Request::get("/")
+        .header("traceparent", "garbage")
+        .body(Body::empty())
+        .unwrap();
+    let Routes::Index { trace } = Routes::from_request_sync(request, NoContext).unwrap();
+    assert!(trace.is_generated());
+}
+
+#[cfg(feature = "tracing")]
+mod tracing_integration {
+    use super::Routes;
+    use futures::{Future, IntoFuture};
+    use hyperdrive::{
+        http::{Request, Response},
+        hyper::{service::Service, Body},
+        service::{AsyncService, ServiceExt},
+    };
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    /// Records the `trace_id` field of all spans.
+    #[derive(Clone, Default)]
+    struct TraceIds(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for TraceIds {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "trace_id" {
+                self.0.lock().unwrap().push(format!("{:?}", value));
+            }
+        }
+    }
+
+    impl Subscriber for TraceIds {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn traced_trace_id(request: Request<Body>) -> (String, String) {
+        let subscriber = TraceIds::default();
+        let handled = Arc::new(Mutex::new(None));
+
+        let handled2 = handled.clone();
+        let mut service = AsyncService::new(move |route: Routes, _| {
+            let Routes::Index { trace } = route;
+            *handled2.lock().unwrap() = Some(trace.trace_id().to_string());
+            Ok(Response::new(Body::empty())).into_future()
+        })
+        .trace_requests();
+
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            service.call(request).wait().unwrap();
+        });
+
+        let recorded = subscriber.0.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 1);
+        let handled = handled.lock().unwrap().clone().unwrap();
+        (recorded[0].clone(), handled)
+    }
+
+    #[test]
+    fn span_records_trace_id() {
+        let request = Request::get("/")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let (recorded, handled) = traced_trace_id(request);
+        assert_eq!(recorded, super::TRACE_ID);
+        assert_eq!(handled, super::TRACE_ID);
+
+        // Generated contexts are shared between the span and the guard
+        let request = Request::get("/").body(Body::empty()).unwrap();
+        let (recorded, handled) = traced_trace_id(request);
+        assert_eq!(recorded, handled);
+    }
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "tracing")]
+
+use futures::{Future, IntoFuture};
+use hyperdrive::{
+    http::{Request, Response, StatusCode},
+    hyper::{service::Service, Body},
+    service::{AsyncService, ServiceExt},
+    BoxedError, Error, FromRequest,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+/// The fields recorded on a span or event.
+#[derive(Debug, Default)]
+struct Fields(HashMap<&'static str, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+/// Collects the fields of all spans and events.
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<Fields>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[span.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/users/{id}")]
+    User { id: u32 },
+}
+
+/// Sends `request` to a traced service and returns the fields of its span.
+fn trace<S>(service: &mut S, request: Request<Body>) -> (Fields, Vec<Fields>)
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+{
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), || {
+        let _ = service.call(request).wait();
+    });
+
+    let mut spans = Arc::try_unwrap(collector.spans)
+        .ok()
+        .unwrap()
+        .into_inner()
+        .unwrap();
+    let events = Arc::try_unwrap(collector.events)
+        .ok()
+        .unwrap()
+        .into_inner()
+        .unwrap();
+    assert_eq!(spans.len(), 1);
+    (spans.remove(0), events)
+}
+
+fn service() -> impl Service<ReqBody = Body, ResBody = Body, Error = BoxedError> {
+    AsyncService::new(|route: Route, _| match route {
+        Route::User { id } => Ok(Response::new(Body::from(format!("user #{}", id)))).into_future(),
+    })
+    .trace_requests()
+}
+
+#[test]
+fn ok() {
+    let request = Request::get("/users/7?details=1")
+        .header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .body(Body::empty())
+        .unwrap();
+    let (span, events) = trace(&mut service(), request);
+
+    assert_eq!(span.0["http.method"], "GET");
+    assert_eq!(span.0["http.target"], "/users/7?details=1");
+    assert_eq!(span.0["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(span.0["route"], "User");
+    assert_eq!(span.0["http.status_code"], "200");
+    assert!(span.0.contains_key("latency_ms"));
+    assert!(events.is_empty());
+}
+
+#[test]
+fn not_found() {
+    let request = Request::get("/missing").body(Body::empty()).unwrap();
+    let (span, events) = trace(&mut service(), request);
+
+    assert_eq!(span.0["http.target"], "/missing");
+    assert_eq!(span.0["http.status_code"], "404");
+    // A trace ID is generated, but there is no route
+    assert_eq!(span.0["trace_id"].len(), 32);
+    assert!(!span.0.contains_key("route"));
+    // `AsyncService` turns the error into a response
+    assert!(events.is_empty());
+}
+
+#[test]
+fn error() {
+    let mut service = hyper::service::service_fn(|_: Request<Body>| {
+        Err::<Response<Body>, BoxedError>(Error::from_status(StatusCode::FORBIDDEN).into())
+    })
+    .trace_requests();
+    let request = Request::get("/").body(Body::empty()).unwrap();
+    let (span, events) = trace(&mut service, request);
+
+    assert_eq!(span.0["http.status_code"], "403");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0["kind"], "Other");
+    assert_eq!(events[0].0["status"], "403");
+    assert_eq!(events[0].0["message"], "request failed");
+}
@@ -0,0 +1,105 @@
+use futures::IntoFuture;
+use hyperdrive::{
+    http::{header, Response, StatusCode},
+    hyper::Body,
+    service::{AsyncService, ServiceExt, TrailingSlash},
+    test::{Client, RequestBuilder},
+    FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/")]
+    Index,
+
+    #[get("/users/")]
+    #[post("/users/")]
+    UsersSlash,
+
+    #[get("/users")]
+    #[post("/users")]
+    Users,
+}
+
+/// Calls an app redirecting according to `policy` and returns the status and
+/// `Location` of the response, or the body if there's no `Location`.
+fn call(policy: TrailingSlash, request: RequestBuilder) -> (StatusCode, String) {
+    let service = AsyncService::new(|route: Routes, _| {
+        let body = match route {
+            Routes::Index => "index",
+            Routes::UsersSlash => "users/",
+            Routes::Users => "users",
+        };
+        Ok(Response::new(Body::from(body))).into_future()
+    })
+    .redirect_trailing_slash(policy);
+
+    let response = Client::new(service).send(request).unwrap();
+    let text = match response.header(header::LOCATION) {
+        Some(location) => location.to_string(),
+        None => response.text().to_string(),
+    };
+    (response.status(), text)
+}
+
+fn redirect(location: &str) -> (StatusCode, String) {
+    (StatusCode::PERMANENT_REDIRECT, location.to_string())
+}
+
+fn ok(body: &str) -> (StatusCode, String) {
+    (StatusCode::OK, body.to_string())
+}
+
+#[test]
+fn add_slash() {
+    let get = RequestBuilder::get;
+    assert_eq!(call(TrailingSlash::Add, get("/users")), redirect("/users/"));
+    assert_eq!(
+        call(TrailingSlash::Add, RequestBuilder::head("/users")),
+        redirect("/users/")
+    );
+    assert_eq!(
+        call(TrailingSlash::Add, get("/users?page=2&sort=name")),
+        redirect("/users/?page=2&sort=name")
+    );
+}
+
+#[test]
+fn remove_slash() {
+    let get = RequestBuilder::get;
+    assert_eq!(
+        call(TrailingSlash::Remove, get("/users/")),
+        redirect("/users")
+    );
+    assert_eq!(
+        call(TrailingSlash::Remove, get("/users//?page=2")),
+        redirect("/users?page=2")
+    );
+}
+
+#[test]
+fn canonical() {
+    let get = RequestBuilder::get;
+    assert_eq!(call(TrailingSlash::Add, get("/users/")), ok("users/"));
+    assert_eq!(call(TrailingSlash::Remove, get("/users")), ok("users"));
+    assert_eq!(call(TrailingSlash::Add, get("/")), ok("index"));
+    assert_eq!(call(TrailingSlash::Remove, get("/")), ok("index"));
+}
+
+#[test]
+fn post_passes_through() {
+    let post = RequestBuilder::post;
+    assert_eq!(call(TrailingSlash::Add, post("/users")), ok("users"));
+    assert_eq!(call(TrailingSlash::Remove, post("/users/")), ok("users/"));
+}
+
+#[test]
+fn no_open_redirect() {
+    let get = RequestBuilder::get;
+    let (status, _) = call(TrailingSlash::Add, get("//example.com"));
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(TrailingSlash::Remove, get("//example.com/"));
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(TrailingSlash::Remove, get("//"));
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,177 @@
+//! Tests accepting WebSocket connections with `WsUpgrade`.
+
+#![cfg(feature = "websocket")]
+
+use futures::{Future, Stream};
+use hyperdrive::{
+    http::{Request, Response, StatusCode},
+    hyper::Body,
+    service::{GracefulShutdown, SyncService},
+    test::{Client, RequestBuilder},
+    ws::{
+        tungstenite::{self, http::Request as ClientRequest, HandshakeError, WebSocket},
+        Message, WsUpgrade,
+    },
+    BoxedError, Error, FromRequest, Guard, NoContext,
+};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Requires an `X-Token` header.
+struct Token;
+
+impl Guard for Token {
+    type Context = NoContext;
+    type Result = Result<Self, BoxedError>;
+
+    fn from_request(request: &Arc<Request<()>>, _: &NoContext) -> Self::Result {
+        if request.headers().contains_key("x-token") {
+            Ok(Token)
+        } else {
+            Err(Error::from_status(StatusCode::UNAUTHORIZED).into())
+        }
+    }
+}
+
+#[derive(FromRequest)]
+enum Route {
+    #[get("/echo")]
+    Echo { _token: Token, upgrade: WsUpgrade },
+}
+
+fn handler(route: Route, _: Arc<Request<()>>) -> Response<Body> {
+    match route {
+        Route::Echo { upgrade, .. } => upgrade.handshake_response(|socket| {
+            let (sink, stream) = socket.split();
+            stream
+                .take_while(|message| Ok(!message.is_close()))
+                .forward(sink)
+                .map(|_| ())
+        }),
+    }
+}
+
+/// Returns a port that is (most likely) free to bind to.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Opens a WebSocket connection, retrying until the server accepts
+/// connections.
+fn connect(port: u16, token: Option<&str>) -> tungstenite::Result<WebSocket<TcpStream>> {
+    let start = Instant::now();
+    let stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => break stream,
+            Err(e) => {
+                assert!(
+                    start.elapsed() < Duration::from_secs(10),
+                    "server didn't start: {}",
+                    e
+                );
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    };
+
+    let mut request = ClientRequest::builder().uri(format!("ws://127.0.0.1:{}/echo", port));
+    if let Some(token) = token {
+        request = request.header("X-Token", token);
+    }
+    match tungstenite::client(request.body(()).unwrap(), stream) {
+        Ok((socket, _)) => Ok(socket),
+        Err(HandshakeError::Failure(e)) => Err(e),
+        Err(HandshakeError::Interrupted(_)) => unreachable!("the stream is blocking"),
+    }
+}
+
+#[test]
+fn echo() {
+    let port = free_port();
+    let shutdown = GracefulShutdown::new();
+    let handle = shutdown.clone();
+    let server = thread::spawn(move || {
+        hyperdrive::serve_with_shutdown(("127.0.0.1", port), SyncService::new(handler), &handle)
+    });
+
+    let mut socket = connect(port, Some("secret")).unwrap();
+    for text in &["Hello", "WebSocket"] {
+        socket.write_message(Message::text(*text)).unwrap();
+        assert_eq!(socket.read_message().unwrap(), Message::text(*text));
+    }
+    socket
+        .write_message(Message::binary(&[1, 2, 3][..]))
+        .unwrap();
+    assert_eq!(
+        socket.read_message().unwrap(),
+        Message::binary(&[1, 2, 3][..])
+    );
+    socket.close(None).unwrap();
+
+    // Other guards run before the connection is upgraded
+    match connect(port, None) {
+        Err(tungstenite::Error::Http(status)) => assert_eq!(status.as_u16(), 401),
+        _ => panic!("expected the handshake to be rejected"),
+    }
+
+    shutdown.shutdown();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn invalid_handshake() {
+    let mut client = Client::new(SyncService::new(handler));
+    let handshake = || {
+        RequestBuilder::get("/echo")
+            .header("X-Token", "secret")
+            .header("Connection", "keep-alive, Upgrade")
+            .header("Upgrade", "websocket")
+    };
+
+    let response = client
+        .send(
+            handshake()
+                .header("Sec-WebSocket-Version", "8")
+                .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+    assert_eq!(response.header("Sec-WebSocket-Version"), Some("13"));
+    assert_eq!(response.header("Upgrade"), Some("websocket"));
+
+    let response = client
+        .send(RequestBuilder::get("/echo").header("X-Token", "secret"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+
+    for key in &["", "not base64!", "dG9vIHNob3J0"] {
+        let response = client
+            .send(
+                handshake()
+                    .header("Sec-WebSocket-Version", "13")
+                    .header("Sec-WebSocket-Key", *key),
+            )
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{:?}", key);
+    }
+
+    // The example handshake from RFC 6455
+    let response = client
+        .send(
+            handshake()
+                .header("Sec-WebSocket-Version", "13")
+                .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        )
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+    assert_eq!(
+        response.header("Sec-WebSocket-Accept"),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+}
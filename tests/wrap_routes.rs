@@ -0,0 +1,124 @@
+use futures::Future;
+use hyperdrive::{
+    http::{HeaderValue, Request, Response},
+    hyper::{service::Service, Body},
+    service::{path_prefix, path_prefixes, Middleware, Next, ServiceExt, SyncService},
+    test::{Client, RequestBuilder},
+    BoxedError, DefaultFuture, FromRequest,
+};
+
+#[derive(FromRequest)]
+enum Routes {
+    #[get("/api")]
+    Api,
+
+    #[get("/api/users")]
+    Users,
+
+    #[get("/apiary")]
+    Apiary,
+
+    #[get("/reports/{id}")]
+    Report { id: u32 },
+
+    #[get("/healthz")]
+    Health,
+}
+
+/// Appends its name to the `X-Middleware` response header.
+struct Tag(&'static str);
+
+impl Middleware for Tag {
+    fn handle(
+        &self,
+        req: Request<Body>,
+        next: Next<'_>,
+    ) -> DefaultFuture<Response<Body>, BoxedError> {
+        let name = self.0;
+        Box::new(next.run(req).map(move |mut response| {
+            response
+                .headers_mut()
+                .append("x-middleware", HeaderValue::from_static(name));
+            response
+        }))
+    }
+}
+
+fn app() -> impl Service<
+    ReqBody = Body,
+    ResBody = Body,
+    Error = BoxedError,
+    Future = DefaultFuture<Response<Body>, BoxedError>,
+> + Clone {
+    SyncService::responding(|route: Routes, _| match route {
+        Routes::Api | Routes::Users | Routes::Apiary | Routes::Health => "ok".to_string(),
+        Routes::Report { id } => format!("report #{}", id),
+    })
+}
+
+/// Returns the middleware that processed the response to `path`, in the order
+/// they ran in.
+fn tags<S>(client: &mut Client<S>, path: &str) -> Vec<String>
+where
+    S: Service<ReqBody = Body, ResBody = Body, Error = BoxedError>,
+    S::Future: Send + 'static,
+{
+    let response = client.send(RequestBuilder::get(path)).unwrap();
+    assert_eq!(response.status(), 200);
+    response
+        .headers()
+        .get_all("x-middleware")
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn api_only() {
+    let mut client = Client::new(app().wrap_routes(path_prefix("/api"), Tag("api")));
+
+    assert_eq!(tags(&mut client, "/api"), vec!["api"]);
+    assert_eq!(tags(&mut client, "/api/users?page=2"), vec!["api"]);
+    // Only whole segments are matched
+    assert!(tags(&mut client, "/apiary").is_empty());
+    assert!(tags(&mut client, "/healthz").is_empty());
+}
+
+#[test]
+fn multiple_prefixes() {
+    let mut client = Client::new(
+        app().wrap_routes(path_prefixes(&["/api/users", "/reports/"]), Tag("selected")),
+    );
+
+    assert_eq!(tags(&mut client, "/api/users"), vec!["selected"]);
+    assert_eq!(tags(&mut client, "/reports/7"), vec!["selected"]);
+    assert!(tags(&mut client, "/api").is_empty());
+}
+
+#[test]
+fn custom_predicate() {
+    let mut client = Client::new(app().wrap_routes(
+        |req: &Request<()>| req.headers().contains_key("x-debug"),
+        Tag("debug"),
+    ));
+
+    assert!(tags(&mut client, "/healthz").is_empty());
+    let response = client
+        .send(RequestBuilder::get("/healthz").header("X-Debug", "1"))
+        .unwrap();
+    assert_eq!(response.header("X-Middleware"), Some("debug"));
+}
+
+#[test]
+fn nesting() {
+    // The middleware added last runs first, so it sees the response last
+    let mut client = Client::new(
+        app()
+            .wrap_routes(path_prefix("/api/users"), Tag("inner"))
+            .wrap_routes(path_prefix("/api"), Tag("outer")),
+    );
+
+    assert_eq!(tags(&mut client, "/api/users"), vec!["inner", "outer"]);
+    assert_eq!(tags(&mut client, "/api"), vec!["outer"]);
+    assert!(tags(&mut client, "/healthz").is_empty());
+}